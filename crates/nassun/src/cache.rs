@@ -0,0 +1,1057 @@
+//! Maintenance for the package cache: checking everything in it against
+//! its hashes, clearing out packages nothing needs anymore, moving
+//! packages between caches in bundles, moving a whole cache somewhere
+//! else, and summing up what's in it and how well it's being used.
+//!
+//! Verification and garbage collection shouldn't run while something else
+//! is writing to the same cache, since content that's been written but not
+//! indexed yet looks just like garbage.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use cacache::WriteOpts;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use ssri::{Algorithm, Integrity, IntegrityOpts};
+
+use crate::client::{CacheStatus, RequestEvent, RequestKind, RequestObserver};
+use crate::error::{NassunError, Result};
+use crate::tarball::{source_integrity, TarballIndex, SOURCE_KEY_PREFIX};
+
+/// Prefix of the keys extracted packages are indexed under. See
+/// [`crate::tarball::tarball_key`].
+const PACKAGE_KEY_PREFIX: &str = "nassun::package::";
+
+/// What a pass over the cache found, and what it cleaned up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheReport {
+    /// How many index entries were looked at.
+    pub entries_checked: usize,
+    /// How many content files were looked at.
+    pub contents_checked: usize,
+    /// Content files that didn't match their hash.
+    pub corrupted: Vec<PathBuf>,
+    /// How many index entries were removed.
+    pub entries_removed: usize,
+    /// How many content files were removed.
+    pub contents_removed: usize,
+    /// Bytes freed up by removing content files.
+    pub reclaimed_bytes: u64,
+}
+
+/// Checks every content file in the cache at `cache` against its hash.
+/// Files that don't match are removed, along with any entries that need
+/// them, so they get fetched again the next time they're needed.
+pub async fn verify(cache: impl AsRef<Path>) -> Result<CacheReport> {
+    let cache = cache.as_ref().to_path_buf();
+    async_std::task::spawn_blocking(move || {
+        let mut report = CacheReport::default();
+        let mut present = HashSet::new();
+        for content in contents(&cache)? {
+            report.contents_checked += 1;
+            if content.is_intact()? {
+                present.insert(content.key);
+            } else {
+                std::fs::remove_file(&content.path)?;
+                report.contents_removed += 1;
+                report.reclaimed_bytes += content.size;
+                report.corrupted.push(content.path);
+            }
+        }
+        for entry in cacache::list_sync(&cache) {
+            let entry = entry.map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+            report.entries_checked += 1;
+            let intact = entry_contents(&entry).map_or(false, |needed| {
+                needed.iter().all(|sri| present.contains(&content_key(sri)))
+            });
+            if !intact {
+                cacache::remove_sync(&cache, &entry.key)
+                    .map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+                report.entries_removed += 1;
+            }
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// Removes every package from the cache at `cache` that isn't in `keep`,
+/// like the integrities of the packages in every lockfile that's still
+/// around, along with whatever remembers which sources they came from, and
+/// then any content nothing refers to anymore. Other entries,
+/// like cached packuments, are left alone.
+pub async fn gc(
+    cache: impl AsRef<Path>,
+    keep: impl IntoIterator<Item = Integrity>,
+) -> Result<CacheReport> {
+    let cache = cache.as_ref().to_path_buf();
+    // Packages are kept if they share any hash with one of these, so the
+    // same package matches even if it was cached under a different set of
+    // algorithms.
+    let keep = keep
+        .into_iter()
+        .flat_map(|sri| sri.hashes.into_iter().map(|hash| hash.to_string()))
+        .collect::<HashSet<_>>();
+    async_std::task::spawn_blocking(move || {
+        let mut report = CacheReport::default();
+        let mut live = HashSet::new();
+        for entry in cacache::list_sync(&cache) {
+            let entry = entry.map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+            report.entries_checked += 1;
+            let package = if let Some(sri) = entry.key.strip_prefix(PACKAGE_KEY_PREFIX) {
+                Some(sri.parse::<Integrity>().ok())
+            } else if entry.key.starts_with(SOURCE_KEY_PREFIX) {
+                Some(source_integrity(&entry))
+            } else {
+                None
+            };
+            if let Some(sri) = package {
+                let wanted = sri.map_or(false, |sri| {
+                    sri.hashes
+                        .iter()
+                        .any(|hash| keep.contains(&hash.to_string()))
+                });
+                if !wanted {
+                    cacache::remove_sync(&cache, &entry.key)
+                        .map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+                    report.entries_removed += 1;
+                    continue;
+                }
+            }
+            if let Some(needed) = entry_contents(&entry) {
+                live.extend(needed.iter().map(content_key));
+            }
+        }
+        for content in contents(&cache)? {
+            report.contents_checked += 1;
+            if !live.contains(&content.key) {
+                std::fs::remove_file(&content.path)?;
+                report.contents_removed += 1;
+                report.reclaimed_bytes += content.size;
+            }
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// What's in a cache, from [`stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// How many index entries there are, of any kind.
+    pub entries: usize,
+    /// How many of those are extracted packages.
+    pub packages: usize,
+    /// How many content files there are.
+    pub contents: usize,
+    /// Total size of the content files, in bytes.
+    pub content_bytes: u64,
+}
+
+/// Counts what's in the cache at `cache`.
+pub async fn stats(cache: impl AsRef<Path>) -> Result<CacheStats> {
+    let cache = cache.as_ref().to_path_buf();
+    async_std::task::spawn_blocking(move || {
+        let mut stats = CacheStats::default();
+        for content in contents(&cache)? {
+            stats.contents += 1;
+            stats.content_bytes += content.size;
+        }
+        for entry in cacache::list_sync(&cache) {
+            let entry = entry.map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+            stats.entries += 1;
+            if entry.key.starts_with(PACKAGE_KEY_PREFIX) {
+                stats.packages += 1;
+            }
+        }
+        Ok(stats)
+    })
+    .await
+}
+
+/// How much a [`crate::Nassun`] has gotten out of its cache so far, from
+/// [`crate::Nassun::cache_counts`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheCounts {
+    /// Packages extracted from the cache.
+    pub package_hits: u64,
+    /// Packages that had to be downloaded, including ones that were in the
+    /// cache but turned out to be corrupted.
+    pub package_misses: u64,
+    /// Packuments the registry said were unchanged from the cached copy.
+    pub packument_revalidations: u64,
+    /// Packuments that had to be downloaded in full.
+    pub packument_misses: u64,
+    /// Response bytes downloaded from registries, for packuments and
+    /// tarballs alike.
+    #[serde(default)]
+    pub bytes_downloaded: u64,
+    /// Milliseconds spent on packument requests. Requests that were in
+    /// flight at the same time are added up separately.
+    #[serde(default)]
+    pub packument_request_ms: u64,
+    /// Milliseconds spent on tarball requests, including reading their
+    /// bodies. Requests that were in flight at the same time are added up
+    /// separately.
+    #[serde(default)]
+    pub tarball_request_ms: u64,
+}
+
+/// Running totals behind [`CacheCounts`], shared by everything a
+/// [`crate::Nassun`] hands out.
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    package_hits: AtomicU64,
+    package_misses: AtomicU64,
+    packument_revalidations: AtomicU64,
+    packument_misses: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    packument_request_ms: AtomicU64,
+    tarball_request_ms: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn package_hit(&self) {
+        self.package_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn package_miss(&self) {
+        self.package_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn counts(&self) -> CacheCounts {
+        CacheCounts {
+            package_hits: self.package_hits.load(Ordering::Relaxed),
+            package_misses: self.package_misses.load(Ordering::Relaxed),
+            packument_revalidations: self.packument_revalidations.load(Ordering::Relaxed),
+            packument_misses: self.packument_misses.load(Ordering::Relaxed),
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            packument_request_ms: self.packument_request_ms.load(Ordering::Relaxed),
+            tarball_request_ms: self.tarball_request_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts packument requests into a [`CacheCounters`], on their way to
+/// whatever observer the user set up, if any.
+#[derive(Debug)]
+pub(crate) struct CountingObserver {
+    pub(crate) counters: Arc<CacheCounters>,
+    pub(crate) inner: Option<Arc<dyn RequestObserver>>,
+}
+
+impl RequestObserver for CountingObserver {
+    fn on_request(&self, event: &RequestEvent) {
+        if event.kind == RequestKind::Packument && event.error.is_none() {
+            match event.cache {
+                // Packuments are always checked with the registry, so a hit
+                // means it said the cached copy is still good.
+                CacheStatus::Hit => {
+                    self.counters
+                        .packument_revalidations
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                CacheStatus::Miss => {
+                    self.counters
+                        .packument_misses
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                CacheStatus::Uncached => {}
+            }
+        }
+        self.counters
+            .bytes_downloaded
+            .fetch_add(event.bytes, Ordering::Relaxed);
+        let request_ms = match event.kind {
+            RequestKind::Packument => Some(&self.counters.packument_request_ms),
+            RequestKind::Tarball => Some(&self.counters.tarball_request_ms),
+            RequestKind::Other => None,
+        };
+        if let Some(request_ms) = request_ms {
+            request_ms.fetch_add(event.duration.as_millis() as u64, Ordering::Relaxed);
+        }
+        if let Some(inner) = &self.inner {
+            inner.on_request(event);
+        }
+    }
+
+    fn on_request_start(&self, url: &url::Url, kind: RequestKind) {
+        if let Some(inner) = &self.inner {
+            inner.on_request_start(url, kind);
+        }
+    }
+}
+
+/// What went into a cache bundle, or came out of one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundleReport {
+    /// How many packages were exported or imported.
+    pub packages: usize,
+    /// How many content files were exported or imported. Files the cache
+    /// already had aren't counted when importing.
+    pub contents: usize,
+    /// Total size of those content files.
+    pub bytes: u64,
+    /// Packages that were asked for but weren't in the cache, so couldn't
+    /// be exported.
+    pub missing: Vec<Integrity>,
+}
+
+/// The list of packages in a bundle, stored as `manifest.json`.
+#[derive(Serialize, Deserialize)]
+struct BundleManifest {
+    version: u32,
+    /// Each package's index key, and where its file index is stored.
+    packages: Vec<(String, String)>,
+}
+
+/// Packs the packages in `keep` that are in the cache at `cache`, like
+/// the ones in a lockfile, into a single gzipped tarball at `to`, along
+/// with all their content. The bundle can be loaded into another cache
+/// with [`import`], to prime it in CI or carry it to a machine without
+/// network access.
+pub async fn export(
+    cache: impl AsRef<Path>,
+    keep: impl IntoIterator<Item = Integrity>,
+    to: impl AsRef<Path>,
+) -> Result<BundleReport> {
+    let cache = cache.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+    let keep = keep.into_iter().collect::<Vec<_>>();
+    async_std::task::spawn_blocking(move || {
+        let wanted = keep
+            .iter()
+            .flat_map(|sri| sri.hashes.iter().map(|hash| hash.to_string()))
+            .collect::<HashSet<_>>();
+        let mut available = contents(&cache)?
+            .into_iter()
+            .map(|content| (content.key.clone(), content))
+            .collect::<HashMap<_, _>>();
+        let mut report = BundleReport::default();
+        let mut found = HashSet::new();
+        let mut manifest = BundleManifest {
+            version: 1,
+            packages: Vec::new(),
+        };
+        let mut bundle = tar::Builder::new(GzEncoder::new(
+            std::fs::File::create(&to)?,
+            Compression::default(),
+        ));
+        for entry in cacache::list_sync(&cache) {
+            let entry = entry.map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+            let Some(sri) = entry
+                .key
+                .strip_prefix(PACKAGE_KEY_PREFIX)
+                .and_then(|sri| sri.parse::<Integrity>().ok())
+            else {
+                continue;
+            };
+            let hashes = sri
+                .hashes
+                .iter()
+                .map(|hash| hash.to_string())
+                .filter(|hash| wanted.contains(hash))
+                .collect::<Vec<_>>();
+            let (Some(needed), Some(index)) = (entry_contents(&entry), &entry.raw_metadata) else {
+                continue;
+            };
+            if hashes.is_empty()
+                || !needed
+                    .iter()
+                    .all(|sri| available.contains_key(&content_key(sri)))
+            {
+                continue;
+            }
+            for sri in &needed {
+                // Packages share files, but they only need to be stored once.
+                if let Some(content) = available.remove(&content_key(sri)) {
+                    let (algorithm, hex) = &content.key;
+                    bundle.append_path_with_name(
+                        &content.path,
+                        format!("content/{algorithm}/{hex}"),
+                    )?;
+                    report.contents += 1;
+                    report.bytes += content.size;
+                }
+            }
+            let index_path = format!("packages/{}", manifest.packages.len());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(index.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            bundle.append_data(&mut header, &index_path, &index[..])?;
+            manifest.packages.push((entry.key.clone(), index_path));
+            found.extend(hashes);
+            report.packages += 1;
+        }
+        let manifest = serde_json::to_vec(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        bundle.append_data(&mut header, "manifest.json", &manifest[..])?;
+        bundle.into_inner()?.finish()?;
+        report.missing = keep
+            .into_iter()
+            .filter(|sri| {
+                !sri.hashes
+                    .iter()
+                    .any(|hash| found.contains(&hash.to_string()))
+            })
+            .collect();
+        Ok(report)
+    })
+    .await
+}
+
+/// Loads a bundle made by [`export`] into the cache at `cache`. Every file
+/// in it is checked against its hash on the way in.
+pub async fn import(cache: impl AsRef<Path>, from: impl AsRef<Path>) -> Result<BundleReport> {
+    let cache = cache.as_ref().to_path_buf();
+    let from = from.as_ref().to_path_buf();
+    async_std::task::spawn_blocking(move || {
+        let mut report = BundleReport::default();
+        let mut manifest = None;
+        let mut indexes = HashMap::new();
+        let mut bundle = tar::Archive::new(GzDecoder::new(std::fs::File::open(&from)?));
+        for entry in bundle.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            if path == "manifest.json" {
+                manifest = Some(serde_json::from_slice::<BundleManifest>(&data)?);
+            } else if path.starts_with("packages/") {
+                indexes.insert(path, data);
+            } else if let Some((algorithm, hex)) = path
+                .strip_prefix("content/")
+                .and_then(|key| key.split_once('/'))
+            {
+                // These become paths in the cache, so make sure they can't point
+                // anywhere else.
+                if !algorithm.chars().all(|c| c.is_ascii_alphanumeric())
+                    || hex.len() < 5
+                    || !hex.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    return Err(NassunError::InvalidCacheBundle(
+                        from,
+                        format!("unexpected file `{path}`"),
+                    ));
+                }
+                if !hash_matches(algorithm, hex, &data) {
+                    return Err(NassunError::InvalidCacheBundle(
+                        from,
+                        format!("`{path}` doesn't match its hash"),
+                    ));
+                }
+                let dest = cache
+                    .join("content-v2")
+                    .join(algorithm)
+                    .join(&hex[..2])
+                    .join(&hex[2..4])
+                    .join(&hex[4..]);
+                if dest.exists() {
+                    continue;
+                }
+                std::fs::create_dir_all(dest.parent().expect("content always has a parent"))?;
+                std::fs::write(&dest, &data)?;
+                report.contents += 1;
+                report.bytes += data.len() as u64;
+            }
+        }
+        let manifest = manifest.ok_or_else(|| {
+            NassunError::InvalidCacheBundle(from.clone(), "missing `manifest.json`".into())
+        })?;
+        for (key, index_path) in manifest.packages {
+            let index = indexes.remove(&index_path).ok_or_else(|| {
+                NassunError::InvalidCacheBundle(from.clone(), format!("missing `{index_path}`"))
+            })?;
+            cacache::index::insert(
+                &cache,
+                &key,
+                WriteOpts::new()
+                    // This is just so the index entry is loadable.
+                    .integrity("sha256-deadbeef".parse().unwrap())
+                    .raw_metadata(index),
+            )
+            .map_err(|e| NassunError::CacheMaintenanceError(e, cache.clone()))?;
+            report.packages += 1;
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// What moving a cache took, from [`relocate`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelocateReport {
+    /// Whether the cache could just be renamed, which keeps every file in
+    /// it exactly as it was. Otherwise, it was copied over and the old one
+    /// removed.
+    pub renamed: bool,
+    /// How many files were copied.
+    pub files_copied: usize,
+    /// How many files were hard links to other files in the cache, like
+    /// the global store's, and got linked to their copies instead of being
+    /// copied again.
+    pub files_linked: usize,
+    /// Bytes copied.
+    pub bytes_copied: u64,
+}
+
+/// Moves the cache at `from` to `to`, which has to be empty or not exist
+/// yet, and can't be inside `from`, like when moving it to a bigger disk or
+/// a shared location. Nothing else should be using the cache while it
+/// moves.
+///
+/// Caches are renamed when possible, which is instant and leaves every
+/// hard link to their files alone. Moving to another volume means copying
+/// instead: everything's copied before anything's removed, and files that
+/// were hard linked to each other inside the cache are still linked to each
+/// other afterwards. Projects that had files hard linked from the old cache
+/// keep their own copies, and go back to sharing them the next time
+/// they're installed.
+pub async fn relocate(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<RelocateReport> {
+    let from = from.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+    async_std::task::spawn_blocking(move || {
+        let relocate_err = |e| NassunError::CacheRelocateError(e, to.clone());
+        // Checked before anything's done to `to`, including removing it
+        // when it's empty.
+        let canonical_from = from.canonicalize().map_err(relocate_err)?;
+        if canonicalize_existing(&to)
+            .map_err(relocate_err)?
+            .starts_with(&canonical_from)
+        {
+            return Err(NassunError::CacheRelocateIntoItself(from, to));
+        }
+        match std::fs::read_dir(&to) {
+            Ok(mut entries) => {
+                if entries.next().is_some() {
+                    return Err(NassunError::CacheRelocateTargetExists(to.clone()));
+                }
+                // Not every platform can rename over an empty directory.
+                std::fs::remove_dir(&to).map_err(relocate_err)?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(relocate_err(e)),
+        }
+        let parent = to.parent().unwrap_or_else(|| Path::new("."));
+        std::fs::create_dir_all(parent).map_err(relocate_err)?;
+        if std::fs::rename(&from, &to).is_ok() {
+            return Ok(RelocateReport {
+                renamed: true,
+                ..Default::default()
+            });
+        }
+        // Usually because `to` is on another volume. The copy goes next to
+        // its final location first, so a failed copy never leaves a
+        // half-copied cache at `to`.
+        let staging = tempfile::Builder::new()
+            .prefix(".oro-relocate-")
+            .tempdir_in(parent)
+            .map_err(relocate_err)?;
+        let mut report = RelocateReport::default();
+        copy_tree(&from, staging.path(), &mut HashMap::new(), &mut report).map_err(relocate_err)?;
+        std::fs::rename(staging.path(), &to).map_err(relocate_err)?;
+        if let Err(e) = std::fs::remove_dir_all(&from) {
+            tracing::warn!(
+                "Moved cache to {}, but failed to remove the old one at {}: {e}",
+                to.display(),
+                from.display()
+            );
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// Canonicalizes `path` as far as it exists, with whatever doesn't exist
+/// yet tacked back on, so it can be compared to other canonical paths
+/// before it's created.
+fn canonicalize_existing(path: &Path) -> std::io::Result<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                return Ok(missing
+                    .into_iter()
+                    .rev()
+                    .fold(canonical, |path, name| path.join(name)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e);
+                };
+                missing.push(name);
+                existing = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Copies everything in `from` into `to`, keeping files that were hard
+/// linked to each other linked. `linked` remembers where files with more
+/// than one link were copied to.
+fn copy_tree(
+    from: &Path,
+    to: &Path,
+    linked: &mut HashMap<(u64, u64), PathBuf>,
+    report: &mut RelocateReport,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let dest = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &dest, linked, report)?;
+            continue;
+        }
+        #[cfg(unix)]
+        if file_type.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &dest)?;
+            continue;
+        }
+        let meta = entry.metadata()?;
+        let id = link_id(&meta);
+        if let Some(original) = id.and_then(|id| linked.get(&id)) {
+            std::fs::hard_link(original, &dest)?;
+            report.files_linked += 1;
+            continue;
+        }
+        report.bytes_copied += std::fs::copy(entry.path(), &dest)?;
+        report.files_copied += 1;
+        if let Some(id) = id {
+            linked.insert(id, dest);
+        }
+    }
+    Ok(())
+}
+
+/// Identifies the file behind `meta`, if it has other links that might
+/// come up later.
+#[cfg(unix)]
+fn link_id(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn link_id(_meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// A file in the cache's content store.
+struct Content {
+    key: (String, String),
+    path: PathBuf,
+    size: u64,
+}
+
+impl Content {
+    /// Whether the file's data still matches the hash it's stored under.
+    fn is_intact(&self) -> Result<bool> {
+        let (algorithm, hex) = &self.key;
+        Ok(hash_matches(algorithm, hex, &std::fs::read(&self.path)?))
+    }
+}
+
+/// Whether `data` hashes to `hex` with the algorithm named `algorithm`.
+/// Algorithms we don't know about never match, since there's no way to
+/// check them.
+fn hash_matches(algorithm: &str, hex: &str, data: &[u8]) -> bool {
+    let Ok(algorithm) = algorithm.parse::<Algorithm>() else {
+        return false;
+    };
+    let actual = IntegrityOpts::new()
+        .algorithm(algorithm)
+        .chain(data)
+        .result();
+    actual.to_hex().1 == hex
+}
+
+/// Every file in the cache's content store, which keeps them at
+/// `content-v2/<algorithm>/<hex[..2]>/<hex[2..4]>/<hex[4..]>`.
+fn contents(cache: &Path) -> Result<Vec<Content>> {
+    fn subdirs(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+        match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .map(|entry| {
+                    let entry = entry?;
+                    Ok((entry.file_name().to_string_lossy().into(), entry.path()))
+                })
+                .collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+    let mut contents = Vec::new();
+    for (algorithm, algorithm_dir) in subdirs(&cache.join("content-v2"))? {
+        for (first, first_dir) in subdirs(&algorithm_dir)? {
+            for (second, second_dir) in subdirs(&first_dir)? {
+                for (rest, path) in subdirs(&second_dir)? {
+                    let size = std::fs::metadata(&path)?.len();
+                    contents.push(Content {
+                        key: (algorithm.clone(), format!("{first}{second}{rest}")),
+                        path,
+                        size,
+                    });
+                }
+            }
+        }
+    }
+    Ok(contents)
+}
+
+fn content_key(sri: &Integrity) -> (String, String) {
+    let (algorithm, hex) = sri.to_hex();
+    (algorithm.to_string(), hex)
+}
+
+/// Content an entry needs to be usable: every file in the package, for
+/// extracted packages, or the entry's own content otherwise. `None` if the
+/// package's file index is missing or unreadable.
+fn entry_contents(entry: &cacache::Metadata) -> Option<Vec<Integrity>> {
+    if entry.key.starts_with(SOURCE_KEY_PREFIX) {
+        // These only point at a package, which has its own entry.
+        return Some(Vec::new());
+    }
+    if !entry.key.starts_with(PACKAGE_KEY_PREFIX) {
+        return Some(vec![entry.integrity.clone()]);
+    }
+    let index = unsafe { rkyv::util::archived_root::<TarballIndex>(entry.raw_metadata.as_ref()?) };
+    index
+        .files
+        .iter()
+        .map(|(_, (sri, _))| sri.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::tarball::tarball_key;
+
+    /// Caches a package made of `files`, the way extracting one would.
+    fn cache_package(cache: &Path, sri: &Integrity, files: &[&[u8]]) {
+        let mut index = TarballIndex::default();
+        for (idx, data) in files.iter().enumerate() {
+            let file_sri = cacache::write_hash_sync(cache, data).unwrap();
+            index
+                .files
+                .insert(format!("file{idx}"), (file_sri.to_string(), 0o644));
+        }
+        cacache::index::insert(
+            cache,
+            &tarball_key(sri),
+            WriteOpts::new()
+                .integrity("sha256-deadbeef".parse().unwrap())
+                .raw_metadata(rkyv::util::to_bytes::<_, 1024>(&index).unwrap().into_vec()),
+        )
+        .unwrap();
+    }
+
+    #[async_std::test]
+    async fn collects_unneeded_packages() -> miette::Result<()> {
+        let cache = tempdir().unwrap();
+        let needed = Integrity::from(b"needed");
+        let unneeded = Integrity::from(b"unneeded");
+        cache_package(cache.path(), &needed, &[b"shared", b"needed file"]);
+        cache_package(cache.path(), &unneeded, &[b"shared", b"unneeded file"]);
+
+        let report = gc(cache.path(), [needed.clone()]).await?;
+        assert_eq!(report.entries_removed, 1);
+        assert_eq!(report.contents_removed, 1);
+        assert_eq!(report.reclaimed_bytes, b"unneeded file".len() as u64);
+        assert!(cacache::index::find(cache.path(), &tarball_key(&needed))
+            .unwrap()
+            .is_some());
+        assert!(cacache::index::find(cache.path(), &tarball_key(&unneeded))
+            .unwrap()
+            .is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn forgets_sources_of_unneeded_packages() -> miette::Result<()> {
+        let cache = tempdir().unwrap();
+        let needed = Integrity::from(b"needed");
+        let unneeded = Integrity::from(b"unneeded");
+        for (source, sri) in [("needed.tgz", &needed), ("unneeded.tgz", &unneeded)] {
+            cache_package(cache.path(), sri, &[source.as_bytes()]);
+            cacache::index::insert(
+                cache.path(),
+                &format!("{SOURCE_KEY_PREFIX}{source}"),
+                WriteOpts::new()
+                    .integrity("sha256-deadbeef".parse().unwrap())
+                    .raw_metadata(sri.to_string().into_bytes()),
+            )
+            .unwrap();
+        }
+
+        // Sources don't have any content of their own to verify.
+        assert_eq!(verify(cache.path()).await?.entries_removed, 0);
+        let report = gc(cache.path(), [needed]).await?;
+        assert_eq!(report.entries_removed, 2);
+        assert!(
+            cacache::index::find(cache.path(), &format!("{SOURCE_KEY_PREFIX}needed.tgz"))
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            cacache::index::find(cache.path(), &format!("{SOURCE_KEY_PREFIX}unneeded.tgz"))
+                .unwrap()
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn counts_what_is_cached() -> miette::Result<()> {
+        let cache = tempdir().unwrap();
+        cache_package(cache.path(), &Integrity::from(b"a"), &[b"shared", b"a"]);
+        cache_package(cache.path(), &Integrity::from(b"b"), &[b"shared", b"b"]);
+        cacache::write_sync(cache.path(), "packument", b"{}").unwrap();
+        assert_eq!(
+            stats(cache.path()).await?,
+            CacheStats {
+                entries: 3,
+                packages: 2,
+                contents: 4,
+                content_bytes: (b"shared".len() + b"a".len() + b"b".len() + b"{}".len()) as u64,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn counts_downloads() {
+        let observer = CountingObserver {
+            counters: Arc::new(CacheCounters::default()),
+            inner: None,
+        };
+        for (kind, cache, bytes, ms) in [
+            (RequestKind::Packument, CacheStatus::Miss, 100, 30),
+            (RequestKind::Packument, CacheStatus::Hit, 0, 10),
+            (RequestKind::Tarball, CacheStatus::Uncached, 2000, 50),
+        ] {
+            observer.on_request(&RequestEvent {
+                url: "https://registry.npmjs.org/foo".parse().unwrap(),
+                kind,
+                status: Some(200),
+                duration: std::time::Duration::from_millis(ms),
+                retries: 0,
+                bytes,
+                cache,
+                error: None,
+            });
+        }
+        let counts = observer.counters.counts();
+        assert_eq!(counts.bytes_downloaded, 2100);
+        assert_eq!(counts.packument_misses, 1);
+        assert_eq!(counts.packument_revalidations, 1);
+        assert_eq!(counts.packument_request_ms, 40);
+        assert_eq!(counts.tarball_request_ms, 50);
+    }
+
+    #[async_std::test]
+    async fn removes_corrupt_content() -> miette::Result<()> {
+        let cache = tempdir().unwrap();
+        let sri = Integrity::from(b"package");
+        cache_package(cache.path(), &sri, &[b"fine", b"about to be corrupted"]);
+        let corrupted = contents(cache.path())?
+            .into_iter()
+            .find(|content| content.key == content_key(&Integrity::from(b"about to be corrupted")))
+            .unwrap();
+        std::fs::write(&corrupted.path, b"oops").unwrap();
+
+        let report = verify(cache.path()).await?;
+        assert_eq!(report.contents_checked, 2);
+        assert_eq!(report.corrupted, vec![corrupted.path]);
+        assert_eq!(report.entries_removed, 1);
+        assert!(cacache::index::find(cache.path(), &tarball_key(&sri))
+            .unwrap()
+            .is_none());
+
+        // Nothing's wrong anymore.
+        let report = verify(cache.path()).await?;
+        assert_eq!(
+            report,
+            CacheReport {
+                contents_checked: 1,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn relocates_caches() -> miette::Result<()> {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("old");
+        let to = dir.path().join("new").join("cache");
+        let sri = Integrity::from(b"package");
+        cache_package(&from, &sri, &[b"file"]);
+        std::fs::create_dir_all(&to).unwrap();
+        std::fs::write(to.join("stuff"), b"in the way").unwrap();
+        assert!(matches!(
+            relocate(&from, &to).await,
+            Err(NassunError::CacheRelocateTargetExists(_))
+        ));
+
+        std::fs::remove_file(to.join("stuff")).unwrap();
+        assert!(relocate(&from, &to).await?.renamed);
+        assert!(!from.exists());
+        assert!(cacache::index::find(&to, &tarball_key(&sri))
+            .unwrap()
+            .is_some());
+        assert_eq!(verify(&to).await?.entries_removed, 0);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refuses_to_relocate_into_itself() -> miette::Result<()> {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("cache");
+        let sri = Integrity::from(b"package");
+        cache_package(&from, &sri, &[b"file"]);
+        // Empty, so it would otherwise get removed before the move.
+        let inside = from.join("empty");
+        std::fs::create_dir(&inside).unwrap();
+
+        for to in [
+            from.clone(),
+            inside.clone(),
+            from.join("missing").join("cache"),
+            // Only the same cache once it's canonicalized.
+            dir.path().join("cache").join(".").join("empty"),
+        ] {
+            assert!(matches!(
+                relocate(&from, &to).await,
+                Err(NassunError::CacheRelocateIntoItself(..))
+            ));
+        }
+        assert!(inside.is_dir());
+        assert!(!from.join("missing").exists());
+        assert_eq!(verify(&from).await?.entries_removed, 0);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[async_std::test]
+    async fn refuses_to_relocate_into_itself_through_symlinks() -> miette::Result<()> {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("cache");
+        let sri = Integrity::from(b"package");
+        cache_package(&from, &sri, &[b"file"]);
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&from, &link).unwrap();
+
+        assert!(matches!(
+            relocate(&from, link.join("moved")).await,
+            Err(NassunError::CacheRelocateIntoItself(..))
+        ));
+        assert!(!from.join("moved").exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_hard_links_as_links() -> std::io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let from = tempdir()?;
+        let to = tempdir()?;
+        std::fs::create_dir_all(from.path().join("store"))?;
+        std::fs::write(from.path().join("content"), b"shared")?;
+        std::fs::write(from.path().join("other"), b"not shared")?;
+        std::fs::hard_link(
+            from.path().join("content"),
+            from.path().join("store").join("file"),
+        )?;
+
+        let mut report = RelocateReport::default();
+        copy_tree(from.path(), to.path(), &mut HashMap::new(), &mut report)?;
+        assert_eq!(report.files_copied, 2);
+        assert_eq!(report.files_linked, 1);
+        assert_eq!(
+            std::fs::metadata(to.path().join("content"))?.ino(),
+            std::fs::metadata(to.path().join("store").join("file"))?.ino()
+        );
+        assert_ne!(
+            std::fs::metadata(to.path().join("content"))?.ino(),
+            std::fs::metadata(from.path().join("content"))?.ino()
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn moves_packages_between_caches() -> miette::Result<()> {
+        let from = tempdir().unwrap();
+        let to = tempdir().unwrap();
+        let bundle = tempdir().unwrap();
+        let bundle = bundle.path().join("bundle.tgz");
+        let wanted = Integrity::from(b"wanted");
+        let unwanted = Integrity::from(b"unwanted");
+        let missing = Integrity::from(b"missing");
+        cache_package(from.path(), &wanted, &[b"shared", b"wanted file"]);
+        cache_package(from.path(), &unwanted, &[b"shared", b"unwanted file"]);
+
+        let report = export(from.path(), [wanted.clone(), missing.clone()], &bundle).await?;
+        assert_eq!(report.packages, 1);
+        assert_eq!(report.contents, 2);
+        assert_eq!(report.missing, vec![missing]);
+
+        let report = import(to.path(), &bundle).await?;
+        assert_eq!(report.packages, 1);
+        assert_eq!(report.contents, 2);
+        assert!(cacache::index::find(to.path(), &tarball_key(&wanted))
+            .unwrap()
+            .is_some());
+        assert!(cacache::index::find(to.path(), &tarball_key(&unwanted))
+            .unwrap()
+            .is_none());
+        // Everything the package needs came along with it.
+        assert_eq!(verify(to.path()).await?.entries_removed, 0);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refuses_content_it_cant_verify() -> miette::Result<()> {
+        let cache = tempdir().unwrap();
+        let bundle = tempdir().unwrap();
+        let bundle = bundle.path().join("bundle.tgz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            std::fs::File::create(&bundle).unwrap(),
+            Compression::default(),
+        ));
+        let data = b"unverifiable";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "content/md5/0123456789abcdef", &data[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        assert!(import(cache.path(), &bundle).await.is_err());
+        assert!(!cache.path().join("content-v2").join("md5").exists());
+        Ok(())
+    }
+}
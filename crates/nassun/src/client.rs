@@ -1,33 +1,162 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use async_std::sync::Arc;
+use chrono::{DateTime, Utc};
+pub use oro_client::Credentials;
 use oro_client::OroClient;
+pub use oro_client::RetryPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+pub use oro_client::S3Credentials;
+#[cfg(not(target_arch = "wasm32"))]
+pub use oro_client::TlsConfig;
+pub use oro_client::{CacheStatus, RequestEvent, RequestKind, RequestObserver};
 use oro_common::{CorgiManifest, CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
 use url::Url;
 
 pub use oro_package_spec::{PackageSpec, VersionSpec};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cache::{CacheCounters, CacheCounts, CountingObserver};
 use crate::entries::Entries;
 use crate::error::Result;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::fetch::DirFetcher;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::fetch::GitFetcher;
-use crate::fetch::{DummyFetcher, NpmFetcher, PackageFetcher};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::fetch::GitPreparer;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::fetch::LocalRegistryFetcher;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::fetch::UrlFetcher;
+use crate::fetch::{DummyFetcher, NpmFetcher, PackageSource};
 use crate::package::Package;
 use crate::resolver::{PackageResolution, PackageResolver};
+use crate::rewrite::TarballRewrite;
+use crate::signatures::SignatureVerification;
 use crate::tarball::Tarball;
 
 /// Build a new Nassun instance with specified options.
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct NassunOpts {
     #[cfg(not(target_arch = "wasm32"))]
     cache: Option<PathBuf>,
     base_dir: Option<PathBuf>,
     default_tag: Option<String>,
+    prefer_lowest: bool,
+    minimum_release_age: Option<Duration>,
+    before: Option<SystemTime>,
     registries: HashMap<Option<String>, Url>,
+    sources: HashMap<String, Arc<dyn PackageSource>>,
+    mirrors: Vec<Url>,
+    tarball_rewrites: Vec<TarballRewrite>,
+    credentials: Vec<(String, Credentials)>,
+    always_auth: Vec<String>,
     memoize_metadata: bool,
+    retry_policy: RetryPolicy,
+    request_observer: Option<Arc<dyn RequestObserver>>,
+    verify_signatures: SignatureVerification,
+    #[cfg(not(target_arch = "wasm32"))]
+    git_preparer: Option<Arc<dyn GitPreparer>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    http1_only: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<Url>,
+    #[cfg(not(target_arch = "wasm32"))]
+    https_proxy: Option<Url>,
+    #[cfg(not(target_arch = "wasm32"))]
+    no_proxy: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls: Option<TlsConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    host_tls: Vec<(String, TlsConfig)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    network_limit: Option<u64>,
+    #[cfg(not(target_arch = "wasm32"))]
+    max_sockets: Option<usize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    max_sockets_per_host: Option<usize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pool_idle_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls_handshake_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    first_byte_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    request_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    dns_ttl: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    mmap: bool,
+    /// Shared by every [`Nassun`] built from these options, or clones of
+    /// them.
+    #[cfg(not(target_arch = "wasm32"))]
+    counters: Arc<CacheCounters>,
+}
+
+// Sources, request observers, and git preparers can't be compared, so
+// they're only equal if they're the same one. Cache counters aren't
+// options at all, so they're left out.
+impl PartialEq for NassunOpts {
+    fn eq(&self, other: &Self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        let native = self.cache == other.cache
+            && same(&self.git_preparer, &other.git_preparer)
+            && self.http1_only == other.http1_only
+            && self.proxy == other.proxy
+            && self.https_proxy == other.https_proxy
+            && self.no_proxy == other.no_proxy
+            && self.tls == other.tls
+            && self.host_tls == other.host_tls
+            && self.network_limit == other.network_limit
+            && self.max_sockets == other.max_sockets
+            && self.max_sockets_per_host == other.max_sockets_per_host
+            && self.pool_idle_timeout == other.pool_idle_timeout
+            && self.connect_timeout == other.connect_timeout
+            && self.tls_handshake_timeout == other.tls_handshake_timeout
+            && self.first_byte_timeout == other.first_byte_timeout
+            && self.request_timeout == other.request_timeout
+            && self.dns_ttl == other.dns_ttl
+            && self.mmap == other.mmap;
+        #[cfg(target_arch = "wasm32")]
+        let native = true;
+        native
+            && self.base_dir == other.base_dir
+            && self.default_tag == other.default_tag
+            && self.prefer_lowest == other.prefer_lowest
+            && self.minimum_release_age == other.minimum_release_age
+            && self.before == other.before
+            && self.registries == other.registries
+            && self.sources.len() == other.sources.len()
+            && self.sources.iter().all(|(scope, source)| {
+                other
+                    .sources
+                    .get(scope)
+                    .map_or(false, |other| Arc::ptr_eq(source, other))
+            })
+            && self.mirrors == other.mirrors
+            && self.tarball_rewrites == other.tarball_rewrites
+            && self.credentials == other.credentials
+            && self.always_auth == other.always_auth
+            && self.memoize_metadata == other.memoize_metadata
+            && self.retry_policy == other.retry_policy
+            && same(&self.request_observer, &other.request_observer)
+            && self.verify_signatures == other.verify_signatures
+    }
+}
+
+impl Eq for NassunOpts {}
+
+/// Whether `a` and `b` are both unset, or both the same shared value.
+fn same<T: ?Sized>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
 }
 
 impl NassunOpts {
@@ -42,6 +171,10 @@ impl NassunOpts {
         self
     }
 
+    /// Registry to fetch packages from. `file:` URLs point at a local
+    /// directory laid out like verdaccio's storage, with a directory for
+    /// each package holding its packument, in `package.json`, and its
+    /// tarballs.
     pub fn registry(mut self, registry: Url) -> Self {
         self.registries.insert(None, registry);
         self
@@ -57,6 +190,50 @@ impl NassunOpts {
         self
     }
 
+    /// Uses a custom [`PackageSource`] for all registry packages in a
+    /// specific scope, instead of fetching them from a registry. This takes
+    /// precedence over [`NassunOpts::scope_registry`].
+    pub fn scope_source(mut self, scope: impl AsRef<str>, source: Arc<dyn PackageSource>) -> Self {
+        let scope = scope.as_ref();
+        self.sources
+            .insert(scope.strip_prefix('@').unwrap_or(scope).to_string(), source);
+        self
+    }
+
+    /// Adds a mirror of the default registry. When the registry fails
+    /// with a server error or times out, packuments and tarballs are
+    /// fetched from its mirrors instead, in the order they were added.
+    pub fn mirror(mut self, mirror: Url) -> Self {
+        self.mirrors.push(mirror);
+        self
+    }
+
+    /// Adds a rule for rewriting `dist.tarball` URLs before they're
+    /// fetched, for installing through artifact proxies that serve
+    /// packuments with upstream tarball URLs. The first matching rule
+    /// applies. See [`TarballRewrite`].
+    pub fn tarball_rewrite(mut self, rewrite: TarballRewrite) -> Self {
+        self.tarball_rewrites.push(rewrite);
+        self
+    }
+
+    /// Credentials to authenticate requests to registries on `host` with.
+    /// Scoped registries on private hosts usually need these. This option
+    /// can be provided multiple times.
+    pub fn credentials(mut self, host: impl AsRef<str>, credentials: Credentials) -> Self {
+        self.credentials.push((host.as_ref().into(), credentials));
+        self
+    }
+
+    /// Sends the credentials for the registry on `host` with requests for
+    /// its packages' tarballs, even when they're hosted somewhere else.
+    /// This is npm's `always-auth`. This option can be provided multiple
+    /// times.
+    pub fn always_auth(mut self, host: impl AsRef<str>) -> Self {
+        self.always_auth.push(host.as_ref().into());
+        self
+    }
+
     /// Base directory to use for resolving relative paths. Defaults to `"."`.
     pub fn base_dir(mut self, base_dir: impl AsRef<Path>) -> Self {
         self.base_dir = Some(PathBuf::from(base_dir.as_ref()));
@@ -69,6 +246,30 @@ impl NassunOpts {
         self
     }
 
+    /// Resolve version ranges to the lowest matching version, instead of the
+    /// highest one. The default tag is still used for specs without a
+    /// version range, such as `foo`.
+    pub fn prefer_lowest(mut self, prefer_lowest: bool) -> Self {
+        self.prefer_lowest = prefer_lowest;
+        self
+    }
+
+    /// Skip versions that were published less than `age` ago when
+    /// resolving version ranges and the default tag. Explicitly requested
+    /// versions and tags are still used as-is.
+    pub fn minimum_release_age(mut self, age: Duration) -> Self {
+        self.minimum_release_age = Some(age);
+        self
+    }
+
+    /// Skip versions that were published after `time` when resolving
+    /// version ranges and the default tag, as if resolving at that point in
+    /// time. Explicitly requested versions and tags are still used as-is.
+    pub fn before(mut self, time: SystemTime) -> Self {
+        self.before = Some(time);
+        self
+    }
+
     /// Whether to memoize package metadata. This will keep any processed
     /// packuments in memory for the lifetime of this `Nassun` instance.
     /// Setting this to `true` may increase performance when fetching many
@@ -78,6 +279,171 @@ impl NassunOpts {
         self
     }
 
+    /// How registry requests that fail for transient reasons, like 5xx
+    /// responses or dropped connections, get retried. See [`RetryPolicy`].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Observer to report every registry request to. See
+    /// [`oro_client::OroClientBuilder::observer`].
+    pub fn request_observer(mut self, observer: Arc<dyn RequestObserver>) -> Self {
+        self.request_observer = Some(observer);
+        self
+    }
+
+    /// Whether to check registry signatures over package integrities before
+    /// fetching their tarballs, and what to do when they can't be
+    /// verified. Defaults to [`SignatureVerification::Off`]. See
+    /// [`crate::signatures`].
+    pub fn verify_signatures(mut self, verify: SignatureVerification) -> Self {
+        self.verify_signatures = verify;
+        self
+    }
+
+    /// Preparer to use when building git dependencies that have a `prepare`
+    /// script. If this isn't set, those dependencies will be installed
+    /// without being built.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn git_preparer(mut self, preparer: Arc<dyn GitPreparer>) -> Self {
+        self.git_preparer = Some(preparer);
+        self
+    }
+
+    /// Whether to use HTTP/2 with registries that support it. Defaults to
+    /// `true`. See [`oro_client::OroClientBuilder::http2`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn http2(mut self, http2: bool) -> Self {
+        self.http1_only = !http2;
+        self
+    }
+
+    /// Proxy to send registry requests through. See
+    /// [`oro_client::OroClientBuilder::proxy`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: Url) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Proxy to send HTTPS registry requests through. See
+    /// [`oro_client::OroClientBuilder::https_proxy`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn https_proxy(mut self, proxy: Url) -> Self {
+        self.https_proxy = Some(proxy);
+        self
+    }
+
+    /// Comma-separated hosts to connect to without a proxy. See
+    /// [`oro_client::OroClientBuilder::no_proxy`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn no_proxy(mut self, hosts: impl AsRef<str>) -> Self {
+        self.no_proxy = Some(hosts.as_ref().into());
+        self
+    }
+
+    /// TLS settings for connecting to registries, like extra CA
+    /// certificates to trust, or a client certificate to present. See
+    /// [`TlsConfig`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// TLS settings for connecting to registries on `host`, instead of the
+    /// ones set with [`NassunOpts::tls`]. This option can be provided
+    /// multiple times.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn host_tls(mut self, host: impl AsRef<str>, tls: TlsConfig) -> Self {
+        self.host_tls.push((host.as_ref().into(), tls));
+        self
+    }
+
+    /// Most bytes per second to download, across all requests. See
+    /// [`oro_client::OroClientBuilder::network_limit`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn network_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.network_limit = Some(bytes_per_sec);
+        self
+    }
+
+    /// Most requests to have open at once. See
+    /// [`oro_client::OroClientBuilder::max_sockets`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_sockets(mut self, max: usize) -> Self {
+        self.max_sockets = Some(max);
+        self
+    }
+
+    /// Most requests to have open at once to any one host. See
+    /// [`oro_client::OroClientBuilder::max_sockets_per_host`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_sockets_per_host(mut self, max: usize) -> Self {
+        self.max_sockets_per_host = Some(max);
+        self
+    }
+
+    /// How long to keep idle connections around. See
+    /// [`oro_client::OroClientBuilder::pool_idle_timeout`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to wait for a connection to a registry. See
+    /// [`oro_client::OroClientBuilder::connect_timeout`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to wait for TLS handshakes. See
+    /// [`oro_client::OroClientBuilder::tls_handshake_timeout`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to wait for registries to start responding. See
+    /// [`oro_client::OroClientBuilder::first_byte_timeout`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
+
+    /// Longest a metadata request can take. See
+    /// [`oro_client::OroClientBuilder::request_timeout`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// How long to remember registry hosts' addresses. See
+    /// [`oro_client::OroClientBuilder::dns_ttl`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dns_ttl(mut self, ttl: Duration) -> Self {
+        self.dns_ttl = Some(ttl);
+        self
+    }
+
+    /// Map big files into memory instead of reading them, when extracting
+    /// tarballs that were too big to download into memory, and when
+    /// checking extracted files against their integrity. Cuts down on
+    /// copying and peak memory use, but can misbehave on network
+    /// filesystems. Defaults to `false`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
     /// Build a new Nassun instance from this options object.
     pub fn build(self) -> Nassun {
         let registry = self
@@ -85,10 +451,88 @@ impl NassunOpts {
             .get(&None)
             .cloned()
             .unwrap_or_else(|| "https://registry.npmjs.org/".parse().unwrap());
+        let mut client_builder = OroClient::builder()
+            .registry(registry)
+            .retry_policy(self.retry_policy);
+        for (host, credentials) in self.credentials {
+            client_builder = client_builder.credentials(host, credentials);
+        }
+        for host in self.always_auth {
+            client_builder = client_builder.always_auth(host);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let counters = self.counters;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            client_builder = client_builder.observer(Arc::new(CountingObserver {
+                counters: counters.clone(),
+                inner: self.request_observer,
+            }));
+        }
         #[cfg(target_arch = "wasm32")]
-        let client_builder = OroClient::builder().registry(registry);
+        if let Some(observer) = self.request_observer {
+            client_builder = client_builder.observer(observer);
+        }
         #[cfg(not(target_arch = "wasm32"))]
-        let mut client_builder = OroClient::builder().registry(registry);
+        {
+            client_builder = client_builder.http2(!self.http1_only);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(proxy) = self.proxy {
+            client_builder = client_builder.proxy(proxy);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(proxy) = self.https_proxy {
+            client_builder = client_builder.https_proxy(proxy);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(hosts) = self.no_proxy {
+            client_builder = client_builder.no_proxy(hosts);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(tls) = self.tls {
+            client_builder = client_builder.tls(tls);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        for (host, tls) in self.host_tls {
+            client_builder = client_builder.host_tls(host, tls);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(limit) = self.network_limit {
+            client_builder = client_builder.network_limit(limit);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(max) = self.max_sockets {
+            client_builder = client_builder.max_sockets(max);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(max) = self.max_sockets_per_host {
+            client_builder = client_builder.max_sockets_per_host(max);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.pool_idle_timeout {
+            client_builder = client_builder.pool_idle_timeout(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.connect_timeout {
+            client_builder = client_builder.connect_timeout(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.tls_handshake_timeout {
+            client_builder = client_builder.tls_handshake_timeout(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.first_byte_timeout {
+            client_builder = client_builder.first_byte_timeout(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.request_timeout {
+            client_builder = client_builder.request_timeout(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ttl) = self.dns_ttl {
+            client_builder = client_builder.dns_ttl(ttl);
+        }
         #[cfg(not(target_arch = "wasm32"))]
         let cache = if let Some(cache) = self.cache {
             client_builder = client_builder.cache(cache.clone());
@@ -97,11 +541,46 @@ impl NassunOpts {
             Arc::new(None)
         };
         let client = client_builder.build();
+        let sources = self.sources;
+        // `file:` registries are directories laid out like a registry's
+        // storage, rather than servers.
+        #[cfg(not(target_arch = "wasm32"))]
+        let (sources, local_registry) = {
+            let mut sources = sources;
+            let mut local_registry = None;
+            for (scope, registry) in &self.registries {
+                let Ok(root) = registry.to_file_path() else {
+                    continue;
+                };
+                let local: Arc<dyn PackageSource> = Arc::new(LocalRegistryFetcher::new(root));
+                match scope {
+                    Some(scope) => {
+                        sources.entry(scope.clone()).or_insert(local);
+                    }
+                    None => local_registry = Some(local),
+                }
+            }
+            (sources, local_registry)
+        };
+        let npm_fetcher: Arc<dyn PackageSource> = Arc::new(NpmFetcher::new(
+            #[allow(clippy::redundant_clone)]
+            client.clone(),
+            self.registries,
+            self.mirrors,
+            self.tarball_rewrites,
+            self.memoize_metadata,
+            self.minimum_release_age.is_some() || self.before.is_some(),
+            self.verify_signatures,
+        ));
+        #[cfg(not(target_arch = "wasm32"))]
+        let npm_fetcher = local_registry.unwrap_or(npm_fetcher);
         Nassun {
             #[cfg(not(target_arch = "wasm32"))]
             cache,
             #[cfg(target_arch = "wasm32")]
             cache: Arc::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            counters: counters.clone(),
             resolver: PackageResolver {
                 #[cfg(target_arch = "wasm32")]
                 base_dir: PathBuf::from("."),
@@ -110,17 +589,22 @@ impl NassunOpts {
                     .base_dir
                     .unwrap_or_else(|| std::env::current_dir().expect("failed to get cwd.")),
                 default_tag: self.default_tag.unwrap_or_else(|| "latest".into()),
+                prefer_lowest: self.prefer_lowest,
+                minimum_release_age: self.minimum_release_age,
+                before: self.before.map(DateTime::<Utc>::from),
+                #[cfg(not(target_arch = "wasm32"))]
+                mmap: self.mmap,
+                #[cfg(not(target_arch = "wasm32"))]
+                counters: counters.clone(),
             },
-            npm_fetcher: Arc::new(NpmFetcher::new(
-                #[allow(clippy::redundant_clone)]
-                client.clone(),
-                self.registries,
-                self.memoize_metadata,
-            )),
+            sources,
+            npm_fetcher,
             #[cfg(not(target_arch = "wasm32"))]
             dir_fetcher: Arc::new(DirFetcher::new()),
             #[cfg(not(target_arch = "wasm32"))]
-            git_fetcher: Arc::new(GitFetcher::new(client)),
+            git_fetcher: Arc::new(GitFetcher::new(client.clone(), self.git_preparer)),
+            #[cfg(not(target_arch = "wasm32"))]
+            url_fetcher: Arc::new(UrlFetcher::new(client)),
         }
     }
 }
@@ -129,12 +613,17 @@ impl NassunOpts {
 #[derive(Clone)]
 pub struct Nassun {
     cache: Arc<Option<PathBuf>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    counters: Arc<CacheCounters>,
     resolver: PackageResolver,
-    npm_fetcher: Arc<dyn PackageFetcher>,
+    sources: HashMap<String, Arc<dyn PackageSource>>,
+    npm_fetcher: Arc<dyn PackageSource>,
+    #[cfg(not(target_arch = "wasm32"))]
+    dir_fetcher: Arc<dyn PackageSource>,
     #[cfg(not(target_arch = "wasm32"))]
-    dir_fetcher: Arc<dyn PackageFetcher>,
+    git_fetcher: Arc<dyn PackageSource>,
     #[cfg(not(target_arch = "wasm32"))]
-    git_fetcher: Arc<dyn PackageFetcher>,
+    url_fetcher: Arc<dyn PackageSource>,
 }
 
 impl Default for Nassun {
@@ -143,6 +632,14 @@ impl Default for Nassun {
     }
 }
 
+impl std::fmt::Debug for Nassun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Nassun")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Nassun {
     /// Creates a new `Nassun` instance with default settings. To configure
     /// `Nassun`, use [`NassunOpts`].
@@ -220,6 +717,15 @@ impl Nassun {
         Self::new().resolve(spec.as_ref()).await?.entries().await
     }
 
+    /// How much this instance has gotten out of the cache so far, along
+    /// with every [`Package`] it's resolved and every other [`Nassun`]
+    /// built from the same [`NassunOpts`]. See [`crate::cache::stats`] for
+    /// what's in the cache itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cache_counts(&self) -> CacheCounts {
+        self.counters.counts()
+    }
+
     /// Resolve a string spec (e.g. `foo@^1.2.3`, `github:foo/bar`, etc), to a
     /// [`Package`] that can be used for further operations.
     pub async fn resolve(&self, spec: impl AsRef<str>) -> Result<Package> {
@@ -268,18 +774,26 @@ impl Nassun {
             },
             base_dir: PathBuf::from("."),
             fetcher: Arc::new(DummyFetcher(manifest)),
+            #[cfg(not(target_arch = "wasm32"))]
+            mmap: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            counters: Default::default(),
         }
     }
 
-    fn pick_fetcher(&self, arg: &PackageSpec) -> Arc<dyn PackageFetcher> {
+    fn pick_fetcher(&self, arg: &PackageSpec) -> Arc<dyn PackageSource> {
         use PackageSpec::*;
         match *arg {
             Alias { ref spec, .. } => self.pick_fetcher(spec),
-            Npm { .. } => self.npm_fetcher.clone(),
+            Npm { ref scope, .. } => scope
+                .as_ref()
+                .and_then(|scope| self.sources.get(scope))
+                .cloned()
+                .unwrap_or_else(|| self.npm_fetcher.clone()),
             #[cfg(not(target_arch = "wasm32"))]
-            Dir { .. } => self.dir_fetcher.clone(),
+            Dir { .. } | Link { .. } => self.dir_fetcher.clone(),
             #[cfg(target_arch = "wasm32")]
-            Dir { .. } => panic!(
+            Dir { .. } | Link { .. } => panic!(
                 "Directory dependencies are not enabled. (While trying to process {})",
                 arg
             ),
@@ -290,6 +804,13 @@ impl Nassun {
                 "Git dependencies are not enabled. (While trying to process {})",
                 arg
             ),
+            #[cfg(not(target_arch = "wasm32"))]
+            Url { .. } => self.url_fetcher.clone(),
+            #[cfg(target_arch = "wasm32")]
+            Url { .. } => panic!(
+                "Tarball URL dependencies are not enabled. (While trying to process {})",
+                arg
+            ),
         }
     }
 }
@@ -30,24 +30,81 @@ pub enum NassunError {
     /// Failed to read a directory dependency. Refer to the error message for
     /// more details.
     #[error("{0}")]
-    #[diagnostic(code(nassun::dir::read), url(docsrs))]
+    #[diagnostic(
+        code(nassun::dir::read),
+        url(docsrs),
+        help("Make sure the directory exists, and has a readable package.json in it.")
+    )]
     DirReadError(#[source] std::io::Error, PathBuf),
 
+    /// Failed to pack a directory dependency into a tarball. Refer to the
+    /// error message for more details.
+    #[error("Failed to pack directory dependency at `{}`.", .1.display())]
+    #[diagnostic(
+        code(nassun::dir::pack),
+        url(docsrs),
+        help("Check that you have permission to read every file in the directory.")
+    )]
+    DirPackError(#[source] std::io::Error, PathBuf),
+
     /// An io-related error occurred while executing git.
     #[error("Failed to execute git subprocess. {0}")]
-    #[diagnostic(code(nassun::git::clone::io), url(docsrs))]
+    #[diagnostic(
+        code(nassun::git::clone::io),
+        url(docsrs),
+        help("Make sure git is installed and working.")
+    )]
     GitIoError(#[source] std::io::Error),
 
     /// An error occurred while trying to clone a repository.
     #[error("Failed to clone repository at `{0}`")]
-    #[diagnostic(code(nassun::git::clone::repo), url(docsrs))]
+    #[diagnostic(
+        code(nassun::git::clone::repo),
+        url(docsrs),
+        help("Make sure the repository exists, and that you have access to it. Private repositories need credentials configured for git itself.")
+    )]
     GitCloneError(String),
 
     /// An error occurred while trying to checkout a repository.
     #[error("Failed to check out `{0}#{1}`")]
-    #[diagnostic(code(nassun::git::checkout::repo), url(docsrs))]
+    #[diagnostic(
+        code(nassun::git::checkout::repo),
+        url(docsrs),
+        help("Make sure the branch, tag, or commit exists in the repository.")
+    )]
     GitCheckoutError(String, String),
 
+    /// The `path:` of a git dependency must be a relative path to a
+    /// directory inside the repository, containing a `package.json`.
+    #[error("Invalid subdirectory `{1}` for git dependency `{0}`.")]
+    #[diagnostic(
+        code(nassun::git::path),
+        url(docsrs),
+        help("`path:` should be a relative path to a directory with a package.json, such as `#path:packages/foo`.")
+    )]
+    GitPathError(String, String),
+
+    /// An error occurred while building a git dependency from source (that
+    /// is, while installing its dependencies or running its `prepare`
+    /// script). Refer to the error message for more details.
+    #[error("Failed to prepare git dependency at `{}`.", .0.display())]
+    #[diagnostic(
+        code(nassun::git::prepare),
+        url(docsrs),
+        help("Git dependencies with a `prepare` script get built after they're cloned. Check that the repository builds on its own.")
+    )]
+    GitPrepareError(PathBuf, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// An error occurred while packing a cloned git dependency into a
+    /// tarball.
+    #[error("Failed to pack git dependency into a tarball.")]
+    #[diagnostic(
+        code(nassun::git::pack),
+        url(docsrs),
+        help("Check that there's enough disk space in the temporary directory.")
+    )]
+    GitPackError(#[source] std::io::Error),
+
     /// Failed to extract a tarball while doing a certain IO operation. Refer
     /// to the error message for more details.
     #[error("Failed to extract tarball while {2}{}", if let Some(path) = .1 {
@@ -55,7 +112,11 @@ pub enum NassunError {
     } else {
         "".to_string()
     })]
-    #[diagnostic(code(nassun::io::extract), url(docsrs))]
+    #[diagnostic(
+        code(nassun::io::extract),
+        url(docsrs),
+        help("Check that there's enough disk space, and that you have permission to write to `node_modules/`.")
+    )]
     ExtractIoError(#[source] std::io::Error, Option<PathBuf>, String),
 
     /// Failed to extract a tarball to the cache. Refer to the error message
@@ -66,18 +127,96 @@ pub enum NassunError {
     } else {
         "".to_string()
     })]
-    #[diagnostic(code(nassun::cache::extract), url(docsrs))]
+    #[diagnostic(
+        code(nassun::cache::extract),
+        url(docsrs),
+        help("The cache may be damaged. Try `oro cache verify`, or clear the cache.")
+    )]
     ExtractCacheError(#[source] cacache::Error, Option<PathBuf>),
 
     #[cfg(not(target_arch = "wasm32"))]
     #[error("Missing file index for cache entry for {0}.")]
-    #[diagnostic(code(nassun::cache::missing_index), url(docsrs))]
+    #[diagnostic(
+        code(nassun::cache::missing_index),
+        url(docsrs),
+        help("The cache may be damaged. Try `oro cache verify`, or clear the cache.")
+    )]
     CacheMissingIndexError(String),
 
+    /// Failed to read or clean up the cache while verifying it or
+    /// collecting garbage from it.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to maintain cache at {}.", .1.display())]
+    #[diagnostic(
+        code(nassun::cache::maintenance),
+        url(docsrs),
+        help("Check that you have permission to read and write the cache directory.")
+    )]
+    CacheMaintenanceError(#[source] cacache::Error, PathBuf),
+
+    /// Failed to take a lock on part of the cache, which keeps other
+    /// processes sharing it from fetching the same package at once.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to lock cache at {}.", .1.display())]
+    #[diagnostic(
+        code(nassun::cache::lock),
+        url(docsrs),
+        help("Check that you have permission to write to the cache directory, and that it isn't on a filesystem without file locking.")
+    )]
+    CacheLockError(#[source] std::io::Error, PathBuf),
+
+    /// A cache couldn't be moved to a new location. The old cache is left
+    /// where it was.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to move cache to {}.", .1.display())]
+    #[diagnostic(
+        code(nassun::cache::relocate),
+        url(docsrs),
+        help("Check that you have permission to write to the new location.")
+    )]
+    CacheRelocateError(#[source] std::io::Error, PathBuf),
+
+    /// A cache can only be moved somewhere that's empty, so nothing already
+    /// there gets mixed in with it or overwritten.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Can't move cache to {}, because something's already there.", .0.display())]
+    #[diagnostic(
+        code(nassun::cache::relocate_target_exists),
+        url(docsrs),
+        help("Pick a directory that doesn't exist yet, or is empty.")
+    )]
+    CacheRelocateTargetExists(PathBuf),
+
+    /// A cache can't be moved into itself, since it would end up copying
+    /// its own copy.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Can't move cache at {} into itself, at {}.", .0.display(), .1.display())]
+    #[diagnostic(
+        code(nassun::cache::relocate_into_itself),
+        url(docsrs),
+        help("Pick a directory outside of the cache.")
+    )]
+    CacheRelocateIntoItself(PathBuf, PathBuf),
+
+    /// A cache bundle couldn't be imported, because it wasn't made by
+    /// `nassun::cache::export` or got damaged along the way.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Invalid cache bundle at {}: {1}", .0.display())]
+    #[diagnostic(
+        code(nassun::cache::invalid_bundle),
+        url(docsrs),
+        help("Export the cache bundle again with `oro cache export`.")
+    )]
+    InvalidCacheBundle(PathBuf, String),
+
     /// A generic IO error occurred. Refer tot he error message for more
     /// details.
     #[error(transparent)]
-    #[diagnostic(code(nassun::io::generic), url(docsrs))]
+    #[diagnostic(
+        code(nassun::io::generic),
+        url(docsrs),
+        help("Check that the paths involved exist and that you have permission to read and write them.")
+    )]
     IoError(#[from] std::io::Error),
 
     /// A generic oro-client error.
@@ -87,23 +226,71 @@ pub enum NassunError {
 
     /// A generic serde error.
     #[error(transparent)]
-    #[diagnostic(code(nassun::serde), url(docsrs))]
+    #[diagnostic(
+        code(nassun::serde),
+        url(docsrs),
+        help("The registry may have sent back something other than JSON. Check that it's configured correctly.")
+    )]
     SerdeError(#[from] serde_json::Error),
 
     /// Failed to parse a URL.
     #[error(transparent)]
-    #[diagnostic(code(nassun::bad_url), url(docsrs))]
+    #[diagnostic(
+        code(nassun::bad_url),
+        url(docsrs),
+        help("Make sure registry and tarball URLs include their scheme, like `https://`.")
+    )]
     UrlError(#[from] url::ParseError),
 
     /// Failed to parse a package integrity string.
     #[error(transparent)]
-    #[diagnostic(code(nassun::integrity_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(nassun::integrity_parse_error),
+        url(docsrs),
+        help("Integrity values should be Subresource Integrity strings, like `sha512-...`.")
+    )]
     IntegrityError(#[from] ssri::Error),
 
+    /// A downloaded tarball didn't match the integrity it was expected to
+    /// have. This is usually because the download was corrupted or cut
+    /// short, so it's worth retrying. If it keeps happening, the registry
+    /// may be serving a tampered tarball, or the lockfile's integrity may be
+    /// out of date.
+    #[error("Tarball integrity check failed. Expected `{expected}`, but got `{found}`.")]
+    #[diagnostic(
+        code(nassun::integrity_mismatch),
+        url(docsrs),
+        help("This is usually a corrupted download, so try again. If it keeps happening, check that the package's integrity in the lockfile is up to date.")
+    )]
+    IntegrityMismatch {
+        expected: Box<ssri::Integrity>,
+        found: Box<ssri::Integrity>,
+    },
+
+    /// A downloaded tarball didn't match the integrity it was expected to
+    /// have, and neither did the one downloaded again to replace it. See
+    /// the help text for what it's likely to mean.
+    #[error("Tarball for {package} failed its integrity check, even after downloading it again. Expected `{expected}`, but got `{first}`, then `{found}`.")]
+    #[diagnostic(code(nassun::integrity_mismatch_after_retry), url(docsrs))]
+    IntegrityMismatchAfterRetry {
+        package: String,
+        expected: Box<ssri::Integrity>,
+        /// What the first download hashed to.
+        first: Box<ssri::Integrity>,
+        /// What the second download hashed to.
+        found: Box<ssri::Integrity>,
+        #[help]
+        help: String,
+    },
+
     /// There's no tarball specified as part of the package metadata for a
     /// given package. This is likely a bug in the registry.
     #[error("Package metadata for {0} is missing a package tarball URL.")]
-    #[diagnostic(code(nassun::no_tarball), url(docsrs))]
+    #[diagnostic(
+        code(nassun::no_tarball),
+        url(docsrs),
+        help("The registry's metadata for this package is broken. Try a different version, or report it to the registry.")
+    )]
     NoTarball(String, PackageSpec, Box<CorgiVersionMetadata>),
 
     /// No matching version could be found for a given specifier. Make sure
@@ -123,10 +310,94 @@ pub enum NassunError {
         versions: Vec<String>,
     },
 
+    /// There are versions matching this spec, but all of them were
+    /// published more recently than the configured minimum release age.
+    #[error("All `{name}` versions matching `{spec}` were published less than {age} ago.")]
+    #[diagnostic(
+        code(resolver::no_old_enough_version),
+        url(docsrs),
+        help("Wait until a matching version is old enough, or lower the minimum release age.")
+    )]
+    NoOldEnoughVersion {
+        name: String,
+        spec: PackageSpec,
+        age: String,
+    },
+
+    /// There are versions matching this spec, but all of them were
+    /// published after the configured `before` time.
+    #[error("All `{name}` versions matching `{spec}` were published after {before}.")]
+    #[diagnostic(
+        code(resolver::no_version_before),
+        url(docsrs),
+        help("Use a later `before` time, or request a version that was published before it.")
+    )]
+    NoVersionBefore {
+        name: String,
+        spec: PackageSpec,
+        before: String,
+    },
+
+    /// A package's provenance attestation isn't about the package it was
+    /// published alongside, or can't be checked against it. The package may
+    /// have been tampered with after it was built.
+    #[error("Provenance attestation for `{package}` doesn't match it: {reason}.")]
+    #[diagnostic(
+        code(nassun::provenance::mismatch),
+        url(docsrs),
+        help("Make sure you trust the registry this package came from before installing it.")
+    )]
+    ProvenanceMismatch { package: String, reason: String },
+
+    /// A registry package's signature couldn't be verified against the
+    /// registry's signing keys, and signature verification is being
+    /// enforced. The package may have been tampered with.
+    #[error("Registry signature for `{package}` couldn't be verified: {reason}.")]
+    #[diagnostic(
+        code(nassun::signatures::verification_failed),
+        url(docsrs),
+        help("Make sure the package comes from the registry it was published to. Set `verify-signatures` to `warn` to install it anyway.")
+    )]
+    SignatureVerificationFailed { package: String, reason: String },
+
+    /// An unknown signature verification mode was requested.
+    #[error("Invalid signature verification mode: `{0}`.")]
+    #[diagnostic(
+        code(nassun::signatures::invalid_mode),
+        url(docsrs),
+        help("Valid modes are `off`, `warn`, and `enforce`.")
+    )]
+    InvalidSignatureVerification(String),
+
+    /// A tarball rewrite rule couldn't be parsed.
+    #[error("Invalid tarball rewrite rule `{0}`: {1}.")]
+    #[diagnostic(
+        code(nassun::invalid_tarball_rewrite),
+        url(docsrs),
+        help("Rules look like `https://registry.npmjs.org/=https://proxy.corp/npm/` for prefixes, or `/^https://registry\\.npmjs\\.org/(.*)$/=https://proxy.corp/npm/$1` for regexes.")
+    )]
+    InvalidTarballRewrite(String, String),
+
+    /// A custom [`PackageSource`](crate::PackageSource) failed. Refer to
+    /// the error message for more details.
+    #[error(transparent)]
+    #[diagnostic(
+        code(nassun::package_source),
+        url(docsrs),
+        help(
+            "This error came from a custom package source. Refer to its documentation for details."
+        )
+    )]
+    PackageSourceError(Box<dyn std::error::Error + Send + Sync>),
+
     /// Generic serde-wasm-bindgen error.
     #[cfg(target_arch = "wasm32")]
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::serde_wasm_bindgen::error), url(docsrs))]
+    #[diagnostic(
+        code(nassun::serde_wasm_bindgen::error),
+        url(docsrs),
+        help("Make sure the options passed in from JavaScript have the right shape.")
+    )]
     SerdeWasmBindgenError(#[from] serde_wasm_bindgen::Error),
 
     /// Failed to find git in the user's `$PATH`.
@@ -144,25 +415,41 @@ pub enum NassunError {
     /// The version resolver ran into an unexpected package spec. This is
     /// almost definitely a bug.
     #[error("Only Version, Tag, Range, and Alias package specs are supported, but got `{0}`.")]
-    #[diagnostic(code(nassun::invalid_package_spec), url(docsrs))]
+    #[diagnostic(
+        code(nassun::invalid_package_spec),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     InvalidPackageSpec(PackageSpec),
 
     /// Some unsupported operation happened while working with a dummy
     /// package. This is an internal detail and almost definitely a bug worth
     /// reporting.
     #[error("Unsupported dummy package operation: {0}")]
-    #[diagnostic(code(nassun::unsupported_dummy_operation), url(docsrs))]
+    #[diagnostic(
+        code(nassun::unsupported_dummy_operation),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     UnsupportedDummyOperation(String),
 
     /// A dummy package was missing a name. This is an internal detail and
     /// almost definitely a bug worth reporting.
     #[error("Dummy package does not have a name.")]
-    #[diagnostic(code(nassun::dummy_no_name), url(docsrs))]
+    #[diagnostic(
+        code(nassun::dummy_no_name),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     DummyNoName,
 
     /// An error occurred while serializing tarball metadata to cache.
     #[error("Failed to serialize tarball metadata to cache: {0}")]
-    #[diagnostic(code(nassun::cache::serialize), url(docsrs))]
+    #[diagnostic(
+        code(nassun::cache::serialize),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     SerializeCacheError(String),
 
     /// A miscellaneous, usually internal error. This is used mainly to wrap
@@ -172,7 +459,11 @@ pub enum NassunError {
     /// If you see this error, please file a bug report so that a better error
     /// can take its place.
     #[error("{0}")]
-    #[diagnostic(code(nassun::misc), url(docsrs))]
+    #[diagnostic(
+        code(nassun::misc),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     MiscError(String),
 }
 
@@ -13,7 +13,7 @@ use oro_package_spec::PackageSpec;
 use serde::{Deserialize, Serialize};
 
 use crate::error::{NassunError, Result};
-use crate::fetch::PackageFetcher;
+use crate::fetch::PackageSource;
 use crate::package::Package;
 use crate::resolver::PackageResolution;
 
@@ -98,11 +98,11 @@ impl DirFetcher {
 
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
-impl PackageFetcher for DirFetcher {
+impl PackageSource for DirFetcher {
     async fn name(&self, spec: &PackageSpec, base_dir: &Path) -> Result<String> {
         let path = match spec {
             PackageSpec::Alias { name, .. } => return Ok(name.clone()),
-            PackageSpec::Dir { path } => path,
+            PackageSpec::Dir { path } | PackageSpec::Link { path } => path,
             _ => panic!("There shouldn't be anything but Dirs here"),
         };
         self.name_from_path(&base_dir.join(path)).await
@@ -110,7 +110,7 @@ impl PackageFetcher for DirFetcher {
 
     async fn metadata(&self, pkg: &Package) -> Result<VersionMetadata> {
         let path = match pkg.resolved() {
-            PackageResolution::Dir { path, .. } => path,
+            PackageResolution::Dir { path, .. } | PackageResolution::Link { path, .. } => path,
             _ => panic!("There shouldn't be anything but Dirs here"),
         };
         self.metadata_from_path(path).await
@@ -118,15 +118,15 @@ impl PackageFetcher for DirFetcher {
 
     async fn corgi_metadata(&self, pkg: &Package) -> Result<CorgiVersionMetadata> {
         let path = match pkg.resolved() {
-            PackageResolution::Dir { path, .. } => path,
+            PackageResolution::Dir { path, .. } | PackageResolution::Link { path, .. } => path,
             _ => panic!("There shouldn't be anything but Dirs here"),
         };
         self.corgi_metadata_from_path(path).await
     }
 
     async fn packument(&self, spec: &PackageSpec, base_dir: &Path) -> Result<Arc<Packument>> {
-        let path = match spec {
-            PackageSpec::Dir { path } => base_dir.join(path),
+        let path = match spec.target() {
+            PackageSpec::Dir { path } | PackageSpec::Link { path } => base_dir.join(path),
             _ => panic!("There shouldn't be anything but Dirs here"),
         };
         self.packument_from_path(&path).await
@@ -137,17 +137,60 @@ impl PackageFetcher for DirFetcher {
         spec: &PackageSpec,
         base_dir: &Path,
     ) -> Result<Arc<CorgiPackument>> {
-        let path = match spec {
-            PackageSpec::Dir { path } => base_dir.join(path),
+        let path = match spec.target() {
+            PackageSpec::Dir { path } | PackageSpec::Link { path } => base_dir.join(path),
             _ => panic!("There shouldn't be anything but Dirs here"),
         };
         self.corgi_packument_from_path(&path).await
     }
 
-    async fn tarball(&self, _pkg: &Package) -> Result<Box<dyn AsyncRead + Unpin + Send + Sync>> {
-        // TODO: need to implement pack before this can be implemented :(
-        unimplemented!()
+    async fn tarball(&self, pkg: &Package) -> Result<Box<dyn AsyncRead + Unpin + Send + Sync>> {
+        let path = match pkg.resolved() {
+            PackageResolution::Dir { path, .. } | PackageResolution::Link { path, .. } => {
+                path.clone()
+            }
+            _ => panic!("There shouldn't be anything but Dirs here"),
+        };
+        let data = async_std::task::spawn_blocking(move || {
+            pack_dir(&path).map_err(|e| NassunError::DirPackError(e, path))
+        })
+        .await?;
+        Ok(Box::new(futures::io::Cursor::new(data)))
+    }
+}
+
+/// Packs a directory into a gzipped tarball, with all its contents placed
+/// under `package/`, the same way they would be in a registry tarball.
+/// `.git/` and `node_modules/` directories are skipped.
+pub(crate) fn pack_dir(dir: &Path) -> std::io::Result<Vec<u8>> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    append_dir_contents(&mut builder, dir, Path::new("package"))?;
+    builder.into_inner()?.finish()
+}
+
+fn append_dir_contents<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    prefix: &Path,
+) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    // Sorted so packing the same directory twice gives the same tarball.
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        if name == ".git" || name == "node_modules" {
+            continue;
+        }
+        let path = entry.path();
+        let archive_path = prefix.join(&name);
+        if entry.file_type()?.is_dir() {
+            append_dir_contents(builder, &path, &archive_path)?;
+        } else {
+            builder.append_path_with_name(&path, &archive_path)?;
+        }
     }
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -204,6 +247,7 @@ impl Manifest {
         let metadata = self.into_corgi_metadata(path)?;
         let mut packument = CorgiPackument {
             versions: HashMap::new(),
+            time: HashMap::new(),
             tags: HashMap::new(),
         };
         let version = metadata
@@ -242,7 +286,7 @@ mod test {
 
     use tempfile::{tempdir, TempDir};
 
-    fn setup_dirs() -> Result<(impl PackageFetcher, PackageSpec, TempDir, PathBuf, PathBuf)> {
+    fn setup_dirs() -> Result<(impl PackageSource, PackageSpec, TempDir, PathBuf, PathBuf)> {
         let tmp = tempdir()?;
         let package_path = tmp.path().join("oro-test");
         let cache_path = tmp.path().join("cache");
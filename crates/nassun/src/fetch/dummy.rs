@@ -1,6 +1,6 @@
 use std::{collections::HashMap, path::Path};
 
-use crate::{fetch::PackageFetcher, package::Package};
+use crate::{fetch::PackageSource, package::Package};
 
 use async_std::sync::Arc;
 use async_trait::async_trait;
@@ -15,7 +15,7 @@ pub(crate) struct DummyFetcher(pub(crate) CorgiManifest);
 
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
-impl PackageFetcher for DummyFetcher {
+impl PackageSource for DummyFetcher {
     async fn name(&self, _spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
         Ok(self
             .0
@@ -47,6 +47,7 @@ impl PackageFetcher for DummyFetcher {
         let corgi_meta: CorgiVersionMetadata = self.0.clone().into();
         let mut packument = CorgiPackument {
             versions: HashMap::new(),
+            time: HashMap::new(),
             tags: HashMap::new(),
         };
         let version = corgi_meta
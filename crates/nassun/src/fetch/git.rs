@@ -6,30 +6,45 @@ use async_trait::async_trait;
 use node_semver::{Range, Version};
 use once_cell::sync::OnceCell;
 use oro_client::{self, OroClient};
-use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
+use oro_common::{BuildManifest, CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
 use oro_package_spec::{GitInfo, PackageSpec};
 use url::Url;
 
 use crate::error::{NassunError, Result};
-use crate::fetch::dir::DirFetcher;
-use crate::fetch::PackageFetcher;
+use crate::fetch::dir::{pack_dir, DirFetcher};
+use crate::fetch::PackageSource;
 use crate::package::Package;
 use crate::resolver::PackageResolution;
 use crate::tarball::Tarball;
 
+/// Builds git dependencies from source.
+///
+/// Git dependencies are often not usable as-is: they might need to be
+/// compiled (for example, from TypeScript) before they can be installed.
+/// When a git dependency has a `prepare` script, Nassun will hand its cloned
+/// directory to a `GitPreparer`, which is expected to install the package's
+/// dependencies (including its `devDependencies`) and execute its `prepare`
+/// script, before the result gets packed into a tarball.
+#[async_trait]
+pub trait GitPreparer: std::fmt::Debug + Send + Sync {
+    async fn prepare(&self, package_dir: &Path) -> Result<()>;
+}
+
 #[derive(Debug)]
 pub(crate) struct GitFetcher {
     client: OroClient,
     dir_fetcher: DirFetcher,
     git: OnceCell<PathBuf>,
+    preparer: Option<Arc<dyn GitPreparer>>,
 }
 
 impl GitFetcher {
-    pub(crate) fn new(client: OroClient) -> Self {
+    pub(crate) fn new(client: OroClient, preparer: Option<Arc<dyn GitPreparer>>) -> Self {
         Self {
             client,
             dir_fetcher: DirFetcher::new(),
             git: OnceCell::new(),
+            preparer,
         }
     }
 
@@ -62,21 +77,30 @@ impl GitFetcher {
                     if let Some(requested) = requested {
                         self.fetch_clone(dir, requested, committish, semver, info)
                             .await?;
-                    } else if let (Some(tarball), Some(https), Some(ssh)) =
-                        (hosted.tarball(), hosted.https(), hosted.ssh())
-                    {
-                        match self.fetch_tarball(dir, &tarball).await {
-                            Ok(_) => {}
-                            Err(_) => {
-                                match self
-                                    .fetch_clone(dir, https.to_string(), committish, semver, info)
-                                    .await
-                                {
-                                    Ok(_) => {}
-                                    Err(_) => {
-                                        self.fetch_clone(dir, ssh, committish, semver, info)
-                                            .await?;
-                                    }
+                    } else if let (Some(https), Some(ssh)) = (hosted.https(), hosted.ssh()) {
+                        let fetched_tarball = if let Some(tarball) = hosted.tarball() {
+                            let res = self.fetch_tarball(dir, &tarball).await;
+                            if res.is_err() {
+                                // Don't let a partial extraction get in the
+                                // way of cloning.
+                                let _ = async_std::fs::remove_dir_all(dir.join("package")).await;
+                            }
+                            res.is_ok()
+                        } else {
+                            false
+                        };
+                        if !fetched_tarball {
+                            match self
+                                .fetch_clone(dir, https.to_string(), committish, semver, info)
+                                .await
+                            {
+                                Ok(_) => {}
+                                Err(_) => {
+                                    // A failed clone might have left some
+                                    // files behind.
+                                    let _ =
+                                        async_std::fs::remove_dir_all(dir.join("package")).await;
+                                    self.fetch_clone(dir, ssh, committish, semver, info).await?;
                                 }
                             }
                         }
@@ -90,14 +114,52 @@ impl GitFetcher {
         Ok(())
     }
 
+    /// Directory the package itself lives in, once `info` has been fetched
+    /// into `dir`. This is the root of the repository, unless a `path:` was
+    /// specified.
+    fn package_dir(&self, info: &GitInfo, dir: &Path) -> Result<PathBuf> {
+        let mut package_dir = dir.join("package");
+        if let Some(subdir) = info.path() {
+            let subdir_path = Path::new(subdir);
+            if !subdir_path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                return Err(NassunError::GitPathError(info.to_string(), subdir.into()));
+            }
+            package_dir.push(subdir_path);
+            if !package_dir.join("package.json").is_file() {
+                return Err(NassunError::GitPathError(info.to_string(), subdir.into()));
+            }
+        }
+        Ok(package_dir)
+    }
+
     async fn fetch_tarball(&self, dir: &Path, tarball: &Url) -> Result<()> {
         let tarball = self.client.stream_external(tarball).await?;
         Tarball::new_unchecked(tarball)
-            .extract_from_tarball_data(dir, None, false)
+            .extract_from_tarball_data(&dir.join("package"), None, false)
             .await?;
         Ok(())
     }
 
+    async fn prepare(&self, dir: &Path) -> Result<()> {
+        let manifest = BuildManifest::from_path(dir.join("package.json"))?;
+        if !manifest.scripts.contains_key("prepare") {
+            return Ok(());
+        }
+        if let Some(preparer) = &self.preparer {
+            tracing::debug!("Building git dependency at {} from source.", dir.display());
+            preparer.prepare(dir).await?;
+        } else {
+            tracing::warn!(
+                "Git dependency at {} has a `prepare` script, but no preparer was configured. It will be installed without being built.",
+                dir.display()
+            );
+        }
+        Ok(())
+    }
+
     async fn fetch_clone(
         &self,
         dir: &Path,
@@ -138,28 +200,44 @@ impl GitFetcher {
                 .output()
                 .await
                 .map_err(NassunError::GitIoError)?;
-            let versions: Vec<Version> = String::from_utf8(refs_output.stdout)
+            // Monorepos usually tag releases as `<name>@<version>`, so
+            // when we know which package we're looking for, those tags are
+            // considered too.
+            let package_name = if info.path().is_some() {
+                let package_dir = self.package_dir(info, dir)?;
+                self.dir_fetcher.name_from_path(&package_dir).await.ok()
+            } else {
+                None
+            };
+            let tags: Vec<(String, Version)> = String::from_utf8(refs_output.stdout)
                 .map_err(|e| {
                     NassunError::MiscError(format!("Could not decode git output as UTF-8. {}", e))
                 })?
                 .lines()
                 .filter_map(|line| {
-                    line.split('/')
-                        .last()
-                        .and_then(|tag| Version::parse(tag).ok())
+                    let tag = line.split_once("refs/tags/")?.1;
+                    let version = match tag.rsplit_once('@') {
+                        Some((name, version)) if Some(name) == package_name.as_deref() => version,
+                        Some(_) => return None,
+                        None => tag,
+                    };
+                    let version = version.strip_prefix('v').unwrap_or(version);
+                    Version::parse(version)
+                        .ok()
+                        .map(|version| (tag.to_string(), version))
                 })
                 .collect();
             Some(
-                versions
-                    .iter()
-                    .filter(|v| range.satisfies(v))
-                    .max()
+                tags.iter()
+                    .filter(|(_, v)| range.satisfies(v))
+                    .max_by(|(_, a), (_, b)| a.cmp(b))
                     .ok_or_else(|| NassunError::NoVersion {
                         name: repo.to_string(),
                         spec: PackageSpec::Git(info.clone()),
-                        versions: versions.iter().map(|v| v.to_string()).collect(),
+                        versions: tags.iter().map(|(_, v)| v.to_string()).collect(),
                     })?
-                    .to_string(),
+                    .0
+                    .clone(),
             )
         } else {
             committish.clone()
@@ -192,7 +270,7 @@ impl GitFetcher {
 
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
-impl PackageFetcher for GitFetcher {
+impl PackageSource for GitFetcher {
     async fn name(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
         use PackageSpec::*;
         let info = match spec {
@@ -203,7 +281,7 @@ impl PackageFetcher for GitFetcher {
         let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
         self.fetch_to_temp_dir(info, dir.path()).await?;
         self.dir_fetcher
-            .name_from_path(&dir.path().join("package"))
+            .name_from_path(&self.package_dir(info, dir.path())?)
             .await
     }
 
@@ -216,7 +294,7 @@ impl PackageFetcher for GitFetcher {
         let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
         self.fetch_to_temp_dir(info, dir.path()).await?;
         self.dir_fetcher
-            .corgi_metadata_from_path(&dir.path().join("package"))
+            .corgi_metadata_from_path(&self.package_dir(info, dir.path())?)
             .await
     }
 
@@ -229,7 +307,7 @@ impl PackageFetcher for GitFetcher {
         let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
         self.fetch_to_temp_dir(info, dir.path()).await?;
         self.dir_fetcher
-            .metadata_from_path(&dir.path().join("package"))
+            .metadata_from_path(&self.package_dir(info, dir.path())?)
             .await
     }
 
@@ -246,7 +324,7 @@ impl PackageFetcher for GitFetcher {
         let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
         self.fetch_to_temp_dir(info, dir.path()).await?;
         self.dir_fetcher
-            .corgi_packument_from_path(&dir.path().join("package"))
+            .corgi_packument_from_path(&self.package_dir(info, dir.path())?)
             .await
     }
 
@@ -263,26 +341,58 @@ impl PackageFetcher for GitFetcher {
         let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
         self.fetch_to_temp_dir(info, dir.path()).await?;
         self.dir_fetcher
-            .packument_from_path(&dir.path().join("package"))
+            .packument_from_path(&self.package_dir(info, dir.path())?)
             .await
     }
 
-    async fn tarball(&self, _pkg: &crate::Package) -> Result<crate::TarballStream> {
-        todo!()
+    async fn tarball(&self, pkg: &crate::Package) -> Result<crate::TarballStream> {
+        use PackageResolution::*;
+        let info = match pkg.resolved() {
+            Git { info, .. } => info,
+            _ => panic!("Only git specs allowed."),
+        };
+        let dir = tempfile::tempdir().map_err(NassunError::GitIoError)?;
+        self.fetch_to_temp_dir(info, dir.path()).await?;
+        let package_dir = self.package_dir(info, dir.path())?;
+        self.prepare(&package_dir).await?;
+        let data = async_std::task::spawn_blocking(move || {
+            let data = pack_dir(&package_dir);
+            // Keep the tempdir around until packing is done.
+            drop(dir);
+            data
+        })
+        .await
+        .map_err(NassunError::GitPackError)?;
+        Ok(Box::new(futures::io::Cursor::new(data)))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::{fs::File, io::Write, process};
+    use std::{fs::File, io::Write, path::Path, process};
 
+    use async_std::sync::Arc;
+    use async_trait::async_trait;
     use oro_client::OroClient;
     use oro_package_spec::{GitInfo, PackageSpec};
     use tempfile::tempdir;
 
-    use crate::fetch::PackageFetcher;
+    use crate::fetch::PackageSource;
+    use crate::package::Package;
+    use crate::resolver::PackageResolution;
 
-    use super::GitFetcher;
+    use super::{GitFetcher, GitPreparer};
+
+    #[derive(Debug)]
+    struct TestPreparer;
+
+    #[async_trait]
+    impl GitPreparer for TestPreparer {
+        async fn prepare(&self, package_dir: &Path) -> crate::error::Result<()> {
+            std::fs::write(package_dir.join("built.js"), "module.exports = 'built'")?;
+            Ok(())
+        }
+    }
 
     fn setup_git_dir() -> miette::Result<tempfile::TempDir> {
         let git_dir = tempdir().unwrap();
@@ -383,13 +493,14 @@ mod test {
     #[async_std::test]
     async fn read_name() -> miette::Result<()> {
         let git_dir = setup_git_dir()?;
-        let fetcher = GitFetcher::new(OroClient::default());
+        let fetcher = GitFetcher::new(OroClient::default(), None);
         let spec = PackageSpec::Git(GitInfo::Url {
             url: format!("file://{}", git_dir.path().to_str().unwrap())
                 .parse()
                 .unwrap(),
             committish: None,
             semver: None,
+            path: None,
         });
         let cache_path = tempdir().unwrap();
         let name = fetcher.name(&spec, cache_path.path()).await?;
@@ -400,7 +511,7 @@ mod test {
     #[async_std::test]
     async fn read_packument() -> miette::Result<()> {
         let git_dir = setup_git_dir()?;
-        let fetcher = GitFetcher::new(OroClient::default());
+        let fetcher = GitFetcher::new(OroClient::default(), None);
         let tmp = tempdir().unwrap();
         // get last commit
         let packument = fetcher
@@ -411,6 +522,7 @@ mod test {
                         .unwrap(),
                     committish: None,
                     semver: None,
+                    path: None,
                 }),
                 tmp.path(),
             )
@@ -434,6 +546,7 @@ mod test {
                         .unwrap(),
                     committish: Some("1.0.0".to_string()),
                     semver: None,
+                    path: None,
                 }),
                 tmp.path(),
             )
@@ -457,6 +570,7 @@ mod test {
                         .unwrap(),
                     committish: None,
                     semver: Some(">1.0.0 <1.5.0".parse()?),
+                    path: None,
                 }),
                 tmp.path(),
             )
@@ -473,4 +587,64 @@ mod test {
         );
         Ok(())
     }
+
+    #[async_std::test]
+    async fn tarball_runs_prepare() -> miette::Result<()> {
+        let git_dir = setup_git_dir()?;
+
+        let mut package_file = File::create(git_dir.path().join("package.json")).unwrap();
+        package_file
+            .write_all(
+                r#"{
+            "name": "oro-test",
+            "version": "2.0.0",
+            "scripts": {
+                "prepare": "tsc"
+            }
+        }"#
+                .as_bytes(),
+            )
+            .unwrap();
+        drop(package_file);
+        process::Command::new("git")
+            .args(["commit", "-a", "-m", "Add prepare script", "--no-gpg-sign"])
+            .current_dir(&git_dir)
+            .status()
+            .expect("Could not commit prepare script");
+
+        let info = GitInfo::Url {
+            url: format!("file://{}", git_dir.path().to_str().unwrap())
+                .parse()
+                .unwrap(),
+            committish: None,
+            semver: None,
+            path: None,
+        };
+        let tmp = tempdir().unwrap();
+        let pkg = Package {
+            from: PackageSpec::Git(info.clone()),
+            name: "oro-test".into(),
+            resolved: PackageResolution::Git {
+                name: "oro-test".into(),
+                info,
+            },
+            fetcher: Arc::new(GitFetcher::new(
+                OroClient::default(),
+                Some(Arc::new(TestPreparer)),
+            )),
+            base_dir: tmp.path().into(),
+            cache: Arc::new(None),
+            mmap: false,
+            counters: Default::default(),
+        };
+        let dest = tmp.path().join("oro-test");
+        pkg.extract_to_dir(&dest, false, false).await?;
+        assert!(dest.join("package.json").exists());
+        assert!(
+            dest.join("built.js").exists(),
+            "prepare output was packed into the tarball"
+        );
+        assert!(!dest.join(".git").exists(), ".git directory was skipped");
+        Ok(())
+    }
 }
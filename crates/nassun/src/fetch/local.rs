@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use oro_client::OroClientError;
+use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
+use oro_package_spec::PackageSpec;
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::error::{NassunError, Result};
+use crate::fetch::PackageSource;
+use crate::package::Package;
+use crate::resolver::PackageResolution;
+
+/// Serves registry packages out of a local directory, for offline installs
+/// and test fixtures. This is what `file:` registry URLs use.
+///
+/// The directory is laid out like verdaccio's storage: each package gets a
+/// directory named after it (so `@scope/pkg` lives in `@scope/pkg/`), with
+/// its packument in `package.json` next to its tarballs. Tarballs are found
+/// by the file name at the end of their `dist.tarball` URL, so packuments
+/// copied from a real registry work as-is.
+#[derive(Debug)]
+pub(crate) struct LocalRegistryFetcher {
+    root: PathBuf,
+    packuments: DashMap<String, Arc<Packument>>,
+    corgi_packuments: DashMap<String, Arc<CorgiPackument>>,
+}
+
+impl LocalRegistryFetcher {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            packuments: DashMap::new(),
+            corgi_packuments: DashMap::new(),
+        }
+    }
+
+    fn _name<'a>(&self, spec: &'a PackageSpec) -> &'a str {
+        match spec.target() {
+            PackageSpec::Npm { ref name, .. } => name,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads the packument for `name`. These don't change during an
+    /// install, so each one only gets read once.
+    async fn read_packument<T: DeserializeOwned>(
+        &self,
+        cache: &DashMap<String, Arc<T>>,
+        name: &str,
+    ) -> Result<Arc<T>> {
+        if let Some(packument) = cache.get(name) {
+            return Ok(packument.value().clone());
+        }
+        let path = self.root.join(name).join("package.json");
+        let data = match async_std::fs::read(&path).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(OroClientError::PackageNotFound(self.url(), name.into()).into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let packument: Arc<T> = Arc::new(serde_json::from_slice(&data)?);
+        cache.insert(name.into(), packument.clone());
+        Ok(packument)
+    }
+
+    fn url(&self) -> Url {
+        Url::from_directory_path(&self.root)
+            .unwrap_or_else(|_| "file:///".parse().expect("valid URL"))
+    }
+}
+
+#[async_trait]
+impl PackageSource for LocalRegistryFetcher {
+    async fn name(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
+        Ok(self._name(spec).to_string())
+    }
+
+    async fn metadata(&self, pkg: &Package) -> Result<VersionMetadata> {
+        let wanted = match pkg.resolved() {
+            PackageResolution::Npm { ref version, .. } => version,
+            _ => unreachable!(),
+        };
+        let packument = self.packument(pkg.from(), Path::new("")).await?;
+        packument
+            .versions
+            .get(wanted)
+            .cloned()
+            .ok_or_else(|| NassunError::MissingVersion(pkg.from().clone(), wanted.clone()))
+    }
+
+    async fn packument(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<Arc<Packument>> {
+        self.read_packument(&self.packuments, self._name(spec))
+            .await
+    }
+
+    async fn corgi_metadata(&self, pkg: &Package) -> Result<CorgiVersionMetadata> {
+        let wanted = match pkg.resolved() {
+            PackageResolution::Npm { ref version, .. } => version,
+            _ => unreachable!(),
+        };
+        let packument = self.corgi_packument(pkg.from(), Path::new("")).await?;
+        packument
+            .versions
+            .get(wanted)
+            .cloned()
+            .ok_or_else(|| NassunError::MissingVersion(pkg.from().clone(), wanted.clone()))
+    }
+
+    async fn corgi_packument(
+        &self,
+        spec: &PackageSpec,
+        _base_dir: &Path,
+    ) -> Result<Arc<CorgiPackument>> {
+        self.read_packument(&self.corgi_packuments, self._name(spec))
+            .await
+    }
+
+    async fn tarball(&self, pkg: &Package) -> Result<crate::TarballStream> {
+        let url = match pkg.resolved() {
+            PackageResolution::Npm { ref tarball, .. } => tarball,
+            _ => unreachable!(),
+        };
+        let file_name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default();
+        let path = self.root.join(self._name(pkg.from())).join(file_name);
+        let file = async_std::fs::File::open(&path).await?;
+        Ok(Box::new(file))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::AsyncReadExt;
+    use tempfile::tempdir;
+
+    use crate::client::NassunOpts;
+
+    #[async_std::test]
+    async fn serves_packages_from_directory() -> miette::Result<()> {
+        let dir = tempdir().unwrap();
+        let pkg_dir = dir.path().join("@scope").join("foo");
+        std::fs::create_dir_all(&pkg_dir).unwrap();
+        std::fs::write(
+            pkg_dir.join("package.json"),
+            serde_json::json!({
+                "name": "@scope/foo",
+                "dist-tags": { "latest": "1.0.0" },
+                "versions": {
+                    "1.0.0": {
+                        "name": "@scope/foo",
+                        "version": "1.0.0",
+                        "dist": {
+                            "tarball": "https://registry.npmjs.org/@scope/foo/-/foo-1.0.0.tgz"
+                        }
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(pkg_dir.join("foo-1.0.0.tgz"), b"tarball").unwrap();
+
+        let nassun = NassunOpts::new()
+            .registry(url::Url::from_directory_path(dir.path()).unwrap())
+            .build();
+        let pkg = nassun.resolve("@scope/foo@^1").await?;
+        assert_eq!(
+            pkg.corgi_metadata().await?.manifest.version,
+            Some("1.0.0".parse().unwrap())
+        );
+        let mut data = Vec::new();
+        pkg.tarball_unchecked()
+            .await?
+            .into_inner()
+            .read_to_end(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, b"tarball");
+
+        assert!(nassun.resolve("missing").await.is_err());
+        Ok(())
+    }
+}
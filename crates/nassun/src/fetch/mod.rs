@@ -1,18 +1,26 @@
 use std::path::Path;
 
+use ::url::Url;
 use async_std::sync::Arc;
 use async_trait::async_trait;
+use oro_client::attestations::Attestations;
 use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
 use oro_package_spec::PackageSpec;
 
 use crate::error::Result;
 use crate::package::Package;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::url::UrlFetcher;
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) use dir::DirFetcher;
 pub(crate) use dummy::DummyFetcher;
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) use git::GitFetcher;
+#[cfg(not(target_arch = "wasm32"))]
+pub use git::GitPreparer;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use local::LocalRegistryFetcher;
 pub(crate) use npm::NpmFetcher;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -20,19 +28,149 @@ mod dir;
 mod dummy;
 #[cfg(not(target_arch = "wasm32"))]
 mod git;
+#[cfg(not(target_arch = "wasm32"))]
+mod local;
 mod npm;
+#[cfg(not(target_arch = "wasm32"))]
+mod url;
 
+/// A backend that packages can be resolved and fetched from.
+///
+/// Nassun comes with sources for registries, git repositories, directories,
+/// and tarball URLs. Other sources, like internal artifact systems, can be
+/// plugged in with
+/// [`NassunOpts::scope_source`](crate::client::NassunOpts::scope_source), and
+/// are then used for every registry package in that scope. Sources are
+/// implemented with [`async_trait`].
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
-pub trait PackageFetcher: std::fmt::Debug + Send + Sync {
+pub trait PackageSource: std::fmt::Debug + Send + Sync {
+    /// Name of the package `spec` refers to.
     async fn name(&self, spec: &PackageSpec, base_dir: &Path) -> Result<String>;
+    /// Full metadata for the version `pkg` was resolved to.
     async fn metadata(&self, pkg: &Package) -> Result<VersionMetadata>;
+    /// Full packument for `pkg`, listing all its versions.
     async fn packument(&self, pkg: &PackageSpec, base_dir: &Path) -> Result<Arc<Packument>>;
+    /// Like [`PackageSource::metadata`], but with only the fields needed
+    /// for installing.
     async fn corgi_metadata(&self, pkg: &Package) -> Result<CorgiVersionMetadata>;
+    /// Like [`PackageSource::packument`], but with only the fields needed
+    /// for resolving versions. The resolver uses this one.
     async fn corgi_packument(
         &self,
         pkg: &PackageSpec,
         base_dir: &Path,
     ) -> Result<Arc<CorgiPackument>>;
+    /// The packument to resolve `spec` against. Sources can leave out
+    /// versions `spec` can't match, to save memory on packages with lots
+    /// of them. Defaults to [`PackageSource::corgi_packument`].
+    async fn resolution_packument(
+        &self,
+        spec: &PackageSpec,
+        base_dir: &Path,
+    ) -> Result<Arc<CorgiPackument>> {
+        self.corgi_packument(spec, base_dir).await
+    }
+    /// The raw tarball for the version `pkg` was resolved to. Nassun checks
+    /// it against the resolved integrity, if there is one.
     async fn tarball(&self, pkg: &Package) -> Result<crate::TarballStream>;
+    /// The raw tarball for `pkg` again, after the one from
+    /// [`PackageSource::tarball`] failed its integrity check. Sources with
+    /// more than one place to get it from, like registry mirrors, can try
+    /// another one. Defaults to [`PackageSource::tarball`].
+    async fn refetch_tarball(&self, pkg: &Package) -> Result<crate::TarballStream> {
+        self.tarball(pkg).await
+    }
+    /// Attestations published at `url`, for sources that can get them.
+    async fn attestations(&self, _url: &Url) -> Result<Option<Attestations>> {
+        Ok(None)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use futures::AsyncReadExt;
+
+    use super::*;
+    use crate::client::NassunOpts;
+    use crate::resolver::PackageResolution;
+
+    /// Serves a single package out of memory.
+    #[derive(Debug)]
+    struct MemorySource(CorgiPackument);
+
+    #[async_trait]
+    impl PackageSource for MemorySource {
+        async fn name(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
+            match spec.target() {
+                PackageSpec::Npm { name, .. } => Ok(name.clone()),
+                _ => unreachable!("scope sources only get registry specs"),
+            }
+        }
+
+        async fn metadata(&self, pkg: &Package) -> Result<VersionMetadata> {
+            Ok(self.corgi_metadata(pkg).await?.into())
+        }
+
+        async fn packument(&self, pkg: &PackageSpec, base_dir: &Path) -> Result<Arc<Packument>> {
+            let packument = self.corgi_packument(pkg, base_dir).await?;
+            Ok(Arc::new((*packument).clone().into()))
+        }
+
+        async fn corgi_metadata(&self, pkg: &Package) -> Result<CorgiVersionMetadata> {
+            let PackageResolution::Npm { version, .. } = pkg.resolved() else {
+                unreachable!("registry packages resolve to registry versions");
+            };
+            Ok(self.0.versions[version].clone())
+        }
+
+        async fn corgi_packument(
+            &self,
+            _pkg: &PackageSpec,
+            _base_dir: &Path,
+        ) -> Result<Arc<CorgiPackument>> {
+            Ok(Arc::new(self.0.clone()))
+        }
+
+        async fn tarball(&self, _pkg: &Package) -> Result<crate::TarballStream> {
+            Ok(Box::new(futures::io::Cursor::new(b"tarball".to_vec())))
+        }
+    }
+
+    #[async_std::test]
+    async fn uses_scope_sources() -> miette::Result<()> {
+        let packument: CorgiPackument = serde_json::from_value(serde_json::json!({
+            "dist-tags": { "latest": "1.1.0" },
+            "versions": {
+                "1.0.0": {
+                    "name": "@internal/foo",
+                    "version": "1.0.0",
+                    "dist": { "tarball": "artifacts://foo/1.0.0" }
+                },
+                "1.1.0": {
+                    "name": "@internal/foo",
+                    "version": "1.1.0",
+                    "dist": { "tarball": "artifacts://foo/1.1.0" }
+                }
+            }
+        }))
+        .unwrap();
+        let nassun = NassunOpts::new()
+            .scope_source("@internal", Arc::new(MemorySource(packument)))
+            .build();
+
+        let pkg = nassun.resolve("@internal/foo@^1").await?;
+        assert_eq!(pkg.name(), "@internal/foo");
+        let manifest = pkg.corgi_metadata().await?.manifest;
+        assert_eq!(manifest.version, Some("1.1.0".parse().unwrap()));
+        let mut data = Vec::new();
+        pkg.tarball_unchecked()
+            .await?
+            .into_inner()
+            .read_to_end(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, b"tarball");
+        Ok(())
+    }
 }
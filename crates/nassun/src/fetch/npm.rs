@@ -1,40 +1,67 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::Path;
 
-use async_std::sync::Arc;
+use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use dashmap::DashMap;
-use oro_client::{self, OroClient};
+use node_semver::{Range, Version};
+use oro_client::{self, attestations::Attestations, keys::RegistryKey, OroClient, OroClientError};
 use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
-use oro_package_spec::PackageSpec;
+use oro_package_spec::{PackageSpec, VersionSpec};
 use url::Url;
 
 use crate::error::{NassunError, Result};
-use crate::fetch::PackageFetcher;
+use crate::fetch::PackageSource;
 use crate::package::Package;
 use crate::resolver::PackageResolution;
+use crate::rewrite::TarballRewrite;
+use crate::signatures::{self, SignatureVerification};
 
 #[derive(Debug)]
 pub(crate) struct NpmFetcher {
     client: OroClient,
     registries: HashMap<Option<String>, Url>,
+    /// Mirrors of the default registry, to fall back to in order when it's
+    /// failing.
+    mirrors: Vec<Url>,
+    /// Rules for rewriting `dist.tarball` URLs before they're fetched.
+    tarball_rewrites: Vec<TarballRewrite>,
     cache_packuments: bool,
+    /// Whether resolving needs publish times, which registries usually
+    /// leave out of Corgi packuments.
+    publish_times: bool,
     packuments: DashMap<String, Arc<Packument>>,
     corgi_packuments: DashMap<String, Arc<CorgiPackument>>,
+    verify_signatures: SignatureVerification,
+    /// Signing keys for each registry.
+    keys: Mutex<HashMap<Url, RegistryKeys>>,
 }
 
+/// A registry's signing keys, or why they couldn't be fetched.
+type RegistryKeys = std::result::Result<Arc<Vec<RegistryKey>>, String>;
+
 impl NpmFetcher {
     pub(crate) fn new(
         client: OroClient,
         registries: HashMap<Option<String>, Url>,
+        mirrors: Vec<Url>,
+        tarball_rewrites: Vec<TarballRewrite>,
         cache_packuments: bool,
+        publish_times: bool,
+        verify_signatures: SignatureVerification,
     ) -> Self {
         Self {
             client,
             registries,
+            mirrors,
+            tarball_rewrites,
             packuments: DashMap::new(),
             corgi_packuments: DashMap::new(),
             cache_packuments,
+            publish_times,
+            verify_signatures,
+            keys: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -47,6 +74,162 @@ impl NpmFetcher {
             .cloned()
             .unwrap_or_else(|| "https://registry.npmjs.org/".parse().unwrap())
     }
+
+    /// Registries to try for packages from `scope`, in order. Mirrors only
+    /// stand in for the default registry, not for scoped ones.
+    fn pick_registries(&self, scope: &Option<String>) -> Vec<Url> {
+        let registry = self.pick_registry(scope);
+        let mut registries = vec![registry.clone()];
+        if registry == self.pick_registry(&None) {
+            registries.extend(self.mirrors.iter().cloned());
+        }
+        registries
+    }
+
+    /// Where to fetch the tarball at `url` from, in order: `url` itself,
+    /// then the same path on each mirror, if it's a tarball on the default
+    /// registry.
+    fn tarball_urls(&self, url: &Url) -> Vec<Url> {
+        let registry = self.pick_registry(&None);
+        let registry = registry.as_str().trim_end_matches('/');
+        let mut urls = vec![url.clone()];
+        if let Some(path) = url
+            .as_str()
+            .strip_prefix(registry)
+            .filter(|path| path.starts_with('/'))
+        {
+            urls.extend(self.mirrors.iter().filter_map(|mirror| {
+                format!("{}{path}", mirror.as_str().trim_end_matches('/'))
+                    .parse()
+                    .ok()
+            }));
+        }
+        urls
+    }
+
+    /// Where to fetch the tarball at `url` from again, after the first copy
+    /// failed its integrity check. Mirrors go first, since whatever served
+    /// the bad copy might well serve it again.
+    fn refetch_tarball_urls(&self, url: &Url) -> Vec<Url> {
+        let mut urls = self.tarball_urls(url);
+        urls.rotate_left(1);
+        urls
+    }
+
+    /// Runs `fetch` against each of `sources` in turn, until one doesn't
+    /// fail with a server error or a timeout.
+    async fn fetch_with_fallback<T, F, Fut>(
+        &self,
+        what: &str,
+        sources: Vec<Url>,
+        fetch: F,
+    ) -> Result<T>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = std::result::Result<T, OroClientError>>,
+    {
+        let mut sources = sources.into_iter();
+        let mut source = sources.next().expect("there's always at least one source");
+        let mut fell_back = false;
+        loop {
+            match fetch(source.clone()).await {
+                Ok(fetched) => {
+                    if fell_back {
+                        tracing::debug!("{what} was served by mirror {source}.");
+                    }
+                    return Ok(fetched);
+                }
+                Err(err) if err.is_transient() => match sources.next() {
+                    Some(next) => {
+                        tracing::debug!(
+                            "Failed to fetch {what} from {source} ({err}), falling back to {next}."
+                        );
+                        source = next;
+                        fell_back = true;
+                    }
+                    None => return Err(err.into()),
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl NpmFetcher {
+    /// Signing keys for `registry`, fetched once and then reused. Registries
+    /// that don't publish any are remembered too, so they're only asked
+    /// once.
+    async fn registry_keys(&self, registry: Url) -> Result<RegistryKeys> {
+        let mut keys = self.keys.lock().await;
+        if let Some(keys) = keys.get(&registry) {
+            return Ok(keys.clone());
+        }
+        let fetched = match self.client.with_registry(registry.clone()).keys().await? {
+            Some(fetched) => Ok(Arc::new(fetched.keys)),
+            None => Err(format!("{registry} doesn't publish signing keys")),
+        };
+        keys.insert(registry, fetched.clone());
+        Ok(fetched)
+    }
+
+    /// Checks the registry's signature over `pkg`'s integrity, before its
+    /// tarball gets fetched.
+    async fn verify_signatures(&self, pkg: &Package) -> Result<()> {
+        let PackageResolution::Npm {
+            version, integrity, ..
+        } = pkg.resolved()
+        else {
+            unreachable!("How did a non-Npm resolution get here?");
+        };
+        let (name, scope) = match pkg.from().target() {
+            PackageSpec::Npm { name, scope, .. } => (name, scope),
+            _ => unreachable!(),
+        };
+        let metadata = self.corgi_metadata(pkg).await?;
+        let signed = metadata.dist.integrity.as_deref().unwrap_or_default();
+        let keys = self.registry_keys(self.pick_registry(scope)).await?;
+        let mut signatures = metadata.dist.signatures.clone();
+        if keys.is_ok() && signatures.is_none() {
+            // Some registries leave signatures out of Corgi packuments, so
+            // check the full one before deciding there aren't any.
+            tracing::debug!(
+                "Corgi metadata for {name}@{version} has no signatures, checking the full packument."
+            );
+            signatures = self.metadata(pkg).await?.dist.signatures;
+        }
+        let result = match keys {
+            Err(reason) => Err(reason),
+            Ok(_) if signed.is_empty() => Err("the registry has no integrity for it".into()),
+            Ok(keys) => signatures::verify(
+                name,
+                &version.to_string(),
+                signed,
+                signatures.as_deref().unwrap_or_default(),
+                &keys,
+            )
+            .and_then(|()| match (integrity, signed.parse::<ssri::Integrity>()) {
+                (Some(integrity), Ok(signed)) if integrity.matches(&signed).is_none() => {
+                    Err("its integrity doesn't match the one the registry signed".into())
+                }
+                _ => Ok(()),
+            }),
+        };
+        match (result, self.verify_signatures) {
+            (Ok(()), _) | (_, SignatureVerification::Off) => Ok(()),
+            (Err(reason), SignatureVerification::Warn) => {
+                tracing::warn!(
+                    "Registry signature for {name}@{version} couldn't be verified: {reason}."
+                );
+                Ok(())
+            }
+            (Err(reason), SignatureVerification::Enforce) => {
+                Err(NassunError::SignatureVerificationFailed {
+                    package: format!("{name}@{version}"),
+                    reason,
+                })
+            }
+        }
+    }
 }
 
 impl NpmFetcher {
@@ -60,7 +243,7 @@ impl NpmFetcher {
 
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
-impl PackageFetcher for NpmFetcher {
+impl PackageSource for NpmFetcher {
     async fn name(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
         Ok(self._name(spec).to_string())
     }
@@ -94,7 +277,7 @@ impl PackageFetcher for NpmFetcher {
     async fn corgi_packument(
         &self,
         spec: &PackageSpec,
-        _base_dir: &Path,
+        base_dir: &Path,
     ) -> Result<Arc<CorgiPackument>> {
         // When fetching the packument itself, we need the _package_ name, not
         // its alias! Hence these shenanigans.
@@ -109,8 +292,25 @@ impl PackageFetcher for NpmFetcher {
                     return Ok(packument.value().clone());
                 }
             }
-            let client = self.client.with_registry(self.pick_registry(scope));
-            let packument = Arc::new(client.corgi_packument(&name).await?);
+            let mut packument = self
+                .fetch_with_fallback(
+                    &format!("packument for {name}"),
+                    self.pick_registries(scope),
+                    |registry| async move {
+                        self.client
+                            .with_registry(registry)
+                            .corgi_packument(name)
+                            .await
+                    },
+                )
+                .await?;
+            if self.publish_times && packument.time.is_empty() {
+                tracing::debug!(
+                    "Corgi packument for {name} has no publish times, fetching the full packument."
+                );
+                packument = (*self.packument(spec, base_dir).await?).clone().into();
+            }
+            let packument = Arc::new(packument);
             if self.cache_packuments {
                 self.corgi_packuments
                     .insert(name.clone(), packument.clone());
@@ -121,6 +321,61 @@ impl PackageFetcher for NpmFetcher {
         }
     }
 
+    async fn resolution_packument(
+        &self,
+        spec: &PackageSpec,
+        base_dir: &Path,
+    ) -> Result<Arc<CorgiPackument>> {
+        // Memoized packuments get reused for other specs, so they need all
+        // their versions. Ones that need publish times usually end up
+        // coming from the full packument anyway.
+        if self.cache_packuments || self.publish_times {
+            return self.corgi_packument(spec, base_dir).await;
+        }
+        let PackageSpec::Npm {
+            ref name,
+            ref scope,
+            requested: Some(ref requested),
+        } = spec.target()
+        else {
+            return self.corgi_packument(spec, base_dir).await;
+        };
+        let keep: Box<dyn Fn(&Version) -> bool + Send + Sync> = match requested {
+            VersionSpec::Version(version) => {
+                let version = version.clone();
+                Box::new(move |v| v == &version)
+            }
+            // Catch-all ranges can resolve to a default tag that doesn't
+            // satisfy them, and wouldn't save anything anyway.
+            VersionSpec::Range(range)
+                if range != &Range::any() && range != &Range::parse("*").unwrap() =>
+            {
+                let range = range.clone();
+                Box::new(move |v| range.satisfies(v))
+            }
+            _ => return self.corgi_packument(spec, base_dir).await,
+        };
+        let keep = &keep;
+        let packument = self
+            .fetch_with_fallback(
+                &format!("packument for {name}"),
+                self.pick_registries(scope),
+                |registry| async move {
+                    self.client
+                        .with_registry(registry)
+                        .corgi_packument_matching(name, keep)
+                        .await
+                },
+            )
+            .await?;
+        if packument.versions.is_empty() {
+            // Nothing matched, so get the rest of the versions for the
+            // error to list.
+            return self.corgi_packument(spec, base_dir).await;
+        }
+        Ok(Arc::new(packument))
+    }
+
     async fn packument(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<Arc<Packument>> {
         // When fetching the packument itself, we need the _package_ name, not
         // its alias! Hence these shenanigans.
@@ -140,8 +395,16 @@ impl PackageFetcher for NpmFetcher {
                     return Ok(packument.value().clone());
                 }
             }
-            let client = self.client.with_registry(self.pick_registry(scope));
-            let packument = Arc::new(client.packument(&name).await?);
+            let packument = self
+                .fetch_with_fallback(
+                    &format!("packument for {name}"),
+                    self.pick_registries(scope),
+                    |registry| async move {
+                        self.client.with_registry(registry).packument(name).await
+                    },
+                )
+                .await?;
+            let packument = Arc::new(packument);
             if self.cache_packuments {
                 self.packuments.insert(name.clone(), packument.clone());
             }
@@ -152,11 +415,53 @@ impl PackageFetcher for NpmFetcher {
     }
 
     async fn tarball(&self, pkg: &Package) -> Result<crate::TarballStream> {
+        self.fetch_tarball(pkg, false).await
+    }
+
+    async fn refetch_tarball(&self, pkg: &Package) -> Result<crate::TarballStream> {
+        self.fetch_tarball(pkg, true).await
+    }
+
+    async fn attestations(&self, url: &Url) -> Result<Option<Attestations>> {
+        Ok(Some(self.client.attestations(url).await?))
+    }
+}
+
+impl NpmFetcher {
+    /// Fetches `pkg`'s tarball, from a mirror first if this is another try
+    /// after a bad one.
+    async fn fetch_tarball(&self, pkg: &Package, refetch: bool) -> Result<crate::TarballStream> {
         let url = match pkg.resolved() {
             PackageResolution::Npm { ref tarball, .. } => tarball,
             _ => panic!("How did a non-Npm resolution get here?"),
         };
-        Ok(self.client.stream_external(url).await?)
+        if self.verify_signatures != SignatureVerification::Off {
+            self.verify_signatures(pkg).await?;
+        }
+        let url = match TarballRewrite::apply_all(&self.tarball_rewrites, url) {
+            Some(rewritten) => {
+                tracing::debug!("Rewrote tarball URL {url} to {rewritten}.");
+                rewritten
+            }
+            None => url.clone(),
+        };
+        let scope = match pkg.from().target() {
+            PackageSpec::Npm { scope, .. } => scope,
+            _ => unreachable!(),
+        };
+        let client = self.client.with_registry(self.pick_registry(scope));
+        let client = &client;
+        let urls = if refetch {
+            self.refetch_tarball_urls(&url)
+        } else {
+            self.tarball_urls(&url)
+        };
+        self.fetch_with_fallback(
+            &format!("tarball for {}", pkg.from()),
+            urls,
+            |url| async move { client.stream_tarball(&url).await },
+        )
+        .await
     }
 }
 
@@ -169,7 +474,15 @@ mod test {
 
     #[async_std::test]
     async fn read_name() -> miette::Result<()> {
-        let fetcher = NpmFetcher::new(oro_client::OroClient::default(), HashMap::default(), false);
+        let fetcher = NpmFetcher::new(
+            oro_client::OroClient::default(),
+            HashMap::default(),
+            Vec::new(),
+            Vec::new(),
+            false,
+            false,
+            SignatureVerification::Off,
+        );
         let spec = PackageSpec::Npm {
             scope: None,
             name: "npm".to_string(),
@@ -260,7 +573,15 @@ mod test {
         let mut registries = HashMap::new();
         registries.insert(None, Url::parse(mock_server.url().as_ref()).unwrap());
 
-        let fetcher = NpmFetcher::new(oro_client::OroClient::default(), registries, false);
+        let fetcher = NpmFetcher::new(
+            oro_client::OroClient::default(),
+            registries,
+            Vec::new(),
+            Vec::new(),
+            false,
+            false,
+            SignatureVerification::Off,
+        );
         let spec = PackageSpec::Npm {
             scope: None,
             name: "oro-test-example".to_string(),
@@ -292,4 +613,208 @@ mod test {
         );
         Ok(())
     }
+
+    #[async_std::test]
+    async fn falls_back_to_mirrors() -> miette::Result<()> {
+        let mut registry = mockito::Server::new();
+        let mut mirror = mockito::Server::new();
+        registry
+            .mock("GET", "/oro-test-example")
+            .with_status(503)
+            .create_async()
+            .await;
+        mirror
+            .mock("GET", "/oro-test-example")
+            .with_body(r#"{"name": "oro-test-example", "versions": {}}"#)
+            .create_async()
+            .await;
+
+        let mut registries = HashMap::new();
+        registries.insert(None, Url::parse(&registry.url()).unwrap());
+        let fetcher = NpmFetcher::new(
+            OroClient::builder()
+                .retry_policy(oro_client::RetryPolicy::none())
+                .build(),
+            registries,
+            vec![Url::parse(&mirror.url()).unwrap()],
+            Vec::new(),
+            false,
+            false,
+            SignatureVerification::Off,
+        );
+        let spec = PackageSpec::Npm {
+            scope: None,
+            name: "oro-test-example".to_string(),
+            requested: None,
+        };
+        let cache_path = tempdir().unwrap();
+        let packument = fetcher.corgi_packument(&spec, cache_path.path()).await?;
+        assert!(packument.versions.is_empty());
+
+        assert_eq!(
+            fetcher.tarball_urls(
+                &format!(
+                    "{}/oro-test-example/-/oro-test-example-1.0.0.tgz",
+                    registry.url()
+                )
+                .parse()
+                .unwrap()
+            ),
+            vec![
+                format!(
+                    "{}/oro-test-example/-/oro-test-example-1.0.0.tgz",
+                    registry.url()
+                )
+                .parse()
+                .unwrap(),
+                format!(
+                    "{}/oro-test-example/-/oro-test-example-1.0.0.tgz",
+                    mirror.url()
+                )
+                .parse::<Url>()
+                .unwrap(),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn refetches_tarballs_from_mirrors_first() {
+        let registry = Url::parse("https://registry.example.com/").unwrap();
+        let mirror = Url::parse("https://mirror.example.com/").unwrap();
+        let mut registries = HashMap::new();
+        registries.insert(None, registry);
+        let fetcher = NpmFetcher::new(
+            OroClient::default(),
+            registries,
+            vec![mirror],
+            Vec::new(),
+            false,
+            false,
+            SignatureVerification::Off,
+        );
+        let tarball = "https://registry.example.com/foo/-/foo-1.0.0.tgz"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            fetcher.refetch_tarball_urls(&tarball),
+            vec![
+                "https://mirror.example.com/foo/-/foo-1.0.0.tgz"
+                    .parse::<Url>()
+                    .unwrap(),
+                tarball.clone(),
+            ]
+        );
+
+        // Without a mirror, it's the same place as before.
+        let elsewhere = "https://cdn.example.com/foo-1.0.0.tgz".parse().unwrap();
+        assert_eq!(fetcher.refetch_tarball_urls(&elsewhere), vec![elsewhere]);
+    }
+
+    #[async_std::test]
+    async fn resolves_against_matching_versions() -> miette::Result<()> {
+        let mut registry = mockito::Server::new();
+        registry
+            .mock("GET", "/oro-test-example")
+            .with_body(
+                r#"{
+                    "name": "oro-test-example",
+                    "dist-tags": { "latest": "2.0.0" },
+                    "versions": {
+                        "1.0.0": { "name": "oro-test-example", "version": "1.0.0" },
+                        "1.1.0": { "name": "oro-test-example", "version": "1.1.0" },
+                        "2.0.0": { "name": "oro-test-example", "version": "2.0.0" }
+                    }
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let mut registries = HashMap::new();
+        registries.insert(None, Url::parse(&registry.url()).unwrap());
+        let fetcher = NpmFetcher::new(
+            OroClient::default(),
+            registries,
+            Vec::new(),
+            Vec::new(),
+            false,
+            false,
+            SignatureVerification::Off,
+        );
+        let cache_path = tempdir().unwrap();
+        let packument = fetcher
+            .resolution_packument(&"oro-test-example@^1".parse()?, cache_path.path())
+            .await?;
+        assert_eq!(packument.versions.len(), 2);
+        assert!(packument.versions.contains_key(&"1.1.0".parse()?));
+
+        // When nothing matches, all the versions are there to report.
+        let packument = fetcher
+            .resolution_packument(&"oro-test-example@^3".parse()?, cache_path.path())
+            .await?;
+        assert_eq!(packument.versions.len(), 3);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn falls_back_to_full_packuments_for_publish_times() -> miette::Result<()> {
+        let mut registry = mockito::Server::new();
+        let corgi = registry
+            .mock("GET", "/oro-test-example")
+            .match_header(
+                "accept",
+                mockito::Matcher::Regex("vnd.npm.install-v1".into()),
+            )
+            .with_body(
+                r#"{
+                    "name": "oro-test-example",
+                    "dist-tags": { "latest": "1.0.0" },
+                    "versions": {
+                        "1.0.0": { "name": "oro-test-example", "version": "1.0.0" }
+                    }
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let full = registry
+            .mock("GET", "/oro-test-example")
+            .match_header("accept", "application/json")
+            .with_body(
+                r#"{
+                    "name": "oro-test-example",
+                    "dist-tags": { "latest": "1.0.0" },
+                    "versions": {
+                        "1.0.0": { "name": "oro-test-example", "version": "1.0.0" }
+                    },
+                    "time": { "1.0.0": "2020-01-01T00:00:00.000Z" }
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut registries = HashMap::new();
+        registries.insert(None, Url::parse(&registry.url()).unwrap());
+        let fetcher = NpmFetcher::new(
+            OroClient::default(),
+            registries,
+            Vec::new(),
+            Vec::new(),
+            true,
+            true,
+            SignatureVerification::Off,
+        );
+        let spec = "oro-test-example@^1".parse()?;
+        let cache_path = tempdir().unwrap();
+        let packument = fetcher.corgi_packument(&spec, cache_path.path()).await?;
+        assert_eq!(packument.time["1.0.0"], "2020-01-01T00:00:00.000Z");
+
+        // Both packuments get memoized.
+        fetcher.corgi_packument(&spec, cache_path.path()).await?;
+        fetcher.packument(&spec, cache_path.path()).await?;
+        corgi.assert_async().await;
+        full.assert_async().await;
+        Ok(())
+    }
 }
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use oro_client::OroClient;
+use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
+use oro_package_spec::PackageSpec;
+use ssri::Integrity;
+use tempfile::TempDir;
+use url::Url;
+
+use crate::error::{NassunError, Result};
+use crate::fetch::dir::DirFetcher;
+use crate::fetch::PackageSource;
+use crate::package::Package;
+use crate::resolver::PackageResolution;
+use crate::tarball::Tarball;
+
+/// Fetches packages from plain tarball URLs, such as
+/// `https://example.com/pkg.tgz`.
+#[derive(Debug)]
+pub(crate) struct UrlFetcher {
+    client: OroClient,
+    dir_fetcher: DirFetcher,
+    /// Tarballs that have already been downloaded, so resolving and
+    /// extracting a package only downloads it once.
+    downloads: Mutex<HashMap<Url, Arc<Download>>>,
+}
+
+/// A downloaded tarball, along with its extracted contents.
+#[derive(Debug)]
+struct Download {
+    dir: TempDir,
+    integrity: Integrity,
+}
+
+impl Download {
+    /// The tarball, as it was downloaded.
+    fn tarball(&self) -> PathBuf {
+        self.dir.path().join("package.tgz")
+    }
+
+    /// Where the tarball was extracted to.
+    fn package(&self) -> PathBuf {
+        self.dir.path().join("package")
+    }
+}
+
+impl UrlFetcher {
+    pub(crate) fn new(client: OroClient) -> Self {
+        Self {
+            client,
+            dir_fetcher: DirFetcher::new(),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The tarball at `url`, downloaded and extracted the first time it's
+    /// asked for, then reused. If an integrity was already pinned, the data
+    /// is checked against it, and a download that doesn't match is
+    /// forgotten, so trying again downloads it again.
+    async fn download(&self, url: &Url, integrity: Option<&Integrity>) -> Result<Arc<Download>> {
+        let mut downloads = self.downloads.lock().await;
+        let download = match downloads.get(url) {
+            Some(download) => download.clone(),
+            None => {
+                let download = Arc::new(self.fetch_to_temp_dir(url).await?);
+                downloads.insert(url.clone(), download.clone());
+                download
+            }
+        };
+        if let Some(expected) = integrity {
+            // The pinned integrity might not use the same algorithm as the
+            // one computed while extracting, so the data itself is checked.
+            let data = async_std::fs::read(download.tarball()).await?;
+            if let Err(ssri::Error::IntegrityCheckError(expected, found)) = expected.check(data) {
+                downloads.remove(url);
+                return Err(NassunError::IntegrityMismatch {
+                    expected: Box::new(expected),
+                    found: Box::new(found),
+                });
+            }
+        }
+        Ok(download)
+    }
+
+    /// Downloads the tarball at `url` into a temporary directory, and
+    /// extracts it next to it.
+    async fn fetch_to_temp_dir(&self, url: &Url) -> Result<Download> {
+        let dir = tempfile::tempdir()?;
+        let tarball_path = dir.path().join("package.tgz");
+        let tarball = self.client.stream_external(url).await?;
+        let mut file = async_std::fs::File::create(&tarball_path).await?;
+        futures::io::copy(tarball, &mut file).await?;
+        drop(file);
+        let tarball = async_std::fs::File::open(&tarball_path).await?;
+        let integrity = Tarball::new_unchecked(Box::new(tarball))
+            .extract_from_tarball_data(&dir.path().join("package"), None, false)
+            .await?;
+        Ok(Download { dir, integrity })
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl PackageSource for UrlFetcher {
+    async fn name(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<String> {
+        use PackageSpec::*;
+        let url = match spec {
+            Alias { name, .. } => return Ok(name.clone()),
+            Url { url } => url,
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, None).await?;
+        self.dir_fetcher.name_from_path(&download.package()).await
+    }
+
+    async fn corgi_metadata(&self, pkg: &Package) -> Result<CorgiVersionMetadata> {
+        use PackageResolution::*;
+        let (url, integrity) = match pkg.resolved() {
+            Url {
+                tarball, integrity, ..
+            } => (tarball, integrity),
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, integrity.as_ref()).await?;
+        let mut metadata = self
+            .dir_fetcher
+            .corgi_metadata_from_path(&download.package())
+            .await?;
+        metadata.dist.tarball = Some(url.clone());
+        metadata.dist.integrity = Some(download.integrity.to_string());
+        Ok(metadata)
+    }
+
+    async fn metadata(&self, pkg: &Package) -> Result<VersionMetadata> {
+        use PackageResolution::*;
+        let (url, integrity) = match pkg.resolved() {
+            Url {
+                tarball, integrity, ..
+            } => (tarball, integrity),
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, integrity.as_ref()).await?;
+        let mut metadata = self
+            .dir_fetcher
+            .metadata_from_path(&download.package())
+            .await?;
+        metadata.dist.tarball = Some(url.clone());
+        metadata.dist.integrity = Some(download.integrity.to_string());
+        Ok(metadata)
+    }
+
+    async fn corgi_packument(
+        &self,
+        spec: &PackageSpec,
+        _base_dir: &Path,
+    ) -> Result<Arc<CorgiPackument>> {
+        use PackageSpec::*;
+        let url = match spec.target() {
+            Url { url } => url,
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, None).await?;
+        let mut packument = self
+            .dir_fetcher
+            .corgi_packument_from_path(&download.package())
+            .await?;
+        for metadata in Arc::make_mut(&mut packument).versions.values_mut() {
+            metadata.dist.tarball = Some(url.clone());
+            metadata.dist.integrity = Some(download.integrity.to_string());
+        }
+        Ok(packument)
+    }
+
+    async fn packument(&self, spec: &PackageSpec, _base_dir: &Path) -> Result<Arc<Packument>> {
+        use PackageSpec::*;
+        let url = match spec.target() {
+            Url { url } => url,
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, None).await?;
+        let mut packument = self
+            .dir_fetcher
+            .packument_from_path(&download.package())
+            .await?;
+        for metadata in Arc::make_mut(&mut packument).versions.values_mut() {
+            metadata.dist.tarball = Some(url.clone());
+            metadata.dist.integrity = Some(download.integrity.to_string());
+        }
+        Ok(packument)
+    }
+
+    async fn tarball(&self, pkg: &Package) -> Result<crate::TarballStream> {
+        use PackageResolution::*;
+        let (url, integrity) = match pkg.resolved() {
+            Url {
+                tarball, integrity, ..
+            } => (tarball, integrity),
+            _ => panic!("Only url specs allowed."),
+        };
+        let download = self.download(url, integrity.as_ref()).await?;
+        Ok(Box::new(
+            async_std::fs::File::open(download.tarball()).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::AsyncReadExt;
+
+    use crate::client::NassunOpts;
+
+    fn tarball() -> Vec<u8> {
+        let manifest = serde_json::json!({ "name": "foo", "version": "1.0.0" }).to_string();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        builder
+            .append_data(&mut header, "package/package.json", manifest.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[async_std::test]
+    async fn downloads_once() -> miette::Result<()> {
+        let data = tarball();
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/foo.tgz")
+            .with_body(&data)
+            .expect(1)
+            .create();
+
+        let nassun = NassunOpts::new().build();
+        let pkg = nassun.resolve(format!("{}/foo.tgz", server.url())).await?;
+        assert_eq!(pkg.name(), "foo");
+        let metadata = pkg.corgi_metadata().await?;
+        assert_eq!(metadata.manifest.version, Some("1.0.0".parse().unwrap()));
+        pkg.packument().await?;
+        let mut downloaded = Vec::new();
+        pkg.tarball()
+            .await?
+            .read_to_end(&mut downloaded)
+            .await
+            .unwrap();
+        assert_eq!(downloaded, data);
+
+        mock.assert();
+        Ok(())
+    }
+}
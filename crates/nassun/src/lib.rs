@@ -4,6 +4,8 @@
 use futures::AsyncRead;
 pub use oro_package_spec::{GitHost, GitInfo, PackageSpec, VersionSpec};
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cache;
 pub mod client;
 pub mod entries;
 #[cfg(not(target_arch = "wasm32"))]
@@ -11,8 +13,15 @@ mod error;
 #[cfg(target_arch = "wasm32")]
 pub mod error;
 pub mod fetch;
+#[cfg(not(target_arch = "wasm32"))]
+mod lock;
+#[cfg(not(target_arch = "wasm32"))]
+mod mmap;
 pub mod package;
+pub mod provenance;
 pub mod resolver;
+pub mod rewrite;
+pub mod signatures;
 pub mod tarball;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
@@ -23,15 +32,21 @@ pub use client::*;
 pub use entries::*;
 #[cfg(not(target_arch = "wasm32"))]
 pub use error::NassunError;
+pub use fetch::PackageSource;
 #[cfg(not(target_arch = "wasm32"))]
 pub use package::*;
+pub use provenance::Provenance;
 pub use resolver::*;
+pub use rewrite::TarballRewrite;
+pub use signatures::SignatureVerification;
 #[cfg(not(target_arch = "wasm32"))]
 pub use tarball::*;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
 
+/// Raw tarball data, as returned by a [`PackageSource`].
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) type TarballStream = Box<dyn AsyncRead + Unpin + Send + Sync>;
+pub type TarballStream = Box<dyn AsyncRead + Unpin + Send + Sync>;
+/// Raw tarball data, as returned by a [`PackageSource`].
 #[cfg(target_arch = "wasm32")]
-pub(crate) type TarballStream = Box<dyn AsyncRead + Unpin>;
+pub type TarballStream = Box<dyn AsyncRead + Unpin>;
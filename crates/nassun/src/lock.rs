@@ -0,0 +1,204 @@
+//! Advisory locks for sharing one cache between several processes, like
+//! parallel installs in a monorepo, or CI jobs running side by side on the
+//! same runner.
+//!
+//! Content and index entries are already written atomically, so readers
+//! never see half-written data. What these locks add is that only one
+//! process at a time fetches and indexes any given package: everyone else
+//! waits, then picks up the finished entry instead of downloading it again.
+//!
+//! Locks are plain files under `<cache>/locks`, created with
+//! [`OpenOptions::create_new`](std::fs::OpenOptions::create_new), which is
+//! atomic on every platform and filesystem we care about. If a process dies
+//! while holding a lock, its file is left behind, so locks that haven't
+//! been touched in [`STALE_AFTER`] are taken over. Holders touch their lock
+//! regularly for as long as they have it, so slow ones aren't mistaken for
+//! dead ones.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use futures::future::{AbortHandle, Abortable};
+use ssri::{Algorithm, IntegrityOpts};
+
+use crate::error::{NassunError, Result};
+
+/// How long a lock can go untouched before it's assumed its holder died.
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// How many times a holder touches its lock within [`STALE_AFTER`], so a
+/// slow filesystem or a busy executor doesn't make it look abandoned.
+const HEARTBEATS: u32 = 4;
+
+/// Shortest and longest waits between attempts to take a busy lock.
+const MIN_WAIT: Duration = Duration::from_millis(10);
+const MAX_WAIT: Duration = Duration::from_millis(500);
+
+/// An advisory lock on a single key in a cache. It's released when
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct CacheLock {
+    path: PathBuf,
+    heartbeat: AbortHandle,
+}
+
+impl CacheLock {
+    /// Takes the lock for `key` in `cache`, waiting for whoever else has it
+    /// to finish.
+    pub(crate) async fn acquire(cache: &Path, key: &str) -> Result<Self> {
+        Self::acquire_with_staleness(cache, key, STALE_AFTER).await
+    }
+
+    async fn acquire_with_staleness(
+        cache: &Path,
+        key: &str,
+        stale_after: Duration,
+    ) -> Result<Self> {
+        let dir = cache.join("locks");
+        let path = dir.join(
+            IntegrityOpts::new()
+                .algorithm(Algorithm::Sha256)
+                .chain(key)
+                .result()
+                .to_hex()
+                .1,
+        );
+        async_std::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| NassunError::CacheLockError(e, dir.clone()))?;
+        let mut wait = MIN_WAIT;
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    // Only for debugging stuck locks, so failing to write
+                    // it doesn't matter.
+                    let _ = write!(file, "{}", std::process::id());
+                    let (heartbeat, registration) = AbortHandle::new_pair();
+                    async_std::task::spawn(Abortable::new(
+                        heartbeat_loop(path.clone(), stale_after / HEARTBEATS),
+                        registration,
+                    ));
+                    return Ok(Self { path, heartbeat });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path, stale_after) {
+                        tracing::debug!("Taking over stale cache lock at {}.", path.display());
+                        // If someone else takes it over at the same time,
+                        // we might both end up holding it. Since writes to
+                        // the cache are atomic anyway, the worst that does
+                        // is fetch a package twice.
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    async_std::task::sleep(wait).await;
+                    wait = (wait * 2).min(MAX_WAIT);
+                }
+                Err(e) => return Err(NassunError::CacheLockError(e, path)),
+            }
+        }
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        self.heartbeat.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Touches the lock at `path` every `interval`, for as long as it's held.
+async fn heartbeat_loop(path: PathBuf, interval: Duration) {
+    loop {
+        async_std::task::sleep(interval).await;
+        // Rewriting the PID updates the lock's modification time. The lock
+        // isn't created again if someone took it over anyway.
+        let touched = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .and_then(|mut file| write!(file, "{}", std::process::id()));
+        if let Err(e) = touched {
+            tracing::debug!("Failed to touch cache lock at {}: {e}", path.display());
+        }
+    }
+}
+
+fn is_stale(path: &Path, stale_after: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age > stale_after)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn excludes_other_holders() -> Result<()> {
+        let cache = tempfile::tempdir()?;
+        let holders = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..8).map(|_| {
+            let cache = cache.path().to_path_buf();
+            let holders = holders.clone();
+            async_std::task::spawn(async move {
+                let _lock = CacheLock::acquire(&cache, "nassun::package::foo").await?;
+                assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                async_std::task::sleep(Duration::from_millis(5)).await;
+                holders.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, NassunError>(())
+            })
+        });
+        futures::future::try_join_all(tasks).await?;
+        assert_eq!(std::fs::read_dir(cache.path().join("locks"))?.count(), 0);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn keeps_held_locks_fresh() -> Result<()> {
+        let cache = tempfile::tempdir()?;
+        let stale_after = Duration::from_millis(100);
+        let lock =
+            CacheLock::acquire_with_staleness(cache.path(), "nassun::package::foo", stale_after)
+                .await?;
+        // Held for longer than it takes to go stale.
+        async_std::task::sleep(stale_after * 3).await;
+        let waiting = async_std::future::timeout(
+            stale_after * 2,
+            CacheLock::acquire_with_staleness(cache.path(), "nassun::package::foo", stale_after),
+        )
+        .await;
+        assert!(waiting.is_err(), "took over a lock that was still held");
+
+        drop(lock);
+        CacheLock::acquire_with_staleness(cache.path(), "nassun::package::foo", stale_after)
+            .await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn takes_over_stale_locks() -> Result<()> {
+        let cache = tempfile::tempdir()?;
+        let lock = CacheLock::acquire(cache.path(), "nassun::package::foo").await?;
+        // The abandoned lock is never released, just like it wouldn't be
+        // if its process had died.
+        std::mem::forget(lock);
+        async_std::task::sleep(Duration::from_millis(20)).await;
+        CacheLock::acquire_with_staleness(
+            cache.path(),
+            "nassun::package::foo",
+            Duration::from_millis(10),
+        )
+        .await?;
+        Ok(())
+    }
+}
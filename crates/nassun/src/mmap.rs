@@ -0,0 +1,81 @@
+//! Memory-mapped reads, for big files that would otherwise get read into
+//! memory in full, or copied through a buffer a bit at a time. Only used
+//! when [`crate::NassunOpts::mmap`] is on: mapping has a setup cost of its
+//! own, and some network filesystems don't handle it well.
+
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+/// Files smaller than this are read instead, since mapping them costs more
+/// than it saves.
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// The contents of a file, either mapped or read into memory.
+pub(crate) enum FileData {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(map) => map,
+            FileData::Read(data) => data,
+        }
+    }
+}
+
+/// Reads all of `path`, mapping it instead when `mmap` is on and the file
+/// is big enough to be worth it.
+pub(crate) fn read(path: &Path, mmap: bool) -> std::io::Result<FileData> {
+    if mmap {
+        let file = File::open(path)?;
+        if file.metadata()?.len() >= MMAP_THRESHOLD {
+            if let Some(map) = map(&file) {
+                return Ok(FileData::Mapped(map));
+            }
+        }
+    }
+    std::fs::read(path).map(FileData::Read)
+}
+
+/// Maps `file` into memory, or returns `None` if it can't be, in which case
+/// it should be read the usual way.
+pub(crate) fn map(file: &File) -> Option<Mmap> {
+    // SAFETY: Mapped files must not be truncated while they're mapped.
+    // Only files nothing else writes to get mapped: our own temp files,
+    // and package files, which are never modified in place.
+    match unsafe { Mmap::map(file) } {
+        Ok(map) => Some(map),
+        Err(e) => {
+            tracing::debug!("Failed to map file into memory, so it'll be read instead: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn maps_big_files() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let small = dir.path().join("small");
+        let big = dir.path().join("big");
+        std::fs::write(&small, b"small")?;
+        std::fs::write(&big, vec![b'x'; MMAP_THRESHOLD as usize])?;
+
+        assert!(matches!(read(&small, true)?, FileData::Read(_)));
+        assert!(matches!(read(&big, false)?, FileData::Read(_)));
+        let mapped = read(&big, true)?;
+        assert!(matches!(mapped, FileData::Mapped(_)));
+        assert_eq!(&mapped[..], &std::fs::read(&big)?[..]);
+        Ok(())
+    }
+}
@@ -4,16 +4,22 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use async_std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use cacache::WriteOpts;
 use oro_common::{CorgiPackument, CorgiVersionMetadata, Packument, VersionMetadata};
 use oro_package_spec::PackageSpec;
 use ssri::Integrity;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cache::CacheCounters;
 use crate::entries::Entries;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::error::NassunError;
 use crate::error::Result;
-use crate::fetch::PackageFetcher;
+use crate::fetch::PackageSource;
 use crate::resolver::PackageResolution;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::tarball::ExtractProgress;
 use crate::tarball::Tarball;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::tarball::TarballIndex;
@@ -25,10 +31,14 @@ pub struct Package {
     pub(crate) from: PackageSpec,
     pub(crate) name: String,
     pub(crate) resolved: PackageResolution,
-    pub(crate) fetcher: Arc<dyn PackageFetcher>,
+    pub(crate) fetcher: Arc<dyn PackageSource>,
     pub(crate) base_dir: PathBuf,
     #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
     pub(crate) cache: Arc<Option<PathBuf>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) mmap: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) counters: Arc<CacheCounters>,
 }
 
 impl Package {
@@ -47,6 +57,23 @@ impl Package {
         &self.resolved
     }
 
+    /// Whether this package satisfies `spec`. Like
+    /// [`PackageResolution::satisfies`], except that relative `file:` and
+    /// `link:` paths are taken relative to this package's base directory,
+    /// which is what they were resolved against.
+    pub fn satisfies(&self, spec: &PackageSpec) -> Result<bool> {
+        let absolute = match spec.target() {
+            PackageSpec::Dir { path } if path.is_relative() => PackageSpec::Dir {
+                path: self.base_dir.join(path),
+            },
+            PackageSpec::Link { path } if path.is_relative() => PackageSpec::Link {
+                path: self.base_dir.join(path),
+            },
+            _ => return self.resolved.satisfies(spec),
+        };
+        self.resolved.satisfies(&absolute)
+    }
+
     /// The full [`Packument`] that this `Package` was resolved from.
     pub async fn packument(&self) -> Result<Arc<Packument>> {
         self.fetcher.packument(&self.from, &self.base_dir).await
@@ -140,12 +167,34 @@ impl Package {
             prefer_copy: bool,
             validate: bool,
         ) -> Result<Integrity> {
-            me.extract_to_dir_inner(dir, me.resolved.integrity(), prefer_copy, validate)
+            me.extract_to_dir_inner(dir, me.resolved.integrity(), prefer_copy, validate, None)
                 .await
         }
         inner(self, dir.as_ref(), prefer_copy, validate).await
     }
 
+    /// Extract tarball to a directory, like [`Package::extract_to_dir`], while
+    /// reporting how many bytes have been processed to `on_progress`. Those
+    /// are bytes of the (compressed) tarball when it gets downloaded, and
+    /// bytes of the extracted files when they come out of the cache.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn extract_to_dir_with_progress(
+        &self,
+        dir: impl AsRef<Path>,
+        prefer_copy: bool,
+        validate: bool,
+        on_progress: ExtractProgress,
+    ) -> Result<Integrity> {
+        self.extract_to_dir_inner(
+            dir.as_ref(),
+            self.resolved.integrity(),
+            prefer_copy,
+            validate,
+            Some(on_progress),
+        )
+        .await
+    }
+
     /// Extract tarball to a directory, optionally caching its contents. The
     /// tarball stream will NOT have its integrity validated. See
     /// [`Package::tarball_unchecked`] for more information.
@@ -162,7 +211,7 @@ impl Package {
             prefer_copy: bool,
             validate: bool,
         ) -> Result<Integrity> {
-            me.extract_to_dir_inner(dir, None, prefer_copy, validate)
+            me.extract_to_dir_inner(dir, None, prefer_copy, validate, None)
                 .await
         }
         inner(self, dir.as_ref(), prefer_copy, validate).await
@@ -186,12 +235,163 @@ impl Package {
             prefer_copy: bool,
             validate: bool,
         ) -> Result<Integrity> {
-            me.extract_to_dir_inner(dir, Some(&sri), prefer_copy, validate)
+            me.extract_to_dir_inner(dir, Some(&sri), prefer_copy, validate, None)
                 .await
         }
         inner(self, dir.as_ref(), sri, prefer_copy, validate).await
     }
 
+    /// Checks the files extracted into `dir` against the integrity hashes
+    /// the cache recorded for each of this package's files, and returns the
+    /// ones that are missing or don't match. Files that aren't part of the
+    /// package are ignored. Returns `None` if there's nothing to check
+    /// against, because the package's integrity isn't known, or it isn't in
+    /// the cache.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn verify_dir(&self, dir: impl AsRef<Path>) -> Result<Option<Vec<PathBuf>>> {
+        let (Some(sri), Some(cache)) = (self.resolved.integrity(), self.cache.as_deref()) else {
+            return Ok(None);
+        };
+        let Some(entry) = cacache::index::find(cache, &crate::tarball::tarball_key(sri))
+            .map_err(|e| NassunError::ExtractCacheError(e, None))?
+        else {
+            return Ok(None);
+        };
+        let dir = dir.as_ref().to_path_buf();
+        let name = self.name().to_owned();
+        let mmap = self.mmap;
+        async_std::task::spawn_blocking(move || {
+            let index = unsafe {
+                rkyv::util::archived_root::<TarballIndex>(
+                    entry
+                        .raw_metadata
+                        .as_ref()
+                        .ok_or_else(|| NassunError::CacheMissingIndexError(name))?,
+                )
+            };
+            let mut damaged = Vec::new();
+            for (path, (sri, _)) in index.files.iter() {
+                let sri: Integrity = sri.parse()?;
+                let path = dir.join(&path[..]);
+                match crate::mmap::read(&path, mmap) {
+                    Ok(data) if sri.check(&*data).is_ok() => {}
+                    _ => damaged.push(path),
+                }
+            }
+            damaged.sort();
+            Ok::<_, NassunError>(Some(damaged))
+        })
+        .await
+    }
+
+    /// Downloads the tarball and extracts it to `dir`, once it's been
+    /// checked against `sri`. Integrity failures are usually corrupted
+    /// downloads, so those get one more try, from a mirror if there is one.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn extract_from_network(
+        &self,
+        dir: &Path,
+        sri: &Integrity,
+        prefer_copy: bool,
+        on_progress: Option<ExtractProgress>,
+    ) -> Result<Integrity> {
+        self.counters.package_miss();
+        let on_progress = &on_progress;
+        let extract = |refetch: bool| async move {
+            let data = if refetch {
+                self.fetcher.refetch_tarball(self).await?
+            } else {
+                self.fetcher.tarball(self).await?
+            };
+            Tarball::new(data, sri.clone())
+                .with_progress(on_progress.clone())
+                .with_mmap(self.mmap)
+                .extract_from_tarball_data(dir, self.cache.as_deref(), prefer_copy)
+                .await
+        };
+        let first = match extract(false).await {
+            Err(NassunError::IntegrityMismatch { found, .. }) => found,
+            result => return result,
+        };
+        tracing::warn!(
+            "Tarball for {} failed its integrity check. Expected `{sri}`, but got `{first}`. Downloading it again.",
+            self.resolved()
+        );
+        match extract(true).await {
+            Err(NassunError::IntegrityMismatch { expected, found }) => {
+                Err(NassunError::IntegrityMismatchAfterRetry {
+                    package: self.resolved().to_string(),
+                    help: if first == found {
+                        "Both downloads had the same contents, so the tarball itself doesn't match. If the package's integrity in the lockfile is out of date, update it. Otherwise, the tarball may have been tampered with.".into()
+                    } else {
+                        "The downloads had different contents, so they're probably getting corrupted along the way. Check your network connection and any proxies in between.".into()
+                    },
+                    expected,
+                    first,
+                    found,
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Makes sure this package is in the cache, downloading it if it isn't,
+    /// without extracting it anywhere. Returns whether it had to be
+    /// downloaded. Does nothing if there's no cache.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn prefetch(&self) -> Result<bool> {
+        let Some(cache) = self.cache.as_deref() else {
+            return Ok(false);
+        };
+        let sri = match self.resolved.integrity() {
+            Some(sri) => Some(sri.clone()),
+            None => self.remembered_integrity()?,
+        };
+        if let Some(sri) = &sri {
+            if cacache::index::find(cache, &crate::tarball::tarball_key(sri))
+                .map_err(|e| NassunError::ExtractCacheError(e, None))?
+                .is_some()
+            {
+                self.counters.package_hit();
+                return Ok(false);
+            }
+        }
+        // Extracting is what puts a package's files in the cache, so it gets
+        // extracted somewhere it can be thrown away from. Being inside the
+        // cache means files can be linked there instead of copied.
+        let dir = tempfile::tempdir_in(cache).map_err(|e| {
+            NassunError::ExtractIoError(
+                e,
+                Some(cache.to_path_buf()),
+                "creating temporary directory to prefetch into.".into(),
+            )
+        })?;
+        self.extract_to_dir_inner(
+            &dir.path().join("package"),
+            sri.as_ref(),
+            false,
+            false,
+            None,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// The integrity this package's source extracted to last time, if it
+    /// doesn't have one of its own. See [`crate::tarball::source_key`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn remembered_integrity(&self) -> Result<Option<Integrity>> {
+        let (Some(cache), Some(key)) = (
+            self.cache.as_deref(),
+            crate::tarball::source_key(&self.resolved),
+        ) else {
+            return Ok(None);
+        };
+        Ok(cacache::index::find(cache, &key)
+            .map_err(|e| NassunError::ExtractCacheError(e, None))?
+            .and_then(|entry| crate::tarball::source_integrity(&entry)))
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     async fn extract_to_dir_inner(
         &self,
@@ -199,8 +399,17 @@ impl Package {
         integrity: Option<&Integrity>,
         prefer_copy: bool,
         validate: bool,
+        on_progress: Option<ExtractProgress>,
     ) -> Result<Integrity> {
-        if let Some(sri) = integrity {
+        let source = self
+            .cache
+            .as_deref()
+            .zip(crate::tarball::source_key(&self.resolved));
+        let remembered = match integrity {
+            None => self.remembered_integrity()?,
+            Some(_) => None,
+        };
+        if let Some(sri) = integrity.or(remembered.as_ref()) {
             if let Some(cache) = self.cache.as_deref() {
                 if let Some(entry) = cacache::index::find(cache, &crate::tarball::tarball_key(sri))
                     .map_err(|e| NassunError::ExtractCacheError(e, None))?
@@ -210,43 +419,90 @@ impl Package {
                     // (bad data, etc), then go ahead and do a network
                     // extract.
                     match self
-                        .extract_from_cache(dir, cache, entry, prefer_copy, validate)
+                        .extract_from_cache(
+                            dir,
+                            cache,
+                            entry,
+                            prefer_copy,
+                            validate,
+                            on_progress.clone(),
+                        )
                         .await
                     {
-                        Ok(_) => return Ok(sri),
+                        Ok(_) => {
+                            self.counters.package_hit();
+                            return Ok(sri);
+                        }
                         Err(e) => {
-                            tracing::warn!("extracting package {:?} from cache failed, possily due to cache corruption: {e}", self.resolved());
-                            if let Some(entry) =
-                                cacache::index::find(cache, &crate::tarball::tarball_key(&sri))
-                                    .map_err(|e| NassunError::ExtractCacheError(e, None))?
-                            {
-                                tracing::debug!("removing corrupted cache entry.");
-                                clean_from_cache(cache, &sri, entry)?;
-                            }
+                            tracing::warn!("extracting package {:?} from cache failed, possibly due to cache corruption: {e}", self.resolved());
+                            let _lock = crate::lock::CacheLock::acquire(
+                                cache,
+                                &crate::tarball::tarball_key(&sri),
+                            )
+                            .await?;
+                            remove_from_cache(cache, &sri)?;
                             return self
-                                .tarball_checked(sri)
-                                .await?
-                                .extract_from_tarball_data(dir, self.cache.as_deref(), prefer_copy)
+                                .extract_from_network(dir, &sri, prefer_copy, on_progress)
                                 .await;
                         }
                     }
                 } else {
+                    let key = crate::tarball::tarball_key(sri);
+                    let _lock = crate::lock::CacheLock::acquire(cache, &key).await?;
+                    // Another process sharing the cache might have
+                    // extracted this package while we were waiting for it.
+                    if let Some(entry) = cacache::index::find(cache, &key)
+                        .map_err(|e| NassunError::ExtractCacheError(e, None))?
+                    {
+                        match self
+                            .extract_from_cache(
+                                dir,
+                                cache,
+                                entry,
+                                prefer_copy,
+                                validate,
+                                on_progress.clone(),
+                            )
+                            .await
+                        {
+                            Ok(_) => {
+                                self.counters.package_hit();
+                                return Ok(sri.clone());
+                            }
+                            Err(e) => {
+                                tracing::warn!("extracting package {:?} from cache failed, possibly due to cache corruption: {e}", self.resolved());
+                                remove_from_cache(cache, sri)?;
+                            }
+                        }
+                    }
                     return self
-                        .tarball_checked(sri.clone())
-                        .await?
-                        .extract_from_tarball_data(dir, self.cache.as_deref(), prefer_copy)
+                        .extract_from_network(dir, sri, prefer_copy, on_progress)
                         .await;
                 }
             }
-            self.tarball_checked(sri.clone())
-                .await?
-                .extract_from_tarball_data(dir, self.cache.as_deref(), prefer_copy)
+            self.extract_from_network(dir, sri, prefer_copy, on_progress)
                 .await
         } else {
-            self.tarball_unchecked()
+            self.counters.package_miss();
+            let sri = self
+                .tarball_unchecked()
                 .await?
+                .with_progress(on_progress)
+                .with_mmap(self.mmap)
                 .extract_from_tarball_data(dir, self.cache.as_deref(), prefer_copy)
-                .await
+                .await?;
+            if let Some((cache, key)) = source {
+                cacache::index::insert(
+                    cache,
+                    &key,
+                    WriteOpts::new()
+                        // This is just so the index entry is loadable.
+                        .integrity("sha256-deadbeef".parse().unwrap())
+                        .raw_metadata(sri.to_string().into_bytes()),
+                )
+                .map_err(|e| NassunError::ExtractCacheError(e, None))?;
+            }
+            Ok(sri)
         }
     }
 
@@ -258,6 +514,7 @@ impl Package {
         entry: cacache::Metadata,
         mut prefer_copy: bool,
         validate: bool,
+        on_progress: Option<ExtractProgress>,
     ) -> Result<()> {
         let dir = PathBuf::from(dir);
         let cache = PathBuf::from(cache);
@@ -297,6 +554,11 @@ impl Package {
                     validate,
                     *mode,
                 )?;
+                if let Some(on_progress) = &on_progress {
+                    if let Ok(meta) = std::fs::metadata(&path) {
+                        on_progress(meta.len());
+                    }
+                }
             }
             #[cfg(unix)]
             for binpath in index.bin_paths.iter() {
@@ -322,6 +584,19 @@ impl fmt::Debug for Package {
     }
 }
 
+/// Removes the cache entry for the tarball with integrity `sri`, if there is
+/// one, so it gets downloaded again instead of being read back corrupted.
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_from_cache(cache: &Path, sri: &Integrity) -> Result<()> {
+    if let Some(entry) = cacache::index::find(cache, &crate::tarball::tarball_key(sri))
+        .map_err(|e| NassunError::ExtractCacheError(e, None))?
+    {
+        tracing::debug!("removing corrupted cache entry.");
+        clean_from_cache(cache, sri, entry)?;
+    }
+    Ok(())
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn clean_from_cache(cache: &Path, sri: &Integrity, entry: cacache::Metadata) -> Result<()> {
     let map = entry
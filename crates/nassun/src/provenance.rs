@@ -0,0 +1,235 @@
+//! Build provenance for registry packages, from the Sigstore attestations
+//! registries publish alongside them.
+//!
+//! A provenance attestation is only accepted if it's about the exact
+//! package being installed: its statement has to name the package's name
+//! and version, and its sha512 digest has to match the package's integrity,
+//! which the tarball itself is checked against when it's extracted. The
+//! attestation's Sigstore signature and certificate chain aren't checked.
+
+use oro_client::attestations::{Statement, SLSA_PROVENANCE_V0_2, SLSA_PROVENANCE_V1};
+use serde_json::Value;
+use ssri::{Algorithm, Integrity};
+
+use crate::error::{NassunError, Result};
+use crate::package::Package;
+use crate::resolver::PackageResolution;
+
+/// Where and how a package version was built, according to its provenance
+/// attestation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Predicate type of the attestation, like
+    /// `https://slsa.dev/provenance/v1`.
+    pub predicate_type: String,
+    /// Source repository the package was built from, if the attestation
+    /// says.
+    pub repository: Option<String>,
+    /// The build that produced the package, like a CI run URL, if the
+    /// attestation says.
+    pub build: Option<String>,
+}
+
+impl Package {
+    /// Build provenance for this package, if it's a registry package with a
+    /// provenance attestation. Attestations that aren't about this exact
+    /// package are an error. See the [module docs](crate::provenance) for
+    /// what's checked.
+    pub async fn provenance(&self) -> Result<Option<Provenance>> {
+        let PackageResolution::Npm {
+            name,
+            version,
+            integrity,
+            ..
+        } = &self.resolved
+        else {
+            return Ok(None);
+        };
+        let metadata = self.metadata().await?;
+        let Some(dist_attestations) = &metadata.dist.attestations else {
+            return Ok(None);
+        };
+        let Some(attestations) = self.fetcher.attestations(&dist_attestations.url).await? else {
+            return Ok(None);
+        };
+        let Some(attestation) = attestations
+            .attestations
+            .iter()
+            .find(|attestation| is_provenance(&attestation.predicate_type))
+        else {
+            return Ok(None);
+        };
+        let integrity = integrity.clone().or_else(|| {
+            metadata
+                .dist
+                .integrity
+                .as_ref()
+                .and_then(|integrity| integrity.parse().ok())
+        });
+        let statement = attestation.statement()?;
+        check_subject(&statement, name, &version.to_string(), integrity.as_ref()).map_err(
+            |reason| NassunError::ProvenanceMismatch {
+                package: format!("{name}@{version}"),
+                reason,
+            },
+        )?;
+        Ok(Some(Provenance::from_statement(statement)))
+    }
+}
+
+fn is_provenance(predicate_type: &str) -> bool {
+    predicate_type == SLSA_PROVENANCE_V1 || predicate_type == SLSA_PROVENANCE_V0_2
+}
+
+/// Makes sure `statement` is about `name@version`, with a tarball matching
+/// `integrity`, returning what doesn't match if it isn't.
+fn check_subject(
+    statement: &Statement,
+    name: &str,
+    version: &str,
+    integrity: Option<&Integrity>,
+) -> std::result::Result<(), String> {
+    if !is_provenance(&statement.predicate_type) {
+        return Err(format!(
+            "the attestation's statement is a `{}`, not build provenance",
+            statement.predicate_type
+        ));
+    }
+    let purl = format!("pkg:npm/{name}@{version}");
+    let Some(subject) = statement
+        .subject
+        .iter()
+        .find(|subject| subject.name.replace("%40", "@") == purl)
+    else {
+        return Err(format!("the attestation isn't about `{purl}`"));
+    };
+    let Some(attested) = subject.digest.get("sha512") else {
+        return Err("the attestation has no sha512 digest".into());
+    };
+    let Some(hash) = integrity.and_then(|integrity| {
+        integrity
+            .hashes
+            .iter()
+            .find(|hash| hash.algorithm == Algorithm::Sha512)
+    }) else {
+        return Err("the package has no sha512 integrity to compare against".into());
+    };
+    let (_, expected) = Integrity {
+        hashes: vec![hash.clone()],
+    }
+    .to_hex();
+    if !attested.eq_ignore_ascii_case(&expected) {
+        return Err(format!(
+            "the attested sha512 digest `{attested}` doesn't match the package's `{expected}`"
+        ));
+    }
+    Ok(())
+}
+
+impl Provenance {
+    fn from_statement(statement: Statement) -> Self {
+        let predicate = &statement.predicate;
+        let string = |value: &Value| value.as_str().map(String::from);
+        let (repository, build) = if statement.predicate_type == SLSA_PROVENANCE_V1 {
+            let workflow = &predicate["buildDefinition"]["externalParameters"]["workflow"];
+            (
+                string(&workflow["repository"]),
+                string(&predicate["runDetails"]["metadata"]["invocationId"]),
+            )
+        } else {
+            (
+                string(&predicate["invocation"]["configSource"]["uri"]),
+                string(&predicate["metadata"]["buildInvocationId"]),
+            )
+        };
+        Self {
+            predicate_type: statement.predicate_type,
+            repository,
+            build,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use oro_client::attestations::StatementSubject;
+    use serde_json::json;
+    use ssri::IntegrityOpts;
+
+    use super::*;
+
+    fn statement(name: &str, hex: &str) -> Statement {
+        Statement {
+            statement_type: "https://in-toto.io/Statement/v1".into(),
+            subject: vec![StatementSubject {
+                name: name.into(),
+                digest: HashMap::from([("sha512".to_string(), hex.to_string())]),
+            }],
+            predicate_type: SLSA_PROVENANCE_V1.into(),
+            predicate: json!({
+                "buildDefinition": {
+                    "externalParameters": {
+                        "workflow": { "repository": "https://github.com/orogene/orogene" },
+                    },
+                },
+                "runDetails": {
+                    "metadata": { "invocationId": "https://github.com/orogene/orogene/actions/runs/1" },
+                },
+            }),
+        }
+    }
+
+    #[test]
+    fn checks_subject() {
+        let integrity = IntegrityOpts::new()
+            .algorithm(Algorithm::Sha512)
+            .chain(b"abcd")
+            .result();
+        let (_, hex) = integrity.to_hex();
+        assert_eq!(
+            check_subject(
+                &statement("pkg:npm/%40scope/pkg@1.0.0", &hex),
+                "@scope/pkg",
+                "1.0.0",
+                Some(&integrity)
+            ),
+            Ok(())
+        );
+        assert!(check_subject(
+            &statement("pkg:npm/%40scope/pkg@1.0.1", &hex),
+            "@scope/pkg",
+            "1.0.0",
+            Some(&integrity)
+        )
+        .is_err());
+        assert!(check_subject(
+            &statement("pkg:npm/%40scope/pkg@1.0.0", "ef01"),
+            "@scope/pkg",
+            "1.0.0",
+            Some(&integrity)
+        )
+        .is_err());
+        assert!(check_subject(
+            &statement("pkg:npm/%40scope/pkg@1.0.0", &hex),
+            "@scope/pkg",
+            "1.0.0",
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reads_build_details() {
+        let provenance = Provenance::from_statement(statement("pkg:npm/pkg@1.0.0", "abcd"));
+        assert_eq!(
+            provenance.repository.as_deref(),
+            Some("https://github.com/orogene/orogene")
+        );
+        assert_eq!(
+            provenance.build.as_deref(),
+            Some("https://github.com/orogene/orogene/actions/runs/1")
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use std::{fmt::Display, path::PathBuf, sync::Arc};
+use std::{fmt::Display, path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
 
 use node_semver::{Range as SemVerRange, Version as SemVerVersion};
 use oro_common::CorgiPackument;
@@ -6,8 +8,10 @@ use oro_package_spec::{GitInfo, PackageSpec, VersionSpec};
 use ssri::Integrity;
 use url::Url;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cache::CacheCounters;
 use crate::error::NassunError;
-use crate::fetch::PackageFetcher;
+use crate::fetch::PackageSource;
 use crate::package::Package;
 
 /// Represents a fully-resolved, specific version of a package as it would be fetched.
@@ -23,18 +27,27 @@ pub enum PackageResolution {
         name: String,
         path: PathBuf,
     },
+    Link {
+        name: String,
+        path: PathBuf,
+    },
     Git {
         name: String,
         info: GitInfo,
     },
+    Url {
+        name: String,
+        tarball: Url,
+        integrity: Option<Integrity>,
+    },
 }
 
 impl PackageResolution {
     pub fn integrity(&self) -> Option<&Integrity> {
         use PackageResolution::*;
         match self {
-            Npm { integrity, .. } => integrity.as_ref(),
-            Dir { .. } => None,
+            Npm { integrity, .. } | Url { integrity, .. } => integrity.as_ref(),
+            Dir { .. } | Link { .. } => None,
             Git { .. } => None,
         }
     }
@@ -45,7 +58,9 @@ impl Display for PackageResolution {
         match self {
             Npm { tarball, .. } => write!(f, "{tarball}"),
             Dir { path, .. } => write!(f, "file:{}", path.to_string_lossy()),
+            Link { path, .. } => write!(f, "link:{}", path.to_string_lossy()),
             Git { info, .. } => write!(f, "{info}"),
+            Url { tarball, .. } => write!(f, "{tarball}"),
         }
     }
 }
@@ -61,7 +76,9 @@ impl std::fmt::Debug for PackageResolution {
                 ..
             } => write!(f, "{name}@{version} ({tarball})"),
             Dir { path, name } => write!(f, "{name}@{}", path.to_string_lossy()),
+            Link { path, name } => write!(f, "{name}@link:{}", path.to_string_lossy()),
             Git { name, info } => write!(f, "{name}@{info}"),
+            Url { name, tarball, .. } => write!(f, "{name}@{tarball}"),
         }
     }
 }
@@ -80,11 +97,21 @@ impl PackageResolution {
                     None => false,
                 }
             }
-            (PR::Dir { path: pr_path, .. }, PS::Dir { path: ps_path }) => {
-                pr_path == &ps_path.canonicalize()?
-            }
+            (PR::Dir { path: pr_path, .. }, spec) => match spec.target() {
+                PS::Dir { path: ps_path } => pr_path == &ps_path.canonicalize()?,
+                _ => false,
+            },
+            (PR::Link { path: pr_path, .. }, spec) => match spec.target() {
+                PS::Link { path: ps_path } => pr_path == &ps_path.canonicalize()?,
+                _ => false,
+            },
             // TODO: Implement this.
             (PR::Git { .. }, PS::Git(..)) => false,
+            // Dependencies on tarball URLs are usually requested through an
+            // alias (`foo@https://...`).
+            (PR::Url { tarball, .. }, spec) => {
+                matches!(spec.target(), PS::Url { url } if url == tarball)
+            }
             _ => false,
         })
     }
@@ -101,6 +128,13 @@ impl PackageResolution {
 pub(crate) struct PackageResolver {
     pub(crate) default_tag: String,
     pub(crate) base_dir: PathBuf,
+    pub(crate) prefer_lowest: bool,
+    pub(crate) minimum_release_age: Option<Duration>,
+    pub(crate) before: Option<DateTime<Utc>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) mmap: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) counters: Arc<CacheCounters>,
 }
 
 impl PackageResolver {
@@ -109,7 +143,7 @@ impl PackageResolver {
         name: String,
         from: PackageSpec,
         resolved: PackageResolution,
-        fetcher: Arc<dyn PackageFetcher>,
+        fetcher: Arc<dyn PackageSource>,
         cache: Arc<Option<PathBuf>>,
     ) -> Package {
         Package {
@@ -119,6 +153,10 @@ impl PackageResolver {
             fetcher,
             cache,
             base_dir: self.base_dir.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            mmap: self.mmap,
+            #[cfg(not(target_arch = "wasm32"))]
+            counters: self.counters.clone(),
         }
     }
 
@@ -126,10 +164,23 @@ impl PackageResolver {
         &self,
         name: String,
         wanted: PackageSpec,
-        fetcher: Arc<dyn PackageFetcher>,
+        fetcher: Arc<dyn PackageSource>,
         cache: Arc<Option<PathBuf>>,
     ) -> Result<Package, NassunError> {
-        let packument = fetcher.corgi_packument(&wanted, &self.base_dir).await?;
+        let mut packument = fetcher
+            .resolution_packument(&wanted, &self.base_dir)
+            .await?;
+        if (self.minimum_release_age.is_some() || self.before.is_some())
+            && packument.time.is_empty()
+            && matches!(wanted.target(), PackageSpec::Npm { .. })
+        {
+            // Registries usually leave publish times out of Corgi
+            // packuments, so we need the full thing to check release ages.
+            // The npm source already does this itself, but other sources
+            // might not.
+            let full = fetcher.packument(&wanted, &self.base_dir).await?;
+            packument = Arc::new((*full).clone().into());
+        }
         let resolved = self.get_resolution(&name, &wanted, &packument)?;
         Ok(Package {
             name,
@@ -138,6 +189,10 @@ impl PackageResolver {
             fetcher,
             base_dir: self.base_dir.clone(),
             cache,
+            #[cfg(not(target_arch = "wasm32"))]
+            mmap: self.mmap,
+            #[cfg(not(target_arch = "wasm32"))]
+            counters: self.counters.clone(),
         })
     }
 
@@ -157,6 +212,13 @@ impl PackageResolver {
             });
         }
 
+        if let Link { ref path } = spec {
+            return Ok(PackageResolution::Link {
+                name: name.into(),
+                path: self.base_dir.join(path).canonicalize()?,
+            });
+        }
+
         if let Git(info) = spec {
             return Ok(PackageResolution::Git {
                 name: name.into(),
@@ -164,6 +226,23 @@ impl PackageResolver {
             });
         }
 
+        if let Url { url } = spec {
+            // Tarball URLs always point at exactly one version, and the
+            // fetcher records the integrity of what it downloaded.
+            let integrity = packument
+                .versions
+                .values()
+                .next()
+                .and_then(|v| v.dist.integrity.as_ref())
+                .map(|i| i.parse())
+                .transpose()?;
+            return Ok(PackageResolution::Url {
+                name: name.into(),
+                tarball: url.clone(),
+                integrity,
+            });
+        }
+
         if packument.versions.is_empty() {
             return Err(NassunError::NoVersion {
                 name: name.into(),
@@ -191,7 +270,16 @@ impl PackageResolver {
             _ => return Err(NassunError::InvalidPackageSpec(spec.clone())),
         };
 
-        let tag_version = packument.tags.get(&self.default_tag);
+        let cutoff = self.release_cutoff();
+        let old_enough = |version: &SemVerVersion| match cutoff {
+            Some(cutoff) => published_at(packument, version).map_or(true, |time| time <= cutoff),
+            None => true,
+        };
+
+        let tag_version = packument
+            .tags
+            .get(&self.default_tag)
+            .filter(|version| old_enough(version));
 
         if target.is_none()
             && tag_version.is_some()
@@ -206,7 +294,7 @@ impl PackageResolver {
                 PackageSpec::Npm {
                     requested: Some(VersionSpec::Range(range)),
                     ..
-                } => range.satisfies(tag_version.as_ref().unwrap()),
+                } => !self.prefer_lowest && range.satisfies(tag_version.as_ref().unwrap()),
                 _ => false,
             }
         {
@@ -219,7 +307,57 @@ impl PackageResolver {
                 ..
             } = spec
             {
-                target = max_satisfying(packument.versions.keys(), range);
+                let versions = packument.versions.keys().filter(|v| old_enough(v));
+                target = if self.prefer_lowest {
+                    min_satisfying(versions, range)
+                } else {
+                    max_satisfying(versions, range)
+                };
+            }
+        }
+
+        if target.is_none() && cutoff.is_some() {
+            if let Npm {
+                requested: None, ..
+            } = spec
+            {
+                // The default tag is too recent, so fall back to the newest
+                // version before it that's old enough.
+                let range = packument
+                    .tags
+                    .get(&self.default_tag)
+                    .and_then(|tag| SemVerRange::parse(format!("<={tag}")).ok());
+                if let Some(range) = range {
+                    target =
+                        max_satisfying(packument.versions.keys().filter(|v| old_enough(v)), &range);
+                }
+            }
+        }
+
+        if target.is_none() && cutoff.is_some() {
+            let unrestricted = PackageResolver {
+                minimum_release_age: None,
+                before: None,
+                ..self.clone()
+            };
+            if unrestricted.get_resolution(name, wanted, packument).is_ok() {
+                // Blame whichever restriction is stricter.
+                let before = self
+                    .before
+                    .filter(|before| self.age_cutoff().map_or(true, |age| *before <= age));
+                return Err(match (before, self.minimum_release_age) {
+                    (Some(before), _) => NassunError::NoVersionBefore {
+                        name: name.into(),
+                        spec: spec.clone(),
+                        before: before.to_rfc3339(),
+                    },
+                    (None, Some(age)) => NassunError::NoOldEnoughVersion {
+                        name: name.into(),
+                        spec: spec.clone(),
+                        age: humanize_age(age),
+                    },
+                    (None, None) => unreachable!("there's a release cutoff"),
+                });
             }
         }
 
@@ -276,6 +414,41 @@ impl PackageResolver {
     }
 }
 
+impl PackageResolver {
+    /// Latest publish time a version can have and still be used, if
+    /// there's a limit at all.
+    fn release_cutoff(&self) -> Option<DateTime<Utc>> {
+        match (self.age_cutoff(), self.before) {
+            (Some(age), Some(before)) => Some(age.min(before)),
+            (age, before) => age.or(before),
+        }
+    }
+
+    fn age_cutoff(&self) -> Option<DateTime<Utc>> {
+        self.minimum_release_age
+            .and_then(|age| chrono::Duration::from_std(age).ok())
+            .and_then(|age| Utc::now().checked_sub_signed(age))
+    }
+}
+
+fn published_at(packument: &CorgiPackument, version: &SemVerVersion) -> Option<DateTime<Utc>> {
+    packument
+        .time
+        .get(&version.to_string())
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn humanize_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        s if s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
 fn max_satisfying<'a>(
     versions: impl Iterator<Item = &'a SemVerVersion>,
     range: &SemVerRange,
@@ -283,8 +456,17 @@ fn max_satisfying<'a>(
     versions.filter(|v| range.satisfies(v)).max()
 }
 
+fn min_satisfying<'a>(
+    versions: impl Iterator<Item = &'a SemVerVersion>,
+    range: &SemVerRange,
+) -> Option<&'a SemVerVersion> {
+    versions.filter(|v| range.satisfies(v)).min()
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use super::*;
     use test_case::test_case;
 
@@ -330,4 +512,185 @@ mod tests {
         };
         assert_eq!(resolution.satisfies(&package_spec).unwrap(), satifies);
     }
+
+    #[test]
+    fn satisfies_url_specs() {
+        let resolution = PackageResolution::Url {
+            name: "oro-test-package".to_owned(),
+            tarball: Url::parse("https://example.com/oro-test-package.tgz").unwrap(),
+            integrity: None,
+        };
+        let spec: PackageSpec = "oro-test-package@https://example.com/oro-test-package.tgz"
+            .parse()
+            .unwrap();
+        assert!(resolution.satisfies(&spec).unwrap());
+        let spec: PackageSpec = "oro-test-package@https://example.com/other.tgz"
+            .parse()
+            .unwrap();
+        assert!(!resolution.satisfies(&spec).unwrap());
+    }
+
+    #[test]
+    fn satisfies_dir_specs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let resolution = PackageResolution::Dir {
+            name: "nassun".to_owned(),
+            path: dir.canonicalize().unwrap(),
+        };
+        let spec: PackageSpec = format!("nassun@file:{}", dir.display()).parse().unwrap();
+        assert!(resolution.satisfies(&spec).unwrap());
+        let spec: PackageSpec = format!("nassun@file:{}", dir.join("src").display())
+            .parse()
+            .unwrap();
+        assert!(!resolution.satisfies(&spec).unwrap());
+    }
+
+    #[test]
+    fn resolves_lowest_versions() {
+        let packument: CorgiPackument = serde_json::from_value(serde_json::json!({
+            "dist-tags": { "latest": "1.2.0" },
+            "versions": {
+                "1.0.0": {
+                    "version": "1.0.0",
+                    "dist": { "tarball": "https://example.com/a-1.0.0.tgz" }
+                },
+                "1.1.0": {
+                    "version": "1.1.0",
+                    "dist": { "tarball": "https://example.com/a-1.1.0.tgz" }
+                },
+                "1.2.0": {
+                    "version": "1.2.0",
+                    "dist": { "tarball": "https://example.com/a-1.2.0.tgz" }
+                },
+                "2.0.0": {
+                    "version": "2.0.0",
+                    "dist": { "tarball": "https://example.com/a-2.0.0.tgz" }
+                },
+            }
+        }))
+        .unwrap();
+        let packument = Arc::new(packument);
+        let resolver = |prefer_lowest| PackageResolver {
+            default_tag: "latest".into(),
+            base_dir: PathBuf::from("."),
+            prefer_lowest,
+            minimum_release_age: None,
+            before: None,
+            mmap: false,
+            counters: Default::default(),
+        };
+        let version = |resolver: PackageResolver, spec: &str| {
+            resolver
+                .get_resolution("a", &spec.parse().unwrap(), &packument)
+                .unwrap()
+                .npm_version()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(version(resolver(false), "a@^1.0.0"), "1.2.0");
+        assert_eq!(version(resolver(true), "a@^1.0.0"), "1.0.0");
+        assert_eq!(version(resolver(true), "a@>=1.1.0"), "1.1.0");
+        // Specs without a range still use the default tag.
+        assert_eq!(version(resolver(true), "a"), "1.2.0");
+    }
+
+    #[test]
+    fn skips_recent_versions() {
+        let recent = (Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+        let packument: CorgiPackument = serde_json::from_value(serde_json::json!({
+            "dist-tags": { "latest": "1.2.0" },
+            "time": {
+                "1.0.0": "2020-01-01T00:00:00.000Z",
+                "1.1.0": "2020-02-01T00:00:00.000Z",
+                "1.2.0": recent,
+            },
+            "versions": {
+                "1.0.0": {
+                    "version": "1.0.0",
+                    "dist": { "tarball": "https://example.com/a-1.0.0.tgz" }
+                },
+                "1.1.0": {
+                    "version": "1.1.0",
+                    "dist": { "tarball": "https://example.com/a-1.1.0.tgz" }
+                },
+                "1.2.0": {
+                    "version": "1.2.0",
+                    "dist": { "tarball": "https://example.com/a-1.2.0.tgz" }
+                },
+            }
+        }))
+        .unwrap();
+        let packument = Arc::new(packument);
+        let resolver = PackageResolver {
+            default_tag: "latest".into(),
+            base_dir: PathBuf::from("."),
+            prefer_lowest: false,
+            minimum_release_age: Some(Duration::from_secs(3 * 24 * 60 * 60)),
+            before: None,
+            mmap: false,
+            counters: Default::default(),
+        };
+        let version = |spec: &str| {
+            resolver
+                .get_resolution("a", &spec.parse().unwrap(), &packument)
+                .map(|resolved| resolved.npm_version().unwrap().to_string())
+        };
+        assert_eq!(version("a@^1.0.0").unwrap(), "1.1.0");
+        assert_eq!(version("a").unwrap(), "1.1.0");
+        // Explicitly requested versions are still allowed.
+        assert_eq!(version("a@1.2.0").unwrap(), "1.2.0");
+        assert!(matches!(
+            version("a@^1.2.0"),
+            Err(NassunError::NoOldEnoughVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn skips_versions_after_cutoff() {
+        let packument: CorgiPackument = serde_json::from_value(serde_json::json!({
+            "dist-tags": { "latest": "1.2.0" },
+            "time": {
+                "1.0.0": "2020-01-01T00:00:00.000Z",
+                "1.1.0": "2020-02-01T00:00:00.000Z",
+                "1.2.0": "2020-03-01T00:00:00.000Z",
+            },
+            "versions": {
+                "1.0.0": {
+                    "version": "1.0.0",
+                    "dist": { "tarball": "https://example.com/a-1.0.0.tgz" }
+                },
+                "1.1.0": {
+                    "version": "1.1.0",
+                    "dist": { "tarball": "https://example.com/a-1.1.0.tgz" }
+                },
+                "1.2.0": {
+                    "version": "1.2.0",
+                    "dist": { "tarball": "https://example.com/a-1.2.0.tgz" }
+                },
+            }
+        }))
+        .unwrap();
+        let packument = Arc::new(packument);
+        let resolver = PackageResolver {
+            default_tag: "latest".into(),
+            base_dir: PathBuf::from("."),
+            prefer_lowest: false,
+            minimum_release_age: None,
+            before: Some("2020-02-15T00:00:00Z".parse().unwrap()),
+            mmap: false,
+            counters: Default::default(),
+        };
+        let version = |spec: &str| {
+            resolver
+                .get_resolution("a", &spec.parse().unwrap(), &packument)
+                .map(|resolved| resolved.npm_version().unwrap().to_string())
+        };
+        assert_eq!(version("a@^1.0.0").unwrap(), "1.1.0");
+        assert_eq!(version("a").unwrap(), "1.1.0");
+        assert_eq!(version("a@1.2.0").unwrap(), "1.2.0");
+        assert!(matches!(
+            version("a@^1.2.0"),
+            Err(NassunError::NoVersionBefore { .. })
+        ));
+    }
 }
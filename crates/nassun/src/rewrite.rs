@@ -0,0 +1,170 @@
+//! Rewriting the `dist.tarball` URLs registries hand out, before they're
+//! fetched. Artifact proxies like Artifactory and Nexus often serve
+//! packuments straight from upstream, with tarball URLs pointing back at
+//! the upstream registry, which isn't reachable from behind the proxy.
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+use url::Url;
+
+use crate::error::NassunError;
+
+/// A rule for rewriting tarball URLs, parsed from `FROM=TO`.
+///
+/// `FROM` is usually a URL prefix to replace with `TO`. When it's wrapped in
+/// slashes, like `/^https://registry\.npmjs\.org/(.*)$/`, it's a regex
+/// instead, and `TO` can refer to its capture groups with `$1`, `$name`,
+/// and so on.
+#[derive(Debug, Clone)]
+pub enum TarballRewrite {
+    Prefix { from: String, to: String },
+    Regex { from: Regex, to: String },
+}
+
+impl TarballRewrite {
+    /// Rewrites `url` with the first of `rules` that matches it, if any.
+    pub fn apply_all(rules: &[TarballRewrite], url: &Url) -> Option<Url> {
+        rules.iter().find_map(|rule| rule.apply(url))
+    }
+
+    /// Rewrites `url`, if this rule matches it and the result is a valid
+    /// URL.
+    pub fn apply(&self, url: &Url) -> Option<Url> {
+        let rewritten = match self {
+            Self::Prefix { from, to } => {
+                format!("{to}{}", url.as_str().strip_prefix(from.as_str())?)
+            }
+            Self::Regex { from, to } => {
+                if !from.is_match(url.as_str()) {
+                    return None;
+                }
+                from.replace(url.as_str(), to.as_str()).into_owned()
+            }
+        };
+        match rewritten.parse() {
+            Ok(rewritten) => Some(rewritten),
+            Err(err) => {
+                tracing::warn!(
+                    "Rewriting tarball URL {url} produced an invalid URL, `{rewritten}`: {err}. Using the original."
+                );
+                None
+            }
+        }
+    }
+}
+
+impl FromStr for TarballRewrite {
+    type Err = NassunError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| NassunError::InvalidTarballRewrite(s.into(), reason.into());
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| invalid("there's no `=` between what to replace and its replacement"))?;
+        if from.is_empty() {
+            return Err(invalid("there's nothing to replace"));
+        }
+        match from
+            .strip_prefix('/')
+            .and_then(|from| from.strip_suffix('/'))
+        {
+            Some(pattern) => Ok(Self::Regex {
+                from: Regex::new(pattern).map_err(|err| invalid(&err.to_string()))?,
+                to: to.into(),
+            }),
+            None => Ok(Self::Prefix {
+                from: from.into(),
+                to: to.into(),
+            }),
+        }
+    }
+}
+
+// Regexes can't be compared directly, so they're compared by their
+// patterns.
+impl PartialEq for TarballRewrite {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Prefix { from, to },
+                Self::Prefix {
+                    from: other_from,
+                    to: other_to,
+                },
+            ) => from == other_from && to == other_to,
+            (
+                Self::Regex { from, to },
+                Self::Regex {
+                    from: other_from,
+                    to: other_to,
+                },
+            ) => from.as_str() == other_from.as_str() && to == other_to,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TarballRewrite {}
+
+impl fmt::Display for TarballRewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Prefix { from, to } => write!(f, "{from}={to}"),
+            Self::Regex { from, to } => write!(f, "/{from}/={to}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn rewrites_urls() {
+        let rules = [
+            "https://registry.npmjs.org/=https://proxy.corp/api/npm/npm-remote/"
+                .parse::<TarballRewrite>()
+                .unwrap(),
+            r"/^https://registry\.yarnpkg\.com/(.*)$/=https://proxy.corp/api/npm/yarn-remote/$1"
+                .parse()
+                .unwrap(),
+        ];
+        assert_eq!(
+            TarballRewrite::apply_all(
+                &rules,
+                &url("https://registry.npmjs.org/foo/-/foo-1.0.0.tgz")
+            ),
+            Some(url(
+                "https://proxy.corp/api/npm/npm-remote/foo/-/foo-1.0.0.tgz"
+            ))
+        );
+        assert_eq!(
+            TarballRewrite::apply_all(
+                &rules,
+                &url("https://registry.yarnpkg.com/foo/-/foo-1.0.0.tgz")
+            ),
+            Some(url(
+                "https://proxy.corp/api/npm/yarn-remote/foo/-/foo-1.0.0.tgz"
+            ))
+        );
+        assert_eq!(
+            TarballRewrite::apply_all(&rules, &url("https://example.com/foo.tgz")),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        assert!("https://registry.npmjs.org/"
+            .parse::<TarballRewrite>()
+            .is_err());
+        assert!("=https://proxy.corp/".parse::<TarballRewrite>().is_err());
+        assert!("/(/=https://proxy.corp/".parse::<TarballRewrite>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Checking the signatures registries make over the packages they serve,
+//! so a tarball's integrity can be trusted to be the one the registry
+//! published, even when it was read from a mirror, proxy, or lockfile.
+//!
+//! Registries sign `<name>@<version>:<integrity>` for every package version
+//! with one of the ECDSA P-256 keys they publish at `-/npm/v1/keys`. Key
+//! expiry isn't taken into account.
+
+use std::fmt;
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use oro_client::keys::RegistryKey;
+use oro_common::DistSignature;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+
+use crate::error::NassunError;
+
+/// What to do about registry packages whose signatures can't be verified.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureVerification {
+    /// Don't check signatures at all.
+    #[default]
+    Off,
+    /// Check signatures, but only log a warning for packages that fail.
+    Warn,
+    /// Refuse to fetch packages that fail.
+    Enforce,
+}
+
+impl FromStr for SignatureVerification {
+    type Err = NassunError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "enforce" => Ok(Self::Enforce),
+            _ => Err(NassunError::InvalidSignatureVerification(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for SignatureVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Warn => write!(f, "warn"),
+            Self::Enforce => write!(f, "enforce"),
+        }
+    }
+}
+
+/// Checks that one of `signatures` is a valid signature over `name`,
+/// `version` and `integrity` by one of `keys`, returning why not if it
+/// isn't.
+pub(crate) fn verify(
+    name: &str,
+    version: &str,
+    integrity: &str,
+    signatures: &[DistSignature],
+    keys: &[RegistryKey],
+) -> Result<(), String> {
+    if signatures.is_empty() {
+        return Err("the registry didn't sign it".into());
+    }
+    let message = format!("{name}@{version}:{integrity}");
+    let mut problem = None;
+    for signature in signatures {
+        let Some(key) = keys.iter().find(|key| key.keyid == signature.keyid) else {
+            problem = Some(format!(
+                "it was signed with `{}`, which isn't one of the registry's keys",
+                signature.keyid
+            ));
+            continue;
+        };
+        match verify_one(&message, &signature.sig, key) {
+            Ok(()) => return Ok(()),
+            Err(err) => problem = Some(err),
+        }
+    }
+    Err(problem.expect("there's at least one signature"))
+}
+
+fn verify_one(message: &str, sig: &str, key: &RegistryKey) -> Result<(), String> {
+    if key.keytype != "ecdsa-sha2-nistp256" {
+        return Err(format!(
+            "registry key `{}` is a `{}` key, which isn't supported",
+            key.keyid, key.keytype
+        ));
+    }
+    let verifying_key = BASE64
+        .decode(&key.key)
+        .ok()
+        .and_then(|der| VerifyingKey::from_public_key_der(&der).ok())
+        .ok_or_else(|| format!("registry key `{}` isn't a valid public key", key.keyid))?;
+    let signature = BASE64
+        .decode(sig)
+        .ok()
+        .and_then(|der| Signature::from_der(&der).ok())
+        .ok_or_else(|| "its signature is malformed".to_string())?;
+    verifying_key
+        .verify(message.as_bytes(), &signature)
+        .map_err(|_| format!("its signature by `{}` doesn't match", key.keyid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE0+ge63X9LExyAdmwIBP3k2LeYjDai1YUHD/qXtyMYRNNCwHb1fHAR0CAh/KM2R/sAXe2WCZcvmyThxfIvEdTlg==";
+    // Signature over `oro-test@1.0.0:sha512-deadbeef`.
+    const SIG: &str = "MEYCIQD/HySOCQrllpzos5n541to9SuODFfcuZUnOWLkwj205wIhAOa3jGHTPCt5VhvI66H0ZQT2RRjXbNJ1WivsEEBRERh0";
+
+    fn keys() -> Vec<RegistryKey> {
+        vec![RegistryKey {
+            keyid: "SHA256:test".into(),
+            keytype: "ecdsa-sha2-nistp256".into(),
+            scheme: "ecdsa-sha2-nistp256".into(),
+            key: KEY.into(),
+            expires: None,
+        }]
+    }
+
+    fn signatures(keyid: &str) -> Vec<DistSignature> {
+        vec![DistSignature {
+            keyid: keyid.into(),
+            sig: SIG.into(),
+        }]
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let keys = keys();
+        assert_eq!(
+            verify(
+                "oro-test",
+                "1.0.0",
+                "sha512-deadbeef",
+                &signatures("SHA256:test"),
+                &keys
+            ),
+            Ok(())
+        );
+        assert!(verify(
+            "oro-test",
+            "1.0.1",
+            "sha512-deadbeef",
+            &signatures("SHA256:test"),
+            &keys
+        )
+        .is_err());
+        assert!(verify(
+            "oro-test",
+            "1.0.0",
+            "sha512-deadbeef",
+            &signatures("SHA256:other"),
+            &keys
+        )
+        .is_err());
+        assert!(verify("oro-test", "1.0.0", "sha512-deadbeef", &[], &keys).is_err());
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(
+            "enforce".parse::<SignatureVerification>().unwrap(),
+            SignatureVerification::Enforce
+        );
+        assert!("sometimes".parse::<SignatureVerification>().is_err());
+    }
+}
@@ -31,16 +31,25 @@ use tempfile::NamedTempFile;
 
 use crate::entries::{Entries, Entry};
 use crate::error::{NassunError, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resolver::PackageResolution;
 use crate::TarballStream;
 
 #[cfg(not(target_arch = "wasm32"))]
 const MAX_IN_MEMORY_TARBALL_SIZE: usize = 1024 * 1024 * 5;
 
+/// Called with the number of bytes processed, each time more of a package
+/// gets extracted. See [`crate::Package::extract_to_dir_with_progress`].
+pub type ExtractProgress = std::sync::Arc<dyn Fn(u64) + Send + Sync>;
+
 pub struct Tarball {
     checker: Option<IntegrityChecker>,
     reader: TarballStream,
     #[cfg(not(target_arch = "wasm32"))]
     integrity: Option<Integrity>,
+    on_progress: Option<ExtractProgress>,
+    #[cfg(not(target_arch = "wasm32"))]
+    mmap: bool,
 }
 
 impl Tarball {
@@ -50,6 +59,9 @@ impl Tarball {
             #[cfg(not(target_arch = "wasm32"))]
             integrity: Some(integrity.clone()),
             checker: Some(IntegrityChecker::new(integrity)),
+            on_progress: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            mmap: false,
         }
     }
 
@@ -59,9 +71,27 @@ impl Tarball {
             checker: None,
             #[cfg(not(target_arch = "wasm32"))]
             integrity: None,
+            on_progress: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            mmap: false,
         }
     }
 
+    /// Reports every chunk read from the tarball stream to `on_progress`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_progress(mut self, on_progress: Option<ExtractProgress>) -> Self {
+        self.on_progress = on_progress;
+        self
+    }
+
+    /// Maps the tarball into memory for extraction, if it's too big to be
+    /// kept there to begin with. See [`crate::NassunOpts::mmap`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
     pub fn into_inner(self) -> TarballStream {
         self.reader
     }
@@ -85,13 +115,12 @@ impl Tarball {
 
     #[cfg(not(target_arch = "wasm32"))]
     async fn into_temp(self) -> Result<TempTarball> {
+        let mmap = self.mmap;
         let mut reader = BufReader::new(self);
         let mut buf = [0u8; 1024 * 8];
         let mut vec = Vec::new();
         loop {
-            let n = reader.read(&mut buf).await.map_err(|e| {
-                NassunError::ExtractIoError(e, None, "reading from tarball stream".into())
-            })?;
+            let n = reader.read(&mut buf).await.map_err(read_error)?;
             if n == 0 {
                 break;
             }
@@ -114,10 +143,16 @@ impl Tarball {
                     )
                 })?;
                 loop {
-                    let n = reader.read(&mut buf).await.map_err(|e| {
-                        NassunError::ExtractIoError(e, None, "reading from tarball stream".into())
-                    })?;
+                    let n = reader.read(&mut buf).await.map_err(read_error)?;
                     if n == 0 {
+                        if mmap {
+                            if let Some(map) = crate::mmap::map(tempfile.as_file()) {
+                                return Ok(TempTarball::Mapped(
+                                    tempfile,
+                                    std::io::Cursor::new(map),
+                                ));
+                            }
+                        }
                         return Ok(TempTarball::File(tempfile));
                     }
                     tempfile.write_all(&buf[..n]).map_err(|e| {
@@ -159,6 +194,22 @@ impl Tarball {
     }
 }
 
+/// Turns an error reading from a tarball stream into a [`NassunError`],
+/// keeping integrity failures distinct from other IO errors.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_error(err: std::io::Error) -> NassunError {
+    if let Some(ssri::Error::IntegrityCheckError(expected, found)) = err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<ssri::Error>())
+    {
+        return NassunError::IntegrityMismatch {
+            expected: Box::new(expected.clone()),
+            found: Box::new(found.clone()),
+        };
+    }
+    NassunError::ExtractIoError(err, None, "reading from tarball stream".into())
+}
+
 impl AsyncRead for Tarball {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -166,6 +217,11 @@ impl AsyncRead for Tarball {
         buf: &mut [u8],
     ) -> Poll<std::io::Result<usize>> {
         let amt = futures::ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        if amt > 0 {
+            if let Some(on_progress) = &self.on_progress {
+                on_progress(amt as u64);
+            }
+        }
         let mut checker_done = false;
         if let Some(checker) = self.checker.as_mut() {
             if amt > 0 {
@@ -174,18 +230,20 @@ impl AsyncRead for Tarball {
                 checker_done = true;
             }
         }
-        if checker_done
-            && self
+        if checker_done {
+            if let Err(err) = self
                 .checker
                 .take()
                 .expect("There should've been a checker here")
                 .result()
-                .is_err()
-        {
-            return Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Integrity check failed",
-            )));
+            {
+                // Kept as the source, so it can be turned back into a
+                // proper integrity error. See `read_error`.
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    err,
+                )));
+            }
         }
         Poll::Ready(Ok(amt))
     }
@@ -194,6 +252,11 @@ impl AsyncRead for Tarball {
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) enum TempTarball {
     File(NamedTempFile),
+    /// Keeps the temp file around for as long as it's mapped.
+    Mapped(
+        #[allow(dead_code)] NamedTempFile,
+        std::io::Cursor<memmap2::Mmap>,
+    ),
     Memory(std::io::Cursor<Vec<u8>>),
 }
 
@@ -403,6 +466,7 @@ impl std::io::Read for TempTarball {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             TempTarball::File(f) => f.read(buf),
+            TempTarball::Mapped(_, m) => m.read(buf),
             TempTarball::Memory(m) => m.read(buf),
         }
     }
@@ -413,6 +477,7 @@ impl std::io::Seek for TempTarball {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
         match self {
             TempTarball::File(f) => f.seek(pos),
+            TempTarball::Mapped(_, m) => m.seek(pos),
             TempTarball::Memory(m) => m.seek(pos),
         }
     }
@@ -438,6 +503,40 @@ pub(crate) fn tarball_key(integrity: &Integrity) -> String {
     format!("nassun::package::{integrity}")
 }
 
+/// Prefix of the keys that remember which cached package a source
+/// extracted to. See [`source_key`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const SOURCE_KEY_PREFIX: &str = "nassun::source::";
+
+/// Key that remembers the integrity of the package a source extracted to,
+/// for packages that don't come with one, so their cached file index can be
+/// found again without fetching and walking their tarball. Only sources
+/// that can never change get one: registry tarballs, and git repositories
+/// pinned to a full commit hash.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn source_key(resolved: &PackageResolution) -> Option<String> {
+    match resolved {
+        PackageResolution::Npm { tarball, .. } => Some(format!("{SOURCE_KEY_PREFIX}{tarball}")),
+        PackageResolution::Git { info, .. }
+            if info.committish().map_or(false, |committish| {
+                committish.len() == 40 && committish.chars().all(|c| c.is_ascii_hexdigit())
+            }) =>
+        {
+            Some(format!("{SOURCE_KEY_PREFIX}{info}"))
+        }
+        _ => None,
+    }
+}
+
+/// The integrity a source entry remembers. See [`source_key`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn source_integrity(entry: &cacache::Metadata) -> Option<Integrity> {
+    std::str::from_utf8(entry.raw_metadata.as_ref()?)
+        .ok()?
+        .parse()
+        .ok()
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn extract_from_cache(
     cache: &Path,
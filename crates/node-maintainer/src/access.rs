@@ -0,0 +1,195 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::NodeMaintainerError;
+use crate::{PNP_DATA_FILE_NAME, PNP_FILE_NAME};
+
+/// Kinds of filesystem modifications the [`AccessTracker`] knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccessKind {
+    Write,
+    Link,
+    Chmod,
+    Remove,
+}
+
+impl fmt::Display for AccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AccessKind::*;
+        write!(
+            f,
+            "{}",
+            match self {
+                Write => "write",
+                Link => "link",
+                Chmod => "chmod",
+                Remove => "remove",
+            }
+        )
+    }
+}
+
+/// Checks filesystem modifications made while applying `node_modules/`.
+///
+/// In least-privilege mode, only `node_modules/` (which includes the
+/// isolated linker's store), the Plug'n'Play files in the project root, and
+/// the cache are allowed to be modified, and
+/// any attempt to touch anything else is reported and denied. Otherwise,
+/// everything is allowed.
+#[derive(Debug, Default)]
+pub(crate) struct AccessTracker {
+    allowed: Option<Vec<PathBuf>>,
+}
+
+impl AccessTracker {
+    pub(crate) fn new(least_privilege: bool, root: &Path, cache: Option<&Path>) -> Self {
+        if !least_privilege {
+            return Self::default();
+        }
+        let mut allowed = vec![
+            resolve(&root.join("node_modules")),
+            resolve(&root.join(PNP_FILE_NAME)),
+            resolve(&root.join(PNP_DATA_FILE_NAME)),
+        ];
+        if let Some(cache) = cache {
+            allowed.push(resolve(cache));
+        }
+        Self {
+            allowed: Some(allowed),
+        }
+    }
+
+    /// Also allows modifying anything inside `path`, if access is
+    /// restricted at all.
+    pub(crate) fn allow(mut self, path: &Path) -> Self {
+        if let Some(allowed) = &mut self.allowed {
+            allowed.push(resolve(path));
+        }
+        self
+    }
+
+    #[cfg_attr(windows, allow(dead_code))]
+    pub(crate) fn is_restricted(&self) -> bool {
+        self.allowed.is_some()
+    }
+
+    /// Makes sure `path` may be modified. Paths are resolved through any
+    /// existing symlinks first, so links can't be used to escape the
+    /// allowed directories.
+    pub(crate) fn check(&self, kind: AccessKind, path: &Path) -> Result<(), NodeMaintainerError> {
+        let Some(allowed) = &self.allowed else {
+            return Ok(());
+        };
+        // Links and removals modify the directory containing the entry,
+        // rather than whatever the entry might point to.
+        let resolved = match (kind, path.parent(), path.file_name()) {
+            (AccessKind::Link | AccessKind::Remove, Some(parent), Some(name)) => {
+                resolve(parent).join(name)
+            }
+            _ => resolve(path),
+        };
+        if allowed.iter().any(|dir| resolved.starts_with(dir)) {
+            Ok(())
+        } else {
+            tracing::error!(
+                "Denied out-of-scope {kind} of {} (resolved to {}).",
+                path.display(),
+                resolved.display()
+            );
+            Err(NodeMaintainerError::OutOfScopeAccess(
+                kind.to_string(),
+                resolved,
+            ))
+        }
+    }
+}
+
+/// Turns `path` into an absolute, normalized path, resolving symlinks for
+/// as much of it as currently exists.
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    let mut resolved = loop {
+        match existing.canonicalize() {
+            Ok(canonical) => break canonical,
+            Err(_) => match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name);
+                    existing = parent;
+                }
+                _ => break existing.to_path_buf(),
+            },
+        }
+    };
+    for name in rest.into_iter().rev() {
+        resolved.push(name);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrestricted_allows_everything() {
+        let tracker = AccessTracker::new(false, Path::new("."), None);
+        assert!(tracker
+            .check(AccessKind::Write, Path::new("/etc/passwd"))
+            .is_ok());
+    }
+
+    #[test]
+    fn restricted_denies_out_of_scope() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let cache = tempfile::tempdir()?;
+        let tracker = AccessTracker::new(true, root.path(), Some(cache.path()));
+        tracker.check(AccessKind::Write, &root.path().join("node_modules/foo"))?;
+        tracker.check(AccessKind::Write, &cache.path().join("content-v2/bar"))?;
+        tracker.check(AccessKind::Write, &root.path().join(PNP_FILE_NAME))?;
+        assert!(tracker
+            .check(AccessKind::Write, &root.path().join("package.json"))
+            .is_err());
+        assert!(tracker
+            .check(
+                AccessKind::Chmod,
+                &root.path().join("node_modules/../escape.js")
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restricted_follows_symlinks() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let outside = tempfile::tempdir()?;
+        std::fs::create_dir_all(root.path().join("node_modules"))?;
+        std::os::unix::fs::symlink(outside.path(), root.path().join("node_modules/sneaky"))?;
+        let tracker = AccessTracker::new(true, root.path(), None);
+        assert!(tracker
+            .check(
+                AccessKind::Write,
+                &root.path().join("node_modules/sneaky/x")
+            )
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Support for dependency catalogs: versions defined once in the root
+//! `package.json`, which the root project and its workspace packages can
+//! refer to with `catalog:` specs instead of repeating a version range.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use indexmap::IndexMap;
+use oro_common::CorgiManifest;
+
+use crate::error::NodeMaintainerError;
+
+const SPEC_PREFIX: &str = "catalog:";
+const DEFAULT_CATALOG: &str = "default";
+
+/// All the catalogs available to a project, keyed by catalog name. The
+/// unnamed catalog is stored as `default`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Catalogs {
+    catalogs: IndexMap<String, IndexMap<String, String>>,
+}
+
+impl Catalogs {
+    /// Loads the catalogs for the project at `root`. The `catalog` field in
+    /// the root `package.json` becomes the default catalog, and named
+    /// catalogs come from its `catalogs` field. `configured` entries are
+    /// added to the default catalog, and take precedence over the ones in
+    /// `package.json`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn load(root: &Path, configured: &IndexMap<String, String>) -> Self {
+        let mut catalogs = Self::default();
+        if let Ok(manifest) = async_std::fs::read_to_string(root.join("package.json")).await {
+            if let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&manifest) {
+                if let Some(named) = manifest["catalogs"].as_object() {
+                    for (catalog, entries) in named {
+                        catalogs.extend(catalog, entries);
+                    }
+                }
+                catalogs.extend(DEFAULT_CATALOG, &manifest["catalog"]);
+            }
+        }
+        catalogs.extend_configured(configured);
+        catalogs
+    }
+
+    /// Catalogs made up of only the `configured` entries, for when there's
+    /// no `package.json` to read them from.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn load(configured: &IndexMap<String, String>) -> Self {
+        let mut catalogs = Self::default();
+        catalogs.extend_configured(configured);
+        catalogs
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn extend(&mut self, catalog: &str, entries: &serde_json::Value) {
+        if let Some(entries) = entries.as_object() {
+            let catalog = self.catalogs.entry(catalog.into()).or_default();
+            for (name, spec) in entries {
+                if let Some(spec) = spec.as_str() {
+                    catalog.insert(name.clone(), spec.into());
+                }
+            }
+        }
+    }
+
+    fn extend_configured(&mut self, configured: &IndexMap<String, String>) {
+        if !configured.is_empty() {
+            self.catalogs
+                .entry(DEFAULT_CATALOG.into())
+                .or_default()
+                .extend(configured.clone());
+        }
+    }
+
+    /// Replaces every `catalog:` spec in `manifest`'s dependencies with the
+    /// spec its catalog has for that package.
+    pub(crate) fn apply(
+        &self,
+        mut manifest: CorgiManifest,
+    ) -> Result<CorgiManifest, NodeMaintainerError> {
+        for deps in [
+            &mut manifest.dependencies,
+            &mut manifest.dev_dependencies,
+            &mut manifest.optional_dependencies,
+            &mut manifest.peer_dependencies,
+        ] {
+            for (name, spec) in deps.iter_mut() {
+                if let Some(resolved) = self.resolve(name, spec)? {
+                    *spec = resolved;
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Looks up what a `catalog:` spec for `name` refers to. Returns `None`
+    /// if `spec` isn't a catalog spec at all.
+    fn resolve(&self, name: &str, spec: &str) -> Result<Option<String>, NodeMaintainerError> {
+        let Some(catalog) = spec.strip_prefix(SPEC_PREFIX) else {
+            return Ok(None);
+        };
+        let catalog = match catalog.trim() {
+            "" => DEFAULT_CATALOG,
+            catalog => catalog,
+        };
+        self.catalogs
+            .get(catalog)
+            .and_then(|entries| entries.get(name))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| NodeMaintainerError::MissingCatalogEntry {
+                name: name.into(),
+                catalog: catalog.into(),
+            })
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::graph::DepType;
+
+/// A package whose requirements couldn't all be satisfied by the resolved
+/// tree, along with everything that asked for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolutionConflict {
+    /// Name of the package the conflict is about.
+    pub name: String,
+    /// What ended up being used for this package, if anything. This is
+    /// usually a version, but might be a path or URL for packages that
+    /// didn't come from a registry.
+    pub found: Option<String>,
+    /// Everything that requested this package.
+    pub requesters: Vec<ConflictRequester>,
+}
+
+/// A single request involved in a [`ResolutionConflict`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictRequester {
+    /// Chain of package names leading from the root project to the
+    /// requester, starting with the root's direct dependency. Empty if the
+    /// request came from the root project itself.
+    pub path: Vec<String>,
+    /// The spec that was requested.
+    pub requested: String,
+    /// Which kind of dependency the request was.
+    pub dep_type: DepType,
+    /// Whether [`ResolutionConflict::found`] satisfies this request.
+    pub satisfied: bool,
+}
+
+impl fmt::Display for ResolutionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => writeln!(f, "Found: {}@{found}", self.name)?,
+            None => writeln!(f, "Found: no usable version of {}", self.name)?,
+        }
+        for requester in &self.requesters {
+            let kind = requester.dep_type.label();
+            let from = if requester.path.is_empty() {
+                "the root project".to_string()
+            } else {
+                requester.path.join(" > ")
+            };
+            writeln!(
+                f,
+                "  {} {kind} {}@{} from {from}",
+                if requester.satisfied {
+                    "ok:"
+                } else {
+                    "conflict:"
+                },
+                self.name,
+                requester.requested,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What's wrong with a [`PeerProblem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerProblemKind {
+    /// Nothing in the tree provides the peer.
+    Missing,
+    /// The peer is in the tree, but at a version that doesn't satisfy the
+    /// peer dependency.
+    Invalid,
+    /// The peer is in the tree at a version that doesn't satisfy the peer
+    /// dependency, because something else that depends on it needs that
+    /// version.
+    Conflicting,
+}
+
+/// A peer dependency that the resolved tree doesn't satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerProblem {
+    /// What's wrong with the peer dependency.
+    pub kind: PeerProblemKind,
+    /// Name of the peer package.
+    pub name: String,
+    /// The spec the dependent asked for.
+    pub requested: String,
+    /// Chain of package names leading from the root project to the package
+    /// that declared the peer dependency, ending with that package.
+    pub path: Vec<String>,
+    /// What the dependent would load for the peer, if anything. This is
+    /// usually a version, but might be a path or URL for packages that
+    /// didn't come from a registry.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for PeerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dependent = self.path.last().map(String::as_str).unwrap_or("");
+        write!(
+            f,
+            "{dependent} has a peer dependency on {}@{}, ",
+            self.name, self.requested
+        )?;
+        match (&self.kind, &self.found) {
+            (PeerProblemKind::Missing, _) | (_, None) => writeln!(f, "but it isn't installed.")?,
+            (PeerProblemKind::Invalid, Some(found)) => {
+                writeln!(f, "but found {}@{found}.", self.name)?
+            }
+            (PeerProblemKind::Conflicting, Some(found)) => writeln!(
+                f,
+                "but found {}@{found}, which other packages depend on.",
+                self.name
+            )?,
+        }
+        write!(f, "  pulled in by: {}", self.path.join(" > "))
+    }
+}
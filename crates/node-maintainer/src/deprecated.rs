@@ -0,0 +1,60 @@
+use std::fmt;
+
+use nassun::PackageResolution;
+use oro_common::DeprecationInfo;
+
+use crate::graph::Graph;
+
+/// A deprecated package version in the resolved tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Deprecation {
+    pub name: String,
+    /// The deprecated version, if the package came from a registry.
+    pub version: Option<String>,
+    /// Why it was deprecated, if the author said.
+    pub message: Option<String>,
+    /// Chain of package names leading from the root project to the
+    /// deprecated package, ending with it.
+    pub path: Vec<String>,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{version}")?;
+        }
+        match &self.message {
+            Some(message) => writeln!(f, ": {message}")?,
+            None => writeln!(f)?,
+        }
+        write!(f, "  pulled in by: {}", self.path.join(" > "))
+    }
+}
+
+/// Every deprecated package in `graph`, sorted by name and version.
+pub(crate) fn deprecations(graph: &Graph) -> Vec<Deprecation> {
+    let mut deprecations = graph
+        .inner
+        .node_indices()
+        .filter_map(|idx| {
+            let node = &graph[idx];
+            let message = match node.deprecated.as_ref()? {
+                DeprecationInfo::Reason(reason) => Some(reason.clone()),
+                DeprecationInfo::UnknownReason => None,
+            };
+            Some(Deprecation {
+                name: node.package.name().into(),
+                version: match node.package.resolved() {
+                    PackageResolution::Npm { version, .. } => Some(version.to_string()),
+                    _ => None,
+                },
+                message,
+                path: graph.dependency_chain(idx),
+            })
+        })
+        .collect::<Vec<_>>();
+    deprecations.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    deprecations
+}
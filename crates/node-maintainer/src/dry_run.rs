@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use nassun::package::Package;
+use serde::Serialize;
+
+/// Everything an apply would've done to `node_modules/`, recorded instead of
+/// done when [`crate::NodeMaintainerOptions::dry_run`] is enabled.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct DryRunReport {
+    /// Files and directories that would be removed while pruning.
+    pub removed: Vec<PathBuf>,
+    /// Packages that would be extracted.
+    pub extracted: Vec<PlannedExtraction>,
+    /// Bins that would be linked.
+    pub linked: Vec<PlannedBin>,
+}
+
+/// A package a dry run would've extracted.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PlannedExtraction {
+    /// Where the package would be extracted to.
+    pub path: PathBuf,
+    /// Name of the package.
+    pub name: String,
+    /// Where the package would come from.
+    pub resolved: String,
+}
+
+/// A bin a dry run would've linked.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PlannedBin {
+    /// Where the bin would be linked.
+    pub to: PathBuf,
+    /// The file the bin would point to.
+    pub from: PathBuf,
+}
+
+/// Collects a [`DryRunReport`] while the linkers go through the motions.
+#[derive(Debug, Default)]
+pub(crate) struct DryRun(Mutex<DryRunReport>);
+
+impl DryRun {
+    pub(crate) fn remove(&self, path: &Path) {
+        tracing::debug!("Would remove {}", path.display());
+        self.0.lock().unwrap().removed.push(path.to_path_buf());
+    }
+
+    pub(crate) fn extract(&self, package: &Package, path: &Path) {
+        tracing::debug!("Would extract {} to {}", package.resolved(), path.display());
+        self.0.lock().unwrap().extracted.push(PlannedExtraction {
+            path: path.to_path_buf(),
+            name: package.name().to_string(),
+            resolved: package.resolved().to_string(),
+        });
+    }
+
+    pub(crate) fn link_bin(&self, from: &Path, to: &Path) {
+        tracing::debug!("Would link {} to {}", to.display(), from.display());
+        self.0.lock().unwrap().linked.push(PlannedBin {
+            to: to.to_path_buf(),
+            from: from.to_path_buf(),
+        });
+    }
+
+    /// Everything recorded so far, sorted so the same apply always gets the
+    /// same report.
+    pub(crate) fn report(&self) -> DryRunReport {
+        let mut report = self.0.lock().unwrap().clone();
+        // Nothing gets removed in a dry run, so pruning can wander into
+        // directories that would've already been removed whole. Parents
+        // sort before their children, so those are easy to drop.
+        report.removed.sort();
+        let mut removed: Vec<PathBuf> = Vec::with_capacity(report.removed.len());
+        for path in report.removed {
+            if !removed.iter().any(|parent| path.starts_with(parent)) {
+                removed.push(path);
+            }
+        }
+        report.removed = removed;
+        report.extracted.sort();
+        report.linked.sort();
+        report
+    }
+}
@@ -3,92 +3,153 @@ use kdl::{KdlDocument, KdlNode};
 use miette::Diagnostic;
 use thiserror::Error;
 
-use crate::{NpmPackageLock, NpmPackageLockEntry};
+use crate::{NpmPackageLock, NpmPackageLockEntry, PolicyViolation, ResolutionConflict};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Error, Diagnostic)]
 pub enum NodeMaintainerError {
     /// Unsupported resolved URL scheme
     #[error("Unsupported resolved URL scheme")]
-    #[diagnostic(code(node_maintainer::kdl::unsupported_url_scheme), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::unsupported_url_scheme),
+        url(docsrs),
+        help("Lockfile entries can only be resolved to registry tarballs, git repositories, or local paths. Remove the lockfile entry to resolve it again.")
+    )]
     UnsupportedScheme(String),
 
     /// Failed to parse a resolved URL while parsing lockfile
     #[error("Failed to parse a resolved URL while parsing lockfile: {0}")]
-    #[diagnostic(code(node_maintainer::kdl::url_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::url_parse_error),
+        url(docsrs),
+        help("The lockfile may have been edited by hand, or damaged. Remove `package-lock.kdl` to resolve the tree again from scratch.")
+    )]
     UrlParseError(String, #[source] url::ParseError),
 
     /// Failed to parse a Semver string.
     #[error("Failed to parse a Semver string.")]
-    #[diagnostic(code(node_maintainer::kdl::semver_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::semver_parse_error),
+        url(docsrs),
+        help("Make sure the version or range is valid semver, like `1.2.3` or `^1.2.0`.")
+    )]
     SemverParseError(#[from] node_semver::SemverError),
 
     /// Missing version for NPM package entry in lockfile.
     #[error("Missing version for NPM package entry in lockfile.")]
-    #[diagnostic(code(node_maintainer::kdl::missing_version), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::missing_version),
+        url(docsrs),
+        help("Registry packages in the lockfile need a `version`. Remove `package-lock.kdl` to resolve the tree again from scratch.")
+    )]
     MissingVersion,
 
     /// Missing resolution for package entry in lockfile.
-    #[error("Missing version for NPM package entry in lockfile.")]
-    #[diagnostic(code(node_maintainer::kdl::missing_version), url(docsrs))]
+    #[error("Missing resolution for package entry in lockfile.")]
+    #[diagnostic(
+        code(node_maintainer::kdl::missing_resolution),
+        url(docsrs),
+        help("Packages in the lockfile need a `resolved` field. Remove `package-lock.kdl` to resolve the tree again from scratch.")
+    )]
     MissingResolution,
 
     /// Failed to parse an integrity value.
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::kdl::integrity_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::integrity_parse_error),
+        url(docsrs),
+        help("Integrity values should be Subresource Integrity strings, like `sha512-...`.")
+    )]
     IntegrityParseError(#[from] ssri::Error),
 
     /// Failed to parse an integrity value while loading lockfile.
     #[error("Failed to parse an integrity value while loading lockfile node:\n{0}")]
-    #[diagnostic(code(node_maintainer::kdl::integrity_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::integrity_parse_error),
+        url(docsrs),
+        help("Integrity values should be Subresource Integrity strings, like `sha512-...`. Remove the package's `integrity` from `package-lock.kdl` to fetch it again.")
+    )]
     KdlLockfileIntegrityParseError(KdlNode, #[source] ssri::Error),
 
     /// Missing package node name.
     #[error("Missing package node name:\n{0}")]
-    #[diagnostic(code(node_maintainer::kdl::missing_node_name), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::missing_node_name),
+        url(docsrs),
+        help("Every package node in `package-lock.kdl` needs a name. Remove `package-lock.kdl` to resolve the tree again from scratch.")
+    )]
     KdlLockMissingName(KdlNode),
 
     /// Missing package node name.
     #[error("Missing package name:\n{0:#?}")]
-    #[diagnostic(code(node_maintainer::npm::missing_name), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::npm::missing_name),
+        url(docsrs),
+        help("Remove `package-lock.json` to resolve the tree again from scratch, or regenerate it with npm.")
+    )]
     NpmLockMissingName(Box<NpmPackageLockEntry>),
 
     /// Failed to parse an integrity value while loading NPM lockfile.
     #[error("Failed to parse an integrity value while loading lockfile node:\n{0:#?}")]
-    #[diagnostic(code(node_maintainer::npm::integrity_parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::npm::integrity_parse_error),
+        url(docsrs),
+        help("Integrity values should be Subresource Integrity strings, like `sha512-...`. Regenerate `package-lock.json` with npm, or remove it.")
+    )]
     NpmLockfileIntegrityParseError(Box<NpmPackageLockEntry>, #[source] ssri::Error),
 
     /// Unsupported NPM Package Lock version.
     #[error("Unsupported NPM Package Lock version: {0}")]
     #[diagnostic(
         code(node_maintainer::npm::unsupported_package_lock_Version),
-        url(docsrs)
+        url(docsrs),
+        help("Regenerate `package-lock.json` with a recent version of npm, or remove it.")
     )]
     NpmUnsupportedPackageLockVersion(u64),
 
     /// No root node in KDL lockfile.
     #[error("No root node in KDL lockfile.")]
-    #[diagnostic(code(node_maintainer::kdl::missing_root), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::missing_root),
+        url(docsrs),
+        help("`package-lock.kdl` needs a `root` node. Remove it to resolve the tree again from scratch.")
+    )]
     KdlLockMissingRoot(KdlDocument),
 
     /// No root node in NPM lockfile.
     #[error("No root package in NPM lockfile.")]
-    #[diagnostic(code(node_maintainer::npm::missing_root), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::npm::missing_root),
+        url(docsrs),
+        help("`package-lock.json` needs a `\"\"` entry under `packages`. Regenerate it with npm 7 or later, or remove it.")
+    )]
     NpmLockMissingRoot(NpmPackageLock),
 
     /// Error parsing lockfile.
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::kdl::parse_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::parse_error),
+        url(docsrs),
+        help("`package-lock.kdl` isn't valid KDL. If it has merge conflict markers in it, resolve them, or remove it to resolve the tree again from scratch.")
+    )]
     KdlParseError(#[from] kdl::KdlError),
 
     #[error("Invalid lockfile version format.")]
-    #[diagnostic(code(node_maintainer::kdl::invalid_lockfile_version), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::kdl::invalid_lockfile_version),
+        url(docsrs),
+        help("The lockfile's `lockfile-version` should be a number. Remove `package-lock.kdl` to resolve the tree again from scratch.")
+    )]
     InvalidLockfileVersion,
 
     /// Error from serde_wasm_bindgen
     #[cfg(target_arch = "wasm32")]
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::serde_wasm_bindgen::error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::serde_wasm_bindgen::error),
+        url(docsrs),
+        help("Make sure the options passed in from JavaScript have the right shape.")
+    )]
     SerdeWasmBindgenError(#[from] serde_wasm_bindgen::Error),
 
     /// Generic package spec error.
@@ -96,9 +157,22 @@ pub enum NodeMaintainerError {
     #[diagnostic(transparent)]
     PackageSpecError(#[from] oro_package_spec::PackageSpecError),
 
+    /// Writing an install into a [`crate::Vfs`] failed.
+    #[error("Failed to write {} to the virtual filesystem.", .0.display())]
+    #[diagnostic(
+        code(node_maintainer::vfs_error),
+        url(docsrs),
+        help("Make sure the path doesn't collide with a file that's already in the virtual filesystem.")
+    )]
+    VfsError(std::path::PathBuf, #[source] std::io::Error),
+
     /// Generic IO Error.
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::io_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::io_error),
+        url(docsrs),
+        help("Check that the paths involved exist and that you have permission to read and write them.")
+    )]
     IoError(#[from] std::io::Error),
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -115,37 +189,61 @@ pub enum NodeMaintainerError {
 
     /// Generic serde_json error.
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::serde_json_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::serde_json_error),
+        url(docsrs),
+        help("Make sure the file being read is valid JSON.")
+    )]
     SerdeJsonError(#[from] serde_json::Error),
 
     /// Generic error. Refer to the error message for more details.
     #[error("{0}")]
-    #[diagnostic(code(node_maintainer::miscellaneous_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::miscellaneous_error),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     MiscError(String),
 
     /// Failed to send data through mpsc channel. This is likely an internal
     /// error of some sort.
     #[error("Failed to send data through mpsc channel.")]
-    #[diagnostic(code(node_maintainer::mpsc_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::mpsc_error),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     TrySendError,
 
     /// Failed to validate a graph. Refer to the error message for more details.
     #[error("{0}")]
-    #[diagnostic(code(node_maintainer::graph_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::graph_error),
+        url(docsrs),
+        help("This is likely a bug in Orogene. Please report it at https://github.com/orogene/orogene/issues/new.")
+    )]
     GraphValidationError(String),
 
     /// Got an error while walking `node_modules`. Refer to the error message
     /// for specific details.
     #[cfg(not(target_arch = "wasm32"))]
     #[error(transparent)]
-    #[diagnostic(code(node_maintainer::walkdir_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::walkdir_error),
+        url(docsrs),
+        help("Check that you have permission to read everything in `node_modules/`.")
+    )]
     WalkDirError(#[from] walkdir::Error),
 
     /// Failed to read `package.json` during the build step. Refer to the
     /// error message for more details.
     #[cfg(not(target_arch = "wasm32"))]
     #[error("Failed to read manifest during build step, at {}", .0.display())]
-    #[diagnostic(code(node_maintainer::build_manifest_read_error), url(docsrs))]
+    #[diagnostic(
+        code(node_maintainer::build_manifest_read_error),
+        url(docsrs),
+        help("The package may not have been extracted properly. Try `oro apply --repair`, or remove `node_modules/` and apply again.")
+    )]
     BuildManifestReadError(std::path::PathBuf, #[source] std::io::Error),
 
     /// Some error occurred while running a script. Refer to the error message
@@ -155,6 +253,272 @@ pub enum NodeMaintainerError {
     #[diagnostic(transparent)]
     OroScriptError(#[from] oro_script::OroScriptError),
 
+    /// A package's install script ran for longer than the configured script
+    /// timeout, so it was killed, along with anything it started.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("The `{event}` script for {name} timed out after {}s, and was killed.", .timeout.as_secs())]
+    #[diagnostic(
+        code(node_maintainer::script_timeout),
+        url(docsrs),
+        help("If the script just needs more time, raise `--script-timeout` or `--total-script-timeout`. Otherwise, it may be waiting for input, or stuck.")
+    )]
+    ScriptTimeout {
+        name: String,
+        event: String,
+        timeout: std::time::Duration,
+    },
+
+    /// A package's install script failed. When there's a cache, everything
+    /// the script printed, along with a summary of the environment it ran
+    /// in, gets written to a log file in the cache's `_logs/` directory, so
+    /// there's no need to run it again with debug logging to see what went
+    /// wrong.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("The `{event}` script for {name} failed.")]
+    #[diagnostic(code(node_maintainer::script_failed), url(docsrs))]
+    ScriptFailed {
+        name: String,
+        event: String,
+        /// Where the script's full output was written, if anywhere.
+        log: Option<std::path::PathBuf>,
+        #[help]
+        help: Option<String>,
+        #[source]
+        source: Box<NodeMaintainerError>,
+    },
+
+    /// Least-privilege mode is enabled, and something tried to modify a path
+    /// outside of `node_modules/` or the cache.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Denied out-of-scope {0} of `{}` while in least-privilege mode.", .1.display())]
+    #[diagnostic(
+        code(node_maintainer::out_of_scope_access),
+        url(docsrs),
+        help("Only `node_modules/` and the cache may be modified in least-privilege mode. If this was caused by a package, please report it to its maintainers.")
+    )]
+    OutOfScopeAccess(String, std::path::PathBuf),
+
+    /// An invalid value was given for the hook scripts mode.
+    #[error("Invalid hook scripts mode: `{0}`. Expected one of `auto`, `run`, or `skip`.")]
+    #[diagnostic(
+        code(node_maintainer::invalid_hook_scripts),
+        url(docsrs),
+        help("Use one of `auto`, `run`, or `skip`.")
+    )]
+    InvalidHookScripts(String),
+
+    /// An invalid value was given for which dependencies may run install
+    /// scripts.
+    #[error("Invalid allow scripts mode: `{0}`. Expected one of `trusted` or `all`.")]
+    #[diagnostic(
+        code(node_maintainer::invalid_allow_scripts),
+        url(docsrs),
+        help("Use one of `trusted` or `all`.")
+    )]
+    InvalidAllowScripts(String),
+
+    /// An invalid value was given for the resolution mode.
+    #[error(
+        "Invalid resolution mode: `{0}`. Expected one of `highest`, `lowest`, or `lowest-direct`."
+    )]
+    #[diagnostic(
+        code(node_maintainer::invalid_resolution_mode),
+        url(docsrs),
+        help("Use one of `highest`, `lowest`, or `lowest-direct`.")
+    )]
+    InvalidResolutionMode(String),
+
+    /// An invalid value was given for the node linker.
+    #[error("Invalid node linker: `{0}`. Expected one of `isolated`, `hoisted`, or `pnp`.")]
+    #[diagnostic(
+        code(node_maintainer::invalid_node_linker),
+        url(docsrs),
+        help("Use one of `isolated`, `hoisted`, or `pnp`.")
+    )]
+    InvalidNodeLinker(String),
+
+    /// An invalid value was given for a link strategy.
+    #[error("Invalid link strategy: `{0}`. Expected one of `reflink`, `clonefile`, `hardlink`, or `copy`.")]
+    #[diagnostic(
+        code(node_maintainer::invalid_link_strategy),
+        url(docsrs),
+        help("Use one of `reflink`, `clonefile`, `hardlink`, or `copy`.")
+    )]
+    InvalidLinkStrategy(String),
+
+    /// An invalid value was given for the script output mode.
+    #[error(
+        "Invalid script output mode: `{0}`. Expected one of `progress`, `prefixed`, or `grouped`."
+    )]
+    #[diagnostic(
+        code(node_maintainer::invalid_script_output_mode),
+        url(docsrs),
+        help("Use one of `progress`, `prefixed`, or `grouped`.")
+    )]
+    InvalidScriptOutputMode(String),
+
+    /// More than one package wants to link a bin with the same name into
+    /// the same `.bin/` directory, and bin collisions are configured to be
+    /// errors.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("The `{name}` bin in {} is provided by more than one package: {}", .bin_dir.display(), .packages.join(", "))]
+    #[diagnostic(
+        code(node_maintainer::bin_collision),
+        url(docsrs),
+        help("Turn off `--strict-bins` to link the bin from the first package listed instead, and only warn about the collision. Direct dependencies win over transitive ones, and ties go to whichever package name sorts first.")
+    )]
+    BinCollision {
+        name: String,
+        bin_dir: std::path::PathBuf,
+        packages: Vec<String>,
+    },
+
+    /// `node_modules/` was installed by another package manager, and
+    /// [`crate::NodeMaintainerOptions::takeover`] wasn't enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("{} was installed by {manager}, and won't be touched.", .path.display())]
+    #[diagnostic(
+        code(node_maintainer::foreign_layout),
+        url(docsrs),
+        help(
+            "Pass `--takeover` to remove it and install again from scratch, or remove it yourself."
+        )
+    )]
+    ForeignLayout {
+        manager: String,
+        path: std::path::PathBuf,
+    },
+
+    /// Failed to read a patch file listed in `patchedDependencies`.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to read patch file at {}", .0.display())]
+    #[diagnostic(
+        code(node_maintainer::patch::read_error),
+        url(docsrs),
+        help("Make sure the patch file listed in `patchedDependencies` exists, relative to the project root.")
+    )]
+    PatchReadError(std::path::PathBuf, #[source] std::io::Error),
+
+    /// A patch file listed in `patchedDependencies` isn't a valid unified
+    /// diff.
+    #[error("Failed to parse patch file at {}:{1}: {2}", .0.display())]
+    #[diagnostic(
+        code(node_maintainer::patch::parse_error),
+        url(docsrs),
+        help("Patches should be unified diffs, such as the ones generated by `git diff`.")
+    )]
+    PatchParseError(std::path::PathBuf, usize, String),
+
+    /// A key in `patchedDependencies` is neither a package name nor a
+    /// `<name>@<semver range>` pair.
+    #[error("Invalid patchedDependencies entry: `{0}`. Expected a package name, optionally followed by `@<semver range>`.")]
+    #[diagnostic(
+        code(node_maintainer::patch::invalid_spec),
+        url(docsrs),
+        help("Use a package name, like `foo`, or a name and a semver range, like `foo@^1.2.0`.")
+    )]
+    InvalidPatchSpec(String),
+
+    /// A patch didn't apply cleanly to the package it's configured for.
+    /// This usually means the package has been updated since the patch was
+    /// written.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to apply patch {} to `{2}` in {1}.", .0.display())]
+    #[diagnostic(
+        code(node_maintainer::patch::apply_error),
+        url(docsrs),
+        help("The patch no longer matches the package's contents. If the package was updated, regenerate the patch against the new version.")
+    )]
+    PatchApplyError(std::path::PathBuf, String, String),
+
+    /// A dependency uses a `catalog:` spec, but the catalog it refers to
+    /// doesn't have an entry for that package.
+    #[error("No entry for `{name}` in the `{catalog}` catalog.")]
+    #[diagnostic(
+        code(node_maintainer::catalog::missing_entry),
+        url(docsrs),
+        help("Add `{name}` to the `catalog` (or `catalogs.{catalog}`) field of your root package.json.")
+    )]
+    MissingCatalogEntry { name: String, catalog: String },
+
+    /// A rule in the project's package policy isn't valid.
+    #[error("Invalid package policy rule: `{0}`")]
+    #[diagnostic(
+        code(node_maintainer::policy::invalid_rule),
+        url(docsrs),
+        help("Policies have `deny`, `allow`, and `require` sections. `deny` takes `package \"<name>\" [\"<semver range>\"]` and `license \"<id>\"...` rules, `allow` only takes `license` rules, and `require` takes an `integrity \"sha256\"|\"sha384\"|\"sha512\"` rule.")
+    )]
+    InvalidPolicyRule(KdlNode),
+
+    /// A package in the dependency tree is blocked by the project's package
+    /// policy. The error names the dependency path that pulled it in.
+    #[error("Package blocked by policy: {0}")]
+    #[diagnostic(
+        code(node_maintainer::policy::violation),
+        url(docsrs),
+        help("Remove or replace the dependency that pulls this package in, or update the project's `oro-policy.kdl`.")
+    )]
+    PolicyViolation(Box<PolicyViolation>),
+
+    /// A package that's required to have provenance doesn't have any, or
+    /// has provenance that isn't about it. The error names the dependency
+    /// path that pulled it in.
+    #[error("Package is missing required provenance: {0}")]
+    #[diagnostic(
+        code(node_maintainer::policy::provenance_required),
+        url(docsrs),
+        help("Use a version of the package that was published with provenance, or stop requiring provenance for it.")
+    )]
+    ProvenanceRequired(Box<PolicyViolation>),
+
+    /// The project's `.oro/` state directory was written by a newer version
+    /// of Orogene, with a layout this version doesn't understand.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("State directory at {} uses layout version {1}, which is newer than this version of Orogene supports.", .0.display())]
+    #[diagnostic(
+        code(node_maintainer::state::unsupported_version),
+        url(docsrs),
+        help("Upgrade Orogene, or run `oro clean-state` to reset the project's state.")
+    )]
+    UnsupportedStateVersion(std::path::PathBuf, u64),
+
+    /// A workspace package was asked for by name, but the project has no
+    /// workspace package with that name.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("No workspace package named `{0}` was found.")]
+    #[diagnostic(
+        code(node_maintainer::unknown_workspace),
+        url(docsrs),
+        help("Make sure the package is listed in the `workspaces` field of your root package.json, and that it has a name and a valid version.")
+    )]
+    UnknownWorkspace(String),
+
+    /// A package's peer dependency is satisfied by a different version of
+    /// that package than the one the peer dependency asks for. The error
+    /// lists everything that requested the package, and whether the version
+    /// that ended up in the tree works for it.
+    ///
+    /// This usually means two packages in the tree want incompatible
+    /// versions of a shared peer, such as a plugin that hasn't been updated
+    /// for a new major version of its host package yet.
+    #[error("Conflicting peer dependency on `{}`.\n{0}", .0.name)]
+    #[diagnostic(
+        code(node_maintainer::peer_conflict),
+        url(docsrs),
+        help("Change the requested versions so they have some overlap, or use `--force` to install anyway, at the risk of a broken install.")
+    )]
+    PeerConflict(Box<ResolutionConflict>),
+
+    /// None of the published versions of a package satisfy what was
+    /// requested. The error lists everything that requested that spec.
+    #[error("No version of `{}` satisfies what was requested.\n{0}", .0.name)]
+    #[diagnostic(
+        code(node_maintainer::no_matching_version),
+        url(docsrs),
+        help("Make sure the requested versions have actually been published, for example using `oro view`, and update the requesting package if they haven't.")
+    )]
+    NoMatchingVersion(Box<ResolutionConflict>),
+
     /// Locked file was requested, but a new dependency tree was resolved that
     /// would cause changes to the lockfile. The contents of `package.json`
     /// may have changed since the last time the lockfile was updated.
@@ -171,6 +535,30 @@ pub enum NodeMaintainerError {
         help("Did you modify package.json by hand?")
     )]
     LockfileMismatch,
+
+    /// Resolving the same dependency tree twice produced different results,
+    /// with package metadata processed in a different order the second
+    /// time. The error lists every package that ended up different, and the
+    /// seed the second resolution was shuffled with, so it can be
+    /// reproduced.
+    #[error("Dependency resolution isn't deterministic: resolving again with shuffle seed {seed} produced a different tree.\n{}", .differences.join("\n"))]
+    #[diagnostic(
+        code(node_maintainer::nondeterministic_resolution),
+        url(docsrs),
+        help("The resolved tree depends on the order package metadata arrives in. An up-to-date `package-lock.kdl` pins it down. If this happens even with one, please report it as a bug, including the seed.")
+    )]
+    NondeterministicResolution { seed: u64, differences: Vec<String> },
+
+    /// Prefetching downloads packages into the cache, so there has to be
+    /// one.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Can't prefetch packages without a cache directory.")]
+    #[diagnostic(
+        code(node_maintainer::prefetch_without_cache),
+        url(docsrs),
+        help("Set a cache directory with `NodeMaintainerOptions::cache`.")
+    )]
+    PrefetchWithoutCache,
 }
 
 impl<T> From<mpsc::TrySendError<T>> for NodeMaintainerError {
@@ -0,0 +1,37 @@
+//! Progress events for everything a [`crate::NodeMaintainer`] does, all
+//! delivered to a single handler. See
+//! [`crate::NodeMaintainerOptions::on_event`].
+
+use std::path::Path;
+
+use nassun::package::Package;
+
+use crate::ExtractBytes;
+
+/// Something that happened while resolving or installing. New kinds of
+/// events may be added in minor releases, so matches on this should have a
+/// catch-all arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum InstallEvent<'a> {
+    /// A dependency was found that still needs to be resolved.
+    ResolutionAdded,
+    /// A package was resolved.
+    Resolved(&'a Package),
+    /// Something that didn't belong in `node_modules/` was removed.
+    Pruned(&'a Path),
+    /// A package finished extracting.
+    Extracted(&'a Package),
+    /// More of a package was extracted.
+    ExtractBytes {
+        package: &'a Package,
+        bytes: &'a ExtractBytes,
+    },
+    /// A package's install script started running.
+    ScriptStarted {
+        package: &'a Package,
+        event: &'a str,
+    },
+    /// An install script printed a line.
+    ScriptLine(&'a str),
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+
+use nassun::PackageSpec;
+use node_semver::Version;
+use oro_common::CorgiPackument;
+use oro_package_spec::VersionSpec;
+use petgraph::stable_graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::Serialize;
+use unicase::UniCase;
+
+use crate::conflict::ConflictRequester;
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+
+/// Why a single copy of a package in the resolved tree ended up at the
+/// version it did. See [`crate::NodeMaintainer::explain`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ResolutionExplanation {
+    pub name: String,
+    /// What was picked. This is usually a version, but might be a path or
+    /// URL for packages that didn't come from a registry.
+    pub picked: String,
+    /// Where this copy gets placed, relative to the project root.
+    pub location: String,
+    /// Everything that depends on this copy.
+    pub requesters: Vec<ConflictRequester>,
+    /// Published versions that at least one of the requesters would accept,
+    /// oldest first. Empty for packages that didn't come from a registry.
+    pub candidates: Vec<Candidate>,
+    /// Why this version won, if the resolver was recording it. See
+    /// [`crate::NodeMaintainerOptions::explain_resolution`].
+    pub reason: Option<String>,
+}
+
+/// A published version considered for a [`ResolutionExplanation`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub version: String,
+    /// Whether every requester would accept this version.
+    pub satisfies_all: bool,
+    /// Whether this is the version that was picked.
+    pub picked: bool,
+}
+
+impl fmt::Display for ResolutionExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}@{} at {}", self.name, self.picked, self.location)?;
+        for requester in &self.requesters {
+            let from = if requester.path.is_empty() {
+                "the root project".to_string()
+            } else {
+                requester.path.join(" > ")
+            };
+            let kind = requester.dep_type.label();
+            writeln!(
+                f,
+                "  {} {kind} {}@{} from {from}",
+                if requester.satisfied {
+                    "ok:"
+                } else {
+                    "unsatisfied:"
+                },
+                self.name,
+                requester.requested,
+            )?;
+        }
+        if !self.candidates.is_empty() {
+            let matching = self
+                .candidates
+                .iter()
+                .filter(|candidate| candidate.satisfies_all)
+                .map(|candidate| {
+                    if candidate.picked {
+                        format!("[{}]", candidate.version)
+                    } else {
+                        candidate.version.clone()
+                    }
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "  {} of {} candidate versions satisfy every requester: {}",
+                matching.len(),
+                self.candidates.len(),
+                if matching.is_empty() {
+                    "none".into()
+                } else {
+                    matching.join(", ")
+                }
+            )?;
+        }
+        match &self.reason {
+            Some(reason) => write!(f, "  why: {reason}"),
+            None => write!(f, "  why: unknown, since the resolver wasn't recording it"),
+        }
+    }
+}
+
+/// How the resolver came to place a node, recorded when
+/// [`crate::NodeMaintainerOptions::explain_resolution`] is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Origin {
+    /// Kept as-is from the lockfile.
+    Lockfile,
+    /// Kept as-is from what's already installed in `node_modules/`.
+    Installed,
+    /// Fetched fresh for `requested`, picking the lowest matching version
+    /// instead of the highest if `lowest` is set. `cutoff` is set if
+    /// versions published too recently were skipped.
+    Resolved {
+        requested: PackageSpec,
+        lowest: bool,
+        cutoff: bool,
+    },
+}
+
+/// Explains every copy of `name` in `graph`, closest to the root first.
+pub(crate) async fn explain(
+    graph: &Graph,
+    name: &str,
+) -> Result<Vec<ResolutionExplanation>, NodeMaintainerError> {
+    let name = UniCase::new(name.to_string());
+    let mut nodes = graph
+        .inner
+        .node_indices()
+        .filter(|idx| *idx != graph.root && UniCase::new(graph[*idx].package.name()) == name)
+        .collect::<Vec<_>>();
+    nodes.sort_by_key(|idx| graph.node_path(*idx).len());
+    let mut explanations = Vec::new();
+    for idx in nodes {
+        explanations.push(explain_node(graph, idx).await?);
+    }
+    Ok(explanations)
+}
+
+async fn explain_node(
+    graph: &Graph,
+    idx: NodeIndex,
+) -> Result<ResolutionExplanation, NodeMaintainerError> {
+    let node = &graph[idx];
+    let package = &node.package;
+    let mut requesters = Vec::new();
+    let mut specs = Vec::new();
+    for edge_ref in graph.inner.edges_directed(idx, Direction::Incoming) {
+        let edge = edge_ref.weight();
+        requesters.push(ConflictRequester {
+            path: graph.dependency_chain(edge_ref.source()),
+            requested: edge.requested.requested().clone(),
+            dep_type: edge.dep_type.clone(),
+            satisfied: package.satisfies(&edge.requested)?,
+        });
+        specs.push(edge.requested.clone());
+    }
+
+    let picked = package.resolved().npm_version();
+    let packument = if picked.is_some() {
+        Some(package.corgi_packument().await?)
+    } else {
+        None
+    };
+    let mut candidates = Vec::new();
+    if let (Some(picked), Some(packument)) = (&picked, &packument) {
+        let mut versions = packument.versions.keys().collect::<Vec<_>>();
+        versions.sort();
+        for version in versions {
+            let accepted = specs
+                .iter()
+                .filter(|spec| accepts(spec, version, packument))
+                .count();
+            if accepted > 0 {
+                candidates.push(Candidate {
+                    version: version.to_string(),
+                    satisfies_all: accepted == specs.len(),
+                    picked: version == picked,
+                });
+            }
+        }
+    }
+
+    let location = graph
+        .node_path(idx)
+        .iter()
+        .map(|name| format!("node_modules/{name}"))
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok(ResolutionExplanation {
+        name: package.name().into(),
+        picked: match &picked {
+            Some(version) => version.to_string(),
+            None => package.resolved().to_string(),
+        },
+        location,
+        requesters,
+        reason: node
+            .origin
+            .as_ref()
+            .map(|origin| reason(origin, picked.as_ref(), packument.as_deref())),
+        candidates,
+    })
+}
+
+/// Whether `spec` would accept `version`, going by `packument`.
+fn accepts(spec: &PackageSpec, version: &Version, packument: &CorgiPackument) -> bool {
+    match spec.target() {
+        PackageSpec::Npm {
+            requested: Some(VersionSpec::Range(range)),
+            ..
+        } => range.satisfies(version),
+        PackageSpec::Npm {
+            requested: Some(VersionSpec::Version(wanted)),
+            ..
+        } => wanted == version,
+        PackageSpec::Npm {
+            requested: Some(VersionSpec::Tag(tag)),
+            ..
+        } => packument.tags.get(tag.as_str()) == Some(version),
+        PackageSpec::Npm {
+            requested: None, ..
+        } => true,
+        _ => false,
+    }
+}
+
+fn reason(origin: &Origin, picked: Option<&Version>, packument: Option<&CorgiPackument>) -> String {
+    let (requested, lowest, cutoff) = match origin {
+        Origin::Lockfile => return "kept from the lockfile".into(),
+        Origin::Installed => return "kept what was already installed in node_modules/".into(),
+        Origin::Resolved {
+            requested,
+            lowest,
+            cutoff,
+        } => (requested, *lowest, *cutoff),
+    };
+    let why = match (requested.target(), picked, packument) {
+        (PackageSpec::Dir { .. } | PackageSpec::Link { .. }, ..) => {
+            "it points at a local directory".into()
+        }
+        (PackageSpec::Git(_), ..) => "it points at a git repository".into(),
+        (PackageSpec::Url { .. }, ..) => "it points at a tarball URL".into(),
+        (
+            PackageSpec::Npm {
+                requested: Some(VersionSpec::Version(_)),
+                ..
+            },
+            ..,
+        ) => "it asks for exactly this version".into(),
+        (
+            PackageSpec::Npm {
+                requested: Some(VersionSpec::Tag(tag)),
+                ..
+            },
+            ..,
+        ) => format!("the `{tag}` dist-tag points to this version"),
+        (
+            PackageSpec::Npm {
+                requested: None, ..
+            },
+            ..,
+        ) => "no version was asked for, so the default dist-tag was used".into(),
+        (
+            PackageSpec::Npm {
+                requested: Some(VersionSpec::Range(range)),
+                ..
+            },
+            Some(picked),
+            Some(packument),
+        ) => {
+            let matching = packument
+                .versions
+                .keys()
+                .filter(|version| range.satisfies(version));
+            let tag = packument
+                .tags
+                .iter()
+                .find(|(_, version)| *version == picked)
+                .map(|(tag, _)| tag);
+            if lowest {
+                "it's the lowest version in range, since the resolution mode asks for the lowest"
+                    .into()
+            } else if matching.max() == Some(picked) {
+                "it's the highest version in range".into()
+            } else if let Some(tag) = tag {
+                format!("the `{tag}` dist-tag points to it, and dist-tags are preferred when they're in range")
+            } else if cutoff {
+                "newer versions in range were skipped for being published too recently".into()
+            } else {
+                "it's in range".into()
+            }
+        }
+        _ => "it's in range".into(),
+    };
+    format!("resolved for `{requested}`: {why}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn packument() -> CorgiPackument {
+        serde_json::from_value(json!({
+            "versions": {
+                "1.0.0": { "name": "b", "version": "1.0.0" },
+                "1.1.0": { "name": "b", "version": "1.1.0" },
+            },
+        }))
+        .unwrap()
+    }
+
+    fn resolved(cutoff: bool) -> Origin {
+        Origin::Resolved {
+            requested: "b@^1.0.0".parse().unwrap(),
+            lowest: false,
+            cutoff,
+        }
+    }
+
+    #[test]
+    fn skipped_for_release_cutoff() {
+        let picked = "1.0.0".parse().unwrap();
+        assert!(reason(&resolved(true), Some(&picked), Some(&packument()))
+            .ends_with("newer versions in range were skipped for being published too recently"));
+    }
+
+    #[test]
+    fn in_range_without_release_cutoff() {
+        let picked = "1.0.0".parse().unwrap();
+        assert!(reason(&resolved(false), Some(&picked), Some(&packument()))
+            .ends_with(": it's in range"));
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::package::Package;
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::ExtractProgress;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ExtractBytesHandler;
+
+/// How many bytes have been processed while extracting packages. See
+/// [`crate::NodeMaintainerOptions::on_extract_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractBytes {
+    /// Bytes processed since the last update for this package.
+    pub bytes: u64,
+    /// Bytes processed for this package so far.
+    pub package_bytes: u64,
+    /// Bytes processed for every package so far.
+    pub total_bytes: u64,
+    /// Time since the first bytes of any package were processed.
+    pub elapsed: Duration,
+}
+
+impl ExtractBytes {
+    /// Overall throughput so far, in bytes per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.total_bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Keeps the running totals reported in [`ExtractBytes`], across everything
+/// that extracts packages: the pipeline, the linkers, and the global store.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ExtractByteCounter {
+    handler: ExtractBytesHandler,
+    started: Mutex<Option<Instant>>,
+    total: AtomicU64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ExtractByteCounter {
+    pub(crate) fn new(handler: ExtractBytesHandler) -> Self {
+        Self {
+            handler,
+            started: Mutex::new(None),
+            total: AtomicU64::new(0),
+        }
+    }
+
+    /// A callback to extract `package` with, which reports its progress to
+    /// the handler.
+    pub(crate) fn for_package(self: &Arc<Self>, package: &Package) -> ExtractProgress {
+        let counter = self.clone();
+        let package = package.clone();
+        let package_bytes = AtomicU64::new(0);
+        Arc::new(move |bytes| {
+            let started = *counter
+                .started
+                .lock()
+                .unwrap()
+                .get_or_insert_with(Instant::now);
+            let package_bytes = package_bytes.fetch_add(bytes, Ordering::SeqCst) + bytes;
+            let total_bytes = counter.total.fetch_add(bytes, Ordering::SeqCst) + bytes;
+            (counter.handler)(
+                &package,
+                &ExtractBytes {
+                    bytes,
+                    package_bytes,
+                    total_bytes,
+                    elapsed: started.elapsed(),
+                },
+            );
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nassun::Nassun;
+    use oro_common::CorgiManifest;
+
+    #[test]
+    fn counts_per_package_and_overall() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_ref = seen.clone();
+        let counter = Arc::new(ExtractByteCounter::new(Arc::new(move |_, bytes| {
+            seen_ref.lock().unwrap().push(*bytes);
+        })));
+        let package = Nassun::dummy_from_manifest(CorgiManifest::default());
+        let a = counter.for_package(&package);
+        let b = counter.for_package(&package);
+        a(10);
+        b(5);
+        a(20);
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            seen.iter()
+                .map(|bytes| (bytes.bytes, bytes.package_bytes, bytes.total_bytes))
+                .collect::<Vec<_>>(),
+            vec![(10, 10, 10), (5, 5, 15), (20, 30, 35)]
+        );
+    }
+
+    #[test]
+    fn throughput() {
+        let bytes = ExtractBytes {
+            bytes: 0,
+            package_bytes: 0,
+            total_bytes: 3000,
+            elapsed: Duration::from_millis(1500),
+        };
+        assert_eq!(bytes.bytes_per_second(), 2000.0);
+        let bytes = ExtractBytes {
+            elapsed: Duration::ZERO,
+            ..bytes
+        };
+        assert_eq!(bytes.bytes_per_second(), 0.0);
+    }
+}
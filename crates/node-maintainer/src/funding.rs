@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use futures::{StreamExt, TryStreamExt};
+use nassun::PackageResolution;
+use oro_common::Manifest;
+
+use crate::graph::Graph;
+use crate::linkers::Linker;
+use crate::NodeMaintainerError;
+
+/// A package in the resolved tree that's looking for funding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FundingInfo {
+    pub name: String,
+    /// The package's version, if it came from a registry.
+    pub version: Option<String>,
+    /// Funding URLs listed in the package's `funding` field, in order.
+    pub urls: Vec<String>,
+}
+
+/// Every package in `graph` that lists funding URLs, sorted by name and
+/// version. Manifests are read from wherever `linker` installed each
+/// package, and packages that aren't on disk (or can't be read there) fall
+/// back to their full registry metadata.
+pub(crate) async fn funding(
+    graph: &Graph,
+    linker: &Linker,
+    concurrency: usize,
+) -> Result<Vec<FundingInfo>, NodeMaintainerError> {
+    let mut funding = futures::stream::iter(
+        graph
+            .inner
+            .node_indices()
+            .filter(|idx| *idx != graph.root)
+            .map(|idx| (idx, linker.package_dir(graph, idx))),
+    )
+    .map(|(idx, dir)| async move {
+        let package = &graph[idx].package;
+        let manifest = match dir.and_then(|dir| read_manifest(dir.join("package.json"))) {
+            Some(manifest) => manifest,
+            None => package.metadata().await?.manifest,
+        };
+        let urls: Vec<String> = manifest
+            .funding
+            .as_ref()
+            .map(|funding| funding.urls().into_iter().map(String::from).collect())
+            .unwrap_or_default();
+        Ok::<_, NodeMaintainerError>((!urls.is_empty()).then(|| FundingInfo {
+            name: package.name().into(),
+            version: match package.resolved() {
+                PackageResolution::Npm { version, .. } => Some(version.to_string()),
+                _ => None,
+            },
+            urls,
+        }))
+    })
+    .buffer_unordered(concurrency)
+    .try_filter_map(|info| async move { Ok(info) })
+    .try_collect::<Vec<_>>()
+    .await?;
+    funding.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(funding)
+}
+
+fn read_manifest(path: PathBuf) -> Option<Manifest> {
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
@@ -0,0 +1,166 @@
+//! Installing packages globally, npm-style: into `<prefix>/lib/node_modules`
+//! (`<prefix>/node_modules` on Windows), with their bins linked into
+//! `<prefix>/bin` (`<prefix>` itself on Windows).
+//!
+//! The global tree has no `package.json`. Instead, the packages that were
+//! asked for are remembered by the metadata file in its `node_modules/`, so
+//! [`global_manifest`] can pick up where the last global install left off.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use oro_common::CorgiManifest;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::access::{AccessKind, AccessTracker};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Lockfile, NodeMaintainerError, META_FILE_NAME};
+
+/// Lists the bins linked into the global bin directory by the last global
+/// install, so they can be cleaned up without touching anything else in
+/// there.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const GLOBAL_BINS_FILE_NAME: &str = ".orogene-global-bins";
+
+/// The directory global packages get installed into, inside its
+/// `node_modules/`. This is what gets used as the project root.
+pub fn global_root(prefix: &Path) -> PathBuf {
+    if cfg!(windows) {
+        prefix.to_path_buf()
+    } else {
+        prefix.join("lib")
+    }
+}
+
+/// The directory bins for global packages get linked into.
+pub fn global_bin_dir(prefix: &Path) -> PathBuf {
+    if cfg!(windows) {
+        prefix.to_path_buf()
+    } else {
+        prefix.join("bin")
+    }
+}
+
+/// The global prefix to use when none is given: `$NPM_CONFIG_PREFIX`, if
+/// it's set, or else the prefix of the `node` on the `PATH`, so global bins
+/// end up right next to it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_global_prefix() -> Option<PathBuf> {
+    if let Some(prefix) = std::env::var_os("NPM_CONFIG_PREFIX")
+        .or_else(|| std::env::var_os("npm_config_prefix"))
+        .filter(|prefix| !prefix.is_empty())
+    {
+        return Some(PathBuf::from(prefix));
+    }
+    let node = if cfg!(windows) { "node.exe" } else { "node" };
+    let node = std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(node))
+        .find(|path| path.is_file())?;
+    let bin_dir = node.parent()?;
+    if cfg!(windows) {
+        Some(bin_dir.to_path_buf())
+    } else {
+        bin_dir.parent().map(Path::to_path_buf)
+    }
+}
+
+/// The packages currently installed globally under `prefix`, as a manifest
+/// that depends on each of them the same way they were originally asked
+/// for. Nothing installed means an empty manifest.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn global_manifest(prefix: &Path) -> Result<CorgiManifest, NodeMaintainerError> {
+    let meta = global_root(prefix)
+        .join("node_modules")
+        .join(META_FILE_NAME);
+    match async_std::fs::read_to_string(&meta).await {
+        Ok(kdl) => Ok(Lockfile::from_kdl(kdl)?.root().clone().into()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CorgiManifest::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Removes whatever bins the last global install linked into `bin_dir`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unlink_global_bins(
+    node_modules: &Path,
+    bin_dir: &Path,
+    access: &AccessTracker,
+) -> Result<(), NodeMaintainerError> {
+    let Ok(names) = std::fs::read_to_string(node_modules.join(GLOBAL_BINS_FILE_NAME)) else {
+        return Ok(());
+    };
+    for name in names.lines().filter(|name| is_plain_name(name)) {
+        let bin = bin_dir.join(name);
+        let mut paths = vec![bin.clone()];
+        if cfg!(windows) {
+            for ext in [".cmd", ".ps1"] {
+                let mut shim = bin.clone().into_os_string();
+                shim.push(ext);
+                paths.push(shim.into());
+            }
+        }
+        for path in paths {
+            if path.symlink_metadata().is_ok() {
+                access.check(AccessKind::Remove, &path)?;
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remembers which bins got linked into the global bin directory, for
+/// [`unlink_global_bins`] to clean up next time.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_global_bins(
+    node_modules: &Path,
+    names: &[String],
+    access: &AccessTracker,
+) -> Result<(), NodeMaintainerError> {
+    let record = node_modules.join(GLOBAL_BINS_FILE_NAME);
+    access.check(AccessKind::Write, &record)?;
+    std::fs::create_dir_all(node_modules)?;
+    let mut names = names.to_vec();
+    names.sort();
+    std::fs::write(record, names.join("\n"))?;
+    Ok(())
+}
+
+/// Bin names end up right in the global bin directory, so anything that
+/// could point somewhere else is ignored.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let prefix = Path::new("prefix");
+        if cfg!(windows) {
+            assert_eq!(global_root(prefix), prefix);
+            assert_eq!(global_bin_dir(prefix), prefix);
+        } else {
+            assert_eq!(global_root(prefix), prefix.join("lib"));
+            assert_eq!(global_bin_dir(prefix), prefix.join("bin"));
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn plain_names() {
+        assert!(is_plain_name("tsc"));
+        assert!(is_plain_name("foo.js"));
+        assert!(!is_plain_name(""));
+        assert!(!is_plain_name("../tsc"));
+        assert!(!is_plain_name("nested/tsc"));
+        assert!(!is_plain_name("/usr/bin/tsc"));
+    }
+}
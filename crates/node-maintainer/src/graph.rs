@@ -1,6 +1,7 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
     ffi::OsStr,
+    fmt,
     ops::{Index, IndexMut},
     path::Path,
 };
@@ -8,13 +9,18 @@ use std::{
 use indexmap::IndexMap;
 use kdl::KdlDocument;
 use nassun::{package::Package, PackageResolution, PackageSpec};
-use oro_common::CorgiManifest;
+use oro_common::{CorgiManifest, DeprecationInfo, PeerDependencyMeta};
+use petgraph::algo::tarjan_scc;
 use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableGraph};
-#[cfg(not(target_arch = "wasm32"))]
 use petgraph::Direction;
+use serde::{Serialize, Serializer};
+use ssri::Integrity;
 use unicase::UniCase;
 
-use crate::{error::NodeMaintainerError, Lockfile, LockfileNode};
+use crate::{
+    error::NodeMaintainerError, explain::Origin, nohoist, Lockfile, LockfileNode, PeerProblem,
+    PolicyViolation, TreeDependency, TreePackage,
+};
 
 #[cfg(debug_assertions)]
 use NodeMaintainerError::GraphValidationError;
@@ -49,6 +55,25 @@ pub struct Node {
     /// not necessarily dependencies, and this Node's dependencies may not all
     /// be in this HashMap.
     pub(crate) children: IndexMap<UniCase<String>, NodeIndex>,
+    /// Integrity of the patch from `patchedDependencies` that applies to
+    /// this Node, if any.
+    pub(crate) patch: Option<Integrity>,
+    /// Peer dependencies this Node expects to find in the tree. These don't
+    /// get placed by the resolver, but whatever the Node would load for them
+    /// gets checked against them.
+    pub(crate) peer_reqs: IndexMap<UniCase<String>, PackageSpec>,
+    /// Peer dependencies marked optional in `peerDependenciesMeta`. It's
+    /// fine for these to be missing from the tree.
+    pub(crate) optional_peers: HashSet<UniCase<String>>,
+    /// Local dependencies marked `injected` in `dependenciesMeta`. These
+    /// always get a copy of their own inside this Node.
+    pub(crate) injected: HashSet<UniCase<String>>,
+    /// Whether this Node's version is deprecated. This is only known for
+    /// packages whose packuments were fetched while resolving.
+    pub(crate) deprecated: Option<DeprecationInfo>,
+    /// How the resolver came to place this Node, if it was asked to keep
+    /// track.
+    pub(crate) origin: Option<Origin>,
 }
 
 impl Node {
@@ -81,11 +106,30 @@ impl Node {
             Box::new(deps)
         };
         let mut dependency_reqs = IndexMap::new();
-        for ((name, spec), dep_type) in deps {
-            dependency_reqs.insert(
-                UniCase::new(name.clone()),
-                (format!("{name}@{spec}").parse()?, dep_type),
-            );
+        let mut peer_reqs = IndexMap::new();
+        let mut optional_peers = HashSet::new();
+        // Linked packages are used in place, so installing their
+        // dependencies is left up to them.
+        if !matches!(package.resolved(), PackageResolution::Link { .. }) {
+            for ((name, spec), dep_type) in deps {
+                dependency_reqs.insert(
+                    UniCase::new(name.clone()),
+                    (format!("{name}@{spec}").parse()?, dep_type),
+                );
+            }
+            if !is_root {
+                for (name, spec) in &manifest.peer_dependencies {
+                    peer_reqs.insert(
+                        UniCase::new(name.clone()),
+                        format!("{name}@{spec}").parse()?,
+                    );
+                }
+                for (name, meta) in &manifest.peer_dependencies_meta {
+                    if meta.optional && manifest.peer_dependencies.contains_key(name) {
+                        optional_peers.insert(UniCase::new(name.clone()));
+                    }
+                }
+            }
         }
         Ok(Self {
             package,
@@ -95,6 +139,17 @@ impl Node {
             children: IndexMap::new(),
             dependencies: IndexMap::new(),
             dependency_reqs,
+            patch: None,
+            peer_reqs,
+            optional_peers,
+            injected: manifest
+                .dependencies_meta
+                .iter()
+                .filter(|(_, meta)| meta.injected)
+                .map(|(name, _)| UniCase::new(name.clone()))
+                .collect(),
+            deprecated: None,
+            origin: None,
         })
     }
 
@@ -112,6 +167,35 @@ pub enum DepType {
     Opt,
 }
 
+impl DepType {
+    /// Name used for this kind of dependency in graph exports and
+    /// machine-readable output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepType::Prod => "prod",
+            DepType::Dev => "dev",
+            DepType::Peer => "peer",
+            DepType::Opt => "optional",
+        }
+    }
+
+    /// How this kind of dependency gets described in messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            DepType::Prod => "dependency",
+            DepType::Dev => "dev dependency",
+            DepType::Peer => "peer dependency",
+            DepType::Opt => "optional dependency",
+        }
+    }
+}
+
+impl Serialize for DepType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Edge {
     pub(crate) requested: PackageSpec,
@@ -127,10 +211,39 @@ impl Edge {
     }
 }
 
+/// A set of packages that depend on each other in a loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    /// The packages in the loop, as `name@version`, starting with the one
+    /// closest to the root. Each package depends on the next one, and the
+    /// last one depends on the first.
+    pub packages: Vec<String>,
+}
+
+impl fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for package in &self.packages {
+            write!(f, "{package} -> ")?;
+        }
+        write!(f, "{}", self.packages[0])
+    }
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct Graph {
     pub(crate) root: NodeIndex,
     pub(crate) inner: StableGraph<Node, Edge>,
+    /// Peer dependencies the resolved tree doesn't satisfy.
+    pub(crate) peer_problems: Vec<PeerProblem>,
+    /// Packages that break the project's policy, when they're being
+    /// reported instead of failing resolution.
+    pub(crate) policy_violations: Vec<PolicyViolation>,
+    /// `nohoist` patterns declared by the root or by workspace packages,
+    /// keyed by the Node that declared them.
+    pub(crate) nohoist: HashMap<NodeIndex, Vec<String>>,
+    /// `hoist-pattern`s for the package names that can be hoisted all the
+    /// way up to the root. `None` means anything can be.
+    pub(crate) hoist_patterns: Option<Vec<String>>,
 }
 
 impl Index<NodeIndex> for Graph {
@@ -186,6 +299,19 @@ impl Graph {
             .any(|parent| parent.idx == ancestor)
     }
 
+    /// Whether `dependent` wants a copy of its own of its `name` dependency,
+    /// rather than sharing one with the rest of the tree. Only local
+    /// packages can be injected.
+    pub(crate) fn is_injected(
+        &self,
+        dependent: NodeIndex,
+        name: &UniCase<String>,
+        requested: &PackageSpec,
+    ) -> bool {
+        matches!(requested.target(), PackageSpec::Dir { .. })
+            && self[dependent].injected.contains(name)
+    }
+
     pub fn to_lockfile(&self) -> Result<Lockfile, NodeMaintainerError> {
         let root = self.node_lockfile_node(self.root, true)?;
         let packages = self
@@ -217,6 +343,398 @@ impl Graph {
         Ok(self.to_lockfile()?.to_kdl())
     }
 
+    /// Renders the graph in Graphviz's DOT format. Nodes are identified by
+    /// their path in `node_modules/`, and edges are labeled with the
+    /// requested spec. `devDependencies` are dashed, `optionalDependencies`
+    /// are dotted, and `peerDependencies` are bold.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for (path, idx) in self.export_nodes() {
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\"];\n",
+                dot_escape(&path),
+                dot_escape(&self.export_label(idx))
+            ));
+        }
+        for (from, to, edge) in self.export_edges() {
+            let style = match edge.dep_type {
+                DepType::Prod => "solid",
+                DepType::Dev => "dashed",
+                DepType::Peer => "bold",
+                DepType::Opt => "dotted",
+            };
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\", style={style}];\n",
+                dot_escape(&from),
+                dot_escape(&to),
+                dot_escape(&edge.requested.requested())
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a JSON object with a list of `nodes` and a list
+    /// of `edges` between them. Nodes are identified by their path in
+    /// `node_modules/`, with the root's being `""`.
+    pub fn to_json(&self) -> Result<serde_json::Value, NodeMaintainerError> {
+        let nodes = self
+            .export_nodes()
+            .into_iter()
+            .map(|(path, idx)| {
+                let node = self.node_lockfile_node(idx, idx == self.root)?;
+                Ok(serde_json::json!({
+                    "id": path,
+                    "name": node.name.to_string(),
+                    "version": node.version.map(|v| v.to_string()),
+                    "resolved": node.resolved,
+                    "integrity": node.integrity.map(|i| i.to_string()),
+                }))
+            })
+            .collect::<Result<Vec<_>, NodeMaintainerError>>()?;
+        let edges = self
+            .export_edges()
+            .into_iter()
+            .map(|(from, to, edge)| {
+                serde_json::json!({
+                    "from": from,
+                    "to": to,
+                    "type": edge.dep_type.as_str(),
+                    "requested": edge.requested.requested(),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(serde_json::json!({
+            "nodes": nodes,
+            "edges": edges,
+        }))
+    }
+
+    /// Renders the graph as a Mermaid flowchart. Edges are labeled with
+    /// their dependency type and requested spec, with `devDependencies` and
+    /// `optionalDependencies` drawn as dotted lines, and `peerDependencies`
+    /// as thick ones.
+    pub fn to_mermaid(&self) -> String {
+        let nodes = self.export_nodes();
+        let ids = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, (path, _))| (path.clone(), format!("n{i}")))
+            .collect::<IndexMap<_, _>>();
+        let mut mermaid = String::from("graph LR\n");
+        for (path, idx) in &nodes {
+            mermaid.push_str(&format!(
+                "    {}[\"{}\"]\n",
+                ids[path],
+                mermaid_escape(&self.export_label(*idx))
+            ));
+        }
+        for (from, to, edge) in self.export_edges() {
+            let arrow = match edge.dep_type {
+                DepType::Prod => "-->",
+                DepType::Dev | DepType::Opt => "-.->",
+                DepType::Peer => "==>",
+            };
+            mermaid.push_str(&format!(
+                "    {} {arrow}|\"{} {}\"| {}\n",
+                ids[&from],
+                edge.dep_type.as_str(),
+                mermaid_escape(&edge.requested.requested()),
+                ids[&to]
+            ));
+        }
+        mermaid
+    }
+
+    /// Every package in the tree, root first, sorted by path.
+    pub(crate) fn tree(&self) -> Vec<TreePackage> {
+        let paths = self
+            .export_nodes()
+            .into_iter()
+            .map(|(path, idx)| {
+                let path = if path.is_empty() {
+                    path
+                } else {
+                    format!("node_modules/{path}")
+                };
+                (idx, path)
+            })
+            .collect::<IndexMap<_, _>>();
+        paths
+            .iter()
+            .map(|(idx, path)| {
+                let node = &self.inner[*idx];
+                let mut dependencies = node
+                    .dependencies
+                    .iter()
+                    .filter_map(|(name, edge_idx)| {
+                        let (_, target) = self.inner.edge_endpoints(*edge_idx)?;
+                        let edge = &self.inner[*edge_idx];
+                        Some(TreeDependency {
+                            name: name.to_string(),
+                            requested: edge.requested.clone(),
+                            dep_type: edge.dep_type.clone(),
+                            path: paths.get(&target)?.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+                TreePackage {
+                    path: path.clone(),
+                    package: node.package.clone(),
+                    dependencies,
+                }
+            })
+            .collect()
+    }
+
+    /// All nodes, along with their path in `node_modules/`, sorted by path
+    /// so exports are stable.
+    fn export_nodes(&self) -> Vec<(String, NodeIndex)> {
+        let mut nodes = self
+            .inner
+            .node_indices()
+            .map(|idx| {
+                let path = self
+                    .node_path(idx)
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/node_modules/");
+                (path, idx)
+            })
+            .collect::<Vec<_>>();
+        nodes.sort();
+        nodes
+    }
+
+    /// All edges as `(from path, to path, edge)`, in the same order as
+    /// [`Graph::export_nodes`], then by dependency name.
+    fn export_edges(&self) -> Vec<(String, String, &Edge)> {
+        let mut edges = Vec::new();
+        for (from, idx) in self.export_nodes() {
+            let mut deps = self.inner[idx].dependencies.iter().collect::<Vec<_>>();
+            deps.sort_by_key(|(name, _)| *name);
+            for (_, edge_idx) in deps {
+                if let Some((_, target)) = self.inner.edge_endpoints(*edge_idx) {
+                    let to = self
+                        .node_path(target)
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join("/node_modules/");
+                    edges.push((from.clone(), to, &self.inner[*edge_idx]));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Human-readable `name@version` label for a node, falling back to
+    /// where it was resolved from for non-registry packages.
+    fn export_label(&self, idx: NodeIndex) -> String {
+        let package = &self.inner[idx].package;
+        match package.resolved() {
+            PackageResolution::Npm { version, .. } => format!("{}@{version}", package.name()),
+            resolved => format!("{}@{resolved}", package.name()),
+        }
+    }
+
+    /// All the dependency cycles in the graph. Each set of packages that
+    /// depend on each other gets reported once, as the shortest loop through
+    /// its package closest to the root.
+    pub(crate) fn cycles(&self) -> Vec<DependencyCycle> {
+        let mut cycles = tarjan_scc(&self.inner)
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.inner.contains_edge(component[0], component[0])
+            })
+            .map(|component| {
+                let start = *component
+                    .iter()
+                    .min_by_key(|idx| (self.inner[**idx].depth(self), self.node_path(**idx)))
+                    .expect("components are never empty");
+                let members = component.into_iter().collect::<HashSet<_>>();
+                DependencyCycle {
+                    packages: self
+                        .cycle_through(start, &members)
+                        .into_iter()
+                        .map(|idx| self.export_label(idx))
+                        .collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+        cycles.sort_by(|a, b| a.packages.cmp(&b.packages));
+        cycles
+    }
+
+    /// Shortest loop of dependencies from `start` back to itself, only going
+    /// through `members`.
+    fn cycle_through(&self, start: NodeIndex, members: &HashSet<NodeIndex>) -> Vec<NodeIndex> {
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(idx) = queue.pop_front() {
+            for dep_idx in self.inner.neighbors_directed(idx, Direction::Outgoing) {
+                if dep_idx == start {
+                    let mut cycle = vec![idx];
+                    let mut current = idx;
+                    while let Some(prev) = previous.get(&current) {
+                        cycle.push(*prev);
+                        current = *prev;
+                    }
+                    cycle.reverse();
+                    return cycle;
+                }
+                if members.contains(&dep_idx) && !previous.contains_key(&dep_idx) {
+                    previous.insert(dep_idx, idx);
+                    queue.push_back(dep_idx);
+                }
+            }
+        }
+        vec![start]
+    }
+
+    /// Groups packages into levels for running lifecycle scripts, where
+    /// every package's dependencies are in earlier levels. Packages in a
+    /// dependency cycle can't be ordered among themselves, so the whole
+    /// cycle shares a level instead of waiting on itself forever.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn script_levels(&self) -> Vec<Vec<NodeIndex>> {
+        let mut levels: Vec<Vec<NodeIndex>> = Vec::new();
+        let mut node_levels = HashMap::new();
+        // Components come out of Tarjan's algorithm with dependencies
+        // before their dependents, so every dependency outside of the
+        // current component already has a level.
+        for component in tarjan_scc(&self.inner) {
+            let level = component
+                .iter()
+                .flat_map(|idx| self.inner.neighbors_directed(*idx, Direction::Outgoing))
+                .filter_map(|dep_idx| node_levels.get(&dep_idx).map(|level| level + 1))
+                .max()
+                .unwrap_or(0);
+            for idx in &component {
+                node_levels.insert(*idx, level);
+            }
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].extend(component);
+        }
+        levels
+    }
+
+    /// The Node that a `name` dependency of `dependent` has to be placed
+    /// inside of, if it can't be hoisted all the way up. See
+    /// [`Graph::nohoist_boundary`] and [`Graph::hoist_pattern_boundary`].
+    pub(crate) fn hoist_boundary(&self, dependent: NodeIndex, name: &str) -> Option<NodeIndex> {
+        self.nohoist_boundary(dependent, name)
+            .or_else(|| self.hoist_pattern_boundary(dependent, name))
+    }
+
+    /// Whether a `name` dependency of `dependent` can use the copy at
+    /// `satisfier`, as far as where it's allowed to be hoisted to goes.
+    pub(crate) fn may_share(&self, dependent: NodeIndex, name: &str, satisfier: NodeIndex) -> bool {
+        if let Some(boundary) = self.nohoist_boundary(dependent, name) {
+            return self.is_ancestor(boundary, satisfier);
+        }
+        match self.hoist_pattern_boundary(dependent, name) {
+            // A copy at the root is only there because the root depends on
+            // it directly, so it's not exposing anything new.
+            Some(boundary) => {
+                self.is_ancestor(boundary, satisfier) || self[satisfier].parent == Some(self.root)
+            }
+            None => true,
+        }
+    }
+
+    /// The top-level package that a `name` dependency of `dependent` has to
+    /// be placed inside of, when `name` doesn't match the `hoist-pattern`s.
+    /// The root's own dependencies always go in the root.
+    fn hoist_pattern_boundary(&self, dependent: NodeIndex, name: &str) -> Option<NodeIndex> {
+        let patterns = self.hoist_patterns.as_ref()?;
+        if dependent == self.root || nohoist::matches_name(patterns, name) {
+            return None;
+        }
+        self.node_parent_iter(dependent)
+            .take_while(|node| node.idx != self.root)
+            .last()
+            .map(|node| node.idx)
+    }
+
+    /// The workspace package that a `name` dependency of `dependent` has to
+    /// be placed inside of, if any `nohoist` pattern matches it.
+    ///
+    /// Patterns are matched against the chain of package names leading to
+    /// the dependency, starting from whichever Node declared them, and
+    /// matching dependencies are kept in the `node_modules/` of the
+    /// nearest workspace package above `dependent`.
+    fn nohoist_boundary(&self, dependent: NodeIndex, name: &str) -> Option<NodeIndex> {
+        if self.nohoist.is_empty() {
+            return None;
+        }
+        let mut path = self.dependency_chain(dependent);
+        path.push(name.to_string());
+        let path = path.join("/");
+        let mut boundary = None;
+        for node in self.node_parent_iter(dependent) {
+            let is_workspace = matches!(node.package.resolved(), PackageResolution::Dir { .. });
+            if node.idx == self.root {
+                // Nothing can be hoisted past the root anyway.
+                boundary.get_or_insert(None);
+            } else if is_workspace {
+                boundary.get_or_insert(Some(node.idx));
+            } else {
+                continue;
+            }
+            let Some(patterns) = self.nohoist.get(&node.idx) else {
+                continue;
+            };
+            let relative = if node.idx == self.root {
+                Some(path.as_str())
+            } else {
+                let prefix = self.dependency_chain(node.idx).join("/");
+                path.strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_prefix('/'))
+            };
+            if relative.map_or(false, |relative| {
+                patterns
+                    .iter()
+                    .any(|pattern| nohoist::matches(pattern, relative))
+            }) {
+                return boundary.flatten();
+            }
+        }
+        None
+    }
+
+    /// Shortest chain of dependencies leading from the root to `node`, as
+    /// package names. The root itself isn't included.
+    pub(crate) fn dependency_chain(&self, node: NodeIndex) -> Vec<String> {
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::from([self.root]);
+        while let Some(idx) = queue.pop_front() {
+            if idx == node {
+                break;
+            }
+            for dep_idx in self.inner.neighbors_directed(idx, Direction::Outgoing) {
+                if dep_idx != self.root && !previous.contains_key(&dep_idx) {
+                    previous.insert(dep_idx, idx);
+                    queue.push_back(dep_idx);
+                }
+            }
+        }
+        let mut chain = VecDeque::new();
+        let mut current = node;
+        while current != self.root {
+            chain.push_front(self.inner[current].package.name().to_string());
+            match previous.get(&current) {
+                Some(prev) => current = *prev,
+                None => break,
+            }
+        }
+        chain.into()
+    }
+
     pub(crate) fn node_parent_iter(&self, idx: NodeIndex) -> NodeParentIterator {
         NodeParentIterator {
             graph: self,
@@ -331,7 +849,7 @@ impl Graph {
                 if let Some(dep_idx) = self.resolve_dep(dependent.idx, dep_name) {
                     let dependency = &self.inner[dep_idx];
 
-                    if !dependency.package.resolved().satisfies(&edge.requested)? {
+                    if !dependency.package.satisfies(&edge.requested)? {
                         return Err(GraphValidationError(format!(
                             "Dependency {:?} does not satisfy requirement {} from {:?}",
                             dependency.package.resolved(),
@@ -363,7 +881,9 @@ impl Graph {
         let resolved = match node.package.resolved() {
             PackageResolution::Npm { tarball, .. } => tarball.to_string(),
             PackageResolution::Dir { path, .. } => path.to_string_lossy().into(),
+            PackageResolution::Link { path, .. } => format!("link:{}", path.to_string_lossy()),
             PackageResolution::Git { info, .. } => info.to_string(),
+            PackageResolution::Url { tarball, .. } => tarball.to_string(),
         };
         let version = if let PackageResolution::Npm { version, .. } = node.package.resolved() {
             Some(version.clone())
@@ -389,6 +909,9 @@ impl Graph {
             };
             deps.insert(name.to_string(), requested.requested().clone());
         }
+        for (name, requested) in &node.peer_reqs {
+            peer_deps.insert(name.to_string(), requested.requested().clone());
+        }
         Ok(LockfileNode {
             name: UniCase::new(node.package.name().to_string()),
             is_root,
@@ -398,15 +921,33 @@ impl Graph {
             dependencies: prod_deps,
             dev_dependencies: dev_deps,
             peer_dependencies: peer_deps,
+            peer_dependencies_meta: node
+                .peer_reqs
+                .keys()
+                .filter(|name| node.optional_peers.contains(*name))
+                .map(|name| (name.to_string(), PeerDependencyMeta { optional: true }))
+                .collect(),
             optional_dependencies: opt_deps,
             integrity: match node.package.resolved() {
-                PackageResolution::Npm { ref integrity, .. } => integrity.clone(),
+                PackageResolution::Npm { ref integrity, .. }
+                | PackageResolution::Url { ref integrity, .. } => integrity.clone(),
                 _ => None,
             },
+            patch: node.patch.clone(),
         })
     }
 }
 
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
 pub(crate) struct NodeParentIterator<'a> {
     graph: &'a Graph,
     current: Option<NodeIndex>,
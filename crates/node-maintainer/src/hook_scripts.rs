@@ -0,0 +1,146 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::NodeMaintainerError;
+
+/// Patterns used to recognize scripts that set up git hooks, when no others
+/// have been configured.
+pub const DEFAULT_HOOK_SCRIPT_PATTERNS: &[&str] = &[
+    "husky",
+    "simple-git-hooks",
+    "lefthook install",
+    "git config core.hooksPath",
+];
+
+/// Controls what happens to lifecycle scripts that set up git hooks (such
+/// as `husky install` or `simple-git-hooks`).
+///
+/// These scripts mutate the surrounding git repository, which is rarely
+/// what you want in CI, and frequently fails in container builds where
+/// there's no `.git/` directory at all.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HookScripts {
+    /// Skip hook scripts when running in CI, or when the project isn't
+    /// inside a git repository. Run them otherwise.
+    #[default]
+    Auto,
+    /// Always run hook scripts.
+    Run,
+    /// Never run hook scripts.
+    Skip,
+}
+
+impl FromStr for HookScripts {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "run" => Ok(Self::Run),
+            "skip" => Ok(Self::Skip),
+            _ => Err(NodeMaintainerError::InvalidHookScripts(s.into())),
+        }
+    }
+}
+
+/// Decides which scripts should be skipped for a particular install, based
+/// on its [`HookScripts`] mode and patterns.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub(crate) struct HookScriptFilter {
+    skip: bool,
+    patterns: Vec<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HookScriptFilter {
+    pub(crate) fn new(mode: HookScripts, patterns: &[String], root: &Path) -> Self {
+        let skip = match mode {
+            HookScripts::Run => false,
+            HookScripts::Skip => true,
+            HookScripts::Auto => oro_common::detect_ci().is_some() || !in_git_repo(root),
+        };
+        Self {
+            skip,
+            patterns: patterns.to_vec(),
+        }
+    }
+
+    /// Whether a script with the given command should be skipped. Patterns
+    /// can match anywhere in the command, and `*` matches any sequence of
+    /// characters.
+    pub(crate) fn should_skip(&self, command: &str) -> bool {
+        self.skip
+            && self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(&format!("*{pattern}*"), command))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn in_git_repo(root: &Path) -> bool {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    root.ancestors().any(|dir| dir.join(".git").exists())
+}
+
+/// Matches `text` against `pattern`, where `*` matches any sequence of
+/// characters.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(skip: bool) -> HookScriptFilter {
+        HookScriptFilter {
+            skip,
+            patterns: DEFAULT_HOOK_SCRIPT_PATTERNS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn matches_hook_scripts() {
+        let filter = filter(true);
+        assert!(filter.should_skip("husky install"));
+        assert!(filter.should_skip("is-ci || husky install"));
+        assert!(filter.should_skip("npx simple-git-hooks"));
+        assert!(!filter.should_skip("tsc -p ."));
+    }
+
+    #[test]
+    fn only_skips_when_enabled() {
+        let filter = filter(false);
+        assert!(!filter.should_skip("husky install"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(glob_match("*git*hooks*", "simple-git-hooks"));
+        assert!(glob_match("husky*", "husky install"));
+        assert!(!glob_match("husky*", "npx husky install"));
+        assert!(glob_match("*install", "husky install"));
+        assert!(!glob_match("*install", "husky install foo"));
+    }
+}
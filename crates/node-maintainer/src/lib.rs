@@ -1,23 +1,121 @@
 //! An NPM dependency resolver for building `node_modules/` trees and
 //! extracting them to their final resting place.
+//!
+//! This is the library Orogene's installer is built on, and it's meant to
+//! be embedded by other tools too: configure a [`NodeMaintainer`] with
+//! [`NodeMaintainer::builder`], resolve a tree, then inspect it, write a
+//! [`Lockfile`], or extract it into `node_modules/`.
+//!
+//! ```no_run
+//! use node_maintainer::{InstallEvent, NodeMaintainer, NodeMaintainerError};
+//!
+//! # async fn install() -> Result<(), NodeMaintainerError> {
+//! let maintainer = NodeMaintainer::builder()
+//!     .root("path/to/project")
+//!     .on_event(|event| {
+//!         if let InstallEvent::Extracted(package) = event {
+//!             println!("extracted {}", package.name());
+//!         }
+//!     })
+//!     .resolve_spec("react@18")
+//!     .await?;
+//! for package in maintainer.tree() {
+//!     println!("{:?} -> {}", package.path, package.package.name());
+//! }
+//! maintainer.extract().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Stability
+//!
+//! This crate follows semver on its own, separately from the `oro` command
+//! line, whose internals aren't meant to be depended on. Types that are
+//! likely to grow, like [`InstallEvent`] and [`TreePackage`], are
+//! `#[non_exhaustive]`. [`Nassun`] is re-exported, so embedders don't need
+//! to keep a separate dependency on it in sync.
 
 pub use nassun::Nassun;
 #[cfg(not(target_arch = "wasm32"))]
 pub use nassun::{NassunError, NassunOpts};
 
+pub use conflict::*;
+pub use deprecated::Deprecation;
+#[cfg(not(target_arch = "wasm32"))]
+pub use dry_run::*;
 pub use error::*;
+pub use events::InstallEvent;
+pub use explain::{Candidate, ResolutionExplanation};
+pub use extract_progress::ExtractBytes;
+#[cfg(not(target_arch = "wasm32"))]
+pub use funding::FundingInfo;
+#[cfg(not(target_arch = "wasm32"))]
+pub use global::{default_global_prefix, global_manifest};
+pub use global::{global_bin_dir, global_root};
+pub use graph::{DepType, DependencyCycle};
+pub use hook_scripts::*;
 pub use into_kdl::IntoKdl;
+pub use link_strategy::LinkStrategy;
 pub use lockfile::*;
 pub use maintainer::*;
+pub use node_linker::*;
+pub use policy::{Policy, PolicyRule, PolicyViolation};
+pub use resolution_mode::*;
+pub use script_output::{ScriptOutput, ScriptOutputMode};
+#[cfg(not(target_arch = "wasm32"))]
+pub use state::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use timings::{PackageTiming, Timings};
+pub use tree::{TreeDependency, TreePackage};
+pub use trusted::{AllowScripts, BlockedScripts};
+pub use vfs::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use virtual_install::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use workspace_scripts::*;
+pub use workspaces::Workspaces;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod access;
+mod catalogs;
+mod conflict;
+mod deprecated;
+#[cfg(not(target_arch = "wasm32"))]
+mod dry_run;
 mod error;
+mod events;
+mod explain;
+mod extract_progress;
+#[cfg(not(target_arch = "wasm32"))]
+mod funding;
+mod global;
 mod graph;
+mod hook_scripts;
 mod into_kdl;
+mod link_strategy;
 mod linkers;
 mod lockfile;
 mod maintainer;
+mod node_linker;
+mod nohoist;
+mod patches;
+mod policy;
+mod resolution_mode;
 mod resolver;
+mod script_output;
+#[cfg(not(target_arch = "wasm32"))]
+mod state;
+#[cfg(not(target_arch = "wasm32"))]
+mod timings;
+mod tree;
+mod trusted;
+mod vfs;
+#[cfg(not(target_arch = "wasm32"))]
+mod virtual_install;
+#[cfg(not(target_arch = "wasm32"))]
+mod workspace_scripts;
+mod workspaces;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
@@ -0,0 +1,268 @@
+//! Picking how files get from the cache into `node_modules/`.
+//!
+//! The strategies in the configured chain are tried in order, and the first
+//! one that works between the cache and `node_modules/` gets used. The only
+//! way to know whether one works is to try it, so the results are
+//! remembered in the cache itself, keyed by the pair of volumes involved,
+//! instead of being probed again on every apply.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::NodeMaintainerError;
+
+/// Remembers which strategies work for which pairs of volumes. Lives in the
+/// cache directory.
+#[cfg(not(target_arch = "wasm32"))]
+const PROBES_FILE_NAME: &str = "_link-strategies.json";
+
+/// A way of getting files out of the cache and into `node_modules/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkStrategy {
+    /// Copy-on-write clones, on filesystems that support them, like btrfs,
+    /// XFS, and ReFS.
+    Reflink,
+    /// Copy-on-write clones using `clonefile(2)`, on macOS's APFS.
+    Clonefile,
+    /// Hard links to the files in the cache. Needs the cache and the project
+    /// to be on the same volume.
+    Hardlink,
+    /// Plain copies, which always work. Filesystems that support
+    /// copy-on-write may still clone the files under the hood.
+    Copy,
+}
+
+impl LinkStrategy {
+    /// The chain used when none is configured: clones if possible, since
+    /// they're as fast as links without sharing files with the cache, then
+    /// hard links, then copies.
+    pub const DEFAULT_CHAIN: [LinkStrategy; 4] = [
+        LinkStrategy::Reflink,
+        LinkStrategy::Clonefile,
+        LinkStrategy::Hardlink,
+        LinkStrategy::Copy,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reflink => "reflink",
+            Self::Clonefile => "clonefile",
+            Self::Hardlink => "hardlink",
+            Self::Copy => "copy",
+        }
+    }
+
+    /// Whether installed files get contents of their own, instead of sharing
+    /// them with the cache. Clones are extracted as copies, which the
+    /// filesystem turns into clones by itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn copies(&self) -> bool {
+        !matches!(self, Self::Hardlink)
+    }
+
+    /// The first strategy in `chain` that works for getting files from
+    /// `cache` into `dest`, falling back to [`LinkStrategy::Copy`] if none
+    /// of them do.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn pick(chain: &[LinkStrategy], cache: &Path, dest: &Path) -> Self {
+        let probes_file = cache.join(PROBES_FILE_NAME);
+        let mut probes = std::fs::read_to_string(&probes_file)
+            .ok()
+            .and_then(|probes| {
+                serde_json::from_str::<BTreeMap<String, BTreeMap<String, bool>>>(&probes).ok()
+            })
+            .unwrap_or_default();
+        let key = volume_id(cache)
+            .zip(volume_id(dest))
+            .map(|(cache, dest)| format!("{cache}:{dest}"));
+        let mut probed = false;
+        let mut picked = Self::Copy;
+        for &strategy in chain {
+            if strategy == Self::Copy {
+                break;
+            }
+            let cached = key
+                .as_ref()
+                .and_then(|key| probes.get(key))
+                .and_then(|results| results.get(strategy.as_str()))
+                .copied();
+            let works = match cached {
+                Some(works) => works,
+                None => {
+                    let works = strategy.probe(cache, dest);
+                    if let Some(key) = &key {
+                        probes
+                            .entry(key.clone())
+                            .or_default()
+                            .insert(strategy.as_str().into(), works);
+                        probed = true;
+                    }
+                    works
+                }
+            };
+            if works {
+                picked = strategy;
+                break;
+            }
+        }
+        if probed {
+            // Written next to the real file and then moved into place, so
+            // other processes sharing the cache never read half of it.
+            let written = serde_json::to_vec_pretty(&probes)
+                .map_err(std::io::Error::from)
+                .and_then(|probes| {
+                    use std::io::Write;
+                    let mut temp = tempfile::NamedTempFile::new_in(cache)?;
+                    temp.write_all(&probes)?;
+                    temp.persist(&probes_file)?;
+                    Ok(())
+                });
+            if let Err(e) = written {
+                tracing::debug!("Failed to remember link strategy probes: {e}");
+            }
+        }
+        tracing::debug!(
+            "Using the `{}` strategy to extract files from {} into {}.",
+            picked.as_str(),
+            cache.display(),
+            dest.display()
+        );
+        picked
+    }
+
+    /// Tries this strategy out for real, with a scratch file from `src_dir`
+    /// and a scratch directory in `dest_dir`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn probe(&self, src_dir: &Path, dest_dir: &Path) -> bool {
+        match self {
+            Self::Copy => return true,
+            // These are the same thing underneath: the reflink crate uses
+            // `clonefile(2)` on macOS, and the `FICLONE` ioctl (or Windows'
+            // block cloning) everywhere else.
+            Self::Reflink if cfg!(target_os = "macos") => return false,
+            Self::Clonefile if !cfg!(target_os = "macos") => return false,
+            _ => {}
+        }
+        let temp = match tempfile::NamedTempFile::new_in(src_dir) {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::debug!(
+                    "error creating tempfile while checking for {} support: {e}.",
+                    self.as_str()
+                );
+                return false;
+            }
+        };
+        if let Err(e) = std::fs::write(&temp, "a") {
+            tracing::debug!(
+                "error writing to tempfile while checking for {} support: {e}.",
+                self.as_str()
+            );
+            return false;
+        }
+        let tempdir = match tempfile::TempDir::new_in(dest_dir) {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::debug!(
+                    "error creating destination tempdir while checking for {} support: {e}.",
+                    self.as_str()
+                );
+                return false;
+            }
+        };
+        let result = match self {
+            Self::Hardlink => std::fs::hard_link(temp.path(), tempdir.path().join("b")),
+            _ => reflink::reflink(temp.path(), tempdir.path().join("b")),
+        };
+        match result {
+            Ok(_) => {
+                tracing::debug!("Verified {} support.", self.as_str());
+                true
+            }
+            Err(e) => {
+                tracing::debug!("{} support check failed. ({e})", self.as_str());
+                false
+            }
+        }
+    }
+}
+
+impl FromStr for LinkStrategy {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reflink" => Ok(Self::Reflink),
+            "clonefile" => Ok(Self::Clonefile),
+            "hardlink" => Ok(Self::Hardlink),
+            "copy" => Ok(Self::Copy),
+            _ => Err(NodeMaintainerError::InvalidLinkStrategy(s.into())),
+        }
+    }
+}
+
+/// Identifies the volume `path` is on, so probe results can be shared
+/// between every project on it.
+#[cfg(unix)]
+fn volume_id(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path)
+        .ok()
+        .map(|meta| meta.dev().to_string())
+}
+
+/// Identifies the volume `path` is on, so probe results can be shared
+/// between every project on it.
+#[cfg(windows)]
+fn volume_id(path: &Path) -> Option<String> {
+    match std::fs::canonicalize(path).ok()?.components().next()? {
+        std::path::Component::Prefix(prefix) => {
+            Some(prefix.as_os_str().to_string_lossy().to_lowercase())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_strategies() {
+        for strategy in LinkStrategy::DEFAULT_CHAIN {
+            assert_eq!(strategy.as_str().parse::<LinkStrategy>().unwrap(), strategy);
+        }
+        assert!("symlink".parse::<LinkStrategy>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remembers_probes() -> Result<(), NodeMaintainerError> {
+        let cache = tempfile::tempdir()?;
+        let dest = tempfile::tempdir()?;
+        let picked = LinkStrategy::pick(
+            &[LinkStrategy::Hardlink, LinkStrategy::Copy],
+            cache.path(),
+            dest.path(),
+        );
+        let probes = std::fs::read_to_string(cache.path().join(PROBES_FILE_NAME))?;
+        let probes: BTreeMap<String, BTreeMap<String, bool>> =
+            serde_json::from_str(&probes).unwrap();
+        let results = probes.values().next().unwrap();
+        assert_eq!(
+            results.get("hardlink").copied(),
+            Some(picked == LinkStrategy::Hardlink)
+        );
+        // Copying always works, so it's never probed.
+        assert!(!results.contains_key("copy"));
+        // A chain without anything that works still ends with a copy.
+        assert_eq!(
+            LinkStrategy::pick(&[], cache.path(), dest.path()),
+            LinkStrategy::Copy
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Picking which package gets to link each bin, when several packages
+//! installed into the same place declare bins with the same name.
+//!
+//! Without this, whichever package happened to get linked last would win.
+//! Instead, packages that are direct dependencies of the `.bin/` directory's
+//! owner win over transitive ones, and ties go to whichever package name
+//! sorts first, so the same tree always links the same bins.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use petgraph::stable_graph::NodeIndex;
+
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+
+/// A package that wants to link a bin.
+pub(crate) struct BinCandidate {
+    pub(crate) idx: NodeIndex,
+    /// Whether the package is a direct dependency of whatever owns the
+    /// `.bin/` directory.
+    pub(crate) direct: bool,
+    /// The file the bin points to.
+    pub(crate) from: PathBuf,
+}
+
+/// Bins wanted for a single `.bin/` directory, by bin name.
+#[derive(Default)]
+pub(crate) struct BinCandidates(BTreeMap<String, Vec<BinCandidate>>);
+
+impl BinCandidates {
+    pub(crate) fn add(&mut self, name: String, candidate: BinCandidate) {
+        self.0.entry(name).or_default().push(candidate);
+    }
+
+    /// Picks one package for every bin name, and returns the bins to link,
+    /// as `(name, from)` pairs. Collisions are warned about, unless `strict`
+    /// is true, in which case they're an error.
+    pub(crate) fn pick(
+        self,
+        graph: &Graph,
+        bin_dir: &Path,
+        strict: bool,
+    ) -> Result<Vec<(String, PathBuf)>, NodeMaintainerError> {
+        let mut picked = Vec::with_capacity(self.0.len());
+        for (name, mut candidates) in self.0 {
+            candidates.sort_by(|a, b| {
+                b.direct
+                    .cmp(&a.direct)
+                    .then_with(|| graph[a.idx].package.name().cmp(graph[b.idx].package.name()))
+                    .then_with(|| a.idx.cmp(&b.idx))
+            });
+            let mut candidates = candidates.into_iter();
+            let winner = candidates
+                .next()
+                .expect("there's always at least one candidate");
+            let losers = candidates
+                .filter(|candidate| candidate.idx != winner.idx)
+                .map(|candidate| graph[candidate.idx].package.name().to_string())
+                .collect::<Vec<_>>();
+            if !losers.is_empty() {
+                let winner_name = graph[winner.idx].package.name().to_string();
+                if strict {
+                    return Err(NodeMaintainerError::BinCollision {
+                        name,
+                        bin_dir: bin_dir.to_path_buf(),
+                        packages: std::iter::once(winner_name).chain(losers).collect(),
+                    });
+                }
+                tracing::warn!(
+                    "The `{name}` bin in {} is provided by {winner_name}, as well as {}. Using the one from {winner_name}.",
+                    bin_dir.display(),
+                    losers.join(", ")
+                );
+            }
+            picked.push((name, winner.from));
+        }
+        Ok(picked)
+    }
+}
@@ -0,0 +1,150 @@
+//! File-level deduplication for the package copies kept inside the cache:
+//! global store entries and side effects snapshots. Lots of packages ship
+//! identical files, like licenses, prebuilt binaries, and the same bundle
+//! under a few names, so every file in those copies is swapped for a hard
+//! link to a single copy kept under `files-v1/`, keyed by its content hash
+//! and mode.
+//!
+//! Files in the cache's content store are already stored once per hash, so
+//! anything hard linked from there is left alone. A `files-v1/` file with
+//! only one link left isn't used by anything anymore, and can be removed.
+
+use std::path::{Path, PathBuf};
+
+use ssri::{Algorithm, IntegrityOpts};
+use walkdir::WalkDir;
+
+use crate::error::NodeMaintainerError;
+
+/// Directory inside the cache that holds deduplicated files.
+const FILES_DIR_NAME: &str = "files-v1";
+
+pub(crate) struct FileDeduper {
+    dir: PathBuf,
+}
+
+impl FileDeduper {
+    pub(crate) fn in_cache(cache: &Path) -> Self {
+        Self {
+            dir: cache.join(FILES_DIR_NAME),
+        }
+    }
+
+    /// Replaces every file under `root` with a hard link to its shared copy,
+    /// making one if there isn't one yet, and returns how many bytes that
+    /// saved. Gives up quietly if hard links don't work, since then there's
+    /// nothing to save.
+    pub(crate) fn dedupe(&self, root: &Path) -> Result<u64, NodeMaintainerError> {
+        let mut saved = 0;
+        // Collected up front, since files get replaced along the way.
+        let entries = WalkDir::new(root)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        for entry in entries {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            if is_shared(&meta) {
+                continue;
+            }
+            let shared = self.location(entry.path(), &meta)?;
+            if shared.exists() {
+                // Linked to a temp name first and then moved over the file,
+                // so the file never goes missing along the way.
+                let mut temp = entry.path().as_os_str().to_owned();
+                temp.push(".oro-dedupe");
+                if let Err(e) = std::fs::hard_link(&shared, &temp) {
+                    tracing::debug!("Not deduplicating files in {}: {e}", root.display());
+                    return Ok(saved);
+                }
+                std::fs::rename(&temp, entry.path())?;
+                saved += meta.len();
+            } else {
+                std::fs::create_dir_all(shared.parent().expect("must have a parent"))?;
+                match std::fs::hard_link(entry.path(), &shared) {
+                    Ok(_) => {}
+                    // Someone else stored the same file just now.
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                    Err(e) => {
+                        tracing::debug!("Not deduplicating files in {}: {e}", root.display());
+                        return Ok(saved);
+                    }
+                }
+            }
+        }
+        Ok(saved)
+    }
+
+    fn location(&self, path: &Path, meta: &std::fs::Metadata) -> std::io::Result<PathBuf> {
+        let mut hasher = IntegrityOpts::new().algorithm(Algorithm::Sha256);
+        std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+        let (_, hex) = hasher.result().to_hex();
+        // Files with different modes can't share an inode.
+        Ok(self
+            .dir
+            .join(&hex[..2])
+            .join(format!("{}-{:o}", &hex[2..], mode(meta))))
+    }
+}
+
+/// Whether the file is already a hard link to something else, like the
+/// cache's content store.
+#[cfg(unix)]
+fn is_shared(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn is_shared(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn mode(meta: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(meta: &std::fs::Metadata) -> u32 {
+    if meta.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+
+    #[test]
+    fn links_identical_files() -> Result<(), NodeMaintainerError> {
+        let cache = tempfile::tempdir()?;
+        let deduper = FileDeduper::in_cache(cache.path());
+        let projects = tempfile::tempdir()?;
+        let a = projects.path().join("a");
+        let b = projects.path().join("b");
+        for dir in [&a, &b] {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(dir.join("LICENSE"), "MIT")?;
+        }
+        std::fs::write(a.join("index.js"), "a")?;
+        std::fs::write(b.join("index.js"), "b")?;
+
+        assert_eq!(deduper.dedupe(&a)?, 0);
+        assert_eq!(deduper.dedupe(&b)?, 3);
+        let license_a = std::fs::metadata(a.join("LICENSE"))?;
+        let license_b = std::fs::metadata(b.join("LICENSE"))?;
+        assert_eq!(license_a.ino(), license_b.ino());
+        let index_a = std::fs::metadata(a.join("index.js"))?;
+        let index_b = std::fs::metadata(b.join("index.js"))?;
+        assert_ne!(index_a.ino(), index_b.ino());
+        assert_eq!(std::fs::read_to_string(b.join("index.js"))?, "b");
+        Ok(())
+    }
+}
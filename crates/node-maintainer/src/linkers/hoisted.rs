@@ -1,19 +1,24 @@
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{atomic, Arc};
 
 use futures::{StreamExt, TryStreamExt};
-use oro_common::BuildManifest;
-use oro_script::OroScript;
+use nassun::PackageResolution;
+use petgraph::stable_graph::NodeIndex;
 use unicase::UniCase;
 use walkdir::WalkDir;
 
+use crate::access::AccessKind;
 use crate::error::NodeMaintainerError;
 use crate::graph::Graph;
-use crate::{META_FILE_NAME, STORE_DIR_NAME};
+use crate::{global, META_FILE_NAME, STORE_DIR_NAME};
 
-use super::LinkerOptions;
+use super::bins::{BinCandidate, BinCandidates};
+use super::materialized::{self, MaterializedPackage, MaterializedTree};
+use super::side_effects::SideEffectsCache;
+use super::{LinkerOptions, ScriptResults, STAGING_DIR_NAME};
 
 pub(crate) struct HoistedLinker(pub(crate) LinkerOptions);
 
@@ -42,15 +47,11 @@ impl HoistedLinker {
             let mut entries = async_std::fs::read_dir(&prefix).await?;
             while let Some(entry) = entries.next().await {
                 let entry = entry?;
-                let ty = entry.file_type().await?;
-                if ty.is_dir() {
-                    async_std::fs::remove_dir_all(entry.path()).await?;
-                } else if ty.is_file() {
-                    async_std::fs::remove_file(entry.path()).await?;
-                } else if ty.is_symlink() && async_std::fs::remove_file(entry.path()).await.is_err()
-                {
-                    async_std::fs::remove_dir_all(entry.path()).await?;
+                if entry.file_name() == STAGING_DIR_NAME {
+                    // Packages are already being staged here.
+                    continue;
                 }
+                super::remove_entry(&self.0, entry.path().as_ref()).await?;
             }
 
             tracing::debug!("No metadata file found in node_modules/. Pruned entire node_modules/ directory in {}ms.", start.elapsed().as_micros() / 1000);
@@ -59,9 +60,23 @@ impl HoistedLinker {
             return Ok(0);
         }
 
+        if let Some(state) = MaterializedTree::load(&prefix) {
+            let extraneous_packages = self.prune_from_state(graph, &prefix, &state).await?;
+            tracing::debug!(
+                "Pruned {extraneous_packages} extraneous package{} using {} in {}ms.",
+                if extraneous_packages == 1 { "" } else { "s" },
+                materialized::STATE_FILE_NAME,
+                start.elapsed().as_micros() / 1000,
+            );
+            return Ok(extraneous_packages);
+        }
+
         let nm_osstr = Some(std::ffi::OsStr::new("node_modules"));
         let bin_osstr = Some(std::ffi::OsStr::new(".bin"));
         let meta = prefix.join(META_FILE_NAME);
+        let state_file = prefix.join(materialized::STATE_FILE_NAME);
+        let staging = prefix.join(STAGING_DIR_NAME);
+        let global_bins = prefix.join(global::GLOBAL_BINS_FILE_NAME);
         let mut extraneous_packages = 0;
         let extraneous = &mut extraneous_packages;
 
@@ -70,8 +85,13 @@ impl HoistedLinker {
             .filter_entry(move |entry| {
                 let entry_path = entry.path();
 
-                if entry_path == meta {
-                    // Skip the meta file
+                if entry_path == meta
+                    || entry_path == state_file
+                    || entry_path == staging
+                    || entry_path == global_bins
+                {
+                    // Skip the meta and state files, anything being staged,
+                    // and the record of bins linked globally.
                     return false;
                 }
 
@@ -150,18 +170,12 @@ impl HoistedLinker {
                     .unwrap_or(false)
             {
                 continue;
-            } else if entry.file_type().is_dir() {
-                if let Some(pb) = &self.0.on_prune_progress {
-                    pb(entry_path);
-                }
-                tracing::trace!("Pruning extraneous directory: {}", entry.path().display());
-                async_std::fs::remove_dir_all(entry.path()).await?;
             } else {
                 if let Some(pb) = &self.0.on_prune_progress {
                     pb(entry_path);
                 }
-                tracing::trace!("Pruning extraneous file: {}", entry.path().display());
-                async_std::fs::remove_file(entry.path()).await?;
+                tracing::trace!("Pruning extraneous entry: {}", entry_path.display());
+                super::remove_entry(&self.0, entry_path).await?;
             }
         }
 
@@ -180,10 +194,60 @@ impl HoistedLinker {
         Ok(extraneous_packages)
     }
 
+    /// Prunes `node_modules/` by comparing the graph against what the last
+    /// apply recorded in `state`, instead of walking the whole directory.
+    /// Recorded packages the graph no longer wants are removed, and so is
+    /// anything unexpected sitting where a new package is about to go.
+    async fn prune_from_state(
+        &self,
+        graph: &Graph,
+        prefix: &Path,
+        state: &MaterializedTree,
+    ) -> Result<usize, NodeMaintainerError> {
+        let mut extraneous = Vec::new();
+        for path in state.paths() {
+            if !state.is_fresh(graph, path) {
+                extraneous.push(path.to_string());
+            }
+        }
+        for idx in graph.inner.node_indices() {
+            if idx == graph.root {
+                continue;
+            }
+            let path = package_subdir(graph, idx);
+            if state.get(&path).is_none() {
+                extraneous.push(path);
+            }
+        }
+        let mut pruned = 0;
+        for path in extraneous {
+            let entry_path = prefix.join(&path);
+            // Parents go first, so their children might already be gone.
+            if async_std::fs::symlink_metadata(&entry_path).await.is_err() {
+                continue;
+            }
+            if let Some(pb) = &self.0.on_prune_progress {
+                pb(&entry_path);
+            }
+            tracing::trace!("Pruning extraneous package: {}", entry_path.display());
+            if super::remove_entry(&self.0, &entry_path).await? {
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
     pub async fn extract(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
         tracing::debug!("Extracting node_modules/...");
         let start = std::time::Instant::now();
 
+        if let Some(dry_run) = &self.0.dry_run {
+            return super::plan_extract(dry_run, graph, |idx| {
+                (!super::is_workspace_link(&self.0, graph, idx))
+                    .then(|| self.package_dir(graph, idx))
+            });
+        }
+
         let root = &self.0.root;
         let stream = futures::stream::iter(graph.inner.node_indices());
         let concurrent_count = Arc::new(AtomicUsize::new(0));
@@ -191,13 +255,20 @@ impl HoistedLinker {
         let total = graph.inner.node_count();
         let total_completed = Arc::new(AtomicUsize::new(0));
         let node_modules = root.join("node_modules");
+        let access = &self.0.access;
+        access.check(AccessKind::Write, &node_modules)?;
         std::fs::create_dir_all(&node_modules)?;
-        let prefer_copy = self.0.prefer_copy
-            || match self.0.cache.as_deref() {
-                Some(cache) => super::supports_reflink(cache, &node_modules),
-                None => false,
-            };
-        let validate = self.0.validate;
+        let prefer_copy = super::prefers_copy(
+            &self.0.link_strategies,
+            self.0.cache.as_deref(),
+            &node_modules,
+        );
+        if let Some(pipeline) = &self.0.pipeline {
+            // Let anything still being staged land before it's needed.
+            pipeline.finish().await;
+        }
+        let previous_state = MaterializedTree::load(&node_modules);
+        self.record_survivors(graph, &node_modules)?;
         stream
             .map(|idx| Ok((idx, concurrent_count.clone(), total_completed.clone(), actually_extracted.clone())))
             .try_for_each_concurrent(
@@ -219,12 +290,31 @@ impl HoistedLinker {
 
                     let start = std::time::Instant::now();
 
-                    if !target_dir.exists() {
-                        graph[child_idx]
-                            .package
-                            .extract_to_dir(&target_dir, prefer_copy, validate)
-                            .await?;
-                        actually_extracted.fetch_add(1, atomic::Ordering::SeqCst);
+                    match graph[child_idx].package.resolved() {
+                        PackageResolution::Link { path, .. } => {
+                            super::link_local_dir(path, &target_dir, access)?;
+                        }
+                        PackageResolution::Dir { path, .. } if super::is_workspace_link(&self.0, graph, child_idx) => {
+                            super::link_local_dir(path, &target_dir, access)?;
+                        }
+                        resolved => {
+                            let fingerprint = if let PackageResolution::Dir { path, .. } = resolved {
+                                Some(super::refresh_local_copy(path, &target_dir, access)?)
+                            } else {
+                                None
+                            };
+                            if !super::is_installed(&target_dir) {
+                                access.check(AccessKind::Write, &target_dir)?;
+                                // Local packages can change at any time, so
+                                // they're never stored.
+                                let store = self.0.global_store.as_ref().filter(|_| fingerprint.is_none());
+                                super::install_package(&self.0, graph, child_idx, &target_dir, store, prefer_copy).await?;
+                                if let Some(fingerprint) = fingerprint {
+                                    super::record_local_fingerprint(&target_dir, &fingerprint, access)?;
+                                }
+                                actually_extracted.fetch_add(1, atomic::Ordering::SeqCst);
+                            }
+                        }
                     }
 
                     if let Some(on_extract) = &self.0.on_extract_progress {
@@ -243,10 +333,33 @@ impl HoistedLinker {
                 },
             )
             .await?;
+        access.check(AccessKind::Write, &node_modules.join(META_FILE_NAME))?;
         std::fs::write(
             node_modules.join(META_FILE_NAME),
             graph.to_kdl()?.to_string(),
         )?;
+        // Bins only need to be read for packages that changed. Local
+        // packages can change without their ID changing, so they're always
+        // read again.
+        let mut state = MaterializedTree::default();
+        for idx in graph.inner.node_indices() {
+            if idx == graph.root {
+                continue;
+            }
+            let path = package_subdir(graph, idx);
+            let id = materialized::package_id(graph, idx);
+            let is_local = matches!(
+                graph[idx].package.resolved(),
+                PackageResolution::Dir { .. } | PackageResolution::Link { .. }
+            );
+            let bins = match previous_state.as_ref().and_then(|state| state.get(&path)) {
+                Some(previous) if previous.id == id && !is_local => previous.bins.clone(),
+                _ => materialized::read_bins(&node_modules.join(&path))?,
+            };
+            state.insert(path, MaterializedPackage { id, bins });
+        }
+        state.write(&node_modules, access)?;
+        super::cleanup_staging(&self.0).await?;
         let actually_extracted = actually_extracted.load(atomic::Ordering::SeqCst);
         tracing::debug!(
             "Extracted {actually_extracted} package{} in {}ms.",
@@ -256,65 +369,155 @@ impl HoistedLinker {
         Ok(actually_extracted)
     }
 
+    /// Until this apply finishes, the only packages in `node_modules/` that
+    /// can be trusted are the ones that were already there and are staying
+    /// put. Recording just those means that if the apply gets interrupted,
+    /// the next one picks up where it left off, instead of mistaking the old
+    /// tree (or a half-finished new one) for what's installed.
+    fn record_survivors(
+        &self,
+        graph: &Graph,
+        node_modules: &Path,
+    ) -> Result<(), NodeMaintainerError> {
+        let access = &self.0.access;
+        let state_file = node_modules.join(materialized::STATE_FILE_NAME);
+        if state_file.exists() {
+            access.check(AccessKind::Remove, &state_file)?;
+            std::fs::remove_file(&state_file)?;
+        }
+        let Some(actual) = &self.0.actual_tree else {
+            return Ok(());
+        };
+        let mut survivors = actual.clone();
+        survivors.packages.retain(|path, node| {
+            graph
+                .node_at_path(Path::new(path.as_str()))
+                .and_then(|ideal| graph.node_lockfile_node(ideal.idx, false).ok())
+                .as_ref()
+                == Some(&*node)
+        });
+        let meta = node_modules.join(META_FILE_NAME);
+        access.check(AccessKind::Write, &meta)?;
+        std::fs::write(&meta, survivors.to_kdl().to_string())?;
+        Ok(())
+    }
+
     async fn link_bins(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        let _timer = self.0.timings.bins();
         tracing::debug!("Linking bins...");
         let start = std::time::Instant::now();
         let root = &self.0.root;
         let linked = Arc::new(AtomicUsize::new(0));
         let bin_file_name = Some(OsStr::new(".bin"));
         let nm_file_name = Some(OsStr::new("node_modules"));
-        for entry in WalkDir::new(root.join("node_modules"))
-            .into_iter()
-            .filter_entry(|e| {
-                let path = e.path().file_name();
-                path == bin_file_name || path == nm_file_name
-            })
-        {
-            let entry = entry?;
-            if entry.path().file_name() == bin_file_name {
-                async_std::fs::remove_dir_all(entry.path()).await?;
+        let state = &MaterializedTree::load(&root.join("node_modules"));
+        let dry_run = self.0.dry_run.as_ref();
+        if dry_run.is_none() {
+            for entry in WalkDir::new(root.join("node_modules"))
+                .into_iter()
+                .filter_entry(|e| {
+                    let path = e.path().file_name();
+                    path == bin_file_name || path == nm_file_name
+                })
+            {
+                let entry = entry?;
+                if entry.path().file_name() == bin_file_name {
+                    super::clear_bin_dir(&self.0, entry.path()).await?;
+                }
+            }
+            if let Some(global_bin_dir) = &self.0.global_bin_dir {
+                global::unlink_global_bins(
+                    &root.join("node_modules"),
+                    global_bin_dir,
+                    &self.0.access,
+                )?;
             }
         }
-        futures::stream::iter(graph.inner.node_indices())
-            .map(|idx| Ok((idx, linked.clone())))
-            .try_for_each_concurrent(self.0.concurrency, move |(idx, linked)| async move {
-                if idx == graph.root {
-                    return Ok(());
-                }
+        // When installing globally, the root's bins go in the global bin
+        // directory instead.
+        let root_bin_dir = root.join("node_modules").join(".bin");
+        // Bins are gathered up front, so packages fighting over the same bin
+        // name get settled the same way every time.
+        let mut wanted: HashMap<PathBuf, BinCandidates> = HashMap::new();
+        for idx in graph.inner.node_indices() {
+            if idx == graph.root {
+                continue;
+            }
+
+            let subdir = package_subdir(graph, idx);
+            let package_dir = root.join("node_modules").join(&subdir);
+            let parent = package_dir.parent().expect("must have parent");
+            let target_dir = if parent.file_name() == Some(OsStr::new("node_modules")) {
+                parent.join(".bin")
+            } else {
+                // Scoped
+                parent.parent().expect("must have parent").join(".bin")
+            };
+            let target_dir = match &self.0.global_bin_dir {
+                Some(global_bin_dir) if target_dir == root_bin_dir => global_bin_dir.clone(),
+                _ => target_dir,
+            };
+
+            // The state file written during extraction already knows
+            // every package's bins.
+            let bins = match state
+                .as_ref()
+                .and_then(|state| state.get(&subdir))
+                .filter(|pkg| pkg.id == materialized::package_id(graph, idx))
+            {
+                Some(pkg) => pkg.bins.clone(),
+                None if dry_run.is_some() => super::planned_bins(graph, idx, &package_dir).await?,
+                None => materialized::read_bins(&package_dir)?,
+            };
 
-                let subdir = graph
-                    .node_path(idx)
-                    .iter()
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>()
-                    .join("/node_modules/");
-                let package_dir = root.join("node_modules").join(subdir);
-                let parent = package_dir.parent().expect("must have parent");
-                let target_dir = if parent.file_name() == Some(OsStr::new("node_modules")) {
-                    parent.join(".bin")
-                } else {
-                    // Scoped
-                    parent.parent().expect("must have parent").join(".bin")
-                };
-
-                let build_mani = BuildManifest::from_path(package_dir.join("package.json"))
-                    .map_err(|e| {
-                        NodeMaintainerError::BuildManifestReadError(
-                            package_dir.join("package.json"),
-                            e,
-                        )
-                    })?;
-
-                for (name, path) in &build_mani.bin {
-                    let target_dir = target_dir.clone();
-                    let to = target_dir.join(name);
-                    let from = package_dir.join(path);
-                    let name = name.clone();
+            // Packages in a `node_modules/` directory are direct dependencies
+            // of its owner if the owner would load them by name.
+            let owner = graph[idx].parent.unwrap_or(graph.root);
+            let direct = graph[owner]
+                .dependencies
+                .contains_key(&UniCase::new(graph[idx].package.name().to_string()));
+            let candidates = wanted.entry(target_dir).or_default();
+            for (name, path) in bins {
+                candidates.add(
+                    name,
+                    BinCandidate {
+                        idx,
+                        direct,
+                        from: package_dir.join(path),
+                    },
+                );
+            }
+        }
+        let mut bins = Vec::new();
+        let mut global_bins = Vec::new();
+        for (target_dir, candidates) in wanted {
+            for (name, from) in candidates.pick(graph, &target_dir, self.0.strict_bins)? {
+                if self.0.global_bin_dir.as_ref() == Some(&target_dir) {
+                    global_bins.push(name.clone());
+                }
+                bins.push((target_dir.clone(), name, from));
+            }
+        }
+        if let (Some(_), None) = (&self.0.global_bin_dir, dry_run) {
+            global::record_global_bins(&root.join("node_modules"), &global_bins, &self.0.access)?;
+        }
+        futures::stream::iter(bins)
+            .map(|bin| Ok((bin, linked.clone())))
+            .try_for_each_concurrent(
+                self.0.concurrency,
+                move |((target_dir, name, from), linked)| async move {
+                    let to = target_dir.join(&name);
+                    if let Some(dry_run) = dry_run {
+                        dry_run.link_bin(&from, &to);
+                        linked.fetch_add(1, atomic::Ordering::SeqCst);
+                        return Ok(());
+                    }
+                    let access = self.0.access.clone();
+                    let bin_symlinks = self.0.bin_symlinks;
                     async_std::task::spawn_blocking(move || {
                         // We only create a symlink if the target bin exists.
                         if from.symlink_metadata().is_ok() {
                             std::fs::create_dir_all(target_dir)?;
-                            // TODO: use a DashMap here to prevent race conditions, maybe?
                             if let Ok(meta) = to.symlink_metadata() {
                                 if meta.is_dir() {
                                     std::fs::remove_dir_all(&to)?;
@@ -322,7 +525,7 @@ impl HoistedLinker {
                                     std::fs::remove_file(&to)?;
                                 }
                             }
-                            super::link_bin(&from, &to)?;
+                            super::link_bin(&from, &to, &access, bin_symlinks)?;
                             tracing::trace!(
                                 "Linked bin for {} from {} to {}",
                                 name,
@@ -334,9 +537,9 @@ impl HoistedLinker {
                     })
                     .await?;
                     linked.fetch_add(1, atomic::Ordering::SeqCst);
-                }
-                Ok::<_, NodeMaintainerError>(())
-            })
+                    Ok::<_, NodeMaintainerError>(())
+                },
+            )
             .await?;
         let linked = linked.load(atomic::Ordering::SeqCst);
         tracing::debug!(
@@ -351,15 +554,52 @@ impl HoistedLinker {
         graph: &Graph,
         ignore_scripts: bool,
     ) -> Result<(), NodeMaintainerError> {
+        if self.0.dry_run.is_some() {
+            // Nothing was actually installed, so there's nothing to run
+            // scripts in.
+            self.link_bins(graph).await?;
+            return Ok(());
+        }
         tracing::debug!("Running lifecycle scripts...");
         let start = std::time::Instant::now();
+        let side_effects = if ignore_scripts {
+            None
+        } else {
+            SideEffectsCache::load(&self.0)
+        };
+        let package_dir = |idx| Some(self.package_dir(graph, idx));
+        let restored = match &side_effects {
+            Some(cache) => cache.restore(graph, package_dir).await?,
+            None => HashSet::new(),
+        };
+        let platform = if ignore_scripts {
+            None
+        } else {
+            ScriptResults::platform()
+        };
+        let mut done = match &platform {
+            Some(platform) => self
+                .0
+                .script_results
+                .unchanged(graph, package_dir, platform),
+            None => HashSet::new(),
+        };
+        done.extend(&restored);
         if !ignore_scripts {
-            self.run_scripts(graph, "preinstall").await?;
+            self.run_scripts(graph, &done, "preinstall").await?;
         }
         self.link_bins(graph).await?;
         if !ignore_scripts {
-            self.run_scripts(graph, "install").await?;
-            self.run_scripts(graph, "postinstall").await?;
+            self.run_scripts(graph, &done, "install").await?;
+            self.run_scripts(graph, &done, "postinstall").await?;
+            if let Some(cache) = &side_effects {
+                cache.save(&self.0, graph, package_dir, &restored).await?;
+            }
+            if let Some(platform) = &platform {
+                self.0
+                    .script_results
+                    .save(&self.0, graph, package_dir, platform)?;
+            }
         }
         tracing::debug!(
             "Ran lifecycle scripts in {}ms.",
@@ -368,123 +608,39 @@ impl HoistedLinker {
         Ok(())
     }
 
-    async fn run_scripts(&self, graph: &Graph, event: &str) -> Result<(), NodeMaintainerError> {
-        tracing::debug!("Running {event} lifecycle scripts");
-        let start = std::time::Instant::now();
+    /// Where the package at `idx` ends up installed.
+    pub(crate) fn package_dir(&self, graph: &Graph, idx: NodeIndex) -> PathBuf {
         let root = &self.0.root;
-        futures::stream::iter(graph.inner.node_indices())
-            .map(Ok)
-            .try_for_each_concurrent(self.0.script_concurrency, move |idx| async move {
-                let package_dir = if idx == graph.root {
-                    root.clone()
-                } else {
-                    let subdir = graph
-                        .node_path(idx)
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<_>>()
-                        .join("/node_modules/");
-                    root.join("node_modules").join(subdir)
-                };
-
-                let is_optional = graph.is_optional(idx);
-
-                let build_mani = BuildManifest::from_path(package_dir.join("package.json"))
-                    .map_err(|e| {
-                        NodeMaintainerError::BuildManifestReadError(
-                            package_dir.join("package.json"),
-                            e,
-                        )
-                    })?;
-
-                let name = graph[idx].package.name().to_string();
-                if build_mani.scripts.contains_key(event) {
-                    let package_dir = package_dir.clone();
-                    let root = root.clone();
-                    let event = event.to_owned();
-                    let event_clone = event.clone();
-                    let span = tracing::info_span!("script");
-                    let _span_enter = span.enter();
-                    if let Some(on_script_start) = &self.0.on_script_start {
-                        on_script_start(&graph[idx].package, &event);
-                    }
-                    std::mem::drop(_span_enter);
-                    let mut script = match async_std::task::spawn_blocking(move || {
-                        OroScript::new(package_dir, event_clone)?
-                            .workspace_path(root)
-                            .spawn()
-                    })
-                    .await
-                    {
-                        Ok(script) => script,
-                        Err(e) if is_optional => {
-                            let e: NodeMaintainerError = e.into();
-                            tracing::debug!("Error in optional dependency script: {}", e);
-                            return Ok(());
-                        }
-                        Err(e) => return Err(e.into()),
-                    };
-                    let stdout = script.stdout.take();
-                    let stderr = script.stderr.take();
-                    let stdout_name = name.clone();
-                    let stderr_name = name.clone();
-                    let stdout_on_line = self.0.on_script_line.clone();
-                    let stderr_on_line = self.0.on_script_line.clone();
-                    let stdout_span = span;
-                    let stderr_span = stdout_span.clone();
-                    let event_clone = event.clone();
-                    let join = futures::try_join!(
-                        async_std::task::spawn_blocking(move || {
-                            let _enter = stdout_span.enter();
-                            if let Some(stdout) = stdout {
-                                for line in BufReader::new(stdout).lines() {
-                                    let line = line?;
-                                    tracing::debug!("stdout::{stdout_name}::{event}: {}", line);
-                                    if let Some(on_script_line) = &stdout_on_line {
-                                        on_script_line(&line);
-                                    }
-                                }
-                            }
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                        async_std::task::spawn_blocking(move || {
-                            let _enter = stderr_span.enter();
-                            if let Some(stderr) = stderr {
-                                for line in BufReader::new(stderr).lines() {
-                                    let line = line?;
-                                    tracing::debug!(
-                                        "stderr::{stderr_name}::{event_clone}: {}",
-                                        line
-                                    );
-                                    if let Some(on_script_line) = &stderr_on_line {
-                                        on_script_line(&line);
-                                    }
-                                }
-                            }
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                        async_std::task::spawn_blocking(move || {
-                            script.wait()?;
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                    );
-                    match join {
-                        Ok(_) => {}
-                        Err(e) if is_optional => {
-                            tracing::debug!("Error in optional dependency script: {}", e);
-                            return Ok(());
-                        }
-                        Err(e) => return Err(e),
-                    }
-                }
+        if idx == graph.root {
+            root.clone()
+        } else {
+            root.join("node_modules").join(package_subdir(graph, idx))
+        }
+    }
 
-                Ok::<_, NodeMaintainerError>(())
+    async fn run_scripts(
+        &self,
+        graph: &Graph,
+        done: &HashSet<NodeIndex>,
+        event: &str,
+    ) -> Result<(), NodeMaintainerError> {
+        super::run_scripts(&self.0, graph, done, event, |idx| {
+            Some(super::ScriptDir {
+                package: self.package_dir(graph, idx),
+                workspace: self.0.root.clone(),
+                env: Vec::new(),
             })
-            .await?;
-        tracing::debug!(
-            "Ran lifecycle scripts for {event} in {}ms.",
-            start.elapsed().as_millis()
-        );
-        Ok(())
+        })
+        .await
     }
 }
+
+/// Path of the package at `idx`, relative to the root `node_modules/`.
+fn package_subdir(graph: &Graph, idx: NodeIndex) -> String {
+    graph
+        .node_path(idx)
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join("/node_modules/")
+}
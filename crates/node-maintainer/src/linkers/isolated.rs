@@ -1,7 +1,6 @@
 use std::{
-    collections::{HashMap, HashSet},
-    io::{BufRead, BufReader},
-    path::Path,
+    collections::{btree_map::Entry, BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
     sync::{
         atomic::{self, AtomicUsize},
         Arc,
@@ -9,14 +8,20 @@ use std::{
 };
 
 use futures::{StreamExt, TryStreamExt};
+use nassun::PackageResolution;
 use oro_common::BuildManifest;
-use oro_script::OroScript;
 use petgraph::{stable_graph::NodeIndex, visit::EdgeRef, Direction};
 use ssri::Integrity;
 
-use crate::{graph::Graph, NodeMaintainerError, META_FILE_NAME, STORE_DIR_NAME};
+use crate::{
+    access::{AccessKind, AccessTracker},
+    graph::Graph,
+    nohoist, NodeMaintainerError, META_FILE_NAME, STORE_DIR_NAME,
+};
 
-use super::LinkerOptions;
+use super::bins::{BinCandidate, BinCandidates};
+use super::side_effects::SideEffectsCache;
+use super::{LinkerOptions, ScriptResults, STAGING_DIR_NAME};
 
 pub(crate) struct IsolatedLinker(pub(crate) LinkerOptions);
 
@@ -43,16 +48,11 @@ impl IsolatedLinker {
             let mut entries = async_std::fs::read_dir(&prefix).await?;
             while let Some(entry) = entries.next().await {
                 let entry = entry?;
-                let path = entry.path();
-                let ty = entry.file_type().await?;
-                if ty.is_dir() {
-                    async_std::fs::remove_dir_all(&path).await?;
-                } else if ty.is_file() {
-                    async_std::fs::remove_file(&path).await?;
-                } else if ty.is_symlink() && async_std::fs::remove_file(entry.path()).await.is_err()
-                {
-                    async_std::fs::remove_dir_all(&path).await?;
+                if entry.file_name() == STAGING_DIR_NAME {
+                    // Packages are already being staged here.
+                    continue;
                 }
+                super::remove_entry(&self.0, entry.path().as_ref()).await?;
             }
 
             tracing::debug!("No metadata file found in node_modules/. Pruned entire node_modules/ directory in {}ms.", start.elapsed().as_micros() / 1000);
@@ -75,7 +75,10 @@ impl IsolatedLinker {
             idx
         });
 
+        let hoisted = public_hoisted(graph, &self.0.public_hoist_patterns);
+        let hoisted_ref = &hoisted;
         let prefix_ref = &prefix;
+        let opts = &self.0;
         futures::stream::iter(indices)
             .map(Ok)
             .try_for_each_concurrent(self.0.concurrency, move |idx| async move {
@@ -93,11 +96,20 @@ impl IsolatedLinker {
 
                 let mut expected_deps = HashMap::new();
 
-                for edge in graph.inner.edges_directed(idx, Direction::Outgoing) {
-                    let dep_pkg = &graph[edge.target()].package;
+                let deps = graph
+                    .inner
+                    .edges_directed(idx, Direction::Outgoing)
+                    .map(|edge| edge.target());
+                let hoisted_deps = if idx == graph.root {
+                    &hoisted_ref[..]
+                } else {
+                    &[]
+                };
+                for dep_idx in deps.chain(hoisted_deps.iter().copied()) {
+                    let dep_pkg = &graph[dep_idx].package;
                     let dep_store_dir = async_std::path::PathBuf::from(
                         store_ref
-                            .join(package_dir_name(graph, edge.target()))
+                            .join(package_dir_name(graph, dep_idx))
                             .join("node_modules")
                             .join(dep_pkg.name()),
                     );
@@ -117,16 +129,11 @@ impl IsolatedLinker {
                             if let Some(target) = expected.get(&path) {
                                 let target = target.clone();
                                 let ty = entry.file_type().await?;
-                                if ty.is_file() {
-                                    async_std::fs::remove_file(&path).await?;
-                                } else if ty.is_dir() {
-                                    async_std::fs::remove_dir_all(&path).await?;
-                                } else if ty.is_symlink() && target != path.read_link().await? {
-                                    if async_std::fs::remove_file(&path).await.is_err() {
-                                        async_std::fs::remove_dir_all(&path).await?;
-                                    }
-                                } else if ty.is_dir() {
-                                    async_std::fs::remove_dir_all(&path).await?;
+                                if ty.is_file()
+                                    || ty.is_dir()
+                                    || (ty.is_symlink() && target != path.read_link().await?)
+                                {
+                                    super::remove_entry(opts, path.as_ref()).await?;
                                 } else {
                                     #[cfg(windows)]
                                     let path_clone = path.clone();
@@ -140,9 +147,8 @@ impl IsolatedLinker {
                                         )
                                     })
                                     .await?
-                                        && async_std::fs::remove_file(&path).await.is_err()
                                     {
-                                        async_std::fs::remove_dir_all(&path).await?;
+                                        super::remove_entry(opts, path.as_ref()).await?;
                                     }
                                 }
                             }
@@ -182,31 +188,20 @@ impl IsolatedLinker {
                                 let next = next?;
                                 if !expected_ref.contains::<std::path::PathBuf>(&next.path().into())
                                 {
-                                    let ty = next.file_type().await?;
-                                    if ty.is_file() {
-                                        async_std::fs::remove_file(next.path()).await?;
-                                    } else if ty.is_dir() {
-                                        async_std::fs::remove_dir_all(next.path()).await?;
-                                    } else if ty.is_symlink()
-                                        && async_std::fs::remove_file(next.path()).await.is_err()
-                                    {
-                                        async_std::fs::remove_dir_all(next.path()).await?;
+                                    if super::remove_entry(opts, next.path().as_ref()).await? {
+                                        pruned.fetch_add(1, atomic::Ordering::SeqCst);
                                     }
-                                    pruned.fetch_add(1, atomic::Ordering::SeqCst);
                                 }
                             }
                         } else {
-                            async_std::fs::remove_dir_all(entry.path()).await?;
+                            if super::remove_entry(opts, path).await? {
+                                pruned.fetch_add(1, atomic::Ordering::SeqCst);
+                            }
+                        }
+                    } else {
+                        if super::remove_entry(opts, path).await? {
                             pruned.fetch_add(1, atomic::Ordering::SeqCst);
                         }
-                    } else if ty.is_file() {
-                        async_std::fs::remove_file(entry.path()).await?;
-                        pruned.fetch_add(1, atomic::Ordering::SeqCst);
-                    } else if ty.is_symlink()
-                        && async_std::fs::remove_file(entry.path()).await.is_err()
-                    {
-                        async_std::fs::remove_dir_all(entry.path()).await?;
-                        pruned.fetch_add(1, atomic::Ordering::SeqCst);
                     }
                 }
                 Ok::<_, NodeMaintainerError>(())
@@ -233,6 +228,13 @@ impl IsolatedLinker {
         tracing::debug!("Applying node_modules/...");
         let start = std::time::Instant::now();
 
+        if let Some(dry_run) = &self.0.dry_run {
+            return super::plan_extract(dry_run, graph, |idx| {
+                (!super::is_workspace_link(&self.0, graph, idx))
+                    .then(|| self.package_dir(graph, idx))
+            });
+        }
+
         let root = &self.0.root;
         let store = root.join("node_modules").join(STORE_DIR_NAME);
         let store_ref = &store;
@@ -242,20 +244,35 @@ impl IsolatedLinker {
         let total = graph.inner.node_count();
         let total_completed = Arc::new(AtomicUsize::new(0));
         let node_modules = root.join("node_modules");
+        let access = &self.0.access;
+        access.check(AccessKind::Write, &node_modules)?;
         std::fs::create_dir_all(&node_modules)?;
-        let prefer_copy = self.0.prefer_copy
-            || match self.0.cache.as_deref() {
-                Some(cache) => super::supports_reflink(cache, &node_modules),
-                None => false,
-            };
-        let validate = self.0.validate;
+        let prefer_copy = super::prefers_copy(
+            &self.0.link_strategies,
+            self.0.cache.as_deref(),
+            &node_modules,
+        );
+        if let Some(pipeline) = &self.0.pipeline {
+            // Let anything still being staged land before it's needed.
+            pipeline.finish().await;
+        }
+        #[cfg(windows)]
+        let symlinks = super::supports_symlinks(&node_modules);
+        #[cfg(not(windows))]
+        let symlinks = true;
+        let hoisted = public_hoisted(graph, &self.0.public_hoist_patterns);
+        let hoisted_ref = &hoisted;
         stream
             .map(|idx| Ok((idx, concurrent_count.clone(), total_completed.clone(), actually_extracted.clone())))
             .try_for_each_concurrent(
                 self.0.concurrency,
                 move |(child_idx, concurrent_count, total_completed, actually_extracted)| async move {
                     if child_idx == graph.root {
-                        link_deps(graph, child_idx, store_ref, &root.join("node_modules")).await?;
+                        let root_nm = root.join("node_modules");
+                        link_deps(graph, child_idx, store_ref, &root_nm, access, symlinks).await?;
+                        for &idx in hoisted_ref {
+                            link_dep(graph, idx, store_ref, &root_nm, access, symlinks).await?;
+                        }
                         return Ok(());
                     }
 
@@ -269,15 +286,34 @@ impl IsolatedLinker {
 
                     let start = std::time::Instant::now();
 
-                    if !target_dir.exists() {
-                        graph[child_idx]
-                            .package
-                            .extract_to_dir(&target_dir, prefer_copy, validate)
-                            .await?;
-                        actually_extracted.fetch_add(1, atomic::Ordering::SeqCst);
+                    match graph[child_idx].package.resolved() {
+                        PackageResolution::Link { path, .. } => {
+                            super::link_local_dir(path, &target_dir, access)?;
+                        }
+                        PackageResolution::Dir { path, .. } if super::is_workspace_link(&self.0, graph, child_idx) => {
+                            super::link_local_dir(path, &target_dir, access)?;
+                        }
+                        resolved => {
+                            let fingerprint = if let PackageResolution::Dir { path, .. } = resolved {
+                                Some(super::refresh_local_copy(path, &target_dir, access)?)
+                            } else {
+                                None
+                            };
+                            if !super::is_installed(&target_dir) {
+                                access.check(AccessKind::Write, &target_dir)?;
+                                // Local packages can change at any time, so
+                                // they're never stored.
+                                let store = self.0.global_store.as_ref().filter(|_| fingerprint.is_none());
+                                super::install_package(&self.0, graph, child_idx, &target_dir, store, prefer_copy).await?;
+                                if let Some(fingerprint) = fingerprint {
+                                    super::record_local_fingerprint(&target_dir, &fingerprint, access)?;
+                                }
+                                actually_extracted.fetch_add(1, atomic::Ordering::SeqCst);
+                            }
+                        }
                     }
 
-                    link_deps(graph, child_idx, store_ref, &target_dir.join("node_modules")).await?;
+                    link_deps(graph, child_idx, store_ref, &target_dir.join("node_modules"), access, symlinks).await?;
 
                     if let Some(on_extract) = &self.0.on_extract_progress {
                         on_extract(&graph[child_idx].package);
@@ -296,10 +332,12 @@ impl IsolatedLinker {
                 },
             )
             .await?;
+        access.check(AccessKind::Write, &node_modules.join(META_FILE_NAME))?;
         std::fs::write(
             node_modules.join(META_FILE_NAME),
             graph.to_kdl()?.to_string(),
         )?;
+        super::cleanup_staging(&self.0).await?;
         let actually_extracted = actually_extracted.load(atomic::Ordering::SeqCst);
 
         tracing::debug!(
@@ -311,6 +349,7 @@ impl IsolatedLinker {
     }
 
     async fn link_bins(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        let _timer = self.0.timings.bins();
         tracing::debug!("Linking bins...");
         let start = std::time::Instant::now();
         let root = &self.0.root;
@@ -327,6 +366,7 @@ impl IsolatedLinker {
                         idx,
                         store_ref,
                         &root.join("node_modules").join(".bin"),
+                        &self.0,
                     )
                     .await?;
                     linked.fetch_add(added, atomic::Ordering::SeqCst);
@@ -341,7 +381,7 @@ impl IsolatedLinker {
                     .join("node_modules")
                     .join(".bin");
 
-                let added = link_dep_bins(graph, idx, store_ref, &pkg_bin_dir).await?;
+                let added = link_dep_bins(graph, idx, store_ref, &pkg_bin_dir, &self.0).await?;
                 linked.fetch_add(added, atomic::Ordering::SeqCst);
 
                 Ok::<_, NodeMaintainerError>(())
@@ -361,15 +401,52 @@ impl IsolatedLinker {
         graph: &Graph,
         ignore_scripts: bool,
     ) -> Result<(), NodeMaintainerError> {
+        if self.0.dry_run.is_some() {
+            // Nothing was actually installed, so there's nothing to run
+            // scripts in.
+            self.link_bins(graph).await?;
+            return Ok(());
+        }
         tracing::debug!("Running lifecycle scripts...");
         let start = std::time::Instant::now();
+        let side_effects = if ignore_scripts {
+            None
+        } else {
+            SideEffectsCache::load(&self.0)
+        };
+        let package_dir = |idx| Some(self.package_dir(graph, idx));
+        let restored = match &side_effects {
+            Some(cache) => cache.restore(graph, package_dir).await?,
+            None => HashSet::new(),
+        };
+        let platform = if ignore_scripts {
+            None
+        } else {
+            ScriptResults::platform()
+        };
+        let mut done = match &platform {
+            Some(platform) => self
+                .0
+                .script_results
+                .unchanged(graph, package_dir, platform),
+            None => HashSet::new(),
+        };
+        done.extend(&restored);
         if !ignore_scripts {
-            self.run_scripts(graph, "preinstall").await?;
+            self.run_scripts(graph, &done, "preinstall").await?;
         }
         self.link_bins(graph).await?;
         if !ignore_scripts {
-            self.run_scripts(graph, "install").await?;
-            self.run_scripts(graph, "postinstall").await?;
+            self.run_scripts(graph, &done, "install").await?;
+            self.run_scripts(graph, &done, "postinstall").await?;
+            if let Some(cache) = &side_effects {
+                cache.save(&self.0, graph, package_dir, &restored).await?;
+            }
+            if let Some(platform) = &platform {
+                self.0
+                    .script_results
+                    .save(&self.0, graph, package_dir, platform)?;
+            }
         }
         tracing::debug!(
             "Ran lifecycle scripts in {}ms.",
@@ -378,132 +455,52 @@ impl IsolatedLinker {
         Ok(())
     }
 
-    async fn run_scripts(&self, graph: &Graph, event: &str) -> Result<(), NodeMaintainerError> {
-        tracing::debug!("Running {event} lifecycle scripts");
-        let start = std::time::Instant::now();
+    /// Where the package at `idx` ends up installed.
+    pub(crate) fn package_dir(&self, graph: &Graph, idx: NodeIndex) -> PathBuf {
         let root = &self.0.root;
-        let store = root.join("node_modules").join(STORE_DIR_NAME);
-        let store_ref = &store;
-        futures::stream::iter(graph.inner.node_indices())
-            .map(Ok)
-            .try_for_each_concurrent(self.0.script_concurrency, move |idx| async move {
-                let pkg_dir = if idx == graph.root {
-                    root.clone()
-                } else {
-                    let pkg = &graph[idx].package;
-                    store_ref
-                        .join(package_dir_name(graph, idx))
-                        .join("node_modules")
-                        .join(pkg.name())
-                };
-
-                let is_optional = graph.is_optional(idx);
-
-                let build_mani =
-                    BuildManifest::from_path(pkg_dir.join("package.json")).map_err(|e| {
-                        NodeMaintainerError::BuildManifestReadError(pkg_dir.join("package.json"), e)
-                    })?;
-
-                let name = graph[idx].package.name().to_string();
-                if build_mani.scripts.contains_key(event) {
-                    let package_dir = pkg_dir.clone();
-                    let package_dir_clone = package_dir.clone();
-                    let event = event.to_owned();
-                    let event_clone = event.clone();
-                    let span = tracing::info_span!("script");
-                    let _span_enter = span.enter();
-                    if let Some(on_script_start) = &self.0.on_script_start {
-                        on_script_start(&graph[idx].package, &event);
-                    }
-                    std::mem::drop(_span_enter);
-                    let mut script = match async_std::task::spawn_blocking(move || {
-                        OroScript::new(package_dir, event_clone)?
-                            .workspace_path(package_dir_clone)
-                            .spawn()
-                    })
-                    .await
-                    {
-                        Ok(script) => script,
-                        Err(e) if is_optional => {
-                            let e: NodeMaintainerError = e.into();
-                            tracing::debug!("Error in optional dependency script: {}", e);
-                            return Ok(());
-                        }
-                        Err(e) => return Err(e.into()),
-                    };
-                    let stdout = script.stdout.take();
-                    let stderr = script.stderr.take();
-                    let stdout_name = name.clone();
-                    let stderr_name = name.clone();
-                    let stdout_on_line = self.0.on_script_line.clone();
-                    let stderr_on_line = self.0.on_script_line.clone();
-                    let stdout_span = span;
-                    let stderr_span = stdout_span.clone();
-                    let event_clone = event.clone();
-                    let join = futures::try_join!(
-                        async_std::task::spawn_blocking(move || {
-                            let _enter = stdout_span.enter();
-                            if let Some(stdout) = stdout {
-                                for line in BufReader::new(stdout).lines() {
-                                    let line = line?;
-                                    tracing::debug!("stdout::{stdout_name}::{event}: {}", line);
-                                    if let Some(on_script_line) = &stdout_on_line {
-                                        on_script_line(&line);
-                                    }
-                                }
-                            }
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                        async_std::task::spawn_blocking(move || {
-                            let _enter = stderr_span.enter();
-                            if let Some(stderr) = stderr {
-                                for line in BufReader::new(stderr).lines() {
-                                    let line = line?;
-                                    tracing::debug!(
-                                        "stderr::{stderr_name}::{event_clone}: {}",
-                                        line
-                                    );
-                                    if let Some(on_script_line) = &stderr_on_line {
-                                        on_script_line(&line);
-                                    }
-                                }
-                            }
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                        async_std::task::spawn_blocking(move || {
-                            script.wait()?;
-                            Ok::<_, NodeMaintainerError>(())
-                        }),
-                    );
-                    match join {
-                        Ok(_) => {}
-                        Err(e) if is_optional => {
-                            tracing::debug!("Error in optional dependency script: {}", e);
-                            return Ok(());
-                        }
-                        Err(e) => return Err(e),
-                    }
-                }
+        if idx == graph.root {
+            root.clone()
+        } else {
+            root.join("node_modules")
+                .join(STORE_DIR_NAME)
+                .join(package_dir_name(graph, idx))
+                .join("node_modules")
+                .join(graph[idx].package.name())
+        }
+    }
 
-                Ok::<_, NodeMaintainerError>(())
+    async fn run_scripts(
+        &self,
+        graph: &Graph,
+        done: &HashSet<NodeIndex>,
+        event: &str,
+    ) -> Result<(), NodeMaintainerError> {
+        super::run_scripts(&self.0, graph, done, event, |idx| {
+            let package_dir = self.package_dir(graph, idx);
+            Some(super::ScriptDir {
+                workspace: package_dir.clone(),
+                package: package_dir,
+                env: Vec::new(),
             })
-            .await?;
-        tracing::debug!(
-            "Ran lifecycle scripts for {event} in {}ms.",
-            start.elapsed().as_millis()
-        );
-        Ok(())
+        })
+        .await
     }
 }
 
 fn package_dir_name(graph: &Graph, idx: NodeIndex) -> String {
     let pkg = &graph[idx].package;
-    let subdir = graph
+    let mut subdir = graph
         .node_path(idx)
         .iter()
         .map(|x| x.to_string())
         .collect::<Vec<_>>()
         .join("/node_modules/");
+    // Patched packages go in their own directory, so that changing the patch
+    // gets them reinstalled.
+    if let Some(patch) = &graph[idx].patch {
+        subdir.push('\n');
+        subdir.push_str(&patch.to_string());
+    }
 
     let mut name = pkg.name().to_string();
     name.push('@');
@@ -513,43 +510,97 @@ fn package_dir_name(graph: &Graph, idx: NodeIndex) -> String {
     name
 }
 
+/// Packages from deeper in the tree that also get linked into the root
+/// `node_modules/`, because their names match one of the `public-hoist`
+/// patterns. This keeps tools that resolve packages naively, like editor
+/// plugins looking for `eslint` or `prettier`, working. The root's own
+/// dependencies always win, and otherwise the copy closest to the root gets
+/// linked, so there's only ever one package per name.
+fn public_hoisted(graph: &Graph, patterns: &[String]) -> Vec<NodeIndex> {
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+    let direct = graph
+        .inner
+        .edges_directed(graph.root, Direction::Outgoing)
+        .map(|edge| graph[edge.target()].package.name())
+        .collect::<HashSet<_>>();
+    let mut hoisted = BTreeMap::new();
+    for idx in graph.inner.node_indices() {
+        let name = graph[idx].package.name();
+        if idx == graph.root || direct.contains(name) || !nohoist::matches_name(patterns, name) {
+            continue;
+        }
+        match hoisted.entry(name) {
+            Entry::Vacant(entry) => {
+                entry.insert(idx);
+            }
+            Entry::Occupied(mut entry) => {
+                if graph.node_path(idx).len() < graph.node_path(*entry.get()).len() {
+                    entry.insert(idx);
+                }
+            }
+        }
+    }
+    hoisted.into_values().collect()
+}
+
 async fn link_deps(
     graph: &Graph,
     node: NodeIndex,
     store_ref: &Path,
     target_nm: &Path,
+    access: &Arc<AccessTracker>,
+    symlinks: bool,
 ) -> Result<(), NodeMaintainerError> {
     // Then we symlink/junction all of the package's dependencies into its `node_modules` dir.
     for edge in graph.inner.edges_directed(node, Direction::Outgoing) {
-        let dep_pkg = &graph[edge.target()].package;
-        let dep_store_dir = store_ref
-            .join(package_dir_name(graph, edge.target()))
-            .join("node_modules")
-            .join(dep_pkg.name());
-        let dep_nm_entry = target_nm.join(dep_pkg.name());
-        if dep_nm_entry.exists() {
-            continue;
-        }
-        let relative = pathdiff::diff_paths(
-            &dep_store_dir,
-            dep_nm_entry.parent().expect("must have a parent"),
-        )
-        .expect("this should never fail");
-        async_std::task::spawn_blocking(move || {
-            std::fs::create_dir_all(dep_nm_entry.parent().expect("definitely has a parent"))?;
-            if dep_nm_entry.symlink_metadata().is_err() {
-                // We don't check the link target here because we assume prune() has already been run and removed any incorrect links.
-                #[cfg(windows)]
+        link_dep(graph, edge.target(), store_ref, target_nm, access, symlinks).await?;
+    }
+    Ok(())
+}
+
+async fn link_dep(
+    graph: &Graph,
+    dep: NodeIndex,
+    store_ref: &Path,
+    target_nm: &Path,
+    access: &Arc<AccessTracker>,
+    #[cfg_attr(not(windows), allow(unused_variables))] symlinks: bool,
+) -> Result<(), NodeMaintainerError> {
+    let dep_pkg = &graph[dep].package;
+    let dep_store_dir = store_ref
+        .join(package_dir_name(graph, dep))
+        .join("node_modules")
+        .join(dep_pkg.name());
+    let dep_nm_entry = target_nm.join(dep_pkg.name());
+    if dep_nm_entry.exists() {
+        return Ok(());
+    }
+    let relative = pathdiff::diff_paths(
+        &dep_store_dir,
+        dep_nm_entry.parent().expect("must have a parent"),
+    )
+    .expect("this should never fail");
+    let access = access.clone();
+    async_std::task::spawn_blocking(move || {
+        access.check(AccessKind::Link, &dep_nm_entry)?;
+        std::fs::create_dir_all(dep_nm_entry.parent().expect("definitely has a parent"))?;
+        if dep_nm_entry.symlink_metadata().is_err() {
+            // We don't check the link target here because we assume prune() has already been run and removed any incorrect links.
+            #[cfg(windows)]
+            if symlinks {
                 std::os::windows::fs::symlink_dir(&relative, &dep_nm_entry)
                     .or_else(|_| junction::create(&dep_store_dir, &dep_nm_entry))?;
-                #[cfg(unix)]
-                std::os::unix::fs::symlink(&relative, &dep_nm_entry)?;
+            } else {
+                junction::create(&dep_store_dir, &dep_nm_entry)?;
             }
-            Ok::<(), NodeMaintainerError>(())
-        })
-        .await?;
-    }
-    Ok(())
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&relative, &dep_nm_entry)?;
+        }
+        Ok::<(), NodeMaintainerError>(())
+    })
+    .await
 }
 
 async fn link_dep_bins(
@@ -557,47 +608,74 @@ async fn link_dep_bins(
     node: NodeIndex,
     store_ref: &Path,
     target_bin: &Path,
+    opts: &LinkerOptions,
 ) -> Result<usize, NodeMaintainerError> {
-    let mut linked = 0;
+    let mut candidates = BinCandidates::default();
     for edge in graph.inner.edges_directed(node, Direction::Outgoing) {
         let dep_pkg = &graph[edge.target()].package;
         let dep_store_dir = store_ref
             .join(package_dir_name(graph, edge.target()))
             .join("node_modules")
             .join(dep_pkg.name());
-        let build_mani =
-            BuildManifest::from_path(dep_store_dir.join("package.json")).map_err(|e| {
-                NodeMaintainerError::BuildManifestReadError(dep_store_dir.join("package.json"), e)
-            })?;
-        for (name, path) in &build_mani.bin {
-            let target_bin = target_bin.to_owned();
-            let to = target_bin.join(name);
-            let from = dep_store_dir.join(path);
-            let name = name.clone();
-            async_std::task::spawn_blocking(move || {
-                // We only create a symlink if the target bin exists.
-                if from.symlink_metadata().is_ok() {
-                    std::fs::create_dir_all(target_bin)?;
-                    if let Ok(meta) = to.symlink_metadata() {
-                        if meta.is_dir() {
-                            std::fs::remove_dir_all(&to)?;
-                        } else {
-                            std::fs::remove_file(&to)?;
-                        }
-                    }
-                    super::link_bin(&from, &to)?;
-                    tracing::trace!(
-                        "Linked bin for {} from {} to {}",
-                        name,
-                        from.display(),
-                        to.display()
-                    );
-                }
-                Ok::<_, NodeMaintainerError>(())
-            })
-            .await?;
+        let bins = if opts.dry_run.is_some() {
+            super::planned_bins(graph, edge.target(), &dep_store_dir).await?
+        } else {
+            BuildManifest::from_path(dep_store_dir.join("package.json"))
+                .map_err(|e| {
+                    NodeMaintainerError::BuildManifestReadError(
+                        dep_store_dir.join("package.json"),
+                        e,
+                    )
+                })?
+                .bin
+                .into_iter()
+                .collect()
+        };
+        for (name, path) in bins {
+            candidates.add(
+                name,
+                BinCandidate {
+                    idx: edge.target(),
+                    direct: true,
+                    from: dep_store_dir.join(path),
+                },
+            );
+        }
+    }
+    let mut linked = 0;
+    for (name, from) in candidates.pick(graph, target_bin, opts.strict_bins)? {
+        let target_bin = target_bin.to_owned();
+        let to = target_bin.join(&name);
+        if let Some(dry_run) = &opts.dry_run {
+            dry_run.link_bin(&from, &to);
             linked += 1;
+            continue;
         }
+        let access = opts.access.clone();
+        let bin_symlinks = opts.bin_symlinks;
+        async_std::task::spawn_blocking(move || {
+            // We only create a symlink if the target bin exists.
+            if from.symlink_metadata().is_ok() {
+                std::fs::create_dir_all(target_bin)?;
+                if let Ok(meta) = to.symlink_metadata() {
+                    if meta.is_dir() {
+                        std::fs::remove_dir_all(&to)?;
+                    } else {
+                        std::fs::remove_file(&to)?;
+                    }
+                }
+                super::link_bin(&from, &to, &access, bin_symlinks)?;
+                tracing::trace!(
+                    "Linked bin for {} from {} to {}",
+                    name,
+                    from.display(),
+                    to.display()
+                );
+            }
+            Ok::<_, NodeMaintainerError>(())
+        })
+        .await?;
+        linked += 1;
     }
     Ok(linked)
 }
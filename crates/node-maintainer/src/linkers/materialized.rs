@@ -0,0 +1,204 @@
+//! `node_modules/.oro-state.kdl`: a compact record of what the hoisted
+//! linker last put in `node_modules/`, so the next apply can work out what
+//! changed without walking the whole directory and reading every
+//! `package.json` in it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use kdl::{KdlDocument, KdlNode};
+use oro_common::BuildManifest;
+use petgraph::stable_graph::NodeIndex;
+
+use crate::access::{AccessKind, AccessTracker};
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+
+pub(crate) const STATE_FILE_NAME: &str = ".oro-state.kdl";
+
+/// Bump this whenever the file's format changes. Files with any other
+/// version are ignored.
+const STATE_FILE_VERSION: i64 = 1;
+
+/// Every package that was extracted into `node_modules/`, keyed by its path
+/// relative to it (for example, `a/node_modules/@scope/b`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct MaterializedTree {
+    packages: HashMap<String, MaterializedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MaterializedPackage {
+    /// Identifies exactly what was extracted. See [`package_id`].
+    pub(crate) id: String,
+    /// Bins the package declares, relative to its own directory.
+    pub(crate) bins: Vec<(String, PathBuf)>,
+}
+
+impl MaterializedTree {
+    /// Reads the state file in `node_modules`. Returns `None` if there isn't
+    /// one, or if it can't be trusted.
+    pub(crate) fn load(node_modules: &Path) -> Option<Self> {
+        let state = std::fs::read_to_string(node_modules.join(STATE_FILE_NAME)).ok()?;
+        let doc: KdlDocument = state.parse().ok()?;
+        if doc.get_arg("state-version")?.as_i64()? != STATE_FILE_VERSION {
+            return None;
+        }
+        let mut packages = HashMap::new();
+        for node in doc.nodes().iter().filter(|n| n.name().value() == "pkg") {
+            let path = node.get(0)?.as_string()?;
+            let id = node.get(1)?.as_string()?;
+            let mut bins = Vec::new();
+            if let Some(children) = node.children() {
+                for bin in children.nodes() {
+                    bins.push((
+                        bin.get(0)?.as_string()?.to_string(),
+                        PathBuf::from(bin.get(1)?.as_string()?),
+                    ));
+                }
+            }
+            packages.insert(
+                path.to_string(),
+                MaterializedPackage {
+                    id: id.to_string(),
+                    bins,
+                },
+            );
+        }
+        Some(Self { packages })
+    }
+
+    /// Records what's now in `node_modules` for the next apply.
+    pub(crate) fn write(
+        &self,
+        node_modules: &Path,
+        access: &AccessTracker,
+    ) -> Result<(), NodeMaintainerError> {
+        let mut doc = KdlDocument::new();
+        doc.set_leading(
+            "// This file is automatically generated and not intended for manual editing.",
+        );
+        let mut version_node = KdlNode::new("state-version");
+        version_node.push(STATE_FILE_VERSION);
+        doc.nodes_mut().push(version_node);
+        let mut paths = self.packages.keys().collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let pkg = &self.packages[path];
+            let mut node = KdlNode::new("pkg");
+            node.push(path.clone());
+            node.push(pkg.id.clone());
+            for (name, bin) in &pkg.bins {
+                let mut bin_node = KdlNode::new("bin");
+                bin_node.push(name.clone());
+                bin_node.push(bin.to_string_lossy().replace('\\', "/"));
+                node.ensure_children().nodes_mut().push(bin_node);
+            }
+            doc.nodes_mut().push(node);
+        }
+        doc.fmt();
+        let path = node_modules.join(STATE_FILE_NAME);
+        access.check(AccessKind::Write, &path)?;
+        std::fs::write(path, doc.to_string())?;
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<&MaterializedPackage> {
+        self.packages.get(path)
+    }
+
+    pub(crate) fn insert(&mut self, path: String, pkg: MaterializedPackage) {
+        self.packages.insert(path, pkg);
+    }
+
+    /// Paths of all the recorded packages, parents before their children.
+    pub(crate) fn paths(&self) -> Vec<&str> {
+        let mut paths = self.packages.keys().map(|p| p.as_str()).collect::<Vec<_>>();
+        paths.sort_by_key(|path| (path.matches("/node_modules/").count(), *path));
+        paths
+    }
+
+    /// Whether the package recorded at `path` is exactly what `graph` wants
+    /// there.
+    pub(crate) fn is_fresh(&self, graph: &Graph, path: &str) -> bool {
+        match (self.get(path), graph.node_at_path(Path::new(path))) {
+            (Some(pkg), Some(node)) => pkg.id == package_id(graph, node.idx),
+            _ => false,
+        }
+    }
+}
+
+/// Identifies what gets extracted for the package at `idx`: where it came
+/// from, its integrity, and any patch applied to it. Local directories are
+/// checked for changes separately, so their path is enough.
+pub(crate) fn package_id(graph: &Graph, idx: NodeIndex) -> String {
+    let node = &graph[idx];
+    let resolved = node.package.resolved();
+    let mut id = resolved.to_string();
+    if let Some(integrity) = resolved.integrity() {
+        id.push(' ');
+        id.push_str(&integrity.to_string());
+    }
+    if let Some(patch) = &node.patch {
+        id.push_str(" patch:");
+        id.push_str(&patch.to_string());
+    }
+    id
+}
+
+/// Bins declared by the package installed in `package_dir`.
+pub(crate) fn read_bins(package_dir: &Path) -> Result<Vec<(String, PathBuf)>, NodeMaintainerError> {
+    let build_mani = BuildManifest::from_path(package_dir.join("package.json")).map_err(|e| {
+        NodeMaintainerError::BuildManifestReadError(package_dir.join("package.json"), e)
+    })?;
+    let mut bins = build_mani.bin.into_iter().collect::<Vec<_>>();
+    bins.sort();
+    Ok(bins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() -> Result<(), NodeMaintainerError> {
+        let dir = tempfile::tempdir()?;
+        let mut tree = MaterializedTree::default();
+        tree.insert(
+            "a/node_modules/@scope/b".into(),
+            MaterializedPackage {
+                id: "https://example.com/b-1.0.0.tgz sha512-deadbeef".into(),
+                bins: vec![("b".into(), PathBuf::from("bin/b.js"))],
+            },
+        );
+        tree.insert(
+            "a".into(),
+            MaterializedPackage {
+                id: "https://example.com/a-1.0.0.tgz".into(),
+                bins: Vec::new(),
+            },
+        );
+        tree.write(dir.path(), &AccessTracker::default())?;
+        let loaded = MaterializedTree::load(dir.path());
+        assert_eq!(loaded.as_ref(), Some(&tree));
+        assert_eq!(
+            loaded.unwrap().paths(),
+            vec!["a", "a/node_modules/@scope/b"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_other_versions() -> Result<(), NodeMaintainerError> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join(STATE_FILE_NAME),
+            format!(
+                "state-version {}\npkg \"a\" \"a\"\n",
+                STATE_FILE_VERSION + 1
+            ),
+        )?;
+        assert_eq!(MaterializedTree::load(dir.path()), None);
+        Ok(())
+    }
+}
@@ -1,35 +1,174 @@
 #[cfg(not(target_arch = "wasm32"))]
+mod bins;
+#[cfg(not(target_arch = "wasm32"))]
+mod dedupe;
+#[cfg(not(target_arch = "wasm32"))]
 mod hoisted;
 #[cfg(not(target_arch = "wasm32"))]
 mod isolated;
+#[cfg(not(target_arch = "wasm32"))]
+mod materialized;
+#[cfg(not(target_arch = "wasm32"))]
+mod pipeline;
+#[cfg(not(target_arch = "wasm32"))]
+mod pnp;
+#[cfg(not(target_arch = "wasm32"))]
+mod script_logs;
+#[cfg(not(target_arch = "wasm32"))]
+mod script_results;
+#[cfg(not(target_arch = "wasm32"))]
+mod script_scheduler;
+#[cfg(not(target_arch = "wasm32"))]
+mod side_effects;
+#[cfg(not(target_arch = "wasm32"))]
+mod store;
+mod vfs;
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{BufRead, BufReader};
 #[cfg(not(target_arch = "wasm32"))]
 use std::path::{Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
 
+#[cfg(not(target_arch = "wasm32"))]
+use futures::{StreamExt, TryStreamExt};
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::{package::Package, NassunError, PackageResolution};
+#[cfg(not(target_arch = "wasm32"))]
+use oro_common::BuildManifest;
+#[cfg(not(target_arch = "wasm32"))]
+use oro_script::{OroScript, OroScriptError, Sandbox};
+#[cfg(not(target_arch = "wasm32"))]
+use petgraph::stable_graph::NodeIndex;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use bins::{BinCandidate, BinCandidates};
 #[cfg(not(target_arch = "wasm32"))]
 use hoisted::HoistedLinker;
 #[cfg(not(target_arch = "wasm32"))]
 use isolated::IsolatedLinker;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use pipeline::{ExtractPipeline, STAGING_DIR_NAME};
+#[cfg(not(target_arch = "wasm32"))]
+use pnp::PnpLinker;
+#[cfg(not(target_arch = "wasm32"))]
+use script_logs::FailedScript;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use script_results::ScriptResults;
+#[cfg(not(target_arch = "wasm32"))]
+use script_scheduler::is_heavy;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use script_scheduler::ScriptScheduler;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use store::GlobalStore;
+pub(crate) use vfs::{package_dir, VfsLinker};
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::{
-    graph::Graph, Lockfile, NodeMaintainerError, ProgressHandler, PruneProgress, ScriptLineHandler,
-    ScriptStartHandler,
+    access::{AccessKind, AccessTracker},
+    dry_run::DryRun,
+    extract_progress::ExtractByteCounter,
+    graph::Graph,
+    hook_scripts::{glob_match, HookScriptFilter},
+    patches::Patches,
+    timings::TimingCounters,
+    trusted::ScriptTrust,
+    ForeignLayout, LinkStrategy, Lockfile, NodeMaintainerError, ProgressHandler, PruneProgress,
+    ScriptLineHandler, ScriptOutput, ScriptStartHandler, PNP_DATA_FILE_NAME, PNP_FILE_NAME,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) struct LinkerOptions {
     pub(crate) concurrency: usize,
     pub(crate) actual_tree: Option<Lockfile>,
-    pub(crate) script_concurrency: usize,
+    /// How many install scripts get to run at once. See
+    /// [`ScriptScheduler`].
+    pub(crate) script_scheduler: ScriptScheduler,
     pub(crate) cache: Option<PathBuf>,
-    pub(crate) prefer_copy: bool,
+    pub(crate) patches: Arc<Patches>,
+    /// Ways of getting files out of the cache, in order of preference.
+    pub(crate) link_strategies: Vec<LinkStrategy>,
     pub(crate) validate: bool,
+    /// Whether to check installed packages for damage while pruning. See
+    /// [`repair`].
+    pub(crate) repair: bool,
+    pub(crate) hook_scripts: HookScriptFilter,
+    pub(crate) access: Arc<AccessTracker>,
     pub(crate) root: PathBuf,
+    /// Packages staged while resolution was still running. See
+    /// [`ExtractPipeline`].
+    pub(crate) pipeline: Option<Arc<ExtractPipeline>>,
+    /// Where packages get hard linked from, if they're installed from the
+    /// [`GlobalStore`] instead of being extracted directly.
+    pub(crate) global_store: Option<GlobalStore>,
+    /// Whether to restore packages from snapshots taken after their install
+    /// scripts ran, instead of running them again. See
+    /// [`side_effects::SideEffectsCache`].
+    pub(crate) side_effects_cache: bool,
+    /// Whether two packages linking a bin with the same name into the same
+    /// place is an error, instead of a warning.
+    pub(crate) strict_bins: bool,
+    /// Whether bins should be real symlinks on Windows, where possible,
+    /// instead of `sh` shims. `.cmd` and `.ps1` shims get written either
+    /// way.
+    pub(crate) bin_symlinks: bool,
+    /// Where planned changes get recorded instead of made, during a dry
+    /// run.
+    pub(crate) dry_run: Option<Arc<DryRun>>,
+    /// Glob patterns for paths inside `node_modules/` that pruning should
+    /// leave alone, like tool caches. See [`is_kept`].
+    pub(crate) prune_keep: Vec<String>,
+    /// Glob patterns for package names the isolated linker also links into
+    /// the root `node_modules/`.
+    pub(crate) public_hoist_patterns: Vec<String>,
+    /// Where bins for the root's dependencies get linked instead of
+    /// `node_modules/.bin`, when installing globally.
+    pub(crate) global_bin_dir: Option<PathBuf>,
+    /// The package manager that installed `node_modules/`, if it wasn't
+    /// Orogene. See [`take_over`].
+    pub(crate) foreign_layout: Option<ForeignLayout>,
+    pub(crate) takeover: bool,
+    /// Which packages get to run their install scripts.
+    pub(crate) script_trust: Arc<ScriptTrust>,
+    /// Whether install scripts run in a [`Sandbox`], and which packages
+    /// are exempt from it.
+    pub(crate) sandbox_scripts: bool,
+    pub(crate) sandbox_exempt: Vec<String>,
+    pub(crate) script_timeouts: Arc<ScriptTimeouts>,
+    /// Configuration passed to install scripts as `npm_config_*`
+    /// variables.
+    pub(crate) script_config: Arc<Vec<(String, String)>>,
+    /// Shell to run install scripts in, instead of the platform's default.
+    pub(crate) script_shell: Option<PathBuf>,
+    /// Whether install scripts share the terminal, instead of having their
+    /// output captured. Scripts run one at a time when they do.
+    pub(crate) foreground_scripts: bool,
+    /// Which packages already ran their install scripts, so they don't
+    /// need to again.
+    pub(crate) script_results: ScriptResults,
+    /// How many install scripts have been started.
+    pub(crate) scripts_run: Arc<AtomicUsize>,
+    /// How long installing and building each package took.
+    pub(crate) timings: Arc<TimingCounters>,
+    /// Canonical paths to the project's workspace packages, which get
+    /// linked into `node_modules/` instead of copied. See
+    /// [`is_workspace_link`].
+    pub(crate) workspace_dirs: HashSet<PathBuf>,
     pub(crate) on_prune_progress: Option<PruneProgress>,
     pub(crate) on_extract_progress: Option<ProgressHandler>,
+    /// Running byte counts for the `on_extract_bytes` handler, if there is
+    /// one.
+    pub(crate) extract_bytes: Option<Arc<ExtractByteCounter>>,
     pub(crate) on_script_start: Option<ScriptStartHandler>,
     pub(crate) on_script_line: Option<ScriptLineHandler>,
+    pub(crate) script_output: Arc<ScriptOutput>,
 }
 
 pub(crate) enum Linker {
@@ -37,7 +176,10 @@ pub(crate) enum Linker {
     Isolated(IsolatedLinker),
     #[cfg(not(target_arch = "wasm32"))]
     Hoisted(HoistedLinker),
-    #[allow(dead_code)]
+    #[cfg(not(target_arch = "wasm32"))]
+    Pnp(PnpLinker),
+    /// Doesn't link anything. Used on wasm32, and for
+    /// [`crate::NodeMaintainerOptions::virtual_install`].
     Null,
 }
 
@@ -52,7 +194,11 @@ impl Linker {
         Self::Hoisted(HoistedLinker(opts))
     }
 
-    #[allow(dead_code)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pnp(opts: LinkerOptions) -> Self {
+        Self::Pnp(PnpLinker(opts))
+    }
+
     pub fn null() -> Self {
         Self::Null
     }
@@ -64,9 +210,26 @@ impl Linker {
     ) -> Result<usize, NodeMaintainerError> {
         match self {
             #[cfg(not(target_arch = "wasm32"))]
-            Self::Isolated(isolated) => isolated.prune(graph).await,
+            Self::Isolated(isolated) => {
+                take_over(&isolated.0).await?;
+                remove_pnp_files(&isolated.0)?;
+                let pruned = isolated.prune(graph).await?;
+                Ok(pruned
+                    + repair(&isolated.0, graph, |idx| isolated.package_dir(graph, idx)).await?)
+            }
             #[cfg(not(target_arch = "wasm32"))]
-            Self::Hoisted(hoisted) => hoisted.prune(graph).await,
+            Self::Hoisted(hoisted) => {
+                take_over(&hoisted.0).await?;
+                remove_pnp_files(&hoisted.0)?;
+                let pruned = hoisted.prune(graph).await?;
+                Ok(pruned
+                    + repair(&hoisted.0, graph, |idx| hoisted.package_dir(graph, idx)).await?)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Pnp(pnp) => {
+                take_over(&pnp.0).await?;
+                pnp.prune(graph).await
+            }
             Self::Null => Ok(0),
         }
     }
@@ -81,6 +244,21 @@ impl Linker {
             Self::Isolated(isolated) => isolated.extract(graph).await,
             #[cfg(not(target_arch = "wasm32"))]
             Self::Hoisted(hoisted) => hoisted.extract(graph).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Pnp(pnp) => pnp.extract(graph).await,
+            Self::Null => Ok(0),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn prefetch(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Isolated(isolated) => prefetch(&isolated.0, graph).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Hoisted(hoisted) => prefetch(&hoisted.0, graph).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Pnp(pnp) => prefetch(&pnp.0, graph).await,
             Self::Null => Ok(0),
         }
     }
@@ -96,66 +274,1027 @@ impl Linker {
             Self::Isolated(isolated) => isolated.rebuild(graph, ignore_scripts).await,
             #[cfg(not(target_arch = "wasm32"))]
             Self::Hoisted(hoisted) => hoisted.rebuild(graph, ignore_scripts).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Pnp(pnp) => pnp.rebuild(graph, ignore_scripts).await,
             Self::Null => Ok(()),
         }
     }
+
+    /// Where the package at `idx` is installed, if it gets a directory of
+    /// its own that its files can be read from.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn package_dir(&self, graph: &Graph, idx: NodeIndex) -> Option<PathBuf> {
+        match self {
+            Self::Isolated(isolated) => Some(isolated.package_dir(graph, idx)),
+            Self::Hoisted(hoisted) => Some(hoisted.package_dir(graph, idx)),
+            // Most packages stay zipped up in the cache.
+            Self::Pnp(_) => None,
+            Self::Null => None,
+        }
+    }
 }
 
+/// Checks every installed package's files against the integrity hashes the
+/// cache has for them, and removes the packages that were tampered with or
+/// damaged, so extracting installs them again from scratch. Local and
+/// patched packages aren't checked, since they're not supposed to match
+/// the cache. Returns how many packages were removed.
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) fn supports_reflink(src_dir: &Path, dest_dir: &Path) -> bool {
-    let temp = match tempfile::NamedTempFile::new_in(src_dir) {
-        Ok(t) => t,
-        Err(e) => {
-            tracing::debug!("error creating tempfile while checking for reflink support: {e}.");
-            return false;
+async fn repair(
+    opts: &LinkerOptions,
+    graph: &Graph,
+    package_dir: impl Fn(NodeIndex) -> PathBuf,
+) -> Result<usize, NodeMaintainerError> {
+    if !opts.repair {
+        return Ok(0);
+    }
+    let start = std::time::Instant::now();
+    let installed = graph
+        .inner
+        .node_indices()
+        .filter(|&idx| {
+            idx != graph.root
+                && graph[idx].patch.is_none()
+                && !matches!(
+                    graph[idx].package.resolved(),
+                    PackageResolution::Dir { .. } | PackageResolution::Link { .. }
+                )
+        })
+        .map(|idx| (idx, package_dir(idx)))
+        .filter(|(_, dir)| dir.exists())
+        .collect::<Vec<_>>();
+    let checked = installed.len();
+    let results = futures::stream::iter(installed)
+        .map(|(idx, dir)| async move {
+            let damaged = graph[idx].package.verify_dir(&dir).await;
+            (idx, dir, damaged)
+        })
+        .buffer_unordered(opts.concurrency)
+        .collect::<Vec<_>>()
+        .await;
+    let mut repaired = 0;
+    for (idx, dir, damaged) in results {
+        let name = graph[idx].package.name();
+        match damaged {
+            Ok(Some(damaged)) if !damaged.is_empty() => {
+                tracing::warn!(
+                    "{name} at {} has {} missing or modified file{}. Reinstalling it.",
+                    dir.display(),
+                    damaged.len(),
+                    if damaged.len() == 1 { "" } else { "s" }
+                );
+                for file in &damaged {
+                    tracing::debug!("Damaged file: {}", file.display());
+                }
+                if remove_entry(opts, &dir).await? {
+                    repaired += 1;
+                }
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                tracing::debug!("Nothing to check {name} against, since it isn't in the cache.");
+            }
+            Err(e) => tracing::debug!("Failed to check {name} for damage: {e}"),
+        }
+    }
+    tracing::debug!(
+        "Checked {checked} package{} for damage in {}ms.",
+        if checked == 1 { "" } else { "s" },
+        start.elapsed().as_millis()
+    );
+    Ok(repaired)
+}
+
+/// Downloads every package in `graph` into the cache, without linking
+/// anything. Local packages are skipped, since there's nothing to download.
+/// Returns how many packages weren't cached yet.
+#[cfg(not(target_arch = "wasm32"))]
+async fn prefetch(opts: &LinkerOptions, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+    if opts.cache.is_none() {
+        return Err(NodeMaintainerError::PrefetchWithoutCache);
+    }
+    let start = Instant::now();
+    let fetched = futures::stream::iter(graph.inner.node_indices().filter(|&idx| {
+        idx != graph.root
+            && !matches!(
+                graph[idx].package.resolved(),
+                PackageResolution::Dir { .. } | PackageResolution::Link { .. }
+            )
+    }))
+    .map(|idx| async move { graph[idx].package.prefetch().await })
+    .buffer_unordered(opts.concurrency)
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?
+    .into_iter()
+    .filter(|fetched| *fetched)
+    .count();
+    tracing::debug!(
+        "Prefetched {fetched} package{} in {}ms.",
+        if fetched == 1 { "" } else { "s" },
+        start.elapsed().as_millis()
+    );
+    Ok(fetched)
+}
+
+/// Whether the package at `idx` is one of the project's workspace packages,
+/// which get symlinked into place just like `link:` dependencies, so changes
+/// to them show up right away. Their dependencies still get installed, into
+/// the workspace package's own `node_modules/`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_workspace_link(opts: &LinkerOptions, graph: &Graph, idx: NodeIndex) -> bool {
+    matches!(
+        graph[idx].package.resolved(),
+        PackageResolution::Dir { path, .. } if opts.workspace_dirs.contains(path)
+    )
+}
+
+/// How a package's install scripts get run, as far as the linker options
+/// are concerned.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ScriptSetup {
+    sandbox: Option<Sandbox>,
+    timeout: Option<Duration>,
+    config: Arc<Vec<(String, String)>>,
+    shell: Option<PathBuf>,
+    foreground: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ScriptSetup {
+    /// Setup for the next script for the package `name`. Any total script
+    /// timeout starts counting down once this is called, and the script
+    /// gets counted as run.
+    pub(crate) fn new(opts: &LinkerOptions, name: &str) -> Self {
+        opts.scripts_run.fetch_add(1, Ordering::Relaxed);
+        Self {
+            sandbox: (opts.sandbox_scripts
+                && !opts.sandbox_exempt.iter().any(|exempt| exempt == name))
+            .then(Sandbox::new),
+            timeout: opts.script_timeouts.next(),
+            config: opts.script_config.clone(),
+            shell: opts.script_shell.clone(),
+            foreground: opts.foreground_scripts,
         }
+    }
+}
+
+/// Sets up the `event` script for the package in `package_dir`, as
+/// `setup` says to.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn lifecycle_script(
+    package_dir: PathBuf,
+    event: String,
+    setup: &ScriptSetup,
+) -> Result<OroScript<'static>, OroScriptError> {
+    let mut script = match &setup.sandbox {
+        Some(sandbox) => OroScript::sandboxed(package_dir, event, sandbox)?,
+        None => OroScript::new(package_dir, event)?,
     };
-    match std::fs::write(&temp, "a") {
-        Ok(_) => {}
-        Err(e) => {
-            tracing::debug!("error writing to tempfile while checking for reflink support: {e}.");
-            return false;
+    if let Some(timeout) = setup.timeout {
+        script = script.timeout(timeout);
+    }
+    if let Some(shell) = &setup.shell {
+        script = script.shell(shell);
+    }
+    if setup.foreground {
+        script = script.foreground();
+    }
+    for (key, value) in setup.config.iter() {
+        script = script.config(key, value);
+    }
+    Ok(script)
+}
+
+/// Where a package's lifecycle scripts run, as far as its linker is
+/// concerned.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ScriptDir {
+    /// The package's own directory.
+    pub(crate) package: PathBuf,
+    /// `node_modules/.bin` directories above this don't go on the `PATH`.
+    pub(crate) workspace: PathBuf,
+    /// Extra environment variables the linker needs scripts to see.
+    pub(crate) env: Vec<(&'static str, String)>,
+}
+
+/// Runs every package's `event` script, skipping anything in `done`.
+/// `script_dir` says where each package's scripts run, or `None` if the
+/// package didn't get installed anywhere.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn run_scripts(
+    opts: &LinkerOptions,
+    graph: &Graph,
+    done: &HashSet<NodeIndex>,
+    event: &str,
+    script_dir: impl Fn(NodeIndex) -> Option<ScriptDir> + Sync,
+) -> Result<(), NodeMaintainerError> {
+    tracing::debug!("Running {event} lifecycle scripts");
+    let start = std::time::Instant::now();
+    let script_dir = &script_dir;
+    // Scripts run one level at a time, so packages get built after
+    // their dependencies. Dependency cycles share a level, since
+    // there's no right order to build them in.
+    for level in graph.script_levels() {
+        futures::stream::iter(level)
+            .map(Ok)
+            .try_for_each_concurrent(opts.script_scheduler.width(), move |idx| async move {
+                // Linked packages are used in place, so building them is up
+                // to their owners.
+                if let PackageResolution::Link { .. } = graph[idx].package.resolved() {
+                    return Ok(());
+                }
+
+                // Packages restored from the side effects cache, or that
+                // already ran their scripts as they're installed now,
+                // have everything their install scripts would've done.
+                if done.contains(&idx) {
+                    return Ok(());
+                }
+
+                let Some(ScriptDir {
+                    package: package_dir,
+                    workspace,
+                    env,
+                }) = script_dir(idx)
+                else {
+                    return Ok(());
+                };
+
+                let is_optional = graph.is_optional(idx);
+
+                let build_mani = BuildManifest::from_path(package_dir.join("package.json"))
+                    .map_err(|e| {
+                        NodeMaintainerError::BuildManifestReadError(package_dir.join("package.json"), e)
+                    })?;
+
+                let name = graph[idx].package.name().to_string();
+                if let Some(command) = build_mani.scripts.get(event) {
+                    if !opts.script_trust.allows(graph, idx) {
+                        opts.script_trust.block(&name, event);
+                        return Ok(());
+                    }
+                    if opts.hook_scripts.should_skip(command) {
+                        tracing::info!("Skipping `{event}` script for {name} (`{command}`), since it looks like it sets up git hooks. Use `--hook-scripts run` to run it anyway.");
+                        return Ok(());
+                    }
+                    let heavy = is_heavy(command, &package_dir);
+                    let _permit = opts.script_scheduler.acquire(heavy).await;
+                    let script_dir = package_dir.clone();
+                    let event = event.to_owned();
+                    let event_clone = event.clone();
+                    // Progress bars would draw over foreground scripts'
+                    // output, or output that gets printed as it comes.
+                    let span = if opts.foreground_scripts || !opts.script_output.shows_progress() {
+                        tracing::Span::none()
+                    } else {
+                        tracing::info_span!("script")
+                    };
+                    let _span_enter = span.enter();
+                    if let Some(on_script_start) = &opts.on_script_start {
+                        on_script_start(&graph[idx].package, &event);
+                    }
+                    std::mem::drop(_span_enter);
+                    let setup = ScriptSetup::new(opts, &name);
+                    let _timer = opts.timings.script(&graph[idx].package);
+                    let mut script = match async_std::task::spawn_blocking(move || {
+                        let mut script = lifecycle_script(package_dir, event_clone, &setup)?
+                            .workspace_path(workspace);
+                        for (key, value) in env {
+                            script = script.env(key, value);
+                        }
+                        script.spawn()
+                    })
+                    .await
+                    {
+                        Ok(script) => script,
+                        Err(e) if is_optional => {
+                            let e: NodeMaintainerError = e.into();
+                            tracing::debug!("Error in optional dependency script: {}", e);
+                            return Ok(());
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let stdout = script.stdout.take();
+                    let stderr = script.stderr.take();
+                    let stdout_name = name.clone();
+                    let stderr_name = name.clone();
+                    let stdout_on_line = opts.on_script_line.clone();
+                    let stderr_on_line = opts.on_script_line.clone();
+                    let stdout_output = opts.script_output.clone();
+                    let stderr_output = opts.script_output.clone();
+                    let captured = Arc::new(Mutex::new(Vec::new()));
+                    let stdout_captured = captured.clone();
+                    let stderr_captured = captured.clone();
+                    let stdout_span = span;
+                    let stderr_span = stdout_span.clone();
+                    let event_clone = event.clone();
+                    let timeout_event = event.clone();
+                    let join = futures::try_join!(
+                        async_std::task::spawn_blocking(move || {
+                            let _enter = stdout_span.enter();
+                            if let Some(stdout) = stdout {
+                                for line in BufReader::new(stdout).lines() {
+                                    let line = line?;
+                                    tracing::debug!("stdout::{stdout_name}::{event}: {}", line);
+                                    if let Some(on_script_line) = &stdout_on_line {
+                                        on_script_line(&line);
+                                    }
+                                    stdout_output.line(&stdout_name, &event, &line);
+                                    stdout_captured.lock().unwrap().push(line);
+                                }
+                            }
+                            Ok::<_, NodeMaintainerError>(())
+                        }),
+                        async_std::task::spawn_blocking(move || {
+                            let _enter = stderr_span.enter();
+                            if let Some(stderr) = stderr {
+                                for line in BufReader::new(stderr).lines() {
+                                    let line = line?;
+                                    tracing::debug!("stderr::{stderr_name}::{event_clone}: {}", line);
+                                    if let Some(on_script_line) = &stderr_on_line {
+                                        on_script_line(&line);
+                                    }
+                                    stderr_output.line(&stderr_name, &event_clone, &line);
+                                    stderr_captured.lock().unwrap().push(line);
+                                }
+                            }
+                            Ok::<_, NodeMaintainerError>(())
+                        }),
+                        async_std::task::spawn_blocking(move || {
+                            script.wait()?;
+                            Ok::<_, NodeMaintainerError>(())
+                        }),
+                    );
+                    opts.script_output.finish(&name, &timeout_event);
+                    match join {
+                        Ok(_) => {}
+                        Err(NodeMaintainerError::OroScriptError(
+                            OroScriptError::ScriptTimeout(timeout),
+                        )) => {
+                            return script_timed_out(&name, &timeout_event, timeout, is_optional);
+                        }
+                        Err(e) if is_optional => {
+                            tracing::debug!("Error in optional dependency script: {}", e);
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            let failed = FailedScript {
+                                name: &name,
+                                event: &timeout_event,
+                                command,
+                                package_dir: &script_dir,
+                                output: &captured.lock().unwrap(),
+                            };
+                            return Err(failed.into_error(opts, e));
+                        }
+                    }
+                }
+
+                Ok::<_, NodeMaintainerError>(())
+            })
+            .await?;
+    }
+    tracing::debug!(
+        "Ran lifecycle scripts for {event} in {}ms.",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// Time limits for install scripts: one for each script on its own, and
+/// one for all of them together, counted from when the first one starts.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+pub(crate) struct ScriptTimeouts {
+    per_script: Option<Duration>,
+    total: Option<Duration>,
+    started: Mutex<Option<Instant>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ScriptTimeouts {
+    pub(crate) fn new(per_script: Option<Duration>, total: Option<Duration>) -> Self {
+        Self {
+            per_script,
+            total,
+            started: Mutex::new(None),
         }
+    }
+
+    /// How long the next script gets to run, if there's a limit.
+    pub(crate) fn next(&self) -> Option<Duration> {
+        let remaining = self.total.map(|total| {
+            let started = *self
+                .started
+                .lock()
+                .unwrap()
+                .get_or_insert_with(Instant::now);
+            total.saturating_sub(started.elapsed())
+        });
+        match (self.per_script, remaining) {
+            (Some(per_script), Some(remaining)) => Some(per_script.min(remaining)),
+            (per_script, remaining) => per_script.or(remaining),
+        }
+    }
+}
+
+/// Reports that `name`'s `event` script got killed for running past
+/// `timeout`. Optional packages just get skipped, like with any other
+/// script failure, but with a warning, since a hung script usually means
+/// something's wrong.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn script_timed_out(
+    name: &str,
+    event: &str,
+    timeout: Duration,
+    is_optional: bool,
+) -> Result<(), NodeMaintainerError> {
+    if is_optional {
+        tracing::warn!(
+            "Skipping optional dependency {name}: its `{event}` script timed out after {}s.",
+            timeout.as_secs()
+        );
+        Ok(())
+    } else {
+        Err(NodeMaintainerError::ScriptTimeout {
+            name: name.into(),
+            event: event.into(),
+            timeout,
+        })
+    }
+}
+
+/// Removes the bookkeeping another package manager left in `node_modules/`,
+/// if it installed it, so nothing it wrote gets mistaken for Orogene's.
+/// The rest of `node_modules/` is pruned away by the linker, since there's
+/// no record of what Orogene installed there. Unless taking over was asked
+/// for, this refuses to touch it at all.
+#[cfg(not(target_arch = "wasm32"))]
+async fn take_over(opts: &LinkerOptions) -> Result<(), NodeMaintainerError> {
+    let Some(foreign) = opts.foreign_layout else {
+        return Ok(());
     };
-    let tempdir = match tempfile::TempDir::new_in(dest_dir) {
+    if !opts.takeover {
+        return Err(NodeMaintainerError::ForeignLayout {
+            manager: foreign.as_str().into(),
+            path: opts.root.join("node_modules"),
+        });
+    }
+    for path in foreign.metadata_paths(&opts.root) {
+        if path.symlink_metadata().is_ok() {
+            remove_entry(opts, &path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the files a Plug'n'Play install leaves in the project root, so
+/// they can't keep overriding `node_modules/` after switching layouts.
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_pnp_files(opts: &LinkerOptions) -> Result<(), NodeMaintainerError> {
+    for name in [PNP_FILE_NAME, PNP_DATA_FILE_NAME] {
+        let path = opts.root.join(name);
+        if path.exists() {
+            if let Some(dry_run) = &opts.dry_run {
+                dry_run.remove(&path);
+                continue;
+            }
+            opts.access.check(AccessKind::Remove, &path)?;
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes whatever's at `path`, whether it's a file, a directory, or a
+/// link, unless it's one of the paths pruning should keep. During a dry run,
+/// this only records that it would've been removed. Returns whether it was
+/// (or would've been) removed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn remove_entry(
+    opts: &LinkerOptions,
+    path: &Path,
+) -> Result<bool, NodeMaintainerError> {
+    if is_kept(opts, path) {
+        tracing::trace!("Keeping {}, since it matches --prune-keep.", path.display());
+        return Ok(false);
+    }
+    if let Some(dry_run) = &opts.dry_run {
+        dry_run.remove(path);
+        return Ok(true);
+    }
+    opts.access.check(AccessKind::Remove, path)?;
+    let meta = async_std::fs::symlink_metadata(path).await?;
+    if meta.is_dir() {
+        async_std::fs::remove_dir_all(path).await?;
+    } else if async_std::fs::remove_file(path).await.is_err() {
+        // Junctions, and symlinks to directories on Windows, have to be
+        // removed as directories.
+        async_std::fs::remove_dir_all(path).await?;
+    }
+    Ok(true)
+}
+
+/// Whether `path`, or any directory it's in, matches one of the
+/// [`LinkerOptions::prune_keep`] patterns. Patterns are matched against the
+/// path relative to `node_modules/`, with `/` separators, and `*` matches
+/// any sequence of characters.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_kept(opts: &LinkerOptions, path: &Path) -> bool {
+    match path.strip_prefix(opts.root.join("node_modules")) {
+        Ok(relative) => matches_keep_pattern(&opts.prune_keep, relative),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn matches_keep_pattern(patterns: &[String], relative: &Path) -> bool {
+    let mut prefix = String::new();
+    relative.components().any(|component| {
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        prefix.push_str(&component.as_os_str().to_string_lossy());
+        patterns.iter().any(|pattern| glob_match(pattern, &prefix))
+    })
+}
+
+/// Empties a `.bin/` directory so bins can be linked into it again, except
+/// for anything pruning should keep.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn clear_bin_dir(
+    opts: &LinkerOptions,
+    bin_dir: &Path,
+) -> Result<(), NodeMaintainerError> {
+    for entry in std::fs::read_dir(bin_dir)? {
+        remove_entry(opts, &entry?.path()).await?;
+    }
+    Ok(())
+}
+
+/// Installs the package at `idx` into `target_dir`, from `store` if there
+/// is one, applying any patch. This all happens in a fresh directory inside
+/// `node_modules/.oro-staging/`, which only gets moved to `target_dir` once
+/// it's complete, so an interrupted apply never leaves a half-written
+/// package behind for the next one to mistake for a finished one.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn install_package(
+    opts: &LinkerOptions,
+    graph: &Graph,
+    idx: NodeIndex,
+    target_dir: &Path,
+    store: Option<&GlobalStore>,
+    prefer_copy: bool,
+) -> Result<(), NodeMaintainerError> {
+    let package = &graph[idx].package;
+    let _timer = opts.timings.extract(package);
+    opts.script_results.extracted(target_dir);
+    let staging = opts.root.join("node_modules").join(STAGING_DIR_NAME);
+    opts.access.check(AccessKind::Write, &staging)?;
+    std::fs::create_dir_all(&staging)?;
+    let temp = tempfile::tempdir_in(&staging)?;
+    let staged = temp.path().join("package");
+    match store {
+        // Stored packages are already patched.
+        Some(store) => {
+            store
+                .install(opts, graph, idx, &staged, prefer_copy)
+                .await?
+        }
+        None => {
+            let from_pipeline = opts
+                .pipeline
+                .as_ref()
+                .map_or(false, |pipeline| pipeline.take_into(package, &staged));
+            if !from_pipeline {
+                extract_package(
+                    package,
+                    &staged,
+                    prefer_copy,
+                    opts.validate,
+                    opts.extract_bytes.as_ref(),
+                )
+                .await?;
+            }
+            if let Some((_, patch)) = opts.patches.find(package) {
+                patch.apply(package.name(), &staged, &opts.access)?;
+            }
+        }
+    }
+    opts.access.check(AccessKind::Write, target_dir)?;
+    move_into_place(&staged, target_dir)?;
+    Ok(())
+}
+
+/// Whether a package has already been installed into `dir`. Packages nested
+/// inside it can be installed first, so `dir` existing isn't enough.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_installed(dir: &Path) -> bool {
+    dir.join("package.json").exists()
+}
+
+/// Moves a staged package to `target_dir`. If packages nested inside it got
+/// there first, `target_dir` already exists, and the staged files are moved
+/// in around them. Whatever is already there is left alone.
+#[cfg(not(target_arch = "wasm32"))]
+fn move_into_place(staged: &Path, target_dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(target_dir.parent().expect("must have a parent"))?;
+    match std::fs::rename(staged, target_dir) {
+        Ok(()) => Ok(()),
+        Err(_) if target_dir.is_dir() => merge_dir(staged, target_dir),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn merge_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        match std::fs::symlink_metadata(&dest) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                match std::fs::rename(entry.path(), &dest) {
+                    Ok(()) => {}
+                    // Something nested got there in the meantime.
+                    Err(_) if dest.is_dir() && entry.file_type()?.is_dir() => {
+                        merge_dir(&entry.path(), &dest)?
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+            Ok(meta) if meta.is_dir() && entry.file_type()?.is_dir() => {
+                merge_dir(&entry.path(), &dest)?
+            }
+            Ok(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Extracts `package` into `dir`, reporting progress to `extract_bytes`, if
+/// there's anything listening for it.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn extract_package(
+    package: &Package,
+    dir: &Path,
+    prefer_copy: bool,
+    validate: bool,
+    extract_bytes: Option<&Arc<ExtractByteCounter>>,
+) -> Result<ssri::Integrity, NassunError> {
+    match extract_bytes {
+        Some(counter) => {
+            package
+                .extract_to_dir_with_progress(
+                    dir,
+                    prefer_copy,
+                    validate,
+                    counter.for_package(package),
+                )
+                .await
+        }
+        None => package.extract_to_dir(dir, prefer_copy, validate).await,
+    }
+}
+
+/// Removes `node_modules/.oro-staging/`, along with anything an
+/// interrupted apply left in it.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn cleanup_staging(opts: &LinkerOptions) -> Result<(), NodeMaintainerError> {
+    let staging = opts.root.join("node_modules").join(STAGING_DIR_NAME);
+    if async_std::path::Path::new(&staging).exists().await {
+        opts.access.check(AccessKind::Remove, &staging)?;
+        async_std::fs::remove_dir_all(&staging).await?;
+    }
+    Ok(())
+}
+
+/// Records every package a dry run would extract, without extracting
+/// anything. `target_dir` says where each package would go, or `None` if it
+/// wouldn't be extracted at all. Returns how many packages were recorded.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn plan_extract(
+    dry_run: &DryRun,
+    graph: &Graph,
+    target_dir: impl Fn(NodeIndex) -> Option<PathBuf>,
+) -> Result<usize, NodeMaintainerError> {
+    let mut planned = 0;
+    for idx in graph.inner.node_indices() {
+        if idx == graph.root {
+            continue;
+        }
+        let Some(target_dir) = target_dir(idx) else {
+            continue;
+        };
+        let package = &graph[idx].package;
+        let needed = match package.resolved() {
+            // Linked packages are only ever linked, never extracted.
+            PackageResolution::Link { .. } => false,
+            PackageResolution::Dir { path, .. } => {
+                !target_dir.exists()
+                    || recorded_fingerprint(&target_dir) != Some(local_fingerprint(path)?)
+            }
+            _ => !target_dir.exists(),
+        };
+        if needed {
+            dry_run.extract(package, &target_dir);
+            planned += 1;
+        }
+    }
+    Ok(planned)
+}
+
+/// Bins declared by the package at `idx`, for a dry run. The package might
+/// not be installed yet, in which case they come from its metadata instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn planned_bins(
+    graph: &Graph,
+    idx: NodeIndex,
+    package_dir: &Path,
+) -> Result<Vec<(String, PathBuf)>, NodeMaintainerError> {
+    if package_dir.join("package.json").exists() {
+        return materialized::read_bins(package_dir);
+    }
+    let mut manifest = graph[idx].package.metadata().await?.manifest;
+    // `directories.bin` can only be expanded once the package is on disk.
+    manifest.directories = None;
+    let build_mani = BuildManifest::from_manifest(&manifest).map_err(|e| {
+        NodeMaintainerError::BuildManifestReadError(package_dir.join("package.json"), e)
+    })?;
+    let mut bins = build_mani.bin.into_iter().collect::<Vec<_>>();
+    bins.sort();
+    Ok(bins)
+}
+
+/// Whether files should be copied out of the cache into `dest`, rather
+/// than hard linked, going by the first of `strategies` that works there.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn prefers_copy(strategies: &[LinkStrategy], cache: Option<&Path>, dest: &Path) -> bool {
+    match cache {
+        Some(cache) => LinkStrategy::pick(strategies, cache, dest).copies(),
+        // Without a cache, there's nothing to link to anyway.
+        None => true,
+    }
+}
+
+/// Checks whether directory symlinks can be created inside `dir`. Creating
+/// symlinks on Windows requires either administrator privileges or Developer
+/// Mode, so regular users often can't. When they're unavailable, a warning is
+/// printed (once per process) and junctions should be used instead.
+///
+/// The answer is remembered for each `dir`, so installing into the same
+/// `node_modules/` again, like with the daemon, doesn't check again.
+#[cfg(windows)]
+pub(crate) fn supports_symlinks(dir: &Path) -> bool {
+    static SUPPORTED: once_cell::sync::Lazy<
+        std::sync::Mutex<std::collections::HashMap<PathBuf, bool>>,
+    > = once_cell::sync::Lazy::new(Default::default);
+    let mut supported = SUPPORTED.lock().unwrap();
+    *supported
+        .entry(dir.to_path_buf())
+        .or_insert_with(|| probe_symlinks(dir))
+}
+
+#[cfg(windows)]
+fn probe_symlinks(dir: &Path) -> bool {
+    static WARN_ONCE: std::sync::Once = std::sync::Once::new();
+    let tempdir = match tempfile::TempDir::new_in(dir) {
         Ok(t) => t,
         Err(e) => {
-            tracing::debug!(
-                "error creating destination tempdir while checking for reflink support: {e}."
-            );
+            tracing::debug!("error creating tempdir while checking for symlink support: {e}.");
             return false;
         }
     };
-    let supports_reflink = reflink::reflink(temp.path(), tempdir.path().join("b"))
-        .map(|_| true)
-        .map_err(|e| {
-            tracing::debug!(
-                "reflink support check failed. Files will be hard linked or copied. ({e})"
-            );
-            e
-        })
-        .unwrap_or(false);
+    let target = tempdir.path().join("target");
+    if let Err(e) = std::fs::create_dir(&target) {
+        tracing::debug!("error creating symlink target while checking for symlink support: {e}.");
+        return false;
+    }
+    match std::os::windows::fs::symlink_dir(&target, tempdir.path().join("link")) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!("symlink support check failed: {e}");
+            WARN_ONCE.call_once(|| {
+                tracing::warn!("Symlinks are unavailable, so dependencies will be linked using junctions instead. Enable Developer Mode or run as an administrator to use symlinks.");
+            });
+            false
+        }
+    }
+}
+
+/// File recording what a `file:` dependency's source directory looked like
+/// when it was last copied into `node_modules/`.
+#[cfg(not(target_arch = "wasm32"))]
+const LOCAL_FINGERPRINT_FILE_NAME: &str = ".orogene-fingerprint";
+
+/// Removes the installed copy of a `file:` dependency at `target` if its
+/// source directory has changed since it was copied, so it'll be extracted
+/// again. Returns the source's current fingerprint, which should be passed
+/// to [`record_local_fingerprint`] once extraction is done.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn refresh_local_copy(
+    source: &Path,
+    target: &Path,
+    access: &AccessTracker,
+) -> Result<String, NodeMaintainerError> {
+    let fingerprint = local_fingerprint(source)?;
+    if target.exists() && recorded_fingerprint(target).as_deref() != Some(fingerprint.as_str()) {
+        tracing::debug!(
+            "{} has changed since it was installed. Copying it again.",
+            source.display()
+        );
+        access.check(AccessKind::Remove, target)?;
+        std::fs::remove_dir_all(target)?;
+    }
+    Ok(fingerprint)
+}
+
+/// The fingerprint recorded when the local package at `target` was copied
+/// there, if any.
+#[cfg(not(target_arch = "wasm32"))]
+fn recorded_fingerprint(target: &Path) -> Option<String> {
+    std::fs::read_to_string(target.join(LOCAL_FINGERPRINT_FILE_NAME)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn record_local_fingerprint(
+    target: &Path,
+    fingerprint: &str,
+    access: &AccessTracker,
+) -> Result<(), NodeMaintainerError> {
+    let path = target.join(LOCAL_FINGERPRINT_FILE_NAME);
+    access.check(AccessKind::Write, &path)?;
+    std::fs::write(path, fingerprint)?;
+    Ok(())
+}
 
-    if supports_reflink {
-        tracing::debug!("Verified reflink support. Extracted data will use copy-on-write reflinks instead of hard links or full copies.")
+/// Summarizes the file names, sizes, and modification times in `dir`, which
+/// is enough to notice local edits without reading every file.
+#[cfg(not(target_arch = "wasm32"))]
+fn local_fingerprint(dir: &Path) -> Result<String, NodeMaintainerError> {
+    let mut summary = String::new();
+    for entry in walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != "node_modules" && e.file_name() != ".git")
+    {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|time| time.as_nanos())
+            .unwrap_or_default();
+        let path = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        summary.push_str(&format!(
+            "{}\t{}\t{modified}\n",
+            path.to_string_lossy(),
+            meta.len()
+        ));
     }
+    Ok(ssri::Integrity::from(summary).to_string())
+}
 
-    supports_reflink
+/// Links a `link:` dependency's directory into `to`, replacing whatever was
+/// there before if it doesn't already point at `from`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn link_local_dir(
+    from: &Path,
+    to: &Path,
+    access: &AccessTracker,
+) -> Result<(), NodeMaintainerError> {
+    let parent = to.parent().expect("must have a parent");
+    let relative = pathdiff::diff_paths(from, parent).expect("this should never fail");
+    if let Ok(meta) = to.symlink_metadata() {
+        if let Ok(target) = to.read_link() {
+            if target == relative || target == from {
+                return Ok(());
+            }
+        }
+        access.check(AccessKind::Remove, to)?;
+        if meta.is_dir() {
+            std::fs::remove_dir_all(to)?;
+        } else if std::fs::remove_file(to).is_err() {
+            // Directory symlinks and junctions on Windows.
+            std::fs::remove_dir(to)?;
+        }
+    }
+    access.check(AccessKind::Link, to)?;
+    std::fs::create_dir_all(parent)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(&relative, to).or_else(|_| junction::create(from, to))?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&relative, to)?;
+    Ok(())
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) fn link_bin(from: &Path, to: &Path) -> Result<(), NodeMaintainerError> {
+pub(crate) fn link_bin(
+    from: &Path,
+    to: &Path,
+    access: &AccessTracker,
+    #[cfg_attr(not(windows), allow(unused_variables))] symlink: bool,
+) -> Result<(), NodeMaintainerError> {
+    access.check(AccessKind::Link, to)?;
     #[cfg(windows)]
-    oro_shim_bin::shim_bin(from, to)?;
+    {
+        // Real symlinks only work with Developer Mode on (or as an admin),
+        // so shims are the fallback.
+        let symlinked = symlink
+            && match oro_shim_bin::symlink_bin(from, to) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::debug!(
+                        "Failed to symlink bin at {}, so it'll be shimmed instead: {e}",
+                        to.display()
+                    );
+                    false
+                }
+            };
+        if !symlinked {
+            oro_shim_bin::shim_bin(from, to)?;
+        }
+    }
     #[cfg(not(windows))]
     {
         use std::os::unix::fs::PermissionsExt;
         let meta = from.metadata()?;
         let mut perms = meta.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(from, perms)?;
+        if access.is_restricted() {
+            // Only add the executable bits, and only if they're missing.
+            if perms.mode() & 0o111 != 0o111 {
+                access.check(AccessKind::Chmod, from)?;
+                perms.set_mode(perms.mode() | 0o111);
+                std::fs::set_permissions(from, perms)?;
+            }
+        } else {
+            perms.set_mode(0o755);
+            std::fs::set_permissions(from, perms)?;
+        }
         let relative = pathdiff::diff_paths(from, to.parent().unwrap()).unwrap();
         std::os::unix::fs::symlink(relative, to)?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_into_place_around_nested_packages() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let staged = dir.path().join("staged");
+        std::fs::create_dir_all(staged.join("node_modules/bundled"))?;
+        std::fs::create_dir_all(staged.join("node_modules/b"))?;
+        std::fs::write(staged.join("package.json"), "{}")?;
+        std::fs::write(staged.join("node_modules/bundled/package.json"), "{}")?;
+        std::fs::write(staged.join("node_modules/b/index.js"), "bundled")?;
+
+        // `a/node_modules/b` was installed before `a` itself.
+        let target = dir.path().join("node_modules/a");
+        std::fs::create_dir_all(target.join("node_modules/b"))?;
+        std::fs::write(target.join("node_modules/b/package.json"), "{}")?;
+        std::fs::write(target.join("node_modules/b/index.js"), "nested")?;
+        assert!(!is_installed(&target));
+
+        move_into_place(&staged, &target)?;
+        assert!(is_installed(&target));
+        assert!(is_installed(&target.join("node_modules/b")));
+        assert!(is_installed(&target.join("node_modules/bundled")));
+        assert_eq!(
+            std::fs::read_to_string(target.join("node_modules/b/index.js"))?,
+            "nested"
+        );
+
+        // Without anything nested, the staged package is just moved over.
+        let staged = dir.path().join("staged-c");
+        std::fs::create_dir_all(&staged)?;
+        std::fs::write(staged.join("package.json"), "{}")?;
+        let target = dir.path().join("node_modules/c");
+        move_into_place(&staged, &target)?;
+        assert!(is_installed(&target));
+        assert!(!staged.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn keep_patterns() {
+        let patterns = vec![".cache".to_string(), ".bin/my-*".to_string()];
+        assert!(matches_keep_pattern(&patterns, Path::new(".cache")));
+        assert!(matches_keep_pattern(
+            &patterns,
+            Path::new(".cache/babel-loader/abc.json")
+        ));
+        assert!(matches_keep_pattern(&patterns, Path::new(".bin/my-tool")));
+        assert!(!matches_keep_pattern(&patterns, Path::new(".bin/tsc")));
+        assert!(!matches_keep_pattern(&patterns, Path::new("react")));
+        assert!(!matches_keep_pattern(&patterns, Path::new("foo/.cache")));
+    }
+}
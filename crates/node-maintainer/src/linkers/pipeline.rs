@@ -0,0 +1,231 @@
+//! Pipelined extraction: registry packages are unpacked into
+//! `node_modules/.oro-staging/` as soon as the resolver places them, while
+//! the rest of the tree is still resolving. Once resolution is done, the
+//! linkers move the staged directories into their final locations instead
+//! of extracting them again, so network metadata latency overlaps with
+//! tarball downloads and extraction.
+//!
+//! Staging is keyed by integrity rather than by location, since packages
+//! can still move around the tree (or get deduplicated away) until
+//! resolution finishes.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_std::task::JoinHandle;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use nassun::{package::Package, PackageResolution};
+
+use crate::access::{AccessKind, AccessTracker};
+use crate::error::NodeMaintainerError;
+use crate::extract_progress::ExtractByteCounter;
+use crate::LinkStrategy;
+
+pub(crate) const STAGING_DIR_NAME: &str = ".oro-staging";
+
+pub(crate) struct ExtractPipeline {
+    dir: PathBuf,
+    access: Arc<AccessTracker>,
+    sender: Mutex<Option<mpsc::Sender<Package>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    offered: Mutex<HashSet<String>>,
+    staged: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+impl ExtractPipeline {
+    /// Starts staging packages into `node_modules/.oro-staging/`.
+    ///
+    /// `depth` is how many placed packages may wait to be staged at once.
+    /// When the queue is full, the resolver doesn't wait for it: packages
+    /// that don't fit are just extracted later, as usual. Up to
+    /// `concurrency` packages are staged at the same time. Returns `None`
+    /// if either is zero, which disables pipelining.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn start(
+        root: &Path,
+        depth: usize,
+        concurrency: usize,
+        cache: Option<&Path>,
+        link_strategies: &[LinkStrategy],
+        validate: bool,
+        access: Arc<AccessTracker>,
+        extract_bytes: Option<Arc<ExtractByteCounter>>,
+    ) -> Option<Arc<Self>> {
+        if depth == 0 || concurrency == 0 {
+            return None;
+        }
+        let node_modules = root.join("node_modules");
+        let dir = node_modules.join(STAGING_DIR_NAME);
+        if access.check(AccessKind::Write, &dir).is_err() {
+            return None;
+        }
+        // Anything left over from an interrupted apply can't be trusted.
+        if dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dir) {
+                tracing::debug!("Failed to clear {}: {e}", dir.display());
+                return None;
+            }
+        }
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::debug!("Failed to create {}: {e}", dir.display());
+            return None;
+        }
+        let prefer_copy = super::prefers_copy(link_strategies, cache, &node_modules);
+        let (sender, receiver) = mpsc::channel::<Package>(depth);
+        let staged = Arc::new(Mutex::new(HashMap::new()));
+        let task = {
+            let dir = dir.clone();
+            let staged = staged.clone();
+            async_std::task::spawn(async move {
+                receiver
+                    .for_each_concurrent(concurrency, |pkg| {
+                        let dir = dir.clone();
+                        let staged = staged.clone();
+                        let extract_bytes = extract_bytes.clone();
+                        async move {
+                            let Some(key) = staging_key(&pkg) else {
+                                return;
+                            };
+                            let target = dir.join(staging_dir_name(&pkg));
+                            match super::extract_package(
+                                &pkg,
+                                &target,
+                                prefer_copy,
+                                validate,
+                                extract_bytes.as_ref(),
+                            )
+                            .await
+                            {
+                                Ok(_) => {
+                                    tracing::trace!("Staged {} while resolving.", pkg.resolved());
+                                    staged.lock().unwrap().insert(key, target);
+                                }
+                                Err(e) => {
+                                    // Whatever went wrong will come up again
+                                    // (and get reported) when it's extracted
+                                    // for real.
+                                    tracing::debug!("Failed to stage {}: {e}", pkg.resolved());
+                                    let _ = std::fs::remove_dir_all(&target);
+                                }
+                            }
+                        }
+                    })
+                    .await;
+            })
+        };
+        Some(Arc::new(Self {
+            dir,
+            access,
+            sender: Mutex::new(Some(sender)),
+            task: Mutex::new(Some(task)),
+            offered: Mutex::new(HashSet::new()),
+            staged,
+        }))
+    }
+
+    /// Queues a freshly-placed package for staging, if it can be staged and
+    /// there's room for it.
+    pub(crate) fn offer(&self, pkg: &Package) {
+        let Some(key) = staging_key(pkg) else {
+            return;
+        };
+        if !self.offered.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+        let mut sender = self.sender.lock().unwrap();
+        let Some(sender) = sender.as_mut() else {
+            return;
+        };
+        if let Err(e) = sender.try_send(pkg.clone()) {
+            if e.is_full() {
+                tracing::trace!(
+                    "Extraction pipeline is full. {} will be extracted after resolution.",
+                    pkg.resolved()
+                );
+            }
+            // It might fit next time it gets placed.
+            self.offered.lock().unwrap().remove(&key);
+        }
+    }
+
+    /// Stops accepting packages and waits for everything already queued to
+    /// finish staging.
+    pub(crate) async fn finish(&self) {
+        self.sender.lock().unwrap().take();
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.await;
+        }
+    }
+
+    /// Abandons any staging still in flight and removes the staging
+    /// directory. Used when resolution fails.
+    pub(crate) async fn cancel(&self) {
+        self.sender.lock().unwrap().take();
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.cancel().await;
+        }
+        let _ = self.cleanup().await;
+    }
+
+    /// Moves the staged copy of `pkg` to `target`, returning `false` if
+    /// there isn't one and it still needs to be extracted.
+    pub(crate) fn take_into(&self, pkg: &Package, target: &Path) -> bool {
+        let Some(key) = staging_key(pkg) else {
+            return false;
+        };
+        let Some(staged) = self.staged.lock().unwrap().remove(&key) else {
+            return false;
+        };
+        if let Some(parent) = target.parent() {
+            if std::fs::create_dir_all(parent).is_err() {
+                return false;
+            }
+        }
+        match std::fs::rename(&staged, target) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::debug!(
+                    "Failed to move staged {} to {}: {e}",
+                    pkg.resolved(),
+                    target.display()
+                );
+                false
+            }
+        }
+    }
+
+    /// Removes the staging directory, along with any packages that were
+    /// staged but ended up not being needed.
+    pub(crate) async fn cleanup(&self) -> Result<(), NodeMaintainerError> {
+        if async_std::path::Path::new(&self.dir).exists().await {
+            self.access.check(AccessKind::Remove, &self.dir)?;
+            async_std::fs::remove_dir_all(&self.dir).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Only registry packages with a known integrity are staged. Their contents
+/// are fully determined by it, no matter where they end up in the tree.
+fn staging_key(pkg: &Package) -> Option<String> {
+    match pkg.resolved() {
+        PackageResolution::Npm {
+            integrity: Some(integrity),
+            ..
+        } => Some(integrity.to_string()),
+        _ => None,
+    }
+}
+
+fn staging_dir_name(pkg: &Package) -> String {
+    let (_, hex) = pkg
+        .resolved()
+        .integrity()
+        .expect("only packages with an integrity are staged")
+        .to_hex();
+    hex
+}
@@ -0,0 +1,214 @@
+#!/usr/bin/env node
+/* eslint-disable */
+// This file is automatically generated by Orogene and not intended for manual
+// editing. It tells Node.js where every package's dependencies live, using
+// the resolution map in `.pnp.data.json`. Load it with
+// `node --require ./.pnp.cjs`, or through `NODE_OPTIONS`.
+"use strict";
+
+const fs = require("fs");
+const path = require("path");
+const Module = require("module");
+
+const DATA_FILE = path.join(__dirname, ".pnp.data.json");
+const TOP_LEVEL = Object.freeze({ name: null, reference: null });
+
+function withTrailingSep(location) {
+  return location.endsWith(path.sep) ? location : location + path.sep;
+}
+
+function load() {
+  const data = JSON.parse(fs.readFileSync(DATA_FILE, "utf8"));
+  const registry = new Map();
+  const lookup = [];
+  for (const [name, references] of data.packageRegistryData) {
+    const infos = new Map();
+    for (const [reference, info] of references) {
+      const entry = {
+        packageLocation: withTrailingSep(
+          path.resolve(__dirname, info.packageLocation)
+        ),
+        packageDependencies: new Map(info.packageDependencies),
+        packagePeers: new Set(info.packagePeers || []),
+        linkType: info.linkType,
+        discardFromLookup: !!info.discardFromLookup,
+      };
+      infos.set(reference, entry);
+      if (!entry.discardFromLookup) {
+        lookup.push([entry.packageLocation, { name, reference }]);
+      }
+    }
+    registry.set(name, infos);
+  }
+  // Longest locations first, so nested packages win over their parents.
+  lookup.sort((a, b) => b[0].length - a[0].length);
+  return { data, registry, lookup };
+}
+
+const state = load();
+
+function getPackageInformation(locator) {
+  const infos = state.registry.get(locator.name);
+  return (infos && infos.get(locator.reference)) || null;
+}
+
+function findPackageLocator(location) {
+  const absolute = path.resolve(location);
+  for (const [packageLocation, locator] of state.lookup) {
+    if (withTrailingSep(absolute).startsWith(packageLocation)) {
+      return locator;
+    }
+  }
+  return null;
+}
+
+function isBuiltin(request) {
+  return (
+    request.startsWith("node:") || Module.builtinModules.includes(request)
+  );
+}
+
+function isBareSpecifier(request) {
+  return !(
+    request.startsWith(".") ||
+    request.startsWith("/") ||
+    path.isAbsolute(request) ||
+    isBuiltin(request)
+  );
+}
+
+function splitRequest(request) {
+  const parts = request.split("/");
+  const nameLength = request.startsWith("@") ? 2 : 1;
+  return {
+    name: parts.slice(0, nameLength).join("/"),
+    subpath: parts.slice(nameLength).join("/"),
+  };
+}
+
+function notFound(message) {
+  const error = new Error(message);
+  error.code = "MODULE_NOT_FOUND";
+  return error;
+}
+
+function describe(locator) {
+  return locator.name === null ? "Your project" : locator.name;
+}
+
+// Figures out which package `request` refers to, from the point of view of
+// `issuer`. Returns `null` when `issuer` isn't part of the project, so Node.js
+// can take over.
+function resolveDependency(request, issuer) {
+  const { name, subpath } = splitRequest(request);
+  const issuerLocator = findPackageLocator(issuer);
+  if (!issuerLocator) {
+    return null;
+  }
+  const issuerInfo = getPackageInformation(issuerLocator);
+  let reference = issuerInfo.packageDependencies.get(name);
+  if (reference === undefined && state.data.enableTopLevelFallback) {
+    reference = getPackageInformation(TOP_LEVEL).packageDependencies.get(name);
+  }
+  if (reference === undefined) {
+    throw notFound(
+      `${describe(issuerLocator)} tried to access ${name}, but it isn't declared in its dependencies.\n\nRequired package: ${name} (via "${request}")\nRequired by: ${issuer}`
+    );
+  }
+  if (reference === null) {
+    throw notFound(
+      `${describe(issuerLocator)} tried to access ${name}, a peer dependency that isn't installed.\n\nRequired package: ${name} (via "${request}")\nRequired by: ${issuer}`
+    );
+  }
+  const locator = Array.isArray(reference)
+    ? { name: reference[0], reference: reference[1] }
+    : { name, reference };
+  const info = getPackageInformation(locator);
+  if (!info) {
+    throw notFound(`${name}@${locator.reference} is missing from ${DATA_FILE}.`);
+  }
+  return { name: locator.name, subpath, info };
+}
+
+function resolveToUnqualified(request, issuer) {
+  if (request === "pnpapi") {
+    return __filename;
+  }
+  if (!isBareSpecifier(request)) {
+    return path.resolve(path.dirname(issuer), request);
+  }
+  const resolved = resolveDependency(request, issuer);
+  if (!resolved) {
+    return null;
+  }
+  return path.join(resolved.info.packageLocation, resolved.subpath);
+}
+
+function resolveRequest(request, issuer) {
+  return Module._resolveFilename(request, {
+    id: issuer,
+    filename: issuer,
+    paths: Module._nodeModulePaths(path.dirname(issuer)),
+  });
+}
+
+const originalResolveFilename = Module._resolveFilename;
+
+function resolveFilename(request, parent, isMain, options) {
+  if (request === "pnpapi") {
+    return __filename;
+  }
+  if (!isBareSpecifier(request)) {
+    return originalResolveFilename.call(this, request, parent, isMain, options);
+  }
+  const issuer =
+    parent && parent.filename
+      ? parent.filename
+      : withTrailingSep(process.cwd());
+  const resolved = resolveDependency(request, issuer);
+  if (!resolved) {
+    return originalResolveFilename.call(this, request, parent, isMain, options);
+  }
+  const { name, subpath, info } = resolved;
+  const suffix = path.join("node_modules", ...name.split("/")) + path.sep;
+  if (info.packageLocation.endsWith(path.sep + suffix)) {
+    // Packages that live in a `node_modules/` directory of their own can be
+    // handed to Node.js as-is, so things like `exports` keep working.
+    const nodeModules = info.packageLocation.slice(
+      0,
+      -(suffix.length - "node_modules".length - 1)
+    );
+    const bareRequest = subpath ? `${name}/${subpath}` : name;
+    return originalResolveFilename.call(this, bareRequest, parent, isMain, {
+      ...options,
+      paths: [nodeModules],
+    });
+  }
+  return originalResolveFilename.call(
+    this,
+    path.join(info.packageLocation, subpath),
+    parent,
+    isMain,
+    options
+  );
+}
+
+function setup() {
+  if (Module._resolveFilename !== resolveFilename) {
+    Module._resolveFilename = resolveFilename;
+  }
+  process.versions.pnp = "3";
+}
+
+module.exports = {
+  VERSIONS: { std: 3, resolveVirtual: 0 },
+  topLevel: TOP_LEVEL,
+  getDependencyTreeRoots: () => state.data.dependencyTreeRoots,
+  getPackageInformation,
+  findPackageLocator,
+  resolveToUnqualified,
+  resolveRequest,
+  setup,
+};
+
+setup();
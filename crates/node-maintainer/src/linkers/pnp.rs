@@ -0,0 +1,829 @@
+//! Plug'n'Play installs: instead of materializing `node_modules/`, packages
+//! are used straight from the [`GlobalStore`], and a
+//! `.pnp.cjs` runtime tells Node.js where each package's dependencies live,
+//! using the resolution map in `.pnp.data.json`. The map uses the same
+//! format as Yarn's, so tools that understand Yarn PnP can read it too.
+//!
+//! Packages that can't be used straight from the store are "unplugged" into
+//! `node_modules/.oro-unplugged/` instead. That's the case for packages with
+//! install scripts (which would otherwise modify the shared store), for
+//! packages that show up more than once in the tree (since each copy can see
+//! different dependencies), and for everything when there's no cache to use
+//! as a store.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::{atomic, Mutex};
+
+use futures::{StreamExt, TryStreamExt};
+use nassun::PackageResolution;
+use petgraph::stable_graph::NodeIndex;
+use serde_json::{json, Value};
+
+use crate::access::AccessKind;
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+use crate::{META_FILE_NAME, PNP_DATA_FILE_NAME, PNP_FILE_NAME};
+
+use super::bins::{BinCandidate, BinCandidates};
+use super::materialized;
+use super::side_effects::SideEffectsCache;
+use super::store::{self, GlobalStore};
+use super::{LinkerOptions, ScriptResults, STAGING_DIR_NAME};
+
+/// Directory inside `node_modules/` for packages that can't be used
+/// straight from the store.
+const UNPLUGGED_DIR_NAME: &str = ".oro-unplugged";
+
+/// The `.pnp.cjs` runtime. It doesn't depend on the project, so it's always
+/// the same.
+const PNP_RUNTIME: &str = include_str!("pnp.cjs");
+
+pub(crate) struct PnpLinker(pub(crate) LinkerOptions);
+
+/// Where a package ended up, and how the resolution map refers to it.
+struct Placement {
+    reference: String,
+    location: PathBuf,
+    /// Name of the package's directory under `node_modules/.oro-unplugged/`,
+    /// if it was unplugged.
+    unplugged: Option<String>,
+    /// Whether the package is a link to a directory the project doesn't
+    /// manage.
+    soft: bool,
+}
+
+impl PnpLinker {
+    pub async fn prune(&self, _graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        let start = std::time::Instant::now();
+
+        let prefix = self.0.root.join("node_modules");
+
+        if !prefix.exists() {
+            tracing::debug!(
+                "Nothing to prune. Completed check in {}ms.",
+                start.elapsed().as_micros() / 1000
+            );
+            return Ok(0);
+        }
+
+        // Nothing in node_modules/ is needed anymore, except for unplugged
+        // packages, which get cleaned up once it's known which ones are
+        // still in use.
+        let mut pruned = 0;
+        let mut entries = async_std::fs::read_dir(&prefix).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if file_name == UNPLUGGED_DIR_NAME || file_name == STAGING_DIR_NAME {
+                continue;
+            }
+            let entry_path: PathBuf = entry.path().into();
+            if let Some(pb) = &self.0.on_prune_progress {
+                pb(&entry_path);
+            }
+            tracing::trace!("Pruning {}", entry_path.display());
+            if super::remove_entry(&self.0, &entry_path).await? {
+                pruned += 1;
+            }
+        }
+
+        tracing::debug!(
+            "Pruned {pruned} entr{} from node_modules/ in {}ms.",
+            if pruned == 1 { "y" } else { "ies" },
+            start.elapsed().as_micros() / 1000
+        );
+        Ok(pruned)
+    }
+
+    pub async fn extract(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        tracing::debug!("Extracting packages for Plug'n'Play...");
+        let start = std::time::Instant::now();
+
+        let root = &self.0.root;
+        let store = self.0.cache.as_deref().map(GlobalStore::in_cache);
+
+        // The same package can only have one location, so copies of it that
+        // need different dependencies can't all be used from the store.
+        let mut instances = HashMap::<(String, String), usize>::new();
+        for idx in graph.inner.node_indices() {
+            if idx == graph.root {
+                continue;
+            }
+            let package = &graph[idx].package;
+            *instances
+                .entry((
+                    package.name().to_string(),
+                    base_reference(root, package.resolved()),
+                ))
+                .or_default() += 1;
+        }
+        let instances = &instances;
+        let is_duplicated = |idx: NodeIndex| {
+            let package = &graph[idx].package;
+            instances
+                .get(&(
+                    package.name().to_string(),
+                    base_reference(root, package.resolved()),
+                ))
+                .map_or(false, |count| *count > 1)
+        };
+
+        if let Some(dry_run) = &self.0.dry_run {
+            // Whether a stored package has to be unplugged anyway, because
+            // of its install scripts, can't be known until it's stored, so
+            // those are reported as going into the store.
+            return super::plan_extract(dry_run, graph, |idx| {
+                let duplicated = is_duplicated(idx);
+                Some(match &store {
+                    Some(store) if !duplicated => store.location(graph, idx, &graph[idx].package),
+                    _ => self.unplugged(graph, idx, duplicated).2,
+                })
+            });
+        }
+
+        let node_modules = root.join("node_modules");
+        let access = &self.0.access;
+        access.check(AccessKind::Write, &node_modules)?;
+        std::fs::create_dir_all(&node_modules)?;
+        if let Some(pipeline) = &self.0.pipeline {
+            // Staged packages would need to be moved into the store, which
+            // isn't worth it. They'll come out of the cache just as well.
+            pipeline.cancel().await;
+        }
+        let prefer_copy = super::prefers_copy(
+            &self.0.link_strategies,
+            self.0.cache.as_deref(),
+            &node_modules,
+        );
+        let is_duplicated = &is_duplicated;
+
+        let placements = Mutex::new(HashMap::new());
+        let placements_ref = &placements;
+        let actually_extracted = AtomicUsize::new(0);
+        let extracted_ref = &actually_extracted;
+        let store = store.as_ref();
+        futures::stream::iter(graph.inner.node_indices())
+            .map(Ok)
+            .try_for_each_concurrent(self.0.concurrency, move |idx| async move {
+                if idx == graph.root {
+                    return Ok(());
+                }
+                let package = &graph[idx].package;
+                let placement = self
+                    .place(
+                        graph,
+                        idx,
+                        is_duplicated(idx),
+                        store,
+                        prefer_copy,
+                        extracted_ref,
+                    )
+                    .await?;
+                if let Some(on_extract) = &self.0.on_extract_progress {
+                    on_extract(package);
+                }
+                tracing::trace!(
+                    "Placed {} at {}.",
+                    package.name(),
+                    placement.location.display()
+                );
+                placements_ref.lock().unwrap().insert(idx, placement);
+                Ok::<_, NodeMaintainerError>(())
+            })
+            .await?;
+        let placements = placements.into_inner().unwrap();
+
+        self.remove_stale_unplugged(&placements).await?;
+
+        let data_path = root.join(PNP_DATA_FILE_NAME);
+        access.check(AccessKind::Write, &data_path)?;
+        std::fs::write(
+            &data_path,
+            serde_json::to_string_pretty(&resolution_map(graph, root, &placements))? + "\n",
+        )?;
+        let runtime_path = root.join(PNP_FILE_NAME);
+        access.check(AccessKind::Write, &runtime_path)?;
+        std::fs::write(&runtime_path, PNP_RUNTIME)?;
+        access.check(AccessKind::Write, &node_modules.join(META_FILE_NAME))?;
+        std::fs::write(
+            node_modules.join(META_FILE_NAME),
+            graph.to_kdl()?.to_string(),
+        )?;
+        super::cleanup_staging(&self.0).await?;
+
+        let actually_extracted = actually_extracted.load(atomic::Ordering::SeqCst);
+        tracing::debug!(
+            "Extracted {actually_extracted} package{} in {}ms.",
+            if actually_extracted == 1 { "" } else { "s" },
+            start.elapsed().as_millis(),
+        );
+        Ok(actually_extracted)
+    }
+
+    /// Makes sure the package at `idx` is available somewhere, and works out
+    /// where that is.
+    async fn place(
+        &self,
+        graph: &Graph,
+        idx: NodeIndex,
+        duplicated: bool,
+        store: Option<&GlobalStore>,
+        prefer_copy: bool,
+        extracted: &AtomicUsize,
+    ) -> Result<Placement, NodeMaintainerError> {
+        let root = &self.0.root;
+        let package = &graph[idx].package;
+        let base = base_reference(root, package.resolved());
+        match package.resolved() {
+            // Local packages are used in place. Unlike with the other
+            // linkers, there's no need to copy `file:` dependencies, since
+            // their dependencies don't depend on where they are.
+            PackageResolution::Dir { path, .. } => {
+                return Ok(Placement {
+                    reference: base,
+                    location: root.join(path),
+                    unplugged: None,
+                    soft: false,
+                });
+            }
+            PackageResolution::Link { path, .. } => {
+                return Ok(Placement {
+                    reference: base,
+                    location: root.join(path),
+                    unplugged: None,
+                    soft: true,
+                });
+            }
+            _ => {}
+        }
+
+        let stored = match store {
+            Some(store) => {
+                let (dir, newly_stored) = store.ensure(&self.0, graph, idx, prefer_copy).await?;
+                if newly_stored {
+                    extracted.fetch_add(1, atomic::Ordering::SeqCst);
+                }
+                Some(dir)
+            }
+            None => None,
+        };
+        let unplug = duplicated
+            || match &stored {
+                Some(dir) => store::has_install_scripts(dir)?,
+                None => true,
+            };
+        let Some(location) = stored.filter(|_| !unplug) else {
+            let (reference, key, location) = self.unplugged(graph, idx, duplicated);
+            if !location.exists() {
+                super::install_package(&self.0, graph, idx, &location, None, prefer_copy).await?;
+                extracted.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+            return Ok(Placement {
+                reference,
+                location,
+                unplugged: Some(key),
+                soft: false,
+            });
+        };
+        Ok(Placement {
+            reference: base,
+            location,
+            unplugged: None,
+            soft: false,
+        })
+    }
+
+    /// Works out the reference, the directory name under
+    /// `node_modules/.oro-unplugged/`, and the location of the package at
+    /// `idx`, for when it gets unplugged.
+    fn unplugged(
+        &self,
+        graph: &Graph,
+        idx: NodeIndex,
+        duplicated: bool,
+    ) -> (String, String, PathBuf) {
+        let root = &self.0.root;
+        let package = &graph[idx].package;
+        let base = base_reference(root, package.resolved());
+        let id = materialized::package_id(graph, idx);
+        let (reference, unplugged_id) = if duplicated {
+            let path = package_path(graph, idx);
+            (
+                format!("virtual:{}#{base}", store::short_hash(&path)),
+                format!("{id} {path}"),
+            )
+        } else {
+            (base, id)
+        };
+        let key = store::short_hash(&unplugged_id);
+        let location = root
+            .join("node_modules")
+            .join(UNPLUGGED_DIR_NAME)
+            .join(&key)
+            .join("node_modules")
+            .join(package.name());
+        (reference, key, location)
+    }
+
+    /// Removes unplugged packages that aren't part of the tree anymore.
+    async fn remove_stale_unplugged(
+        &self,
+        placements: &HashMap<NodeIndex, Placement>,
+    ) -> Result<usize, NodeMaintainerError> {
+        let dir = self.0.root.join("node_modules").join(UNPLUGGED_DIR_NAME);
+        if !dir.exists() {
+            return Ok(0);
+        }
+        let in_use = placements
+            .values()
+            .filter_map(|placement| placement.unplugged.as_deref())
+            .collect::<HashSet<_>>();
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if in_use.contains(entry.file_name().to_string_lossy().as_ref()) {
+                continue;
+            }
+            let entry_path = entry.path();
+            self.0.access.check(AccessKind::Remove, &entry_path)?;
+            if let Some(pb) = &self.0.on_prune_progress {
+                pb(&entry_path);
+            }
+            tracing::trace!("Pruning stale unplugged package: {}", entry_path.display());
+            async_std::fs::remove_dir_all(&entry_path).await?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Links the bins of the root package's dependencies into
+    /// `node_modules/.bin/`, and the bins of each unplugged package's
+    /// dependencies next to it, so they're available to lifecycle scripts.
+    async fn link_bins(
+        &self,
+        graph: &Graph,
+        placements: &HashMap<NodeIndex, Placement>,
+    ) -> Result<usize, NodeMaintainerError> {
+        let _timer = self.0.timings.bins();
+        tracing::debug!("Linking bins...");
+        let start = std::time::Instant::now();
+        let mut linked = 0;
+        let mut targets = vec![(graph.root, self.0.root.join("node_modules").join(".bin"))];
+        for (idx, placement) in placements {
+            let Some(key) = &placement.unplugged else {
+                continue;
+            };
+            targets.push((
+                *idx,
+                self.0
+                    .root
+                    .join("node_modules")
+                    .join(UNPLUGGED_DIR_NAME)
+                    .join(key)
+                    .join("node_modules")
+                    .join(".bin"),
+            ));
+        }
+        for (idx, bin_dir) in targets {
+            if let Ok(meta) = bin_dir.symlink_metadata() {
+                if meta.is_dir() {
+                    super::clear_bin_dir(&self.0, &bin_dir).await?;
+                }
+            }
+            let mut candidates = BinCandidates::default();
+            for edge_idx in graph[idx].dependencies.values() {
+                let (_, dep_idx) = graph
+                    .inner
+                    .edge_endpoints(*edge_idx)
+                    .expect("edge must have endpoints");
+                let Some(placement) = placements.get(&dep_idx) else {
+                    continue;
+                };
+                for (name, path) in materialized::read_bins(&placement.location)? {
+                    candidates.add(
+                        name,
+                        BinCandidate {
+                            idx: dep_idx,
+                            direct: true,
+                            from: placement.location.join(path),
+                        },
+                    );
+                }
+            }
+            for (name, from) in candidates.pick(graph, &bin_dir, self.0.strict_bins)? {
+                let to = bin_dir.join(&name);
+                // We only create a symlink if the target bin exists.
+                if from.symlink_metadata().is_err() {
+                    continue;
+                }
+                std::fs::create_dir_all(&bin_dir)?;
+                if let Ok(meta) = to.symlink_metadata() {
+                    if meta.is_dir() {
+                        std::fs::remove_dir_all(&to)?;
+                    } else {
+                        std::fs::remove_file(&to)?;
+                    }
+                }
+                super::link_bin(&from, &to, &self.0.access, self.0.bin_symlinks)?;
+                tracing::trace!(
+                    "Linked bin for {} from {} to {}",
+                    name,
+                    from.display(),
+                    to.display()
+                );
+                linked += 1;
+            }
+        }
+        tracing::debug!(
+            "Linked {linked} package bins in {}ms.",
+            start.elapsed().as_millis()
+        );
+        Ok(linked)
+    }
+
+    pub async fn rebuild(
+        &self,
+        graph: &Graph,
+        ignore_scripts: bool,
+    ) -> Result<(), NodeMaintainerError> {
+        if self.0.dry_run.is_some() {
+            // Bins are linked based on where extraction placed everything,
+            // which a dry run never writes down.
+            return Ok(());
+        }
+        tracing::debug!("Running lifecycle scripts...");
+        let start = std::time::Instant::now();
+        let placements = self.placements(graph)?;
+        let side_effects = if ignore_scripts {
+            None
+        } else {
+            SideEffectsCache::load(&self.0)
+        };
+        // Only unplugged packages have directories of their own to build in.
+        let package_dir = |idx| {
+            placements
+                .get(&idx)
+                .filter(|placement| placement.unplugged.is_some())
+                .map(|placement| placement.location.clone())
+        };
+        let restored = match &side_effects {
+            Some(cache) => cache.restore(graph, package_dir).await?,
+            None => HashSet::new(),
+        };
+        let platform = if ignore_scripts {
+            None
+        } else {
+            ScriptResults::platform()
+        };
+        let mut done = match &platform {
+            Some(platform) => self
+                .0
+                .script_results
+                .unchanged(graph, package_dir, platform),
+            None => HashSet::new(),
+        };
+        done.extend(&restored);
+        if !ignore_scripts {
+            self.run_scripts(graph, &placements, &done, "preinstall")
+                .await?;
+        }
+        self.link_bins(graph, &placements).await?;
+        if !ignore_scripts {
+            self.run_scripts(graph, &placements, &done, "install")
+                .await?;
+            self.run_scripts(graph, &placements, &done, "postinstall")
+                .await?;
+            if let Some(cache) = &side_effects {
+                cache.save(&self.0, graph, package_dir, &restored).await?;
+            }
+            if let Some(platform) = &platform {
+                self.0
+                    .script_results
+                    .save(&self.0, graph, package_dir, platform)?;
+            }
+        }
+        tracing::debug!(
+            "Ran lifecycle scripts in {}ms.",
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    /// Reads back where every package was placed from the resolution map
+    /// written during extraction.
+    fn placements(
+        &self,
+        graph: &Graph,
+    ) -> Result<HashMap<NodeIndex, Placement>, NodeMaintainerError> {
+        let root = &self.0.root;
+        let data: Value =
+            serde_json::from_str(&std::fs::read_to_string(root.join(PNP_DATA_FILE_NAME))?)?;
+        let unplugged_dir = root.join("node_modules").join(UNPLUGGED_DIR_NAME);
+        let mut locations = HashMap::new();
+        for entry in data["packageRegistryData"].as_array().into_iter().flatten() {
+            let Some(name) = entry[0].as_str() else {
+                continue;
+            };
+            for reference in entry[1].as_array().into_iter().flatten() {
+                let (Some(reference), Some(location)) = (
+                    reference[0].as_str(),
+                    reference[1]["packageLocation"].as_str(),
+                ) else {
+                    continue;
+                };
+                locations.insert(
+                    (name.to_string(), reference.to_string()),
+                    root.join(location),
+                );
+            }
+        }
+        let mut placements = HashMap::new();
+        for idx in graph.inner.node_indices() {
+            if idx == graph.root {
+                continue;
+            }
+            let package = &graph[idx].package;
+            let base = base_reference(root, package.resolved());
+            let virtual_reference = format!(
+                "virtual:{}#{base}",
+                store::short_hash(&package_path(graph, idx))
+            );
+            let Some((reference, location)) =
+                [virtual_reference, base].into_iter().find_map(|reference| {
+                    let location =
+                        locations.get(&(package.name().to_string(), reference.clone()))?;
+                    Some((reference, location.clone()))
+                })
+            else {
+                continue;
+            };
+            let unplugged = location
+                .strip_prefix(&unplugged_dir)
+                .ok()
+                .and_then(|rest| rest.iter().next())
+                .map(|key| key.to_string_lossy().to_string());
+            placements.insert(
+                idx,
+                Placement {
+                    reference,
+                    location,
+                    unplugged,
+                    soft: matches!(package.resolved(), PackageResolution::Link { .. }),
+                },
+            );
+        }
+        Ok(placements)
+    }
+
+    async fn run_scripts(
+        &self,
+        graph: &Graph,
+        placements: &HashMap<NodeIndex, Placement>,
+        done: &HashSet<NodeIndex>,
+        event: &str,
+    ) -> Result<(), NodeMaintainerError> {
+        let root = &self.0.root;
+        let node_options = node_options(root);
+        super::run_scripts(&self.0, graph, done, event, |idx| {
+            let package = if idx == graph.root {
+                root.clone()
+            } else {
+                placements.get(&idx)?.location.clone()
+            };
+            Some(super::ScriptDir {
+                package,
+                workspace: root.clone(),
+                env: vec![("NODE_OPTIONS", node_options.clone())],
+            })
+        })
+        .await
+    }
+}
+
+/// Builds the contents of `.pnp.data.json`.
+fn resolution_map(graph: &Graph, root: &Path, placements: &HashMap<NodeIndex, Placement>) -> Value {
+    let reference_of = |idx: NodeIndex| {
+        if idx == graph.root {
+            Some("workspace:.".to_string())
+        } else {
+            placements
+                .get(&idx)
+                .map(|placement| placement.reference.clone())
+        }
+    };
+    let mut registry = BTreeMap::<String, BTreeMap<String, Value>>::new();
+    let mut indices = vec![graph.root];
+    indices.extend(placements.keys().copied());
+    for idx in indices {
+        let node = &graph[idx];
+        let name = node.package.name();
+        let Some(reference) = reference_of(idx) else {
+            continue;
+        };
+        // Packages can depend on themselves by name.
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert(name.to_string(), json!(reference));
+        for (dep_name, edge_idx) in &node.dependencies {
+            let (_, dep_idx) = graph
+                .inner
+                .edge_endpoints(*edge_idx)
+                .expect("edge must have endpoints");
+            let Some(dep_reference) = reference_of(dep_idx) else {
+                continue;
+            };
+            let dep_name = dep_name.to_string();
+            let dep_package_name = graph[dep_idx].package.name();
+            let value = if dep_package_name == dep_name {
+                json!(dep_reference)
+            } else {
+                // Aliased dependencies refer to the actual package.
+                json!([dep_package_name, dep_reference])
+            };
+            dependencies.insert(dep_name, value);
+        }
+        // Peer dependencies get whatever the package would see from where
+        // it is in the tree, or nothing at all.
+        let mut peers = Vec::new();
+        for peer in node.peer_reqs.keys() {
+            let peer_name = peer.to_string();
+            peers.push(peer_name.clone());
+            if dependencies.contains_key(&peer_name) {
+                continue;
+            }
+            let value = graph
+                .resolve_dep(idx, peer)
+                .and_then(|peer_idx| {
+                    let peer_reference = reference_of(peer_idx)?;
+                    let peer_package_name = graph[peer_idx].package.name();
+                    Some(if peer_package_name == peer_name {
+                        json!(peer_reference)
+                    } else {
+                        json!([peer_package_name, peer_reference])
+                    })
+                })
+                .unwrap_or(Value::Null);
+            dependencies.insert(peer_name, value);
+        }
+        let (location, soft) = if idx == graph.root {
+            (root.to_path_buf(), false)
+        } else {
+            let placement = &placements[&idx];
+            (placement.location.clone(), placement.soft)
+        };
+        registry.entry(name.to_string()).or_default().insert(
+            reference,
+            json!({
+                "packageLocation": package_location(root, &location),
+                "packageDependencies": dependencies.into_iter().collect::<Vec<_>>(),
+                "packagePeers": peers,
+                "linkType": if soft { "SOFT" } else { "HARD" },
+            }),
+        );
+    }
+
+    // Files that don't belong to any package are treated like they belong
+    // to the root.
+    let top_level = registry
+        .get(graph[graph.root].package.name())
+        .and_then(|references| references.get("workspace:."))
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    let mut package_registry_data = vec![json!([null, [[null, top_level]]])];
+    package_registry_data.extend(
+        registry
+            .into_iter()
+            .map(|(name, references)| json!([name, references.into_iter().collect::<Vec<_>>()])),
+    );
+    json!({
+        "__info": [
+            "This file is automatically generated by Orogene. Do not edit it manually.",
+            "Use it with `node --require ./.pnp.cjs`.",
+        ],
+        "dependencyTreeRoots": [{
+            "name": graph[graph.root].package.name(),
+            "reference": "workspace:.",
+        }],
+        "enableTopLevelFallback": true,
+        "ignorePatternData": null,
+        "fallbackExclusionList": [],
+        "fallbackPool": [],
+        "packageRegistryData": package_registry_data,
+    })
+}
+
+/// How the resolution map refers to a package, before telling apart copies
+/// of it that need different dependencies.
+fn base_reference(root: &Path, resolved: &PackageResolution) -> String {
+    match resolved {
+        PackageResolution::Npm { version, .. } => format!("npm:{version}"),
+        PackageResolution::Dir { path, .. } => {
+            format!("workspace:{}", relative_path(root, &root.join(path)))
+        }
+        PackageResolution::Link { path, .. } => {
+            format!("link:{}", relative_path(root, &root.join(path)))
+        }
+        resolved => resolved.to_string(),
+    }
+}
+
+/// `location`, in the form `.pnp.data.json` expects: relative to the
+/// project root, starting with `./` or `../`, and ending with a `/`.
+fn package_location(root: &Path, location: &Path) -> String {
+    let relative = relative_path(root, location);
+    if relative == "." {
+        "./".to_string()
+    } else if relative.starts_with("../") || Path::new(&relative).is_absolute() {
+        format!("{relative}/")
+    } else {
+        format!("./{relative}/")
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = pathdiff::diff_paths(path, root).unwrap_or_else(|| path.to_path_buf());
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    if relative.is_empty() {
+        ".".into()
+    } else {
+        relative
+    }
+}
+
+/// Where the package at `idx` is in the tree, for telling apart copies of
+/// the same package.
+fn package_path(graph: &Graph, idx: NodeIndex) -> String {
+    graph
+        .node_path(idx)
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join("/node_modules/")
+}
+
+/// `NODE_OPTIONS` for lifecycle scripts, so any Node.js process they start
+/// resolves packages through `.pnp.cjs`.
+fn node_options(root: &Path) -> String {
+    let runtime = root.join(PNP_FILE_NAME);
+    // Scripts run inside each package, not the project root.
+    let runtime = if runtime.is_absolute() {
+        runtime
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(&runtime))
+            .unwrap_or(runtime)
+    };
+    let require = format!("--require \"{}\"", runtime.display());
+    match std::env::var("NODE_OPTIONS") {
+        Ok(existing) if !existing.trim().is_empty() => format!("{existing} {require}"),
+        _ => require,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_locations() {
+        let root = Path::new("/project");
+        assert_eq!(package_location(root, root), "./");
+        assert_eq!(
+            package_location(
+                root,
+                &root.join("node_modules/.oro-unplugged/abc/node_modules/a")
+            ),
+            "./node_modules/.oro-unplugged/abc/node_modules/a/"
+        );
+        assert_eq!(
+            package_location(root, Path::new("/cache/store-v1/abc/node_modules/@s/b")),
+            "../cache/store-v1/abc/node_modules/@s/b/"
+        );
+    }
+
+    #[test]
+    fn local_references() {
+        let root = Path::new("/project");
+        assert_eq!(
+            base_reference(
+                root,
+                &PackageResolution::Dir {
+                    name: "b".into(),
+                    path: "packages/b".into(),
+                }
+            ),
+            "workspace:packages/b"
+        );
+        assert_eq!(
+            base_reference(
+                root,
+                &PackageResolution::Link {
+                    name: "c".into(),
+                    path: "/elsewhere/c".into(),
+                }
+            ),
+            "link:../elsewhere/c"
+        );
+    }
+}
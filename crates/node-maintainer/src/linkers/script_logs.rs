@@ -0,0 +1,149 @@
+//! Logs for install scripts that failed.
+//!
+//! Script output normally only goes to the progress bar and the debug log,
+//! so by the time a script fails, most of what it printed is gone. Failed
+//! scripts get everything they printed written to a log file in the cache's
+//! `_logs/` directory instead, along with a summary of the environment they
+//! ran in, and the error points to it.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::access::AccessKind;
+use crate::error::NodeMaintainerError;
+
+use super::LinkerOptions;
+
+const LOGS_DIR_NAME: &str = "_logs";
+
+/// A script that failed, and everything it printed, stdout and stderr
+/// interleaved.
+pub(crate) struct FailedScript<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) event: &'a str,
+    pub(crate) command: &'a str,
+    pub(crate) package_dir: &'a Path,
+    pub(crate) output: &'a [String],
+}
+
+impl FailedScript<'_> {
+    /// Writes a log for the script failing with `error`, and returns the
+    /// error to report, pointing to the log if it got written.
+    pub(crate) fn into_error(
+        self,
+        opts: &LinkerOptions,
+        error: NodeMaintainerError,
+    ) -> NodeMaintainerError {
+        let log = match self.write_log(opts, &error) {
+            Ok(log) => log,
+            Err(e) => {
+                tracing::debug!("Failed to write a log for the failed script: {e}");
+                None
+            }
+        };
+        NodeMaintainerError::ScriptFailed {
+            name: self.name.into(),
+            event: self.event.into(),
+            help: log.as_ref().map(|log| {
+                format!(
+                    "Its full output and environment were written to {}",
+                    log.display()
+                )
+            }),
+            log,
+            source: Box::new(error),
+        }
+    }
+
+    fn write_log(
+        &self,
+        opts: &LinkerOptions,
+        error: &NodeMaintainerError,
+    ) -> Result<Option<PathBuf>, NodeMaintainerError> {
+        let Some(cache) = &opts.cache else {
+            return Ok(None);
+        };
+        let dir = cache.join(LOGS_DIR_NAME);
+        let file = dir.join(self.file_name());
+        opts.access.check(AccessKind::Write, &file)?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&file, self.contents(opts, error))?;
+        Ok(Some(file))
+    }
+
+    fn file_name(&self) -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let name = self.name.replace('@', "").replace('/', "-");
+        format!("oro-script-{name}-{}-{millis}.log", self.event)
+    }
+
+    fn contents(&self, opts: &LinkerOptions, error: &NodeMaintainerError) -> String {
+        let mut contents = String::new();
+        // Writing to a String can't fail.
+        let _ = writeln!(contents, "package: {}", self.name);
+        let _ = writeln!(contents, "script: {} (`{}`)", self.event, self.command);
+        let _ = writeln!(contents, "directory: {}", self.package_dir.display());
+        let _ = writeln!(contents, "error: {error}");
+        let _ = writeln!(contents);
+        let _ = writeln!(contents, "orogene: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(
+            contents,
+            "platform: {}-{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        let _ = writeln!(contents, "node: {}", node_version());
+        let _ = writeln!(
+            contents,
+            "shell: {}",
+            opts.script_shell
+                .as_ref()
+                .map(|shell| shell.display().to_string())
+                .unwrap_or_else(|| "default".into())
+        );
+        let sandboxed =
+            opts.sandbox_scripts && !opts.sandbox_exempt.iter().any(|exempt| exempt == self.name);
+        let _ = writeln!(contents, "sandboxed: {sandboxed}");
+        let _ = writeln!(contents);
+        let _ = writeln!(contents, "--- output ---");
+        for line in self.output {
+            let _ = writeln!(contents, "{line}");
+        }
+        contents
+    }
+}
+
+/// Version of the `node` that scripts run with, if there is one.
+fn node_version() -> String {
+    Command::new("node")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "not found".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_are_flat() {
+        let failed = FailedScript {
+            name: "@scope/pkg",
+            event: "install",
+            command: "node-gyp rebuild",
+            package_dir: Path::new("node_modules/@scope/pkg"),
+            output: &[],
+        };
+        let name = failed.file_name();
+        assert!(name.starts_with("oro-script-scope-pkg-install-"));
+        assert!(name.ends_with(".log"));
+    }
+}
@@ -0,0 +1,220 @@
+//! Remembers which installed packages have already had their install
+//! scripts run, so later applies (like `oro reapply`) don't run them again
+//! for packages that haven't changed.
+//!
+//! Results are recorded in the project's `.oro/scripts/` directory, by
+//! where each package is installed, and keyed by the package's resolved
+//! source, integrity, and patch, along with the OS, architecture, and
+//! Node.js ABI version the scripts ran under. Packages that get extracted
+//! again, for whatever reason, always have their scripts run again, since
+//! whatever the scripts did got thrown away with the old copy.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use kdl::{KdlDocument, KdlNode};
+use petgraph::stable_graph::NodeIndex;
+
+use crate::access::AccessKind;
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+use crate::state::ProjectState;
+
+use super::side_effects::{self, is_cacheable};
+use super::{materialized, store, LinkerOptions};
+
+const RESULTS_FILE_NAME: &str = "results.kdl";
+
+/// Bump this whenever the file's format changes. Files with any other
+/// version are ignored.
+const RESULTS_FILE_VERSION: i64 = 1;
+
+#[derive(Debug)]
+pub(crate) struct ScriptResults {
+    root: PathBuf,
+    /// Run every script, even for packages whose results are recorded.
+    force: bool,
+    /// Packages extracted during this apply.
+    extracted: Mutex<HashSet<PathBuf>>,
+}
+
+impl ScriptResults {
+    pub(crate) fn new(root: &Path, force: bool) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            force,
+            extracted: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Records that a fresh copy of a package was just put in `dir`, so its
+    /// scripts need to run again.
+    pub(crate) fn extracted(&self, dir: &Path) {
+        self.extracted.lock().unwrap().insert(dir.to_path_buf());
+    }
+
+    /// The OS, architecture, and Node.js ABI version that scripts run
+    /// under. Returns `None` if Node.js can't be found, in which case
+    /// nothing gets recorded or skipped.
+    pub(crate) fn platform() -> Option<String> {
+        match side_effects::node_abi() {
+            Ok(abi) => Some(side_effects::platform(&abi)),
+            Err(e) => {
+                tracing::debug!("Not reusing install script results, since the Node.js ABI version couldn't be determined: {e}");
+                None
+            }
+        }
+    }
+
+    /// Packages whose install scripts already ran, for exactly what's
+    /// installed now, on this `platform`.
+    pub(crate) fn unchanged(
+        &self,
+        graph: &Graph,
+        package_dir: impl Fn(NodeIndex) -> Option<PathBuf>,
+        platform: &str,
+    ) -> HashSet<NodeIndex> {
+        if self.force {
+            return HashSet::new();
+        }
+        let Some(recorded) = self.load() else {
+            return HashSet::new();
+        };
+        let extracted = self.extracted.lock().unwrap();
+        graph
+            .inner
+            .node_indices()
+            .filter(|idx| is_cacheable(graph, *idx))
+            .filter(|idx| {
+                let Some(dir) = package_dir(*idx) else {
+                    return false;
+                };
+                !extracted.contains(&dir)
+                    && recorded.get(&self.relative(&dir)) == Some(&key(graph, *idx, platform))
+            })
+            .collect()
+    }
+
+    /// Records every package that has install scripts and got to run them.
+    /// Optional packages are left out, since their scripts are allowed to
+    /// fail, and so are packages whose scripts weren't allowed to run.
+    pub(crate) fn save(
+        &self,
+        opts: &LinkerOptions,
+        graph: &Graph,
+        package_dir: impl Fn(NodeIndex) -> Option<PathBuf>,
+        platform: &str,
+    ) -> Result<(), NodeMaintainerError> {
+        let mut recorded = Vec::new();
+        for idx in graph.inner.node_indices() {
+            if graph.is_optional(idx)
+                || !is_cacheable(graph, idx)
+                || !opts.script_trust.allows(graph, idx)
+            {
+                continue;
+            }
+            let Some(dir) = package_dir(idx) else {
+                continue;
+            };
+            if store::has_install_scripts(&dir)? {
+                recorded.push((self.relative(&dir), key(graph, idx, platform)));
+            }
+        }
+        recorded.sort();
+        let state = ProjectState::new(&self.root);
+        if let Err(e) = state.ensure() {
+            tracing::debug!("Not recording install script results: {e}");
+            return Ok(());
+        }
+        let mut doc = KdlDocument::new();
+        doc.set_leading(
+            "// This file is automatically generated and not intended for manual editing.",
+        );
+        let mut version_node = KdlNode::new("results-version");
+        version_node.push(RESULTS_FILE_VERSION);
+        doc.nodes_mut().push(version_node);
+        for (path, key) in recorded {
+            let mut node = KdlNode::new("ran");
+            node.push(path);
+            node.push(key);
+            doc.nodes_mut().push(node);
+        }
+        doc.fmt();
+        let dir = state.scripts_dir();
+        opts.access.check(AccessKind::Write, &dir)?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(RESULTS_FILE_NAME), doc.to_string())?;
+        Ok(())
+    }
+
+    /// Recorded results, by package directory relative to the project root.
+    fn load(&self) -> Option<HashMap<String, String>> {
+        let file = ProjectState::new(&self.root)
+            .scripts_dir()
+            .join(RESULTS_FILE_NAME);
+        let doc: KdlDocument = std::fs::read_to_string(file).ok()?.parse().ok()?;
+        if doc.get_arg("results-version")?.as_i64()? != RESULTS_FILE_VERSION {
+            return None;
+        }
+        let mut recorded = HashMap::new();
+        for node in doc.nodes().iter().filter(|n| n.name().value() == "ran") {
+            recorded.insert(
+                node.get(0)?.as_string()?.to_string(),
+                node.get(1)?.as_string()?.to_string(),
+            );
+        }
+        Some(recorded)
+    }
+
+    fn relative(&self, dir: &Path) -> String {
+        dir.strip_prefix(&self.root)
+            .unwrap_or(dir)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+fn key(graph: &Graph, idx: NodeIndex, platform: &str) -> String {
+    store::short_hash(&format!(
+        "{} {platform}",
+        materialized::package_id(graph, idx)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        let results = ScriptResults::new(Path::new("/project"), false);
+        assert_eq!(
+            results.relative(Path::new("/project/node_modules/@scope/a")),
+            "node_modules/@scope/a"
+        );
+    }
+
+    #[test]
+    fn ignores_other_versions() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let results = ScriptResults::new(root.path(), false);
+        let dir = ProjectState::new(root.path()).scripts_dir();
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join(RESULTS_FILE_NAME),
+            "results-version 1\nran \"node_modules/a\" \"abc\"\n",
+        )?;
+        assert_eq!(
+            results
+                .load()
+                .unwrap()
+                .get("node_modules/a")
+                .map(|k| k.as_str()),
+            Some("abc")
+        );
+        std::fs::write(dir.join(RESULTS_FILE_NAME), "results-version 2\n")?;
+        assert!(results.load().is_none());
+        Ok(())
+    }
+}
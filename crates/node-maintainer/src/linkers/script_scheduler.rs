@@ -0,0 +1,327 @@
+//! Decides how many install scripts get to run at once.
+//!
+//! Most install scripts are quick, like ones that print a message or copy
+//! a file, and can run side by side without much trouble. Native builds,
+//! like `node-gyp rebuild`, each keep a CPU busy for a while and can use a
+//! lot of memory, so running too many of them at once just makes them all
+//! slower, or runs the machine out of memory. Unless a fixed limit is
+//! given, scripts get started based on how many CPUs the machine has, how
+//! busy they already are, and how much memory is left, with native builds
+//! held back more than everything else.
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Commands that mean a script builds native code.
+const HEAVY_COMMANDS: &[&str] = &[
+    "node-gyp",
+    "node-pre-gyp",
+    "prebuild-install",
+    "cmake-js",
+    "napi build",
+    "cargo",
+    "make",
+];
+
+/// How long a sample of the system's load is reused for, since load
+/// averages don't change that fast anyway.
+const SAMPLE_TTL: Duration = Duration::from_secs(1);
+
+/// Below this fraction of memory still available, the machine counts as
+/// being under memory pressure.
+const LOW_MEMORY: f64 = 0.1;
+
+#[derive(Debug)]
+pub(crate) struct ScriptScheduler {
+    /// Fixed number of scripts to run at once, no matter the load.
+    fixed: Option<usize>,
+    cpus: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    heavy: usize,
+    sample: Option<(Instant, Sample)>,
+    waiting: Vec<Waker>,
+}
+
+/// How busy the machine is. Either part is `None` where it can't be
+/// found out.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    /// One minute load average.
+    load: Option<f64>,
+    /// Fraction of memory that's still available.
+    memory: Option<f64>,
+}
+
+/// How many scripts may run at once, right now.
+#[derive(Debug, PartialEq, Eq)]
+struct Limits {
+    total: usize,
+    heavy: usize,
+}
+
+impl ScriptScheduler {
+    /// A scheduler that adapts to the machine's load, unless `fixed` is
+    /// given.
+    pub(crate) fn new(fixed: Option<usize>) -> Self {
+        Self {
+            fixed: fixed.map(|fixed| fixed.max(1)),
+            cpus: std::thread::available_parallelism()
+                .map(|cpus| cpus.get())
+                .unwrap_or(1),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Most scripts that could ever run at once.
+    pub(crate) fn width(&self) -> usize {
+        self.fixed.unwrap_or(self.cpus * 2)
+    }
+
+    /// Waits until the machine has room for another script, which counts
+    /// as running until the returned permit is dropped.
+    pub(crate) fn acquire(&self, heavy: bool) -> Acquire<'_> {
+        Acquire {
+            scheduler: self,
+            heavy,
+        }
+    }
+
+    fn try_start(&self, state: &mut State, heavy: bool) -> bool {
+        // Something always gets to run, or nothing ever would.
+        let admitted = if state.running == 0 {
+            true
+        } else if let Some(fixed) = self.fixed {
+            state.running < fixed
+        } else {
+            let sample = match state.sample {
+                Some((at, sample)) if at.elapsed() < SAMPLE_TTL => sample,
+                _ => {
+                    let sample = Sample::current();
+                    state.sample = Some((Instant::now(), sample));
+                    sample
+                }
+            };
+            let limits = Limits::new(self.cpus, sample, state.heavy);
+            state.running < limits.total && (!heavy || state.heavy < limits.heavy)
+        };
+        if admitted {
+            state.running += 1;
+            if heavy {
+                state.heavy += 1;
+            }
+        }
+        admitted
+    }
+}
+
+/// Future returned by [`ScriptScheduler::acquire`].
+pub(crate) struct Acquire<'a> {
+    scheduler: &'a ScriptScheduler,
+    heavy: bool,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = ScriptPermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.scheduler.state.lock().unwrap();
+        if self.scheduler.try_start(&mut state, self.heavy) {
+            Poll::Ready(ScriptPermit {
+                scheduler: self.scheduler,
+                heavy: self.heavy,
+            })
+        } else {
+            // Woken up whenever another script finishes.
+            state.waiting.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// A script's place among the ones that are running.
+pub(crate) struct ScriptPermit<'a> {
+    scheduler: &'a ScriptScheduler,
+    heavy: bool,
+}
+
+impl Drop for ScriptPermit<'_> {
+    fn drop(&mut self) {
+        let waiting = {
+            let mut state = self.scheduler.state.lock().unwrap();
+            state.running -= 1;
+            if self.heavy {
+                state.heavy -= 1;
+            }
+            std::mem::take(&mut state.waiting)
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+}
+
+/// Whether running `command` for the package in `package_dir` looks like
+/// it builds native code.
+pub(crate) fn is_heavy(command: &str, package_dir: &Path) -> bool {
+    let words = command
+        .split(|c: char| c.is_whitespace() || c == '&' || c == ';' || c == '|')
+        .collect::<Vec<_>>();
+    HEAVY_COMMANDS.iter().any(|heavy| {
+        if heavy.contains(' ') {
+            command.contains(heavy)
+        } else {
+            words.contains(heavy)
+        }
+    }) || package_dir.join("binding.gyp").exists()
+}
+
+impl Limits {
+    /// Limits for a machine with `cpus` CPUs, as busy as `sample` says,
+    /// with `heavy` native builds of our own already running.
+    fn new(cpus: usize, sample: Sample, heavy: usize) -> Self {
+        let low_memory = sample.memory.map_or(false, |memory| memory < LOW_MEMORY);
+        if low_memory {
+            return Self { total: 1, heavy: 1 };
+        }
+        // Our own builds show up in the load average too, so they
+        // shouldn't count twice.
+        let busy = sample
+            .load
+            .map_or(0.0, |load| (load - heavy as f64).max(0.0));
+        let idle = (cpus as f64 - busy).floor().max(1.0) as usize;
+        Self {
+            total: (idle * 2).max(1),
+            heavy: idle.min(cpus),
+        }
+    }
+}
+
+impl Sample {
+    #[cfg(target_os = "linux")]
+    fn current() -> Self {
+        Self {
+            load: std::fs::read_to_string("/proc/loadavg")
+                .ok()
+                .and_then(|loadavg| parse_loadavg(&loadavg)),
+            memory: std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| parse_meminfo(&meminfo)),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn current() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_loadavg(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_meminfo(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse::<f64>()
+            .ok()
+    };
+    let total = field("MemTotal")?;
+    if total <= 0.0 {
+        return None;
+    }
+    Some(field("MemAvailable")? / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_native_builds() {
+        let dir = Path::new("does-not-exist");
+        assert!(is_heavy("node-gyp rebuild", dir));
+        assert!(is_heavy("prebuild-install || node-gyp rebuild", dir));
+        assert!(is_heavy("napi build --release", dir));
+        assert!(!is_heavy("node install.js", dir));
+        assert!(!is_heavy("echo makefile", dir));
+    }
+
+    #[test]
+    fn limits_follow_load() {
+        let idle = Sample {
+            load: Some(0.0),
+            memory: Some(0.5),
+        };
+        assert_eq!(
+            Limits::new(8, idle, 0),
+            Limits {
+                total: 16,
+                heavy: 8
+            }
+        );
+        let busy = Sample {
+            load: Some(6.0),
+            memory: Some(0.5),
+        };
+        assert_eq!(Limits::new(8, busy, 0), Limits { total: 4, heavy: 2 });
+        // Our own builds don't count against us.
+        assert_eq!(
+            Limits::new(8, busy, 4),
+            Limits {
+                total: 12,
+                heavy: 6
+            }
+        );
+        let overloaded = Sample {
+            load: Some(20.0),
+            memory: None,
+        };
+        assert_eq!(Limits::new(8, overloaded, 0), Limits { total: 2, heavy: 1 });
+        let low_memory = Sample {
+            load: Some(0.0),
+            memory: Some(0.05),
+        };
+        assert_eq!(Limits::new(8, low_memory, 0), Limits { total: 1, heavy: 1 });
+    }
+
+    #[test]
+    fn parses_proc_files() {
+        assert_eq!(parse_loadavg("1.50 0.80 0.40 2/345 6789\n"), Some(1.5));
+        assert_eq!(
+            parse_meminfo(
+                "MemTotal:       1000 kB\nMemFree:         100 kB\nMemAvailable:    250 kB\n"
+            ),
+            Some(0.25)
+        );
+        assert_eq!(parse_meminfo("MemFree: 100 kB\n"), None);
+    }
+
+    #[async_std::test]
+    async fn fixed_limit() {
+        let scheduler = ScriptScheduler::new(Some(2));
+        let first = scheduler.acquire(true).await;
+        let _second = scheduler.acquire(false).await;
+        {
+            let mut state = scheduler.state.lock().unwrap();
+            assert!(!scheduler.try_start(&mut state, false));
+        }
+        drop(first);
+        let _third = scheduler.acquire(false).await;
+        assert_eq!(scheduler.state.lock().unwrap().running, 2);
+    }
+}
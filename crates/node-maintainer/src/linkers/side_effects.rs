@@ -0,0 +1,206 @@
+//! A cache of what install scripts leave behind. Once a package's
+//! `preinstall`, `install`, and `postinstall` scripts have run, its directory
+//! is snapshotted into the cache, and later installs of the same package
+//! restore the snapshot instead of running the scripts again. This mostly
+//! saves on native builds, like `node-gyp` compiles.
+//!
+//! Snapshots are keyed by the package's resolved source, integrity, and
+//! patch, along with the OS, architecture, and Node.js ABI version, since
+//! build output usually only works where it was built.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use nassun::PackageResolution;
+use petgraph::stable_graph::NodeIndex;
+
+use crate::access::AccessKind;
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+
+use super::dedupe::FileDeduper;
+use super::store::{self, link_tree};
+use super::{materialized, LinkerOptions};
+
+/// Directory inside the cache that holds the snapshots.
+const SIDE_EFFECTS_DIR_NAME: &str = "side-effects-v1";
+
+pub(crate) struct SideEffectsCache {
+    dir: PathBuf,
+    platform: String,
+    files: Arc<FileDeduper>,
+}
+
+impl SideEffectsCache {
+    /// Sets up the side effects cache, if it's enabled and there's a cache
+    /// to put it in. Returns `None` if Node.js can't be found, since there's
+    /// no way to tell which snapshots would work with it.
+    pub(crate) fn load(opts: &LinkerOptions) -> Option<Self> {
+        if !opts.side_effects_cache {
+            return None;
+        }
+        let cache = opts.cache.as_deref()?;
+        let abi = match node_abi() {
+            Ok(abi) => abi,
+            Err(e) => {
+                tracing::debug!("Not using the side effects cache, since the Node.js ABI version couldn't be determined: {e}");
+                return None;
+            }
+        };
+        Some(Self::new(cache, &abi))
+    }
+
+    fn new(cache: &Path, abi: &str) -> Self {
+        Self {
+            dir: cache.join(SIDE_EFFECTS_DIR_NAME),
+            platform: platform(abi),
+            files: Arc::new(FileDeduper::in_cache(cache)),
+        }
+    }
+
+    /// Replaces installed packages with their snapshots, wherever there is
+    /// one, and returns the packages that were restored. Their install
+    /// scripts shouldn't be run again.
+    pub(crate) async fn restore(
+        &self,
+        graph: &Graph,
+        package_dir: impl Fn(NodeIndex) -> Option<PathBuf>,
+    ) -> Result<HashSet<NodeIndex>, NodeMaintainerError> {
+        let mut restored = HashSet::new();
+        for idx in graph.inner.node_indices() {
+            if !is_cacheable(graph, idx) {
+                continue;
+            }
+            let Some(dir) = package_dir(idx) else {
+                continue;
+            };
+            let snapshot = self.location(graph, idx);
+            if !snapshot.exists() {
+                continue;
+            }
+            tracing::debug!(
+                "Restoring {} from the side effects cache.",
+                graph[idx].package.name()
+            );
+            async_std::task::spawn_blocking(move || {
+                if dir.exists() {
+                    std::fs::remove_dir_all(&dir)?;
+                }
+                link_tree(&snapshot, &dir, true)
+            })
+            .await?;
+            restored.insert(idx);
+        }
+        Ok(restored)
+    }
+
+    /// Snapshots every package that ran install scripts, and wasn't restored
+    /// from a snapshot to begin with. Optional packages are left out, since
+    /// their scripts are allowed to fail.
+    pub(crate) async fn save(
+        &self,
+        opts: &LinkerOptions,
+        graph: &Graph,
+        package_dir: impl Fn(NodeIndex) -> Option<PathBuf>,
+        restored: &HashSet<NodeIndex>,
+    ) -> Result<(), NodeMaintainerError> {
+        for idx in graph.inner.node_indices() {
+            if restored.contains(&idx) || graph.is_optional(idx) || !is_cacheable(graph, idx) {
+                continue;
+            }
+            let Some(dir) = package_dir(idx) else {
+                continue;
+            };
+            let snapshot = self.location(graph, idx);
+            if snapshot.exists() || !store::has_install_scripts(&dir)? {
+                continue;
+            }
+            opts.access.check(AccessKind::Write, &self.dir)?;
+            std::fs::create_dir_all(&self.dir)?;
+            let temp = tempfile::tempdir_in(&self.dir)?;
+            let files = self.files.clone();
+            async_std::task::spawn_blocking(move || {
+                // Snapshots are copied next to their final location and then
+                // moved into place, so other installs never see a partial
+                // one.
+                let staged = temp.path().join("package");
+                link_tree(&dir, &staged, true)?;
+                // Most of a snapshot is usually the package's own files,
+                // untouched by its scripts.
+                files.dedupe(&staged)?;
+                std::fs::create_dir_all(snapshot.parent().expect("must have a parent"))?;
+                if let Err(e) = std::fs::rename(&staged, &snapshot) {
+                    // Another install might have gotten there first.
+                    if !snapshot.exists() {
+                        return Err(e.into());
+                    }
+                }
+                Ok::<_, NodeMaintainerError>(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    fn location(&self, graph: &Graph, idx: NodeIndex) -> PathBuf {
+        self.dir.join(store::short_hash(&format!(
+            "{} {}",
+            materialized::package_id(graph, idx),
+            self.platform
+        )))
+    }
+}
+
+/// Identifies where build output works: the OS, architecture, and Node.js
+/// ABI version.
+pub(super) fn platform(abi: &str) -> String {
+    format!(
+        "{}-{}-node{abi}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )
+}
+
+/// Local packages are built in place by their owners, so only packages that
+/// came from somewhere else get snapshots.
+pub(super) fn is_cacheable(graph: &Graph, idx: NodeIndex) -> bool {
+    idx != graph.root
+        && !matches!(
+            graph[idx].package.resolved(),
+            PackageResolution::Dir { .. } | PackageResolution::Link { .. }
+        )
+}
+
+/// The `NODE_MODULE_VERSION` of the Node.js on the `PATH`, which native
+/// addons are built against.
+pub(super) fn node_abi() -> Result<String, NodeMaintainerError> {
+    let output = Command::new("node")
+        .args(["-p", "process.versions.modules"])
+        .output()?;
+    let abi = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || abi.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("`node` exited with {}", output.status),
+        )
+        .into());
+    }
+    Ok(abi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_snapshots_by_platform() {
+        let cache = Path::new("cache");
+        let linux = SideEffectsCache::new(cache, "108");
+        let other = SideEffectsCache::new(cache, "115");
+        assert!(linux.dir.ends_with(SIDE_EFFECTS_DIR_NAME));
+        assert!(linux.platform.ends_with("-node108"));
+        assert_ne!(linux.platform, other.platform);
+    }
+}
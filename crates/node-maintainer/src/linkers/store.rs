@@ -0,0 +1,202 @@
+//! A global store of extracted packages, kept inside the cache and shared by
+//! every project that uses it, the way pnpm's content-addressable store
+//! works. Each package is extracted into the store once, and installs hard
+//! link its files into place from there. Identical packages across projects
+//! take up disk space once, and installing an already-stored package is
+//! mostly a matter of creating links.
+//!
+//! Stored packages are keyed by their resolved source, integrity, and patch,
+//! so patched packages get an entry of their own and patches never touch
+//! another project's files.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use nassun::package::Package;
+use oro_common::BuildManifest;
+use petgraph::stable_graph::NodeIndex;
+use walkdir::WalkDir;
+
+use crate::access::AccessKind;
+use crate::error::NodeMaintainerError;
+use crate::graph::Graph;
+
+use super::dedupe::FileDeduper;
+use super::{materialized, LinkerOptions};
+
+/// Directory inside the cache that holds the store.
+const GLOBAL_STORE_DIR_NAME: &str = "store-v1";
+
+/// Lifecycle events that might modify a package's own files.
+const INSTALL_EVENTS: &[&str] = &["preinstall", "install", "postinstall"];
+
+pub(crate) struct GlobalStore {
+    dir: PathBuf,
+    files: Arc<FileDeduper>,
+}
+
+impl GlobalStore {
+    pub(crate) fn in_cache(cache: &Path) -> Self {
+        Self {
+            dir: cache.join(GLOBAL_STORE_DIR_NAME),
+            files: Arc::new(FileDeduper::in_cache(cache)),
+        }
+    }
+
+    /// Extracts the package at `idx` into the store, unless it's already
+    /// there, and returns its location, along with whether it had to be
+    /// extracted. Packages are extracted next to their final location and
+    /// then moved into place, so other installs sharing the store never see
+    /// a half-extracted package.
+    pub(crate) async fn ensure(
+        &self,
+        opts: &LinkerOptions,
+        graph: &Graph,
+        idx: NodeIndex,
+        prefer_copy: bool,
+    ) -> Result<(PathBuf, bool), NodeMaintainerError> {
+        let package = &graph[idx].package;
+        let target = self.location(graph, idx, package);
+        if target.exists() {
+            return Ok((target, false));
+        }
+        let access = &opts.access;
+        access.check(AccessKind::Write, &self.dir)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let temp = tempfile::tempdir_in(&self.dir)?;
+        let staged = temp.path().join("package");
+        super::extract_package(
+            package,
+            &staged,
+            prefer_copy,
+            opts.validate,
+            opts.extract_bytes.as_ref(),
+        )
+        .await?;
+        if let Some((_, patch)) = opts.patches.find(package) {
+            patch.apply(package.name(), &staged, access)?;
+        }
+        let files = self.files.clone();
+        let deduped = staged.clone();
+        let saved = async_std::task::spawn_blocking(move || files.dedupe(&deduped)).await?;
+        if saved > 0 {
+            tracing::debug!(
+                "Saved {saved} bytes storing {} by sharing files with other packages.",
+                package.name()
+            );
+        }
+        std::fs::create_dir_all(target.parent().expect("must have a parent"))?;
+        if let Err(e) = std::fs::rename(&staged, &target) {
+            // Another install might have gotten there first.
+            if !target.exists() {
+                return Err(e.into());
+            }
+        }
+        Ok((target, true))
+    }
+
+    /// Installs the package at `idx` into `target` from the store, storing
+    /// it first if needed. Files are hard linked, unless `copy` is true or
+    /// the package has install scripts (which would modify the shared
+    /// files), in which case they're copied, using reflinks where possible.
+    pub(crate) async fn install(
+        &self,
+        opts: &LinkerOptions,
+        graph: &Graph,
+        idx: NodeIndex,
+        target: &Path,
+        copy: bool,
+    ) -> Result<(), NodeMaintainerError> {
+        let (stored, _) = self.ensure(opts, graph, idx, copy).await?;
+        let copy = copy || has_install_scripts(&stored)?;
+        let target = target.to_path_buf();
+        async_std::task::spawn_blocking(move || link_tree(&stored, &target, copy)).await
+    }
+
+    pub(crate) fn location(&self, graph: &Graph, idx: NodeIndex, package: &Package) -> PathBuf {
+        self.dir
+            .join(short_hash(&materialized::package_id(graph, idx)))
+            .join("node_modules")
+            .join(package.name())
+    }
+}
+
+/// Recreates the directory tree at `from` in `to`, hard linking (or
+/// copying) each file.
+pub(crate) fn link_tree(from: &Path, to: &Path, copy: bool) -> Result<(), NodeMaintainerError> {
+    static WARN_ONCE: std::sync::Once = std::sync::Once::new();
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let path = to.join(
+            entry
+                .path()
+                .strip_prefix(from)
+                .expect("walked paths are inside the walked directory"),
+        );
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if !copy {
+            match std::fs::hard_link(entry.path(), &path) {
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::debug!(
+                        "Failed to hard link {} from the global store: {e}",
+                        path.display()
+                    );
+                    WARN_ONCE.call_once(|| {
+                        tracing::warn!("Hard linking from the global store failed, so files will be copied instead. Make sure the cache is on the same drive as your project.");
+                    });
+                }
+            }
+        }
+        reflink::reflink_or_copy(entry.path(), &path)?;
+    }
+    Ok(())
+}
+
+/// Short, filesystem-friendly hash of `input`.
+pub(crate) fn short_hash(input: &str) -> String {
+    let (_, hex) = ssri::Integrity::from(input).to_hex();
+    hex[..16].to_string()
+}
+
+/// Whether the package at `dir` has scripts that would run when it's
+/// installed.
+pub(crate) fn has_install_scripts(dir: &Path) -> Result<bool, NodeMaintainerError> {
+    let build_mani = BuildManifest::from_path(dir.join("package.json"))
+        .map_err(|e| NodeMaintainerError::BuildManifestReadError(dir.join("package.json"), e))?;
+    Ok(INSTALL_EVENTS
+        .iter()
+        .any(|event| build_mani.scripts.contains_key(*event)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_tree_shares_files() -> Result<(), NodeMaintainerError> {
+        let from = tempfile::tempdir()?;
+        let to = tempfile::tempdir()?;
+        std::fs::create_dir_all(from.path().join("lib"))?;
+        std::fs::write(from.path().join("package.json"), "{}")?;
+        std::fs::write(from.path().join("lib/index.js"), "module.exports = 1")?;
+        let target = to.path().join("node_modules/a");
+        link_tree(from.path(), &target, false)?;
+        assert_eq!(
+            std::fs::read_to_string(target.join("lib/index.js"))?,
+            "module.exports = 1"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(
+                std::fs::metadata(from.path().join("package.json"))?.ino(),
+                std::fs::metadata(target.join("package.json"))?.ino()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! Materializes a resolved tree into a [`Vfs`] instead of the real
+//! filesystem, laid out the way [`super::hoisted`] would lay it out. This
+//! is the only way to link on wasm32. Install scripts never run here, since
+//! there's nothing to run them with.
+
+use std::path::{Path, PathBuf};
+
+use futures::{AsyncReadExt, StreamExt, TryStreamExt};
+use oro_common::{BuildManifest, Manifest};
+use petgraph::stable_graph::NodeIndex;
+
+use crate::{graph::Graph, NodeMaintainerError, Vfs};
+
+pub(crate) struct VfsLinker<'a> {
+    pub(crate) fs: &'a dyn Vfs,
+    pub(crate) concurrency: usize,
+}
+
+impl VfsLinker<'_> {
+    /// Replaces `node_modules/` in the filesystem with the packages in
+    /// `graph`, and links the bins of the root's dependencies into
+    /// `node_modules/.bin`. Returns how many packages were extracted.
+    pub(crate) async fn extract(&self, graph: &Graph) -> Result<usize, NodeMaintainerError> {
+        let node_modules = Path::new("node_modules");
+        self.fs
+            .remove_all(node_modules)
+            .map_err(|e| NodeMaintainerError::VfsError(node_modules.into(), e))?;
+        let extracted =
+            futures::stream::iter(graph.inner.node_indices().filter(|idx| *idx != graph.root))
+                .map(|idx| async move {
+                    let manifest = self.extract_package(graph, idx).await?;
+                    Ok::<_, NodeMaintainerError>((idx, manifest))
+                })
+                .buffer_unordered(self.concurrency)
+                .try_collect::<Vec<_>>()
+                .await?;
+        for (idx, manifest) in &extracted {
+            if graph[*idx].parent == Some(graph.root) {
+                if let Some(manifest) = manifest {
+                    self.link_bins(graph[*idx].package.name(), manifest)?;
+                }
+            }
+        }
+        Ok(extracted.len())
+    }
+
+    /// Writes the contents of a package's tarball into its directory, and
+    /// returns its `package.json`, if it has a valid one.
+    async fn extract_package(
+        &self,
+        graph: &Graph,
+        idx: NodeIndex,
+    ) -> Result<Option<Manifest>, NodeMaintainerError> {
+        let dir = package_dir(graph, idx);
+        let mut manifest = None;
+        let mut entries = graph[idx].package.entries().await?;
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry?;
+            // Tarball entries all live under a single top-level directory,
+            // usually `package/`, which is left out.
+            let relative = {
+                let path = entry.path()?;
+                let path: &async_std::path::Path = &path;
+                let path: &Path = path.as_ref();
+                path.components().skip(1).collect::<PathBuf>()
+            };
+            let path = dir.join(crate::vfs::normalize(&relative));
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                self.fs
+                    .create_dir_all(&path)
+                    .map_err(|e| NodeMaintainerError::VfsError(path, e))?;
+            } else if entry_type.is_file() {
+                let executable = entry
+                    .header()
+                    .mode()
+                    .map_or(false, |mode| mode & 0o111 != 0);
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).await?;
+                if let Some(parent) = path.parent() {
+                    self.fs
+                        .create_dir_all(parent)
+                        .map_err(|e| NodeMaintainerError::VfsError(parent.into(), e))?;
+                }
+                self.fs
+                    .write_file(&path, &contents, executable)
+                    .map_err(|e| NodeMaintainerError::VfsError(path.clone(), e))?;
+                if relative == Path::new("package.json") {
+                    manifest = serde_json::from_slice(&contents).ok();
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    fn link_bins(&self, name: &str, manifest: &Manifest) -> Result<(), NodeMaintainerError> {
+        // Bins from `directories.bin` can't be found without reading the
+        // directory, so those are skipped.
+        let Ok(build) = BuildManifest::from_manifest(manifest) else {
+            return Ok(());
+        };
+        for (bin, target) in build.bin {
+            let link = Path::new("node_modules").join(".bin").join(&bin);
+            let target = Path::new("..").join(name).join(target);
+            self.fs
+                .symlink(&target, &link)
+                .map_err(|e| NodeMaintainerError::VfsError(link, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a package goes in a hoisted `node_modules/`, relative to the
+/// project root.
+pub(crate) fn package_dir(graph: &Graph, idx: NodeIndex) -> PathBuf {
+    let mut dir = PathBuf::new();
+    for name in graph.node_path(idx) {
+        dir.push("node_modules");
+        dir.push(name.as_str());
+    }
+    dir
+}
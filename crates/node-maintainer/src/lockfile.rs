@@ -1,8 +1,10 @@
+use std::collections::HashSet;
+
 use indexmap::IndexMap;
-use kdl::{KdlDocument, KdlNode};
+use kdl::{KdlDocument, KdlEntry, KdlNode};
 use nassun::{client::Nassun, package::Package, PackageResolution};
 use node_semver::Version;
-use oro_common::CorgiManifest;
+use oro_common::{CorgiManifest, PeerDependencyMeta};
 use oro_package_spec::PackageSpec;
 use serde::{Deserialize, Serialize};
 use ssri::Integrity;
@@ -126,6 +128,136 @@ impl Lockfile {
         }
         inner(pkglock)
     }
+
+    /// Converts this lockfile to an npm `package-lock.json`, using lockfile
+    /// version 3.
+    ///
+    /// The `dev`, `optional`, and `devOptional` flags npm uses to decide
+    /// what to install are worked out from the dependency tree, so the
+    /// result can be used with `npm ci`.
+    pub fn to_npm(&self) -> NpmPackageLock {
+        let not_dev = self.reachable(&[DepType::Prod, DepType::Opt, DepType::Peer], true);
+        let not_optional = self.reachable(&[DepType::Prod, DepType::Dev, DepType::Peer], false);
+        let required = self.reachable(&[DepType::Prod, DepType::Peer], false);
+        let mut packages = IndexMap::new();
+        packages.insert("".into(), self.root.to_npm());
+        let mut sorted = self.packages.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|(path, _)| *path);
+        for (path, node) in sorted {
+            let mut entry = node.to_npm();
+            let path = path.to_string();
+            entry.dev = !not_dev.contains(path.as_str());
+            entry.optional = !not_optional.contains(path.as_str());
+            entry.dev_optional = !entry.dev && !entry.optional && !required.contains(path.as_str());
+            packages.insert(format!("node_modules/{path}"), entry);
+        }
+        NpmPackageLock {
+            name: Some(self.root.name.to_string()).filter(|name| !name.is_empty()),
+            version: self.root.version.as_ref().map(|v| v.to_string()),
+            lockfile_version: Some(3),
+            requires: true,
+            packages,
+        }
+    }
+
+    /// Converts this lockfile to the "hidden" lockfile npm keeps at
+    /// `node_modules/.package-lock.json`, which is the same as
+    /// [`Lockfile::to_npm`] minus the root package.
+    ///
+    /// npm only trusts hidden lockfiles that describe the layout of
+    /// `node_modules/` exactly, so this is only useful for hoisted installs.
+    pub fn to_npm_hidden(&self) -> NpmPackageLock {
+        let mut lock = self.to_npm();
+        lock.packages.shift_remove("");
+        lock
+    }
+
+    /// Paths of all packages reachable from the root through the given
+    /// kinds of root dependencies. Only the root's `devDependencies` are
+    /// ever followed, and `optionalDependencies` are only followed below
+    /// the root if `follow_optional` is set.
+    fn reachable(&self, root_types: &[DepType], follow_optional: bool) -> HashSet<&str> {
+        let mut seen = HashSet::new();
+        let mut queue = vec![("", &self.root, root_types.to_vec())];
+        while let Some((path, node, dep_types)) = queue.pop() {
+            for dep_type in dep_types {
+                for name in node.deps_of_type(&dep_type).keys() {
+                    let Some((dep_path, dep)) = self.find_dep(path, name) else {
+                        continue;
+                    };
+                    if seen.insert(dep_path) {
+                        let mut types = vec![DepType::Prod, DepType::Peer];
+                        if follow_optional {
+                            types.push(DepType::Opt);
+                        }
+                        queue.push((dep_path, dep, types));
+                    }
+                }
+            }
+        }
+        seen
+    }
+
+    /// Describes every package that differs between this lockfile and
+    /// `other`, one line per path.
+    pub(crate) fn differences(&self, other: &Lockfile) -> Vec<String> {
+        fn describe(node: Option<&LockfileNode>) -> String {
+            match node {
+                Some(node) => node
+                    .version
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .or_else(|| node.resolved.clone())
+                    .unwrap_or_else(|| "?".into()),
+                None => "nothing".into(),
+            }
+        }
+        let mut diffs = Vec::new();
+        if self.root != other.root {
+            diffs.push("  root: dependencies differ".into());
+        }
+        let mut paths = self
+            .packages
+            .keys()
+            .chain(other.packages.keys())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        for path in paths {
+            let (ours, theirs) = (self.packages.get(path), other.packages.get(path));
+            if ours == theirs {
+                continue;
+            }
+            let (ours, theirs) = (describe(ours), describe(theirs));
+            if ours == theirs {
+                diffs.push(format!("  {path}: {ours}, with different dependencies"));
+            } else {
+                diffs.push(format!("  {path}: {ours} vs {theirs}"));
+            }
+        }
+        diffs
+    }
+
+    /// Finds the package `name` resolves to from the package at `from`, the
+    /// same way Node.js would: by looking in each `node_modules/` directory
+    /// on the way up to the root.
+    pub(crate) fn find_dep(&self, from: &str, name: &str) -> Option<(&str, &LockfileNode)> {
+        let mut base = from;
+        loop {
+            let candidate = if base.is_empty() {
+                name.to_string()
+            } else {
+                format!("{base}/node_modules/{name}")
+            };
+            if let Some((path, node)) = self.packages.get_key_value(&UniCase::new(candidate)) {
+                return Some((path.as_str(), node));
+            }
+            if base.is_empty() {
+                return None;
+            }
+            base = base.rfind("/node_modules/").map_or("", |idx| &base[..idx]);
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -136,9 +268,11 @@ pub struct LockfileNode {
     pub resolved: Option<String>,
     pub version: Option<Version>,
     pub integrity: Option<Integrity>,
+    pub patch: Option<Integrity>,
     pub dependencies: IndexMap<String, String>,
     pub dev_dependencies: IndexMap<String, String>,
     pub peer_dependencies: IndexMap<String, String>,
+    pub peer_dependencies_meta: IndexMap<String, PeerDependencyMeta>,
     pub optional_dependencies: IndexMap<String, String>,
 }
 
@@ -150,7 +284,9 @@ impl From<LockfileNode> for CorgiManifest {
             dependencies: value.dependencies,
             dev_dependencies: value.dev_dependencies,
             peer_dependencies: value.peer_dependencies,
+            peer_dependencies_meta: value.peer_dependencies_meta,
             optional_dependencies: value.optional_dependencies,
+            dependencies_meta: IndexMap::new(),
             bundled_dependencies: Vec::new(),
         }
     }
@@ -180,6 +316,13 @@ impl LockfileNode {
                 };
                 nassun.resolve_from(self.name.to_string(), spec, resolution)
             }
+            PackageSpec::Link { path } => {
+                let resolution = PackageResolution::Link {
+                    name: self.name.to_string(),
+                    path: path.clone(),
+                };
+                nassun.resolve_from(self.name.to_string(), spec, resolution)
+            }
             PackageSpec::Npm { name, .. } => {
                 let version = if let Some(ref version) = self.version {
                     version
@@ -211,6 +354,18 @@ impl LockfileNode {
                     nassun.resolve(spec.to_string()).await?
                 }
             }
+            PackageSpec::Url { url } => {
+                if self.integrity.is_some() {
+                    let resolution = PackageResolution::Url {
+                        name: self.name.to_string(),
+                        tarball: url.clone(),
+                        integrity: self.integrity.clone(),
+                    };
+                    nassun.resolve_from(self.name.to_string(), spec, resolution)
+                } else {
+                    nassun.resolve(spec.to_string()).await?
+                }
+            }
             PackageSpec::Alias { .. } => {
                 unreachable!("Alias should have already been resolved by the .target() call above.")
             }
@@ -260,20 +415,46 @@ impl LockfileNode {
             .get_arg("resolved")
             .and_then(|resolved| resolved.as_string())
             .map(|resolved| resolved.to_string());
+        let patch = children
+            .get_arg("patch")
+            .and_then(|i| i.as_string())
+            .map(|i| i.parse())
+            .transpose()
+            .map_err(|e| NodeMaintainerError::KdlLockfileIntegrityParseError(node.clone(), e))?;
         Ok(Self {
             name,
             is_root,
             path,
             integrity,
+            patch,
             resolved,
             version,
             dependencies: Self::from_kdl_deps(&children, &DepType::Prod)?,
             dev_dependencies: Self::from_kdl_deps(&children, &DepType::Dev)?,
             optional_dependencies: Self::from_kdl_deps(&children, &DepType::Opt)?,
             peer_dependencies: Self::from_kdl_deps(&children, &DepType::Peer)?,
+            peer_dependencies_meta: Self::from_kdl_peer_meta(&children),
         })
     }
 
+    fn from_kdl_peer_meta(children: &KdlDocument) -> IndexMap<String, PeerDependencyMeta> {
+        let mut meta = IndexMap::new();
+        if let Some(children) = children
+            .get("peer-dependencies")
+            .and_then(|node| node.children())
+        {
+            for dep in children.nodes() {
+                if let Some(optional) = dep.get("optional").and_then(|val| val.as_bool()) {
+                    meta.insert(
+                        dep.name().value().to_string(),
+                        PeerDependencyMeta { optional },
+                    );
+                }
+            }
+        }
+        meta
+    }
+
     fn from_kdl_deps(
         children: &KdlDocument,
         dep_type: &DepType,
@@ -325,6 +506,11 @@ impl LockfileNode {
                 }
             }
         }
+        if let Some(patch) = &self.patch {
+            let mut pnode = KdlNode::new("patch");
+            pnode.push(patch.to_string());
+            kdl_node.ensure_children().nodes_mut().push(pnode);
+        }
         if !self.dependencies.is_empty() {
             kdl_node
                 .ensure_children()
@@ -352,6 +538,52 @@ impl LockfileNode {
         kdl_node
     }
 
+    fn deps_of_type(&self, dep_type: &DepType) -> &IndexMap<String, String> {
+        match dep_type {
+            DepType::Prod => &self.dependencies,
+            DepType::Dev => &self.dev_dependencies,
+            DepType::Peer => &self.peer_dependencies,
+            DepType::Opt => &self.optional_dependencies,
+        }
+    }
+
+    fn to_npm(&self) -> NpmPackageLockEntry {
+        let (resolved, link) = match self.resolved.as_deref() {
+            _ if self.is_root => (None, false),
+            Some(resolved) => match resolved.strip_prefix("link:") {
+                Some(path) => (Some(path.to_string()), true),
+                None if std::path::Path::new(resolved).is_absolute() => {
+                    (Some(format!("file:{resolved}")), false)
+                }
+                None => (Some(resolved.to_string()), false),
+            },
+            None => (None, false),
+        };
+        NpmPackageLockEntry {
+            name: if self.is_root || self.path.last() != Some(&self.name) {
+                Some(self.name.to_string()).filter(|name| !name.is_empty())
+            } else {
+                None
+            },
+            version: self.version.as_ref().map(|v| v.to_string()),
+            resolved,
+            integrity: self.integrity.as_ref().map(|i| i.to_string()),
+            link,
+            dev: false,
+            optional: false,
+            dev_optional: false,
+            dependencies: self.dependencies.clone(),
+            dev_dependencies: if self.is_root {
+                self.dev_dependencies.clone()
+            } else {
+                IndexMap::new()
+            },
+            optional_dependencies: self.optional_dependencies.clone(),
+            peer_dependencies: self.peer_dependencies.clone(),
+            peer_dependencies_meta: self.peer_dependencies_meta.clone(),
+        }
+    }
+
     fn to_kdl_deps(&self, dep_type: &DepType, deps: &IndexMap<String, String>) -> KdlNode {
         use DepType::*;
         let type_name = match dep_type {
@@ -365,6 +597,14 @@ impl LockfileNode {
             let children = deps_node.ensure_children();
             let mut ddnode = KdlNode::new(name.clone());
             ddnode.push(requested.clone());
+            if *dep_type == Peer
+                && self
+                    .peer_dependencies_meta
+                    .get(name)
+                    .map_or(false, |meta| meta.optional)
+            {
+                ddnode.push(KdlEntry::new_prop("optional", true));
+            }
             children.nodes_mut().push(ddnode);
         }
         deps_node
@@ -387,6 +627,7 @@ impl LockfileNode {
             .clone()
             .map(UniCase::new)
             .or_else(|| path.last().cloned())
+            .or_else(|| path_str.is_empty().then(|| UniCase::new("".into())))
             .ok_or_else(|| NodeMaintainerError::NpmLockMissingName(Box::new(npm.clone())))?;
         let integrity = npm
             .integrity
@@ -406,12 +647,14 @@ impl LockfileNode {
             is_root: path.is_empty(),
             path,
             integrity,
+            patch: None,
             resolved: npm.resolved.clone(),
             version,
             dependencies: npm.dependencies.clone(),
             dev_dependencies: npm.dev_dependencies.clone(),
             optional_dependencies: npm.optional_dependencies.clone(),
             peer_dependencies: npm.peer_dependencies.clone(),
+            peer_dependencies_meta: npm.peer_dependencies_meta.clone(),
         })
     }
 }
@@ -419,6 +662,10 @@ impl LockfileNode {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NpmPackageLock {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     #[serde(default)]
     pub lockfile_version: Option<usize>,
     #[serde(default)]
@@ -430,20 +677,119 @@ pub struct NpmPackageLock {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NpmPackageLockEntry {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub resolved: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub integrity: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub link: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dev: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub optional: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dev_optional: bool,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub dependencies: IndexMap<String, String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub dev_dependencies: IndexMap<String, String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub optional_dependencies: IndexMap<String, String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub peer_dependencies: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub peer_dependencies_meta: IndexMap<String, PeerDependencyMeta>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_npm_lockfile() -> Result<(), NodeMaintainerError> {
+        let lock = Lockfile::from_kdl(
+            r#"
+            lockfile-version 1
+            root {
+                dependencies {
+                    a "^1.0.0"
+                }
+                dev-dependencies {
+                    b "^1.0.0"
+                }
+                optional-dependencies {
+                    c "^1.0.0"
+                }
+            }
+            pkg "a" {
+                version "1.0.0"
+                resolved "https://example.com/a-1.0.0.tgz"
+                integrity "sha512-deadbeef"
+                dependencies {
+                    shared "^1.0.0"
+                }
+            }
+            pkg "b" {
+                version "1.0.0"
+                resolved "https://example.com/b-1.0.0.tgz"
+                dependencies {
+                    shared "^2.0.0"
+                }
+            }
+            pkg "b" "shared" {
+                version "2.0.0"
+                resolved "https://example.com/shared-2.0.0.tgz"
+            }
+            pkg "c" {
+                version "1.0.0"
+                resolved "https://example.com/c-1.0.0.tgz"
+            }
+            pkg "shared" {
+                version "1.0.0"
+                resolved "https://example.com/shared-1.0.0.tgz"
+            }
+            pkg "linked" {
+                resolved "link:/src/linked"
+            }
+            "#,
+        )?;
+        let npm = lock.to_npm();
+        assert_eq!(npm.lockfile_version, Some(3));
+        assert!(npm.packages.contains_key(""));
+        let entry = |path: &str| npm.packages[path].clone();
+        let a = entry("node_modules/a");
+        assert!(!a.dev && !a.optional && !a.dev_optional);
+        assert_eq!(a.integrity.as_deref(), Some("sha512-deadbeef"));
+        assert!(entry("node_modules/b").dev);
+        assert!(entry("node_modules/b/node_modules/shared").dev);
+        assert!(!entry("node_modules/shared").dev);
+        assert!(entry("node_modules/c").optional);
+        let linked = entry("node_modules/linked");
+        assert!(linked.link);
+        assert_eq!(linked.resolved.as_deref(), Some("/src/linked"));
+
+        // Round-trips through npm's format.
+        let reparsed = Lockfile::from_npm(serde_json::to_string(&npm)?)?;
+        let mut before = lock.packages().keys().collect::<Vec<_>>();
+        // `from_npm` also keeps npm's root entry among the packages.
+        let mut after = reparsed
+            .packages()
+            .keys()
+            .filter(|path| !path.is_empty())
+            .collect::<Vec<_>>();
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+
+        assert!(!lock.to_npm_hidden().packages.contains_key(""));
+        Ok(())
+    }
 }
@@ -1,28 +1,98 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[cfg(not(target_arch = "wasm32"))]
 use async_std::fs;
-use nassun::client::{Nassun, NassunOpts};
+#[cfg(not(target_arch = "wasm32"))]
+use async_trait::async_trait;
+use indexmap::IndexMap;
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::cache::CacheCounts;
+use nassun::client::{Nassun, NassunOpts, RequestObserver};
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::fetch::GitPreparer;
 use nassun::package::Package;
+use nassun::Credentials;
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::NassunError;
+use nassun::PackageSource;
+use nassun::SignatureVerification;
+use nassun::TarballRewrite;
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::TlsConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use oro_common::BuildManifest;
 use oro_common::CorgiManifest;
+#[cfg(not(target_arch = "wasm32"))]
+use oro_script::OroScript;
 use url::Url;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::access::AccessTracker;
+use crate::catalogs::Catalogs;
+use crate::conflict::PeerProblem;
+use crate::deprecated::Deprecation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dry_run::{DryRun, DryRunReport};
 use crate::error::NodeMaintainerError;
-use crate::graph::{Graph, Node};
-use crate::linkers::Linker;
+use crate::events::InstallEvent;
+use crate::explain::ResolutionExplanation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::extract_progress::ExtractByteCounter;
+use crate::extract_progress::ExtractBytes;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::funding::FundingInfo;
+use crate::graph::{DependencyCycle, Graph, Node};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::hook_scripts::HookScriptFilter;
+use crate::hook_scripts::{HookScripts, DEFAULT_HOOK_SCRIPT_PATTERNS};
+use crate::link_strategy::LinkStrategy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::linkers::{
+    ExtractPipeline, GlobalStore, LinkerOptions, ScriptResults, ScriptScheduler, ScriptTimeouts,
+};
+use crate::linkers::{Linker, VfsLinker};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::node_linker::ForeignLayout;
+use crate::node_linker::NodeLinker;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::nohoist;
+use crate::patches::Patches;
+use crate::policy::{Policy, PolicyViolation};
+use crate::resolution_mode::ResolutionMode;
+use crate::resolver::{Resolver, Shuffle};
+use crate::script_output::ScriptOutput;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::timings::{TimingCounters, Timings};
+use crate::tree::TreePackage;
+use crate::trusted::AllowScripts;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::trusted::{BlockedScripts, ScriptTrust};
+use crate::vfs::Vfs;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::linkers::LinkerOptions;
-use crate::resolver::Resolver;
+use crate::virtual_install::VirtualInstall;
+use crate::workspaces::Workspaces;
 use crate::{IntoKdl, Lockfile};
 
 pub const DEFAULT_CONCURRENCY: usize = 50;
+pub const DEFAULT_PIPELINE_DEPTH: usize = 100;
 pub const DEFAULT_SCRIPT_CONCURRENCY: usize = 6;
+pub const DEFAULT_PRUNE_KEEP_PATTERNS: &[&str] = &[".cache", ".vite"];
+pub const DEFAULT_PUBLIC_HOIST_PATTERNS: &[&str] = &["eslint*", "*prettier*"];
+pub const DEFAULT_HOIST_PATTERNS: &[&str] = &["*"];
 pub const META_FILE_NAME: &str = ".orogene-meta.kdl";
 pub const STORE_DIR_NAME: &str = ".oro-store";
+pub const PNP_FILE_NAME: &str = ".pnp.cjs";
+pub const PNP_DATA_FILE_NAME: &str = ".pnp.data.json";
 
 pub type ProgressAdded = Arc<dyn Fn() + Send + Sync>;
 pub type ProgressHandler = Arc<dyn Fn(&Package) + Send + Sync>;
+pub type ExtractBytesHandler = Arc<dyn Fn(&Package, &ExtractBytes) + Send + Sync>;
 pub type PruneProgress = Arc<dyn Fn(&Path) + Send + Sync>;
 pub type ScriptStartHandler = Arc<dyn Fn(&Package, &str) + Send + Sync>;
 pub type ScriptLineHandler = Arc<dyn Fn(&str) + Send + Sync>;
@@ -30,23 +100,98 @@ pub type ScriptLineHandler = Arc<dyn Fn(&str) + Send + Sync>;
 #[derive(Clone)]
 pub struct NodeMaintainerOptions {
     nassun_opts: NassunOpts,
+    nassun: Option<Nassun>,
     concurrency: usize,
     locked: bool,
+    dedupe: bool,
+    force: bool,
+    resolution_mode: ResolutionMode,
+    explain_resolution: bool,
+    release_cutoff: bool,
     kdl_lock: Option<Lockfile>,
     npm_lock: Option<Lockfile>,
+    policy: Option<Policy>,
+    report_policy_violations: bool,
+    require_provenance: Vec<String>,
 
+    #[allow(dead_code)]
+    node_linker: Option<NodeLinker>,
     #[allow(dead_code)]
     hoisted: bool,
     #[allow(dead_code)]
-    script_concurrency: usize,
+    pnp: bool,
+    #[allow(dead_code)]
+    global_store: bool,
+    #[allow(dead_code)]
+    side_effects_cache: bool,
+    #[allow(dead_code)]
+    strict_bins: bool,
+    #[allow(dead_code)]
+    bin_symlinks: bool,
+    #[allow(dead_code)]
+    dry_run: bool,
+    #[allow(dead_code)]
+    virtual_install: bool,
+    #[allow(dead_code)]
+    prune_keep: Vec<String>,
+    #[allow(dead_code)]
+    public_hoist_patterns: Vec<String>,
+    #[allow(dead_code)]
+    global_bin_dir: Option<PathBuf>,
+    #[allow(dead_code)]
+    takeover: bool,
+    #[allow(dead_code)]
+    script_concurrency: Option<usize>,
+    #[allow(dead_code)]
+    pipeline_depth: usize,
+    #[allow(dead_code)]
+    pipeline_concurrency: Option<usize>,
+    shuffle_seed: Option<u64>,
     #[allow(dead_code)]
     cache: Option<PathBuf>,
     #[allow(dead_code)]
     prefer_copy: bool,
     #[allow(dead_code)]
+    link_strategies: Vec<LinkStrategy>,
+    #[allow(dead_code)]
     validate: bool,
     #[allow(dead_code)]
+    repair: bool,
+    #[allow(dead_code)]
     root: Option<PathBuf>,
+    #[allow(dead_code)]
+    least_privilege: bool,
+    #[allow(dead_code)]
+    hook_scripts: HookScripts,
+    #[allow(dead_code)]
+    hook_script_patterns: Vec<String>,
+    #[allow(dead_code)]
+    allow_scripts: AllowScripts,
+    #[allow(dead_code)]
+    sandbox_scripts: bool,
+    #[allow(dead_code)]
+    sandbox_exempt: Vec<String>,
+    #[allow(dead_code)]
+    script_timeout: Option<Duration>,
+    #[allow(dead_code)]
+    total_script_timeout: Option<Duration>,
+    #[allow(dead_code)]
+    script_config: Vec<(String, String)>,
+    #[allow(dead_code)]
+    script_shell: Option<PathBuf>,
+    #[allow(dead_code)]
+    foreground_scripts: bool,
+    #[allow(dead_code)]
+    force_rebuild: bool,
+    #[allow(dead_code)]
+    patched_dependencies: IndexMap<String, PathBuf>,
+    catalog: IndexMap<String, String>,
+    #[allow(dead_code)]
+    prefer_workspace_packages: bool,
+    #[allow(dead_code)]
+    link_workspaces: bool,
+    nohoist: Vec<String>,
+    hoist_patterns: Vec<String>,
 
     // Intended for progress bars
     on_resolution_added: Option<ProgressAdded>,
@@ -56,9 +201,13 @@ pub struct NodeMaintainerOptions {
     #[allow(dead_code)]
     on_extract_progress: Option<ProgressHandler>,
     #[allow(dead_code)]
+    on_extract_bytes: Option<ExtractBytesHandler>,
+    #[allow(dead_code)]
     on_script_start: Option<ScriptStartHandler>,
     #[allow(dead_code)]
     on_script_line: Option<ScriptLineHandler>,
+    #[allow(dead_code)]
+    script_output: Arc<ScriptOutput>,
 }
 
 impl NodeMaintainerOptions {
@@ -75,6 +224,32 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// Keep fetched packuments in memory, so they're only fetched once per
+    /// [`Nassun`]. See [`NodeMaintainerOptions::nassun`] for keeping them
+    /// across resolutions.
+    pub fn memoize_metadata(mut self, memoize: bool) -> Self {
+        self.nassun_opts = self.nassun_opts.memoize_metadata(memoize);
+        self
+    }
+
+    /// Resolve with `nassun` instead of building a new [`Nassun`] from the
+    /// other options. Use [`NodeMaintainerOptions::build_nassun`] to get one
+    /// with this builder's options, and pass it to every resolution that
+    /// should share its memoized metadata, like in a long-running process.
+    ///
+    /// This isn't used for [`ResolutionMode::Lowest`] resolutions, or for
+    /// the direct dependencies of [`ResolutionMode::LowestDirect`] ones,
+    /// which need a `Nassun` that prefers the lowest versions.
+    pub fn nassun(mut self, nassun: Nassun) -> Self {
+        self.nassun = Some(nassun);
+        self
+    }
+
+    /// Builds the [`Nassun`] that resolutions with these options would use.
+    pub fn build_nassun(&self) -> Nassun {
+        self.nassun_client(false)
+    }
+
     /// Controls number of concurrent operations during various apply steps
     /// (resolution fetches, extractions, etc). Tuning this might help reduce
     /// memory usage.
@@ -90,11 +265,102 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// After resolving, merge duplicate copies of packages into a single
+    /// copy higher up in the tree wherever one version satisfies everything
+    /// that depends on it, to keep `node_modules/` as small as possible.
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Go ahead with the install even if some packages' peer dependencies
+    /// conflict with what's in the tree, logging a warning for each conflict
+    /// instead of failing.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Skip package versions published less than `age` ago when resolving
+    /// version ranges, as a guard against freshly-published malicious
+    /// releases. Versions pinned by the lockfile aren't affected.
+    pub fn minimum_release_age(mut self, age: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.minimum_release_age(age);
+        self.release_cutoff = true;
+        self
+    }
+
+    /// Skip package versions published after `time` when resolving version
+    /// ranges, to get the tree that would've been resolved back then.
+    /// Versions pinned by the lockfile aren't affected.
+    pub fn before(mut self, time: SystemTime) -> Self {
+        self.nassun_opts = self.nassun_opts.before(time);
+        self.release_cutoff = true;
+        self
+    }
+
+    /// Check registry signatures over package integrities before fetching
+    /// tarballs, and either warn about or refuse packages that can't be
+    /// verified. Defaults to [`SignatureVerification::Off`].
+    pub fn verify_signatures(mut self, verify: SignatureVerification) -> Self {
+        self.nassun_opts = self.nassun_opts.verify_signatures(verify);
+        self
+    }
+
+    /// Which version to pick when resolving semver ranges. Only affects
+    /// packages that aren't already pinned by the lockfile.
+    pub fn resolution_mode(mut self, mode: ResolutionMode) -> Self {
+        self.resolution_mode = mode;
+        self
+    }
+
+    /// When this is true, the resolver keeps track of how it came to pick
+    /// each package's version, so [`NodeMaintainer::explain`] can say why.
+    pub fn explain_resolution(mut self, explain: bool) -> Self {
+        self.explain_resolution = explain;
+        self
+    }
+
     /// Controls number of concurrent script executions while running
     /// `run_script`. This option is separate from `concurrency` because
     /// executing concurrent scripts is a much heavier operation.
+    ///
+    /// By default, this adapts to the machine: scripts get started based on
+    /// how many CPUs there are, how busy they are, and how much memory is
+    /// left, and native builds, like `node-gyp rebuild`, are held back more
+    /// than other scripts. Setting this uses a fixed limit instead.
     pub fn script_concurrency(mut self, concurrency: usize) -> Self {
-        self.script_concurrency = concurrency;
+        self.script_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Maximum number of resolved packages that can be waiting to be
+    /// extracted while the rest of the dependency tree is still resolving.
+    /// Packages that don't fit are extracted after resolution, as usual.
+    ///
+    /// This is `0` (off) by default, since it only pays off when
+    /// [`NodeMaintainer::extract`] gets called afterwards.
+    /// [`DEFAULT_PIPELINE_DEPTH`] is a reasonable value otherwise.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pipeline_depth(mut self, depth: usize) -> Self {
+        self.pipeline_depth = depth;
+        self
+    }
+
+    /// Controls how many packages can be extracted at once while the
+    /// dependency tree is still resolving. Defaults to `concurrency`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pipeline_concurrency(mut self, concurrency: usize) -> Self {
+        self.pipeline_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Shuffle the order fetched package metadata gets processed in during
+    /// resolution, using the given seed. This should never change the
+    /// resolved tree, and is mostly useful for checking that it doesn't. See
+    /// [`NodeMaintainerOptions::check_determinism`].
+    pub fn shuffle_seed(mut self, seed: u64) -> Self {
+        self.shuffle_seed = Some(seed);
         self
     }
 
@@ -118,6 +384,36 @@ impl NodeMaintainerOptions {
         Ok(self)
     }
 
+    /// Configure the package policy that NodeMaintainer will enforce. See
+    /// [`Policy`] for the format.
+    ///
+    /// If this option is not specified, NodeMaintainer will try to read the
+    /// policy from `<root>/oro-policy.kdl`.
+    pub fn policy(mut self, policy: impl IntoKdl) -> Result<Self, NodeMaintainerError> {
+        self.policy = Some(Policy::from_kdl(policy)?);
+        Ok(self)
+    }
+
+    /// When this is true, packages that break the project's policy, or
+    /// don't have the provenance they're required to, don't fail
+    /// resolution. Every violation gets recorded instead, and can be listed
+    /// with [`NodeMaintainer::policy_violations`].
+    pub fn report_policy_violations(mut self, report: bool) -> Self {
+        self.report_policy_violations = report;
+        self
+    }
+
+    /// Adds a pattern for registry packages that have to come with a
+    /// provenance attestation, like `@my-org/*`. Patterns are globs matched
+    /// against package names, and ones starting with `!` exclude packages
+    /// matched by earlier ones. Installs fail if any matching package has
+    /// no provenance, or has provenance that isn't about it. See
+    /// [`nassun::provenance`] for what's checked.
+    pub fn require_provenance(mut self, pattern: impl AsRef<str>) -> Self {
+        self.require_provenance.push(pattern.as_ref().into());
+        self
+    }
+
     /// Registry used for unscoped packages.
     ///
     /// Defaults to https://registry.npmjs.org.
@@ -134,6 +430,181 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// Custom [`PackageSource`] to fetch all packages in a given `@scope`
+    /// from, instead of a registry, like an internal artifact system. This
+    /// option can be provided multiple times.
+    pub fn scope_source(mut self, scope: impl AsRef<str>, source: Arc<dyn PackageSource>) -> Self {
+        self.nassun_opts = self.nassun_opts.scope_source(scope, source);
+        self
+    }
+
+    /// Mirror of the default registry, to fetch packuments and tarballs
+    /// from when the registry fails with a server error or times out.
+    /// Mirrors are tried in the order they're added. This option can be
+    /// provided multiple times.
+    pub fn mirror(mut self, mirror: Url) -> Self {
+        self.nassun_opts = self.nassun_opts.mirror(mirror);
+        self
+    }
+
+    /// Observer to report every registry request to, with its timing,
+    /// retries, byte count, and whether it was served from the cache.
+    pub fn request_observer(mut self, observer: Arc<dyn RequestObserver>) -> Self {
+        self.nassun_opts = self.nassun_opts.request_observer(observer);
+        self
+    }
+
+    /// Rule for rewriting the tarball URLs registries hand out before
+    /// fetching them, like pointing upstream URLs at an artifact proxy. The
+    /// first matching rule applies. This option can be provided multiple
+    /// times.
+    pub fn tarball_rewrite(mut self, rewrite: TarballRewrite) -> Self {
+        self.nassun_opts = self.nassun_opts.tarball_rewrite(rewrite);
+        self
+    }
+
+    /// Credentials to authenticate requests to registries on `host` with,
+    /// like `registry.corp.example.com`. This option can be provided
+    /// multiple times.
+    pub fn credentials(mut self, host: impl AsRef<str>, credentials: Credentials) -> Self {
+        self.nassun_opts = self.nassun_opts.credentials(host, credentials);
+        self
+    }
+
+    /// Sends the credentials for the registry on `host` with requests for
+    /// its packages' tarballs, even when they're hosted somewhere else.
+    /// This option can be provided multiple times.
+    pub fn always_auth(mut self, host: impl AsRef<str>) -> Self {
+        self.nassun_opts = self.nassun_opts.always_auth(host);
+        self
+    }
+
+    /// Proxy to send registry requests through, and HTTPS ones too unless
+    /// [`NodeMaintainerOptions::https_proxy`] is set. Defaults to the
+    /// `HTTP_PROXY` environment variable.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: Url) -> Self {
+        self.nassun_opts = self.nassun_opts.proxy(proxy);
+        self
+    }
+
+    /// Proxy to send HTTPS registry requests through. Defaults to the
+    /// `HTTPS_PROXY` environment variable.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn https_proxy(mut self, proxy: Url) -> Self {
+        self.nassun_opts = self.nassun_opts.https_proxy(proxy);
+        self
+    }
+
+    /// Comma-separated hosts to connect to without a proxy. Defaults to the
+    /// `NO_PROXY` environment variable.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn no_proxy(mut self, hosts: impl AsRef<str>) -> Self {
+        self.nassun_opts = self.nassun_opts.no_proxy(hosts);
+        self
+    }
+
+    /// TLS settings for connecting to registries, like CA certificates to
+    /// trust for private registries, client certificates for ones that
+    /// require mutual TLS, or turning off certificate checks.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.nassun_opts = self.nassun_opts.tls(tls);
+        self
+    }
+
+    /// TLS settings for connecting to registries on `host` in particular.
+    /// This option can be provided multiple times.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn host_tls(mut self, host: impl AsRef<str>, tls: TlsConfig) -> Self {
+        self.nassun_opts = self.nassun_opts.host_tls(host, tls);
+        self
+    }
+
+    /// Most bytes per second to download, shared between all concurrent
+    /// downloads, so installs don't saturate links shared with others.
+    /// Unlimited by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn network_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.nassun_opts = self.nassun_opts.network_limit(bytes_per_sec);
+        self
+    }
+
+    /// Most registry requests to have open at once, so installs don't
+    /// overwhelm proxies that can't take many connections. Unlimited by
+    /// default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_sockets(mut self, max: usize) -> Self {
+        self.nassun_opts = self.nassun_opts.max_sockets(max);
+        self
+    }
+
+    /// Most registry requests to have open at once to any one host.
+    /// Unlimited by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_sockets_per_host(mut self, max: usize) -> Self {
+        self.nassun_opts = self.nassun_opts.max_sockets_per_host(max);
+        self
+    }
+
+    /// How long to keep idle registry connections around for reuse.
+    /// Defaults to 90 seconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.pool_idle_timeout(timeout);
+        self
+    }
+
+    /// How long to wait for a TCP connection to a registry to be established.
+    /// Defaults to 10 seconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.connect_timeout(timeout);
+        self
+    }
+
+    /// How long to wait for the TLS handshake with a registry to finish.
+    /// Defaults to 10 seconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.tls_handshake_timeout(timeout);
+        self
+    }
+
+    /// How long to wait for a registry to start responding, and how long a
+    /// tarball download can go without receiving data before it's resumed.
+    /// Defaults to 30 seconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.first_byte_timeout(timeout);
+        self
+    }
+
+    /// Longest a metadata request, like a packument fetch, can take, retries
+    /// included. Tarball downloads aren't limited by this. Defaults to 5
+    /// minutes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.request_timeout(timeout);
+        self
+    }
+
+    /// How long to remember registry hosts' addresses once they've been
+    /// looked up. Defaults to 5 minutes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dns_ttl(mut self, ttl: Duration) -> Self {
+        self.nassun_opts = self.nassun_opts.dns_ttl(ttl);
+        self
+    }
+
+    /// Map big files into memory instead of reading them. See
+    /// [`nassun::NassunOpts::mmap`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.nassun_opts = self.nassun_opts.mmap(mmap);
+        self
+    }
+
     /// Root directory of the project.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn root(mut self, path: impl AsRef<Path>) -> Self {
@@ -153,13 +624,38 @@ impl NodeMaintainerOptions {
     ///
     /// This option has no effect if hard linking fails (for example, if the
     /// cache is on a different drive), or if the project is on a filesystem
-    /// that supports Copy-on-Write (zfs, btrfs, APFS (macOS), etc).
+    /// that supports Copy-on-Write (zfs, btrfs, APFS (macOS), etc). It's
+    /// the same as leaving [`LinkStrategy::Hardlink`] out of
+    /// [`NodeMaintainerOptions::link_strategies`].
     #[cfg(not(target_arch = "wasm32"))]
     pub fn prefer_copy(mut self, prefer_copy: bool) -> Self {
         self.prefer_copy = prefer_copy;
         self
     }
 
+    /// The ways to try getting files out of the cache and into
+    /// `node_modules/`, in order of preference. The first one that works
+    /// between the cache's volume and the project's gets used, and copying
+    /// is always the last resort. Which ones work is remembered in the
+    /// cache, so each pair of volumes only gets probed once. Defaults to
+    /// [`LinkStrategy::DEFAULT_CHAIN`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn link_strategies(mut self, strategies: impl IntoIterator<Item = LinkStrategy>) -> Self {
+        self.link_strategies = strategies.into_iter().collect();
+        self
+    }
+
+    /// The configured link strategies, minus hard links with
+    /// [`NodeMaintainerOptions::prefer_copy`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn link_strategy_chain(&self) -> Vec<LinkStrategy> {
+        self.link_strategies
+            .iter()
+            .copied()
+            .filter(|strategy| !self.prefer_copy || *strategy != LinkStrategy::Hardlink)
+            .collect()
+    }
+
     /// When this is true, node-maintainer will validate integrity hashes for
     /// all files extracted from the cache, as well as verify that any files
     /// in the existing `node_modules` are unmodified. If verification fails,
@@ -170,16 +666,385 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// When this is true, [`NodeMaintainer::prune`] also hashes the files of
+    /// every package already in `node_modules/`, and compares them against
+    /// what the cache recorded when the package was first extracted. Any
+    /// package with missing or modified files is removed, so
+    /// [`NodeMaintainer::extract`] installs it again. Local and patched
+    /// packages aren't checked.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Which layout to install dependencies with. When this isn't set (and
+    /// neither is [`NodeMaintainerOptions::hoisted`] or
+    /// [`NodeMaintainerOptions::pnp`]), whatever layout `node_modules/`
+    /// already uses is kept, so switching between them never leaves a mix
+    /// of both behind. New projects use [`NodeLinker::Isolated`].
+    pub fn node_linker(mut self, node_linker: Option<NodeLinker>) -> Self {
+        self.node_linker = node_linker;
+        self
+    }
+
     /// Use the hoisted installation mode, where all dependencies and their
     /// transitive dependencies are installed as high up in the `node_modules`
     /// tree as possible. This can potentially mean that packages have access
     /// to dependencies they did not specify in their package.json, but it
     /// might be useful for compatibility.
+    ///
+    /// This takes precedence over [`NodeMaintainerOptions::node_linker`].
     pub fn hoisted(mut self, hoisted: bool) -> Self {
         self.hoisted = hoisted;
         self
     }
 
+    /// Use Plug'n'Play instead of materializing `node_modules/`. Packages
+    /// are extracted once into a store inside the cache, and a `.pnp.cjs`
+    /// file is written to the project root that tells Node.js where to find
+    /// each package's dependencies. Packages that can't be used from the
+    /// store, like ones with install scripts, are extracted into
+    /// `node_modules/.oro-unplugged/` instead. This takes precedence over
+    /// [`NodeMaintainerOptions::hoisted`] and
+    /// [`NodeMaintainerOptions::node_linker`].
+    pub fn pnp(mut self, pnp: bool) -> Self {
+        self.pnp = pnp;
+        self
+    }
+
+    /// Install packages from a global store inside the cache, pnpm-style.
+    /// Each package is extracted into the store once, and its files are
+    /// hard linked into `node_modules/` from there, so identical packages
+    /// across projects only take up disk space once. Packages with install
+    /// scripts are copied instead, so they can't modify the shared files.
+    /// This has no effect without a cache.
+    pub fn global_store(mut self, global_store: bool) -> Self {
+        self.global_store = global_store;
+        self
+    }
+
+    /// Snapshot packages into the cache after their install scripts run,
+    /// and restore those snapshots on later installs instead of running the
+    /// scripts again, which mostly saves on rebuilding native addons.
+    /// Snapshots are specific to the OS, architecture, and Node.js ABI
+    /// version they were taken on. This has no effect without a cache, or
+    /// when `node` can't be found.
+    pub fn side_effects_cache(mut self, side_effects_cache: bool) -> Self {
+        self.side_effects_cache = side_effects_cache;
+        self
+    }
+
+    /// When more than one package links a bin with the same name into the
+    /// same `.bin/` directory, direct dependencies win over transitive ones,
+    /// and ties go to whichever package name sorts first. Collisions are
+    /// warned about by default. When this is true, they're an error
+    /// instead.
+    pub fn strict_bins(mut self, strict_bins: bool) -> Self {
+        self.strict_bins = strict_bins;
+        self
+    }
+
+    /// On Windows, bins normally get `sh`, `.cmd`, and `.ps1` shims. When
+    /// this is true, the `sh` shim is replaced with a real symlink to the
+    /// bin wherever symlinks can be created, which requires Developer Mode
+    /// or administrator privileges. This has no effect on other platforms,
+    /// where bins are always symlinked.
+    pub fn bin_symlinks(mut self, bin_symlinks: bool) -> Self {
+        self.bin_symlinks = bin_symlinks;
+        self
+    }
+
+    /// When this is true, [`NodeMaintainer::prune`],
+    /// [`NodeMaintainer::extract`], and [`NodeMaintainer::rebuild`] work out
+    /// what they would remove, extract, and link, without touching
+    /// `node_modules/`. Lifecycle scripts are never run. What would've been
+    /// done is available from [`NodeMaintainer::dry_run_report`].
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// When this is true, nothing is ever written to disk:
+    /// [`NodeMaintainer::prune`], [`NodeMaintainer::extract`], and
+    /// [`NodeMaintainer::rebuild`] do nothing, and no tarballs get
+    /// downloaded. Use [`NodeMaintainer::virtual_install`] to see what the
+    /// install would look like instead. Handy for testing resolution and
+    /// layout without a scratch directory.
+    pub fn virtual_install(mut self, virtual_install: bool) -> Self {
+        self.virtual_install = virtual_install;
+        self
+    }
+
+    /// When this is true, a `node_modules/` installed by another package
+    /// manager gets its bookkeeping removed and is pruned away entirely, so
+    /// it can be installed again from scratch. Otherwise,
+    /// [`NodeMaintainer::prune`] refuses to touch it, rather than leave a
+    /// mix of both layouts behind. See [`crate::ForeignLayout`].
+    pub fn takeover(mut self, takeover: bool) -> Self {
+        self.takeover = takeover;
+        self
+    }
+
+    /// Glob patterns for paths inside `node_modules/` that pruning should
+    /// never remove, such as caches other tools keep there. Patterns are
+    /// relative to `node_modules/` and use `/` separators, `*` matches any
+    /// sequence of characters, and matching a directory keeps everything
+    /// in it. Bins linked by hand into a `.bin/` directory can be kept with
+    /// patterns like `.bin/my-tool`. Defaults to
+    /// [`DEFAULT_PRUNE_KEEP_PATTERNS`].
+    pub fn prune_keep(mut self, patterns: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.prune_keep = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Glob patterns for package names that the isolated linker should also
+    /// link into the root `node_modules/`, no matter how deep in the tree
+    /// they are, so tools that look for them there keep working. Patterns
+    /// starting with `!` exclude packages, and later patterns win over
+    /// earlier ones. Other linkers ignore this. Defaults to
+    /// [`DEFAULT_PUBLIC_HOIST_PATTERNS`].
+    pub fn public_hoist_patterns(
+        mut self,
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        self.public_hoist_patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// When this is true, extraction and linking will refuse to modify
+    /// anything outside of `node_modules/` and the cache, and will only
+    /// change file permissions where strictly needed (making bins
+    /// executable). Any out-of-scope access is reported as an error.
+    ///
+    /// Note that this doesn't apply to lifecycle scripts, which can do
+    /// whatever they want. Consider disabling them as well.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn least_privilege(mut self, least_privilege: bool) -> Self {
+        self.least_privilege = least_privilege;
+        self
+    }
+
+    /// Controls whether lifecycle scripts that set up git hooks (`husky
+    /// install`, `simple-git-hooks`, etc) get run. By default, they're
+    /// skipped in CI and when the project isn't inside a git repository.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn hook_scripts(mut self, hook_scripts: HookScripts) -> Self {
+        self.hook_scripts = hook_scripts;
+        self
+    }
+
+    /// Patterns used to recognize git hook scripts, matched against the
+    /// script's command. `*` matches any sequence of characters. Defaults to
+    /// [`DEFAULT_HOOK_SCRIPT_PATTERNS`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn hook_script_patterns(
+        mut self,
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        self.hook_script_patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Controls which dependencies get to run their install scripts. By
+    /// default, only the project's direct dependencies, and the packages
+    /// listed under `trustedDependencies` in its `package.json`, do. Skipped
+    /// scripts are available from [`NodeMaintainer::blocked_scripts`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn allow_scripts(mut self, allow_scripts: AllowScripts) -> Self {
+        self.allow_scripts = allow_scripts;
+        self
+    }
+
+    /// When this is true, install scripts run in a sandbox that only lets
+    /// them write to their own package directory and the temporary
+    /// directory, and keeps them off the network. This needs `bwrap` on
+    /// Linux, and isn't supported on Windows yet. See
+    /// [`oro_script::Sandbox`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sandbox_scripts(mut self, sandbox_scripts: bool) -> Self {
+        self.sandbox_scripts = sandbox_scripts;
+        self
+    }
+
+    /// Names of packages whose install scripts run outside the sandbox,
+    /// even when [`NodeMaintainerOptions::sandbox_scripts`] is enabled, such
+    /// as ones that need to download something while building.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sandbox_exempt(mut self, names: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.sandbox_exempt = names
+            .into_iter()
+            .map(|name| name.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Kills any install script still running after `timeout`, along with
+    /// everything it started. Optional dependencies whose scripts time out
+    /// get skipped, while anything else fails the install. By default,
+    /// scripts can run for as long as they like.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn script_timeout(mut self, timeout: Duration) -> Self {
+        self.script_timeout = Some(timeout);
+        self
+    }
+
+    /// Like [`NodeMaintainerOptions::script_timeout`], but for all of an
+    /// install's scripts together, counted from when the first one starts.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn total_script_timeout(mut self, timeout: Duration) -> Self {
+        self.total_script_timeout = Some(timeout);
+        self
+    }
+
+    /// Passes a configuration value on to install scripts, as an
+    /// `npm_config_<key>` environment variable, the way npm passes its
+    /// effective configuration along. Scripts also always get the
+    /// `npm_package_*`, `npm_lifecycle_event`, and `INIT_CWD` variables npm
+    /// sets.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn script_config(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.script_config
+            .push((key.as_ref().into(), value.as_ref().into()));
+        self
+    }
+
+    /// Shell to run install scripts in, like `bash`, `pwsh`, or the path to
+    /// any other interpreter that takes a command with `-c`. Defaults to
+    /// `sh`, or `%ComSpec%` on Windows.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn script_shell(mut self, shell: impl AsRef<Path>) -> Self {
+        self.script_shell = Some(shell.as_ref().into());
+        self
+    }
+
+    /// Runs install scripts in the foreground, one at a time, connected
+    /// directly to this process's stdin, stdout, and stderr, so scripts that
+    /// prompt for input work. Their output isn't passed to
+    /// [`NodeMaintainerOptions::on_script_line`] in this mode.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn foreground_scripts(mut self, foreground: bool) -> Self {
+        self.foreground_scripts = foreground;
+        self
+    }
+
+    /// Runs every install script, even for packages whose scripts already
+    /// ran. Normally, which packages ran their scripts is recorded in the
+    /// project's `.oro/` directory, and packages that are still installed
+    /// exactly as they were, with the same OS, architecture, and Node.js
+    /// ABI version, don't run them again.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn force_rebuild(mut self, force: bool) -> Self {
+        self.force_rebuild = force;
+        self
+    }
+
+    /// Patch to apply to a package right after it's extracted, as a path to
+    /// a unified diff file, relative to the project root. `spec` is either a
+    /// package name, or a `<name>@<semver range>` to only patch some
+    /// versions. This option can be provided multiple times.
+    ///
+    /// Patches listed under `patchedDependencies` in the project's
+    /// `package.json` are also applied, and entries given here override
+    /// them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn patched_dependency(mut self, spec: impl AsRef<str>, patch: impl AsRef<Path>) -> Self {
+        self.patched_dependencies
+            .insert(spec.as_ref().into(), patch.as_ref().into());
+        self
+    }
+
+    /// Adds an entry to the project's default dependency catalog, which
+    /// `catalog:` dependency specs refer to. Catalogs listed under `catalog`
+    /// and `catalogs` in the project's `package.json` are used as well, but
+    /// entries added here take precedence over them.
+    pub fn catalog(mut self, name: impl AsRef<str>, spec: impl AsRef<str>) -> Self {
+        self.catalog
+            .insert(name.as_ref().into(), spec.as_ref().into());
+        self
+    }
+
+    /// Resolve plain semver ranges to the project's own workspace packages
+    /// (listed under `workspaces` in its `package.json`) whenever their
+    /// version satisfies the range, instead of fetching the package from the
+    /// registry. This is how pnpm links packages in a monorepo.
+    pub fn prefer_workspace_packages(mut self, prefer: bool) -> Self {
+        self.prefer_workspace_packages = prefer;
+        self
+    }
+
+    /// Installs every workspace package (listed under `workspaces` in the
+    /// project's `package.json`) into the root `node_modules/`, as a
+    /// symlink to the package itself, and links its bins into
+    /// `node_modules/.bin`. Their own dependencies get installed as usual.
+    /// Defaults to true.
+    pub fn link_workspaces(mut self, link: bool) -> Self {
+        self.link_workspaces = link;
+        self
+    }
+
+    /// Adds a pattern for dependencies that shouldn't be hoisted past the
+    /// workspace package that pulls them in, like the patterns under
+    /// `workspaces.nohoist` in the project's `package.json` (which are
+    /// used as well). Patterns are matched against the chain of package
+    /// names leading to a dependency, like `my-app/react-native`, where
+    /// `**` matches any number of packages. Workspace packages can declare
+    /// patterns of their own, relative to themselves.
+    ///
+    /// This only changes how packages are laid out in the hoisted
+    /// `node_modules/`.
+    pub fn nohoist(mut self, pattern: impl AsRef<str>) -> Self {
+        self.nohoist.push(pattern.as_ref().into());
+        self
+    }
+
+    /// Installs into the global prefix at `prefix`, npm-style, instead of
+    /// into a project. Packages go in `<prefix>/lib/node_modules` and their
+    /// bins in `<prefix>/bin` (`<prefix>/node_modules` and `<prefix>` on
+    /// Windows). See [`global_root`](crate::global_root) and
+    /// [`global_bin_dir`](crate::global_bin_dir).
+    ///
+    /// This replaces any [`NodeMaintainerOptions::root`], and always uses
+    /// the hoisted layout, with each package's dependencies kept inside of
+    /// it so global packages don't leak into each other. Use
+    /// [`global_manifest`](crate::global_manifest) to find out what's
+    /// already installed there.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn global(mut self, prefix: impl AsRef<Path>) -> Self {
+        let prefix = prefix.as_ref();
+        self.root = Some(crate::global_root(prefix));
+        self.global_bin_dir = Some(crate::global_bin_dir(prefix));
+        self.node_linker = Some(NodeLinker::Hoisted);
+        self.hoisted = true;
+        self.pnp = false;
+        self.hoist_patterns = Vec::new();
+        self
+    }
+
+    /// Glob patterns for package names that can be hoisted all the way up
+    /// to the root `node_modules/` by the hoisted linker. Anything else is
+    /// kept inside the top-level package that depends on it, so it can't
+    /// be required from the project itself unless the project depends on it
+    /// directly. Patterns starting with `!` exclude packages, and later
+    /// patterns win over earlier ones. Defaults to
+    /// [`DEFAULT_HOIST_PATTERNS`], which hoists everything.
+    pub fn hoist_patterns(mut self, patterns: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.hoist_patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_string())
+            .collect();
+        self
+    }
+
     pub fn on_resolution_added<F>(mut self, f: F) -> Self
     where
         F: Fn() + Send + Sync + 'static,
@@ -214,6 +1079,20 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// Called as packages get extracted, with how many bytes have been
+    /// processed for each package and overall. Unlike
+    /// [`NodeMaintainerOptions::on_extract_progress`], which is called once
+    /// per package, this is called many times for each one, so it can drive
+    /// byte-accurate progress bars and transfer rates.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn on_extract_bytes<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Package, &ExtractBytes) + Send + Sync + 'static,
+    {
+        self.on_extract_bytes = Some(Arc::new(f));
+        self
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn on_script_start<F>(mut self, f: F) -> Self
     where
@@ -232,6 +1111,55 @@ impl NodeMaintainerOptions {
         self
     }
 
+    /// Sends every progress event to `f`, as an [`InstallEvent`]. This is
+    /// the same as setting each of the `on_*` handlers, and replaces any
+    /// that were set before it.
+    pub fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&InstallEvent<'_>) + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let handler = f.clone();
+        self.on_resolution_added = Some(Arc::new(move || handler(&InstallEvent::ResolutionAdded)));
+        let handler = f.clone();
+        self.on_resolve_progress = Some(Arc::new(move |package: &Package| {
+            handler(&InstallEvent::Resolved(package))
+        }));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let handler = f.clone();
+            self.on_prune_progress = Some(Arc::new(move |path: &Path| {
+                handler(&InstallEvent::Pruned(path))
+            }));
+            let handler = f.clone();
+            self.on_extract_progress = Some(Arc::new(move |package: &Package| {
+                handler(&InstallEvent::Extracted(package))
+            }));
+            let handler = f.clone();
+            self.on_extract_bytes =
+                Some(Arc::new(move |package: &Package, bytes: &ExtractBytes| {
+                    handler(&InstallEvent::ExtractBytes { package, bytes })
+                }));
+            let handler = f.clone();
+            self.on_script_start = Some(Arc::new(move |package: &Package, event: &str| {
+                handler(&InstallEvent::ScriptStarted { package, event })
+            }));
+            self.on_script_line = Some(Arc::new(move |line: &str| {
+                f(&InstallEvent::ScriptLine(line))
+            }));
+        }
+        self
+    }
+
+    /// How install scripts' output gets shown, on top of being passed to
+    /// [`NodeMaintainerOptions::on_script_line`]. By default, it's only
+    /// passed along.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn script_output(mut self, output: ScriptOutput) -> Self {
+        self.script_output = Arc::new(output);
+        self
+    }
+
     async fn get_lockfile(&self) -> Result<Option<Lockfile>, NodeMaintainerError> {
         if let Some(kdl_lock) = &self.kdl_lock {
             return Ok(Some(kdl_lock.clone()));
@@ -280,44 +1208,303 @@ impl NodeMaintainerOptions {
         Ok(None)
     }
 
+    async fn get_policy(&self) -> Result<Policy, NodeMaintainerError> {
+        if let Some(policy) = &self.policy {
+            return Ok(policy.clone());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(root) = &self.root {
+            let policy = root.join("oro-policy.kdl");
+            if policy.exists() {
+                return Policy::from_kdl(async_std::fs::read_to_string(policy).await?);
+            }
+        }
+        Ok(Policy::default())
+    }
+
+    /// Picks the layout to install with: whatever was asked for, or else
+    /// whatever the project at `root` is already installed with.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn pick_node_linker(&self, root: &Path) -> NodeLinker {
+        let requested = if self.pnp {
+            Some(NodeLinker::Pnp)
+        } else if self.hoisted {
+            Some(NodeLinker::Hoisted)
+        } else {
+            self.node_linker
+        };
+        match (requested, NodeLinker::detect(root)) {
+            (Some(requested), Some(existing)) if requested != existing => {
+                tracing::info!(
+                    "Switching from the {} layout to the {} layout. The old one will be removed.",
+                    existing.as_str(),
+                    requested.as_str()
+                );
+                requested
+            }
+            (Some(requested), _) => requested,
+            (None, Some(existing)) => {
+                tracing::debug!("Keeping the existing {} layout.", existing.as_str());
+                existing
+            }
+            (None, None) => NodeLinker::default(),
+        }
+    }
+
+    fn nassun_client(&self, prefer_lowest: bool) -> Nassun {
+        if let Some(nassun) = self.nassun.as_ref().filter(|_| !prefer_lowest) {
+            return nassun.clone();
+        }
+        let nassun_opts = self.nassun_opts.clone().prefer_lowest(prefer_lowest);
+        // Git dependencies with a `prepare` script get built using a nested
+        // install with the same settings, minus the bits that only make
+        // sense for the toplevel project.
+        #[cfg(not(target_arch = "wasm32"))]
+        let nassun_opts =
+            nassun_opts.git_preparer(Arc::new(GitDepPreparer(NodeMaintainerOptions {
+                // The nested install has its own root to resolve from.
+                nassun: None,
+                kdl_lock: None,
+                npm_lock: None,
+                locked: false,
+                root: None,
+                // Built packages get packed up from their own node_modules/.
+                pnp: false,
+                node_linker: self
+                    .node_linker
+                    .filter(|node_linker| *node_linker != NodeLinker::Pnp),
+                patched_dependencies: IndexMap::new(),
+                catalog: IndexMap::new(),
+                nohoist: Vec::new(),
+                global_bin_dir: None,
+                // Git dependencies have to really be built to be packed up.
+                dry_run: false,
+                virtual_install: false,
+                on_resolution_added: None,
+                on_resolve_progress: None,
+                on_prune_progress: None,
+                on_extract_progress: None,
+                on_extract_bytes: None,
+                on_script_start: None,
+                on_script_line: None,
+                ..self.clone()
+            })));
+        nassun_opts.build()
+    }
+
     /// Resolves a [`NodeMaintainer`] using an existing [`CorgiManifest`].
     pub async fn resolve_manifest(
         self,
         root: CorgiManifest,
     ) -> Result<NodeMaintainer, NodeMaintainerError> {
         let lockfile = self.get_lockfile().await?;
-        let nassun = self.nassun_opts.build();
+        let policy = self.get_policy().await?;
+        let nassun = self.nassun_client(self.resolution_mode == ResolutionMode::Lowest);
+        let direct_nassun = (self.resolution_mode == ResolutionMode::LowestDirect)
+            .then(|| self.nassun_client(true));
+        let proj_root = self.root.clone().unwrap_or_else(|| PathBuf::from("."));
+        #[cfg(not(target_arch = "wasm32"))]
+        let node_linker = self.pick_node_linker(&proj_root);
+        #[cfg(target_arch = "wasm32")]
+        let node_linker = NodeLinker::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let patches = Patches::load(&proj_root, &self.patched_dependencies).await?;
+        #[cfg(target_arch = "wasm32")]
+        let patches = Patches::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let catalogs = Catalogs::load(&proj_root, &self.catalog).await;
+        #[cfg(target_arch = "wasm32")]
+        let catalogs = Catalogs::load(&self.catalog);
+        #[cfg(not(target_arch = "wasm32"))]
+        let workspaces = if self.prefer_workspace_packages || self.link_workspaces {
+            Workspaces::load(&proj_root)
+                .await
+                .prefer(self.prefer_workspace_packages)
+        } else {
+            Workspaces::default()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let workspaces = Workspaces::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let link_strategies = self.link_strategy_chain();
+        #[cfg(not(target_arch = "wasm32"))]
+        let nohoist = [nohoist::load(&proj_root).await, self.nohoist].concat();
+        #[cfg(target_arch = "wasm32")]
+        let nohoist = self.nohoist;
+        #[cfg(not(target_arch = "wasm32"))]
+        let access = AccessTracker::new(self.least_privilege, &proj_root, self.cache.as_deref());
+        #[cfg(not(target_arch = "wasm32"))]
+        let access = Arc::new(match &self.global_bin_dir {
+            Some(bin_dir) => access.allow(bin_dir),
+            None => access,
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        let extract_bytes = self
+            .on_extract_bytes
+            .clone()
+            .map(|handler| Arc::new(ExtractByteCounter::new(handler)));
+        #[cfg(not(target_arch = "wasm32"))]
+        let pipeline = ExtractPipeline::start(
+            &proj_root,
+            // Packages go into the global store instead of node_modules/,
+            // or nowhere at all.
+            if node_linker == NodeLinker::Pnp
+                || self.global_store
+                || self.dry_run
+                || self.virtual_install
+            {
+                0
+            } else {
+                self.pipeline_depth
+            },
+            self.pipeline_concurrency.unwrap_or(self.concurrency),
+            self.cache.as_deref(),
+            &link_strategies,
+            self.validate,
+            access.clone(),
+            extract_bytes.clone(),
+        );
+        let root = workspaces.apply(catalogs.apply(root)?);
+        let root = if self.link_workspaces {
+            workspaces.link_all(root)
+        } else {
+            root
+        };
         let root_pkg = Nassun::dummy_from_manifest(root.clone());
-        let proj_root = self.root.unwrap_or_else(|| PathBuf::from("."));
         let mut resolver = Resolver {
-            nassun,
+            nassun: nassun.clone(),
+            direct_nassun,
             graph: Default::default(),
             concurrency: self.concurrency,
             locked: self.locked,
+            dedupe: self.dedupe,
+            force: self.force,
             root: &proj_root,
             actual_tree: None,
+            patches: &patches,
+            catalogs: &catalogs,
+            workspaces: &workspaces,
+            policy: &policy,
+            require_provenance: &self.require_provenance,
+            report_policy: self.report_policy_violations,
             on_resolution_added: self.on_resolution_added,
             on_resolve_progress: self.on_resolve_progress,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline: pipeline.clone(),
+            shuffle: self.shuffle_seed.map(Shuffle::new),
+            resolution_mode: self.resolution_mode,
+            release_cutoff: self.release_cutoff,
+            explain: self.explain_resolution,
         };
         let node = resolver
             .graph
             .inner
             .add_node(Node::new(root_pkg, root, true)?);
         resolver.graph[node].root = node;
-        let (graph, _actual_tree) = resolver.run_resolver(lockfile).await?;
+        if !nohoist.is_empty() {
+            resolver.graph.nohoist.insert(node, nohoist);
+        }
+        if node_linker == NodeLinker::Hoisted {
+            resolver.graph.hoist_patterns = Some(self.hoist_patterns);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let foreign_layout = ForeignLayout::detect(&proj_root);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(foreign) = foreign_layout {
+            // Whatever Orogene recorded last time can't be trusted anymore.
+            if self.takeover {
+                tracing::info!("Taking over node_modules/ from {}.", foreign.as_str());
+            } else {
+                tracing::warn!(
+                    "node_modules/ was installed by {}. Pass --takeover to replace it.",
+                    foreign.as_str()
+                );
+            }
+        } else {
+            resolver.load_actual().await?;
+        }
+        let (graph, _actual_tree) = match resolver.run_resolver(lockfile).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(pipeline) = &pipeline {
+                    pipeline.cancel().await;
+                }
+                return Err(e);
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let global_store = self
+            .cache
+            .as_deref()
+            .filter(|_| self.global_store)
+            .map(GlobalStore::in_cache);
+        #[cfg(not(target_arch = "wasm32"))]
+        let dry_run = self.dry_run.then(|| Arc::new(DryRun::default()));
+        #[cfg(not(target_arch = "wasm32"))]
+        let script_trust = Arc::new(ScriptTrust::load(&proj_root, self.allow_scripts).await);
+        #[cfg(not(target_arch = "wasm32"))]
+        let script_results = ScriptResults::new(&proj_root, self.force_rebuild);
+        #[cfg(not(target_arch = "wasm32"))]
+        let scripts_run = Arc::new(AtomicUsize::new(0));
+        #[cfg(not(target_arch = "wasm32"))]
+        let timings = Arc::new(TimingCounters::default());
         #[cfg(not(target_arch = "wasm32"))]
         let linker_opts = LinkerOptions {
             actual_tree: _actual_tree,
             concurrency: self.concurrency,
-            script_concurrency: self.script_concurrency,
+            script_scheduler: ScriptScheduler::new(if self.foreground_scripts {
+                Some(1)
+            } else {
+                self.script_concurrency
+            }),
+            access,
             cache: self.cache,
-            prefer_copy: self.prefer_copy,
+            patches: Arc::new(patches),
+            link_strategies,
             validate: self.validate,
+            repair: self.repair,
+            hook_scripts: HookScriptFilter::new(
+                self.hook_scripts,
+                &self.hook_script_patterns,
+                &proj_root,
+            ),
+            workspace_dirs: if self.link_workspaces {
+                workspaces.dirs(&proj_root)
+            } else {
+                HashSet::new()
+            },
             root: proj_root,
+            pipeline,
+            global_store,
+            side_effects_cache: self.side_effects_cache,
+            strict_bins: self.strict_bins,
+            bin_symlinks: self.bin_symlinks,
+            dry_run: dry_run.clone(),
+            script_trust: script_trust.clone(),
+            sandbox_scripts: self.sandbox_scripts,
+            sandbox_exempt: self.sandbox_exempt,
+            script_timeouts: Arc::new(ScriptTimeouts::new(
+                self.script_timeout,
+                self.total_script_timeout,
+            )),
+            script_config: Arc::new(self.script_config),
+            script_shell: self.script_shell,
+            foreground_scripts: self.foreground_scripts,
+            script_results,
+            scripts_run: scripts_run.clone(),
+            timings: timings.clone(),
+            prune_keep: self.prune_keep,
+            public_hoist_patterns: self.public_hoist_patterns,
+            global_bin_dir: self.global_bin_dir,
+            foreign_layout,
+            takeover: self.takeover,
             on_prune_progress: self.on_prune_progress,
             on_extract_progress: self.on_extract_progress,
+            extract_bytes,
             on_script_start: self.on_script_start,
             on_script_line: self.on_script_line,
+            script_output: self.script_output,
         };
         #[cfg(target_arch = "wasm32")]
         let linker = Linker::null();
@@ -326,17 +1513,153 @@ impl NodeMaintainerOptions {
             #[cfg(target_arch = "wasm32")]
             linker: Linker::null(),
             #[cfg(not(target_arch = "wasm32"))]
-            linker: if self.hoisted {
-                Linker::hoisted(linker_opts)
-            } else {
-                Linker::isolated(linker_opts)
+            linker: match node_linker {
+                _ if self.virtual_install => Linker::null(),
+                NodeLinker::Isolated => Linker::isolated(linker_opts),
+                NodeLinker::Hoisted => Linker::hoisted(linker_opts),
+                NodeLinker::Pnp => Linker::pnp(linker_opts),
             },
+            node_linker,
+            concurrency: self.concurrency,
+            #[cfg(not(target_arch = "wasm32"))]
+            dry_run,
+            #[cfg(not(target_arch = "wasm32"))]
+            script_trust,
+            #[cfg(not(target_arch = "wasm32"))]
+            scripts_run,
+            #[cfg(not(target_arch = "wasm32"))]
+            timings,
+            #[cfg(not(target_arch = "wasm32"))]
+            nassun,
         };
         #[cfg(debug_assertions)]
         nm.graph.validate()?;
         Ok(nm)
     }
 
+    /// Resolves the dependency tree for an existing [`CorgiManifest`] and
+    /// returns it as a [`Lockfile`], without setting up anything needed to
+    /// install it. This is meant for tools that only want to look at the
+    /// resolved tree.
+    ///
+    /// Unlike [`NodeMaintainerOptions::resolve_manifest`], this never looks
+    /// at the project's `node_modules/`, and nothing gets written to disk.
+    /// An existing lockfile is still used, if there is one.
+    pub async fn resolve_only(self, root: CorgiManifest) -> Result<Lockfile, NodeMaintainerError> {
+        let lockfile = self.get_lockfile().await?;
+        let policy = self.get_policy().await?;
+        let nassun = self.nassun_client(self.resolution_mode == ResolutionMode::Lowest);
+        let direct_nassun = (self.resolution_mode == ResolutionMode::LowestDirect)
+            .then(|| self.nassun_client(true));
+        let proj_root = self.root.clone().unwrap_or_else(|| PathBuf::from("."));
+        #[cfg(not(target_arch = "wasm32"))]
+        let node_linker = self.pick_node_linker(&proj_root);
+        #[cfg(target_arch = "wasm32")]
+        let node_linker = NodeLinker::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let patches = Patches::load(&proj_root, &self.patched_dependencies).await?;
+        #[cfg(target_arch = "wasm32")]
+        let patches = Patches::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let catalogs = Catalogs::load(&proj_root, &self.catalog).await;
+        #[cfg(target_arch = "wasm32")]
+        let catalogs = Catalogs::load(&self.catalog);
+        #[cfg(not(target_arch = "wasm32"))]
+        let workspaces = if self.prefer_workspace_packages || self.link_workspaces {
+            Workspaces::load(&proj_root)
+                .await
+                .prefer(self.prefer_workspace_packages)
+        } else {
+            Workspaces::default()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let workspaces = Workspaces::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let nohoist = [nohoist::load(&proj_root).await, self.nohoist].concat();
+        #[cfg(target_arch = "wasm32")]
+        let nohoist = self.nohoist;
+        let root = workspaces.apply(catalogs.apply(root)?);
+        let root = if self.link_workspaces {
+            workspaces.link_all(root)
+        } else {
+            root
+        };
+        let root_pkg = Nassun::dummy_from_manifest(root.clone());
+        let mut resolver = Resolver {
+            nassun,
+            direct_nassun,
+            graph: Default::default(),
+            concurrency: self.concurrency,
+            locked: self.locked,
+            dedupe: self.dedupe,
+            force: self.force,
+            root: &proj_root,
+            actual_tree: None,
+            patches: &patches,
+            catalogs: &catalogs,
+            workspaces: &workspaces,
+            policy: &policy,
+            require_provenance: &self.require_provenance,
+            report_policy: self.report_policy_violations,
+            on_resolution_added: self.on_resolution_added,
+            on_resolve_progress: self.on_resolve_progress,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline: None,
+            shuffle: self.shuffle_seed.map(Shuffle::new),
+            resolution_mode: self.resolution_mode,
+            release_cutoff: self.release_cutoff,
+            explain: self.explain_resolution,
+        };
+        let node = resolver
+            .graph
+            .inner
+            .add_node(Node::new(root_pkg, root, true)?);
+        resolver.graph[node].root = node;
+        if !nohoist.is_empty() {
+            resolver.graph.nohoist.insert(node, nohoist);
+        }
+        if node_linker == NodeLinker::Hoisted {
+            resolver.graph.hoist_patterns = Some(self.hoist_patterns);
+        }
+        let (graph, _) = resolver.run_resolver(lockfile).await?;
+        #[cfg(debug_assertions)]
+        graph.validate()?;
+        graph.to_lockfile()
+    }
+
+    /// Resolves the dependency tree for `root` twice, the second time with
+    /// the resolver's iteration order shuffled, and errors with
+    /// [`NodeMaintainerError::NondeterministicResolution`] if the two trees
+    /// differ. Returns the resolved tree otherwise.
+    ///
+    /// Like [`NodeMaintainerOptions::resolve_only`], this doesn't touch
+    /// `node_modules/` or write anything to disk. If no seed was set using
+    /// [`NodeMaintainerOptions::shuffle_seed`], a random one is used.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn check_determinism(
+        self,
+        root: CorgiManifest,
+    ) -> Result<Lockfile, NodeMaintainerError> {
+        let seed = self.shuffle_seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|since| since.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+        let mut unshuffled = self.clone();
+        unshuffled.shuffle_seed = None;
+        let expected = unshuffled.resolve_only(root.clone()).await?;
+        let shuffled = self.shuffle_seed(seed).resolve_only(root).await?;
+        if expected == shuffled {
+            Ok(expected)
+        } else {
+            Err(NodeMaintainerError::NondeterministicResolution {
+                seed,
+                differences: expected.differences(&shuffled),
+            })
+        }
+    }
+
     /// Resolves a [`NodeMaintainer`] using a particular package spec (for
     /// example, `foo@1.2.3` or `./root`) as its "root" package.
     pub async fn resolve_spec(
@@ -344,50 +1667,226 @@ impl NodeMaintainerOptions {
         root_spec: impl AsRef<str>,
     ) -> Result<NodeMaintainer, NodeMaintainerError> {
         let lockfile = self.get_lockfile().await?;
-        let nassun = self.nassun_opts.build();
+        let policy = self.get_policy().await?;
+        let nassun = self.nassun_client(self.resolution_mode == ResolutionMode::Lowest);
+        let direct_nassun = (self.resolution_mode == ResolutionMode::LowestDirect)
+            .then(|| self.nassun_client(true));
         let root_pkg = nassun.resolve(root_spec).await?;
-        let proj_root = self.root.unwrap_or_else(|| PathBuf::from("."));
+        let proj_root = self.root.clone().unwrap_or_else(|| PathBuf::from("."));
+        #[cfg(not(target_arch = "wasm32"))]
+        let node_linker = self.pick_node_linker(&proj_root);
+        #[cfg(target_arch = "wasm32")]
+        let node_linker = NodeLinker::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let patches = Patches::load(&proj_root, &self.patched_dependencies).await?;
+        #[cfg(target_arch = "wasm32")]
+        let patches = Patches::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let catalogs = Catalogs::load(&proj_root, &self.catalog).await;
+        #[cfg(target_arch = "wasm32")]
+        let catalogs = Catalogs::load(&self.catalog);
+        #[cfg(not(target_arch = "wasm32"))]
+        let workspaces = if self.prefer_workspace_packages {
+            Workspaces::load(&proj_root).await
+        } else {
+            Workspaces::default()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let workspaces = Workspaces::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let link_strategies = self.link_strategy_chain();
+        #[cfg(not(target_arch = "wasm32"))]
+        let nohoist = [nohoist::load(&proj_root).await, self.nohoist].concat();
+        #[cfg(target_arch = "wasm32")]
+        let nohoist = self.nohoist;
+        #[cfg(not(target_arch = "wasm32"))]
+        let access = AccessTracker::new(self.least_privilege, &proj_root, self.cache.as_deref());
+        #[cfg(not(target_arch = "wasm32"))]
+        let access = Arc::new(match &self.global_bin_dir {
+            Some(bin_dir) => access.allow(bin_dir),
+            None => access,
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        let extract_bytes = self
+            .on_extract_bytes
+            .clone()
+            .map(|handler| Arc::new(ExtractByteCounter::new(handler)));
+        #[cfg(not(target_arch = "wasm32"))]
+        let pipeline = ExtractPipeline::start(
+            &proj_root,
+            // Packages go into the global store instead of node_modules/,
+            // or nowhere at all.
+            if node_linker == NodeLinker::Pnp
+                || self.global_store
+                || self.dry_run
+                || self.virtual_install
+            {
+                0
+            } else {
+                self.pipeline_depth
+            },
+            self.pipeline_concurrency.unwrap_or(self.concurrency),
+            self.cache.as_deref(),
+            &link_strategies,
+            self.validate,
+            access.clone(),
+            extract_bytes.clone(),
+        );
         let mut resolver = Resolver {
-            nassun,
+            nassun: nassun.clone(),
+            direct_nassun,
             graph: Default::default(),
             concurrency: self.concurrency,
             locked: self.locked,
+            dedupe: self.dedupe,
+            force: self.force,
             root: &proj_root,
             actual_tree: None,
+            patches: &patches,
+            catalogs: &catalogs,
+            workspaces: &workspaces,
+            policy: &policy,
+            require_provenance: &self.require_provenance,
+            report_policy: self.report_policy_violations,
             on_resolution_added: self.on_resolution_added,
             on_resolve_progress: self.on_resolve_progress,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline: pipeline.clone(),
+            shuffle: self.shuffle_seed.map(Shuffle::new),
+            resolution_mode: self.resolution_mode,
+            release_cutoff: self.release_cutoff,
+            explain: self.explain_resolution,
         };
-        let corgi = root_pkg.corgi_metadata().await?.manifest;
+        let corgi = workspaces.apply(catalogs.apply(root_pkg.corgi_metadata().await?.manifest)?);
         let node = resolver
             .graph
             .inner
             .add_node(Node::new(root_pkg, corgi, true)?);
         resolver.graph[node].root = node;
-        let (graph, _actual_tree) = resolver.run_resolver(lockfile).await?;
+        if !nohoist.is_empty() {
+            resolver.graph.nohoist.insert(node, nohoist);
+        }
+        if node_linker == NodeLinker::Hoisted {
+            resolver.graph.hoist_patterns = Some(self.hoist_patterns);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let foreign_layout = ForeignLayout::detect(&proj_root);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(foreign) = foreign_layout {
+            // Whatever Orogene recorded last time can't be trusted anymore.
+            if self.takeover {
+                tracing::info!("Taking over node_modules/ from {}.", foreign.as_str());
+            } else {
+                tracing::warn!(
+                    "node_modules/ was installed by {}. Pass --takeover to replace it.",
+                    foreign.as_str()
+                );
+            }
+        } else {
+            resolver.load_actual().await?;
+        }
+        let (graph, _actual_tree) = match resolver.run_resolver(lockfile).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(pipeline) = &pipeline {
+                    pipeline.cancel().await;
+                }
+                return Err(e);
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let global_store = self
+            .cache
+            .as_deref()
+            .filter(|_| self.global_store)
+            .map(GlobalStore::in_cache);
+        #[cfg(not(target_arch = "wasm32"))]
+        let dry_run = self.dry_run.then(|| Arc::new(DryRun::default()));
+        #[cfg(not(target_arch = "wasm32"))]
+        let script_trust = Arc::new(ScriptTrust::load(&proj_root, self.allow_scripts).await);
+        #[cfg(not(target_arch = "wasm32"))]
+        let script_results = ScriptResults::new(&proj_root, self.force_rebuild);
+        #[cfg(not(target_arch = "wasm32"))]
+        let scripts_run = Arc::new(AtomicUsize::new(0));
+        #[cfg(not(target_arch = "wasm32"))]
+        let timings = Arc::new(TimingCounters::default());
         #[cfg(not(target_arch = "wasm32"))]
         let linker_opts = LinkerOptions {
             actual_tree: _actual_tree,
             concurrency: self.concurrency,
-            script_concurrency: self.script_concurrency,
+            script_scheduler: ScriptScheduler::new(if self.foreground_scripts {
+                Some(1)
+            } else {
+                self.script_concurrency
+            }),
+            access,
             cache: self.cache,
-            prefer_copy: self.prefer_copy,
+            patches: Arc::new(patches),
+            link_strategies,
             validate: self.validate,
+            repair: self.repair,
+            hook_scripts: HookScriptFilter::new(
+                self.hook_scripts,
+                &self.hook_script_patterns,
+                &proj_root,
+            ),
             root: proj_root,
+            pipeline,
+            global_store,
+            side_effects_cache: self.side_effects_cache,
+            strict_bins: self.strict_bins,
+            bin_symlinks: self.bin_symlinks,
+            dry_run: dry_run.clone(),
+            script_trust: script_trust.clone(),
+            sandbox_scripts: self.sandbox_scripts,
+            sandbox_exempt: self.sandbox_exempt,
+            script_timeouts: Arc::new(ScriptTimeouts::new(
+                self.script_timeout,
+                self.total_script_timeout,
+            )),
+            script_config: Arc::new(self.script_config),
+            script_shell: self.script_shell,
+            foreground_scripts: self.foreground_scripts,
+            script_results,
+            scripts_run: scripts_run.clone(),
+            timings: timings.clone(),
+            workspace_dirs: HashSet::new(),
+            prune_keep: self.prune_keep,
+            public_hoist_patterns: self.public_hoist_patterns,
+            global_bin_dir: self.global_bin_dir,
+            foreign_layout,
+            takeover: self.takeover,
             on_prune_progress: self.on_prune_progress,
             on_extract_progress: self.on_extract_progress,
+            extract_bytes,
             on_script_start: self.on_script_start,
             on_script_line: self.on_script_line,
+            script_output: self.script_output,
         };
         let nm = NodeMaintainer {
             graph,
             #[cfg(target_arch = "wasm32")]
             linker: Linker::null(),
             #[cfg(not(target_arch = "wasm32"))]
-            linker: if self.hoisted {
-                Linker::hoisted(linker_opts)
-            } else {
-                Linker::isolated(linker_opts)
+            linker: match node_linker {
+                _ if self.virtual_install => Linker::null(),
+                NodeLinker::Isolated => Linker::isolated(linker_opts),
+                NodeLinker::Hoisted => Linker::hoisted(linker_opts),
+                NodeLinker::Pnp => Linker::pnp(linker_opts),
             },
+            node_linker,
+            concurrency: self.concurrency,
+            #[cfg(not(target_arch = "wasm32"))]
+            dry_run,
+            #[cfg(not(target_arch = "wasm32"))]
+            script_trust,
+            #[cfg(not(target_arch = "wasm32"))]
+            scripts_run,
+            #[cfg(not(target_arch = "wasm32"))]
+            timings,
+            #[cfg(not(target_arch = "wasm32"))]
+            nassun,
         };
         #[cfg(debug_assertions)]
         nm.graph.validate()?;
@@ -399,23 +1898,145 @@ impl Default for NodeMaintainerOptions {
     fn default() -> Self {
         NodeMaintainerOptions {
             nassun_opts: Default::default(),
+            nassun: None,
             concurrency: DEFAULT_CONCURRENCY,
             kdl_lock: None,
             npm_lock: None,
+            policy: None,
+            report_policy_violations: false,
+            require_provenance: Vec::new(),
             locked: false,
-            script_concurrency: DEFAULT_SCRIPT_CONCURRENCY,
+            dedupe: false,
+            force: false,
+            resolution_mode: ResolutionMode::default(),
+            explain_resolution: false,
+            release_cutoff: false,
+            script_concurrency: None,
+            pipeline_depth: 0,
+            pipeline_concurrency: None,
+            shuffle_seed: None,
             cache: None,
+            node_linker: None,
             hoisted: false,
+            pnp: false,
+            global_store: false,
+            side_effects_cache: false,
+            strict_bins: false,
+            bin_symlinks: false,
+            dry_run: false,
+            virtual_install: false,
+            prune_keep: DEFAULT_PRUNE_KEEP_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            public_hoist_patterns: DEFAULT_PUBLIC_HOIST_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            global_bin_dir: None,
+            takeover: false,
             prefer_copy: false,
+            link_strategies: LinkStrategy::DEFAULT_CHAIN.to_vec(),
             validate: false,
+            repair: false,
             root: None,
+            least_privilege: false,
+            hook_scripts: HookScripts::default(),
+            hook_script_patterns: DEFAULT_HOOK_SCRIPT_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            allow_scripts: AllowScripts::default(),
+            sandbox_scripts: false,
+            sandbox_exempt: Vec::new(),
+            script_timeout: None,
+            total_script_timeout: None,
+            script_config: Vec::new(),
+            script_shell: None,
+            foreground_scripts: false,
+            force_rebuild: false,
+            patched_dependencies: IndexMap::new(),
+            catalog: IndexMap::new(),
+            prefer_workspace_packages: false,
+            link_workspaces: true,
+            nohoist: Vec::new(),
+            hoist_patterns: DEFAULT_HOIST_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
             on_resolution_added: None,
             on_resolve_progress: None,
             on_prune_progress: None,
             on_extract_progress: None,
+            on_extract_bytes: None,
             on_script_start: None,
             on_script_line: None,
+            script_output: Arc::new(ScriptOutput::default()),
+        }
+    }
+}
+
+/// Builds git dependencies from source by installing their dependencies
+/// (including `devDependencies`) into their cloned directory and running
+/// their `prepare` script, the same way the NPM CLI does.
+#[cfg(not(target_arch = "wasm32"))]
+struct GitDepPreparer(NodeMaintainerOptions);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::fmt::Debug for GitDepPreparer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitDepPreparer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl GitPreparer for GitDepPreparer {
+    async fn prepare(&self, package_dir: &Path) -> Result<(), NassunError> {
+        self.prepare_inner(package_dir)
+            .await
+            .map_err(|e| NassunError::GitPrepareError(package_dir.into(), Box::new(e)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GitDepPreparer {
+    async fn prepare_inner(&self, package_dir: &Path) -> Result<(), NodeMaintainerError> {
+        let manifest: CorgiManifest =
+            serde_json::from_str(&fs::read_to_string(package_dir.join("package.json")).await?)?;
+        let maintainer = self
+            .0
+            .clone()
+            .root(package_dir)
+            .resolve_manifest(manifest)
+            .await?;
+        maintainer.extract().await?;
+        maintainer.rebuild(false).await?;
+        let manifest_path = package_dir.join("package.json");
+        let Some(command) = BuildManifest::from_path(&manifest_path)
+            .map_err(|e| NodeMaintainerError::BuildManifestReadError(manifest_path, e))?
+            .scripts
+            .remove("prepare")
+        else {
+            return Ok(());
+        };
+        let hook_scripts = HookScriptFilter::new(
+            self.0.hook_scripts,
+            &self.0.hook_script_patterns,
+            package_dir,
+        );
+        if hook_scripts.should_skip(&command) {
+            tracing::info!("Skipping `prepare` script for the git dependency at {} (`{command}`), since it looks like it sets up git hooks. Use `--hook-scripts run` to run it anyway.", package_dir.display());
+            return Ok(());
         }
+        let package_dir = package_dir.to_path_buf();
+        async_std::task::spawn_blocking(move || {
+            OroScript::new(&package_dir, "prepare")?
+                .workspace_path(&package_dir)
+                .output()
+        })
+        .await?;
+        Ok(())
     }
 }
 
@@ -424,6 +2045,18 @@ pub struct NodeMaintainer {
     pub(crate) graph: Graph,
     #[allow(dead_code)]
     linker: Linker,
+    node_linker: NodeLinker,
+    concurrency: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    dry_run: Option<Arc<DryRun>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    script_trust: Arc<ScriptTrust>,
+    #[cfg(not(target_arch = "wasm32"))]
+    scripts_run: Arc<AtomicUsize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    timings: Arc<TimingCounters>,
+    #[cfg(not(target_arch = "wasm32"))]
+    nassun: Nassun,
 }
 
 impl NodeMaintainer {
@@ -441,6 +2074,14 @@ impl NodeMaintainer {
         Self::builder().resolve_manifest(root).await
     }
 
+    /// Resolves the dependency tree for an existing [`CorgiManifest`],
+    /// without touching `node_modules/`. See
+    /// [`NodeMaintainerOptions::resolve_only`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn resolve_only(root: CorgiManifest) -> Result<Lockfile, NodeMaintainerError> {
+        Self::builder().resolve_only(root).await
+    }
+
     /// Resolves a [`NodeMaintainer`] using a particular package spec (for
     /// example, `foo@1.2.3` or `./root`) as its "root" package.
     #[cfg(not(target_arch = "wasm32"))]
@@ -457,6 +2098,36 @@ impl NodeMaintainer {
         Ok(())
     }
 
+    /// Writes an npm-compatible `package-lock.json` (lockfile version 3) to
+    /// the file path, for tools that don't understand `package-lock.kdl`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn write_npm_lockfile(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), NodeMaintainerError> {
+        let npm = self.graph.to_lockfile()?.to_npm();
+        fs::write(path.as_ref(), serde_json::to_string_pretty(&npm)? + "\n").await?;
+        Ok(())
+    }
+
+    /// Writes npm's hidden `node_modules/.package-lock.json` to the file
+    /// path. npm only uses this file when it exactly matches the layout of
+    /// `node_modules/`, so it should only be written for hoisted installs.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn write_hidden_npm_lockfile(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), NodeMaintainerError> {
+        let npm = self.graph.to_lockfile()?.to_npm_hidden();
+        fs::write(path.as_ref(), serde_json::to_string_pretty(&npm)? + "\n").await?;
+        Ok(())
+    }
+
+    /// The layout dependencies are installed with.
+    pub fn node_linker(&self) -> NodeLinker {
+        self.node_linker
+    }
+
     /// Returns a [`crate::Lockfile`] representation of the current resolved graph.
     pub fn to_lockfile(&self) -> Result<crate::Lockfile, NodeMaintainerError> {
         self.graph.to_lockfile()
@@ -467,6 +2138,23 @@ impl NodeMaintainer {
         self.graph.to_kdl()
     }
 
+    /// Returns the resolved dependency graph in Graphviz's DOT format.
+    pub fn to_dot(&self) -> String {
+        self.graph.to_dot()
+    }
+
+    /// Returns the resolved dependency graph as JSON, with a list of
+    /// `nodes`, identified by their path in `node_modules/`, and a list of
+    /// `edges` between them.
+    pub fn to_json(&self) -> Result<serde_json::Value, NodeMaintainerError> {
+        self.graph.to_json()
+    }
+
+    /// Returns the resolved dependency graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        self.graph.to_mermaid()
+    }
+
     /// Returns a [`Package`] for the given package spec, if it is present in
     /// the dependency tree. The path should be relative to the root of the
     /// project, and can optionally start with `"node_modules/"`.
@@ -474,6 +2162,36 @@ impl NodeMaintainer {
         self.graph.package_at_path(path)
     }
 
+    /// Sets of packages in the resolved graph that depend on each other in a
+    /// loop. These are allowed, but lifecycle scripts for packages in a
+    /// cycle can't run in dependency order.
+    pub fn cycles(&self) -> Vec<DependencyCycle> {
+        self.graph.cycles()
+    }
+
+    /// Peer dependencies in the resolved tree that aren't satisfied. Peers
+    /// that are installed at the wrong version only end up here when
+    /// [`NodeMaintainerOptions::force`] is set, since resolution fails on
+    /// them otherwise.
+    pub fn peer_problems(&self) -> &[PeerProblem] {
+        &self.graph.peer_problems
+    }
+
+    /// Packages in the resolved tree that break the project's policy,
+    /// sorted by dependency path. This is always empty unless
+    /// [`NodeMaintainerOptions::report_policy_violations`] is set, since
+    /// resolution fails on the first violation otherwise.
+    pub fn policy_violations(&self) -> &[PolicyViolation] {
+        &self.graph.policy_violations
+    }
+
+    /// Every package in the resolved tree, root first, sorted by where it
+    /// goes in `node_modules/`, along with what its dependencies resolved
+    /// to.
+    pub fn tree(&self) -> Vec<TreePackage> {
+        self.graph.tree()
+    }
+
     /// Number of unique packages in the dependency tree.
     pub fn package_count(&self) -> usize {
         self.graph.inner.node_count()
@@ -496,10 +2214,108 @@ impl NodeMaintainer {
         self.linker.extract(&self.graph).await
     }
 
+    /// Extracts the `node_modules/` directory into `fs` instead of the
+    /// project root, laid out like the hoisted linker would, downloading
+    /// packages as needed. No install scripts are run. This is how installs
+    /// get materialized on wasm32, where there's no filesystem to link into.
+    pub async fn extract_to(&self, fs: &dyn Vfs) -> Result<usize, NodeMaintainerError> {
+        VfsLinker {
+            fs,
+            concurrency: self.concurrency,
+        }
+        .extract(&self.graph)
+        .await
+    }
+
+    /// Downloads every package in the dependency tree into the cache,
+    /// without touching `node_modules/`, so later installs can run offline.
+    /// Returns how many packages weren't already cached.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn prefetch(&self) -> Result<usize, NodeMaintainerError> {
+        self.linker.prefetch(&self.graph).await
+    }
+
+    /// How much resolving and installing have gotten out of the cache so
+    /// far: packages extracted from it or downloaded, and packuments
+    /// revalidated or downloaded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cache_counts(&self) -> CacheCounts {
+        self.nassun.cache_counts()
+    }
+
+    /// Where the time spent resolving and installing so far went, including
+    /// which packages took the longest.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn timings(&self) -> Timings {
+        self.timings.timings(&self.nassun.cache_counts())
+    }
+
     /// Runs the `preinstall`, `install`, and `postinstall` lifecycle scripts,
     /// as well as linking the package bins as needed.
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn rebuild(&self, ignore_scripts: bool) -> Result<(), NodeMaintainerError> {
         self.linker.rebuild(&self.graph, ignore_scripts).await
     }
+
+    /// Everything [`NodeMaintainer::prune`], [`NodeMaintainer::extract`], and
+    /// [`NodeMaintainer::rebuild`] would've done so far, if this is a dry
+    /// run. See [`NodeMaintainerOptions::dry_run`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dry_run_report(&self) -> Option<DryRunReport> {
+        self.dry_run.as_ref().map(|dry_run| dry_run.report())
+    }
+
+    /// Works out where every package would go, which bins would get
+    /// linked, and which install scripts would run, without touching the
+    /// disk or downloading any tarballs. The tree is laid out the way the
+    /// hoisted linker would lay it out. See
+    /// [`NodeMaintainerOptions::virtual_install`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn virtual_install(&self) -> Result<VirtualInstall, NodeMaintainerError> {
+        crate::virtual_install::virtual_install(&self.graph).await
+    }
+
+    /// Packages in the resolved tree that list funding URLs in their
+    /// `package.json`. Installed packages are read from `node_modules/`,
+    /// and anything else has its full metadata fetched from the registry.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn funding(&self) -> Result<Vec<FundingInfo>, NodeMaintainerError> {
+        crate::funding::funding(&self.graph, &self.linker, self.concurrency).await
+    }
+
+    /// Explains why every copy of `name` in the resolved tree ended up at
+    /// the version it did: what depends on it, which published versions
+    /// those would accept, and, with
+    /// [`NodeMaintainerOptions::explain_resolution`], why the resolver
+    /// picked the one it did. Registry packages have their packuments
+    /// fetched to find the candidates.
+    pub async fn explain(
+        &self,
+        name: &str,
+    ) -> Result<Vec<ResolutionExplanation>, NodeMaintainerError> {
+        crate::explain::explain(&self.graph, name).await
+    }
+
+    /// Deprecated package versions in the resolved tree. Only packages
+    /// whose metadata was fetched while resolving are checked, so ones
+    /// reused as-is from the lockfile or `node_modules/` won't show up.
+    pub fn deprecations(&self) -> Vec<Deprecation> {
+        crate::deprecated::deprecations(&self.graph)
+    }
+
+    /// Packages whose install scripts were skipped by
+    /// [`NodeMaintainer::rebuild`] because they aren't trusted. See
+    /// [`NodeMaintainerOptions::allow_scripts`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn blocked_scripts(&self) -> Vec<BlockedScripts> {
+        self.script_trust.blocked()
+    }
+
+    /// How many install scripts [`NodeMaintainer::rebuild`] has run so far,
+    /// counting each of a package's `preinstall`, `install`, and
+    /// `postinstall` separately.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn scripts_run(&self) -> usize {
+        self.scripts_run.load(Ordering::Relaxed)
+    }
 }
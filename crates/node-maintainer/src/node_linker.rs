@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::NodeMaintainerError;
+use crate::{META_FILE_NAME, PNP_FILE_NAME, STORE_DIR_NAME};
+
+/// Controls how dependencies are laid out on disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NodeLinker {
+    /// Every package gets its own `node_modules/`, containing links to only
+    /// the dependencies it declared, backed by `node_modules/.oro-store/`.
+    #[default]
+    Isolated,
+    /// Dependencies are installed as high up in `node_modules/` as
+    /// possible, like the NPM CLI does.
+    Hoisted,
+    /// Dependencies are resolved through a `.pnp.cjs` file instead of
+    /// `node_modules/`.
+    Pnp,
+}
+
+impl NodeLinker {
+    /// Works out which layout the project at `root` is currently installed
+    /// with, if any. Layouts written by other package managers are
+    /// recognized too, so switching to Orogene keeps the same layout.
+    pub fn detect(root: &Path) -> Option<Self> {
+        let node_modules = root.join("node_modules");
+        if root.join(PNP_FILE_NAME).exists() {
+            Some(Self::Pnp)
+        } else if node_modules.join(STORE_DIR_NAME).is_dir() || node_modules.join(".pnpm").is_dir()
+        {
+            Some(Self::Isolated)
+        } else if [META_FILE_NAME, ".package-lock.json", ".yarn-state.yml"]
+            .iter()
+            .any(|file| node_modules.join(file).exists())
+        {
+            Some(Self::Hoisted)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Isolated => "isolated",
+            Self::Hoisted => "hoisted",
+            Self::Pnp => "pnp",
+        }
+    }
+}
+
+/// A `node_modules/` installed by another package manager, which Orogene
+/// can't reuse as-is without ending up with a mix of both layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignLayout {
+    Npm,
+    Yarn,
+    Pnpm,
+}
+
+impl ForeignLayout {
+    /// Works out whether `node_modules/` in the project at `root` was last
+    /// installed by another package manager. A foreign layout next to
+    /// Orogene's own metadata file only counts if it's something Orogene
+    /// never writes itself, since that means another package manager ran
+    /// after Orogene did.
+    pub fn detect(root: &Path) -> Option<Self> {
+        let node_modules = root.join("node_modules");
+        let ours = node_modules.join(META_FILE_NAME).exists();
+        if node_modules.join(".pnpm").is_dir() || node_modules.join(".modules.yaml").exists() {
+            Some(Self::Pnpm)
+        } else if node_modules.join(".yarn-state.yml").exists()
+            || node_modules.join(".yarn-integrity").exists()
+        {
+            Some(Self::Yarn)
+        } else if !ours && node_modules.join(".package-lock.json").exists() {
+            Some(Self::Npm)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Npm => "npm",
+            Self::Yarn => "yarn",
+            Self::Pnpm => "pnpm",
+        }
+    }
+
+    /// Files and directories the package manager keeps its own bookkeeping
+    /// in, relative to the project root.
+    pub(crate) fn metadata_paths(&self, root: &Path) -> Vec<PathBuf> {
+        let node_modules = root.join("node_modules");
+        let names: &[&str] = match self {
+            Self::Npm => &[".package-lock.json"],
+            Self::Yarn => &[".yarn-state.yml", ".yarn-integrity"],
+            Self::Pnpm => &[".pnpm", ".modules.yaml"],
+        };
+        names.iter().map(|name| node_modules.join(name)).collect()
+    }
+}
+
+impl FromStr for NodeLinker {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "isolated" => Ok(Self::Isolated),
+            "hoisted" => Ok(Self::Hoisted),
+            "pnp" => Ok(Self::Pnp),
+            _ => Err(NodeMaintainerError::InvalidNodeLinker(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_layouts() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let root = root.path();
+        assert_eq!(NodeLinker::detect(root), None);
+        std::fs::create_dir_all(root.join("node_modules"))?;
+        std::fs::write(root.join("node_modules").join(META_FILE_NAME), "")?;
+        assert_eq!(NodeLinker::detect(root), Some(NodeLinker::Hoisted));
+        std::fs::create_dir_all(root.join("node_modules/.pnpm"))?;
+        assert_eq!(NodeLinker::detect(root), Some(NodeLinker::Isolated));
+        std::fs::write(root.join(PNP_FILE_NAME), "")?;
+        assert_eq!(NodeLinker::detect(root), Some(NodeLinker::Pnp));
+        Ok(())
+    }
+
+    #[test]
+    fn detects_foreign_layouts() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let root = root.path();
+        let node_modules = root.join("node_modules");
+        std::fs::create_dir_all(&node_modules)?;
+        assert_eq!(ForeignLayout::detect(root), None);
+        std::fs::write(node_modules.join(".package-lock.json"), "")?;
+        assert_eq!(ForeignLayout::detect(root), Some(ForeignLayout::Npm));
+        // Hoisted installs write their own hidden npm lockfile.
+        std::fs::write(node_modules.join(META_FILE_NAME), "")?;
+        assert_eq!(ForeignLayout::detect(root), None);
+        std::fs::write(node_modules.join(".yarn-state.yml"), "")?;
+        assert_eq!(ForeignLayout::detect(root), Some(ForeignLayout::Yarn));
+        std::fs::create_dir_all(node_modules.join(".pnpm"))?;
+        assert_eq!(ForeignLayout::detect(root), Some(ForeignLayout::Pnpm));
+        Ok(())
+    }
+}
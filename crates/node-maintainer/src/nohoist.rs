@@ -0,0 +1,101 @@
+//! Hoisting boundaries ("nohoist"): patterns that keep matching packages in
+//! the `node_modules/` of the workspace that pulls them in, instead of
+//! hoisting them up to the project root. Tools like React Native that
+//! expect to find their dependencies next to the workspace itself rely on
+//! this.
+//!
+//! Also home to the simpler package name patterns used by `hoist-pattern`
+//! and `public-hoist-pattern`.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use crate::hook_scripts::glob_match;
+
+/// Reads the `workspaces.nohoist` patterns from the `package.json` in
+/// `dir`, if it has any. The plain array form of `workspaces` can't declare
+/// any.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn load(dir: &Path) -> Vec<String> {
+    let Ok(manifest) = async_std::fs::read_to_string(dir.join("package.json")).await else {
+        return Vec::new();
+    };
+    let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&manifest) else {
+        return Vec::new();
+    };
+    manifest["workspaces"]["nohoist"]
+        .as_array()
+        .map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|pattern| pattern.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `pattern` matches `path`, a `/`-separated chain of package
+/// names. `**` matches any number of packages, and `*` matches any
+/// sequence of characters within a single path segment.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<_>>();
+    let path = path.split('/').collect::<Vec<_>>();
+    matches_segments(&pattern, &path)
+}
+
+/// Whether `name` matches a list of package name patterns, like
+/// `hoist-pattern`'s. Patterns starting with `!` exclude whatever they
+/// match, and later patterns win over earlier ones.
+pub(crate) fn matches_name(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .fold(false, |matched, pattern| match pattern.strip_prefix('!') {
+            Some(negated) if glob_match(negated, name) => false,
+            Some(_) => matched,
+            None => matched || glob_match(pattern, name),
+        })
+}
+
+fn matches_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path)) => glob_match(segment, name) && matches_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globstars() {
+        assert!(matches("**/react-native", "react-native"));
+        assert!(matches("**/react-native", "app/react-native"));
+        assert!(!matches("**/react-native", "app/react-native/metro"));
+        assert!(matches("**/react-native/**", "app/react-native/metro/x"));
+        assert!(matches("app/react-*", "app/react-native"));
+        assert!(!matches("app/react-native", "other/react-native"));
+        assert!(matches("**/@babel/*", "app/@babel/core"));
+    }
+
+    #[test]
+    fn name_patterns() {
+        let patterns = ["eslint*", "*prettier*", "!eslint-config-*"]
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect::<Vec<_>>();
+        assert!(matches_name(&patterns, "eslint"));
+        assert!(matches_name(&patterns, "eslint-plugin-react"));
+        assert!(matches_name(
+            &patterns,
+            "@trivago/prettier-plugin-sort-imports"
+        ));
+        assert!(!matches_name(&patterns, "eslint-config-prettier-ish"));
+        assert!(!matches_name(&patterns, "react"));
+        assert!(!matches_name(&[], "eslint"));
+    }
+}
@@ -0,0 +1,498 @@
+//! Support for `patchedDependencies`: unified diffs that get applied to
+//! packages right after they're extracted into `node_modules/`.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::path::{Component, PathBuf};
+
+use indexmap::IndexMap;
+use nassun::{package::Package, PackageResolution};
+use node_semver::Range;
+use ssri::Integrity;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::access::{AccessKind, AccessTracker};
+use crate::error::NodeMaintainerError;
+
+/// All the patches configured for a project, keyed by the package they apply
+/// to: either a bare package name, or `<name>@<semver range>`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Patches {
+    patches: IndexMap<String, (PatchTarget, Patch)>,
+}
+
+#[derive(Debug, Clone)]
+struct PatchTarget {
+    name: String,
+    range: Option<Range>,
+}
+
+impl Patches {
+    /// Reads all configured patches. Patches listed in the project's
+    /// `package.json` under `patchedDependencies` are loaded first, and
+    /// `configured` entries take precedence over them. Patch paths are
+    /// relative to `root`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn load(
+        root: &Path,
+        configured: &IndexMap<String, PathBuf>,
+    ) -> Result<Self, NodeMaintainerError> {
+        let mut specs = IndexMap::new();
+        if let Ok(manifest) = async_std::fs::read_to_string(root.join("package.json")).await {
+            if let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&manifest) {
+                if let Some(patched) = manifest["patchedDependencies"].as_object() {
+                    for (spec, path) in patched {
+                        if let Some(path) = path.as_str() {
+                            specs.insert(spec.clone(), PathBuf::from(path));
+                        }
+                    }
+                }
+            }
+        }
+        specs.extend(configured.clone());
+
+        let mut patches = IndexMap::new();
+        for (spec, path) in specs {
+            let path = root.join(path);
+            let contents = async_std::fs::read_to_string(&path)
+                .await
+                .map_err(|e| NodeMaintainerError::PatchReadError(path.clone(), e))?;
+            let target = PatchTarget::parse(&spec)?;
+            let patch = Patch::parse(path, &contents)?;
+            patches.insert(spec, (target, patch));
+        }
+        Ok(Self { patches })
+    }
+
+    /// Specs of all the configured patches.
+    pub(crate) fn specs(&self) -> impl Iterator<Item = &str> {
+        self.patches.keys().map(|spec| spec.as_str())
+    }
+
+    /// Finds the patch that applies to `pkg`, if any, along with the spec it
+    /// was configured under. Patches for a specific version range win over
+    /// patches for any version of a package.
+    pub(crate) fn find(&self, pkg: &Package) -> Option<(&str, &Patch)> {
+        let version = match pkg.resolved() {
+            PackageResolution::Npm { version, .. } => Some(version),
+            _ => None,
+        };
+        let mut fallback = None;
+        for (spec, (target, patch)) in &self.patches {
+            if target.name != pkg.name() {
+                continue;
+            }
+            match (&target.range, version) {
+                (Some(range), Some(version)) if range.satisfies(version) => {
+                    return Some((spec, patch));
+                }
+                (None, _) => fallback = Some((spec.as_str(), patch)),
+                _ => {}
+            }
+        }
+        fallback
+    }
+}
+
+impl PatchTarget {
+    fn parse(spec: &str) -> Result<Self, NodeMaintainerError> {
+        if spec.is_empty() {
+            return Err(NodeMaintainerError::InvalidPatchSpec(spec.into()));
+        }
+        // Skip the first character so we don't split on a scope's `@`.
+        match spec[1..].rfind('@').map(|idx| idx + 1) {
+            Some(idx) => Ok(Self {
+                name: spec[..idx].into(),
+                range: Some(
+                    spec[idx + 1..]
+                        .parse()
+                        .map_err(|_| NodeMaintainerError::InvalidPatchSpec(spec.into()))?,
+                ),
+            }),
+            None => Ok(Self {
+                name: spec.into(),
+                range: None,
+            }),
+        }
+    }
+}
+
+/// A parsed unified diff, possibly touching multiple files.
+#[derive(Debug, Clone)]
+pub(crate) struct Patch {
+    path: PathBuf,
+    integrity: Integrity,
+    files: Vec<FilePatch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilePatch {
+    path: PathBuf,
+    kind: FilePatchKind,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilePatchKind {
+    Create,
+    Modify,
+    Delete,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Hunk {
+    old_start: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+    old_missing_newline: bool,
+    new_missing_newline: bool,
+}
+
+impl Patch {
+    /// Hash of the patch file's contents, which gets recorded in the
+    /// lockfile.
+    pub(crate) fn integrity(&self) -> &Integrity {
+        &self.integrity
+    }
+
+    fn parse(path: PathBuf, contents: &str) -> Result<Self, NodeMaintainerError> {
+        let parse_err = |line: usize, msg: &str| {
+            NodeMaintainerError::PatchParseError(path.clone(), line + 1, msg.into())
+        };
+        // Carriage returns are kept, so patches for CRLF files still match.
+        let lines = contents
+            .split_inclusive('\n')
+            .map(|l| l.strip_suffix('\n').unwrap_or(l))
+            .collect::<Vec<_>>();
+        let mut files = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let (Some(old), Some(new)) = (
+                lines[i].strip_prefix("--- "),
+                lines.get(i + 1).and_then(|l| l.strip_prefix("+++ ")),
+            ) else {
+                // Anything outside of file sections (`diff --git` lines,
+                // commit messages, etc) is ignored, just like `patch` does.
+                i += 1;
+                continue;
+            };
+            let old = diff_path(old);
+            let new = diff_path(new);
+            let (path, kind) = match (old, new) {
+                (None, Some(new)) => (new, FilePatchKind::Create),
+                (Some(old), None) => (old, FilePatchKind::Delete),
+                (Some(_), Some(new)) => (new, FilePatchKind::Modify),
+                (None, None) => return Err(parse_err(i, "file header has no paths")),
+            };
+            if path.is_absolute()
+                || path
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                return Err(parse_err(
+                    i,
+                    "patched file paths must stay inside the package",
+                ));
+            }
+            i += 2;
+
+            let mut hunks = Vec::new();
+            while let Some(header) = lines.get(i).and_then(|l| l.strip_prefix("@@ ")) {
+                let (old_start, mut old_len, mut new_len) =
+                    parse_hunk_header(header).ok_or_else(|| parse_err(i, "invalid hunk header"))?;
+                i += 1;
+                let mut hunk = Hunk {
+                    old_start,
+                    ..Default::default()
+                };
+                while old_len > 0 || new_len > 0 {
+                    let Some(line) = lines.get(i) else {
+                        return Err(parse_err(i, "hunk ends early"));
+                    };
+                    // Some editors strip the trailing space off of empty
+                    // context lines.
+                    let (tag, text) = if line.is_empty() {
+                        (' ', "")
+                    } else {
+                        (
+                            line.as_bytes()[0] as char,
+                            line.get(1..).unwrap_or_default(),
+                        )
+                    };
+                    match tag {
+                        ' ' if old_len > 0 && new_len > 0 => {
+                            hunk.old.push(text.into());
+                            hunk.new.push(text.into());
+                            old_len -= 1;
+                            new_len -= 1;
+                        }
+                        '-' if old_len > 0 => {
+                            hunk.old.push(text.into());
+                            old_len -= 1;
+                        }
+                        '+' if new_len > 0 => {
+                            hunk.new.push(text.into());
+                            new_len -= 1;
+                        }
+                        '\\' => {}
+                        _ => return Err(parse_err(i, "unexpected line in hunk")),
+                    }
+                    i += 1;
+                    if lines.get(i).map(|l| l.starts_with('\\')).unwrap_or(false) {
+                        match tag {
+                            '-' => hunk.old_missing_newline = true,
+                            '+' => hunk.new_missing_newline = true,
+                            _ => {
+                                hunk.old_missing_newline = true;
+                                hunk.new_missing_newline = true;
+                            }
+                        }
+                        i += 1;
+                    }
+                }
+                hunks.push(hunk);
+            }
+            files.push(FilePatch { path, kind, hunks });
+        }
+        if files.is_empty() {
+            return Err(parse_err(0, "no file changes found"));
+        }
+        Ok(Self {
+            integrity: Integrity::from(contents),
+            path,
+            files,
+        })
+    }
+
+    /// Applies this patch to the package extracted at `dir`. Patched files
+    /// are always written anew, rather than in place, so that files
+    /// hard-linked from the cache are left untouched.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn apply(
+        &self,
+        name: &str,
+        dir: &Path,
+        access: &AccessTracker,
+    ) -> Result<(), NodeMaintainerError> {
+        let apply_err = |file: &FilePatch| {
+            NodeMaintainerError::PatchApplyError(
+                self.path.clone(),
+                name.into(),
+                file.path.display().to_string(),
+            )
+        };
+        for file in &self.files {
+            let target = dir.join(&file.path);
+            match file.kind {
+                FilePatchKind::Create => {
+                    let contents = apply_hunks("", &file.hunks).ok_or_else(|| apply_err(file))?;
+                    access.check(AccessKind::Write, &target)?;
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    if target.exists() {
+                        std::fs::remove_file(&target)?;
+                    }
+                    std::fs::write(&target, contents)?;
+                }
+                FilePatchKind::Delete => {
+                    if !target.exists() {
+                        return Err(apply_err(file));
+                    }
+                    access.check(AccessKind::Remove, &target)?;
+                    std::fs::remove_file(&target)?;
+                }
+                FilePatchKind::Modify => {
+                    let original = std::fs::read_to_string(&target).map_err(|_| apply_err(file))?;
+                    let contents =
+                        apply_hunks(&original, &file.hunks).ok_or_else(|| apply_err(file))?;
+                    let permissions = target.metadata()?.permissions();
+                    access.check(AccessKind::Write, &target)?;
+                    std::fs::remove_file(&target)?;
+                    std::fs::write(&target, contents)?;
+                    std::fs::set_permissions(&target, permissions)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the path from a `---`/`+++` file header, dropping any trailing
+/// timestamp and the leading `a/`/`b/` component. Returns `None` for
+/// `/dev/null`.
+fn diff_path(header: &str) -> Option<PathBuf> {
+    let path = header.split('\t').next().unwrap_or_default().trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(PathBuf::from(match path.split_once('/') {
+        Some((_, rest)) => rest,
+        None => path,
+    }))
+}
+
+/// Parses the `-1,2 +3,4 @@` part of a hunk header into the old start line,
+/// and the old and new line counts.
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut ranges = header.split_whitespace();
+    let old = ranges.next()?.strip_prefix('-')?;
+    let new = ranges.next()?.strip_prefix('+')?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = parse_range(old)?;
+    let (_, new_len) = parse_range(new)?;
+    Some((old_start, old_len, new_len))
+}
+
+/// Applies `hunks` to `original`. Each hunk is first tried at the line it
+/// says it starts at, and then anywhere after the previous hunk, to allow
+/// for small shifts in the original file. Returns `None` if any hunk's
+/// context can't be found.
+fn apply_hunks(original: &str, hunks: &[Hunk]) -> Option<String> {
+    let mut lines = original
+        .split_inclusive('\n')
+        .map(|l| l.trim_end_matches('\n').to_string())
+        .collect::<Vec<_>>();
+    let mut trailing_newline = original.is_empty() || original.ends_with('\n');
+    let mut min_pos = 0;
+    let mut offset: isize = 0;
+    for hunk in hunks {
+        let matches_at = |pos: usize| {
+            pos >= min_pos
+                && pos + hunk.old.len() <= lines.len()
+                && lines[pos..pos + hunk.old.len()] == hunk.old[..]
+        };
+        // Zero-length hunks use the line *before* the change as their start.
+        let expected = if hunk.old.is_empty() {
+            hunk.old_start as isize
+        } else {
+            hunk.old_start as isize - 1
+        } + offset;
+        let expected = expected.clamp(0, lines.len() as isize) as usize;
+        let pos = if matches_at(expected) {
+            expected
+        } else {
+            (min_pos..=lines.len().saturating_sub(hunk.old.len())).find(|pos| matches_at(*pos))?
+        };
+        let at_end = pos + hunk.old.len() == lines.len();
+        lines.splice(pos..pos + hunk.old.len(), hunk.new.iter().cloned());
+        offset += hunk.new.len() as isize - hunk.old.len() as isize;
+        min_pos = pos + hunk.new.len();
+        if at_end {
+            if hunk.new_missing_newline {
+                trailing_newline = false;
+            } else if hunk.old_missing_newline || !hunk.new.is_empty() {
+                trailing_newline = true;
+            }
+        }
+    }
+    let mut out = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        out.push('\n');
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Patch {
+        Patch::parse(PathBuf::from("test.patch"), contents).unwrap()
+    }
+
+    #[test]
+    fn parses_git_diffs() {
+        let patch = parse(
+            "diff --git a/index.js b/index.js\n\
+             index 1234567..89abcde 100644\n\
+             --- a/index.js\n\
+             +++ b/index.js\n\
+             @@ -1,3 +1,3 @@\n \
+             const a = 1;\n\
+             -const b = 2;\n\
+             +const b = 3;\n \
+             module.exports = a + b;\n\
+             diff --git a/lib/new.js b/lib/new.js\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/lib/new.js\n\
+             @@ -0,0 +1 @@\n\
+             +module.exports = 'new';\n",
+        );
+        assert_eq!(patch.files.len(), 2);
+        assert_eq!(patch.files[0].path, PathBuf::from("index.js"));
+        assert_eq!(patch.files[0].kind, FilePatchKind::Modify);
+        assert_eq!(patch.files[1].path, PathBuf::from("lib/new.js"));
+        assert_eq!(patch.files[1].kind, FilePatchKind::Create);
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_package() {
+        let res = Patch::parse(
+            PathBuf::from("test.patch"),
+            "--- a/../evil.js\n+++ b/../evil.js\n@@ -1 +1 @@\n-a\n+b\n",
+        );
+        assert!(matches!(
+            res,
+            Err(NodeMaintainerError::PatchParseError(_, 1, _))
+        ));
+    }
+
+    #[test]
+    fn applies_hunks() {
+        let patch = parse(
+            "--- a/index.js\n\
+             +++ b/index.js\n\
+             @@ -2,3 +2,4 @@\n \
+             b\n\
+             -c\n\
+             +C\n\
+             +C2\n \
+             d\n",
+        );
+        assert_eq!(
+            apply_hunks("a\nb\nc\nd\ne\n", &patch.files[0].hunks).as_deref(),
+            Some("a\nb\nC\nC2\nd\ne\n")
+        );
+        // Hunks can move around a bit.
+        assert_eq!(
+            apply_hunks("z\na\nb\nc\nd\n", &patch.files[0].hunks).as_deref(),
+            Some("z\na\nb\nC\nC2\nd\n")
+        );
+        // But their context has to match.
+        assert_eq!(apply_hunks("a\nb\nx\nd\n", &patch.files[0].hunks), None);
+    }
+
+    #[test]
+    fn handles_missing_trailing_newlines() {
+        let patch = parse(
+            "--- a/index.js\n\
+             +++ b/index.js\n\
+             @@ -1 +1 @@\n\
+             -a\n\
+             \\ No newline at end of file\n\
+             +b\n",
+        );
+        assert_eq!(
+            apply_hunks("a", &patch.files[0].hunks).as_deref(),
+            Some("b\n")
+        );
+    }
+
+    #[test]
+    fn parses_patch_targets() {
+        let target = PatchTarget::parse("@scope/pkg@^1.2.0").unwrap();
+        assert_eq!(target.name, "@scope/pkg");
+        assert!(target.range.is_some());
+        let target = PatchTarget::parse("@scope/pkg").unwrap();
+        assert_eq!(target.name, "@scope/pkg");
+        assert!(target.range.is_none());
+        assert!(PatchTarget::parse("").is_err());
+    }
+}
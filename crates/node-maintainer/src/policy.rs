@@ -0,0 +1,266 @@
+//! Package policies: rules about which packages are allowed to end up in the
+//! dependency tree, usually loaded from `oro-policy.kdl` in the project root.
+
+use std::fmt;
+
+use kdl::KdlNode;
+use nassun::{package::Package, PackageResolution};
+use node_semver::Range;
+use oro_common::{LicenseExpression, LicenseRequirement, Manifest};
+use ssri::Algorithm;
+
+use crate::error::NodeMaintainerError;
+use crate::IntoKdl;
+
+/// A set of rules that every package in the dependency tree has to follow.
+///
+/// Policies are written in KDL:
+///
+/// ```kdl
+/// deny {
+///     package "event-stream"
+///     package "lodash" "<4.17.21"
+///     license "GPL-3.0-only" "AGPL-3.0-only"
+/// }
+/// allow {
+///     license "MIT" "ISC" "Apache-2.0"
+/// }
+/// require {
+///     integrity "sha512"
+/// }
+/// ```
+///
+/// Denied packages are blocked entirely, or only for versions matching the
+/// given semver range. Packages also have to be usable under a license that
+/// isn't denied, and if any licenses are allowed explicitly, under one of
+/// those. Registry packages can also be required to have integrity hashes
+/// at least as strong as a given algorithm, so ones with only a `sha1`
+/// hash, or none at all, are blocked.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    denied_packages: Vec<(String, Option<Range>)>,
+    denied_licenses: Vec<String>,
+    allowed_licenses: Vec<String>,
+    required_integrity: Option<Algorithm>,
+}
+
+impl Policy {
+    pub fn from_kdl(kdl: impl IntoKdl) -> Result<Self, NodeMaintainerError> {
+        let kdl = kdl.into_kdl()?;
+        let mut policy = Self::default();
+        for section in kdl.nodes() {
+            let section_name = section.name().value();
+            if !matches!(section_name, "deny" | "allow" | "require") {
+                return Err(NodeMaintainerError::InvalidPolicyRule(section.clone()));
+            }
+            let Some(rules) = section.children() else {
+                continue;
+            };
+            for rule in rules.nodes() {
+                let args = rule_args(rule)?;
+                match (section_name, rule.name().value()) {
+                    ("deny", "package") if args.len() == 1 || args.len() == 2 => {
+                        let range = args
+                            .get(1)
+                            .map(|range| range.parse())
+                            .transpose()
+                            .map_err(|_| NodeMaintainerError::InvalidPolicyRule(rule.clone()))?;
+                        policy.denied_packages.push((args[0].clone(), range));
+                    }
+                    ("deny", "license") if !args.is_empty() => policy.denied_licenses.extend(args),
+                    ("allow", "license") if !args.is_empty() => {
+                        policy.allowed_licenses.extend(args)
+                    }
+                    ("require", "integrity") if args.len() == 1 => {
+                        policy.required_integrity = Some(match &args[0][..] {
+                            "sha256" => Algorithm::Sha256,
+                            "sha384" => Algorithm::Sha384,
+                            "sha512" => Algorithm::Sha512,
+                            _ => return Err(NodeMaintainerError::InvalidPolicyRule(rule.clone())),
+                        });
+                    }
+                    _ => return Err(NodeMaintainerError::InvalidPolicyRule(rule.clone())),
+                }
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Whether this policy restricts licenses at all. Checking licenses
+    /// requires full package metadata, so it's skipped when it isn't
+    /// needed.
+    pub(crate) fn checks_licenses(&self) -> bool {
+        !self.denied_licenses.is_empty() || !self.allowed_licenses.is_empty()
+    }
+
+    /// Checks `pkg` against the package rules, returning which rule blocks
+    /// it and why, if any.
+    pub(crate) fn check_package(&self, pkg: &Package) -> Option<(PolicyRule, String)> {
+        let version = match pkg.resolved() {
+            PackageResolution::Npm { version, .. } => Some(version),
+            _ => None,
+        };
+        if let (Some(required), PackageResolution::Npm { integrity, .. }) =
+            (self.required_integrity, pkg.resolved())
+        {
+            let algorithms = integrity
+                .iter()
+                .flat_map(|integrity| &integrity.hashes)
+                .map(|hash| hash.algorithm)
+                .collect::<Vec<_>>();
+            if algorithms.is_empty() {
+                return Some((
+                    PolicyRule::Integrity,
+                    format!("has no integrity hash, but `{required}` integrity is required"),
+                ));
+            }
+            if !algorithms
+                .iter()
+                .any(|algorithm| strength(*algorithm) >= strength(required))
+            {
+                let algorithms = algorithms
+                    .iter()
+                    .map(|algorithm| format!("`{algorithm}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Some((
+                    PolicyRule::Integrity,
+                    format!(
+                        "only has {algorithms} integrity, but `{required}` integrity is required"
+                    ),
+                ));
+            }
+        }
+        self.denied_packages
+            .iter()
+            .find(|(name, range)| {
+                name == pkg.name()
+                    && match (range, version) {
+                        (Some(range), Some(version)) => range.satisfies(version),
+                        (Some(_), None) => false,
+                        (None, _) => true,
+                    }
+            })
+            .map(|(_, range)| {
+                let reason = match range {
+                    Some(range) => format!("matches the denied range `{range}`"),
+                    None => "is a denied package".into(),
+                };
+                (PolicyRule::Package, reason)
+            })
+    }
+
+    /// Checks the license in `manifest` against the license rules,
+    /// returning why it's blocked, if it is.
+    pub(crate) fn check_license(&self, manifest: &Manifest) -> Option<String> {
+        if !self.checks_licenses() {
+            return None;
+        }
+        let Some(expr) = license_expression(manifest) else {
+            return (!self.allowed_licenses.is_empty()).then(|| {
+                "has no valid license, so it can't be checked against the allowed licenses".into()
+            });
+        };
+        let accept = |req: &LicenseRequirement| {
+            !self.denied_licenses.iter().any(|id| req.matches(id))
+                && (self.allowed_licenses.is_empty()
+                    || self.allowed_licenses.iter().any(|id| req.matches(id)))
+        };
+        (!expr.satisfies(&accept)).then(|| format!("uses a license that isn't allowed: `{expr}`"))
+    }
+}
+
+/// How strong an integrity algorithm is, for comparing against the required
+/// one. Anything weaker than `sha256`, like `sha1`, never satisfies it.
+fn strength(algorithm: Algorithm) -> u8 {
+    match algorithm {
+        Algorithm::Sha512 => 3,
+        Algorithm::Sha384 => 2,
+        Algorithm::Sha256 => 1,
+        _ => 0,
+    }
+}
+
+fn rule_args(rule: &KdlNode) -> Result<Vec<String>, NodeMaintainerError> {
+    rule.entries()
+        .iter()
+        .map(|entry| match (entry.name(), entry.value().as_string()) {
+            (None, Some(arg)) => Ok(arg.to_string()),
+            _ => Err(NodeMaintainerError::InvalidPolicyRule(rule.clone())),
+        })
+        .collect()
+}
+
+/// Reads a package's license from its `license` field, or from the legacy
+/// `licenses` array if that's all it has.
+fn license_expression(manifest: &Manifest) -> Option<LicenseExpression> {
+    if let Some(license) = &manifest.license {
+        return license.parse().ok();
+    }
+    let licenses = manifest
+        ._rest
+        .get("licenses")?
+        .as_array()?
+        .iter()
+        .filter_map(|license| license.as_str().or_else(|| license["type"].as_str()))
+        .collect::<Vec<_>>();
+    LicenseExpression::from_legacy_licenses(&licenses).ok()
+}
+
+/// The kind of rule a [`PolicyViolation`] broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolicyRule {
+    /// A `deny { package ... }` rule.
+    Package,
+    /// A `deny { license ... }` or `allow { license ... }` rule.
+    License,
+    /// A `require { integrity ... }` rule.
+    Integrity,
+    /// A [`crate::NodeMaintainerOptions::require_provenance`] pattern.
+    Provenance,
+}
+
+impl PolicyRule {
+    /// A short, stable name for the rule, like `license`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Package => "package",
+            Self::License => "license",
+            Self::Integrity => "integrity",
+            Self::Provenance => "provenance",
+        }
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A package that isn't allowed by the project's [`Policy`], along with how
+/// it got into the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    /// Name of the blocked package.
+    pub name: String,
+    /// What was resolved for the package. This is usually a version, but
+    /// might be a path or URL for packages that didn't come from a
+    /// registry.
+    pub found: String,
+    /// The kind of rule that blocks the package.
+    pub rule: PolicyRule,
+    /// Why the package is blocked.
+    pub reason: String,
+    /// Chain of package names leading from the root project to the blocked
+    /// package, starting with the root's direct dependency and ending with
+    /// the package itself.
+    pub path: Vec<String>,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}@{} {}.", self.name, self.found, self.reason)?;
+        write!(f, "  pulled in by: {}", self.path.join(" > "))
+    }
+}
@@ -0,0 +1,34 @@
+use std::str::FromStr;
+
+use crate::error::NodeMaintainerError;
+
+/// Controls which version gets picked when resolving a semver range.
+///
+/// Resolving to the lowest versions is mostly useful for libraries, to check
+/// that the lower bounds of their dependency ranges actually work.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionMode {
+    /// Pick the highest version that satisfies the range, preferring the
+    /// default dist-tag when it matches.
+    #[default]
+    Highest,
+    /// Pick the lowest version that satisfies the range, for every
+    /// dependency in the tree.
+    Lowest,
+    /// Pick the lowest version for the root project's direct dependencies,
+    /// and the highest version for everything else.
+    LowestDirect,
+}
+
+impl FromStr for ResolutionMode {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "highest" => Ok(Self::Highest),
+            "lowest" => Ok(Self::Lowest),
+            "lowest-direct" => Ok(Self::LowestDirect),
+            _ => Err(NodeMaintainerError::InvalidResolutionMode(s.into())),
+        }
+    }
+}
@@ -4,22 +4,34 @@ use std::path::Path;
 use std::sync::Arc;
 
 use async_std::sync::Mutex;
-#[cfg(not(target_arch = "wasm32"))]
-use colored::Colorize;
-use futures::{StreamExt, TryFutureExt};
+use futures::{FutureExt, StreamExt};
 use indexmap::IndexMap;
 use nassun::client::Nassun;
+#[cfg(target_arch = "wasm32")]
+use nassun::error::NassunError;
 use nassun::package::Package;
-use nassun::PackageSpec;
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::NassunError;
+use nassun::{PackageResolution, PackageSpec};
 use oro_common::{CorgiManifest, CorgiVersionMetadata};
 use petgraph::stable_graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 use unicase::UniCase;
 
+use crate::catalogs::Catalogs;
+use crate::conflict::{ConflictRequester, PeerProblem, PeerProblemKind, ResolutionConflict};
 use crate::error::NodeMaintainerError;
+use crate::explain::Origin;
 use crate::graph::{DepType, Edge, Graph, Node};
 #[cfg(not(target_arch = "wasm32"))]
+use crate::linkers::ExtractPipeline;
+use crate::nohoist;
+use crate::patches::Patches;
+use crate::policy::{Policy, PolicyRule, PolicyViolation};
+use crate::resolution_mode::ResolutionMode;
+use crate::workspaces::Workspaces;
+#[cfg(not(target_arch = "wasm32"))]
 use crate::META_FILE_NAME;
 use crate::{Lockfile, LockfileNode, ProgressAdded, ProgressHandler};
 
@@ -33,14 +45,46 @@ struct NodeDependency {
 
 pub(crate) struct Resolver<'a> {
     pub(crate) nassun: Nassun,
+    /// Used instead of `nassun` for the root's direct dependencies, when
+    /// they should be resolved differently from the rest of the tree.
+    pub(crate) direct_nassun: Option<Nassun>,
     pub(crate) graph: Graph,
     pub(crate) concurrency: usize,
     pub(crate) locked: bool,
+    pub(crate) dedupe: bool,
+    /// Warn about peer dependency conflicts instead of failing.
+    pub(crate) force: bool,
     #[allow(dead_code)]
     pub(crate) root: &'a Path,
     pub(crate) actual_tree: Option<Lockfile>,
+    pub(crate) patches: &'a Patches,
+    pub(crate) catalogs: &'a Catalogs,
+    pub(crate) workspaces: &'a Workspaces,
+    pub(crate) policy: &'a Policy,
+    /// Patterns for registry packages that have to come with provenance.
+    pub(crate) require_provenance: &'a [String],
+    /// Record every policy violation in the graph instead of failing on
+    /// the first one.
+    pub(crate) report_policy: bool,
     pub(crate) on_resolution_added: Option<ProgressAdded>,
     pub(crate) on_resolve_progress: Option<ProgressHandler>,
+    /// Packages are handed to this as they're placed, so they can start
+    /// extracting while the rest of the tree resolves.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) pipeline: Option<Arc<ExtractPipeline>>,
+    /// Shuffles the order fetched packages get processed in, to check that
+    /// it doesn't change the resolved tree.
+    pub(crate) shuffle: Option<Shuffle>,
+    /// Used to tell whether a range resolved to its lowest or highest
+    /// version, when explaining resolutions.
+    pub(crate) resolution_mode: ResolutionMode,
+    /// Whether versions published too recently get skipped, which is why a
+    /// range might not resolve to its highest version, when explaining
+    /// resolutions.
+    pub(crate) release_cutoff: bool,
+    /// Record how each node got placed, so
+    /// [`crate::NodeMaintainer::explain`] can say why.
+    pub(crate) explain: bool,
 }
 
 impl<'a> Resolver<'a> {
@@ -51,12 +95,22 @@ impl<'a> Resolver<'a> {
         #[cfg(not(target_arch = "wasm32"))]
         let start = std::time::Instant::now();
 
-        #[cfg(not(target_arch = "wasm32"))]
-        self.load_actual().await?;
-
         let (package_sink, package_stream) = futures::channel::mpsc::unbounded();
         let mut q = VecDeque::new();
-        q.push_back(self.graph.root);
+        // Reuse whatever we can from an existing lockfile (or the actual
+        // tree), so small changes to the root's dependencies don't require
+        // resolving the whole tree again.
+        if let Some(lock) = &lockfile {
+            q.extend(self.seed_from_lockfile(lock, Origin::Lockfile).await?);
+        } else if let Some(actual_tree) = self.actual_tree.take() {
+            q.extend(
+                self.seed_from_lockfile(&actual_tree, Origin::Installed)
+                    .await?,
+            );
+            self.actual_tree = Some(actual_tree);
+        } else {
+            q.push_back(self.graph.root);
+        }
 
         // Number of dependencies queued for processing in `package_stream`
         let mut in_flight = 0;
@@ -69,8 +123,14 @@ impl<'a> Resolver<'a> {
         let fetches: IndexMap<PackageSpec, Vec<NodeDependency>> = IndexMap::new();
         let fetches = Arc::new(Mutex::new(fetches));
 
+        let root = self.graph.root;
+        // The stream lives for the whole loop, which needs `self` mutably,
+        // so it gets its own handles to the clients.
+        let nassun = self.nassun.clone();
+        let direct_nassun = self.direct_nassun.clone();
         let mut package_stream = package_stream
             .map(|dep: NodeDependency| {
+                let direct = dep.node_idx == root;
                 let maybe_spec = if let Some(mut fetches) = fetches.try_lock() {
                     if let Some(list) = fetches.get_mut(&dep.spec) {
                         // Package fetch is already in-flight, add dependency
@@ -80,19 +140,21 @@ impl<'a> Resolver<'a> {
                     } else {
                         // Fetch package since we are the first one to get here.
                         fetches.insert(dep.spec.clone(), vec![dep.clone()]);
-                        Some(dep.spec.clone())
+                        Some((dep.spec.clone(), direct))
                     }
                 } else {
                     // Mutex is locked - fetch the package
-                    Some(dep.spec)
+                    Some((dep.spec, direct))
                 };
                 futures::future::ready(maybe_spec)
             })
             .filter_map(|maybe_spec| maybe_spec)
-            .map(|spec| {
-                self.nassun
-                    .resolve_spec(spec.clone())
-                    .map_ok(move |p| (p, spec))
+            .map(|(spec, direct)| {
+                let nassun = match &direct_nassun {
+                    Some(direct_nassun) if direct => direct_nassun,
+                    _ => &nassun,
+                };
+                nassun.resolve_spec(spec.clone()).map(move |p| (p, spec))
             })
             .buffer_unordered(self.concurrency)
             .ready_chunks(self.concurrency);
@@ -100,11 +162,18 @@ impl<'a> Resolver<'a> {
         // Start iterating over the queue. We'll be adding things to it as we find them.
         while !q.is_empty() || in_flight != 0 {
             while let Some(node_idx) = q.pop_front() {
+                #[cfg(not(target_arch = "wasm32"))]
+                self.load_nohoist(node_idx).await;
                 let mut names = HashSet::new();
                 // Grab all the deps from the current package and fire off a
                 // lookup. These will be resolved concurrently.
                 for (name, (spec, dep_type)) in self.graph[node_idx].dependency_reqs.clone() {
-                    if names.contains(&name) {
+                    // Dependencies that were already hooked up when seeding
+                    // the graph from the lockfile don't need to be resolved
+                    // again.
+                    if names.contains(&name)
+                        || self.graph[node_idx].dependencies.contains_key(&name)
+                    {
                         continue;
                     } else {
                         names.insert(name.clone());
@@ -160,7 +229,16 @@ impl<'a> Resolver<'a> {
                                     lockfile_node.into(),
                                     Some(target_path),
                                 )?;
+                                self.record(
+                                    child_idx,
+                                    if lockfile.is_some() {
+                                        Origin::Lockfile
+                                    } else {
+                                        Origin::Installed
+                                    },
+                                );
                                 q.push_back(child_idx);
+                                self.stage(child_idx);
 
                                 if let Some(handler) = &self.on_resolve_progress {
                                     handler(&self.graph[child_idx].package);
@@ -184,9 +262,21 @@ impl<'a> Resolver<'a> {
 
             // Order doesn't matter here: each node name is unique, so we
             // don't have to worry about races messing with placement.
-            if let Some(packages) = package_stream.next().await {
-                for res in packages {
-                    let (package, spec) = res?;
+            if let Some(mut packages) = package_stream.next().await {
+                if let Some(shuffle) = &mut self.shuffle {
+                    shuffle.shuffle(&mut packages);
+                }
+                for (res, spec) in packages {
+                    let package = match res {
+                        Ok(package) => package,
+                        Err(NassunError::NoVersion { name, .. }) => {
+                            let deps = fetches.lock().await.remove(&spec).unwrap_or_default();
+                            return Err(NodeMaintainerError::NoMatchingVersion(Box::new(
+                                self.no_version_conflict(name, &deps),
+                            )));
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
                     let deps = fetches.lock().await.remove(&spec);
 
                     if let Some(deps) = deps {
@@ -201,19 +291,23 @@ impl<'a> Resolver<'a> {
 
                         #[cfg(not(target_arch = "wasm32"))]
                         if let Some(deprecated) = deprecated {
-                            tracing::warn!(
-                                "{} {}@{}: {}",
-                                "deprecated".on_magenta(),
-                                manifest.name.as_ref().unwrap(),
-                                manifest
-                                    .version
-                                    .as_ref()
-                                    .map(|v| v.to_string())
-                                    .unwrap_or_else(|| "unknown".into()),
-                                deprecated
+                            tracing::debug!(
+                                "{}@{} is deprecated: {deprecated}",
+                                package.name(),
+                                package.resolved()
                             );
                         }
 
+                        // Local packages, such as workspace packages, can
+                        // use the root project's catalogs and workspaces too.
+                        let manifest =
+                            if matches!(package.resolved(), PackageResolution::Dir { .. }) {
+                                self.workspaces
+                                    .apply(self.catalogs.apply(manifest.clone())?)
+                            } else {
+                                manifest.clone()
+                            };
+
                         for dep in deps {
                             if let Some(_child_idx) =
                                 Self::satisfy_dependency(&mut self.graph, &dep)?
@@ -234,7 +328,21 @@ impl<'a> Resolver<'a> {
                                 None,
                             )?;
 
+                            #[cfg(not(target_arch = "wasm32"))]
+                            {
+                                self.graph[child_idx].deprecated = deprecated.clone();
+                            }
+                            self.record(
+                                child_idx,
+                                Origin::Resolved {
+                                    requested: dep.spec.clone(),
+                                    lowest: self.resolves_lowest(dep.node_idx),
+                                    cutoff: self.release_cutoff,
+                                },
+                            );
+
                             q.push_back(child_idx);
+                            self.stage(child_idx);
 
                             if let Some(handler) = &self.on_resolve_progress {
                                 handler(&self.graph[child_idx].package);
@@ -256,6 +364,17 @@ impl<'a> Resolver<'a> {
             }
         }
 
+        if self.dedupe {
+            let removed = self.dedupe()?;
+            tracing::debug!("Removed {removed} duplicate packages while deduping");
+        }
+
+        self.check_policy().await?;
+
+        self.check_peers()?;
+
+        self.assign_patches();
+
         if self.locked {
             if let Some(lockfile) = lockfile {
                 if lockfile != self.graph.to_lockfile()? {
@@ -273,16 +392,523 @@ impl<'a> Resolver<'a> {
         Ok((self.graph, self.actual_tree))
     }
 
+    /// Places everything from `lockfile` that's still usable directly into
+    /// the graph, exactly where the lockfile has it. Packages that are only
+    /// reachable through root dependencies that were added, removed, or
+    /// changed since the lockfile was written are left out, so only that
+    /// part of the tree gets resolved again.
+    ///
+    /// Returns the nodes that still have unresolved dependencies, starting
+    /// with the root.
+    async fn seed_from_lockfile(
+        &mut self,
+        lockfile: &Lockfile,
+        origin: Origin,
+    ) -> Result<Vec<NodeIndex>, NodeMaintainerError> {
+        let root = self.graph.root;
+        let changed = Self::changed_root_deps(&self.graph[root], lockfile.root());
+
+        // Figure out which lockfile entries are still in use, by following
+        // dependencies the same way Node.js would look them up.
+        let mut in_use = HashSet::new();
+        let mut stack = Vec::new();
+        for name in self.graph[root].dependency_reqs.keys() {
+            if changed.contains(name) {
+                continue;
+            }
+            if let Some((path, node)) = lockfile.find_dep("", name) {
+                if in_use.insert(path) {
+                    stack.push((path, node));
+                }
+            }
+        }
+        while let Some((path, node)) = stack.pop() {
+            for name in node
+                .dependencies
+                .keys()
+                .chain(node.optional_dependencies.keys())
+            {
+                let Some((dep_path, dep)) = lockfile.find_dep(path, name) else {
+                    continue;
+                };
+                // Toplevel copies of changed root dependencies are going to
+                // be replaced, so anything using them has to be re-resolved.
+                if changed.contains(&UniCase::new(dep_path.to_string())) {
+                    continue;
+                }
+                if in_use.insert(dep_path) {
+                    stack.push((dep_path, dep));
+                }
+            }
+        }
+
+        // Place the surviving packages, parents first.
+        let mut reused = lockfile
+            .packages()
+            .iter()
+            .filter(|(path, _)| in_use.contains(path.as_str()))
+            .collect::<Vec<_>>();
+        reused.sort_by(|(a_path, a), (b_path, b)| {
+            a.path
+                .len()
+                .cmp(&b.path.len())
+                .then_with(|| a_path.cmp(b_path))
+        });
+        let mut placed = IndexMap::new();
+        for (path, lockfile_node) in reused {
+            let parent_idx = if lockfile_node.path.len() <= 1 {
+                root
+            } else {
+                let parent_path = UniCase::from(
+                    lockfile_node.path[..lockfile_node.path.len() - 1]
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join("/node_modules/"),
+                );
+                match placed.get(&parent_path) {
+                    Some(parent_idx) => *parent_idx,
+                    None => continue,
+                }
+            };
+            let Some(package) = lockfile_node.to_package(&self.nassun).await? else {
+                continue;
+            };
+            let child_name = UniCase::new(package.name().to_string());
+            let child_idx =
+                self.graph
+                    .inner
+                    .add_node(Node::new(package, lockfile_node.clone().into(), false)?);
+            let child = &mut self.graph[child_idx];
+            child.idx = child_idx;
+            child.root = root;
+            child.parent = Some(parent_idx);
+            self.graph[parent_idx]
+                .children
+                .insert(child_name, child_idx);
+            placed.insert(path.clone(), child_idx);
+            self.record(child_idx, origin.clone());
+            self.stage(child_idx);
+            #[cfg(not(target_arch = "wasm32"))]
+            self.load_nohoist(child_idx).await;
+        }
+
+        // Hook up dependencies, and keep track of anything that's still
+        // missing so the resolver can take care of it.
+        let mut unresolved = vec![root];
+        for node_idx in std::iter::once(root).chain(placed.into_values()) {
+            let mut complete = true;
+            for (name, (spec, dep_type)) in self.graph[node_idx].dependency_reqs.clone() {
+                if node_idx == root && changed.contains(&name) {
+                    complete = false;
+                    continue;
+                }
+                let dep = NodeDependency {
+                    name,
+                    spec,
+                    dep_type,
+                    node_idx,
+                };
+                if let Some(child_idx) = Self::satisfy_dependency(&mut self.graph, &dep)? {
+                    if let Some(handler) = &self.on_resolution_added {
+                        handler();
+                    }
+                    if let Some(handler) = &self.on_resolve_progress {
+                        handler(&self.graph[child_idx].package);
+                    }
+                } else {
+                    complete = false;
+                }
+            }
+            if !complete && node_idx != root {
+                unresolved.push(node_idx);
+            }
+        }
+
+        tracing::debug!(
+            "Reused {} packages from the lockfile, {} root dependencies changed",
+            self.graph.inner.node_count() - 1,
+            changed.len()
+        );
+        Ok(unresolved)
+    }
+
+    /// Names of the root's dependencies that were added, removed, or had
+    /// their requested spec or type changed, compared to `locked`.
+    fn changed_root_deps(root: &Node, locked: &LockfileNode) -> HashSet<UniCase<String>> {
+        let mut previous = IndexMap::new();
+        for (deps, dep_type) in [
+            (&locked.dependencies, DepType::Prod),
+            (&locked.dev_dependencies, DepType::Dev),
+            (&locked.optional_dependencies, DepType::Opt),
+        ] {
+            for (name, spec) in deps {
+                previous.insert(UniCase::new(name.clone()), (spec, dep_type.clone()));
+            }
+        }
+        let mut changed = HashSet::new();
+        for (name, (spec, dep_type)) in &root.dependency_reqs {
+            match previous.remove(name) {
+                Some((old_spec, old_type))
+                    if *old_spec == spec.requested() && &old_type == dep_type => {}
+                _ => {
+                    changed.insert(name.clone());
+                }
+            }
+        }
+        changed.extend(previous.into_keys());
+        changed
+    }
+
+    /// Merges nested copies of packages into the copy that Node.js would
+    /// find further up the tree, if that one satisfies everything depending
+    /// on the nested copy. Anything nested under a removed copy is only used
+    /// from inside of it, so it gets removed along with it.
+    ///
+    /// Returns the number of nodes that were removed from the graph.
+    fn dedupe(&mut self) -> Result<usize, NodeMaintainerError> {
+        let root = self.graph.root;
+        let mut removed = 0;
+        loop {
+            // Shallow nodes go first, since removing them takes care of
+            // everything under them, too.
+            let mut candidates = self
+                .graph
+                .inner
+                .node_indices()
+                .filter(|idx| *idx != root && self.graph[*idx].parent != Some(root))
+                .collect::<Vec<_>>();
+            candidates.sort_by_key(|idx| self.graph[*idx].depth(&self.graph));
+            let mut changed = false;
+            for dupe_idx in candidates {
+                if !self.graph.inner.contains_node(dupe_idx) {
+                    continue;
+                }
+                let name = UniCase::new(self.graph[dupe_idx].package.name().to_string());
+                let Some(parent_idx) = self.graph[dupe_idx].parent else {
+                    continue;
+                };
+                let Some(kept_idx) = self.graph[parent_idx]
+                    .parent
+                    .and_then(|grandparent| self.graph.resolve_dep(grandparent, &name))
+                else {
+                    continue;
+                };
+
+                let mut subtree = HashSet::new();
+                let mut stack = vec![dupe_idx];
+                while let Some(idx) = stack.pop() {
+                    subtree.insert(idx);
+                    stack.extend(self.graph[idx].children.values());
+                }
+
+                let mut dependents = Vec::new();
+                for edge_ref in self
+                    .graph
+                    .inner
+                    .edges_directed(dupe_idx, Direction::Incoming)
+                {
+                    if subtree.contains(&edge_ref.source()) {
+                        continue;
+                    }
+                    let stays_inside =
+                        !self.graph.is_injected(
+                            edge_ref.source(),
+                            &name,
+                            &edge_ref.weight().requested,
+                        ) && self.graph.may_share(edge_ref.source(), &name, kept_idx);
+                    if !stays_inside
+                        || !self.graph[kept_idx]
+                            .package
+                            .satisfies(&edge_ref.weight().requested)?
+                    {
+                        dependents.clear();
+                        break;
+                    }
+                    dependents.push((edge_ref.source(), edge_ref.weight().clone()));
+                }
+                if dependents.is_empty() {
+                    continue;
+                }
+
+                for (dependent_idx, edge) in dependents {
+                    let edge_idx = self.graph.inner.add_edge(dependent_idx, kept_idx, edge);
+                    self.graph[dependent_idx]
+                        .dependencies
+                        .insert(name.clone(), edge_idx);
+                }
+                self.graph[parent_idx].children.shift_remove(&name);
+                for idx in &subtree {
+                    self.graph.inner.remove_node(*idx);
+                    self.graph.nohoist.remove(idx);
+                }
+                removed += subtree.len();
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Makes sure every package in the tree is allowed by the project's
+    /// policy. Licenses are only checked for registry packages, since
+    /// that's the only place full package metadata can come from.
+    async fn check_policy(&mut self) -> Result<(), NodeMaintainerError> {
+        let mut violations = Vec::new();
+        let mut license_checks = Vec::new();
+        for idx in self.graph.inner.node_indices() {
+            if idx == self.graph.root {
+                continue;
+            }
+            let package = &self.graph[idx].package;
+            if let Some((rule, reason)) = self.policy.check_package(package) {
+                self.violation(&mut violations, idx, rule, reason)?;
+            }
+            if self.policy.checks_licenses()
+                && matches!(package.resolved(), PackageResolution::Npm { .. })
+            {
+                license_checks.push(idx);
+            }
+        }
+        let mut metadata = futures::stream::iter(license_checks)
+            .map(|idx| {
+                self.graph[idx]
+                    .package
+                    .metadata()
+                    .map(move |metadata| (idx, metadata))
+            })
+            .buffered(self.concurrency);
+        while let Some((idx, metadata)) = metadata.next().await {
+            if let Some(reason) = self.policy.check_license(&metadata?.manifest) {
+                self.violation(&mut violations, idx, PolicyRule::License, reason)?;
+            }
+        }
+        drop(metadata);
+        self.check_provenance(&mut violations).await?;
+        violations.sort_by(|a, b| a.path.cmp(&b.path));
+        self.graph.policy_violations = violations;
+        Ok(())
+    }
+
+    /// Makes sure every registry package matching `require_provenance` has
+    /// provenance that's about it.
+    async fn check_provenance(
+        &self,
+        violations: &mut Vec<PolicyViolation>,
+    ) -> Result<(), NodeMaintainerError> {
+        if self.require_provenance.is_empty() {
+            return Ok(());
+        }
+        let checks = self.graph.inner.node_indices().filter(|idx| {
+            let package = &self.graph[*idx].package;
+            *idx != self.graph.root
+                && matches!(package.resolved(), PackageResolution::Npm { .. })
+                && nohoist::matches_name(self.require_provenance, package.name())
+        });
+        let mut provenance = futures::stream::iter(checks)
+            .map(|idx| {
+                self.graph[idx]
+                    .package
+                    .provenance()
+                    .map(move |provenance| (idx, provenance))
+            })
+            .buffered(self.concurrency);
+        while let Some((idx, provenance)) = provenance.next().await {
+            let reason = match provenance {
+                Ok(Some(_)) => continue,
+                Ok(None) => "has no provenance attestation".to_string(),
+                Err(NassunError::ProvenanceMismatch { reason, .. }) => {
+                    format!("has a provenance attestation that doesn't match it: {reason}")
+                }
+                Err(err) => return Err(err.into()),
+            };
+            self.violation(violations, idx, PolicyRule::Provenance, reason)?;
+        }
+        Ok(())
+    }
+
+    /// Fails with a violation of `rule` by the package at `idx`, or adds it
+    /// to `violations` if they're only being reported.
+    fn violation(
+        &self,
+        violations: &mut Vec<PolicyViolation>,
+        idx: NodeIndex,
+        rule: PolicyRule,
+        reason: String,
+    ) -> Result<(), NodeMaintainerError> {
+        let package = &self.graph[idx].package;
+        let violation = PolicyViolation {
+            name: package.name().to_string(),
+            found: match package.resolved() {
+                PackageResolution::Npm { version, .. } => version.to_string(),
+                resolved => resolved.to_string(),
+            },
+            rule,
+            reason,
+            path: self.graph.dependency_chain(idx),
+        };
+        if self.report_policy {
+            violations.push(violation);
+            return Ok(());
+        }
+        Err(match rule {
+            PolicyRule::Provenance => NodeMaintainerError::ProvenanceRequired(Box::new(violation)),
+            _ => NodeMaintainerError::PolicyViolation(Box::new(violation)),
+        })
+    }
+
+    /// Makes sure that wherever a package has a peer dependency, the copy
+    /// of that peer it would actually load satisfies it. Every peer
+    /// dependency that isn't satisfied is recorded in the graph's peer
+    /// problems. Missing peers are only reported, and optional peers are
+    /// only checked if something else installs them.
+    fn check_peers(&mut self) -> Result<(), NodeMaintainerError> {
+        let mut problems = Vec::new();
+        for node in self.graph.inner.node_weights() {
+            for (name, requested) in &node.peer_reqs {
+                let Some(peer_idx) = self.graph.resolve_dep(node.idx, name) else {
+                    if !node.optional_peers.contains(name) {
+                        problems.push(PeerProblem {
+                            kind: PeerProblemKind::Missing,
+                            name: name.to_string(),
+                            requested: requested.requested().clone(),
+                            path: self.graph.dependency_chain(node.idx),
+                            found: None,
+                        });
+                    }
+                    continue;
+                };
+                if self.graph[peer_idx].package.satisfies(requested)? {
+                    continue;
+                }
+                let conflict = self.peer_conflict(node.idx, peer_idx, requested)?;
+                if !self.force {
+                    return Err(NodeMaintainerError::PeerConflict(Box::new(conflict)));
+                }
+                problems.push(PeerProblem {
+                    kind: if conflict.requesters.iter().any(|req| req.satisfied) {
+                        PeerProblemKind::Conflicting
+                    } else {
+                        PeerProblemKind::Invalid
+                    },
+                    name: conflict.name,
+                    requested: requested.requested().clone(),
+                    path: self.graph.dependency_chain(node.idx),
+                    found: conflict.found,
+                });
+            }
+        }
+        self.graph.peer_problems = problems;
+        Ok(())
+    }
+
+    /// Describes a peer dependency from `dependent_idx` that isn't satisfied
+    /// by `peer_idx`, along with everything else that depends on the peer.
+    fn peer_conflict(
+        &self,
+        dependent_idx: NodeIndex,
+        peer_idx: NodeIndex,
+        requested: &PackageSpec,
+    ) -> Result<ResolutionConflict, NodeMaintainerError> {
+        let peer = &self.graph[peer_idx];
+        let mut requesters = Vec::new();
+        for edge_ref in self
+            .graph
+            .inner
+            .edges_directed(peer_idx, Direction::Incoming)
+        {
+            let edge = edge_ref.weight();
+            requesters.push(ConflictRequester {
+                path: self.graph.dependency_chain(edge_ref.source()),
+                requested: edge.requested.requested().clone(),
+                dep_type: edge.dep_type.clone(),
+                satisfied: peer.package.satisfies(&edge.requested)?,
+            });
+        }
+        requesters.push(ConflictRequester {
+            path: self.graph.dependency_chain(dependent_idx),
+            requested: requested.requested().clone(),
+            dep_type: DepType::Peer,
+            satisfied: false,
+        });
+        Ok(ResolutionConflict {
+            name: peer.package.name().to_string(),
+            found: Some(match peer.package.resolved() {
+                PackageResolution::Npm { version, .. } => version.to_string(),
+                resolved => resolved.to_string(),
+            }),
+            requesters,
+        })
+    }
+
+    /// Describes a package that no published version could be found for,
+    /// along with everything that was waiting on it.
+    fn no_version_conflict(&self, name: String, deps: &[NodeDependency]) -> ResolutionConflict {
+        ResolutionConflict {
+            name,
+            found: None,
+            requesters: deps
+                .iter()
+                .map(|dep| ConflictRequester {
+                    path: self.graph.dependency_chain(dep.node_idx),
+                    requested: dep.spec.requested().clone(),
+                    dep_type: dep.dep_type.clone(),
+                    satisfied: false,
+                })
+                .collect(),
+        }
+    }
+
+    /// Records which nodes get patched, so patch changes show up in the
+    /// lockfile (and in `node_modules/`'s own metadata).
+    fn assign_patches(&mut self) {
+        let mut used = HashSet::new();
+        let root = self.graph.root;
+        for node in self.graph.inner.node_weights_mut() {
+            if node.idx == root {
+                continue;
+            }
+            if let Some((spec, patch)) = self.patches.find(&node.package) {
+                used.insert(spec);
+                if let PackageResolution::Link { path, .. } = node.package.resolved() {
+                    tracing::warn!(
+                        "Not applying patch for `{spec}` to {}, because linked packages are used as-is.",
+                        path.display()
+                    );
+                    continue;
+                }
+                node.patch = Some(patch.integrity().clone());
+            }
+        }
+        for spec in self.patches.specs() {
+            if !used.contains(spec) {
+                tracing::warn!(
+                    "Patch for `{spec}` doesn't match any package in the dependency tree."
+                );
+            }
+        }
+    }
+
     fn satisfy_dependency(
         graph: &mut Graph,
         dep: &NodeDependency,
     ) -> Result<Option<NodeIndex>, NodeMaintainerError> {
         if let Some(satisfier_idx) = graph.find_by_name(dep.node_idx, &dep.name)? {
-            if graph[satisfier_idx]
-                .package
-                .resolved()
-                .satisfies(&dep.spec)?
+            // Injected packages can only be satisfied by the dependent's own
+            // copy.
+            if graph.is_injected(dep.node_idx, &dep.name, &dep.spec)
+                && graph[satisfier_idx].parent != Some(dep.node_idx)
             {
+                return Ok(None);
+            }
+            // Packages that can't be hoisted need a copy of their own inside
+            // their workspace, or their top-level package.
+            if !graph.may_share(dep.node_idx, &dep.name, satisfier_idx) {
+                return Ok(None);
+            }
+            if graph[satisfier_idx].package.satisfies(&dep.spec)? {
                 let edge_idx = graph.inner.add_edge(
                     dep.node_idx,
                     satisfier_idx,
@@ -322,7 +948,7 @@ impl<'a> Resolver<'a> {
             path.pop_back();
             if let Some(lockfile_node) = lockfile.packages().get(&path_str) {
                 if let Some(package) = lockfile_node.to_package(&self.nassun).await? {
-                    if package.resolved().satisfies(requested)? {
+                    if package.satisfies(requested)? {
                         return Ok(Some((package, lockfile_node.clone())));
                     } else {
                         // TODO: Log this We found a lockfile node in a place
@@ -341,6 +967,32 @@ impl<'a> Resolver<'a> {
         Ok(None)
     }
 
+    /// Remembers how `idx` got placed, if the resolver is keeping track.
+    fn record(&mut self, idx: NodeIndex, origin: Origin) {
+        if self.explain {
+            self.graph[idx].origin = Some(origin);
+        }
+    }
+
+    /// Whether dependencies of `dependent_idx` resolve to the lowest
+    /// version in range instead of the highest.
+    fn resolves_lowest(&self, dependent_idx: NodeIndex) -> bool {
+        match self.resolution_mode {
+            ResolutionMode::Highest => false,
+            ResolutionMode::Lowest => true,
+            ResolutionMode::LowestDirect => dependent_idx == self.graph.root,
+        }
+    }
+
+    /// Offers a newly-placed package to the extraction pipeline, if there
+    /// is one.
+    fn stage(&self, _idx: NodeIndex) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(pipeline) = &self.pipeline {
+            pipeline.offer(&self.graph[_idx].package);
+        }
+    }
+
     fn place_child(
         graph: &mut Graph,
         dependent_idx: NodeIndex,
@@ -351,6 +1003,11 @@ impl<'a> Resolver<'a> {
         target_path: Option<Vec<UniCase<String>>>,
     ) -> Result<NodeIndex, NodeMaintainerError> {
         let child_name = UniCase::new(package.name().to_string());
+        let boundary = if graph.is_injected(dependent_idx, &child_name, requested) {
+            Some(dependent_idx)
+        } else {
+            graph.hoist_boundary(dependent_idx, &child_name)
+        };
         let child_node = Node::new(package, corgi, false)?;
         let child_idx = graph.inner.add_node(child_node);
         graph[child_idx].root = graph.root;
@@ -380,7 +1037,7 @@ impl<'a> Resolver<'a> {
                         .edge_endpoints(edge_ref.id())
                         .expect("Where did the edge go?!?!");
                     if graph.is_ancestor(curr_target_idx, from)
-                        && !graph[resolved].package.resolved().satisfies(requested)?
+                        && !graph[resolved].package.satisfies(requested)?
                     {
                         break 'outer;
                     }
@@ -388,7 +1045,7 @@ impl<'a> Resolver<'a> {
             }
 
             if let Some((req, _)) = graph[curr_target_idx].dependency_reqs.get(&child_name) {
-                if !graph[child_idx].package.resolved().satisfies(req)? {
+                if !graph[child_idx].package.satisfies(req)? {
                     break 'outer;
                 }
             }
@@ -397,6 +1054,12 @@ impl<'a> Resolver<'a> {
             target_idx = curr_target_idx;
             parent_idx = graph[curr_target_idx].parent;
 
+            // ...unless a `nohoist` or `hoist-pattern` says this is as high
+            // as it goes.
+            if boundary == Some(target_idx) {
+                break 'outer;
+            }
+
             // Try and place it where the lockfile asks us to, but only if it
             // makes sense for us to do so.
             if let Some(locked) = &target_path {
@@ -433,8 +1096,25 @@ impl<'a> Resolver<'a> {
         Ok(child_idx)
     }
 
+    /// Picks up the `nohoist` patterns declared by a local package, such as
+    /// a workspace package, so its own dependencies get placed accordingly.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn load_nohoist(&mut self, idx: NodeIndex) {
+        if idx == self.graph.root || self.graph.nohoist.contains_key(&idx) {
+            return;
+        }
+        if let PackageResolution::Dir { path, .. } = self.graph[idx].package.resolved() {
+            let patterns = nohoist::load(path).await;
+            if !patterns.is_empty() {
+                self.graph.nohoist.insert(idx, patterns);
+            }
+        }
+    }
+
+    /// Loads the tree that's currently in `node_modules/`, if any, so it
+    /// can be reused when there's no lockfile.
     #[cfg(not(target_arch = "wasm32"))]
-    async fn load_actual(&mut self) -> Result<(), NodeMaintainerError> {
+    pub(crate) async fn load_actual(&mut self) -> Result<(), NodeMaintainerError> {
         let meta = self.root.join("node_modules").join(META_FILE_NAME);
         self.actual_tree = async_std::fs::read_to_string(&meta)
             .await
@@ -448,3 +1128,30 @@ impl<'a> Resolver<'a> {
         Ok(())
     }
 }
+
+/// Shuffles the resolver's iteration order, seeded so any difference it
+/// causes can be reproduced. The order only needs to be unpredictable, so
+/// this is a plain SplitMix64 generator rather than anything fancier.
+#[derive(Debug, Clone)]
+pub(crate) struct Shuffle(u64);
+
+impl Shuffle {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
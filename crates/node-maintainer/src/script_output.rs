@@ -0,0 +1,230 @@
+//! Showing what scripts print when several of them run at once.
+//!
+//! By default, scripts' output only feeds the progress display, through
+//! [`crate::NodeMaintainerOptions::on_script_line`] and friends. The other
+//! modes print it as it comes in, tagged with a `name:event |` prefix so
+//! lines from different packages can be told apart, or hold on to it until
+//! each script is done, so every script's output stays in one piece.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use colored::{Color, Colorize};
+
+use crate::error::NodeMaintainerError;
+
+/// Colors to tell packages apart by. Each package always gets the same one.
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// How the output of scripts gets shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptOutputMode {
+    /// Only pass lines on to the script handlers, for a progress display.
+    #[default]
+    Progress,
+    /// Print every line as soon as it comes in, prefixed with the package
+    /// and script it came from.
+    Prefixed,
+    /// Like [`ScriptOutputMode::Prefixed`], but hold on to each script's
+    /// output until it finishes, and print it all at once.
+    Grouped,
+}
+
+impl FromStr for ScriptOutputMode {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "progress" => Ok(Self::Progress),
+            "prefixed" => Ok(Self::Prefixed),
+            "grouped" => Ok(Self::Grouped),
+            _ => Err(NodeMaintainerError::InvalidScriptOutputMode(s.into())),
+        }
+    }
+}
+
+/// Prints script output according to a [`ScriptOutputMode`].
+pub struct ScriptOutput {
+    mode: ScriptOutputMode,
+    color: bool,
+    out: Mutex<Box<dyn Write + Send>>,
+    /// Output held back until its script finishes, in
+    /// [`ScriptOutputMode::Grouped`] mode.
+    groups: Mutex<HashMap<(String, String), Vec<String>>>,
+}
+
+impl std::fmt::Debug for ScriptOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptOutput")
+            .field("mode", &self.mode)
+            .field("color", &self.color)
+            .finish()
+    }
+}
+
+impl Default for ScriptOutput {
+    fn default() -> Self {
+        Self::new(ScriptOutputMode::default())
+    }
+}
+
+impl ScriptOutput {
+    /// Prints to stdout.
+    pub fn new(mode: ScriptOutputMode) -> Self {
+        Self::with_writer(mode, std::io::stdout())
+    }
+
+    fn with_writer(mode: ScriptOutputMode, out: impl Write + Send + 'static) -> Self {
+        Self {
+            mode,
+            color: true,
+            out: Mutex::new(Box::new(out)),
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether prefixes get colored. Defaults to true.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn mode(&self) -> ScriptOutputMode {
+        self.mode
+    }
+
+    /// Whether script output is left to a progress display, which means
+    /// nothing else should be drawing over it.
+    pub fn shows_progress(&self) -> bool {
+        self.mode == ScriptOutputMode::Progress
+    }
+
+    /// Handles a `line` printed by `name`'s `event` script.
+    pub fn line(&self, name: &str, event: &str, line: &str) {
+        match self.mode {
+            ScriptOutputMode::Progress => {}
+            ScriptOutputMode::Prefixed => {
+                let line = format!("{} {line}", self.prefix(name, event));
+                self.write(&[line]);
+            }
+            ScriptOutputMode::Grouped => {
+                self.groups
+                    .lock()
+                    .unwrap()
+                    .entry((name.into(), event.into()))
+                    .or_default()
+                    .push(line.into());
+            }
+        }
+    }
+
+    /// Called once `name`'s `event` script has exited, successfully or not.
+    pub fn finish(&self, name: &str, event: &str) {
+        if self.mode != ScriptOutputMode::Grouped {
+            return;
+        }
+        let Some(lines) = self
+            .groups
+            .lock()
+            .unwrap()
+            .remove(&(name.to_string(), event.to_string()))
+        else {
+            return;
+        };
+        let prefix = self.prefix(name, event);
+        let lines = lines
+            .into_iter()
+            .map(|line| format!("{prefix} {line}"))
+            .collect::<Vec<_>>();
+        self.write(&lines);
+    }
+
+    fn write(&self, lines: &[String]) {
+        let mut out = self.out.lock().unwrap();
+        for line in lines {
+            // There's nowhere better to report a broken stdout to.
+            let _ = writeln!(out, "{line}");
+        }
+        let _ = out.flush();
+    }
+
+    fn prefix(&self, name: &str, event: &str) -> String {
+        let prefix = format!("{name}:{event} |");
+        if self.color {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            name.hash(&mut hasher);
+            let color = PREFIX_COLORS[hasher.finish() as usize % PREFIX_COLORS.len()];
+            prefix.color(color).to_string()
+        } else {
+            prefix
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(
+            "grouped".parse::<ScriptOutputMode>().unwrap(),
+            ScriptOutputMode::Grouped
+        );
+        assert!("loud".parse::<ScriptOutputMode>().is_err());
+    }
+
+    #[test]
+    fn prefixes_lines() {
+        let captured = Captured::default();
+        let output =
+            ScriptOutput::with_writer(ScriptOutputMode::Prefixed, captured.clone()).color(false);
+        output.line("a", "build", "one");
+        output.line("b", "build", "two");
+        assert_eq!(captured.text(), "a:build | one\nb:build | two\n");
+    }
+
+    #[test]
+    fn groups_lines_until_finished() {
+        let captured = Captured::default();
+        let output =
+            ScriptOutput::with_writer(ScriptOutputMode::Grouped, captured.clone()).color(false);
+        output.line("a", "build", "one");
+        output.line("b", "build", "two");
+        output.line("a", "build", "three");
+        assert_eq!(captured.text(), "");
+        output.finish("a", "build");
+        assert_eq!(captured.text(), "a:build | one\na:build | three\n");
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use kdl::{KdlDocument, KdlNode};
+
+use crate::error::NodeMaintainerError;
+
+/// Name of the per-project state directory, relative to the project root.
+pub const STATE_DIR_NAME: &str = ".oro";
+
+/// Version of the state directory layout. Bump this whenever the layout
+/// changes in a way older versions of Orogene wouldn't understand.
+pub const STATE_VERSION: u64 = 1;
+
+const STATE_FILE_NAME: &str = "state.kdl";
+
+/// Orogene's own per-project state, stored in `<root>/.oro/`.
+///
+/// Everything in here can be safely deleted at any time (for example, with
+/// `oro clean-state`), and is never meant to be committed. The layout is:
+///
+/// ```text
+/// .oro/
+/// ├── state.kdl      # `state-version <n>`, for the layout itself
+/// ├── .gitignore     # ignores the whole directory
+/// ├── logs/          # debug logs for commands run in this project
+/// ├── reports/       # install reports
+/// ├── scripts/       # cached lifecycle script results
+/// ├── journal/       # records of in-progress applies, for resuming them
+/// ├── approvals.kdl  # approvals granted for this project
+/// └── daemon.sock    # socket `oro daemon` listens on, while it runs
+/// ```
+///
+/// If the directory was written with an older layout version, it's wiped
+/// and recreated. Directories written by a newer version of Orogene are left
+/// alone, and [`ProjectState::ensure`] returns an error instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectState {
+    dir: PathBuf,
+}
+
+impl ProjectState {
+    /// State directory for the project at `root`. Nothing is created until
+    /// [`ProjectState::ensure`] is called.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: root.as_ref().join(STATE_DIR_NAME),
+        }
+    }
+
+    /// Path to the state directory itself.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Directory for debug logs.
+    pub fn logs_dir(&self) -> PathBuf {
+        self.dir.join("logs")
+    }
+
+    /// Directory for install reports.
+    pub fn reports_dir(&self) -> PathBuf {
+        self.dir.join("reports")
+    }
+
+    /// Directory for cached lifecycle script results.
+    pub fn scripts_dir(&self) -> PathBuf {
+        self.dir.join("scripts")
+    }
+
+    /// Directory for journals of in-progress applies.
+    pub fn journal_dir(&self) -> PathBuf {
+        self.dir.join("journal")
+    }
+
+    /// File recording approvals granted for this project.
+    pub fn approvals_file(&self) -> PathBuf {
+        self.dir.join("approvals.kdl")
+    }
+
+    /// Socket that `oro daemon` listens on by default.
+    pub fn daemon_socket(&self) -> PathBuf {
+        self.dir.join("daemon.sock")
+    }
+
+    /// Layout version of the existing state directory, if there is one and
+    /// its version can be read.
+    pub fn version(&self) -> Option<u64> {
+        let state = std::fs::read_to_string(self.dir.join(STATE_FILE_NAME)).ok()?;
+        let doc: KdlDocument = state.parse().ok()?;
+        doc.get_arg("state-version")
+            .and_then(|v| v.as_i64())
+            .and_then(|v| v.try_into().ok())
+    }
+
+    /// Makes sure the state directory exists and uses the current layout,
+    /// creating or resetting it as needed.
+    pub fn ensure(&self) -> Result<(), NodeMaintainerError> {
+        match self.version() {
+            Some(STATE_VERSION) => return Ok(()),
+            Some(version) if version > STATE_VERSION => {
+                return Err(NodeMaintainerError::UnsupportedStateVersion(
+                    self.dir.clone(),
+                    version,
+                ));
+            }
+            Some(version) => {
+                tracing::debug!(
+                    "Resetting {} from state version {version} to {STATE_VERSION}.",
+                    self.dir.display()
+                );
+                std::fs::remove_dir_all(&self.dir)?;
+            }
+            None => {}
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(".gitignore"), "*\n")?;
+        let mut doc = KdlDocument::new();
+        doc.set_leading(
+            "// This file is automatically generated and not intended for manual editing.",
+        );
+        let mut version_node = KdlNode::new("state-version");
+        version_node.push(STATE_VERSION as i64);
+        doc.nodes_mut().push(version_node);
+        doc.fmt();
+        std::fs::write(self.dir.join(STATE_FILE_NAME), doc.to_string())?;
+        Ok(())
+    }
+
+    /// Deletes the whole state directory. Returns `false` if there was
+    /// nothing to delete.
+    pub fn clean(&self) -> Result<bool, NodeMaintainerError> {
+        if !self.dir.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_dir_all(&self.dir)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ensure_creates_versioned_dir() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let state = ProjectState::new(root.path());
+        assert_eq!(state.version(), None);
+        state.ensure()?;
+        assert_eq!(state.version(), Some(STATE_VERSION));
+        assert!(state.dir().join(".gitignore").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_resets_older_layouts() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let state = ProjectState::new(root.path());
+        std::fs::create_dir_all(state.logs_dir())?;
+        std::fs::write(state.dir().join(STATE_FILE_NAME), "state-version 0\n")?;
+        state.ensure()?;
+        assert_eq!(state.version(), Some(STATE_VERSION));
+        assert!(!state.logs_dir().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_rejects_newer_layouts() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let state = ProjectState::new(root.path());
+        std::fs::create_dir_all(state.dir())?;
+        std::fs::write(
+            state.dir().join(STATE_FILE_NAME),
+            format!("state-version {}\n", STATE_VERSION + 1),
+        )?;
+        assert!(matches!(
+            state.ensure(),
+            Err(NodeMaintainerError::UnsupportedStateVersion(_, v)) if v == STATE_VERSION + 1
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn clean_removes_everything() -> Result<(), NodeMaintainerError> {
+        let root = tempfile::tempdir()?;
+        let state = ProjectState::new(root.path());
+        assert!(!state.clean()?);
+        state.ensure()?;
+        std::fs::create_dir_all(state.reports_dir())?;
+        assert!(state.clean()?);
+        assert!(!state.dir().exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Where the time in an install went, for performance reports. See
+//! [`crate::NodeMaintainer::timings`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nassun::cache::CacheCounts;
+use nassun::{package::Package, PackageResolution};
+
+/// How long each part of installing took. Things that ran at the same time
+/// are added up separately, so these can add up to more than the install
+/// itself took.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Timings {
+    /// Time spent fetching package metadata from registries.
+    pub metadata: Duration,
+    /// Time spent downloading tarballs. Packages get extracted as they
+    /// download, so this overlaps with `extract`.
+    pub download: Duration,
+    /// Time spent installing packages into `node_modules/`.
+    pub extract: Duration,
+    /// Time spent linking bins.
+    pub bins: Duration,
+    /// Time spent running install scripts.
+    pub scripts: Duration,
+    /// Every package that was extracted or ran install scripts, slowest
+    /// first.
+    pub packages: Vec<PackageTiming>,
+}
+
+/// How long it took to install a single package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PackageTiming {
+    pub name: String,
+    /// The package's version, if it came from a registry.
+    pub version: Option<String>,
+    pub extract: Duration,
+    /// All of the package's install scripts, together.
+    pub scripts: Duration,
+}
+
+impl PackageTiming {
+    pub fn total(&self) -> Duration {
+        self.extract + self.scripts
+    }
+}
+
+/// Running totals behind [`Timings`], shared by the linker.
+#[derive(Debug, Default)]
+pub(crate) struct TimingCounters {
+    bins: Mutex<Duration>,
+    packages: Mutex<HashMap<(String, Option<String>), PackageTime>>,
+}
+
+/// Time spent extracting and running scripts for a package.
+type PackageTime = (Duration, Duration);
+
+impl TimingCounters {
+    /// Times installing `package`, until the returned timer is dropped.
+    pub(crate) fn extract(&self, package: &Package) -> Timer<'_> {
+        self.timer(Phase::Extract(package_key(package)))
+    }
+
+    /// Times one of `package`'s install scripts, until the returned timer is
+    /// dropped.
+    pub(crate) fn script(&self, package: &Package) -> Timer<'_> {
+        self.timer(Phase::Script(package_key(package)))
+    }
+
+    /// Times linking bins, until the returned timer is dropped.
+    pub(crate) fn bins(&self) -> Timer<'_> {
+        self.timer(Phase::Bins)
+    }
+
+    fn timer(&self, phase: Phase) -> Timer<'_> {
+        Timer {
+            counters: self,
+            phase: Some(phase),
+            start: Instant::now(),
+        }
+    }
+
+    /// Everything timed so far, along with the request times in `counts`.
+    pub(crate) fn timings(&self, counts: &CacheCounts) -> Timings {
+        let mut timings = Timings {
+            metadata: Duration::from_millis(counts.packument_request_ms),
+            download: Duration::from_millis(counts.tarball_request_ms),
+            bins: *self.bins.lock().unwrap(),
+            ..Default::default()
+        };
+        for ((name, version), (extract, scripts)) in self.packages.lock().unwrap().iter() {
+            timings.extract += *extract;
+            timings.scripts += *scripts;
+            timings.packages.push(PackageTiming {
+                name: name.clone(),
+                version: version.clone(),
+                extract: *extract,
+                scripts: *scripts,
+            });
+        }
+        timings.packages.sort_by(|a, b| {
+            b.total()
+                .cmp(&a.total())
+                .then_with(|| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+        });
+        timings
+    }
+}
+
+enum Phase {
+    Extract((String, Option<String>)),
+    Script((String, Option<String>)),
+    Bins,
+}
+
+/// Adds the time since it was created to its [`TimingCounters`] when it's
+/// dropped, however whatever it's timing ends.
+pub(crate) struct Timer<'a> {
+    counters: &'a TimingCounters,
+    phase: Option<Phase>,
+    start: Instant,
+}
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        match self.phase.take() {
+            Some(Phase::Extract(key)) => {
+                self.counters
+                    .packages
+                    .lock()
+                    .unwrap()
+                    .entry(key)
+                    .or_default()
+                    .0 += elapsed;
+            }
+            Some(Phase::Script(key)) => {
+                self.counters
+                    .packages
+                    .lock()
+                    .unwrap()
+                    .entry(key)
+                    .or_default()
+                    .1 += elapsed;
+            }
+            Some(Phase::Bins) => *self.counters.bins.lock().unwrap() += elapsed,
+            None => {}
+        }
+    }
+}
+
+fn package_key(package: &Package) -> (String, Option<String>) {
+    let version = match package.resolved() {
+        PackageResolution::Npm { version, .. } => Some(version.to_string()),
+        _ => None,
+    };
+    (package.name().into(), version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_slowest_first() {
+        let counters = TimingCounters::default();
+        {
+            let mut packages = counters.packages.lock().unwrap();
+            packages.insert(
+                ("fast".into(), Some("1.0.0".into())),
+                (Duration::from_millis(5), Duration::ZERO),
+            );
+            packages.insert(
+                ("slow".into(), Some("1.0.0".into())),
+                (Duration::from_millis(10), Duration::from_millis(100)),
+            );
+        }
+        let timings = counters.timings(&CacheCounts {
+            packument_request_ms: 20,
+            ..Default::default()
+        });
+        assert_eq!(timings.metadata, Duration::from_millis(20));
+        assert_eq!(timings.extract, Duration::from_millis(15));
+        assert_eq!(timings.scripts, Duration::from_millis(100));
+        let names = timings
+            .packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["slow", "fast"]);
+        assert_eq!(timings.packages[0].total(), Duration::from_millis(110));
+    }
+}
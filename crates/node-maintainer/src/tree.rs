@@ -0,0 +1,30 @@
+//! A read-only view of a resolved dependency tree, for embedders that want
+//! to walk it without depending on how it's stored.
+
+use nassun::{package::Package, PackageSpec};
+
+use crate::DepType;
+
+/// A package in a resolved tree.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TreePackage {
+    /// Where the package goes, relative to the project root, like
+    /// `node_modules/a/node_modules/b`. The root's is `""`.
+    pub path: String,
+    pub package: Package,
+    /// The package's dependencies that are in the tree, sorted by name.
+    pub dependencies: Vec<TreeDependency>,
+}
+
+/// A dependency of a [`TreePackage`], and what it resolved to.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TreeDependency {
+    pub name: String,
+    /// What the dependent asked for.
+    pub requested: PackageSpec,
+    pub dep_type: DepType,
+    /// [`TreePackage::path`] of the package it resolved to.
+    pub path: String,
+}
@@ -0,0 +1,174 @@
+//! Support for `trustedDependencies`: an allowlist of packages whose install
+//! scripts get to run even though they're only transitive dependencies.
+//!
+//! Install scripts are arbitrary code, and a compromised package deep in
+//! the tree shouldn't get to run any just by being installed. So, unless
+//! [`AllowScripts::All`] is used, only the project's own scripts, those of
+//! its direct dependencies (and of its workspace packages' direct
+//! dependencies), and those of packages listed in `trustedDependencies` are
+//! run. Everything else gets skipped, and reported afterwards.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+
+#[cfg(not(target_arch = "wasm32"))]
+use nassun::PackageResolution;
+#[cfg(not(target_arch = "wasm32"))]
+use petgraph::{stable_graph::NodeIndex, Direction};
+
+use crate::error::NodeMaintainerError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::graph::Graph;
+
+/// Controls which dependencies get to run their install scripts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AllowScripts {
+    /// Only direct dependencies, and dependencies listed in
+    /// `trustedDependencies`, run their install scripts.
+    #[default]
+    Trusted,
+    /// Every dependency runs its install scripts.
+    All,
+}
+
+impl FromStr for AllowScripts {
+    type Err = NodeMaintainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trusted" => Ok(Self::Trusted),
+            "all" => Ok(Self::All),
+            _ => Err(NodeMaintainerError::InvalidAllowScripts(s.into())),
+        }
+    }
+}
+
+/// A package whose install scripts were skipped because it isn't trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedScripts {
+    /// Name of the package.
+    pub name: String,
+    /// The lifecycle events it has scripts for, such as `postinstall`.
+    pub events: Vec<String>,
+}
+
+/// Decides which packages get to run their install scripts for a
+/// particular install, and keeps track of the ones that didn't.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+pub(crate) struct ScriptTrust {
+    all: bool,
+    trusted: HashSet<String>,
+    blocked: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ScriptTrust {
+    /// Reads `trustedDependencies` from the project's `package.json`.
+    pub(crate) async fn load(root: &Path, mode: AllowScripts) -> Self {
+        let mut trusted = HashSet::new();
+        if let Ok(manifest) = async_std::fs::read_to_string(root.join("package.json")).await {
+            if let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&manifest) {
+                if let Some(names) = manifest["trustedDependencies"].as_array() {
+                    trusted.extend(
+                        names
+                            .iter()
+                            .filter_map(|name| name.as_str())
+                            .map(Into::into),
+                    );
+                }
+            }
+        }
+        Self {
+            all: mode == AllowScripts::All,
+            trusted,
+            blocked: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Whether the package at `idx` may run its install scripts. Local
+    /// packages, and direct dependencies of the root or of local packages,
+    /// always can.
+    pub(crate) fn allows(&self, graph: &Graph, idx: NodeIndex) -> bool {
+        self.all
+            || is_local(graph, idx)
+            || self.trusted.contains(graph[idx].package.name())
+            || graph
+                .inner
+                .neighbors_directed(idx, Direction::Incoming)
+                .any(|dependent| is_local(graph, dependent))
+    }
+
+    /// Records that `name`'s `event` script was skipped.
+    pub(crate) fn block(&self, name: &str, event: &str) {
+        tracing::debug!("Skipping `{event}` script for {name}, since it isn't trusted.");
+        self.blocked
+            .lock()
+            .unwrap()
+            .entry(name.into())
+            .or_default()
+            .insert(event.into());
+    }
+
+    /// Every package that had scripts skipped so far, sorted by name.
+    pub(crate) fn blocked(&self) -> Vec<BlockedScripts> {
+        self.blocked
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, events)| BlockedScripts {
+                name: name.clone(),
+                events: events.iter().cloned().collect(),
+            })
+            .collect()
+    }
+}
+
+/// Whether the package at `idx` is the project itself, or one of its
+/// workspace packages.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_local(graph: &Graph, idx: NodeIndex) -> bool {
+    idx == graph.root || matches!(graph[idx].package.resolved(), PackageResolution::Dir { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(
+            "trusted".parse::<AllowScripts>().unwrap(),
+            AllowScripts::Trusted
+        );
+        assert_eq!("all".parse::<AllowScripts>().unwrap(), AllowScripts::All);
+        assert!("none".parse::<AllowScripts>().is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn reports_blocked_scripts() {
+        let trust = ScriptTrust::default();
+        trust.block("esbuild", "postinstall");
+        trust.block("core-js", "postinstall");
+        trust.block("esbuild", "install");
+        assert_eq!(
+            trust.blocked(),
+            vec![
+                BlockedScripts {
+                    name: "core-js".into(),
+                    events: vec!["postinstall".into()],
+                },
+                BlockedScripts {
+                    name: "esbuild".into(),
+                    events: vec!["install".into(), "postinstall".into()],
+                },
+            ]
+        );
+    }
+}
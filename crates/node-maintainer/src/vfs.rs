@@ -0,0 +1,254 @@
+//! Filesystems that installs can be materialized into, other than the one
+//! the process is running on. This is what lets the wasm32 build, which
+//! has no filesystem of its own, lay out a `node_modules/` tree: either
+//! into a filesystem provided by the caller, like an in-browser one, or
+//! into a [`MemoryFs`] that can be inspected afterwards.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// `Send + Sync`, except on wasm32, where the JavaScript values that
+/// caller-provided filesystems are made of are neither.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync> MaybeSync for T {}
+/// `Send + Sync`, except on wasm32, where the JavaScript values that
+/// caller-provided filesystems are made of are neither.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSync for T {}
+
+/// A filesystem to materialize an install into. Paths are relative to the
+/// project root, like `node_modules/foo/package.json`, and it's up to the
+/// filesystem where that root actually is. See
+/// [`crate::NodeMaintainer::extract_to`].
+pub trait Vfs: MaybeSync {
+    /// Creates a directory, and any of its parents that don't exist yet.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Writes a file, replacing it if it already exists. Its parent
+    /// directory has already been created.
+    fn write_file(&self, path: &Path, contents: &[u8], executable: bool) -> io::Result<()>;
+
+    /// Creates a symlink at `link` that points to `target`, which is
+    /// relative to `link`'s parent directory.
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+
+    /// Removes a file, symlink, or directory and everything in it, if it
+    /// exists.
+    fn remove_all(&self, path: &Path) -> io::Result<()>;
+}
+
+/// An entry in a [`MemoryFs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VfsEntry {
+    Dir,
+    File { contents: Vec<u8>, executable: bool },
+    Symlink(PathBuf),
+}
+
+/// A [`Vfs`] that keeps everything in memory.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    entries: Mutex<BTreeMap<PathBuf, VfsEntry>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The entry at `path`, if there is one.
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<VfsEntry> {
+        self.lock().get(&normalize(path.as_ref())).cloned()
+    }
+
+    /// Every entry, sorted by path.
+    pub fn entries(&self) -> Vec<(PathBuf, VfsEntry)> {
+        self.lock()
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, VfsEntry>> {
+        // Nothing panics while holding the lock, so it can't be poisoned
+        // halfway through a change.
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.lock();
+        for dir in normalize(path).ancestors() {
+            if dir.as_os_str().is_empty() {
+                break;
+            }
+            match entries.get(dir) {
+                Some(VfsEntry::Dir) => {}
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} is not a directory", dir.display()),
+                    ))
+                }
+                None => {
+                    entries.insert(dir.to_path_buf(), VfsEntry::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, contents: &[u8], executable: bool) -> io::Result<()> {
+        let path = normalize(path);
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        self.lock().insert(
+            path,
+            VfsEntry::File {
+                contents: contents.to_vec(),
+                executable,
+            },
+        );
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let link = normalize(link);
+        if let Some(parent) = link.parent() {
+            self.create_dir_all(parent)?;
+        }
+        self.lock()
+            .insert(link, VfsEntry::Symlink(target.to_path_buf()));
+        Ok(())
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        self.lock().retain(|entry, _| !entry.starts_with(&path));
+        Ok(())
+    }
+}
+
+/// A [`Vfs`] backed by the real filesystem, with paths relative to `root`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct StdFs {
+    root: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StdFs {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Vfs for StdFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(self.root.join(normalize(path)))
+    }
+
+    fn write_file(&self, path: &Path, contents: &[u8], executable: bool) -> io::Result<()> {
+        let path = self.root.join(normalize(path));
+        std::fs::write(&path, contents)?;
+        #[cfg(unix)]
+        if executable {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        #[cfg(not(unix))]
+        let _ = executable;
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let link = self.root.join(normalize(link));
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, link);
+        #[cfg(windows)]
+        return std::os::windows::fs::symlink_file(target, link);
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        let path = self.root.join(normalize(path));
+        let result = match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(&path),
+            Ok(_) => std::fs::remove_file(&path),
+            Err(err) => Err(err),
+        };
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Turns `path` into a plain relative one, without `.` or `..` components,
+/// so nothing gets written outside of the filesystem's root.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn memory_fs() -> io::Result<()> {
+        let fs = MemoryFs::new();
+        fs.write_file(Path::new("node_modules/foo/index.js"), b"hi", false)?;
+        fs.symlink(
+            Path::new("../foo/index.js"),
+            Path::new("node_modules/.bin/foo"),
+        )?;
+        assert_eq!(fs.get("node_modules"), Some(VfsEntry::Dir));
+        assert_eq!(
+            fs.get("./node_modules/foo/index.js"),
+            Some(VfsEntry::File {
+                contents: b"hi".to_vec(),
+                executable: false
+            })
+        );
+        assert_eq!(fs.entries().len(), 5);
+        assert!(fs
+            .write_file(Path::new("node_modules/foo/index.js/bar"), b"", false)
+            .is_err());
+        fs.remove_all(Path::new("node_modules/foo"))?;
+        assert_eq!(fs.get("node_modules/foo/index.js"), None);
+        assert_eq!(fs.entries().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize(Path::new("/node_modules/../../foo/./bar")),
+            PathBuf::from("foo/bar")
+        );
+    }
+}
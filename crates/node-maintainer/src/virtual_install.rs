@@ -0,0 +1,160 @@
+//! Installs that only happen in memory: where every package would go in
+//! `node_modules/`, which bins would get linked, and which install scripts
+//! would run, without touching the disk or downloading any tarballs. This
+//! makes resolution and layout cheap to test, both for Orogene itself and
+//! for tools built on top of it. See
+//! [`crate::NodeMaintainerOptions::virtual_install`].
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use nassun::PackageResolution;
+use oro_common::BuildManifest;
+use unicase::UniCase;
+
+use crate::linkers::{package_dir, BinCandidate, BinCandidates};
+use crate::{graph::Graph, NodeMaintainerError};
+
+/// Lifecycle scripts that run when a package gets installed, in the order
+/// they run in.
+const INSTALL_EVENTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+
+/// What an install would've done, laid out the way the hoisted linker
+/// would lay it out. Paths are relative to the project root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VirtualInstall {
+    /// Every package but the root, sorted by path.
+    pub packages: Vec<VirtualPackage>,
+    /// Bins that would be linked, sorted by where they'd be linked.
+    pub bins: Vec<VirtualBin>,
+    /// Install scripts that would run, sorted by package path, and then in
+    /// the order each package's scripts run in.
+    pub scripts: Vec<PlannedScript>,
+}
+
+impl VirtualInstall {
+    /// The package that would be installed at `path`, like
+    /// `node_modules/foo`, if any.
+    pub fn package(&self, path: impl AsRef<Path>) -> Option<&VirtualPackage> {
+        let path = path.as_ref();
+        self.packages.iter().find(|package| package.path == path)
+    }
+}
+
+/// A package in a [`VirtualInstall`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VirtualPackage {
+    /// Where the package would go, like `node_modules/a/node_modules/b`.
+    pub path: PathBuf,
+    pub name: String,
+    /// The package's version, if it came from a registry.
+    pub version: Option<String>,
+    /// Where the package would come from.
+    pub resolved: String,
+}
+
+/// A bin in a [`VirtualInstall`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VirtualBin {
+    pub name: String,
+    /// Where the bin would be linked, like `node_modules/.bin/foo`.
+    pub link: PathBuf,
+    /// The file the bin would run, like `node_modules/foo/cli.js`.
+    pub target: PathBuf,
+}
+
+/// An install script in a [`VirtualInstall`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PlannedScript {
+    /// Path of the package the script belongs to.
+    pub package: PathBuf,
+    /// Which script it is, like `postinstall`.
+    pub event: String,
+    pub command: String,
+}
+
+/// Works out the [`VirtualInstall`] for `graph`, using package metadata
+/// instead of the packages themselves. Bins from `directories.bin`, and
+/// `install` scripts that only exist because of a `binding.gyp`, can't be
+/// found without the package's files, so they're left out.
+pub(crate) async fn virtual_install(graph: &Graph) -> Result<VirtualInstall, NodeMaintainerError> {
+    let mut install = VirtualInstall::default();
+    // Packages fighting over the same bin name get settled the same way the
+    // hoisted linker settles them.
+    let mut wanted: BTreeMap<PathBuf, BinCandidates> = BTreeMap::new();
+    for idx in graph.inner.node_indices() {
+        if idx == graph.root {
+            continue;
+        }
+        let package = &graph[idx].package;
+        let path = package_dir(graph, idx);
+        install.packages.push(VirtualPackage {
+            path: path.clone(),
+            name: package.name().into(),
+            version: match package.resolved() {
+                PackageResolution::Npm { version, .. } => Some(version.to_string()),
+                _ => None,
+            },
+            resolved: package.resolved().to_string(),
+        });
+
+        let mut manifest = package.metadata().await?.manifest;
+        // `directories.bin` can only be expanded once the package is on disk.
+        manifest.directories = None;
+        let build_mani = BuildManifest::from_manifest(&manifest).map_err(|e| {
+            NodeMaintainerError::BuildManifestReadError(path.join("package.json"), e)
+        })?;
+
+        let parent = path.parent().expect("must have parent");
+        let bin_dir = if parent.file_name() == Some(OsStr::new("node_modules")) {
+            parent.join(".bin")
+        } else {
+            // Scoped
+            parent.parent().expect("must have parent").join(".bin")
+        };
+        let owner = graph[idx].parent.unwrap_or(graph.root);
+        let direct = graph[owner]
+            .dependencies
+            .contains_key(&UniCase::new(package.name().to_string()));
+        let candidates = wanted.entry(bin_dir).or_default();
+        for (name, target) in build_mani.bin {
+            candidates.add(
+                name,
+                BinCandidate {
+                    idx,
+                    direct,
+                    from: crate::vfs::normalize(&path.join(target)),
+                },
+            );
+        }
+
+        for event in INSTALL_EVENTS {
+            if let Some(command) = build_mani.scripts.get(event) {
+                install.scripts.push(PlannedScript {
+                    package: path.clone(),
+                    event: event.into(),
+                    command: command.clone(),
+                });
+            }
+        }
+    }
+    for (bin_dir, candidates) in wanted {
+        for (name, target) in candidates.pick(graph, &bin_dir, false)? {
+            install.bins.push(VirtualBin {
+                link: bin_dir.join(&name),
+                name,
+                target,
+            });
+        }
+    }
+    install.packages.sort_by(|a, b| a.path.cmp(&b.path));
+    // Sorting is stable, so each package's scripts stay in the order they'd
+    // run in.
+    install.scripts.sort_by(|a, b| a.package.cmp(&b.package));
+    Ok(install)
+}
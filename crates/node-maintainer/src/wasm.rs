@@ -1,4 +1,8 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
 
 use futures::{StreamExt, TryStreamExt};
 use js_sys::Promise;
@@ -10,6 +14,7 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 
 use crate::error::NodeMaintainerError;
+use crate::vfs::{MemoryFs, Vfs, VfsEntry};
 
 type Result<T> = std::result::Result<T, NodeMaintainerError>;
 
@@ -27,12 +32,109 @@ export interface PackageJson {
     optionalDependencies?: Record<string, string>;
     bundledDependencies?: string[];
 }
+
+export interface VirtualFs {
+    createDirAll(path: string): void;
+    writeFile(path: string, contents: Uint8Array, executable: boolean): void;
+    symlink(target: string, link: string): void;
+    removeAll(path: string): void;
+}
+
+export type VirtualFsEntry =
+    | { path: string; type: "dir" }
+    | { path: string; type: "file"; contents: Uint8Array; executable: boolean }
+    | { path: string; type: "symlink"; target: string };
 "#;
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(typescript_type = "PackageJson")]
     pub type PackageJson;
+
+    /// A filesystem provided by JavaScript to extract `node_modules/` into.
+    /// Its methods are synchronous, and paths are relative to the project
+    /// root.
+    #[wasm_bindgen(typescript_type = "VirtualFs")]
+    pub type VirtualFs;
+
+    #[wasm_bindgen(method, catch, js_name = "createDirAll")]
+    fn create_dir_all(this: &VirtualFs, path: &str) -> std::result::Result<(), JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = "writeFile")]
+    fn write_file(
+        this: &VirtualFs,
+        path: &str,
+        contents: &[u8],
+        executable: bool,
+    ) -> std::result::Result<(), JsValue>;
+
+    #[wasm_bindgen(method, catch)]
+    fn symlink(this: &VirtualFs, target: &str, link: &str) -> std::result::Result<(), JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = "removeAll")]
+    fn remove_all(this: &VirtualFs, path: &str) -> std::result::Result<(), JsValue>;
+}
+
+/// Paths are always handed to JavaScript with `/` separators.
+fn js_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn js_error(e: JsValue) -> io::Error {
+    let message = e
+        .dyn_ref::<js_sys::Error>()
+        .map(|e| String::from(e.message()))
+        .or_else(|| e.as_string())
+        .unwrap_or_else(|| format!("{e:?}"));
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+impl Vfs for VirtualFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        VirtualFs::create_dir_all(self, &js_path(path)).map_err(js_error)
+    }
+
+    fn write_file(&self, path: &Path, contents: &[u8], executable: bool) -> io::Result<()> {
+        VirtualFs::write_file(self, &js_path(path), contents, executable).map_err(js_error)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        VirtualFs::symlink(self, &js_path(target), &js_path(link)).map_err(js_error)
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        VirtualFs::remove_all(self, &js_path(path)).map_err(js_error)
+    }
+}
+
+fn entry_to_js(path: PathBuf, entry: VfsEntry) -> std::result::Result<JsValue, JsValue> {
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &"path".into(), &js_path(&path).into())?;
+    match entry {
+        VfsEntry::Dir => {
+            js_sys::Reflect::set(&obj, &"type".into(), &"dir".into())?;
+        }
+        VfsEntry::File {
+            contents,
+            executable,
+        } => {
+            js_sys::Reflect::set(&obj, &"type".into(), &"file".into())?;
+            js_sys::Reflect::set(
+                &obj,
+                &"contents".into(),
+                &js_sys::Uint8Array::from(&contents[..]).into(),
+            )?;
+            js_sys::Reflect::set(&obj, &"executable".into(), &executable.into())?;
+        }
+        VfsEntry::Symlink(target) => {
+            js_sys::Reflect::set(&obj, &"type".into(), &"symlink".into())?;
+            js_sys::Reflect::set(&obj, &"target".into(), &js_path(&target).into())?;
+        }
+    }
+    Ok(obj.into())
 }
 
 impl From<NodeMaintainerError> for JsValue {
@@ -164,6 +266,27 @@ impl NodeMaintainer {
             .map(Package::from_core_package)
     }
 
+    /// Extracts `node_modules/` into `fs`, downloading packages as needed.
+    /// Install scripts are never run. Returns how many packages were
+    /// extracted.
+    #[wasm_bindgen(js_name = "extractTo")]
+    pub async fn extract_to(&self, fs: VirtualFs) -> Result<usize> {
+        self.inner.extract_to(&fs).await
+    }
+
+    /// Extracts `node_modules/` into memory, downloading packages as needed,
+    /// and returns every directory, file, and symlink in it, as
+    /// `VirtualFsEntry` objects.
+    #[wasm_bindgen(js_name = "extractToMemory")]
+    pub async fn extract_to_memory(&self) -> std::result::Result<js_sys::Array, JsValue> {
+        let fs = MemoryFs::new();
+        self.inner.extract_to(&fs).await?;
+        fs.entries()
+            .into_iter()
+            .map(|(path, entry)| entry_to_js(path, entry))
+            .collect()
+    }
+
     /// Concurrently over all packages in the tree, calling `f` on each.
     #[wasm_bindgen(js_name = "forEachPackage")]
     pub async fn for_each_package(&self, f: &js_sys::Function) -> std::result::Result<(), JsValue> {
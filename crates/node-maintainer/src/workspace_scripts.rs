@@ -0,0 +1,410 @@
+//! Running a script across the project's workspace packages, like `oro run
+//! -r build`.
+//!
+//! Packages run in dependency order: a package's script only starts once
+//! the scripts of every workspace package it depends on, directly or
+//! through other workspace packages, have finished. Packages in a
+//! dependency cycle share a level, since there's no right order to run them
+//! in.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use nassun::{package::Package, Nassun, PackageResolution};
+use oro_common::Manifest;
+use oro_package_spec::PackageSpec;
+use oro_script::OroScript;
+use petgraph::{algo::tarjan_scc, graph::DiGraph, Direction};
+
+use crate::error::NodeMaintainerError;
+use crate::script_output::ScriptOutput;
+use crate::workspaces::Workspaces;
+use crate::{ScriptLineHandler, ScriptStartHandler, DEFAULT_SCRIPT_CONCURRENCY};
+
+/// Runs a script in every workspace package that has it, in dependency
+/// order. See [`WorkspaceScripts::run`].
+#[derive(Clone)]
+pub struct WorkspaceScripts {
+    concurrency: usize,
+    bail: bool,
+    only: Vec<String>,
+    script_shell: Option<PathBuf>,
+    on_script_start: Option<ScriptStartHandler>,
+    on_script_line: Option<ScriptLineHandler>,
+    script_output: Arc<ScriptOutput>,
+}
+
+impl Default for WorkspaceScripts {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_SCRIPT_CONCURRENCY,
+            bail: true,
+            only: Vec::new(),
+            script_shell: None,
+            on_script_start: None,
+            on_script_line: None,
+            script_output: Arc::new(ScriptOutput::default()),
+        }
+    }
+}
+
+/// What happened when running a script across workspace packages.
+#[derive(Debug, Default)]
+pub struct WorkspaceScriptReport {
+    /// Packages whose scripts finished successfully, in the order they
+    /// finished.
+    pub succeeded: Vec<String>,
+    /// Packages whose scripts failed.
+    pub failed: Vec<WorkspaceScriptFailure>,
+    /// Packages whose scripts never ran, because an earlier failure
+    /// stopped the run. See [`WorkspaceScripts::bail`].
+    pub skipped: Vec<String>,
+}
+
+/// A workspace package whose script failed.
+#[derive(Debug)]
+pub struct WorkspaceScriptFailure {
+    /// Name of the package.
+    pub name: String,
+    /// Why the script failed.
+    pub error: NodeMaintainerError,
+    /// Everything the script printed, stdout and stderr interleaved.
+    pub output: String,
+}
+
+/// A workspace package, as far as running scripts in it goes.
+#[derive(Debug)]
+struct Member {
+    name: String,
+    dir: PathBuf,
+    /// Names of everything the package depends on, of any type.
+    deps: Vec<String>,
+    /// Whether the package has the script, and was selected to run it.
+    runs: bool,
+}
+
+impl WorkspaceScripts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of scripts to run at the same time. Defaults to
+    /// [`DEFAULT_SCRIPT_CONCURRENCY`].
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Whether to stop starting new scripts as soon as one fails. Scripts
+    /// that are already running get to finish either way. Defaults to true.
+    pub fn bail(mut self, bail: bool) -> Self {
+        self.bail = bail;
+        self
+    }
+
+    /// Only run the script in the workspace package named `name`. This
+    /// option can be provided multiple times. By default, the script runs in
+    /// every workspace package that has it.
+    pub fn workspace(mut self, name: impl AsRef<str>) -> Self {
+        self.only.push(name.as_ref().into());
+        self
+    }
+
+    /// Shell to run the scripts in. See [`OroScript::shell`].
+    pub fn script_shell(mut self, shell: impl AsRef<Path>) -> Self {
+        self.script_shell = Some(shell.as_ref().into());
+        self
+    }
+
+    /// Called right before each package's script starts, with the package
+    /// and the name of the script.
+    pub fn on_script_start<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Package, &str) + Send + Sync + 'static,
+    {
+        self.on_script_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Called with every line the scripts print, on either stdout or
+    /// stderr.
+    pub fn on_script_line<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_script_line = Some(Arc::new(f));
+        self
+    }
+
+    /// How the scripts' output gets shown, on top of being passed to
+    /// [`WorkspaceScripts::on_script_line`]. By default, it's only passed
+    /// along.
+    pub fn script_output(mut self, output: ScriptOutput) -> Self {
+        self.script_output = Arc::new(output);
+        self
+    }
+
+    /// Runs `script` in the workspace packages of the project at `root`.
+    /// Packages without the script are skipped, but still count for
+    /// ordering. Failing scripts don't make this return an error: they're
+    /// listed in the report instead.
+    pub async fn run(
+        self,
+        root: impl AsRef<Path>,
+        script: &str,
+    ) -> Result<WorkspaceScriptReport, NodeMaintainerError> {
+        let root = root.as_ref();
+        let workspaces = Workspaces::load(root).await;
+        if let Some(unknown) = self
+            .only
+            .iter()
+            .find(|name| !workspaces.packages().any(|(known, _)| known == *name))
+        {
+            return Err(NodeMaintainerError::UnknownWorkspace(unknown.clone()));
+        }
+        let mut members = Vec::new();
+        for (name, path) in workspaces.packages() {
+            let dir = root.join(path);
+            let manifest: Manifest = serde_json::from_str(
+                &async_std::fs::read_to_string(dir.join("package.json")).await?,
+            )?;
+            members.push(Member {
+                name: name.into(),
+                dir,
+                deps: manifest
+                    .dependencies
+                    .keys()
+                    .chain(manifest.dev_dependencies.keys())
+                    .chain(manifest.optional_dependencies.keys())
+                    .chain(manifest.peer_dependencies.keys())
+                    .cloned()
+                    .collect(),
+                runs: manifest.scripts.contains_key(script)
+                    && (self.only.is_empty() || self.only.iter().any(|only| only == name)),
+            });
+        }
+
+        let this = &self;
+        let nassun = Nassun::new();
+        let stop = AtomicBool::new(false);
+        let report = Mutex::new(WorkspaceScriptReport::default());
+        for level in levels(&members) {
+            futures::stream::iter(level)
+                .for_each_concurrent(self.concurrency, |idx| {
+                    let member = &members[idx];
+                    let (nassun, stop, report) = (&nassun, &stop, &report);
+                    async move {
+                        if stop.load(Ordering::SeqCst) {
+                            report.lock().unwrap().skipped.push(member.name.clone());
+                            return;
+                        }
+                        let output = Arc::new(Mutex::new(Vec::new()));
+                        let result = this
+                            .run_one(nassun, root, member, script, output.clone())
+                            .await;
+                        let mut report = report.lock().unwrap();
+                        match result {
+                            Ok(()) => report.succeeded.push(member.name.clone()),
+                            Err(error) => {
+                                if this.bail {
+                                    stop.store(true, Ordering::SeqCst);
+                                }
+                                report.failed.push(WorkspaceScriptFailure {
+                                    name: member.name.clone(),
+                                    error,
+                                    output: output.lock().unwrap().join("\n"),
+                                });
+                            }
+                        }
+                    }
+                })
+                .await;
+        }
+        Ok(report.into_inner().unwrap())
+    }
+
+    async fn run_one(
+        &self,
+        nassun: &Nassun,
+        root: &Path,
+        member: &Member,
+        script: &str,
+        output: Arc<Mutex<Vec<String>>>,
+    ) -> Result<(), NodeMaintainerError> {
+        let span = if self.script_output.shows_progress() {
+            tracing::info_span!("script")
+        } else {
+            tracing::Span::none()
+        };
+        if let Some(on_script_start) = &self.on_script_start {
+            let package = nassun.resolve_from(
+                member.name.clone(),
+                PackageSpec::Dir {
+                    path: member.dir.clone(),
+                },
+                PackageResolution::Dir {
+                    name: member.name.clone(),
+                    path: member.dir.clone(),
+                },
+            );
+            let _enter = span.enter();
+            on_script_start(&package, script);
+        }
+        let dir = member.dir.clone();
+        let root = root.to_path_buf();
+        let event = script.to_string();
+        let shell = self.script_shell.clone();
+        let mut child = async_std::task::spawn_blocking(move || {
+            let mut script = OroScript::new(dir, event)?.workspace_path(root);
+            if let Some(shell) = shell {
+                script = script.shell(shell);
+            }
+            script.spawn()
+        })
+        .await?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let join = futures::try_join!(
+            self.read_lines(stdout, &span, &member.name, script, output.clone()),
+            self.read_lines(stderr, &span, &member.name, script, output),
+            async_std::task::spawn_blocking(move || {
+                child.wait()?;
+                Ok::<_, NodeMaintainerError>(())
+            }),
+        );
+        self.script_output.finish(&member.name, script);
+        join?;
+        Ok(())
+    }
+
+    /// Passes along every line of a script's `stream` as it comes in.
+    async fn read_lines(
+        &self,
+        stream: Option<impl Read + Send + 'static>,
+        span: &tracing::Span,
+        name: &str,
+        script: &str,
+        output: Arc<Mutex<Vec<String>>>,
+    ) -> Result<(), NodeMaintainerError> {
+        let Some(stream) = stream else {
+            return Ok(());
+        };
+        let span = span.clone();
+        let on_script_line = self.on_script_line.clone();
+        let script_output = self.script_output.clone();
+        let (name, script) = (name.to_string(), script.to_string());
+        let prefix = format!("{name}::{script}");
+        async_std::task::spawn_blocking(move || {
+            let _enter = span.enter();
+            for line in BufReader::new(stream).lines() {
+                let line = line?;
+                tracing::debug!("{prefix}: {line}");
+                if let Some(on_script_line) = &on_script_line {
+                    on_script_line(&line);
+                }
+                script_output.line(&name, &script, &line);
+                output.lock().unwrap().push(line);
+            }
+            Ok::<_, NodeMaintainerError>(())
+        })
+        .await
+    }
+}
+
+/// Groups the members that run the script into levels, where every level
+/// only depends on the ones before it.
+fn levels(members: &[Member]) -> Vec<Vec<usize>> {
+    let mut graph = DiGraph::<usize, ()>::new();
+    let nodes = (0..members.len())
+        .map(|idx| graph.add_node(idx))
+        .collect::<Vec<_>>();
+    let by_name = members
+        .iter()
+        .enumerate()
+        .map(|(idx, member)| (member.name.as_str(), idx))
+        .collect::<HashMap<_, _>>();
+    for (idx, member) in members.iter().enumerate() {
+        for dep in &member.deps {
+            if let Some(&dep_idx) = by_name.get(dep.as_str()) {
+                if dep_idx != idx {
+                    graph.add_edge(nodes[idx], nodes[dep_idx], ());
+                }
+            }
+        }
+    }
+    let mut levels: Vec<Vec<usize>> = Vec::new();
+    let mut node_levels = HashMap::new();
+    // Components come out of Tarjan's algorithm with dependencies before
+    // their dependents, so every dependency outside of the current
+    // component already has a level.
+    for component in tarjan_scc(&graph) {
+        let level = component
+            .iter()
+            .flat_map(|node| graph.neighbors_directed(*node, Direction::Outgoing))
+            .filter_map(|dep| node_levels.get(&dep).map(|level| level + 1))
+            .max()
+            .unwrap_or(0);
+        for node in &component {
+            node_levels.insert(*node, level);
+        }
+        if levels.len() <= level {
+            levels.resize_with(level + 1, Vec::new);
+        }
+        levels[level].extend(
+            component
+                .into_iter()
+                .map(|node| graph[node])
+                .filter(|idx| members[*idx].runs),
+        );
+    }
+    for level in &mut levels {
+        level.sort();
+    }
+    levels.retain(|level| !level.is_empty());
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str, deps: &[&str], runs: bool) -> Member {
+        Member {
+            name: name.into(),
+            dir: PathBuf::from(name),
+            deps: deps.iter().map(|dep| dep.to_string()).collect(),
+            runs,
+        }
+    }
+
+    #[test]
+    fn orders_by_dependencies() {
+        let members = [
+            member("app", &["lib", "react"], true),
+            member("lib", &["utils"], true),
+            member("utils", &[], true),
+            member("docs", &[], true),
+        ];
+        assert_eq!(levels(&members), vec![vec![2, 3], vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn orders_through_packages_without_the_script() {
+        let members = [
+            member("app", &["lib"], true),
+            member("lib", &["utils"], false),
+            member("utils", &[], true),
+        ];
+        assert_eq!(levels(&members), vec![vec![2], vec![0]]);
+    }
+
+    #[test]
+    fn cycles_share_a_level() {
+        let members = [member("a", &["b"], true), member("b", &["a"], true)];
+        assert_eq!(levels(&members), vec![vec![0, 1]]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Support for the project's own workspace packages. Every workspace package
+//! gets installed into the root `node_modules/`, and plain semver ranges
+//! that a workspace package satisfies can resolve to that workspace package
+//! instead of a registry version, the way pnpm links packages in a monorepo.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use node_semver::{Range, Version};
+use oro_common::CorgiManifest;
+
+/// The project's workspace packages, keyed by package name.
+#[derive(Debug, Clone, Default)]
+pub struct Workspaces {
+    packages: IndexMap<String, WorkspacePackage>,
+    /// Whether [`Workspaces::apply`] resolves semver ranges to workspace
+    /// packages.
+    prefer: bool,
+}
+
+#[derive(Debug, Clone)]
+struct WorkspacePackage {
+    /// Path to the package, relative to the project root.
+    path: String,
+    /// Path to link the package from, relative to the project root. Not
+    /// the same as `path` when it publishes a build output directory.
+    link_path: String,
+    version: Version,
+}
+
+impl Workspaces {
+    /// Finds the workspace packages listed in the `workspaces` field of the
+    /// `package.json` at `root`, the way [`Workspaces::package_dirs`] does.
+    /// Packages without a name or a valid version can't satisfy anything,
+    /// so they're skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn load(root: &Path) -> Self {
+        let mut workspaces = Self {
+            prefer: true,
+            ..Self::default()
+        };
+        let Some(manifest) = read_manifest(root).await else {
+            return workspaces;
+        };
+        for dir in Self::package_dirs(root, &manifest.workspaces) {
+            let Some(pkg) = read_manifest(&root.join(&dir)).await else {
+                continue;
+            };
+            let link_path = link_path(root, &dir, &pkg);
+            if let (Some(name), Some(version)) = (pkg.name, pkg.version) {
+                workspaces.packages.entry(name).or_insert(WorkspacePackage {
+                    path: dir,
+                    link_path,
+                    version,
+                });
+            }
+        }
+        workspaces
+    }
+
+    /// Directories of the packages that `patterns`, the `workspaces` field
+    /// of the `package.json` at `root`, lists, relative to `root` and in the
+    /// order they're listed in. Entries can be literal paths, or paths
+    /// ending in `/*`, which match every immediate subdirectory. Entries
+    /// starting with `!` drop directories that earlier entries matched, like
+    /// with npm. Directories without a `package.json` are skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn package_dirs(root: &Path, patterns: &[String]) -> Vec<String> {
+        let mut dirs: Vec<String> = Vec::new();
+        for pattern in patterns {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                dirs.retain(|dir| !pattern_matches(negated, dir));
+                continue;
+            }
+            let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+            let mut matched = Vec::new();
+            if let Some(parent) = pattern.strip_suffix("/*") {
+                let Ok(entries) = std::fs::read_dir(root.join(parent)) else {
+                    continue;
+                };
+                let mut names = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+                names.sort();
+                matched.extend(names.into_iter().map(|name| format!("{parent}/{name}")));
+            } else {
+                matched.push(pattern.to_string());
+            }
+            for dir in matched {
+                if root.join(&dir).join("package.json").is_file() && !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+        dirs
+    }
+
+    /// Whether to resolve semver ranges to workspace packages at all, or
+    /// only install the workspace packages themselves.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn prefer(mut self, prefer: bool) -> Self {
+        self.prefer = prefer;
+        self
+    }
+
+    /// Replaces every semver range in `manifest`'s dependencies that's
+    /// satisfied by a workspace package with a `file:` spec pointing at
+    /// that package. Peer dependencies are left alone, since they're
+    /// satisfied by whatever ends up in the tree anyway.
+    pub(crate) fn apply(&self, mut manifest: CorgiManifest) -> CorgiManifest {
+        if !self.prefer || self.packages.is_empty() {
+            return manifest;
+        }
+        let own_name = manifest.name.clone();
+        for deps in [
+            &mut manifest.dependencies,
+            &mut manifest.dev_dependencies,
+            &mut manifest.optional_dependencies,
+        ] {
+            for (name, spec) in deps.iter_mut() {
+                if own_name.as_ref() == Some(name) {
+                    continue;
+                }
+                if let Some(path) = self.satisfying(name, spec) {
+                    *spec = format!("file:{path}");
+                }
+            }
+        }
+        manifest
+    }
+
+    /// Adds a `file:` dependency on every workspace package that `manifest`
+    /// doesn't already depend on one way or another, so they all end up in
+    /// the root `node_modules/`, with their bins in `node_modules/.bin`.
+    pub(crate) fn link_all(&self, mut manifest: CorgiManifest) -> CorgiManifest {
+        for (name, pkg) in &self.packages {
+            let already_depended_on = manifest.name.as_ref() == Some(name)
+                || manifest.dependencies.contains_key(name)
+                || manifest.dev_dependencies.contains_key(name)
+                || manifest.optional_dependencies.contains_key(name)
+                || manifest.peer_dependencies.contains_key(name);
+            if !already_depended_on {
+                manifest
+                    .dependencies
+                    .insert(name.clone(), format!("file:{}", pkg.link_path));
+            }
+        }
+        manifest
+    }
+
+    /// Canonical paths to every workspace package, for telling them apart
+    /// from other `file:` dependencies once they've been resolved.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn dirs(&self, root: &Path) -> HashSet<PathBuf> {
+        self.packages
+            .values()
+            .filter_map(|pkg| root.join(&pkg.link_path).canonicalize().ok())
+            .collect()
+    }
+
+    /// Names of the workspace packages, along with their paths relative to
+    /// the project root, in the order they're listed in.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn packages(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.packages
+            .iter()
+            .map(|(name, pkg)| (name.as_str(), pkg.path.as_str()))
+    }
+
+    /// Path to the workspace package named `name`, if `spec` is a semver
+    /// range it satisfies.
+    fn satisfying(&self, name: &str, spec: &str) -> Option<&str> {
+        let pkg = self.packages.get(name)?;
+        let range = spec.parse::<Range>().ok()?;
+        range
+            .satisfies(&pkg.version)
+            .then_some(pkg.link_path.as_str())
+    }
+}
+
+/// Where a workspace package gets linked from. Packages that publish a
+/// build output directory, with `publishConfig.directory`, get linked from
+/// there too, once it's been built, the same way pnpm does it, unless
+/// `publishConfig.linkDirectory` is `false`.
+#[cfg(not(target_arch = "wasm32"))]
+fn link_path(root: &Path, dir: &str, pkg: &oro_common::Manifest) -> String {
+    let settings = pkg.publish_settings();
+    match settings.directory {
+        Some(directory) if settings.link_directory != Some(false) => {
+            let directory = directory.trim_start_matches("./").trim_end_matches('/');
+            let linked = format!("{dir}/{directory}");
+            if root.join(&linked).join("package.json").is_file() {
+                linked
+            } else {
+                dir.to_string()
+            }
+        }
+        _ => dir.to_string(),
+    }
+}
+
+/// Whether the `workspaces` entry `pattern` matches `dir`, a path relative
+/// to the project root.
+#[cfg(not(target_arch = "wasm32"))]
+fn pattern_matches(pattern: &str, dir: &str) -> bool {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    match pattern.strip_suffix("/*") {
+        Some(parent) => dir
+            .rsplit_once('/')
+            .map_or(false, |(dir_parent, _)| dir_parent == parent),
+        None => pattern == dir,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_manifest(dir: &Path) -> Option<oro_common::Manifest> {
+    let manifest = async_std::fs::read_to_string(dir.join("package.json"))
+        .await
+        .ok()?;
+    serde_json::from_str(&manifest).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspaces() -> Workspaces {
+        let mut workspaces = Workspaces::default();
+        workspaces.packages.insert(
+            "b".into(),
+            WorkspacePackage {
+                path: "packages/b".into(),
+                link_path: "packages/b".into(),
+                version: "2.1.0".parse().unwrap(),
+            },
+        );
+        workspaces
+    }
+
+    #[test]
+    fn only_satisfied_ranges() {
+        let workspaces = workspaces();
+        assert_eq!(workspaces.satisfying("b", "^2.0.0"), Some("packages/b"));
+        assert_eq!(workspaces.satisfying("b", "*"), Some("packages/b"));
+        assert_eq!(workspaces.satisfying("b", "^3.0.0"), None);
+        assert_eq!(workspaces.satisfying("b", "latest"), None);
+        assert_eq!(workspaces.satisfying("b", "npm:c@^2.0.0"), None);
+        assert_eq!(workspaces.satisfying("c", "^2.0.0"), None);
+    }
+
+    #[test]
+    fn links_missing_packages() {
+        let workspaces = workspaces();
+        let manifest = workspaces.link_all(CorgiManifest::default());
+        assert_eq!(
+            manifest.dependencies.get("b").map(String::as_str),
+            Some("file:packages/b")
+        );
+
+        let mut manifest = CorgiManifest::default();
+        manifest
+            .dev_dependencies
+            .insert("b".into(), "^2.0.0".into());
+        let manifest = workspaces.link_all(manifest);
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn package_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["packages/a", "packages/b", "packages/empty", "tools/c"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        for dir in ["packages/a", "packages/b", "tools/c"] {
+            std::fs::write(root.path().join(dir).join("package.json"), "{}").unwrap();
+        }
+        let patterns = ["packages/*", "!packages/b", "./tools/c/", "missing"].map(String::from);
+        assert_eq!(
+            Workspaces::package_dirs(root.path(), &patterns),
+            vec!["packages/a", "tools/c"]
+        );
+
+        let patterns = ["packages/*", "tools/c", "!packages/*"].map(String::from);
+        assert_eq!(
+            Workspaces::package_dirs(root.path(), &patterns),
+            vec!["tools/c"]
+        );
+    }
+}
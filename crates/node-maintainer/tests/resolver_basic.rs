@@ -2,7 +2,10 @@ use std::collections::HashMap;
 
 use kdl::KdlDocument;
 use miette::{IntoDiagnostic, Result};
-use node_maintainer::NodeMaintainer;
+use node_maintainer::{
+    ConflictRequester, DepType, DependencyCycle, NodeMaintainer, NodeMaintainerError, PeerProblem,
+    PeerProblemKind, PolicyRule, PolicyViolation, ResolutionConflict, VirtualInstall,
+};
 use pretty_assertions::assert_eq;
 use serde_json::json;
 use wiremock::{
@@ -245,41 +248,1268 @@ pkg "d" {
     Ok(())
 }
 
-async fn mocks_from_kdl(mock_server: &MockServer, doc: KdlDocument) {
-    let mut packuments = HashMap::new();
-    for node in doc.nodes() {
-        let name = node.name().value().to_owned();
-        let children = node.children().unwrap();
-        let version = children
-            .get_arg("version")
-            .unwrap()
-            .as_string()
-            .unwrap()
-            .to_owned();
-        let dependencies = children.get("dependencies").map(|deps| {
-            let dep_kids = deps.children().unwrap();
-            let mut deps = json!({});
-            for dep in dep_kids.nodes() {
-                deps[dep.name().to_string()] = json!(dep.get(0).unwrap().as_string().unwrap());
+#[async_std::test]
+async fn incremental_reresolution() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^2.0.0"
+            c "^3.0.0"
+        }
+    }
+    b {
+        version "2.0.0"
+        dependencies {
+            d "^4.0.0"
+        }
+    }
+    c {
+        version "3.0.0"
+        dependencies {
+            d "^5.0.0"
+        }
+    }
+    d {
+        version "4.0.0"
+    }
+    d {
+        version "5.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+    let lock = nm.to_kdl()?;
+
+    // Swapping `c` for `e` should only need to look up `e`. This registry
+    // doesn't know about anything else, so reusing the rest of the lockfile
+    // is the only way this resolves.
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    e {
+        version "1.0.0"
+        dependencies {
+            d "^4.0.0"
+        }
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let manifest = serde_json::from_value(json!({
+        "name": "a",
+        "version": "1.0.0",
+        "dependencies": {
+            "b": "^2.0.0",
+            "e": "^1.0.0"
+        }
+    }))
+    .into_diagnostic()?;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .kdl_lock(lock)?
+        .resolve_manifest(manifest)
+        .await?;
+
+    assert_eq!(package_paths(&nm)?, vec!["b@2.0.0", "d@4.0.0", "e@1.0.0"]);
+    Ok(())
+}
+
+#[async_std::test]
+async fn dedupe_nested_duplicates() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    b {
+        version "1.0.0"
+        dependencies {
+            d "^1.0.0"
+        }
+    }
+    d {
+        version "1.0.0"
+    }
+    d {
+        version "1.1.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    // `b/node_modules/d` is redundant, since the toplevel `d` also satisfies
+    // `b`'s dependency on it.
+    let lock = r#"
+    lockfile-version 1
+    root {
+        dependencies {
+            b ">=1.0.0 <2.0.0-0"
+            d ">=1.0.0 <2.0.0-0"
+        }
+    }
+    pkg "b" {
+        version "1.0.0"
+        resolved "https://example.com/-/b-1.0.0.tgz"
+        integrity "sha512-deadbeef"
+        dependencies {
+            d ">=1.0.0 <2.0.0-0"
+        }
+    }
+    pkg "b" "d" {
+        version "1.0.0"
+        resolved "https://example.com/-/d-1.0.0.tgz"
+        integrity "sha512-deadbeef"
+    }
+    pkg "d" {
+        version "1.1.0"
+        resolved "https://example.com/-/d-1.1.0.tgz"
+        integrity "sha512-deadbeef"
+    }
+    "#;
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "a",
+            "version": "1.0.0",
+            "dependencies": {
+                "b": "^1.0.0",
+                "d": "^1.0.0"
             }
-            deps
-        });
-        let packument = packuments.entry(name.clone()).or_insert_with(|| {
+        }))
+        .into_diagnostic()
+    };
+
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .kdl_lock(lock.to_string())?
+        .resolve_manifest(manifest()?)
+        .await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec!["b/node_modules/d@1.0.0", "b@1.0.0", "d@1.1.0"]
+    );
+
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .kdl_lock(lock.to_string())?
+        .dedupe(true)
+        .resolve_manifest(manifest()?)
+        .await?;
+    assert_eq!(package_paths(&nm)?, vec!["b@1.0.0", "d@1.1.0"]);
+    Ok(())
+}
+
+fn package_paths(nm: &NodeMaintainer) -> Result<Vec<String>> {
+    let lock = nm.to_lockfile()?;
+    let mut packages = lock
+        .packages()
+        .iter()
+        .map(|(path, node)| {
+            format!(
+                "{path}@{}",
+                node.version
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    packages.sort();
+    Ok(packages)
+}
+
+#[async_std::test]
+async fn graph_exports() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^2.0.0"
+        }
+    }
+    b {
+        version "2.0.0"
+        dependencies {
+            c "^3.0.0"
+        }
+    }
+    c {
+        version "3.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+
+    assert_eq!(
+        nm.to_dot(),
+        r#"digraph dependencies {
+    "" [label="a@1.0.0"];
+    "b" [label="b@2.0.0"];
+    "c" [label="c@3.0.0"];
+    "" -> "b" [label=">=2.0.0 <3.0.0-0", style=solid];
+    "b" -> "c" [label=">=3.0.0 <4.0.0-0", style=solid];
+}
+"#
+    );
+    assert_eq!(
+        nm.to_mermaid(),
+        r#"graph LR
+    n0["a@1.0.0"]
+    n1["b@2.0.0"]
+    n2["c@3.0.0"]
+    n0 -->|"prod #gt;=2.0.0 #lt;3.0.0-0"| n1
+    n1 -->|"prod #gt;=3.0.0 #lt;4.0.0-0"| n2
+"#
+    );
+    let json = nm.to_json()?;
+    assert_eq!(json["nodes"].as_array().map(|nodes| nodes.len()), Some(3));
+    assert_eq!(
+        json["edges"][1],
+        json!({
+            "from": "b",
+            "to": "c",
+            "type": "prod",
+            "requested": ">=3.0.0 <4.0.0-0",
+        })
+    );
+    let tree = nm.tree();
+    assert_eq!(
+        tree.iter()
+            .map(|package| (package.path.as_str(), package.package.name()))
+            .collect::<Vec<_>>(),
+        vec![("", "a"), ("node_modules/b", "b"), ("node_modules/c", "c")]
+    );
+    let dep = &tree[1].dependencies[0];
+    assert_eq!(
+        (dep.name.as_str(), &dep.dep_type, dep.path.as_str()),
+        ("c", &DepType::Prod, "node_modules/c")
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn resolve_only() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    b {
+        version "2.0.0"
+        dependencies {
+            c "^3.0.0"
+        }
+    }
+    c {
+        version "3.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "a",
+            "version": "1.0.0",
+            "dependencies": {
+                "b": "^2.0.0"
+            }
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?);
+
+    let lock = builder.clone().resolve_only(manifest()?).await?;
+    let nm = builder.resolve_manifest(manifest()?).await?;
+    assert_eq!(lock, nm.to_lockfile()?);
+    let mut paths = lock
+        .packages()
+        .keys()
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, vec!["b", "c"]);
+    Ok(())
+}
+
+#[async_std::test]
+async fn check_determinism() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    b {
+        version "2.0.0"
+        dependencies {
+            d "^4.0.0"
+        }
+    }
+    c {
+        version "3.0.0"
+        dependencies {
+            d "^4.0.0"
+            e "^5.0.0"
+        }
+    }
+    d {
+        version "4.0.0"
+    }
+    e {
+        version "5.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "a",
+            "version": "1.0.0",
+            "dependencies": {
+                "b": "^2.0.0",
+                "c": "^3.0.0"
+            }
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(4)
+        .registry(mock_server.uri().parse().into_diagnostic()?);
+
+    let expected = builder.clone().resolve_only(manifest()?).await?;
+    for seed in 0..8 {
+        let lock = builder
+            .clone()
+            .shuffle_seed(seed)
+            .check_determinism(manifest()?)
+            .await?;
+        assert_eq!(lock, expected);
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn catalog_specs() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    b {
+        version "1.0.0"
+    }
+    b {
+        version "2.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let manifest = |spec: &str| {
+        serde_json::from_value(json!({
+            "name": "a",
+            "version": "1.0.0",
+            "dependencies": {
+                "b": spec
+            }
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?);
+
+    let nm = builder
+        .clone()
+        .catalog("b", "^1.0.0")
+        .resolve_manifest(manifest("catalog:")?)
+        .await?;
+    assert_eq!(package_paths(&nm)?, vec!["b@1.0.0"]);
+    let lock = nm.to_lockfile()?;
+    assert_eq!(
+        lock.root().dependencies.get("b").map(|s| s.as_str()),
+        Some(">=1.0.0 <2.0.0-0")
+    );
+
+    match builder.resolve_manifest(manifest("catalog:")?).await {
+        Err(NodeMaintainerError::MissingCatalogEntry { name, catalog }) => {
+            assert_eq!(name, "b");
+            assert_eq!(catalog, "default");
+        }
+        Err(e) => return Err(e.into()),
+        Ok(_) => panic!("expected a missing catalog entry"),
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn nohoist_patterns() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // `app` is a workspace package that needs `rn` and everything under it
+    // to stay in its own node_modules/, even though nothing would conflict
+    // at the root.
+    let mock_data = r#"
+    rn {
+        version "1.0.0"
+        dependencies {
+            metro "^1.0.0"
+        }
+    }
+    metro {
+        version "1.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let root = tempfile::tempdir().into_diagnostic()?;
+    let app = root.path().join("packages").join("app");
+    std::fs::create_dir_all(&app).into_diagnostic()?;
+    let write_app = |nohoist: serde_json::Value| {
+        std::fs::write(
+            app.join("package.json"),
             json!({
-                "versions": {},
-                "dist-tags": {}
+                "name": "app",
+                "version": "1.0.0",
+                "dependencies": {
+                    "rn": "^1.0.0"
+                },
+                "workspaces": {
+                    "nohoist": nohoist
+                }
             })
-        });
-        packument["versions"][version.clone()] = json!({
-            "name": name.clone(),
-            "version": version.clone(),
-            "dist": {
-                "tarball": format!("https://example.com/-/{name}-{version}.tgz"),
-                "integrity": "sha512-deadbeef"
+            .to_string(),
+        )
+        .into_diagnostic()
+    };
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "root",
+            "dependencies": {
+                "app": "file:packages/app"
             }
-        });
-        if let Some(deps) = dependencies {
-            packument["versions"][version.clone()]["dependencies"] = deps;
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .root(root.path());
+
+    write_app(json!([]))?;
+    let nm = builder.clone().resolve_manifest(manifest()?).await?;
+    assert_eq!(package_paths(&nm)?, vec!["app@", "metro@1.0.0", "rn@1.0.0"]);
+
+    write_app(json!(["rn", "rn/**"]))?;
+    let nm = builder.clone().resolve_manifest(manifest()?).await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec![
+            "app/node_modules/metro@1.0.0",
+            "app/node_modules/rn@1.0.0",
+            "app@"
+        ]
+    );
+
+    // Patterns from the root are relative to the root, and only pin what
+    // they match.
+    write_app(json!([]))?;
+    let nm = builder
+        .nohoist("app/rn")
+        .resolve_manifest(manifest()?)
+        .await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec!["app/node_modules/rn@1.0.0", "app@", "metro@1.0.0"]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn hoist_patterns() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+            react "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        dependencies {
+            c "^1.0.0"
+        }
+    }
+    c {
+        version "1.0.0"
+    }
+    react {
+        version "1.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let root = tempfile::tempdir().into_diagnostic()?;
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "root",
+            "dependencies": {
+                "a": "^1.0.0",
+                "react": "^1.0.0"
+            }
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .root(root.path())
+        .hoist_patterns(["b"]);
+
+    // Patterns only apply to the hoisted layout.
+    let nm = builder.clone().resolve_manifest(manifest()?).await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec!["a@1.0.0", "b@1.0.0", "c@1.0.0", "react@1.0.0"]
+    );
+
+    // `c` stays inside `b`, while `react` is shared with the root, which
+    // depends on it directly anyway.
+    let nm = builder.hoisted(true).resolve_manifest(manifest()?).await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec![
+            "a@1.0.0",
+            "b/node_modules/c@1.0.0",
+            "b@1.0.0",
+            "react@1.0.0"
+        ]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn prefer_workspace_packages() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // `b` is also a workspace package, at 2.0.0.
+    let mock_data = r#"
+    b {
+        version "1.0.0"
+    }
+    b {
+        version "2.0.0"
+    }
+    c {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let root = tempfile::tempdir().into_diagnostic()?;
+    let b = root.path().join("packages").join("b");
+    std::fs::create_dir_all(&b).into_diagnostic()?;
+    std::fs::write(
+        b.join("package.json"),
+        json!({
+            "name": "b",
+            "version": "2.0.0"
+        })
+        .to_string(),
+    )
+    .into_diagnostic()?;
+    let manifest = json!({
+        "name": "root",
+        "workspaces": ["packages/*"],
+        "dependencies": {
+            "b": "^2.0.0",
+            "c": "^1.0.0"
+        }
+    });
+    std::fs::write(root.path().join("package.json"), manifest.to_string()).into_diagnostic()?;
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .root(root.path());
+
+    let nm = builder
+        .clone()
+        .resolve_manifest(serde_json::from_value(manifest.clone()).into_diagnostic()?)
+        .await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec!["b@2.0.0", "c/node_modules/b@1.0.0", "c@1.0.0"]
+    );
+
+    // Ranges the workspace package doesn't satisfy still come from the
+    // registry.
+    let nm = builder
+        .prefer_workspace_packages(true)
+        .resolve_manifest(serde_json::from_value(manifest).into_diagnostic()?)
+        .await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec!["b@", "c/node_modules/b@1.0.0", "c@1.0.0"]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn injected_dependencies() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    react {
+        version "17.0.0"
+    }
+    react {
+        version "18.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    // `lib` is a component library that needs the same React as `app`,
+    // which isn't the one at the root.
+    let root = tempfile::tempdir().into_diagnostic()?;
+    let lib = root.path().join("packages").join("lib");
+    let app = root.path().join("packages").join("app");
+    std::fs::create_dir_all(&lib).into_diagnostic()?;
+    std::fs::create_dir_all(&app).into_diagnostic()?;
+    std::fs::write(
+        lib.join("package.json"),
+        json!({
+            "name": "lib",
+            "version": "1.0.0",
+            "peerDependencies": {
+                "react": "^17.0.0"
+            }
+        })
+        .to_string(),
+    )
+    .into_diagnostic()?;
+    let write_app = |injected: bool| {
+        std::fs::write(
+            app.join("package.json"),
+            json!({
+                "name": "app",
+                "version": "1.0.0",
+                "dependencies": {
+                    "lib": "file:packages/lib",
+                    "react": "^17.0.0"
+                },
+                "dependenciesMeta": {
+                    "lib": {
+                        "injected": injected
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .into_diagnostic()
+    };
+    let manifest = || {
+        serde_json::from_value(json!({
+            "name": "root",
+            "dependencies": {
+                "app": "file:packages/app",
+                "react": "^18.0.0"
+            }
+        }))
+        .into_diagnostic()
+    };
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .root(root.path());
+
+    // A shared copy of `lib` gets hoisted to the root, where it sees the
+    // wrong React.
+    write_app(false)?;
+    assert!(matches!(
+        builder.clone().resolve_manifest(manifest()?).await,
+        Err(NodeMaintainerError::PeerConflict(_))
+    ));
+
+    write_app(true)?;
+    let nm = builder.resolve_manifest(manifest()?).await?;
+    assert_eq!(
+        package_paths(&nm)?,
+        vec![
+            "app/node_modules/lib@",
+            "app/node_modules/react@17.0.0",
+            "app@",
+            "react@18.0.0"
+        ]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn policy_violations() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        license "MIT"
+        dependencies {
+            c "^1.0.0"
+        }
+    }
+    c {
+        version "1.0.0"
+        license "GPL-3.0-only"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?);
+
+    let violation = |policy: &str| {
+        let builder = builder.clone().policy(policy);
+        async move {
+            match builder?.resolve_spec("a@^1").await {
+                Err(NodeMaintainerError::PolicyViolation(violation)) => Ok(Some(*violation)),
+                Err(e) => Err(e),
+                Ok(_) => Ok(None),
+            }
+        }
+    };
+
+    let found = violation(r#"deny { package "c" "<2.0.0"; }"#).await?;
+    assert_eq!(
+        found,
+        Some(PolicyViolation {
+            name: "c".into(),
+            found: "1.0.0".into(),
+            rule: PolicyRule::Package,
+            reason: "matches the denied range `<2.0.0`".into(),
+            path: vec!["b".into(), "c".into()],
+        })
+    );
+    let found = violation(r#"deny { license "GPL-3.0-only"; }"#).await?;
+    assert_eq!(
+        found.map(|v| (v.name, v.rule)),
+        Some(("c".into(), PolicyRule::License))
+    );
+    let found = violation(r#"allow { license "MIT" "GPL-3.0-only"; }"#).await?;
+    assert_eq!(found, None);
+
+    // Reporting violations finds all of them, without failing.
+    let nm = builder
+        .policy(r#"deny { package "b"; license "GPL-3.0-only"; }"#)?
+        .report_policy_violations(true)
+        .resolve_spec("a@^1")
+        .await?;
+    let found = nm
+        .policy_violations()
+        .iter()
+        .map(|v| (v.name.as_str(), v.rule))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![("b", PolicyRule::Package), ("c", PolicyRule::License)]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn dependency_cycles() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        dependencies {
+            c "^1.0.0"
+        }
+    }
+    c {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+            d "^1.0.0"
+        }
+    }
+    d {
+        version "1.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+
+    let cycles = nm.cycles();
+    assert_eq!(
+        cycles,
+        vec![DependencyCycle {
+            packages: vec!["b@1.0.0".into(), "c@1.0.0".into()],
+        }]
+    );
+    assert_eq!(cycles[0].to_string(), "b@1.0.0 -> c@1.0.0 -> b@1.0.0");
+    Ok(())
+}
+
+#[async_std::test]
+async fn peer_conflict() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+            c "^17.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        peer-dependencies {
+            c "^18.0.0"
+        }
+    }
+    c {
+        version "17.0.0"
+    }
+    c {
+        version "18.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let builder = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?);
+
+    match builder.clone().resolve_spec("a@^1").await {
+        Err(NodeMaintainerError::PeerConflict(conflict)) => assert_eq!(
+            *conflict,
+            ResolutionConflict {
+                name: "c".into(),
+                found: Some("17.0.0".into()),
+                requesters: vec![
+                    ConflictRequester {
+                        path: vec![],
+                        requested: ">=17.0.0 <18.0.0-0".into(),
+                        dep_type: DepType::Prod,
+                        satisfied: true,
+                    },
+                    ConflictRequester {
+                        path: vec!["b".into()],
+                        requested: ">=18.0.0 <19.0.0-0".into(),
+                        dep_type: DepType::Peer,
+                        satisfied: false,
+                    },
+                ],
+            }
+        ),
+        Err(e) => return Err(e.into()),
+        Ok(_) => panic!("expected a peer conflict"),
+    }
+
+    // `--force` installs anyway.
+    let nm = builder.force(true).resolve_spec("a@^1").await?;
+    assert_eq!(package_paths(&nm)?, vec!["b@1.0.0", "c@17.0.0"]);
+    assert_eq!(
+        nm.peer_problems(),
+        &[PeerProblem {
+            kind: PeerProblemKind::Conflicting,
+            name: "c".into(),
+            requested: ">=18.0.0 <19.0.0-0".into(),
+            path: vec!["b".into()],
+            found: Some("17.0.0".into()),
+        }]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn missing_peers() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Peers that nothing installs get reported, unless they're optional.
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        dependencies {
+            c "^1.0.0"
+        }
+    }
+    c {
+        version "1.0.0"
+        peer-dependencies {
+            d "^2.0.0"
+            e "^3.0.0" optional=true
+        }
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+
+    assert_eq!(package_paths(&nm)?, vec!["b@1.0.0", "c@1.0.0"]);
+    assert_eq!(
+        nm.peer_problems(),
+        &[PeerProblem {
+            kind: PeerProblemKind::Missing,
+            name: "d".into(),
+            requested: ">=2.0.0 <3.0.0-0".into(),
+            path: vec!["b".into(), "c".into()],
+            found: None,
+        }]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn virtual_install() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Nothing gets written or downloaded, but paths, bins, and install
+    // scripts all come out the way a hoisted install would lay them out.
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+            c "^1.0.0"
+            "@s/d" "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        dependencies {
+            c "^2.0.0"
+        }
+        bin {
+            b "./bin/b.js"
+        }
+        scripts {
+            postinstall "node setup.js"
+            preinstall "node check.js"
+            test "exit 1"
+        }
+    }
+    c {
+        version "1.0.0"
+        bin {
+            c "cli.js"
+        }
+    }
+    c {
+        version "2.0.0"
+        bin {
+            c "cli2.js"
+        }
+    }
+    "@s/d" {
+        version "1.0.0"
+        bin {
+            d "d.js"
+        }
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let dir = tempfile::tempdir().into_diagnostic()?;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .root(dir.path())
+        .virtual_install(true)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+    assert_eq!(nm.prune().await?, 0);
+    assert_eq!(nm.extract().await?, 0);
+    nm.rebuild(false).await?;
+    assert!(!dir.path().join("node_modules").exists());
+
+    let install: VirtualInstall = nm.virtual_install().await?;
+    let packages = install
+        .packages
+        .iter()
+        .map(|pkg| {
+            format!(
+                "{}={}@{}",
+                pkg.path.display(),
+                pkg.name,
+                pkg.version.as_deref().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        packages,
+        vec![
+            "node_modules/@s/d=@s/d@1.0.0",
+            "node_modules/b=b@1.0.0",
+            "node_modules/b/node_modules/c=c@2.0.0",
+            "node_modules/c=c@1.0.0",
+        ]
+    );
+    assert_eq!(
+        install.package("node_modules/c").unwrap().resolved,
+        "https://example.com/-/c-1.0.0.tgz"
+    );
+    let bins = install
+        .bins
+        .iter()
+        .map(|bin| format!("{} -> {}", bin.link.display(), bin.target.display()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bins,
+        vec![
+            "node_modules/.bin/b -> node_modules/b/bin/b.js",
+            "node_modules/.bin/c -> node_modules/c/cli.js",
+            "node_modules/.bin/d -> node_modules/@s/d/d.js",
+            "node_modules/b/node_modules/.bin/c -> node_modules/b/node_modules/c/cli2.js",
+        ]
+    );
+    let scripts = install
+        .scripts
+        .iter()
+        .map(|script| {
+            format!(
+                "{} {}: {}",
+                script.package.display(),
+                script.event,
+                script.command
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        scripts,
+        vec![
+            "node_modules/b preinstall: node check.js",
+            "node_modules/b postinstall: node setup.js",
+        ]
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn deprecations() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Deprecated versions get reported along with who pulled them in.
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        dependencies {
+            c "^1.0.0"
+        }
+    }
+    c {
+        version "1.0.0"
+        deprecated "use d instead"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+    let deprecations = nm.deprecations();
+    assert_eq!(deprecations.len(), 1);
+    let deprecation = &deprecations[0];
+    assert_eq!(deprecation.name, "c");
+    assert_eq!(deprecation.version.as_deref(), Some("1.0.0"));
+    assert_eq!(deprecation.message.as_deref(), Some("use d instead"));
+    assert_eq!(deprecation.path, vec!["b", "c"]);
+    Ok(())
+}
+
+#[async_std::test]
+async fn explain_resolution() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Explanations list the requesters, the versions they'd accept, and why
+    // the winner was picked.
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+    }
+    b {
+        version "1.1.0"
+    }
+    b {
+        version "2.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .explain_resolution(true)
+        .resolve_spec("a@^1")
+        .await?;
+    let explanations = nm.explain("b").await?;
+    assert_eq!(explanations.len(), 1);
+    let explanation = &explanations[0];
+    assert_eq!(explanation.picked, "1.1.0");
+    assert_eq!(explanation.location, "node_modules/b");
+    assert_eq!(
+        explanation.requesters,
+        vec![ConflictRequester {
+            // `a` is the root, since it's what was resolved.
+            path: vec![],
+            requested: ">=1.0.0 <2.0.0-0".into(),
+            dep_type: DepType::Prod,
+            satisfied: true,
+        }]
+    );
+    let candidates = explanation
+        .candidates
+        .iter()
+        .map(|candidate| (candidate.version.as_str(), candidate.picked))
+        .collect::<Vec<_>>();
+    assert_eq!(candidates, vec![("1.0.0", false), ("1.1.0", true)]);
+    assert!(explanation
+        .reason
+        .as_deref()
+        .unwrap()
+        .ends_with("it's the highest version in range"));
+    assert!(nm.explain("nope").await?.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn funding() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Packages that aren't installed get their funding from the registry.
+    let mock_data = r#"
+    a {
+        version "1.0.0"
+        dependencies {
+            b "^1.0.0"
+            c "^1.0.0"
+        }
+    }
+    b {
+        version "1.0.0"
+        funding "https://example.com/fund-b"
+    }
+    c {
+        version "1.0.0"
+    }
+    "#;
+    mocks_from_kdl(&mock_server, mock_data.parse()?).await;
+    let dir = tempfile::tempdir().into_diagnostic()?;
+    let nm = NodeMaintainer::builder()
+        .concurrency(1)
+        .root(dir.path())
+        .registry(mock_server.uri().parse().into_diagnostic()?)
+        .resolve_spec("a@^1")
+        .await?;
+    let funding = nm.funding().await?;
+    assert_eq!(funding.len(), 1);
+    assert_eq!(funding[0].name, "b");
+    assert_eq!(funding[0].version.as_deref(), Some("1.0.0"));
+    assert_eq!(funding[0].urls, vec!["https://example.com/fund-b"]);
+    Ok(())
+}
+
+async fn mocks_from_kdl(mock_server: &MockServer, doc: KdlDocument) {
+    let mut packuments = HashMap::new();
+    for node in doc.nodes() {
+        let name = node.name().value().to_owned();
+        let children = node.children().unwrap();
+        let version = children
+            .get_arg("version")
+            .unwrap()
+            .as_string()
+            .unwrap()
+            .to_owned();
+        let deps_of_type = |ty: &str| {
+            children.get(ty).map(|deps| {
+                let dep_kids = deps.children().unwrap();
+                let mut deps = json!({});
+                for dep in dep_kids.nodes() {
+                    deps[dep.name().value()] = json!(dep.get(0).unwrap().as_string().unwrap());
+                }
+                deps
+            })
+        };
+        let dependencies = deps_of_type("dependencies");
+        let peer_dependencies = deps_of_type("peer-dependencies");
+        let bin = deps_of_type("bin");
+        let scripts = deps_of_type("scripts");
+        let mut peer_dependencies_meta = json!({});
+        if let Some(peers) = children.get("peer-dependencies") {
+            for peer in peers.children().unwrap().nodes() {
+                if let Some(optional) = peer.get("optional").and_then(|opt| opt.as_bool()) {
+                    peer_dependencies_meta[peer.name().value()] =
+                        json!({ "optional": optional });
+                }
+            }
+        }
+        let license = children
+            .get_arg("license")
+            .and_then(|license| license.as_string())
+            .map(|license| license.to_owned());
+        let deprecated = children
+            .get_arg("deprecated")
+            .and_then(|deprecated| deprecated.as_string())
+            .map(|deprecated| deprecated.to_owned());
+        let funding = children
+            .get_arg("funding")
+            .and_then(|funding| funding.as_string())
+            .map(|funding| funding.to_owned());
+        let packument = packuments.entry(name.clone()).or_insert_with(|| {
+            json!({
+                "versions": {},
+                "dist-tags": {}
+            })
+        });
+        packument["versions"][version.clone()] = json!({
+            "name": name.clone(),
+            "version": version.clone(),
+            "dist": {
+                "tarball": format!("https://example.com/-/{name}-{version}.tgz"),
+                "integrity": "sha512-deadbeef"
+            }
+        });
+        if let Some(deps) = dependencies {
+            packument["versions"][version.clone()]["dependencies"] = deps;
+        }
+        if let Some(deps) = peer_dependencies {
+            packument["versions"][version.clone()]["peerDependencies"] = deps;
+            packument["versions"][version.clone()]["peerDependenciesMeta"] = peer_dependencies_meta;
+        }
+        if let Some(deprecated) = deprecated {
+            packument["versions"][version.clone()]["deprecated"] = json!(deprecated);
+        }
+        if let Some(funding) = funding {
+            packument["versions"][version.clone()]["funding"] = json!(funding);
+        }
+        if let Some(license) = license {
+            packument["versions"][version.clone()]["license"] = json!(license);
+        }
+        if let Some(bin) = bin {
+            packument["versions"][version.clone()]["bin"] = bin;
+        }
+        if let Some(scripts) = scripts {
+            packument["versions"][version.clone()]["scripts"] = scripts;
         }
         // Last version gets "latest"
         packument["dist-tags"]["latest"] = json!(version);
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use futures::future::try_join_all;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::observe::RequestKind;
+use crate::{OroClient, OroClientError};
+
+/// Most packages to ask about in a single bulk advisory request. Bigger
+/// requests are split up into batches of this size, so huge projects don't
+/// hit the registry's request size limits.
+pub const BULK_ADVISORY_BATCH_SIZE: usize = 500;
+
+/// How bad a vulnerability is, from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+/// A security advisory for a package, as returned by the bulk advisory
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Advisory {
+    pub id: u64,
+    pub url: String,
+    pub title: String,
+    pub severity: Severity,
+    /// Range of versions the advisory applies to.
+    pub vulnerable_versions: String,
+    #[serde(default)]
+    pub cwe: Vec<String>,
+    pub cvss: Option<Cvss>,
+}
+
+/// A CVSS score for a vulnerability.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cvss {
+    pub score: f64,
+    #[serde(rename = "vectorString")]
+    pub vector_string: Option<String>,
+}
+
+/// Advisories for each package that has any, keyed by package name.
+pub type BulkAdvisories = HashMap<String, Vec<Advisory>>;
+
+/// A dependency tree to audit with the quick audit endpoint, in the shape
+/// of an npm v1 lockfile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickAuditRequest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The root package's own dependencies, and their requested ranges.
+    #[serde(default)]
+    pub requires: HashMap<String, String>,
+    #[serde(default)]
+    pub dependencies: HashMap<String, QuickAuditDependency>,
+}
+
+/// A single installed package in a [`QuickAuditRequest`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickAuditDependency {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dev: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub requires: HashMap<String, String>,
+    /// Packages nested under this one, because they couldn't be hoisted.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub dependencies: HashMap<String, QuickAuditDependency>,
+}
+
+/// The quick audit endpoint's report on a dependency tree.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuickAuditReport {
+    /// Advisories that apply to the tree, keyed by advisory ID.
+    #[serde(default)]
+    pub advisories: HashMap<String, QuickAuditAdvisory>,
+    pub metadata: QuickAuditMetadata,
+    /// Everything else in the report, like suggested `actions`.
+    #[serde(flatten)]
+    pub rest: HashMap<String, Value>,
+}
+
+/// An advisory in a [`QuickAuditReport`], with the installed versions it
+/// was found in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuickAuditAdvisory {
+    pub id: u64,
+    pub title: String,
+    pub module_name: String,
+    pub severity: Severity,
+    pub vulnerable_versions: String,
+    pub patched_versions: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub findings: Vec<QuickAuditFinding>,
+    #[serde(flatten)]
+    pub rest: HashMap<String, Value>,
+}
+
+/// An installed version an advisory applies to, and the paths it was
+/// installed at, like `foo>bar`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickAuditFinding {
+    pub version: String,
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// Totals for a [`QuickAuditReport`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickAuditMetadata {
+    /// How many vulnerabilities were found, by severity, like
+    /// `"moderate"`.
+    #[serde(default)]
+    pub vulnerabilities: HashMap<String, u64>,
+    #[serde(default)]
+    pub dependencies: u64,
+    #[serde(default, rename = "devDependencies")]
+    pub dev_dependencies: u64,
+    #[serde(default, rename = "totalDependencies")]
+    pub total_dependencies: u64,
+}
+
+impl OroClient {
+    /// Fetches advisories that apply to any of the given versions of each
+    /// package, keyed by package name. Packages without any are left out.
+    /// Large requests are split into batches of
+    /// [`BULK_ADVISORY_BATCH_SIZE`] packages.
+    pub async fn bulk_advisories(
+        &self,
+        packages: &HashMap<String, Vec<String>>,
+    ) -> Result<BulkAdvisories, OroClientError> {
+        let url = self.registry.join("-/npm/v1/security/advisories/bulk")?;
+        let mut names = packages.keys().collect::<Vec<_>>();
+        // Sorted, so the same packages always make the same batches.
+        names.sort();
+        let batches = names.chunks(BULK_ADVISORY_BATCH_SIZE).map(|names| {
+            let batch = names
+                .iter()
+                .map(|name| (*name, &packages[*name]))
+                .collect::<HashMap<_, _>>();
+            self.post_audit::<_, BulkAdvisories>(&url, batch)
+        });
+        let mut advisories = BulkAdvisories::new();
+        for batch in try_join_all(batches).await? {
+            advisories.extend(batch);
+        }
+        Ok(advisories)
+    }
+
+    /// Audits a whole dependency tree at once, like `npm audit` does.
+    pub async fn quick_audit(
+        &self,
+        tree: &QuickAuditRequest,
+    ) -> Result<QuickAuditReport, OroClientError> {
+        let url = self.registry.join("-/npm/v1/security/audits/quick")?;
+        self.post_audit(&url, tree).await
+    }
+
+    async fn post_audit<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &Url,
+        body: B,
+    ) -> Result<T, OroClientError> {
+        let mut observation = self.observe(url, RequestKind::Other).await;
+        let result = self
+            .within_request_timeout(url, async {
+                let res = self
+                    .send_retrying(&mut observation, || async {
+                        let mut req = self.client_uncached_for(url).post(url.clone());
+                        req = req.headers(self.auth_headers(url).await?);
+                        Ok(req.json(&body).send().await?)
+                    })
+                    .await?;
+                match res.status() {
+                    StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => {
+                        return Err(OroClientError::AuditUnsupported(url.clone()));
+                    }
+                    StatusCode::BAD_REQUEST => {
+                        return Err(OroClientError::AuditRejected {
+                            url: url.clone(),
+                            message: res.text().await.unwrap_or_default(),
+                        });
+                    }
+                    _ => {}
+                }
+                let text = res.error_for_status()?.text().await?;
+                observation.bytes = text.len() as u64;
+                Ok::<_, OroClientError>(text)
+            })
+            .await;
+        observation.finish_with(self, &result);
+        let text = result?;
+        serde_json::from_str(&text)
+            .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use maplit::hashmap;
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn bulk_advisories() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::new(mock_server.uri().parse().into_diagnostic()?);
+
+        Mock::given(method("POST"))
+            .and(path("-/npm/v1/security/advisories/bulk"))
+            .and(body_json(json!({ "minimist": ["1.2.0"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "minimist": [{
+                    "id": 1179,
+                    "url": "https://github.com/advisories/GHSA-vh95-rmgr-6w4m",
+                    "title": "Prototype Pollution in minimist",
+                    "severity": "moderate",
+                    "vulnerable_versions": "<0.2.1 || >=1.0.0 <1.2.3",
+                    "cwe": ["CWE-1321"],
+                    "cvss": {
+                        "score": 5.6,
+                        "vectorString": "CVSS:3.1/AV:N/AC:H/PR:N/UI:N/S:U/C:L/I:L/A:L"
+                    }
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let advisories = client
+            .bulk_advisories(&hashmap! { "minimist".into() => vec!["1.2.0".into()] })
+            .await?;
+        assert_eq!(advisories["minimist"].len(), 1);
+        assert_eq!(advisories["minimist"][0].id, 1179);
+        assert_eq!(advisories["minimist"][0].severity, Severity::Moderate);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn unsupported_audits() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::new(mock_server.uri().parse().into_diagnostic()?);
+
+        Mock::given(method("POST"))
+            .and(path("-/npm/v1/security/audits/quick"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert!(matches!(
+            client.quick_audit(&QuickAuditRequest::default()).await,
+            Err(OroClientError::AuditUnsupported(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::observe::RequestKind;
+use crate::{OroClient, OroClientError};
+
+/// Predicate type of SLSA build provenance attestations.
+pub const SLSA_PROVENANCE_V1: &str = "https://slsa.dev/provenance/v1";
+/// Predicate type of older SLSA build provenance attestations.
+pub const SLSA_PROVENANCE_V0_2: &str = "https://slsa.dev/provenance/v0.2";
+
+/// Attestations published for a package version, as returned by the
+/// registry's attestations endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestations {
+    pub attestations: Vec<Attestation>,
+}
+
+/// A single attestation, in the form of a Sigstore bundle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+    pub bundle: AttestationBundle,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationBundle {
+    #[serde(rename = "dsseEnvelope")]
+    pub dsse_envelope: DsseEnvelope,
+    #[serde(flatten)]
+    pub rest: HashMap<String, Value>,
+}
+
+/// A signed envelope around an in-toto statement.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DsseEnvelope {
+    /// Base64-encoded statement.
+    pub payload: String,
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+    #[serde(default)]
+    pub signatures: Vec<Value>,
+}
+
+/// The in-toto statement an attestation makes about its subjects.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    pub statement_type: String,
+    pub subject: Vec<StatementSubject>,
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+    #[serde(default)]
+    pub predicate: Value,
+}
+
+/// An artifact an in-toto statement is about, like
+/// `pkg:npm/%40scope/pkg@1.0.0`, and its digests by algorithm.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementSubject {
+    pub name: String,
+    pub digest: HashMap<String, String>,
+}
+
+impl Attestation {
+    /// Decodes the statement inside this attestation's envelope. This
+    /// doesn't check the envelope's signatures.
+    pub fn statement(&self) -> Result<Statement, OroClientError> {
+        let payload = BASE64
+            .decode(&self.bundle.dsse_envelope.payload)
+            .map_err(|e| OroClientError::InvalidAttestation(e.to_string()))?;
+        serde_json::from_slice(&payload)
+            .map_err(|e| OroClientError::InvalidAttestation(e.to_string()))
+    }
+}
+
+impl OroClient {
+    /// Fetches the attestations at `url`, which usually comes from a
+    /// package version's `dist.attestations.url`.
+    pub async fn attestations(&self, url: &Url) -> Result<Attestations, OroClientError> {
+        let mut observation = self.observe(url, RequestKind::Other).await;
+        let result = self
+            .within_request_timeout(url, async {
+                let text = self
+                    .send_retrying(&mut observation, || async {
+                        let mut req = self.client_for(url).get(url.clone());
+                        req = req.headers(self.auth_headers(url).await?);
+                        Ok(req.send().await?)
+                    })
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                observation.bytes = text.len() as u64;
+                Ok::<_, OroClientError>(text)
+            })
+            .await;
+        observation.finish_with(self, &result);
+        let text = result?;
+        serde_json::from_str(&text)
+            .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn attestations() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::new(mock_server.uri().parse().into_diagnostic()?);
+        let statement = json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": [{
+                "name": "pkg:npm/oro@1.0.0",
+                "digest": { "sha512": "abcd" },
+            }],
+            "predicateType": SLSA_PROVENANCE_V1,
+            "predicate": {},
+        });
+        let body = json!({
+            "attestations": [{
+                "predicateType": SLSA_PROVENANCE_V1,
+                "bundle": {
+                    "mediaType": "application/vnd.dev.sigstore.bundle+json;version=0.1",
+                    "dsseEnvelope": {
+                        "payload": BASE64.encode(statement.to_string()),
+                        "payloadType": "application/vnd.in-toto+json",
+                        "signatures": [],
+                    },
+                },
+            }],
+        });
+
+        Mock::given(method("GET"))
+            .and(path("-/npm/v1/attestations/oro@1.0.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}/-/npm/v1/attestations/oro@1.0.0", mock_server.uri())
+            .parse()
+            .into_diagnostic()?;
+        let attestations = client.attestations(&url).await?;
+        let statement = attestations.attestations[0].statement()?;
+        assert_eq!(statement.predicate_type, SLSA_PROVENANCE_V1);
+        assert_eq!(statement.subject[0].name, "pkg:npm/oro@1.0.0");
+        assert_eq!(statement.subject[0].digest["sha512"], "abcd");
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::observe::RequestKind;
+use crate::{OroClient, OroClientError};
+
+/// Keys a registry signs package metadata with, as returned by its keys
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryKeys {
+    pub keys: Vec<RegistryKey>,
+}
+
+/// A single registry signing key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryKey {
+    pub keyid: String,
+    /// Kind of key, like `ecdsa-sha2-nistp256`.
+    pub keytype: String,
+    pub scheme: String,
+    /// Base64-encoded public key, in DER-encoded SubjectPublicKeyInfo form.
+    pub key: String,
+    /// When the key stopped being used to sign new packages, if it has.
+    pub expires: Option<String>,
+}
+
+impl OroClient {
+    /// Fetches the registry's signing keys, or `None` if it doesn't publish
+    /// any.
+    pub async fn keys(&self) -> Result<Option<RegistryKeys>, OroClientError> {
+        let url = self.registry.join("-/npm/v1/keys")?;
+        let mut observation = self.observe(&url, RequestKind::Other).await;
+        let result = self
+            .within_request_timeout(&url, async {
+                let res = self
+                    .send_retrying(&mut observation, || async {
+                        let mut req = self.client_for(&url).get(url.clone());
+                        req = req.headers(self.auth_headers(&url).await?);
+                        Ok(req.send().await?)
+                    })
+                    .await?;
+                if res.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                let text = res.error_for_status()?.text().await?;
+                observation.bytes = text.len() as u64;
+                Ok::<_, OroClientError>(Some(text))
+            })
+            .await;
+        observation.finish_with(self, &result);
+        let Some(text) = result? else {
+            return Ok(None);
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn keys() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::new(mock_server.uri().parse().into_diagnostic()?);
+        let body = json!({
+            "keys": [{
+                "expires": null,
+                "keyid": "SHA256:jl3bwswu80PjjokCgh0o2w5c2U4LhQAE57gj9cz1kzA",
+                "keytype": "ecdsa-sha2-nistp256",
+                "scheme": "ecdsa-sha2-nistp256",
+                "key": "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE1Olb3zMAFFxXKHiIkQO5cJ3Yhl5i6UPp+IhuteBJbuHcA5UogKo0EWtlWwW6KSaKoTNEYL7JlCQiVnkhBktUgg==",
+            }],
+        });
+
+        Mock::given(method("GET"))
+            .and(path("-/npm/v1/keys"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let keys = client.keys().await?.expect("keys were published");
+        assert_eq!(keys.keys.len(), 1);
+        assert_eq!(keys.keys[0].keytype, "ecdsa-sha2-nistp256");
+        assert_eq!(keys.keys[0].expires, None);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn no_keys() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::new(mock_server.uri().parse().into_diagnostic()?);
+
+        Mock::given(method("GET"))
+            .and(path("-/npm/v1/keys"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(client.keys().await?, None);
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+pub mod advisories;
+pub mod attestations;
+pub mod keys;
 pub mod packument;
 pub mod ping;
 pub mod stream_external;
@@ -1,6 +1,12 @@
+use node_semver::Version;
 use oro_common::{CorgiPackument, Packument};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
 use reqwest::{StatusCode, Url};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::observe::CacheStatus;
+use crate::observe::{Observation, RequestKind};
 use crate::{OroClient, OroClientError};
 
 pub(crate) const CORGI_HEADER: &str =
@@ -32,38 +38,127 @@ impl OroClient {
             .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
     }
 
+    /// Like [`OroClient::packument`], but only materializes the versions
+    /// `keep` accepts, which takes a lot less memory for packages with
+    /// many versions.
+    pub async fn packument_matching(
+        &self,
+        package_name: impl AsRef<str>,
+        keep: impl Fn(&Version) -> bool,
+    ) -> Result<Packument, OroClientError> {
+        let url = self.registry.join(package_name.as_ref())?;
+        let text = self.packument_impl(package_name, &url, false).await?;
+        Packument::from_json_matching(&text, keep)
+            .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
+    }
+
+    /// Like [`OroClient::corgi_packument`], but only materializes the
+    /// versions `keep` accepts, which takes a lot less memory for packages
+    /// with many versions.
+    pub async fn corgi_packument_matching(
+        &self,
+        package_name: impl AsRef<str>,
+        keep: impl Fn(&Version) -> bool,
+    ) -> Result<CorgiPackument, OroClientError> {
+        let url = self.registry.join(package_name.as_ref())?;
+        let text = self.packument_impl(package_name, &url, true).await?;
+        CorgiPackument::from_json_matching(&text, keep)
+            .map_err(move |e| OroClientError::from_json_err(e, url.to_string(), text))
+    }
+
     async fn packument_impl(
         &self,
         package_name: impl AsRef<str>,
         url: &Url,
         use_corgi: bool,
     ) -> Result<String, OroClientError> {
-        Ok(self
-            .client
-            .get(url.clone())
-            .header(
-                "Accept",
-                if use_corgi {
-                    CORGI_HEADER
-                } else {
-                    "application/json"
-                },
+        let mut observation = self.observe(url, RequestKind::Packument).await;
+        let result = self
+            .within_request_timeout(
+                url,
+                self.fetch_packument(package_name, url, use_corgi, &mut observation),
             )
-            .send()
-            .await?
-            .error_for_status()
-            .map_err(|err| {
-                if err.status() == Some(StatusCode::NOT_FOUND) {
-                    OroClientError::PackageNotFound(
-                        (*self.registry).clone(),
-                        package_name.as_ref().to_string(),
-                    )
-                } else {
-                    OroClientError::RequestError(err)
+            .await;
+        observation.finish_with(self, &result);
+        result
+    }
+
+    async fn fetch_packument(
+        &self,
+        package_name: impl AsRef<str>,
+        url: &Url,
+        use_corgi: bool,
+        observation: &mut Observation,
+    ) -> Result<String, OroClientError> {
+        // Packuments get revalidated against our own cache, rather than
+        // going through the generic HTTP cache.
+        #[cfg(not(target_arch = "wasm32"))]
+        let cache_key = format!(
+            "oro-client::packument::{}::{url}",
+            if use_corgi { "corgi" } else { "full" }
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        let cached = match self.cache.as_deref() {
+            Some(cache) => {
+                observation.cache = CacheStatus::Miss;
+                crate::cache::read(cache, &cache_key).await
+            }
+            None => None,
+        };
+        let res = self
+            .send_retrying(observation, || async {
+                let mut req = self.client_uncached_for(url).get(url.clone()).header(
+                    "Accept",
+                    if use_corgi {
+                        CORGI_HEADER
+                    } else {
+                        "application/json"
+                    },
+                );
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(cached) = &cached {
+                    if let Some(etag) = &cached.validators.etag {
+                        req = req.header(IF_NONE_MATCH, etag);
+                    }
+                    if let Some(last_modified) = &cached.validators.last_modified {
+                        req = req.header(IF_MODIFIED_SINCE, last_modified);
+                    }
                 }
-            })?
-            .text()
-            .await?)
+                req = req.headers(self.auth_headers(url).await?);
+                Ok(req.send().await?)
+            })
+            .await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if res.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                tracing::trace!("packument for {} is unchanged", package_name.as_ref());
+                observation.cache = CacheStatus::Hit;
+                return Ok(cached.body);
+            }
+        }
+        let res = res.error_for_status().map_err(|err| {
+            if err.status() == Some(StatusCode::NOT_FOUND) {
+                OroClientError::PackageNotFound(
+                    (*self.registry).clone(),
+                    package_name.as_ref().to_string(),
+                )
+            } else {
+                OroClientError::RequestError(err)
+            }
+        })?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let validators = crate::cache::Validators::from_headers(res.headers());
+        let text = res.text().await?;
+        observation.bytes = text.len() as u64;
+        // Packuments are read whole, so they count against the network
+        // limit once they're in.
+        #[cfg(not(target_arch = "wasm32"))]
+        self.throttle(text.len()).await;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Some(cache), Some(validators)) = (self.cache.as_deref(), validators) {
+            crate::cache::write(cache, &cache_key, &text, &validators).await;
+        }
+        Ok(text)
     }
 }
 
@@ -161,4 +256,41 @@ mod test {
 
         Ok(())
     }
+    #[async_std::test]
+    async fn packument_revalidation() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let cache = tempfile::tempdir().into_diagnostic()?;
+        let client = OroClient::builder()
+            .registry(mock_server.uri().parse().into_diagnostic()?)
+            .cache(cache.path())
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("some-pkg"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("some-pkg"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_json(json!({
+                        "versions": {
+                            "1.0.0": { "name": "some-pkg", "version": "1.0.0" }
+                        }
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let fetched = client.corgi_packument("some-pkg").await?;
+        let revalidated = client.corgi_packument("some-pkg").await?;
+        assert_eq!(fetched, revalidated);
+        assert_eq!(revalidated.versions.len(), 1);
+        Ok(())
+    }
 }
@@ -1,15 +1,28 @@
+use crate::observe::RequestKind;
 use crate::{OroClient, OroClientError};
 
 impl OroClient {
     pub async fn ping(&self) -> Result<String, OroClientError> {
-        Ok(self
-            .client
-            .get(self.registry.join("-/ping?write=true")?)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?)
+        let url = self.registry.join("-/ping?write=true")?;
+        let mut observation = self.observe(&url, RequestKind::Other).await;
+        let result = self
+            .within_request_timeout(&url, async {
+                let text = self
+                    .send_retrying(&mut observation, || async {
+                        let mut req = self.client_for(&url).get(url.clone());
+                        req = req.headers(self.auth_headers(&url).await?);
+                        Ok(req.send().await?)
+                    })
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                observation.bytes = text.len() as u64;
+                Ok::<_, OroClientError>(text)
+            })
+            .await;
+        observation.finish_with(self, &result);
+        result
     }
 }
 
@@ -1,9 +1,26 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::pin::Pin;
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::{Context, Poll};
+
+#[cfg(not(target_arch = "wasm32"))]
+use bytes::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::{BoxStream, Stream};
 use futures::{
     stream::{StreamExt, TryStreamExt},
     AsyncRead,
 };
+use reqwest::header::HeaderMap;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::header::{HeaderValue, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::{Response, StatusCode};
 use url::Url;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::observe::Observation;
+use crate::observe::RequestKind;
 use crate::{OroClient, OroClientError};
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -12,15 +29,81 @@ type Result = std::result::Result<Box<dyn AsyncRead + Unpin + Send + Sync>, OroC
 type Result = std::result::Result<Box<dyn AsyncRead + Unpin>, OroClientError>;
 
 impl OroClient {
+    /// Streams the body at `url`, like a package tarball. If the connection
+    /// drops partway through, the download picks up where it left off with
+    /// a `Range` request, as many times as the retry policy allows, instead
+    /// of starting over. Nothing here checks that the resumed body is
+    /// intact, so callers should check its integrity once it's complete.
     pub async fn stream_external(&self, url: &Url) -> Result {
+        self.stream(url, false).await
+    }
+
+    /// Streams the tarball at `url`, of a package from this client's
+    /// registry, like [`OroClient::stream_external`]. The registry's
+    /// credentials get sent along even if the tarball is hosted somewhere
+    /// else, when it's set to [`crate::OroClientBuilder::always_auth`].
+    pub async fn stream_tarball(&self, url: &Url) -> Result {
+        self.stream(url, true).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn stream(&self, url: &Url, registry_auth: bool) -> Result {
+        let mut observation = self.observe(url, RequestKind::Tarball).await;
+        let res = match self
+            .request_external(url, None, registry_auth, &mut observation)
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                observation.finish(self, Some(&err));
+                return Err(err);
+            }
+        };
+        let download = Download {
+            client: self.clone(),
+            url: url.clone(),
+            registry_auth,
+            validator: res
+                .headers()
+                .get(ETAG)
+                .or_else(|| res.headers().get(LAST_MODIFIED))
+                .cloned(),
+            received: 0,
+            skip: 0,
+            resumes: 0,
+            body: res.bytes_stream().boxed(),
+            done: false,
+            observation: Some(observation),
+        };
+        let stream = futures::stream::unfold(download, |mut download| async move {
+            let chunk = download.next_chunk().await?;
+            Some((chunk, download))
+        });
         Ok(Box::new(
-            // NOTE: We don't want to cache these requests. If you want to
-            // cache them, cache them manually.
-            self.client_uncached
-                .get(url.to_string())
-                .send()
+            SyncStream(std::sync::Mutex::new(stream.boxed())).into_async_read(),
+        ))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn stream(&self, url: &Url, registry_auth: bool) -> Result {
+        // Bodies are handed straight to the caller here, so their size
+        // isn't known when the request gets reported.
+        let mut observation = self.observe(url, RequestKind::Tarball).await;
+        let result = async {
+            Ok::<_, OroClientError>(
+                self.send_retrying(&mut observation, || async {
+                    let mut req = self.client_uncached_for(url).get(url.to_string());
+                    req = req.headers(self.external_auth_headers(url, registry_auth).await?);
+                    Ok(req.send().await?)
+                })
                 .await?
-                .error_for_status()?
+                .error_for_status()?,
+            )
+        }
+        .await;
+        observation.finish_with(self, &result);
+        Ok(Box::new(
+            result?
                 .bytes_stream()
                 .map(|r| match r {
                     Ok(bytes) => Ok(bytes),
@@ -29,6 +112,212 @@ impl OroClient {
                 .into_async_read(),
         ))
     }
+
+    /// Requests `url`, starting at byte `from` if it's given. `validator`
+    /// makes sure the rest of the body comes from the same version of it.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn request_external(
+        &self,
+        url: &Url,
+        from: Option<(u64, Option<&HeaderValue>)>,
+        registry_auth: bool,
+        observation: &mut Observation,
+    ) -> std::result::Result<Response, OroClientError> {
+        // NOTE: We don't want to cache these requests. If you want to
+        // cache them, cache them manually.
+        Ok(self
+            .send_retrying(observation, || async {
+                let mut req = self.client_uncached_for(url).get(url.to_string());
+                if let Some((from, validator)) = from {
+                    req = req.header(RANGE, format!("bytes={from}-"));
+                    if let Some(validator) = validator {
+                        req = req.header(IF_RANGE, validator.clone());
+                    }
+                }
+                req = req.headers(self.external_auth_headers(url, registry_auth).await?);
+                Ok(req.send().await?)
+            })
+            .await?
+            .error_for_status()?)
+    }
+
+    async fn external_auth_headers(
+        &self,
+        url: &Url,
+        registry_auth: bool,
+    ) -> std::result::Result<HeaderMap, OroClientError> {
+        if registry_auth {
+            self.tarball_auth_headers(url).await
+        } else {
+            self.auth_headers(url).await
+        }
+    }
+}
+
+/// Makes the download stream `Sync`, even though the requests it makes
+/// partway through aren't. It's only ever polled through `&mut`, so the
+/// lock is never contended.
+#[cfg(not(target_arch = "wasm32"))]
+struct SyncStream(std::sync::Mutex<BoxStream<'static, std::io::Result<Bytes>>>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Stream for SyncStream {
+    type Item = std::io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().0.get_mut() {
+            Ok(stream) => stream.poll_next_unpin(cx),
+            Err(poisoned) => poisoned.into_inner().poll_next_unpin(cx),
+        }
+    }
+}
+
+/// A download that can pick up where it left off when its connection
+/// drops.
+#[cfg(not(target_arch = "wasm32"))]
+struct Download {
+    client: OroClient,
+    url: Url,
+    /// Whether this is a tarball from the client's registry, which might
+    /// need the registry's credentials.
+    registry_auth: bool,
+    /// `ETag` or `Last-Modified` of the original response, so resumed
+    /// requests only get the rest of the same body.
+    validator: Option<HeaderValue>,
+    /// Bytes handed out so far.
+    received: u64,
+    /// Bytes to drop from the start of the current response, when the
+    /// server sent the whole body again instead of just the rest of it.
+    skip: u64,
+    resumes: u32,
+    body: BoxStream<'static, reqwest::Result<Bytes>>,
+    done: bool,
+    /// Reported once the download is done, or given up on.
+    observation: Option<Observation>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Download {
+    async fn next_chunk(&mut self) -> Option<std::io::Result<Bytes>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            // A download that goes quiet for too long gets resumed, just
+            // like one whose connection dropped.
+            let chunk =
+                async_std::future::timeout(self.client.first_byte_timeout, self.body.next())
+                    .await
+                    .map(|chunk| chunk.map(|chunk| chunk.map_err(OroClientError::from)))
+                    .unwrap_or_else(|_| {
+                        Some(Err(OroClientError::ResponseTimeout(
+                            self.url.clone(),
+                            self.client.first_byte_timeout,
+                        )))
+                    });
+            match chunk {
+                Some(Ok(mut bytes)) => {
+                    if let Some(observation) = &mut self.observation {
+                        observation.bytes += bytes.len() as u64;
+                    }
+                    if self.skip > 0 {
+                        let skipped = self.skip.min(bytes.len() as u64);
+                        self.skip -= skipped;
+                        bytes = bytes.slice(skipped as usize..);
+                        if bytes.is_empty() {
+                            continue;
+                        }
+                    }
+                    self.client.throttle(bytes.len()).await;
+                    self.received += bytes.len() as u64;
+                    return Some(Ok(bytes));
+                }
+                Some(Err(err)) if self.resumes < self.client.retry.retries => {
+                    self.resumes += 1;
+                    tracing::debug!(
+                        "Download of {} failed after {} bytes ({err}), resuming.",
+                        self.url,
+                        self.received
+                    );
+                    if let Err(err) = self.resume().await {
+                        self.finish(Some(&err));
+                        return Some(Err(std::io::Error::new(std::io::ErrorKind::Other, err)));
+                    }
+                }
+                Some(Err(err)) => {
+                    self.finish(Some(&err));
+                    return Some(Err(std::io::Error::new(std::io::ErrorKind::Other, err)));
+                }
+                None => {
+                    self.finish(None);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, error: Option<&dyn std::fmt::Display>) {
+        self.done = true;
+        if let Some(observation) = self.observation.take() {
+            observation.finish(&self.client, error);
+        }
+    }
+
+    /// Asks for the rest of the body, starting from the first byte that
+    /// hasn't been handed out yet.
+    async fn resume(&mut self) -> std::result::Result<(), OroClientError> {
+        let delay = self.client.retry.delay(self.resumes - 1, None);
+        async_std::task::sleep(delay).await;
+        let mut observation = match self.observation.take() {
+            Some(observation) => observation,
+            None => self.client.observe(&self.url, RequestKind::Tarball).await,
+        };
+        observation.retries += 1;
+        let res = self
+            .client
+            .request_external(
+                &self.url,
+                Some((self.received, self.validator.as_ref())),
+                self.registry_auth,
+                &mut observation,
+            )
+            .await;
+        self.observation = Some(observation);
+        let res = res?;
+        self.skip = if res.status() == StatusCode::PARTIAL_CONTENT {
+            let start = res
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .and_then(|range| range.strip_prefix("bytes "))
+                .and_then(|range| range.split('-').next())
+                .and_then(|start| start.parse::<u64>().ok());
+            match start {
+                Some(start) if start <= self.received => self.received - start,
+                _ => {
+                    return Err(OroClientError::BadContentRange(
+                        self.url.clone(),
+                        self.received,
+                    ))
+                }
+            }
+        } else {
+            // The server ignored the range, or the body changed since the
+            // download started, so this is the whole body again.
+            self.received
+        };
+        self.body = res.bytes_stream().boxed();
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Download {
+    fn drop(&mut self) {
+        if !self.done {
+            self.finish(Some(&"the download was abandoned before it finished"));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -36,7 +325,7 @@ mod test {
     use futures::AsyncReadExt;
     use miette::{IntoDiagnostic, Result};
     use pretty_assertions::assert_eq;
-    use wiremock::matchers::{method, path, query_param};
+    use wiremock::matchers::{header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
@@ -44,7 +333,9 @@ mod test {
     #[async_std::test]
     async fn stream_external() -> Result<()> {
         let mock_server = MockServer::start().await;
-        let client: OroClient = Default::default();
+        let client = OroClient::builder()
+            .retry_policy(crate::RetryPolicy::none())
+            .build();
         let server_url: Url = mock_server.uri().parse().into_diagnostic()?;
 
         {
@@ -96,4 +387,158 @@ mod test {
 
         Ok(())
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_std::test]
+    async fn resumes_downloads() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::builder()
+            .retry_policy(crate::RetryPolicy::none())
+            .build();
+        let url: Url = mock_server
+            .uri()
+            .parse::<Url>()
+            .into_diagnostic()?
+            .join("tarball.tgz")
+            .into_diagnostic()?;
+        let interrupted = || Download {
+            client: client.clone(),
+            url: url.clone(),
+            registry_auth: false,
+            validator: Some(HeaderValue::from_static("\"v1\"")),
+            received: 3,
+            skip: 0,
+            resumes: 1,
+            body: futures::stream::empty().boxed(),
+            done: false,
+            observation: None,
+        };
+        let rest = |mut download: Download| async move {
+            download.resume().await?;
+            let mut data = Vec::new();
+            while let Some(chunk) = download.next_chunk().await {
+                data.extend_from_slice(&chunk.into_diagnostic()?);
+            }
+            Ok::<_, miette::Report>(data)
+        };
+
+        {
+            let _guard = Mock::given(method("GET"))
+                .and(path("tarball.tgz"))
+                .and(header("range", "bytes=3-"))
+                .and(header("if-range", "\"v1\""))
+                .respond_with(
+                    ResponseTemplate::new(206)
+                        .insert_header("content-range", "bytes 3-5/6")
+                        .set_body_raw("bar".as_bytes().to_owned(), "application/octet-stream"),
+                )
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+            assert_eq!(rest(interrupted()).await?, "bar".as_bytes().to_owned());
+        }
+
+        {
+            // Servers that don't do ranges send the whole thing again.
+            let _guard = Mock::given(method("GET"))
+                .and(path("tarball.tgz"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_raw("foobar".as_bytes().to_owned(), "application/octet-stream"),
+                )
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+            assert_eq!(rest(interrupted()).await?, "bar".as_bytes().to_owned());
+        }
+
+        {
+            let _guard = Mock::given(method("GET"))
+                .and(path("tarball.tgz"))
+                .respond_with(
+                    ResponseTemplate::new(206)
+                        .insert_header("content-range", "bytes 4-5/6")
+                        .set_body_raw("ar".as_bytes().to_owned(), "application/octet-stream"),
+                )
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+            assert!(matches!(
+                interrupted().resume().await,
+                Err(OroClientError::BadContentRange(..))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_std::test]
+    async fn gives_up_after_retries() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::builder()
+            .retry_policy(
+                crate::RetryPolicy::new()
+                    .retries(1)
+                    .base_delay(std::time::Duration::ZERO)
+                    .jitter(false),
+            )
+            .first_byte_timeout(std::time::Duration::from_millis(50))
+            .build();
+        let url: Url = mock_server
+            .uri()
+            .parse::<Url>()
+            .into_diagnostic()?
+            .join("tarball.tgz")
+            .into_diagnostic()?;
+        // A download whose connection went quiet after 3 bytes.
+        let stalled = |resumes| Download {
+            client: client.clone(),
+            url: url.clone(),
+            registry_auth: false,
+            validator: Some(HeaderValue::from_static("\"v1\"")),
+            received: 3,
+            skip: 0,
+            resumes,
+            body: futures::stream::pending().boxed(),
+            done: false,
+            observation: None,
+        };
+
+        {
+            let _guard = Mock::given(method("GET"))
+                .and(path("tarball.tgz"))
+                .and(header("range", "bytes=3-"))
+                .respond_with(
+                    ResponseTemplate::new(206)
+                        .insert_header("content-range", "bytes 3-5/6")
+                        .set_body_raw("bar".as_bytes().to_owned(), "application/octet-stream"),
+                )
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+            let mut download = stalled(0);
+            let chunk = download.next_chunk().await.unwrap().into_diagnostic()?;
+            assert_eq!(chunk, "bar".as_bytes());
+            assert_eq!(download.resumes, 1);
+        }
+
+        {
+            // Once the retries are used up, the error is returned instead
+            // of resuming again.
+            let _guard = Mock::given(method("GET"))
+                .and(path("tarball.tgz"))
+                .respond_with(ResponseTemplate::new(206))
+                .expect(0)
+                .mount_as_scoped(&mock_server)
+                .await;
+            let mut download = stalled(1);
+            let err = download.next_chunk().await.unwrap().unwrap_err();
+            assert!(err.to_string().contains("tarball.tgz"), "{err}");
+            assert!(download.done);
+            assert!(download.next_chunk().await.is_none());
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,102 @@
+//! Registry responses kept in the cache along with their `ETag` and
+//! `Last-Modified` validators, so they can be revalidated with a
+//! conditional request instead of downloaded again. Popular packuments can
+//! be several megabytes, while a `304 Not Modified` is tiny.
+//!
+//! The cache is best-effort: entries that can't be read or written are
+//! treated as missing.
+
+use std::path::Path;
+
+use futures::AsyncWriteExt;
+use reqwest::header::{HeaderMap, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+
+/// A cached response body, and the validators to revalidate it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CachedResponse {
+    pub(crate) body: String,
+    pub(crate) validators: Validators,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Validators {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Validators a response came with, if any.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let validators = Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        (validators.etag.is_some() || validators.last_modified.is_some()).then_some(validators)
+    }
+}
+
+pub(crate) async fn read(cache: &Path, key: &str) -> Option<CachedResponse> {
+    let entry = match cacache::metadata(cache, key).await {
+        Ok(entry) => entry?,
+        Err(err) => {
+            tracing::debug!("Failed to read cache index for {key}: {err}");
+            return None;
+        }
+    };
+    let validators = serde_json::from_value(entry.metadata).ok()?;
+    let body = match cacache::read_hash(cache, &entry.integrity).await {
+        Ok(body) => String::from_utf8(body).ok()?,
+        Err(err) => {
+            tracing::debug!("Failed to read cached response for {key}: {err}");
+            return None;
+        }
+    };
+    Some(CachedResponse { body, validators })
+}
+
+pub(crate) async fn write(cache: &Path, key: &str, body: &str, validators: &Validators) {
+    let result = async {
+        let mut writer = cacache::WriteOpts::new()
+            .size(body.len())
+            .metadata(serde_json::to_value(validators)?)
+            .open(cache, key)
+            .await?;
+        writer.write_all(body.as_bytes()).await?;
+        writer.commit().await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+    }
+    .await;
+    if let Err(err) = result {
+        tracing::debug!("Failed to cache response for {key}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn round_trips() {
+        let cache = tempfile::tempdir().unwrap();
+        let validators = Validators {
+            etag: Some("\"abc\"".into()),
+            last_modified: None,
+        };
+        assert_eq!(read(cache.path(), "key").await, None);
+        write(cache.path(), "key", "{}", &validators).await;
+        assert_eq!(
+            read(cache.path(), "key").await,
+            Some(CachedResponse {
+                body: "{}".into(),
+                validators,
+            })
+        );
+    }
+}
@@ -0,0 +1,509 @@
+//! Credentials for registries that only serve packages to authenticated
+//! clients, like most private registries.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use url::Url;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::helper::CredentialHelper;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::s3::S3Credentials;
+use crate::{OroClient, OroClientError};
+
+/// How to authenticate requests to a registry. Credentials are only ever
+/// sent to the host they were configured for, including requests for
+/// tarballs hosted there.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// An access token, sent as a bearer token. This is what `_authToken`
+    /// is in `.npmrc`.
+    Token(String),
+    /// A username and password, sent with HTTP basic auth.
+    Basic { username: String, password: String },
+    /// An already-encoded `username:password` pair, like `.npmrc`'s legacy
+    /// `_auth` setting.
+    LegacyAuth(String),
+    /// Access keys for S3-compatible object storage that packages are
+    /// mirrored into. Requests are signed with AWS Signature Version 4.
+    #[cfg(not(target_arch = "wasm32"))]
+    S3(S3Credentials),
+    /// An external command that hands out short-lived credentials whenever
+    /// they're needed. See [`CredentialHelper`].
+    #[cfg(not(target_arch = "wasm32"))]
+    Helper(CredentialHelper),
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // These end up in debug logs, so secrets are never printed.
+        match self {
+            Self::Token(_) => f.write_str("Token(<redacted>)"),
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+            Self::LegacyAuth(_) => f.write_str("LegacyAuth(<redacted>)"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::S3(s3) => f.debug_tuple("S3").field(s3).finish(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Helper(helper) => helper.fmt(f),
+        }
+    }
+}
+
+impl Credentials {
+    /// Credentials configured in an `.npmrc` file, keyed the way
+    /// [`crate::OroClientBuilder::credentials`] expects. These are the
+    /// settings scoped to a registry URL, like
+    /// `//registry.corp/npm/:_authToken=...`. `${VAR}` references are
+    /// replaced with environment variables, and credentials that refer to
+    /// unset ones are skipped, unless they're written as `${VAR?}`.
+    pub fn from_npmrc(src: &str) -> Vec<(String, Credentials)> {
+        Self::from_npmrc_with_env(src, &|name| std::env::var(name).ok())
+    }
+
+    fn from_npmrc_with_env(
+        src: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Vec<(String, Credentials)> {
+        npmrc_fields(src, env)
+            .into_iter()
+            .filter_map(|(key, fields)| {
+                let credentials = if let Some(token) = fields.get("_authToken") {
+                    Credentials::Token(token.clone())
+                } else if let (Some(username), Some(password)) =
+                    (fields.get("username"), fields.get("_password"))
+                {
+                    // Unlike everything else, `_password` is base64-encoded.
+                    let Some(password) = BASE64
+                        .decode(password)
+                        .ok()
+                        .and_then(|password| String::from_utf8(password).ok())
+                    else {
+                        tracing::warn!(
+                            "Ignoring credentials for //{key} in .npmrc, because `_password` isn't valid base64."
+                        );
+                        return None;
+                    };
+                    Credentials::Basic {
+                        username: username.clone(),
+                        password,
+                    }
+                } else if let Some(auth) = fields.get("_auth") {
+                    Credentials::LegacyAuth(auth.clone())
+                } else {
+                    return None;
+                };
+                Some((key, credentials))
+            })
+            .collect()
+    }
+
+    /// Registries an `.npmrc` file sets `always-auth` for, keyed the way
+    /// [`crate::OroClientBuilder::always_auth`] expects.
+    pub fn always_auth_from_npmrc(src: &str) -> Vec<String> {
+        Self::always_auth_from_npmrc_with_env(src, &|name| std::env::var(name).ok())
+    }
+
+    fn always_auth_from_npmrc_with_env(
+        src: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Vec<String> {
+        npmrc_fields(src, env)
+            .into_iter()
+            .filter(|(_, fields)| fields.get("always-auth").map(String::as_str) == Some("true"))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Headers that authenticate a request to `url` with these
+    /// credentials.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn headers(&self, url: &Url) -> Result<HeaderMap, reqwest::header::InvalidHeaderValue> {
+        let value = match self {
+            Self::Token(token) => format!("Bearer {token}"),
+            Self::Basic { username, password } => {
+                format!("Basic {}", BASE64.encode(format!("{username}:{password}")))
+            }
+            Self::LegacyAuth(auth) => format!("Basic {auth}"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::S3(s3) => return s3.sign(url, chrono::Utc::now()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Helper(_) => unreachable!("helpers are run before their headers are needed"),
+        };
+        let mut value = HeaderValue::from_str(&value)?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, value);
+        Ok(headers)
+    }
+}
+
+impl OroClient {
+    /// Headers that authenticate a request to `url`, if there are
+    /// credentials for it.
+    pub(crate) async fn auth_headers(&self, url: &Url) -> Result<HeaderMap, OroClientError> {
+        match self.credentials_for(url) {
+            Some((key, credentials)) => Self::headers_for(key, credentials, url).await,
+            None => Ok(HeaderMap::new()),
+        }
+    }
+
+    /// Headers that authenticate a request for a tarball at `url`, of a
+    /// package from this client's registry. Tarballs hosted somewhere the
+    /// registry's credentials don't cover only get them when the registry
+    /// is set to [`crate::OroClientBuilder::always_auth`].
+    pub(crate) async fn tarball_auth_headers(
+        &self,
+        url: &Url,
+    ) -> Result<HeaderMap, OroClientError> {
+        let credentials = self.credentials_for(url).or_else(|| {
+            self.credentials_for(&self.registry)
+                .filter(|(key, _)| self.always_auth.contains(key))
+        });
+        match credentials {
+            Some((key, credentials)) => Self::headers_for(key, credentials, url).await,
+            None => Ok(HeaderMap::new()),
+        }
+    }
+
+    /// Headers that authenticate a request to `url` with `credentials`,
+    /// which were configured for `key`. Credential helpers get asked for
+    /// the actual credentials first.
+    async fn headers_for(
+        key: String,
+        credentials: &Credentials,
+        url: &Url,
+    ) -> Result<HeaderMap, OroClientError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Credentials::Helper(helper) = credentials {
+            let registry = format!("{}://{key}/", url.scheme());
+            return helper
+                .credentials(&registry)
+                .await?
+                .headers(url)
+                .map_err(|_| OroClientError::InvalidCredentials(key));
+        }
+        credentials
+            .headers(url)
+            .map_err(|_| OroClientError::InvalidCredentials(key))
+    }
+
+    /// The credentials for `url`, and the key they were configured for.
+    /// Like npm, these are the ones for the longest prefix of the URL they
+    /// were configured for, like `registry.corp:8443/npm`, then
+    /// `registry.corp:8443`, then `registry.corp`.
+    fn credentials_for(&self, url: &Url) -> Option<(String, &Credentials)> {
+        if self.credentials.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?.to_lowercase();
+        let segments = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let hosts = url
+            .port()
+            .map(|port| format!("{host}:{port}"))
+            .into_iter()
+            .chain(std::iter::once(host.clone()));
+        for host in hosts {
+            for len in (0..=segments.len()).rev() {
+                let key = if len == 0 {
+                    host.clone()
+                } else {
+                    format!("{host}/{}", segments[..len].join("/"))
+                };
+                if let Some(credentials) = self.credentials.get(&key) {
+                    return Some((key, credentials));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Registry-specific settings in an `.npmrc` file, like
+/// `//registry.corp/npm/:_authToken`, grouped by the normalized key they're
+/// for. Settings that aren't scoped to a registry, like a bare `_auth`, are
+/// npm's legacy way of configuring the default registry, so they're used
+/// for the file's `registry`, unless it has scoped ones of its own.
+fn npmrc_fields<'a>(
+    src: &'a str,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> BTreeMap<String, HashMap<&'a str, String>> {
+    let mut fields = BTreeMap::<String, HashMap<&str, String>>::new();
+    let mut registry = "https://registry.npmjs.org/".to_string();
+    let mut legacy = HashMap::new();
+    for line in src.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim().trim_matches('"');
+        if key == "registry" {
+            registry = value.to_string();
+            continue;
+        }
+        let (prefix, field) = match key.strip_prefix("//").and_then(|key| key.rsplit_once(':')) {
+            Some((prefix, field)) => (Some(prefix), field),
+            None if LEGACY_FIELDS.contains(&key) => (None, key),
+            None => continue,
+        };
+        let Some(value) = interpolate(value, env) else {
+            tracing::warn!(
+                "Ignoring `{field}` for //{} in .npmrc, because it refers to an unset environment variable.",
+                prefix.unwrap_or("the default registry")
+            );
+            continue;
+        };
+        match prefix {
+            Some(prefix) => {
+                fields
+                    .entry(normalize_key(prefix))
+                    .or_default()
+                    .insert(field, value);
+            }
+            None => {
+                legacy.insert(field, value);
+            }
+        }
+    }
+    if !legacy.is_empty() {
+        let key = normalize_key(
+            registry
+                .split_once("://")
+                .map_or(registry.as_str(), |(_, rest)| rest),
+        );
+        fields.entry(key).or_insert(legacy);
+    }
+    fields
+}
+
+/// `.npmrc` settings that configure the default registry when they're not
+/// scoped to one.
+const LEGACY_FIELDS: &[&str] = &[
+    "_auth",
+    "_authToken",
+    "username",
+    "_password",
+    "always-auth",
+];
+
+/// Normalizes a key that credentials are configured for, like
+/// `//Registry.corp:8443/npm/`, into `registry.corp:8443/npm`. Only the
+/// host is case-insensitive.
+pub(crate) fn normalize_key(key: &str) -> String {
+    let key = key.trim_start_matches("//").trim_end_matches('/');
+    match key.split_once('/') {
+        Some((host, path)) => format!("{}/{path}", host.to_lowercase()),
+        None => key.to_lowercase(),
+    }
+}
+
+/// Replaces `${VAR}` references in an `.npmrc` value with environment
+/// variables. Returns `None` if any of them aren't set, unless they're
+/// written as `${VAR?}`, which are replaced with nothing instead.
+fn interpolate(value: &str, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    let mut interpolated = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        interpolated.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + len];
+        match name.strip_suffix('?') {
+            Some(name) => interpolated.push_str(&env(name).unwrap_or_default()),
+            None => interpolated.push_str(&env(name)?),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    interpolated.push_str(rest);
+    Some(interpolated)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn picks_credentials_by_host() -> Result<()> {
+        let client = OroClient::builder()
+            .credentials("Registry.corp", Credentials::Token("abc".into()))
+            .credentials(
+                "registry.corp:8443",
+                Credentials::Basic {
+                    username: "user".into(),
+                    password: "pass".into(),
+                },
+            )
+            .build();
+        let auth = |url: &'static str| {
+            let client = &client;
+            async move {
+                Ok::<_, miette::Report>(
+                    client
+                        .auth_headers(&url.parse().into_diagnostic()?)
+                        .await?
+                        .get(AUTHORIZATION)
+                        .map(|value| value.to_str().unwrap().to_string()),
+                )
+            }
+        };
+        assert_eq!(
+            auth("https://registry.corp/foo").await?,
+            Some("Bearer abc".into())
+        );
+        assert_eq!(
+            auth("https://registry.corp:8443/foo").await?,
+            Some("Basic dXNlcjpwYXNz".into())
+        );
+        assert_eq!(auth("https://registry.npmjs.org/foo").await?, None);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn picks_longest_prefix() -> Result<()> {
+        let client = OroClient::builder()
+            .credentials("registry.corp", Credentials::Token("host".into()))
+            .credentials("//registry.corp/npm/", Credentials::Token("path".into()))
+            .build();
+        let auth = |url: &'static str| {
+            let client = &client;
+            async move {
+                Ok::<_, miette::Report>(
+                    client
+                        .auth_headers(&url.parse().into_diagnostic()?)
+                        .await?
+                        .get(AUTHORIZATION)
+                        .map(|value| value.to_str().unwrap().to_string()),
+                )
+            }
+        };
+        assert_eq!(
+            auth("https://registry.corp/npm/@scope%2fpkg").await?,
+            Some("Bearer path".into())
+        );
+        assert_eq!(
+            auth("https://registry.corp/other/pkg").await?,
+            Some("Bearer host".into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_npmrc() {
+        let env = |name: &str| (name == "NPM_TOKEN").then(|| "sekrit".to_string());
+        let npmrc = r#"
+registry=https://registry.corp/npm/
+//registry.corp/npm/:_authToken=${NPM_TOKEN}
+//other.corp:8443/:username=user
+//other.corp:8443/:_password="cGFzcw=="
+//missing.corp/:_authToken=${UNSET_TOKEN}
+//optional.corp/:_authToken=prefix-${UNSET_TOKEN?}
+"#;
+        assert_eq!(
+            Credentials::from_npmrc_with_env(npmrc, &env),
+            vec![
+                ("optional.corp".into(), Credentials::Token("prefix-".into())),
+                (
+                    "other.corp:8443".into(),
+                    Credentials::Basic {
+                        username: "user".into(),
+                        password: "pass".into(),
+                    }
+                ),
+                (
+                    "registry.corp/npm".into(),
+                    Credentials::Token("sekrit".into())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_legacy_npmrc() {
+        let env = |_: &str| None;
+        let npmrc = r#"
+_auth=dXNlcjpwYXNz
+always-auth=true
+registry=https://registry.corp/npm/
+//other.corp/:_authToken=abc
+//other.corp/:always-auth=false
+"#;
+        assert_eq!(
+            Credentials::from_npmrc_with_env(npmrc, &env),
+            vec![
+                ("other.corp".into(), Credentials::Token("abc".into())),
+                (
+                    "registry.corp/npm".into(),
+                    Credentials::LegacyAuth("dXNlcjpwYXNz".into())
+                ),
+            ]
+        );
+        assert_eq!(
+            Credentials::always_auth_from_npmrc_with_env(npmrc, &env),
+            vec!["registry.corp/npm".to_string()]
+        );
+    }
+
+    #[async_std::test]
+    async fn always_auth_for_tarballs() -> Result<()> {
+        let client = OroClient::builder()
+            .registry("https://registry.corp/npm/".parse().into_diagnostic()?)
+            .credentials("registry.corp/npm", Credentials::Token("abc".into()))
+            .build();
+        let url = "https://cdn.corp/foo.tgz".parse().into_diagnostic()?;
+        assert!(client.tarball_auth_headers(&url).await?.is_empty());
+        let client = OroClient::builder()
+            .registry("https://registry.corp/npm/".parse().into_diagnostic()?)
+            .credentials("registry.corp/npm", Credentials::Token("abc".into()))
+            .always_auth("//registry.corp/npm/")
+            .build();
+        assert_eq!(
+            client.tarball_auth_headers(&url).await?.get(AUTHORIZATION),
+            Some(&HeaderValue::from_static("Bearer abc"))
+        );
+        assert!(client.auth_headers(&url).await?.is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sends_credentials() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let url: Url = mock_server.uri().parse().into_diagnostic()?;
+        let client = OroClient::builder()
+            .registry(url.clone())
+            .credentials(
+                format!("{}:{}", url.host_str().unwrap(), url.port().unwrap()),
+                Credentials::Token("sekrit".into()),
+            )
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .and(header("Authorization", "Bearer sekrit"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(client.ping().await?, "ok");
+        Ok(())
+    }
+}
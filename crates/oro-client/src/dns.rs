@@ -0,0 +1,140 @@
+//! An in-process DNS cache. Installs make hundreds of requests to the same
+//! few hosts, and without this, every new connection looks the host up
+//! again, which adds up quickly with slow resolvers.
+//!
+//! Addresses are also handed out alternating between IPv6 and IPv4, so
+//! connection attempts get raced across both families ("happy eyeballs",
+//! [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305)): when IPv6 looks
+//! available but doesn't actually work, IPv4 takes over after a short
+//! delay, instead of every request waiting on IPv6 to time out.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// reqwest 0.11 hands resolvers hyper's `Name`, without re-exporting it.
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+
+#[derive(Clone, Debug)]
+pub(crate) struct DnsCache {
+    ttl: Duration,
+    hosts: Arc<Mutex<HashMap<String, HostEntry>>>,
+}
+
+/// A host's cached lookup. Each host gets its own lock, so concurrent
+/// lookups for the same host wait for the first one instead of racing it.
+type HostEntry = Arc<async_std::sync::Mutex<Option<Entry>>>;
+
+#[derive(Debug)]
+struct Entry {
+    addrs: Vec<SocketAddr>,
+    expires: Instant,
+}
+
+impl DnsCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Addresses for `host`, looking it up if it isn't cached or its entry
+    /// has expired.
+    async fn lookup(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+        let entry = self
+            .hosts
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(host.to_lowercase())
+            .or_default()
+            .clone();
+        // Holding the host's lock while looking it up means requests that
+        // all start at once share a single lookup.
+        let mut entry = entry.lock().await;
+        if let Some(entry) = &*entry {
+            if entry.expires > Instant::now() {
+                return Ok(entry.addrs.clone());
+            }
+        }
+        let addrs = interleave(
+            async_std::net::ToSocketAddrs::to_socket_addrs(&(host, 0))
+                .await?
+                .collect(),
+        );
+        *entry = Some(Entry {
+            addrs: addrs.clone(),
+            expires: Instant::now() + self.ttl,
+        });
+        Ok(addrs)
+    }
+}
+
+impl Resolve for DnsCache {
+    fn resolve(&self, name: Name) -> Resolving {
+        let cache = self.clone();
+        Box::pin(async move {
+            let addrs = cache.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Alternates between address families, starting with whichever family
+/// the resolver preferred, keeping each family's own order.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_ipv6 = first.is_ipv6();
+    let (preferred, fallback): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut interleaved = Vec::with_capacity(preferred.len() + fallback.len());
+    let (mut preferred, mut fallback) = (preferred.into_iter(), fallback.into_iter());
+    loop {
+        match (preferred.next(), fallback.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn interleaves_address_families() {
+        let addrs = [
+            "[::1]:0",
+            "[::2]:0",
+            "[::3]:0",
+            "127.0.0.1:0",
+            "127.0.0.2:0",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect::<Vec<SocketAddr>>();
+        let interleaved = interleave(addrs.clone());
+        assert_eq!(
+            interleaved,
+            vec![addrs[0], addrs[3], addrs[1], addrs[4], addrs[2]]
+        );
+    }
+
+    #[async_std::test]
+    async fn caches_lookups() {
+        let cache = DnsCache::new(Duration::from_secs(60));
+        let addrs = cache.lookup("localhost").await.unwrap();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        let hosts = cache.hosts.lock().unwrap();
+        let entry = hosts["localhost"].try_lock().unwrap();
+        assert_eq!(entry.as_ref().unwrap().addrs, addrs);
+    }
+}
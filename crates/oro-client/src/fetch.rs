@@ -0,0 +1,25 @@
+//! Requests on wasm32 are made with the runtime's `fetch()`, through
+//! reqwest's wasm backend, so they work the same in browsers, web workers,
+//! and edge runtimes. Browsers don't say why a `fetch()` failed, though,
+//! and by far the most common reason is a registry that doesn't allow
+//! cross-origin requests, so those failures get an error that says as much.
+
+use reqwest::Url;
+
+use crate::OroClientError;
+
+/// Turns `err` into [`OroClientError::FetchFailed`] if it's from `fetch()`
+/// itself failing, rather than from a response that came back.
+pub(crate) fn fetch_error(url: &Url, err: OroClientError) -> OroClientError {
+    match err {
+        OroClientError::RequestError(source)
+            if source.is_request() && source.status().is_none() =>
+        {
+            OroClientError::FetchFailed {
+                url: url.clone(),
+                source,
+            }
+        }
+        err => err,
+    }
+}
@@ -0,0 +1,177 @@
+//! Credential helpers: external commands that hand out short-lived
+//! credentials for a registry when they're needed, so SSO-backed registries
+//! work without any secrets stored in config. They speak the same protocol
+//! as [docker credential
+//! helpers](https://github.com/docker/docker-credential-helpers): the
+//! helper is run with a `get` argument and the registry's URL on stdin, and
+//! prints something like `{"Username": "<token>", "Secret": "abc123"}`.
+
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_std::sync::Mutex;
+use serde::Deserialize;
+
+use crate::{Credentials, OroClientError};
+
+/// How long credentials from a helper get reused for, unless it says when
+/// they expire.
+const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Credentials from a helper are refreshed this long before they expire, so
+/// requests don't get sent with ones that are about to.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// A command to get credentials for a registry from, whenever they're
+/// needed. The command is split on whitespace, without any shell quoting.
+#[derive(Clone)]
+pub struct CredentialHelper {
+    command: String,
+    cached: Arc<Mutex<Option<(Credentials, Instant)>>>,
+}
+
+impl CredentialHelper {
+    pub fn new(command: impl AsRef<str>) -> Self {
+        Self {
+            command: command.as_ref().into(),
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Credentials for `registry`, from the helper. They're only asked for
+    /// again once they expire, and concurrent requests wait for the same
+    /// run of the helper instead of starting their own.
+    pub(crate) async fn credentials(&self, registry: &str) -> Result<Credentials, OroClientError> {
+        let mut cached = self.cached.lock().await;
+        if let Some((credentials, expires)) = &*cached {
+            if Instant::now() < *expires {
+                return Ok(credentials.clone());
+            }
+        }
+        let command = self.command.clone();
+        let registry = registry.to_string();
+        let (credentials, ttl) =
+            async_std::task::spawn_blocking(move || run(&command, &registry)).await?;
+        *cached = Some((credentials.clone(), Instant::now() + ttl));
+        Ok(credentials)
+    }
+}
+
+impl fmt::Debug for CredentialHelper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CredentialHelper")
+            .field(&self.command)
+            .finish()
+    }
+}
+
+impl PartialEq for CredentialHelper {
+    fn eq(&self, other: &Self) -> bool {
+        self.command == other.command
+    }
+}
+
+impl Eq for CredentialHelper {}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperOutput {
+    #[serde(default)]
+    username: String,
+    secret: String,
+    /// When the credentials expire, as an RFC 3339 timestamp.
+    expires_at: Option<String>,
+}
+
+/// Runs the helper `command` for `registry`, and returns the credentials it
+/// printed, along with how long they can be used for.
+fn run(command: &str, registry: &str) -> Result<(Credentials, Duration), OroClientError> {
+    let error = |message: String| OroClientError::CredentialHelperFailed {
+        command: command.into(),
+        message,
+    };
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| error("the command is empty".into()))?;
+    tracing::debug!("Getting credentials for {registry} from `{command}`.");
+    let mut child = Command::new(program)
+        .args(parts)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| error(e.to_string()))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(registry.as_bytes())
+        .map_err(|e| error(e.to_string()))?;
+    let output = child.wait_with_output().map_err(|e| error(e.to_string()))?;
+    if !output.status.success() {
+        return Err(error(format!("it exited with {}", output.status)));
+    }
+    let output: HelperOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| error(format!("its output couldn't be parsed: {e}")))?;
+    let ttl = output
+        .expires_at
+        .as_deref()
+        .and_then(|expires| chrono::DateTime::parse_from_rfc3339(expires).ok())
+        .map(|expires| {
+            (expires.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+                .saturating_sub(EXPIRY_MARGIN)
+        })
+        .unwrap_or(DEFAULT_TTL);
+    // Docker helpers use `<token>` as the username for identity tokens, and
+    // some leave it out entirely.
+    let credentials = if output.username.is_empty() || output.username == "<token>" {
+        Credentials::Token(output.secret)
+    } else {
+        Credentials::Basic {
+            username: output.username,
+            password: output.secret,
+        }
+    };
+    Ok((credentials, ttl))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[async_std::test]
+    async fn runs_helper() -> Result<(), OroClientError> {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("helper");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nread registry\necho \"{\\\"Username\\\": \\\"<token>\\\", \\\"Secret\\\": \\\"$1 $registry\\\"}\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let helper = CredentialHelper::new(script.display().to_string());
+        assert_eq!(
+            helper.credentials("https://registry.corp/").await?,
+            Credentials::Token("get https://registry.corp/".into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_failures() {
+        assert!(matches!(
+            run("false", "https://registry.corp/"),
+            Err(OroClientError::CredentialHelperFailed { .. })
+        ));
+    }
+}
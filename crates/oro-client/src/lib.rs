@@ -1,9 +1,40 @@
 //! A general-use client for interacting with NPM registry APIs.
 
 mod api;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
 mod client;
+mod credentials;
+#[cfg(not(target_arch = "wasm32"))]
+mod dns;
 mod error;
+#[cfg(target_arch = "wasm32")]
+mod fetch;
+#[cfg(not(target_arch = "wasm32"))]
+mod helper;
+mod observe;
+mod retry;
+#[cfg(not(target_arch = "wasm32"))]
+mod s3;
+#[cfg(not(target_arch = "wasm32"))]
+mod sockets;
+#[cfg(not(target_arch = "wasm32"))]
+mod throttle;
+#[cfg(not(target_arch = "wasm32"))]
+mod tls;
 
+pub use api::advisories;
+pub use api::attestations;
+pub use api::keys;
 pub use api::packument;
 pub use client::{OroClient, OroClientBuilder};
+pub use credentials::Credentials;
+#[cfg(not(target_arch = "wasm32"))]
+pub use credentials::S3Credentials;
 pub use error::OroClientError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use helper::CredentialHelper;
+pub use observe::{CacheStatus, RequestEvent, RequestKind, RequestObserver};
+pub use retry::{RetryHistory, RetryPolicy};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tls::TlsConfig;
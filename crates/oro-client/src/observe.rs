@@ -0,0 +1,239 @@
+//! Hooks for observing the requests a client makes, so things like install
+//! times, cache hit rates, and retries can be tracked across machines.
+//!
+//! Every request is reported once it's done, whether it succeeded or not,
+//! to the [`RequestObserver`] set with
+//! [`OroClientBuilder::observer`](crate::OroClientBuilder::observer), and
+//! to `tracing` at the `trace` level.
+
+use std::fmt;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use url::Url;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::sockets::Permit;
+use crate::{OroClient, OroClientError};
+
+/// Receives a [`RequestEvent`] for every request a client makes.
+/// Observers are called inline, so they should hand anything slow, like
+/// exporting metrics, off to somewhere else.
+pub trait RequestObserver: fmt::Debug + Send + Sync {
+    /// Called once a request is done.
+    fn on_request(&self, event: &RequestEvent);
+
+    /// Called right before a request's first attempt, once the client's
+    /// socket limits leave room for it.
+    fn on_request_start(&self, _url: &Url, _kind: RequestKind) {}
+}
+
+/// What a request was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Packument,
+    Tarball,
+    /// Anything else, like registry keys, attestations, or pings.
+    Other,
+}
+
+/// How a request used the packument cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// There's no cache involved, either because none is configured or
+    /// because this kind of request isn't cached.
+    Uncached,
+    /// Nothing usable was cached, so the response was downloaded.
+    Miss,
+    /// The cached response was still fresh, and got used instead of
+    /// downloading it again.
+    Hit,
+}
+
+/// A single request, from its first attempt until its body was read or it
+/// failed.
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    pub url: Url,
+    pub kind: RequestKind,
+    /// Status of the last response, if there was one.
+    pub status: Option<u16>,
+    /// How long the request took, including retries and reading its body.
+    /// Always zero on wasm.
+    pub duration: Duration,
+    /// How many times the request was retried, or resumed, after the
+    /// first attempt.
+    pub retries: u32,
+    /// Response body bytes received over the network.
+    pub bytes: u64,
+    pub cache: CacheStatus,
+    /// Why the request failed, if it did.
+    pub error: Option<String>,
+}
+
+/// A request that's in progress, and gets reported when it's done.
+#[derive(Debug)]
+pub(crate) struct Observation {
+    /// Room for this request under the client's socket limits, given back
+    /// once it's done.
+    #[cfg(not(target_arch = "wasm32"))]
+    _permits: Vec<Permit>,
+    pub(crate) url: Url,
+    kind: RequestKind,
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+    pub(crate) status: Option<u16>,
+    pub(crate) retries: u32,
+    pub(crate) bytes: u64,
+    pub(crate) cache: CacheStatus,
+}
+
+impl OroClient {
+    /// Starts observing a request to `url`, once the client's socket
+    /// limits leave room for it.
+    pub(crate) async fn observe(&self, url: &Url, kind: RequestKind) -> Observation {
+        #[cfg(not(target_arch = "wasm32"))]
+        let permits = self.sockets.acquire(url).await;
+        if let Some(observer) = &self.observer {
+            observer.on_request_start(url, kind);
+        }
+        Observation {
+            #[cfg(not(target_arch = "wasm32"))]
+            _permits: permits,
+            url: url.clone(),
+            kind,
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+            status: None,
+            retries: 0,
+            bytes: 0,
+            cache: CacheStatus::Uncached,
+        }
+    }
+}
+
+impl Observation {
+    /// Reports the request as done, successfully or not.
+    pub(crate) fn finish(self, client: &OroClient, error: Option<&dyn fmt::Display>) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let duration = self.start.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        let duration = Duration::ZERO;
+        let event = RequestEvent {
+            url: self.url,
+            kind: self.kind,
+            status: self.status,
+            duration,
+            retries: self.retries,
+            bytes: self.bytes,
+            cache: self.cache,
+            error: error.map(|err| err.to_string()),
+        };
+        tracing::trace!(
+            "{:?} request to {} finished in {}ms: status {:?}, {} retries, {} bytes, cache {:?}{}",
+            event.kind,
+            event.url,
+            event.duration.as_millis(),
+            event.status,
+            event.retries,
+            event.bytes,
+            event.cache,
+            event
+                .error
+                .as_ref()
+                .map(|err| format!(", failed: {err}"))
+                .unwrap_or_default()
+        );
+        if let Some(observer) = &client.observer {
+            observer.on_request(&event);
+        }
+    }
+
+    /// Reports the request as done, with the outcome of `result`.
+    pub(crate) fn finish_with<T>(self, client: &OroClient, result: &Result<T, OroClientError>) {
+        self.finish(
+            client,
+            result.as_ref().err().map(|err| err as &dyn fmt::Display),
+        );
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::RetryPolicy;
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<RequestEvent>>, Mutex<Vec<RequestKind>>);
+
+    impl RequestObserver for Recorder {
+        fn on_request(&self, event: &RequestEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+
+        fn on_request_start(&self, _url: &Url, kind: RequestKind) {
+            self.1.lock().unwrap().push(kind);
+        }
+    }
+
+    #[async_std::test]
+    async fn reports_requests() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let recorder = Arc::new(Recorder::default());
+        let client = OroClient::builder()
+            .registry(mock_server.uri().parse().into_diagnostic()?)
+            .retry_policy(
+                RetryPolicy::new()
+                    .retries(1)
+                    .base_delay(Duration::from_millis(1))
+                    .jitter(false),
+            )
+            .observer(recorder.clone())
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        client.ping().await?;
+        assert!(client.packument("missing").await.is_err());
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            *recorder.1.lock().unwrap(),
+            vec![RequestKind::Other, RequestKind::Packument]
+        );
+        assert_eq!(events[0].kind, RequestKind::Other);
+        assert_eq!(events[0].status, Some(200));
+        assert_eq!(events[0].retries, 1);
+        assert_eq!(events[0].bytes, 2);
+        assert_eq!(events[0].error, None);
+        assert_eq!(events[1].kind, RequestKind::Packument);
+        assert_eq!(events[1].status, Some(404));
+        assert_eq!(events[1].cache, CacheStatus::Uncached);
+        assert!(events[1].error.is_some());
+        Ok(())
+    }
+}
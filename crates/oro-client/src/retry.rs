@@ -0,0 +1,383 @@
+//! Retrying requests that failed for reasons that might go away on their
+//! own, like a registry that's overloaded or briefly down, or a connection
+//! that got dropped halfway through.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use reqwest::{Response, StatusCode};
+
+use crate::observe::Observation;
+use crate::{OroClient, OroClientError};
+
+/// How failed requests get retried. Only failures that might go away on
+/// their own are retried: 5xx and 429 responses, timeouts, and connections
+/// that couldn't be made or were reset.
+///
+/// Retries back off exponentially, starting at
+/// [`RetryPolicy::base_delay`], unless the registry asks for a specific
+/// delay with a `Retry-After` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub(crate) retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that never retries anything.
+    pub fn none() -> Self {
+        Self::new().retries(0)
+    }
+
+    /// How many times to retry a request after the first attempt fails.
+    /// Defaults to 3.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// How long to wait before the first retry. Each retry after that waits
+    /// twice as long as the one before. Defaults to 1 second.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Longest to ever wait between attempts, including when a
+    /// `Retry-After` header asks for longer. Defaults to 30 seconds.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Whether to wait a random amount between half and all of each
+    /// backoff delay, so lots of requests that failed together don't all
+    /// retry at the same moment. Delays asked for with `Retry-After` are
+    /// used as-is. Defaults to `true`.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// How long to wait before retry number `retry`, counting from 0.
+    pub(crate) fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        if self.jitter {
+            let half = delay / 2;
+            half + half.mul_f64(random_fraction())
+        } else {
+            delay
+        }
+    }
+}
+
+/// Every failed attempt at a request, in order.
+#[derive(Clone, Debug, Default)]
+pub struct RetryHistory(pub Vec<String>);
+
+impl fmt::Display for RetryHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, attempt) in self.0.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "attempt {}: {attempt}", idx + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl OroClient {
+    /// Makes a request with `send`, as many times as the retry policy
+    /// allows. Responses with an error status are only turned into errors
+    /// when they'd have been retried, so callers still get to handle ones
+    /// like 404s themselves. Retries and response statuses are recorded in
+    /// `observation`.
+    pub(crate) async fn send_retrying<F, Fut>(
+        &self,
+        observation: &mut Observation,
+        send: F,
+    ) -> Result<Response, OroClientError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Response, OroClientError>>,
+    {
+        let url = observation.url.clone();
+        let mut history = Vec::new();
+        loop {
+            #[cfg(not(target_arch = "wasm32"))]
+            let result = async_std::future::timeout(self.first_byte_timeout, send())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(OroClientError::ResponseTimeout(
+                        url.clone(),
+                        self.first_byte_timeout,
+                    ))
+                });
+            #[cfg(target_arch = "wasm32")]
+            let result = send()
+                .await
+                .map_err(|err| crate::fetch::fetch_error(&url, err));
+            if let Ok(res) = &result {
+                observation.status = Some(res.status().as_u16());
+            }
+            let (transient, asked_delay, attempt) = match &result {
+                Ok(res) if is_transient_status(res.status()) => {
+                    (true, retry_after(res), res.status().to_string())
+                }
+                Ok(_) => return result,
+                Err(err) => (is_transient_error(err), None, err.to_string()),
+            };
+            history.push(attempt);
+            let retry = history.len() as u32 - 1;
+            // There's nothing to wait with in the browser.
+            let out_of_retries = cfg!(target_arch = "wasm32") || retry >= self.retry.retries;
+            if !transient || out_of_retries {
+                let err = match result {
+                    Ok(res) => match res.error_for_status() {
+                        Ok(res) => return Ok(res),
+                        Err(err) => err.into(),
+                    },
+                    Err(err) => err,
+                };
+                return Err(if history.len() == 1 {
+                    err
+                } else {
+                    OroClientError::RetriesExhausted {
+                        url,
+                        history: RetryHistory(history),
+                        source: Box::new(err),
+                    }
+                });
+            }
+            let delay = self.retry.delay(retry, asked_delay);
+            tracing::debug!(
+                "Request to {url} failed ({}), retrying in {}ms.",
+                history.last().expect("just pushed"),
+                delay.as_millis()
+            );
+            observation.retries += 1;
+            #[cfg(not(target_arch = "wasm32"))]
+            async_std::task::sleep(delay).await;
+        }
+    }
+}
+
+impl OroClientError {
+    /// Whether this is the kind of failure that tends to go away on its own,
+    /// like a server error or a timeout, as opposed to one that'd happen
+    /// again no matter how many times or where the request was made.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, OroClientError::RetriesExhausted { .. }) || is_transient_error(self)
+    }
+}
+
+/// A random number between 0 and 1. Good enough for spreading retries out,
+/// without pulling in a whole RNG for it.
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    // Every `RandomState` is seeded differently.
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn is_transient_error(err: &OroClientError) -> bool {
+    match err {
+        OroClientError::RequestError(err) => is_transient_reqwest_error(err),
+        #[cfg(not(target_arch = "wasm32"))]
+        OroClientError::RequestMiddlewareError(reqwest_middleware::Error::Reqwest(err)) => {
+            is_transient_reqwest_error(err)
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        OroClientError::ResponseTimeout(..) | OroClientError::RequestTimeout(..) => true,
+        _ => false,
+    }
+}
+
+fn is_transient_reqwest_error(err: &reqwest::Error) -> bool {
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+    if let Some(status) = err.status() {
+        return is_transient_status(status);
+    }
+    // Connection resets show up as IO errors somewhere down the chain.
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Delay asked for by a response's `Retry-After` header, if it's given in
+/// seconds. HTTP dates aren't supported, and fall back to the usual
+/// backoff.
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use miette::{IntoDiagnostic, Result};
+    use pretty_assertions::assert_eq;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy::new()
+            .retries(2)
+            .base_delay(Duration::from_millis(1))
+            .jitter(false)
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_secs(1))
+            .max_delay(Duration::from_secs(5))
+            .jitter(false);
+        assert_eq!(policy.delay(0, None), Duration::from_secs(1));
+        assert_eq!(policy.delay(2, None), Duration::from_secs(4));
+        assert_eq!(policy.delay(3, None), Duration::from_secs(5));
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(60))),
+            Duration::from_secs(5)
+        );
+        let jittered = policy.jitter(true).delay(2, None);
+        assert!(jittered >= Duration::from_secs(2) && jittered <= Duration::from_secs(4));
+    }
+
+    #[async_std::test]
+    async fn retries_transient_failures() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::builder()
+            .registry(mock_server.uri().parse().into_diagnostic()?)
+            .retry_policy(fast())
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(client.ping().await?, "ok");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn reports_retry_history() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::builder()
+            .registry(mock_server.uri().parse().into_diagnostic()?)
+            .retry_policy(fast())
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        match client.ping().await {
+            Err(OroClientError::RetriesExhausted { history, .. }) => {
+                assert_eq!(history.0.len(), 3);
+                assert!(history.to_string().starts_with("attempt 1: 500"));
+            }
+            other => panic!("expected retries to run out, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn retries_slow_responses() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = OroClient::builder()
+            .registry(mock_server.uri().parse().into_diagnostic()?)
+            .retry_policy(fast())
+            .first_byte_timeout(Duration::from_millis(100))
+            .build();
+
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("ok")
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("-/ping"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(client.ping().await?, "ok");
+        Ok(())
+    }
+}
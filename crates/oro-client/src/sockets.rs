@@ -0,0 +1,113 @@
+//! Limits on how many requests a client has open at once, in total and to
+//! each host. A request counts as open from its first attempt until its
+//! body has been read, since that's how long it keeps a connection busy.
+//!
+//! Some corporate proxies fall over, or start refusing connections, when
+//! an install opens too many at once.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_std::channel::{bounded, Receiver, Sender};
+use url::Url;
+
+#[derive(Debug)]
+pub(crate) struct Sockets {
+    total: Option<Limit>,
+    per_host: Option<usize>,
+    hosts: Mutex<HashMap<String, Limit>>,
+}
+
+impl Sockets {
+    pub(crate) fn new(total: Option<usize>, per_host: Option<usize>) -> Self {
+        Self {
+            total: total.map(Limit::new),
+            per_host,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until there's room for another request to `url`.
+    pub(crate) async fn acquire(&self, url: &Url) -> Vec<Permit> {
+        // Waiting on the host first means requests to busy hosts don't
+        // tie up room other hosts could've used.
+        let mut permits = Vec::new();
+        if let (Some(per_host), Some(host)) = (self.per_host, url.host_str()) {
+            let limit = self
+                .hosts
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .entry(host.to_lowercase())
+                .or_insert_with(|| Limit::new(per_host))
+                .clone();
+            permits.push(limit.acquire().await);
+        }
+        if let Some(total) = &self.total {
+            permits.push(total.acquire().await);
+        }
+        permits
+    }
+}
+
+/// A counting semaphore, made out of a channel holding one token for each
+/// request that can still be made.
+#[derive(Debug, Clone)]
+struct Limit {
+    tokens: Sender<()>,
+    available: Receiver<()>,
+}
+
+impl Limit {
+    fn new(max: usize) -> Self {
+        let max = max.max(1);
+        let (tokens, available) = bounded(max);
+        for _ in 0..max {
+            tokens
+                .try_send(())
+                .expect("channel has room for every token");
+        }
+        Self { tokens, available }
+    }
+
+    async fn acquire(&self) -> Permit {
+        self.available
+            .recv()
+            .await
+            .expect("the sender lives as long as the receiver");
+        Permit(self.tokens.clone())
+    }
+}
+
+/// Room for one request, given back when it's dropped.
+#[derive(Debug)]
+pub(crate) struct Permit(Sender<()>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn limits_requests_per_host() {
+        let sockets = Sockets::new(None, Some(1));
+        let a: Url = "https://a.example.com/foo".parse().unwrap();
+        let b: Url = "https://b.example.com/foo".parse().unwrap();
+        let held = sockets.acquire(&a).await;
+        // Other hosts aren't affected.
+        drop(sockets.acquire(&b).await);
+        assert!(
+            async_std::future::timeout(Duration::from_millis(50), sockets.acquire(&a))
+                .await
+                .is_err()
+        );
+        drop(held);
+        assert_eq!(sockets.acquire(&a).await.len(), 1);
+    }
+}
@@ -0,0 +1,60 @@
+//! Bandwidth limiting, shared by every download a client makes.
+//!
+//! Each chunk of a response body reserves the next slot of time it fits
+//! in, at the configured rate, and waits for that slot to pass before it's
+//! handed out. Slots are handed out in the order chunks arrive in, so
+//! concurrent downloads take turns instead of the first one starving the
+//! rest.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    bytes_per_sec: u64,
+    /// When the latest reserved slot ends.
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// How long to wait before `bytes` more bytes can be handed out.
+    pub(crate) fn reserve(&self, bytes: usize) -> Duration {
+        let mut next = self.next.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        // Time nobody used doesn't carry over, so a quiet client can't
+        // build up a burst.
+        let start = (*next).max(now);
+        *next = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        *next - now
+    }
+
+    /// Waits until `bytes` more bytes can be handed out.
+    pub(crate) async fn take(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            async_std::task::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserves_slots_in_order() {
+        let throttle = Throttle::new(1000);
+        let first = throttle.reserve(500);
+        let second = throttle.reserve(500);
+        assert!(first <= Duration::from_millis(500));
+        assert!(second > Duration::from_millis(900));
+        assert!(second <= Duration::from_millis(1000));
+    }
+}
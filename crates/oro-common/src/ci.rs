@@ -0,0 +1,66 @@
+/// Environment variables set by CI services, along with the service's name.
+const CI_SERVICES: &[(&str, &str)] = &[
+    ("GITHUB_ACTIONS", "GitHub Actions"),
+    ("GITLAB_CI", "GitLab CI"),
+    ("BUILDKITE", "Buildkite"),
+    ("CIRCLECI", "CircleCI"),
+    ("TRAVIS", "Travis CI"),
+    ("TF_BUILD", "Azure Pipelines"),
+    ("JENKINS_URL", "Jenkins"),
+    ("TEAMCITY_VERSION", "TeamCity"),
+    ("BITBUCKET_BUILD_NUMBER", "Bitbucket Pipelines"),
+    ("CODEBUILD_BUILD_ID", "AWS CodeBuild"),
+    ("APPVEYOR", "AppVeyor"),
+    ("DRONE", "Drone"),
+    ("CI", "CI"),
+    ("CONTINUOUS_INTEGRATION", "CI"),
+    ("BUILD_NUMBER", "CI"),
+];
+
+/// Name of the CI service Orogene is running in, if it's running in one.
+/// Setting `CI=false` turns detection off, even inside a CI service.
+pub fn detect_ci() -> Option<&'static str> {
+    detect_ci_from(|var| std::env::var(var).ok())
+}
+
+fn detect_ci_from(env: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    let is_set = |var: &str| {
+        env(var).map(|val| !val.is_empty() && val != "0" && val.to_lowercase() != "false")
+    };
+    if is_set("CI") == Some(false) {
+        return None;
+    }
+    CI_SERVICES
+        .iter()
+        .find(|(var, _)| is_set(var) == Some(true))
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> Option<&'static str> {
+        detect_ci_from(|var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, val)| val.to_string())
+        })
+    }
+
+    #[test]
+    fn detects_ci_services() {
+        assert_eq!(detect(&[]), None);
+        assert_eq!(
+            detect(&[("CI", "true"), ("GITHUB_ACTIONS", "true")]),
+            Some("GitHub Actions")
+        );
+        assert_eq!(detect(&[("CI", "1")]), Some("CI"));
+        assert_eq!(
+            detect(&[("JENKINS_URL", "https://ci.example.com/")]),
+            Some("Jenkins")
+        );
+        assert_eq!(detect(&[("CI", "false"), ("GITLAB_CI", "true")]), None);
+        assert_eq!(detect(&[("TRAVIS", "")]), None);
+    }
+}
@@ -2,10 +2,14 @@
 //! packument/package.json/manifest types.
 
 pub use build_manifest::*;
+pub use ci::detect_ci;
+pub use license::*;
 pub use manifest::Bin;
 pub use manifest::*;
 pub use packument::*;
 
 mod build_manifest;
+mod ci;
+mod license;
 mod manifest;
 mod packument;
@@ -0,0 +1,483 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// An invalid SPDX license expression was found.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Diagnostic)]
+#[error("Invalid license expression `{input}`: {reason}")]
+#[diagnostic(
+    code(oro_common::invalid_license_expression),
+    url(docsrs),
+    help("License expressions use SPDX syntax, such as `MIT`, `(MIT OR Apache-2.0)`, or `GPL-2.0-or-later WITH Classpath-exception-2.0`. See https://spdx.github.io/spdx-spec/v2.3/SPDX-license-expressions/")
+)]
+pub struct LicenseExpressionError {
+    pub input: String,
+    pub reason: String,
+}
+
+/// A single license in an SPDX expression, such as `MIT`, `GPL-2.0+`, or
+/// `GPL-2.0-or-later WITH Classpath-exception-2.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LicenseRequirement {
+    /// SPDX license identifier, or a `LicenseRef-`.
+    pub id: String,
+    /// Whether the license was suffixed with `+` ("this version or later").
+    pub or_later: bool,
+    /// Exception applied to the license with `WITH`, if any.
+    pub exception: Option<String>,
+}
+
+impl LicenseRequirement {
+    /// Whether this requirement refers to `id`, ignoring case. An `or_later`
+    /// requirement (`GPL-2.0+`) also matches its `-or-later` spelling.
+    pub fn matches(&self, id: &str) -> bool {
+        let plain = if self.or_later {
+            self.id
+                .eq_ignore_ascii_case(id.strip_suffix('+').unwrap_or(id))
+                || id
+                    .strip_suffix("-or-later")
+                    .map_or(false, |base| self.id.eq_ignore_ascii_case(base))
+        } else {
+            self.id.eq_ignore_ascii_case(id)
+        };
+        if plain {
+            return true;
+        }
+        // Policies can also name a license together with its exception,
+        // such as `GPL-2.0-only WITH Classpath-exception-2.0`.
+        if let Some(exception) = &self.exception {
+            if let Some((license, with)) = id.split_once(" WITH ") {
+                return with.trim().eq_ignore_ascii_case(exception)
+                    && LicenseRequirement {
+                        exception: None,
+                        ..self.clone()
+                    }
+                    .matches(license.trim());
+            }
+        }
+        false
+    }
+}
+
+impl Display for LicenseRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)?;
+        if self.or_later {
+            write!(f, "+")?;
+        }
+        if let Some(exception) = &self.exception {
+            write!(f, " WITH {exception}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A parsed SPDX license expression, as found in a package's `license`
+/// field.
+///
+/// Expressions are normalized while parsing: nested `AND`s and `OR`s are
+/// flattened, and duplicate terms are removed, so `MIT AND (MIT AND ISC)` is
+/// the same as `MIT AND ISC`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseExpression {
+    License(LicenseRequirement),
+    /// All of these licenses apply at once.
+    And(Vec<LicenseExpression>),
+    /// Any one of these licenses may be chosen.
+    Or(Vec<LicenseExpression>),
+}
+
+impl LicenseExpression {
+    /// Builds an expression from the legacy `licenses` array, which lists
+    /// alternative licenses for a package.
+    pub fn from_legacy_licenses<S: AsRef<str>>(
+        licenses: &[S],
+    ) -> Result<Self, LicenseExpressionError> {
+        let exprs = licenses
+            .iter()
+            .map(|license| license.as_ref().parse())
+            .collect::<Result<Vec<LicenseExpression>, _>>()?;
+        if exprs.is_empty() {
+            return Err(LicenseExpressionError {
+                input: String::new(),
+                reason: "no licenses were given".into(),
+            });
+        }
+        Ok(Self::join(exprs, false))
+    }
+
+    /// All the distinct licenses mentioned in this expression.
+    pub fn requirements(&self) -> Vec<&LicenseRequirement> {
+        let mut seen = HashSet::new();
+        let mut reqs = Vec::new();
+        self.collect_requirements(&mut seen, &mut reqs);
+        reqs
+    }
+
+    fn collect_requirements<'a>(
+        &'a self,
+        seen: &mut HashSet<&'a LicenseRequirement>,
+        reqs: &mut Vec<&'a LicenseRequirement>,
+    ) {
+        match self {
+            Self::License(req) => {
+                if seen.insert(req) {
+                    reqs.push(req);
+                }
+            }
+            Self::And(exprs) | Self::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_requirements(seen, reqs);
+                }
+            }
+        }
+    }
+
+    /// Whether this expression can be satisfied using only licenses that
+    /// `accept` returns `true` for. For `OR`, at least one alternative must
+    /// be acceptable, while for `AND`, all of them must be.
+    pub fn satisfies(&self, accept: &impl Fn(&LicenseRequirement) -> bool) -> bool {
+        match self {
+            Self::License(req) => accept(req),
+            Self::And(exprs) => exprs.iter().all(|expr| expr.satisfies(accept)),
+            Self::Or(exprs) => exprs.iter().any(|expr| expr.satisfies(accept)),
+        }
+    }
+
+    /// Picks the licenses to comply with for this expression, choosing the
+    /// first acceptable alternative for each `OR`. Returns `None` if the
+    /// expression can't be satisfied.
+    pub fn choose(
+        &self,
+        accept: &impl Fn(&LicenseRequirement) -> bool,
+    ) -> Option<Vec<&LicenseRequirement>> {
+        match self {
+            Self::License(req) => accept(req).then(|| vec![req]),
+            Self::And(exprs) => {
+                let mut chosen = Vec::new();
+                for expr in exprs {
+                    for req in expr.choose(accept)? {
+                        if !chosen.contains(&req) {
+                            chosen.push(req);
+                        }
+                    }
+                }
+                Some(chosen)
+            }
+            Self::Or(exprs) => exprs.iter().find_map(|expr| expr.choose(accept)),
+        }
+    }
+
+    fn join(exprs: Vec<LicenseExpression>, and: bool) -> Self {
+        let mut flattened: Vec<LicenseExpression> = Vec::new();
+        for expr in exprs {
+            let nested = match expr {
+                Self::And(nested) if and => nested,
+                Self::Or(nested) if !and => nested,
+                other => vec![other],
+            };
+            for expr in nested {
+                if !flattened.contains(&expr) {
+                    flattened.push(expr);
+                }
+            }
+        }
+        if flattened.len() == 1 {
+            flattened.pop().unwrap()
+        } else if and {
+            Self::And(flattened)
+        } else {
+            Self::Or(flattened)
+        }
+    }
+}
+
+impl Display for LicenseExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (exprs, op) = match self {
+            Self::License(req) => return write!(f, "{req}"),
+            Self::And(exprs) => (exprs, " AND "),
+            Self::Or(exprs) => (exprs, " OR "),
+        };
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{op}")?;
+            }
+            // AND binds tighter than OR, so only nested ORs need parens.
+            match expr {
+                Self::Or(_) => write!(f, "({expr})")?,
+                Self::And(_) if op == " AND " => write!(f, "({expr})")?,
+                _ => write!(f, "{expr}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LicenseExpression {
+    type Err = LicenseExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| LicenseExpressionError {
+            input: s.into(),
+            reason: reason.into(),
+        };
+        let tokens = tokenize(s).ok_or_else(|| error("found invalid characters"))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or_expr().map_err(error)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(error("unexpected trailing input"));
+        }
+        Ok(expr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    With,
+    Id(&'a str),
+}
+
+fn tokenize(input: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '(' => {
+                tokens.push(Token::Open);
+                1
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                1
+            }
+            c if is_id_char(c) => {
+                let len = rest.find(|c| !is_id_char(c)).unwrap_or(rest.len());
+                let word = &rest[..len];
+                // Operators are supposed to be uppercase, but lowercase ones
+                // are common enough in the wild to be worth accepting.
+                tokens.push(match word {
+                    "AND" | "and" => Token::And,
+                    "OR" | "or" => Token::Or,
+                    "WITH" | "with" => Token::With,
+                    id => Token::Id(id),
+                });
+                len
+            }
+            _ => return None,
+        };
+        rest = rest[len..].trim_start();
+    }
+    Some(tokens)
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':' | '+')
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next_if(&mut self, token: &Token<'a>) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<LicenseExpression, &'static str> {
+        let mut exprs = vec![self.and_expr()?];
+        while self.next_if(&Token::Or) {
+            exprs.push(self.and_expr()?);
+        }
+        Ok(LicenseExpression::join(exprs, false))
+    }
+
+    fn and_expr(&mut self) -> Result<LicenseExpression, &'static str> {
+        let mut exprs = vec![self.term()?];
+        while self.next_if(&Token::And) {
+            exprs.push(self.term()?);
+        }
+        Ok(LicenseExpression::join(exprs, true))
+    }
+
+    fn term(&mut self) -> Result<LicenseExpression, &'static str> {
+        if self.next_if(&Token::Open) {
+            let expr = self.or_expr()?;
+            if !self.next_if(&Token::Close) {
+                return Err("missing closing parenthesis");
+            }
+            return Ok(expr);
+        }
+        let Some(Token::Id(id)) = self.tokens.get(self.pos).cloned() else {
+            return Err("expected a license identifier");
+        };
+        self.pos += 1;
+        let (id, or_later) = match id.strip_suffix('+') {
+            Some(id) => (id, true),
+            None => (id, false),
+        };
+        if id.is_empty() || id.contains('+') {
+            return Err("invalid license identifier");
+        }
+        let exception = if self.next_if(&Token::With) {
+            match self.tokens.get(self.pos).cloned() {
+                Some(Token::Id(exception)) if !exception.contains('+') => {
+                    self.pos += 1;
+                    Some(exception.to_string())
+                }
+                _ => return Err("expected a license exception after `WITH`"),
+            }
+        } else {
+            None
+        };
+        Ok(LicenseExpression::License(LicenseRequirement {
+            id: id.into(),
+            or_later,
+            exception,
+        }))
+    }
+}
+
+/// A set of allowed and denied licenses to check license expressions
+/// against.
+///
+/// Licenses are matched by their SPDX identifiers, ignoring case. When
+/// `allowed` is empty, any license that isn't denied is allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LicensePolicy {
+    pub allowed: Vec<String>,
+    pub denied: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// Whether a single license is acceptable under this policy.
+    pub fn accepts(&self, req: &LicenseRequirement) -> bool {
+        !self.denied.iter().any(|id| req.matches(id))
+            && (self.allowed.is_empty() || self.allowed.iter().any(|id| req.matches(id)))
+    }
+
+    /// Whether a package licensed under `expr` can be used under this
+    /// policy. Dual-licensed packages are permitted as long as any of their
+    /// alternatives is.
+    pub fn permits(&self, expr: &LicenseExpression) -> bool {
+        expr.satisfies(&|req| self.accepts(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> LicenseExpression {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_expressions() {
+        assert_eq!(parse("MIT").to_string(), "MIT");
+        assert_eq!(
+            parse("(MIT OR Apache-2.0)").to_string(),
+            "MIT OR Apache-2.0"
+        );
+        assert_eq!(
+            parse("MIT AND (ISC OR BSD-3-Clause) AND Apache-2.0").to_string(),
+            "MIT AND (ISC OR BSD-3-Clause) AND Apache-2.0"
+        );
+        // AND binds tighter than OR.
+        assert_eq!(
+            parse("MIT OR ISC AND Apache-2.0"),
+            LicenseExpression::Or(vec![
+                parse("MIT"),
+                LicenseExpression::And(vec![parse("ISC"), parse("Apache-2.0")]),
+            ])
+        );
+        assert_eq!(
+            parse("GPL-2.0+ WITH Classpath-exception-2.0"),
+            LicenseExpression::License(LicenseRequirement {
+                id: "GPL-2.0".into(),
+                or_later: true,
+                exception: Some("Classpath-exception-2.0".into()),
+            })
+        );
+        assert_eq!(parse("mit or isc").to_string(), "mit OR isc");
+    }
+
+    #[test]
+    fn dedupes_licenses() {
+        assert_eq!(parse("MIT AND MIT"), parse("MIT"));
+        assert_eq!(parse("MIT OR (MIT OR ISC)"), parse("MIT OR ISC"));
+        assert_eq!(parse("(MIT AND ISC) AND MIT").requirements().len(), 2);
+        assert_eq!(
+            LicenseExpression::from_legacy_licenses(&["MIT", "Apache-2.0", "MIT"]).unwrap(),
+            parse("MIT OR Apache-2.0")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for input in [
+            "",
+            "MIT OR",
+            "(MIT",
+            "MIT)",
+            "MIT WITH",
+            "AND MIT",
+            "SEE LICENSE IN LICENSE.md",
+            "MIT/Apache",
+        ] {
+            assert!(
+                input.parse::<LicenseExpression>().is_err(),
+                "{input} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_policies() {
+        let policy = LicensePolicy {
+            allowed: vec![],
+            denied: vec!["GPL-3.0-only".into(), "AGPL-3.0-only".into()],
+        };
+        assert!(policy.permits(&parse("MIT")));
+        assert!(!policy.permits(&parse("GPL-3.0-only")));
+        // Dual-licensed packages are fine as long as one option is.
+        assert!(policy.permits(&parse("GPL-3.0-only OR MIT")));
+        assert!(!policy.permits(&parse("GPL-3.0-only AND MIT")));
+        assert!(!policy.permits(&parse("gpl-3.0-only OR agpl-3.0-only")));
+
+        let policy = LicensePolicy {
+            allowed: vec![
+                "MIT".into(),
+                "GPL-2.0-or-later".into(),
+                "GPL-2.0-only WITH Classpath-exception-2.0".into(),
+            ],
+            denied: vec![],
+        };
+        assert!(policy.permits(&parse("(ISC OR MIT) AND MIT")));
+        assert!(!policy.permits(&parse("ISC")));
+        assert!(policy.permits(&parse("GPL-2.0+")));
+        assert!(policy.permits(&parse("GPL-2.0-only WITH Classpath-exception-2.0")));
+        assert_eq!(
+            parse("ISC OR (MIT AND GPL-2.0+)")
+                .choose(&|req| policy.accepts(req))
+                .unwrap()
+                .iter()
+                .map(|req| req.to_string())
+                .collect::<Vec<_>>(),
+            vec!["MIT", "GPL-2.0+"]
+        );
+    }
+}
@@ -23,10 +23,37 @@ pub struct CorgiManifest {
     pub optional_dependencies: IndexMap<String, String>,
     #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub peer_dependencies: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub peer_dependencies_meta: IndexMap<String, PeerDependencyMeta>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub dependencies_meta: IndexMap<String, DependencyMeta>,
     #[serde(default, alias = "bundleDependencies", alias = "bundledDependencies")]
     pub bundled_dependencies: Vec<String>,
 }
 
+/// Extra information about a single entry in `peerDependencies`.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerDependencyMeta {
+    /// Whether the package works without this peer. Optional peers are only
+    /// checked if something else installs them.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// Extra information about how a single dependency should be installed, from
+/// `dependenciesMeta`.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyMeta {
+    /// Whether a local dependency should get a copy of its own inside the
+    /// dependent's `node_modules/`, so its peer dependencies are resolved
+    /// against the dependent, instead of being shared with everything else
+    /// that uses it.
+    #[serde(default)]
+    pub injected: bool,
+}
+
 #[derive(Builder, Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Manifest {
@@ -119,6 +146,11 @@ pub struct Manifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repository: Option<Repository>,
 
+    /// Where to go to help fund the package's development.
+    #[builder(setter(strip_option), default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding: Option<Funding>,
+
     /// Information about build scripts the package uses.
     ///
     /// Use [`crate::BuildManifest::from_manifest`][] to get a normalized version
@@ -179,6 +211,14 @@ pub struct Manifest {
     #[builder(default)]
     pub peer_dependencies: IndexMap<String, String>,
 
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    #[builder(default)]
+    pub peer_dependencies_meta: IndexMap<String, PeerDependencyMeta>,
+
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    #[builder(default)]
+    pub dependencies_meta: IndexMap<String, DependencyMeta>,
+
     #[serde(
         default,
         alias = "bundleDependencies",
@@ -188,7 +228,13 @@ pub struct Manifest {
     #[builder(default)]
     pub bundled_dependencies: Vec<String>,
 
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    // NOTE: Yarn also allows `"workspaces": { "packages": [...] }`, so
+    // workspaces can declare `nohoist` patterns next to their packages.
+    #[serde(
+        default,
+        deserialize_with = "workspace_packages",
+        skip_serializing_if = "Vec::is_empty"
+    )]
     #[builder(default)]
     pub workspaces: Vec<String>,
 
@@ -206,6 +252,8 @@ impl From<CorgiManifest> for Manifest {
             dev_dependencies: value.dev_dependencies,
             optional_dependencies: value.optional_dependencies,
             peer_dependencies: value.peer_dependencies,
+            peer_dependencies_meta: value.peer_dependencies_meta,
+            dependencies_meta: value.dependencies_meta,
             bundled_dependencies: value.bundled_dependencies,
             ..Default::default()
         }
@@ -221,6 +269,8 @@ impl From<Manifest> for CorgiManifest {
             dev_dependencies: value.dev_dependencies,
             optional_dependencies: value.optional_dependencies,
             peer_dependencies: value.peer_dependencies,
+            peer_dependencies_meta: value.peer_dependencies_meta,
+            dependencies_meta: value.dependencies_meta,
             bundled_dependencies: value.bundled_dependencies,
         }
     }
@@ -244,6 +294,79 @@ impl From<Manifest> for VersionMetadata {
     }
 }
 
+/// Fields in `publishConfig` that replace the manifest's own when it gets
+/// published.
+const PUBLISH_OVERRIDES: &[&str] = &[
+    "bin",
+    "browser",
+    "cpu",
+    "es2015",
+    "esnext",
+    "exports",
+    "main",
+    "module",
+    "os",
+    "types",
+    "typesVersions",
+    "typings",
+    "umd:main",
+    "unpkg",
+];
+
+impl Manifest {
+    /// The settings in `publishConfig` that change where and how the package
+    /// gets published. Anything that doesn't parse is left out.
+    pub fn publish_settings(&self) -> PublishConfig {
+        let get = |key: &str| {
+            self.publish_config
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(String::from)
+        };
+        PublishConfig {
+            registry: get("registry"),
+            access: get("access"),
+            tag: get("tag"),
+            directory: get("directory"),
+            link_directory: self
+                .publish_config
+                .get("linkDirectory")
+                .and_then(|value| value.as_bool()),
+        }
+    }
+
+    /// The manifest as it gets published, with any fields `publishConfig`
+    /// overrides, like `main` or `exports`, replaced.
+    pub fn for_publishing(&self) -> serde_json::Result<Manifest> {
+        let mut manifest = serde_json::to_value(self)?;
+        if let Value::Object(fields) = &mut manifest {
+            for key in PUBLISH_OVERRIDES {
+                if let Some(value) = self.publish_config.get(*key) {
+                    fields.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        serde_json::from_value(manifest)
+    }
+}
+
+/// Settings from a manifest's `publishConfig`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PublishConfig {
+    /// Registry to publish to, instead of the configured one.
+    pub registry: Option<String>,
+    /// `public` or `restricted`, for scoped packages.
+    pub access: Option<String>,
+    /// Dist-tag to publish under, instead of `latest`.
+    pub tag: Option<String>,
+    /// Subdirectory to publish instead of the package's own directory,
+    /// usually holding build output with its own `package.json`.
+    pub directory: Option<String>,
+    /// Whether `directory` should also be what gets linked when the package
+    /// is installed as a workspace package. Defaults to `true`.
+    pub link_directory: Option<bool>,
+}
+
 fn object_or_bust<'de, D, K, V>(deserializer: D) -> std::result::Result<HashMap<K, V>, D::Error>
 where
     D: Deserializer<'de>,
@@ -258,6 +381,24 @@ where
     }
 }
 
+fn workspace_packages<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Workspaces {
+        Packages(Vec<String>),
+        Object {
+            #[serde(default)]
+            packages: Vec<String>,
+        },
+    }
+    Ok(match Deserialize::deserialize(deserializer)? {
+        Workspaces::Packages(packages) | Workspaces::Object { packages } => packages,
+    })
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum ObjectOrBust<K, V>
@@ -344,6 +485,32 @@ pub enum Repository {
     },
 }
 
+/// Where to go to help fund a package's development. Packages can list a
+/// single URL, an object with a `type` and `url`, or an array of either.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Funding {
+    Str(String),
+    Obj {
+        #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+        funding_type: Option<String>,
+        url: String,
+    },
+    Vec(Vec<Funding>),
+    Other(Value),
+}
+
+impl Funding {
+    /// Every funding URL listed, in order.
+    pub fn urls(&self) -> Vec<&str> {
+        match self {
+            Funding::Str(url) | Funding::Obj { url, .. } => vec![url.as_str()],
+            Funding::Vec(funding) => funding.iter().flat_map(Funding::urls).collect(),
+            Funding::Other(_) => Vec::new(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -449,6 +616,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn object_workspaces() -> Result<()> {
+        let string = r#"
+{
+    "workspaces": {
+        "packages": ["packages/*"],
+        "nohoist": ["**/react-native"]
+    }
+}
+        "#;
+        let parsed = serde_json::from_str::<Manifest>(string).into_diagnostic()?;
+        assert_eq!(
+            parsed,
+            ManifestBuilder::default()
+                .workspaces(vec!["packages/*".into()])
+                .build()
+                .unwrap()
+        );
+        Ok(())
+    }
+
     #[test]
     fn licence_alias() -> Result<()> {
         let string = r#"
@@ -467,6 +655,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn funding_urls() -> Result<()> {
+        let string = r#"
+{
+    "funding": [
+        "https://example.com/donate",
+        { "type": "patreon", "url": "https://www.patreon.com/someone" },
+        { "type": "broken" }
+    ]
+}
+        "#;
+        let parsed = serde_json::from_str::<Manifest>(string).into_diagnostic()?;
+        assert_eq!(
+            parsed.funding.unwrap().urls(),
+            vec![
+                "https://example.com/donate",
+                "https://www.patreon.com/someone"
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn parse_version() -> Result<()> {
         let string = r#"
@@ -507,4 +717,39 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn publish_config() -> Result<()> {
+        let string = r#"
+{
+    "name": "hello",
+    "main": "src/index.ts",
+    "bin": "src/cli.ts",
+    "publishConfig": {
+        "registry": "https://registry.corp.example.com/",
+        "access": "public",
+        "directory": "dist",
+        "main": "index.js",
+        "bin": {
+            "hello": "cli.js"
+        }
+    }
+}
+        "#;
+        let parsed = serde_json::from_str::<Manifest>(string).into_diagnostic()?;
+        let settings = parsed.publish_settings();
+        assert_eq!(
+            settings.registry.as_deref(),
+            Some("https://registry.corp.example.com/")
+        );
+        assert_eq!(settings.access.as_deref(), Some("public"));
+        assert_eq!(settings.tag, None);
+        assert_eq!(settings.directory.as_deref(), Some("dist"));
+
+        let published = parsed.for_publishing().into_diagnostic()?;
+        assert_eq!(published.main.as_deref(), Some("index.js"));
+        assert!(matches!(published.bin, Some(Bin::Hash(bins)) if bins.contains_key("hello")));
+        assert_eq!(published.name.as_deref(), Some("hello"));
+        Ok(())
+    }
 }
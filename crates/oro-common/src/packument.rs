@@ -1,8 +1,9 @@
 use derive_builder::Builder;
 use node_semver::Version;
+use serde::de::{DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use serde_json::Value;
-use std::{collections::HashMap, fmt::Display};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fmt, fmt::Display};
 use url::Url;
 
 use crate::{CorgiManifest, Manifest, PersonField};
@@ -16,6 +17,10 @@ use crate::{CorgiManifest, Manifest, PersonField};
 pub struct CorgiPackument {
     #[serde(default)]
     pub versions: HashMap<Version, CorgiVersionMetadata>,
+    /// Publish times, keyed by version. Registries usually leave these out
+    /// of Corgi packuments, in which case this will be empty.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub time: HashMap<String, String>,
     #[serde(default, rename = "dist-tags")]
     pub tags: HashMap<String, Version>,
 }
@@ -34,6 +39,118 @@ pub struct Packument {
     pub rest: HashMap<String, Value>,
 }
 
+impl CorgiPackument {
+    /// Parses a packument, skipping over the versions `keep` rejects
+    /// instead of materializing them. Packuments for popular packages can
+    /// list thousands of versions, when a resolution only needs the few
+    /// that match what was asked for.
+    pub fn from_json_matching(
+        json: &str,
+        keep: impl Fn(&Version) -> bool,
+    ) -> serde_json::Result<Self> {
+        parse_matching(json, Some(&["versions", "time", "dist-tags"]), &keep)
+    }
+}
+
+impl Packument {
+    /// Parses a packument, skipping over the versions `keep` rejects
+    /// instead of materializing them. See
+    /// [`CorgiPackument::from_json_matching`].
+    pub fn from_json_matching(
+        json: &str,
+        keep: impl Fn(&Version) -> bool,
+    ) -> serde_json::Result<Self> {
+        parse_matching(json, None, &keep)
+    }
+}
+
+/// Parses a packument in two steps: first into a [`Value`] holding only
+/// the wanted `fields` (or all of them) and versions, then into `T`.
+/// Skipped parts are never allocated.
+fn parse_matching<T: DeserializeOwned>(
+    json: &str,
+    fields: Option<&[&str]>,
+    keep: &dyn Fn(&Version) -> bool,
+) -> serde_json::Result<T> {
+    let mut de = serde_json::Deserializer::from_str(json);
+    let value = FilteredPackument { fields, keep }.deserialize(&mut de)?;
+    de.end()?;
+    serde_json::from_value(value)
+}
+
+struct FilteredPackument<'a> {
+    fields: Option<&'a [&'a str]>,
+    keep: &'a dyn Fn(&Version) -> bool,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for FilteredPackument<'a> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for FilteredPackument<'a> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a packument")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut packument = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "versions" {
+                let versions = map.next_value_seed(FilteredVersions { keep: self.keep })?;
+                packument.insert(key, versions);
+            } else if self
+                .fields
+                .map_or(true, |fields| fields.contains(&key.as_str()))
+            {
+                packument.insert(key, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(Value::Object(packument))
+    }
+}
+
+struct FilteredVersions<'a> {
+    keep: &'a dyn Fn(&Version) -> bool,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for FilteredVersions<'a> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for FilteredVersions<'a> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of versions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut versions = Map::new();
+        while let Some(version) = map.next_key::<String>()? {
+            // Versions that don't parse are kept, so they fail the same way
+            // they would without filtering.
+            if Version::parse(&version).map_or(true, |version| (self.keep)(&version)) {
+                versions.insert(version, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(Value::Object(versions))
+    }
+}
+
 impl From<CorgiPackument> for Packument {
     fn from(value: CorgiPackument) -> Self {
         Packument {
@@ -42,6 +159,7 @@ impl From<CorgiPackument> for Packument {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            time: value.time,
             tags: value.tags,
             ..Default::default()
         }
@@ -56,6 +174,7 @@ impl From<Packument> for CorgiPackument {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            time: value.time,
             tags: value.tags,
         }
     }
@@ -218,6 +337,7 @@ pub struct CorgiDist {
     pub integrity: Option<String>,
     #[serde(rename = "npm-signature")]
     pub npm_signature: Option<String>,
+    pub signatures: Option<Vec<DistSignature>>,
 }
 
 /// Distribution information for a particular package version.
@@ -233,11 +353,36 @@ pub struct Dist {
     pub unpacked_size: Option<usize>,
     #[serde(rename = "npm-signature")]
     pub npm_signature: Option<String>,
+    pub signatures: Option<Vec<DistSignature>>,
+    pub attestations: Option<DistAttestations>,
 
     #[serde(flatten)]
     pub rest: HashMap<String, Value>,
 }
 
+/// A registry's signature over a package version's name, version, and
+/// integrity, made with one of the registry's published signing keys.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistSignature {
+    pub keyid: String,
+    pub sig: String,
+}
+
+/// Where to find the attestations published for a particular package
+/// version, like its build provenance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistAttestations {
+    pub url: Url,
+    pub provenance: Option<DistProvenance>,
+}
+
+/// Summary of a package version's build provenance attestation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistProvenance {
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+}
+
 impl From<CorgiDist> for Dist {
     fn from(value: CorgiDist) -> Self {
         Dist {
@@ -245,6 +390,7 @@ impl From<CorgiDist> for Dist {
             tarball: value.tarball,
             integrity: value.integrity,
             npm_signature: value.npm_signature,
+            signatures: value.signatures,
             ..Default::default()
         }
     }
@@ -257,6 +403,43 @@ impl From<Dist> for CorgiDist {
             tarball: value.tarball,
             integrity: value.integrity,
             npm_signature: value.npm_signature,
+            signatures: value.signatures,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn skips_unwanted_versions() {
+        let json = r#"{
+            "name": "foo",
+            "readme": "A very long readme",
+            "dist-tags": { "latest": "2.0.0" },
+            "versions": {
+                "1.0.0": { "name": "foo", "version": "1.0.0" },
+                "1.1.0": { "name": "foo", "version": "1.1.0" },
+                "2.0.0": { "name": "foo", "version": "2.0.0" }
+            }
+        }"#;
+        let keep = |version: &Version| version.major == 1;
+
+        let corgi = CorgiPackument::from_json_matching(json, keep).unwrap();
+        let mut versions = corgi
+            .versions
+            .keys()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        versions.sort();
+        assert_eq!(versions, vec!["1.0.0", "1.1.0"]);
+        assert_eq!(corgi.tags["latest"].to_string(), "2.0.0");
+
+        let full = Packument::from_json_matching(json, keep).unwrap();
+        assert_eq!(full.versions.len(), 2);
+        assert_eq!(full.rest["readme"], "A very long readme");
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::{error::OroConfigError, OroConfig};
+
+/// Options that get read straight out of the config, before the command
+/// line is parsed with it layered in, like where to put debug logs.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BaseOptions {
+    /// Root of the current project.
+    pub root: Option<PathBuf>,
+    /// Location of the cache.
+    pub cache: Option<PathBuf>,
+}
+
+impl BaseOptions {
+    pub fn from_config(config: &OroConfig) -> Result<Self, OroConfigError> {
+        Ok(config.clone().try_deserialize()?)
+    }
+}
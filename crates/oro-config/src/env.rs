@@ -0,0 +1,163 @@
+//! Options from environment variables. `oro_*` variables set Orogene
+//! options directly, like `oro_registry` for `--registry`, and
+//! `npm_config_*` ones are read like `.npmrc` settings, so environments
+//! already set up for npm work as-is.
+//!
+//! Variable names are case-insensitive, and underscores in them stand in
+//! for dashes, so `ORO_HTTPS_PROXY` sets `--https-proxy`. `oro_config_*`
+//! also works, for compatibility with older versions.
+
+use config::{ConfigError, Map, Source, Value, ValueKind};
+
+use crate::npmrc::NpmSettings;
+
+#[derive(Clone, Debug)]
+pub(crate) struct EnvSource {
+    vars: Vec<(String, String)>,
+    npm: bool,
+}
+
+impl EnvSource {
+    /// Reads `oro_*` variables.
+    pub(crate) fn oro() -> Self {
+        Self {
+            vars: current_vars(),
+            npm: false,
+        }
+    }
+
+    /// Reads `npm_config_*` variables.
+    pub(crate) fn npm() -> Self {
+        Self {
+            vars: current_vars(),
+            npm: true,
+        }
+    }
+
+    /// The `oro_*` variables each option got set by, keyed by option name.
+    pub(crate) fn names(&self) -> Vec<(String, String)> {
+        self.oro_vars()
+            .into_iter()
+            .map(|(key, name, _)| (key, name.to_string()))
+            .collect()
+    }
+
+    /// Option names, variable names, and values of the `oro_*` variables,
+    /// in the order they should be applied in.
+    fn oro_vars(&self) -> Vec<(String, &str, Value)> {
+        let mut vars = Vec::new();
+        // Older `oro_config_*` names go first, so the newer names win when
+        // both are set.
+        for prefix in ["oro_config_", "oro_"] {
+            for (name, value) in &self.vars {
+                let lower = name.to_lowercase();
+                let Some(key) = lower.strip_prefix(prefix) else {
+                    continue;
+                };
+                if prefix == "oro_" && key.starts_with("config_") {
+                    continue;
+                }
+                let value = match value.as_str() {
+                    "true" => ValueKind::Boolean(true),
+                    "false" => ValueKind::Boolean(false),
+                    _ => ValueKind::String(value.clone()),
+                };
+                vars.push((
+                    key.replace('_', "-"),
+                    name.as_str(),
+                    Value::new(None, value),
+                ));
+            }
+        }
+        vars
+    }
+}
+
+impl Source for EnvSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        if self.npm {
+            let mut settings = NpmSettings::default();
+            for (name, value) in &self.vars {
+                if let Some(key) = name.to_lowercase().strip_prefix("npm_config_") {
+                    settings.add(&key.replace('_', "-"), value.clone());
+                }
+            }
+            return Ok(settings.into_map());
+        }
+        let mut map = Map::new();
+        for (key, _, value) in self.oro_vars() {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+fn current_vars() -> Vec<(String, String)> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_oro_vars() {
+        let map = EnvSource {
+            vars: vars(&[
+                ("ORO_HTTPS_PROXY", "http://proxy"),
+                ("oro_config_registry", "https://old.example.com"),
+                ("ORO_REGISTRY", "https://new.example.com"),
+                ("ORO_EMOJI", "false"),
+                ("NPM_CONFIG_REGISTRY", "https://npm.example.com"),
+            ]),
+            npm: false,
+        }
+        .collect()
+        .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            map["https-proxy"].clone().into_string().unwrap(),
+            "http://proxy"
+        );
+        assert_eq!(
+            map["registry"].clone().into_string().unwrap(),
+            "https://new.example.com"
+        );
+        assert!(!map["emoji"].clone().into_bool().unwrap());
+    }
+
+    #[test]
+    fn reads_npm_vars() {
+        let map = EnvSource {
+            vars: vars(&[
+                ("npm_config_registry", "https://npm.example.com"),
+                ("NPM_CONFIG_IGNORE_SCRIPTS", "true"),
+                ("npm_config_init_author_name", "Someone"),
+                ("ORO_EMOJI", "false"),
+            ]),
+            npm: true,
+        }
+        .collect()
+        .unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map["registry"].clone().into_string().unwrap(),
+            "https://npm.example.com"
+        );
+        assert!(!map["scripts"].clone().into_bool().unwrap());
+    }
+}
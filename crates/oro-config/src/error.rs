@@ -1,4 +1,4 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 
 #[derive(Debug, Error, Diagnostic)]
@@ -14,4 +14,59 @@ pub enum OroConfigError {
     #[error(transparent)]
     #[diagnostic(code(oro_config::error), url(docsrs))]
     ConfigParseError(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Only a few options can be overridden for individual workspace
+    /// packages: the ones that can differ between packages sharing a
+    /// project.
+    #[error("`{option}` can't be overridden for workspace `{workspace}`.")]
+    #[diagnostic(
+        code(oro_config::unsupported_workspace_option),
+        url(docsrs),
+        help("Workspaces can only override `registry`, `scoped-registries`, `scripts`, `hoist-pattern`, and `public-hoist-pattern`.")
+    )]
+    UnsupportedWorkspaceOption { workspace: String, option: String },
+
+    /// The config file sets an option that doesn't exist. Options in
+    /// `oro.kdl` have the same names as their command line versions.
+    #[error("Unknown option `{option}`.")]
+    #[diagnostic(
+        code(oro_config::unknown_option),
+        url(docsrs),
+        help("Options are named like their command line versions, without the leading `--`. Run `oro help` to see them.")
+    )]
+    UnknownOption {
+        option: String,
+        #[source_code]
+        src: NamedSource,
+        #[label("not an Orogene option")]
+        span: SourceSpan,
+    },
+
+    /// The config file gives an option the wrong kind of value, like a list
+    /// for an option that only takes one value, or a string for one that's
+    /// either on or off.
+    #[error("`{option}` should be set to {expected}.")]
+    #[diagnostic(code(oro_config::invalid_option_type), url(docsrs))]
+    InvalidOptionType {
+        option: String,
+        expected: &'static str,
+        #[source_code]
+        src: NamedSource,
+        #[label("expected {expected}")]
+        span: SourceSpan,
+    },
+
+    /// The config file sets an option to a value it doesn't accept.
+    #[error("`{value}` isn't a valid value for `{option}`.")]
+    #[diagnostic(code(oro_config::invalid_option_value), url(docsrs))]
+    InvalidOptionValue {
+        option: String,
+        value: String,
+        #[source_code]
+        src: NamedSource,
+        #[label("invalid value")]
+        span: SourceSpan,
+        #[help]
+        help: String,
+    },
 }
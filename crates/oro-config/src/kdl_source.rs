@@ -1,6 +1,8 @@
 use config::{ConfigError, FileStoredFormat, Format, Map, Source, Value, ValueKind};
 use kdl::{KdlDocument, KdlNode, KdlValue};
 
+use crate::error::OroConfigError;
+
 #[derive(Clone, Debug)]
 pub(crate) struct KdlSource(KdlDocument);
 
@@ -22,6 +24,72 @@ impl Source for KdlSource {
     }
 }
 
+/// Options that can be overridden for individual workspace packages.
+pub(crate) const WORKSPACE_OPTIONS: &[&str] = &[
+    "registry",
+    "scoped-registries",
+    "scripts",
+    "hoist-pattern",
+    "public-hoist-pattern",
+];
+
+/// Overrides for a single workspace package, from the toplevel
+/// `workspaces` node, which holds them keyed by each workspace package's
+/// path or name:
+///
+/// ```kdl
+/// workspaces {
+///     "packages/legacy" {
+///         registry "https://old-registry.corp.example.com/"
+///         scripts false
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub(crate) struct WorkspaceKdlFormat {
+    /// The workspace package's path, relative to its project, and its name,
+    /// if it has one.
+    pub(crate) keys: Vec<String>,
+}
+
+impl Format for WorkspaceKdlFormat {
+    fn parse(
+        &self,
+        _uri: Option<&String>,
+        text: &str,
+    ) -> Result<Map<String, Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let doc: KdlDocument = text.parse()?;
+        let mut map = Map::new();
+        let Some(workspaces) = doc.get("workspaces").and_then(|node| node.children()) else {
+            return Ok(map);
+        };
+        for workspace in workspaces.nodes() {
+            let key = workspace.name().value();
+            let key = key.trim_start_matches("./").trim_end_matches('/');
+            if !self.keys.iter().any(|wanted| wanted == key) {
+                continue;
+            }
+            for node in workspace.children().iter().flat_map(|doc| doc.nodes()) {
+                let option = node.name().value();
+                if !WORKSPACE_OPTIONS.contains(&option) {
+                    return Err(Box::new(OroConfigError::UnsupportedWorkspaceOption {
+                        workspace: key.into(),
+                        option: option.into(),
+                    }));
+                }
+                map.insert(option.to_string(), node_value(node));
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl FileStoredFormat for WorkspaceKdlFormat {
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["kdl"]
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct KdlFormat;
 
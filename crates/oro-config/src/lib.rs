@@ -1,17 +1,31 @@
 //! Configuration loader for Orogene config files.
 
-use std::{collections::HashSet, ffi::OsString, path::PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
 
+pub use base::BaseOptions;
 pub use clap::{ArgMatches, Command};
 pub use config::Config as OroConfig;
-use config::{builder::DefaultState, ConfigBuilder, Environment, File, ValueKind};
-use kdl_source::KdlFormat;
+use config::{builder::DefaultState, ConfigBuilder, File, Map, Source, Value, ValueKind};
+use env::EnvSource;
+use kdl_source::{KdlFormat, WorkspaceKdlFormat};
 use miette::Result;
+use npmrc::NpmrcFormat;
+pub use origin::{ConfigOrigin, ConfigOrigins};
+pub use schema::ConfigSchema;
 
 use error::OroConfigError;
 
+mod base;
+mod env;
 mod error;
 mod kdl_source;
+mod npmrc;
+mod origin;
+mod schema;
 
 pub trait OroConfigLayerExt {
     fn with_negations(self) -> Self;
@@ -52,25 +66,43 @@ impl OroConfigLayerExt for Command {
     }
 
     fn layered_args(&self, args: &mut Vec<OsString>, config: &OroConfig) -> Result<()> {
-        let mut long_opts = HashSet::new();
+        let mut long_opts = HashMap::new();
         for opt in self.get_arguments() {
-            if opt.get_long().is_some() {
-                long_opts.insert(opt.get_id().to_string());
+            if let Some(long) = opt.get_long() {
+                // Options like `--no-emoji` get turned back on with their
+                // negation, `--emoji`, which overrides them on the command
+                // line just as much.
+                let negation = long
+                    .strip_prefix("no-")
+                    .map(|long| OsString::from(format!("--{long}")));
+                long_opts.insert(
+                    opt.get_id().to_string(),
+                    (opt.get_action().takes_values(), negation),
+                );
             }
         }
         let matches = self
             .clone()
             .ignore_errors(true)
             .get_matches_from(&args.clone());
-        for opt in long_opts {
+        for (opt, (takes_values, negation)) in long_opts {
             // TODO: _prepend_ args unconditionally if they're coming from
             // config, so multi-args get parsed right. Right now, if you have
             // something in your config, it'll get completely overridden by
             // the command line.
             if matches.value_source(&opt) != Some(clap::parser::ValueSource::CommandLine) {
                 let opt = opt.replace('_', "-");
-                if !args.contains(&OsString::from(format!("--no-{opt}"))) {
-                    if let Ok(bool) = config.get_bool(&opt) {
+                let negated = args.contains(&OsString::from(format!("--no-{opt}")))
+                    || negation.map_or(false, |negation| args.contains(&negation));
+                if !negated {
+                    // Values like `1` would pass for booleans too, so
+                    // only flags get treated as ones.
+                    let flag = if takes_values {
+                        None
+                    } else {
+                        config.get_bool(&opt).ok()
+                    };
+                    if let Some(bool) = flag {
                         if bool {
                             args.push(OsString::from(format!("--{}", opt)));
                         } else {
@@ -117,6 +149,10 @@ pub struct OroConfigOptions {
     env: bool,
     pkg_root: Option<PathBuf>,
     global_config_file: Option<PathBuf>,
+    npmrc_files: Vec<PathBuf>,
+    workspace_of: Option<(PathBuf, Vec<String>)>,
+    schema: Option<ConfigSchema>,
+    defaults: Vec<(String, ConfigOrigin)>,
 }
 
 impl Default for OroConfigOptions {
@@ -127,6 +163,10 @@ impl Default for OroConfigOptions {
             env: true,
             pkg_root: None,
             global_config_file: None,
+            npmrc_files: Vec::new(),
+            workspace_of: None,
+            schema: None,
+            defaults: Vec::new(),
         }
     }
 }
@@ -156,28 +196,146 @@ impl OroConfigOptions {
         self
     }
 
+    /// `.npmrc` files to read npm's settings from, like `registry` and
+    /// `ignore-scripts`. Later files take precedence over earlier ones, and
+    /// all `oro.kdl` files and environment variables take precedence over
+    /// them.
+    pub fn npmrc_files(mut self, files: Vec<PathBuf>) -> Self {
+        self.npmrc_files = files;
+        self
+    }
+
+    /// Treats the package at `pkg_root` as the workspace package at `path`,
+    /// relative to `project`, and named `name`. The project's own `oro.kdl`
+    /// options get used too, along with any overrides for the package in
+    /// its `workspaces` node, keyed by either `path` or `name`. The
+    /// package's own `oro.kdl` still takes precedence over both.
+    pub fn workspace_of(mut self, project: PathBuf, path: String, name: Option<String>) -> Self {
+        self.workspace_of = Some((project, std::iter::once(path).chain(name).collect()));
+        self
+    }
+
+    /// Checks the options in every `oro.kdl` against `schema` before
+    /// loading them, so unknown options and invalid values are reported
+    /// with where they are in the file.
+    pub fn schema(mut self, schema: ConfigSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
     pub fn set_default(mut self, key: &str, value: &str) -> Result<Self, OroConfigError> {
         self.builder = self.builder.set_default(key, value)?;
+        self.defaults.push((key.into(), ConfigOrigin::Default));
+        Ok(self)
+    }
+
+    /// Sets a default that only applies because Orogene is running in the
+    /// CI service named `ci`, so it shows up as coming from there.
+    pub fn ci_default(mut self, ci: &str, key: &str, value: &str) -> Result<Self, OroConfigError> {
+        self.builder = self.builder.set_default(key, value)?;
+        self.defaults
+            .push((key.into(), ConfigOrigin::CiDefault(ci.into())));
         Ok(self)
     }
 
+    /// Loads configuration from every source, in this order of precedence,
+    /// highest first:
+    ///
+    /// 1. `oro_*` environment variables
+    /// 2. The project's `oro.kdl`
+    /// 3. Overrides for the project in the `oro.kdl` of the project it's a
+    ///    workspace package of, if it is one, followed by that `oro.kdl`'s
+    ///    options. See [`OroConfigOptions::workspace_of`].
+    /// 4. The global `oro.kdl`, or the one given with
+    ///    [`OroConfigOptions::global_config_file`]
+    /// 5. `npm_config_*` environment variables
+    /// 6. [`.npmrc` files](OroConfigOptions::npmrc_files)
+    /// 7. Defaults
+    ///
+    /// Command line options take precedence over all of these, once
+    /// they're layered on top with [`OroConfigLayerExt::layered_args`].
     pub fn load(self) -> Result<OroConfig> {
-        let mut builder = self.builder;
+        Ok(self.load_with_origins()?.0)
+    }
+
+    /// Like [`OroConfigOptions::load`], but also returns where each
+    /// option's value came from.
+    pub fn load_with_origins(self) -> Result<(OroConfig, ConfigOrigins)> {
+        let validate = |file: &Path| match &self.schema {
+            Some(schema) => schema.validate_file(file),
+            None => Ok(()),
+        };
+        let mut origins = ConfigOrigins::default();
+        for (key, origin) in &self.defaults {
+            origins.insert(key.clone(), origin.clone());
+        }
+        let mut layers: Vec<(Box<dyn Source + Send + Sync>, ConfigOrigin)> = Vec::new();
+        for npmrc in self.npmrc_files {
+            let file = File::new(&npmrc.display().to_string(), NpmrcFormat).required(false);
+            layers.push((Box::new(file), ConfigOrigin::Npmrc(npmrc)));
+        }
+        if self.env {
+            layers.push((Box::new(EnvSource::npm()), ConfigOrigin::NpmEnv));
+        }
         if self.global {
             if let Some(config_file) = self.global_config_file {
+                validate(&config_file)?;
                 let path = config_file.display().to_string();
-                builder = builder.add_source(File::new(&path, KdlFormat).required(false));
+                let file = File::new(&path, KdlFormat).required(false);
+                layers.push((Box::new(file), ConfigOrigin::UserConfig(config_file)));
             }
         }
-        if self.env {
-            builder = builder.add_source(Environment::with_prefix("oro_config"));
+        if let Some((project, keys)) = self.workspace_of {
+            let config_file = project.join("oro.kdl");
+            validate(&config_file)?;
+            let path = config_file.display().to_string();
+            let file = File::new(&path, KdlFormat).required(false);
+            layers.push((
+                Box::new(file),
+                ConfigOrigin::ProjectConfig(config_file.clone()),
+            ));
+            let file = File::new(&path, WorkspaceKdlFormat { keys }).required(false);
+            layers.push((Box::new(file), ConfigOrigin::WorkspaceOverride(config_file)));
         }
         if let Some(root) = self.pkg_root {
-            builder = builder.add_source(
-                File::new(&root.join("oro.kdl").display().to_string(), KdlFormat).required(false),
-            );
+            let config_file = root.join("oro.kdl");
+            validate(&config_file)?;
+            let file = File::new(&config_file.display().to_string(), KdlFormat).required(false);
+            layers.push((Box::new(file), ConfigOrigin::ProjectConfig(config_file)));
+        }
+        // Each source is collected here, instead of by the builder, so its
+        // options can be recorded along with where they came from.
+        let mut builder = self.builder;
+        for (source, origin) in layers {
+            let map = source.collect().map_err(OroConfigError::ConfigError)?;
+            for key in map.keys() {
+                origins.insert(key.clone(), origin.clone());
+            }
+            builder = builder.add_source(Layer(map));
+        }
+        if self.env {
+            let env = EnvSource::oro();
+            for (key, name) in env.names() {
+                origins.insert(key, ConfigOrigin::Env(name));
+            }
+            builder = builder.add_source(env);
         }
-        Ok(builder.build().map_err(OroConfigError::ConfigError)?)
+        let config = builder.build().map_err(OroConfigError::ConfigError)?;
+        Ok((config, origins))
+    }
+}
+
+/// Options already collected from a source.
+#[derive(Clone, Debug)]
+pub(crate) struct Layer(pub(crate) Map<String, Value>);
+
+impl Source for Layer {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, config::ConfigError> {
+        Ok(self.0.clone())
     }
 }
 
@@ -221,6 +379,159 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn npmrc_config() -> Result<()> {
+        let dir = tempdir().into_diagnostic()?;
+        let user = dir.path().join("user-npmrc");
+        let project = dir.path().join(".npmrc");
+        let oro = dir.path().join("oro.kdl");
+        fs::write(&user, "registry=https://user.example.com/\nsave-exact=true")
+            .into_diagnostic()?;
+        fs::write(
+            &project,
+            "registry=https://project.example.com/\nproxy=http://proxy",
+        )
+        .into_diagnostic()?;
+        fs::write(&oro, "options{\nproxy \"http://oro-proxy\"\n}").into_diagnostic()?;
+        let config = OroConfigOptions::new()
+            .env(false)
+            .npmrc_files(vec![user, project])
+            .global_config_file(Some(oro))
+            .load()?;
+        assert_eq!(
+            config.get_string("registry").into_diagnostic()?,
+            "https://project.example.com/"
+        );
+        assert!(config.get_bool("save-exact").into_diagnostic()?);
+        assert_eq!(
+            config.get_string("proxy").into_diagnostic()?,
+            "http://oro-proxy"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_origins() -> Result<()> {
+        let dir = tempdir().into_diagnostic()?;
+        let npmrc = dir.path().join(".npmrc");
+        let oro = dir.path().join("oro.kdl");
+        fs::write(
+            &npmrc,
+            "registry=https://npm.example.com/\nproxy=http://proxy",
+        )
+        .into_diagnostic()?;
+        fs::write(&oro, "options{\nproxy \"http://oro-proxy\"\n}").into_diagnostic()?;
+        let (config, origins) = OroConfigOptions::new()
+            .env(false)
+            .set_default("cache", "/tmp/cache")?
+            .npmrc_files(vec![npmrc.clone()])
+            .global_config_file(Some(oro.clone()))
+            .load_with_origins()?;
+        assert_eq!(
+            config.get_string("proxy").into_diagnostic()?,
+            "http://oro-proxy"
+        );
+        assert_eq!(origins.get("cache"), Some(&ConfigOrigin::Default));
+        assert_eq!(origins.get("registry"), Some(&ConfigOrigin::Npmrc(npmrc)));
+        assert_eq!(origins.get("proxy"), Some(&ConfigOrigin::UserConfig(oro)));
+        assert_eq!(origins.get("store"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn workspace_overrides() -> Result<()> {
+        let dir = tempdir().into_diagnostic()?;
+        let project = dir.path().to_path_buf();
+        let pkg = project.join("packages").join("legacy");
+        fs::create_dir_all(&pkg).into_diagnostic()?;
+        fs::write(
+            project.join("oro.kdl"),
+            r#"
+options {
+    registry "https://registry.example.com/"
+    emoji false
+}
+workspaces {
+    "packages/legacy" {
+        registry "https://old-registry.example.com/"
+    }
+    "@corp/other" {
+        scripts false
+    }
+}
+"#,
+        )
+        .into_diagnostic()?;
+        let load = |path: &str, name: &str| {
+            OroConfigOptions::new()
+                .global(false)
+                .env(false)
+                .workspace_of(project.clone(), path.into(), Some(name.into()))
+                .pkg_root(Some(pkg.clone()))
+                .load()
+        };
+
+        let config = load("packages/legacy", "@corp/legacy")?;
+        assert_eq!(
+            config.get_string("registry").into_diagnostic()?,
+            "https://old-registry.example.com/"
+        );
+        assert!(!config.get_bool("emoji").into_diagnostic()?);
+        assert!(config.get_bool("scripts").is_err());
+
+        let config = load("packages/other", "@corp/other")?;
+        assert_eq!(
+            config.get_string("registry").into_diagnostic()?,
+            "https://registry.example.com/"
+        );
+        assert!(!config.get_bool("scripts").into_diagnostic()?);
+
+        fs::write(
+            project.join("oro.kdl"),
+            "workspaces {\n\"packages/legacy\" {\ncache \"/tmp\"\n}\n}",
+        )
+        .into_diagnostic()?;
+        assert!(load("packages/legacy", "@corp/legacy").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ci_defaults() -> Result<()> {
+        let command = Command::new("oro")
+            .arg(
+                clap::Arg::new("locked")
+                    .long("locked")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("emoji")
+                    .long("no-emoji")
+                    .action(clap::ArgAction::SetFalse),
+            )
+            .with_negations();
+        let (config, origins) = OroConfigOptions::new()
+            .global(false)
+            .env(false)
+            .ci_default("GitHub Actions", "locked", "true")?
+            .ci_default("GitHub Actions", "emoji", "false")?
+            .load_with_origins()?;
+        assert_eq!(
+            origins.get("locked"),
+            Some(&ConfigOrigin::CiDefault("GitHub Actions".into()))
+        );
+
+        let mut args = vec![OsString::from("oro"), OsString::from("--emoji")];
+        command.layered_args(&mut args, &config)?;
+        assert!(args.contains(&OsString::from("--locked")));
+        assert!(!args.contains(&OsString::from("--no-emoji")));
+
+        let mut args = vec![OsString::from("oro"), OsString::from("--no-locked")];
+        command.layered_args(&mut args, &config)?;
+        assert!(!args.contains(&OsString::from("--locked")));
+        assert!(args.contains(&OsString::from("--no-emoji")));
+        Ok(())
+    }
+
     #[test]
     fn missing_config() -> Result<()> {
         let config = OroConfigOptions::new().global(false).env(false).load()?;
@@ -0,0 +1,189 @@
+//! Settings from npm's `.npmrc` files, so projects already configured for
+//! npm work without repeating their configuration in `oro.kdl`.
+//!
+//! Only the settings that mean the same thing to Orogene are picked up, and
+//! they're renamed to the options they correspond to. Everything else,
+//! including credentials, which get read separately, is ignored.
+
+use config::{FileStoredFormat, Format, Map, Value, ValueKind};
+
+#[derive(Clone, Debug)]
+pub(crate) struct NpmrcFormat;
+
+impl Format for NpmrcFormat {
+    fn parse(
+        &self,
+        _uri: Option<&String>,
+        text: &str,
+    ) -> Result<Map<String, Value>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(parse_with_env(text, &|name| std::env::var(name).ok()))
+    }
+}
+
+impl FileStoredFormat for NpmrcFormat {
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+fn parse_with_env(text: &str, env: &dyn Fn(&str) -> Option<String>) -> Map<String, Value> {
+    let mut settings = NpmSettings::default();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';', '[']) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        // Settings that refer to unset environment variables are skipped,
+        // the same way npm would fail to use them.
+        let Some(value) = interpolate(unquote(value.trim()), env) else {
+            continue;
+        };
+        settings.add(key.trim(), value);
+    }
+    settings.into_map()
+}
+
+/// npm settings, renamed to the Orogene options they correspond to. Used
+/// for both `.npmrc` files and `npm_config_*` environment variables.
+#[derive(Debug, Default)]
+pub(crate) struct NpmSettings {
+    map: Map<String, Value>,
+    scoped_registries: Map<String, Value>,
+    ca: Vec<Value>,
+}
+
+impl NpmSettings {
+    pub(crate) fn add(&mut self, key: &str, value: String) {
+        if let Some(scope) = key
+            .strip_suffix(":registry")
+            .filter(|scope| scope.starts_with('@'))
+        {
+            self.scoped_registries
+                .insert(scope.to_string(), string(value));
+            return;
+        }
+        let (name, value) = match key {
+            "registry" | "proxy" | "https-proxy" | "cafile" | "save-prefix" => (key, string(value)),
+            "noproxy" | "no-proxy" => ("noproxy", string(value)),
+            "maxsockets" => ("max-sockets", string(value)),
+            "strict-ssl" | "save-exact" | "save-dev" | "save-optional" | "save-prod" => {
+                (key, boolean(&value))
+            }
+            "ignore-scripts" => ("scripts", Value::new(None, !is_true(&value))),
+            "ca" | "ca[]" => {
+                self.ca.push(string(value));
+                return;
+            }
+            _ => return,
+        };
+        self.map.insert(name.to_string(), value);
+    }
+
+    pub(crate) fn into_map(mut self) -> Map<String, Value> {
+        if !self.scoped_registries.is_empty() {
+            self.map.insert(
+                "scoped-registries".into(),
+                Value::new(None, ValueKind::Table(self.scoped_registries)),
+            );
+        }
+        if !self.ca.is_empty() {
+            self.map
+                .insert("ca".into(), Value::new(None, ValueKind::Array(self.ca)));
+        }
+        self.map
+    }
+}
+
+fn string(value: String) -> Value {
+    Value::new(None, ValueKind::String(value))
+}
+
+fn boolean(value: &str) -> Value {
+    Value::new(None, is_true(value))
+}
+
+fn is_true(value: &str) -> bool {
+    value == "true"
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Replaces `${VAR}` references in `value` with environment variables, or
+/// returns `None` if one isn't set. `${VAR?}` is replaced with an empty
+/// string instead.
+fn interpolate(value: &str, env: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    let mut interpolated = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        interpolated.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + len];
+        match name.strip_suffix('?') {
+            Some(name) => interpolated.push_str(&env(name).unwrap_or_default()),
+            None => interpolated.push_str(&env(name)?),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    interpolated.push_str(rest);
+    Some(interpolated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn maps_npm_settings() {
+        let map = parse_with_env(
+            r#"
+; comments are skipped
+registry = "https://registry.corp.example.com/"
+@mycorp:registry=https://${CORP_HOST}/npm/
+@other:registry=https://${UNSET}/
+proxy=http://proxy.corp:8080
+strict-ssl=false
+ignore-scripts=true
+save-exact=true
+save-dev=true
+ca[]=first
+ca[]=second
+//registry.corp.example.com/:_authToken=secret
+init-author-name=Someone
+"#,
+            &|name| (name == "CORP_HOST").then(|| "npm.corp".to_string()),
+        );
+        let get = |key: &str| map.get(key).cloned().unwrap();
+        assert_eq!(
+            get("registry").into_string().unwrap(),
+            "https://registry.corp.example.com/"
+        );
+        assert_eq!(
+            get("proxy").into_string().unwrap(),
+            "http://proxy.corp:8080"
+        );
+        assert!(!get("strict-ssl").into_bool().unwrap());
+        assert!(!get("scripts").into_bool().unwrap());
+        assert!(get("save-exact").into_bool().unwrap());
+        assert!(get("save-dev").into_bool().unwrap());
+        let scoped = get("scoped-registries").into_table().unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(
+            scoped["@mycorp"].clone().into_string().unwrap(),
+            "https://npm.corp/npm/"
+        );
+        assert_eq!(get("ca").into_array().unwrap().len(), 2);
+        assert_eq!(map.len(), 8);
+    }
+}
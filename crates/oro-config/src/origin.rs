@@ -0,0 +1,217 @@
+//! Tracking of where each option's value came from, so surprising values
+//! can be traced back to the file or variable that set them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use clap::{parser::ValueSource, ArgAction, ArgMatches, Command};
+use config::{Map, Value, ValueKind};
+
+use crate::{error::OroConfigError, Layer, OroConfig};
+
+/// Where an option's value came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    /// Built into Orogene.
+    Default,
+    /// Built into Orogene, for running in the named CI service.
+    CiDefault(String),
+    /// An `.npmrc` file.
+    Npmrc(PathBuf),
+    /// An `npm_config_*` environment variable.
+    NpmEnv,
+    /// The global `oro.kdl`, or the one given with `--config`.
+    UserConfig(PathBuf),
+    /// The project's `oro.kdl`, or the one of the project it's a workspace
+    /// package of.
+    ProjectConfig(PathBuf),
+    /// The workspace package's overrides in its project's `oro.kdl`.
+    WorkspaceOverride(PathBuf),
+    /// An `oro_*` environment variable, with the given name.
+    Env(String),
+    /// A command line option.
+    CommandLine,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::CiDefault(ci) => write!(f, "default for {ci}"),
+            ConfigOrigin::Npmrc(path) => write!(f, "npmrc {}", path.display()),
+            ConfigOrigin::NpmEnv => write!(f, "npm_config_* environment variable"),
+            ConfigOrigin::UserConfig(path) => write!(f, "user config {}", path.display()),
+            ConfigOrigin::ProjectConfig(path) => write!(f, "project config {}", path.display()),
+            ConfigOrigin::WorkspaceOverride(path) => {
+                write!(f, "workspace override in {}", path.display())
+            }
+            ConfigOrigin::Env(name) => write!(f, "environment variable {name}"),
+            ConfigOrigin::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// The origin of every option set while loading a config, keyed by option
+/// name. For tables, like `scoped-registries`, which are merged across
+/// sources, this is the last source that added to them.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigOrigins(BTreeMap<String, ConfigOrigin>);
+
+impl ConfigOrigins {
+    pub fn get(&self, key: &str) -> Option<&ConfigOrigin> {
+        self.0.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConfigOrigin)> {
+        self.0.iter().map(|(key, origin)| (key.as_str(), origin))
+    }
+
+    pub(crate) fn insert(&mut self, key: impl Into<String>, origin: ConfigOrigin) {
+        self.0.insert(key.into(), origin);
+    }
+
+    /// Records the global options `matches` got from the command line, and
+    /// returns `config` with their values layered on top, so it has every
+    /// option's effective value.
+    pub fn add_command_line(
+        &mut self,
+        config: OroConfig,
+        command: &Command,
+        matches: &ArgMatches,
+    ) -> Result<OroConfig, OroConfigError> {
+        let mut map = Map::new();
+        for arg in command.get_arguments() {
+            if !arg.is_global_set() || arg.get_long().is_none() {
+                continue;
+            }
+            let id = arg.get_id().as_str();
+            if matches.value_source(id) != Some(ValueSource::CommandLine) {
+                continue;
+            }
+            // Negations, added by `with_negations`, are the only options
+            // with `no-` ids, and mean the opposite of what they negate.
+            let original = id.trim_start_matches("no-");
+            let key = original.replace('_', "-");
+            let value = if arg.get_action().takes_values() {
+                let mut values = matches
+                    .get_raw(id)
+                    .into_iter()
+                    .flatten()
+                    .map(|value| Value::new(None, value.to_string_lossy().into_owned()))
+                    .collect::<Vec<_>>();
+                if values.len() == 1 {
+                    values.remove(0)
+                } else {
+                    Value::new(None, ValueKind::Array(values))
+                }
+            } else {
+                let sets_false = command
+                    .get_arguments()
+                    .find(|arg| arg.get_id().as_str() == original)
+                    .map(|arg| matches!(arg.get_action(), ArgAction::SetFalse))
+                    .unwrap_or(false);
+                Value::new(None, sets_false == (id != original))
+            };
+            map.insert(key.clone(), value);
+            self.insert(key, ConfigOrigin::CommandLine);
+        }
+        OroConfig::builder()
+            .add_source(config)
+            .add_source(Layer(map))
+            .build()
+            .map_err(OroConfigError::ConfigError)
+    }
+
+    /// Adds a tip to a command line parsing `error` about where the option
+    /// it's about was set, if that was in config instead of on the command
+    /// line.
+    pub fn explain(&self, command: &Command, error: &mut clap::Error) {
+        use clap::error::{ContextKind, ContextValue};
+
+        let Some(ContextValue::String(arg)) = error.get(ContextKind::InvalidArg) else {
+            return;
+        };
+        let long = arg
+            .trim_start_matches("--")
+            .split([' ', '='])
+            .next()
+            .unwrap_or_default();
+        let key = option_key(command, long).unwrap_or_else(|| long.to_string());
+        let Some(origin) = self.get(&key) else {
+            return;
+        };
+        if *origin == ConfigOrigin::CommandLine || *origin == ConfigOrigin::Default {
+            return;
+        }
+        let tip = format!("`{key}` was set by the {origin}").into();
+        let mut tips = match error.get(ContextKind::Suggested) {
+            Some(ContextValue::StyledStrs(tips)) => tips.clone(),
+            _ => Vec::new(),
+        };
+        tips.push(tip);
+        error.insert(ContextKind::Suggested, ContextValue::StyledStrs(tips));
+    }
+}
+
+/// The config key of the option with the `long` name, in `command` or any
+/// of its subcommands.
+fn option_key(command: &Command, long: &str) -> Option<String> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+        .map(|arg| arg.get_id().as_str().replace('_', "-"))
+        .or_else(|| {
+            command
+                .get_subcommands()
+                .find_map(|subcommand| option_key(subcommand, long))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Arg;
+    use pretty_assertions::assert_eq;
+
+    use crate::OroConfigLayerExt;
+
+    #[test]
+    fn command_line_origins() -> Result<(), OroConfigError> {
+        let command = Command::new("oro")
+            .arg(Arg::new("registry").long("registry").global(true))
+            .arg(
+                Arg::new("emoji")
+                    .long("no-emoji")
+                    .global(true)
+                    .action(ArgAction::SetFalse),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .global(true)
+                    .action(ArgAction::SetTrue),
+            )
+            .with_negations();
+        let matches = command.clone().get_matches_from([
+            "oro",
+            "--registry",
+            "https://registry.example.com/",
+            "--emoji",
+            "--no-json",
+        ]);
+        let mut origins = ConfigOrigins::default();
+        origins.insert("registry", ConfigOrigin::Env("oro_registry".into()));
+        let config = origins.add_command_line(OroConfig::default(), &command, &matches)?;
+        assert_eq!(
+            config.get_string("registry")?,
+            "https://registry.example.com/"
+        );
+        assert!(config.get_bool("emoji")?);
+        assert!(!config.get_bool("json")?);
+        assert_eq!(origins.get("registry"), Some(&ConfigOrigin::CommandLine));
+        assert_eq!(origins.get("emoji"), Some(&ConfigOrigin::CommandLine));
+        Ok(())
+    }
+}
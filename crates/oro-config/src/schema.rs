@@ -0,0 +1,317 @@
+//! Checks the options in `oro.kdl` files against the ones Orogene's
+//! commands actually accept, so typos and bad values get pointed out right
+//! in the file, instead of being ignored or turning into confusing command
+//! line errors later on.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use clap::{ArgAction, Command};
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use miette::{NamedSource, SourceSpan};
+
+use crate::error::OroConfigError;
+
+/// The options that can be set in config files, worked out from the
+/// command line options of a [`Command`] and all its subcommands.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSchema {
+    options: HashMap<String, OptionSchema>,
+}
+
+#[derive(Clone, Debug)]
+struct OptionSchema {
+    kind: OptionKind,
+    possible_values: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OptionKind {
+    /// `--foo`, set with `foo true`, `foo false`, or just `foo`.
+    Flag,
+    /// `--foo <value>`, set with `foo "value"`.
+    Value,
+    /// `--foo <value>` that can be given more than once, set with a list
+    /// or a table, like `foo "a" "b"` or `foo a="b"`.
+    Values,
+}
+
+impl ConfigSchema {
+    pub fn from_command(command: &Command) -> Self {
+        let mut schema = Self::default();
+        schema.add_command(command);
+        schema
+    }
+
+    fn add_command(&mut self, command: &Command) {
+        for arg in command.get_arguments() {
+            if arg.get_long().is_none() {
+                continue;
+            }
+            let kind = match arg.get_action() {
+                action if !action.takes_values() => OptionKind::Flag,
+                ArgAction::Append => OptionKind::Values,
+                _ => OptionKind::Value,
+            };
+            let possible_values = arg
+                .get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string())
+                .collect();
+            self.options.insert(
+                arg.get_id().to_string().replace('_', "-"),
+                OptionSchema {
+                    kind,
+                    possible_values,
+                },
+            );
+        }
+        for subcommand in command.get_subcommands() {
+            self.add_command(subcommand);
+        }
+    }
+
+    /// Checks the `oro.kdl` at `path`, if there is one. Syntax errors are
+    /// left for the config loader to report.
+    pub(crate) fn validate_file(&self, path: &Path) -> Result<(), OroConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => self.validate(&path.display().to_string(), text),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn validate(&self, name: &str, text: String) -> Result<(), OroConfigError> {
+        let Ok(doc) = text.parse::<KdlDocument>() else {
+            return Ok(());
+        };
+        let options = doc.get("options").and_then(|node| node.children());
+        let overrides = doc
+            .get("workspaces")
+            .and_then(|node| node.children())
+            .into_iter()
+            .flat_map(|workspaces| workspaces.nodes())
+            .filter_map(|workspace| workspace.children());
+        for node in options
+            .into_iter()
+            .chain(overrides)
+            .flat_map(|doc| doc.nodes())
+        {
+            if let Some(problem) = self.check(node) {
+                return Err(problem.into_error(NamedSource::new(name, text)));
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, node: &KdlNode) -> Option<Problem> {
+        let option = node.name().value().to_string();
+        let Some(schema) = self.options.get(&option) else {
+            return Some(Problem::Unknown {
+                option,
+                span: node.name().span(),
+            });
+        };
+        let values = node
+            .entries()
+            .iter()
+            .filter(|entry| entry.name().is_none())
+            .collect::<Vec<_>>();
+        let has_props = values.len() != node.entries().len();
+        let has_children = node.children().is_some();
+        let not_scalar = |entry: &&KdlEntry| {
+            matches!(entry.value(), KdlValue::Null) || entry.value().as_bool().is_some()
+        };
+        let expected = match schema.kind {
+            OptionKind::Flag => {
+                let is_bool = !has_props
+                    && !has_children
+                    && (values.is_empty()
+                        || values.len() == 1 && values[0].value().as_bool().is_some());
+                (!is_bool).then_some("`true` or `false`")
+            }
+            OptionKind::Value => {
+                let is_single =
+                    values.len() == 1 && !has_props && !has_children && !not_scalar(&values[0]);
+                (!is_single).then_some("a single value")
+            }
+            OptionKind::Values => {
+                let is_empty = node.entries().is_empty() && !has_children;
+                (is_empty || node.entries().iter().any(|entry| not_scalar(&entry)))
+                    .then_some("one or more values")
+            }
+        };
+        if let Some(expected) = expected {
+            return Some(Problem::Type {
+                option,
+                expected,
+                span: entries_span(node),
+            });
+        }
+        if schema.possible_values.is_empty() {
+            return None;
+        }
+        for entry in values {
+            let Some(value) = entry.value().as_string() else {
+                continue;
+            };
+            if !schema
+                .possible_values
+                .iter()
+                .any(|possible| possible == value)
+            {
+                return Some(Problem::Value {
+                    option,
+                    value: value.into(),
+                    span: entry.span(),
+                    possible: schema.possible_values.clone(),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// What's wrong with an option, before it's turned into an error pointing
+/// at the file it's in.
+enum Problem {
+    Unknown {
+        option: String,
+        span: SourceSpan,
+    },
+    Type {
+        option: String,
+        expected: &'static str,
+        span: SourceSpan,
+    },
+    Value {
+        option: String,
+        value: String,
+        span: SourceSpan,
+        possible: Vec<String>,
+    },
+}
+
+impl Problem {
+    fn into_error(self, src: NamedSource) -> OroConfigError {
+        match self {
+            Problem::Unknown { option, span } => {
+                OroConfigError::UnknownOption { option, src, span }
+            }
+            Problem::Type {
+                option,
+                expected,
+                span,
+            } => OroConfigError::InvalidOptionType {
+                option,
+                expected,
+                src,
+                span,
+            },
+            Problem::Value {
+                option,
+                value,
+                span,
+                possible,
+            } => OroConfigError::InvalidOptionValue {
+                option,
+                value,
+                src,
+                span,
+                help: format!(
+                    "Expected one of {}.",
+                    possible
+                        .iter()
+                        .map(|value| format!("`{value}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+        }
+    }
+}
+
+/// The span of a node's values, or of its name if it doesn't have any.
+fn entries_span(node: &KdlNode) -> SourceSpan {
+    match (node.entries().first(), node.entries().last()) {
+        (Some(first), Some(last)) => {
+            let start = first.span().offset();
+            let end = last.span().offset() + last.span().len();
+            (start, end - start).into()
+        }
+        _ => node.name().span(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Arg;
+
+    fn schema() -> ConfigSchema {
+        ConfigSchema::from_command(
+            &Command::new("oro")
+                .arg(Arg::new("emoji").long("emoji").action(ArgAction::SetTrue))
+                .arg(Arg::new("registry").long("registry"))
+                .subcommand(
+                    Command::new("apply")
+                        .arg(
+                            Arg::new("linker")
+                                .long("linker")
+                                .value_parser(["isolated", "hoisted"]),
+                        )
+                        .arg(
+                            Arg::new("hoist_pattern")
+                                .long("hoist-pattern")
+                                .action(ArgAction::Append),
+                        ),
+                ),
+        )
+    }
+
+    fn validate(options: &str) -> Result<(), OroConfigError> {
+        schema().validate("oro.kdl", format!("options {{\n{options}\n}}"))
+    }
+
+    #[test]
+    fn accepts_valid_options() {
+        assert!(validate(
+            r#"
+emoji false
+registry "https://registry.example.com/"
+linker "hoisted"
+hoist-pattern "*eslint*" "*babel*"
+"#
+        )
+        .is_ok());
+        assert!(validate("emoji").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(matches!(
+            validate("regsitry \"https://registry.example.com/\""),
+            Err(OroConfigError::UnknownOption { option, .. }) if option == "regsitry"
+        ));
+        assert!(matches!(
+            validate("emoji \"yes\""),
+            Err(OroConfigError::InvalidOptionType { option, .. }) if option == "emoji"
+        ));
+        assert!(matches!(
+            validate("registry \"a\" \"b\""),
+            Err(OroConfigError::InvalidOptionType { option, .. }) if option == "registry"
+        ));
+        assert!(matches!(
+            validate("linker \"symlinked\""),
+            Err(OroConfigError::InvalidOptionValue { option, value, .. })
+                if option == "linker" && value == "symlinked"
+        ));
+        assert!(matches!(
+            schema().validate(
+                "oro.kdl",
+                "workspaces {\n\"packages/a\" {\nemoji 1\n}\n}".into()
+            ),
+            Err(OroConfigError::InvalidOptionType { option, .. }) if option == "emoji"
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * C interface for embedding Orogene's resolver and installer.
+ *
+ * Every call takes a NUL-terminated JSON request and, whether it succeeds
+ * or not, sets `*response` to a NUL-terminated JSON response that must be
+ * freed with `oro_string_free`. Failed calls respond with
+ * `{"code": "...", "message": "..."}`, where `code` is the same diagnostic
+ * code `oro` prints, when there is one.
+ *
+ * Requests can have these fields, all optional unless noted:
+ *
+ *   root           Project root, with the package.json to install.
+ *                  Required by oro_resolve and oro_install.
+ *   cache          Cache directory. Required by the oro_cache_* calls.
+ *   registry       Registry URL. Defaults to https://registry.npmjs.org/.
+ *   concurrency    How many packages to fetch at once.
+ *   ignoreScripts  Skip install scripts.
+ *   lockfileOnly   Only write package-lock.kdl, without touching
+ *                  node_modules/.
+ *
+ * Calls block until they're done, and are safe to make from multiple
+ * threads at once.
+ */
+
+#ifndef OROGENE_H
+#define OROGENE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum oro_status {
+    ORO_OK = 0,
+    /* The request was NULL, not UTF-8, not valid JSON, or missing a
+     * required field. */
+    ORO_INVALID_ARGUMENT = 1,
+    /* The operation itself failed. */
+    ORO_FAILED = 2,
+    /* Orogene hit a bug and gave up on the call. */
+    ORO_PANIC = 3,
+} oro_status;
+
+/* Orogene's version, like "0.3.23". Statically allocated; don't free it. */
+const char *oro_version(void);
+
+/* Resolves the project's dependency tree without installing anything.
+ * Responds with {"lockfile": "<package-lock.kdl contents>", "packages": N}. */
+oro_status oro_resolve(const char *request, char **response);
+
+/* Makes node_modules/ match package.json, and writes package-lock.kdl.
+ * Responds with {"packages": N, "pruned": N, "extracted": N}. */
+oro_status oro_install(const char *request, char **response);
+
+/* Counts what's in the cache. Responds with {"entries": N, "packages": N,
+ * "contents": N, "contentBytes": N}. */
+oro_status oro_cache_stats(const char *request, char **response);
+
+/* Checks everything in the cache against its hash, removing anything
+ * corrupted. Responds with {"checked": N, "corrupted": ["<path>", ...],
+ * "reclaimedBytes": N}. */
+oro_status oro_cache_verify(const char *request, char **response);
+
+/* Frees a response. Does nothing if it's NULL. */
+void oro_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* OROGENE_H */
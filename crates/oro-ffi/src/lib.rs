@@ -0,0 +1,347 @@
+//! A C ABI for embedding Orogene's resolver and installer. Requests and
+//! responses are JSON strings, so callers don't need to mirror any of
+//! Orogene's types. See `include/orogene.h` for the C declarations.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use miette::Diagnostic;
+use node_maintainer::{NodeMaintainer, NodeMaintainerOptions};
+use oro_common::CorgiManifest;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// How a call went. Whatever it is, the call's response has the details.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OroStatus {
+    Ok = 0,
+    /// The request was null, not UTF-8, not valid JSON, or missing a
+    /// required field.
+    InvalidArgument = 1,
+    /// The operation itself failed.
+    Failed = 2,
+    /// Orogene hit a bug and gave up on the call.
+    Panic = 3,
+}
+
+/// Everything a request can have. Each call only uses some of these.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Request {
+    root: Option<PathBuf>,
+    cache: Option<PathBuf>,
+    registry: Option<String>,
+    concurrency: Option<usize>,
+    ignore_scripts: bool,
+    lockfile_only: bool,
+}
+
+impl Request {
+    fn root(&self) -> Result<&Path, Failure> {
+        self.root
+            .as_deref()
+            .ok_or_else(|| Failure::invalid("`root` is required."))
+    }
+
+    fn cache(&self) -> Result<&Path, Failure> {
+        self.cache
+            .as_deref()
+            .ok_or_else(|| Failure::invalid("`cache` is required."))
+    }
+}
+
+/// Why a call failed, as sent back to the caller.
+#[derive(Debug)]
+struct Failure {
+    status: OroStatus,
+    code: Option<String>,
+    message: String,
+}
+
+impl Failure {
+    fn invalid(message: impl Into<String>) -> Self {
+        Self {
+            status: OroStatus::InvalidArgument,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    fn failed(message: impl Into<String>) -> Self {
+        Self {
+            status: OroStatus::Failed,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    fn from_diagnostic(err: impl Diagnostic) -> Self {
+        Self {
+            status: OroStatus::Failed,
+            code: err.code().map(|code| code.to_string()),
+            message: err.to_string(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "code": self.code,
+            "message": self.message,
+        })
+    }
+}
+
+/// Orogene's version, like `0.3.23`. The string is statically allocated,
+/// and must not be freed.
+#[no_mangle]
+pub extern "C" fn oro_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Resolves the dependency tree of the project at `root`, without
+/// installing anything.
+///
+/// # Safety
+///
+/// `request` must be null or a NUL-terminated string, and `response` must
+/// be a valid pointer to write the response to. The response must be freed
+/// with [`oro_string_free`].
+#[no_mangle]
+pub unsafe extern "C" fn oro_resolve(
+    request: *const c_char,
+    response: *mut *mut c_char,
+) -> OroStatus {
+    call(request, response, |request| {
+        async_std::task::block_on(async {
+            let maintainer = resolve_tree(&request).await?;
+            let lockfile = maintainer
+                .to_kdl()
+                .map_err(Failure::from_diagnostic)?
+                .to_string();
+            Ok(json!({
+                "lockfile": lockfile,
+                "packages": maintainer.package_count(),
+            }))
+        })
+    })
+}
+
+/// Makes `node_modules/` in the project at `root` match its
+/// `package.json`, and writes its `package-lock.kdl`.
+///
+/// # Safety
+///
+/// Same as [`oro_resolve`].
+#[no_mangle]
+pub unsafe extern "C" fn oro_install(
+    request: *const c_char,
+    response: *mut *mut c_char,
+) -> OroStatus {
+    call(request, response, |request| {
+        async_std::task::block_on(async {
+            let maintainer = resolve_tree(&request).await?;
+            let (mut pruned, mut extracted) = (0, 0);
+            if !request.lockfile_only {
+                pruned = maintainer.prune().await.map_err(Failure::from_diagnostic)?;
+                extracted = maintainer
+                    .extract()
+                    .await
+                    .map_err(Failure::from_diagnostic)?;
+                maintainer
+                    .rebuild(request.ignore_scripts)
+                    .await
+                    .map_err(Failure::from_diagnostic)?;
+            }
+            maintainer
+                .write_lockfile(request.root()?.join("package-lock.kdl"))
+                .await
+                .map_err(Failure::from_diagnostic)?;
+            Ok(json!({
+                "packages": maintainer.package_count(),
+                "pruned": pruned,
+                "extracted": extracted,
+            }))
+        })
+    })
+}
+
+/// Counts what's in the cache at `cache`.
+///
+/// # Safety
+///
+/// Same as [`oro_resolve`].
+#[no_mangle]
+pub unsafe extern "C" fn oro_cache_stats(
+    request: *const c_char,
+    response: *mut *mut c_char,
+) -> OroStatus {
+    call(request, response, |request| {
+        let stats = async_std::task::block_on(nassun::cache::stats(request.cache()?))
+            .map_err(Failure::from_diagnostic)?;
+        Ok(json!({
+            "entries": stats.entries,
+            "packages": stats.packages,
+            "contents": stats.contents,
+            "contentBytes": stats.content_bytes,
+        }))
+    })
+}
+
+/// Checks everything in the cache at `cache` against its hash, and removes
+/// anything that's corrupted.
+///
+/// # Safety
+///
+/// Same as [`oro_resolve`].
+#[no_mangle]
+pub unsafe extern "C" fn oro_cache_verify(
+    request: *const c_char,
+    response: *mut *mut c_char,
+) -> OroStatus {
+    call(request, response, |request| {
+        let report = async_std::task::block_on(nassun::cache::verify(request.cache()?))
+            .map_err(Failure::from_diagnostic)?;
+        Ok(json!({
+            "checked": report.contents_checked,
+            "corrupted": report.corrupted,
+            "reclaimedBytes": report.reclaimed_bytes,
+        }))
+    })
+}
+
+/// Frees a response from any other `oro_*` call. Does nothing if `string`
+/// is null.
+///
+/// # Safety
+///
+/// `string` must be null or a response that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn oro_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Parses `request`, runs `op` with it, and writes whatever it returns, or
+/// why it failed, to `response`. Panics are caught here, since unwinding
+/// into C is undefined behavior.
+unsafe fn call(
+    request: *const c_char,
+    response: *mut *mut c_char,
+    op: impl FnOnce(Request) -> Result<Value, Failure>,
+) -> OroStatus {
+    if response.is_null() {
+        return OroStatus::InvalidArgument;
+    }
+    let result = catch_unwind(AssertUnwindSafe(|| op(parse_request(request)?)));
+    let (status, body) = match result {
+        Ok(Ok(value)) => (OroStatus::Ok, value),
+        Ok(Err(failure)) => (failure.status, failure.to_json()),
+        Err(_) => (
+            OroStatus::Panic,
+            json!({
+                "code": null,
+                "message": "Orogene panicked. This is a bug, and should be reported.",
+            }),
+        ),
+    };
+    // JSON escapes NULs, so there can't be any in here.
+    *response = CString::new(body.to_string())
+        .expect("JSON never contains NUL bytes")
+        .into_raw();
+    status
+}
+
+unsafe fn parse_request(request: *const c_char) -> Result<Request, Failure> {
+    if request.is_null() {
+        return Err(Failure::invalid("The request is null."));
+    }
+    let request = CStr::from_ptr(request)
+        .to_str()
+        .map_err(|e| Failure::invalid(format!("The request isn't UTF-8: {e}")))?;
+    serde_json::from_str(request)
+        .map_err(|e| Failure::invalid(format!("The request isn't valid JSON: {e}")))
+}
+
+async fn resolve_tree(request: &Request) -> Result<NodeMaintainer, Failure> {
+    let root = request.root()?;
+    let manifest_path = root.join("package.json");
+    let manifest = async_std::fs::read_to_string(&manifest_path)
+        .await
+        .map_err(|e| Failure::failed(format!("Failed to read {}: {e}", manifest_path.display())))?;
+    let manifest: CorgiManifest = serde_json::from_str(&manifest).map_err(|e| {
+        Failure::failed(format!("Failed to parse {}: {e}", manifest_path.display()))
+    })?;
+    maintainer_options(request)?
+        .resolve_manifest(manifest)
+        .await
+        .map_err(Failure::from_diagnostic)
+}
+
+fn maintainer_options(request: &Request) -> Result<NodeMaintainerOptions, Failure> {
+    let mut opts = NodeMaintainer::builder().root(request.root()?);
+    if let Some(registry) = &request.registry {
+        opts = opts.registry(
+            registry
+                .parse()
+                .map_err(|e| Failure::invalid(format!("Invalid registry URL {registry}: {e}")))?,
+        );
+    }
+    if let Some(cache) = &request.cache {
+        opts = opts.cache(cache);
+    }
+    if let Some(concurrency) = request.concurrency {
+        opts = opts.concurrency(concurrency);
+    }
+    Ok(opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    unsafe fn call_with(
+        f: unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> OroStatus,
+        request: &str,
+    ) -> (OroStatus, Value) {
+        let request = CString::new(request).unwrap();
+        let mut response = std::ptr::null_mut();
+        let status = f(request.as_ptr(), &mut response);
+        let body = serde_json::from_str(CStr::from_ptr(response).to_str().unwrap()).unwrap();
+        oro_string_free(response);
+        (status, body)
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        unsafe {
+            let (status, body) = call_with(oro_resolve, "not json");
+            assert_eq!(status, OroStatus::InvalidArgument);
+            assert!(body["message"].as_str().unwrap().contains("valid JSON"));
+
+            let (status, body) = call_with(oro_install, "{}");
+            assert_eq!(status, OroStatus::InvalidArgument);
+            assert_eq!(body["message"], "`root` is required.");
+
+            let (status, _) = call_with(oro_cache_stats, "{\"root\": \".\"}");
+            assert_eq!(status, OroStatus::InvalidArgument);
+
+            let mut response = std::ptr::null_mut();
+            assert_eq!(
+                oro_resolve(std::ptr::null(), &mut response),
+                OroStatus::InvalidArgument
+            );
+            oro_string_free(response);
+        }
+    }
+
+    #[test]
+    fn version() {
+        let version = unsafe { CStr::from_ptr(oro_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,50 @@
+# Messages printed by the `oro` command line. Every locale needs to translate
+# all of these. Diagnostics aren't listed here: their English text lives with
+# their definitions, and translations look them up by their code, in
+# `diagnostics.ftl`. See `oro_i18n::localize`.
+
+## Printed after an error.
+
+debug-log-location = A complete debug log of this run can be found in:
+
+## `--dry-run`
+
+dry-run-remove = remove  { $path }
+dry-run-extract = extract { $name } ({ $resolved }) to { $path }
+dry-run-link = link    { $to } -> { $from }
+dry-run-summary = { $removed } to remove, { $extracted } to extract, { $linked } to link.
+
+## `oro view`
+
+view-summary = { $name }@{ $version } | { $license } | deps: { $deps } | releases: { $releases }
+view-deprecated = DEPRECATED
+view-keywords = keywords: { $keywords }
+view-bins = bins: { $bins }
+view-provenance = provenance: { $status }
+view-provenance-verified = verified
+view-provenance-none = none
+view-provenance-invalid = invalid
+view-dependencies = dependencies:
+view-more-dependencies = (...and { $count } more)
+view-maintainers = maintainers:
+view-published = published { $when } by { $user }
+
+## `oro inspect`
+
+inspect-resolved = resolved: { $resolved }
+inspect-integrity = integrity: { $integrity }
+inspect-size = size: { $size } ({ $unpacked } unpacked)
+inspect-files = files ({ $count }):
+inspect-shebangs = shebangs:
+inspect-install-scripts = install scripts:
+inspect-install-scripts-none = none
+
+## `oro audit`
+
+audit-vulnerability = { $name }@{ $version } ({ $severity }): { $title }
+audit-installed-at = installed at: { $path }
+audit-violation = { $name }@{ $version } { $reason }. ({ $rule } policy)
+audit-pulled-in-by = pulled in by: { $path }
+audit-clean = No known vulnerabilities or policy violations found.
+audit-sarif-written = Wrote SARIF results to { $path }.
+audit-advisories-unsupported = { $url } doesn't serve security advisories, so only the policy was checked.
@@ -0,0 +1,53 @@
+# Spanish translations of diagnostics, by code. Diagnostics that aren't
+# listed here are printed in English. See `oro_i18n::localize`.
+
+oro-remove-invalid_package_name = { $name } no es un nombre de paquete válido. A `oro remove` solo se le deben pasar nombres de paquetes, pero se le pasó un especificador que no es de NPM o un nombre de paquete no válido.
+    .help = Usa el nombre del paquete tal como aparece en tu package.json.
+oro-run-missing_script = El paquete `{ $package }` no tiene ningún script llamado `{ $script }`.
+    .help = Añade una entrada `{ $script }` al campo `scripts` del package.json de este paquete.
+oro-run-dependency_cycle = Se detectó un ciclo de dependencias entre scripts: { $cycle }
+    .help = Quita una de las entradas `dependsOn` de este ciclo.
+oro-run-unknown_workspace = No se encontró ningún paquete del workspace llamado `{ $workspace }`.
+    .help = Asegúrate de que el paquete aparezca en el campo `workspaces` del package.json raíz.
+oro-run-workspace_scripts_failed =
+    `{ $script }` falló en { $count ->
+        [one] un paquete
+       *[other] { $count } paquetes
+    } del workspace.
+    .help = Revisa la salida de los scripts que fallaron, más arriba.
+oro-migrate-diverged =
+    { $count ->
+        [one] Un paquete se resolvió
+       *[other] { $count } paquetes se resolvieron
+    } a versiones distintas de las del lockfile anterior.
+    .help = Revisa las diferencias de arriba y vuelve a ejecutar con `--force` para migrar de todos modos.
+oro-apply-toolchain_mismatch =
+    Este proyecto espera otras versiones de sus herramientas:
+    { $problems }
+    .help = Cambia a las versiones que pide el proyecto, o usa `--no-toolchain-check` para omitir esta comprobación.
+oro-apply-no_global_prefix = No se pudo determinar dónde instalar los paquetes globales.
+    .help = Pasa `--global-prefix`, define `NPM_CONFIG_PREFIX`, o asegúrate de que `node` esté en tu `PATH`.
+oro-tls-missing_client_key = No se indicó ninguna clave de cliente para el certificado de `{ $host }`.
+    .help = Pasa también `--registry-key { $host }=/ruta/a/la/clave.pem`.
+oro-tls-missing_client_cert = No se indicó ningún certificado de cliente para la clave de `{ $host }`.
+    .help = Pasa también `--registry-cert { $host }=/ruta/al/certificado.pem`.
+oro-credentials-unknown_field = Campo de credenciales desconocido `{ $field }` para `{ $host }`.
+    .help = Los campos admitidos son `token`, `username`, `password`, `auth`, `s3-access-key-id`, `s3-secret-access-key`, `s3-session-token` y `s3-region`.
+oro-credentials-incomplete = Credenciales incompletas para `{ $host }`.
+    .help = Indica un `token`, un `username` y un `password`, un `auth`, un `s3-access-key-id` y un `s3-secret-access-key`, o un `helper`.
+oro-daemon-already_running = Ya hay un daemon escuchando en { $socket }.
+    .help = Envíale primero una petición `shutdown`, o pasa `--socket` para escuchar en otro lugar.
+oro-daemon-unsupported = `oro daemon` todavía no funciona en esta plataforma.
+oro-reporter-unknown = Reporter desconocido: `{ $reporter }`.
+    .help = Usa `default` o `ndjson`.
+oro-completions-unknown_shell = No se pueden generar completados para el shell desconocido `{ $shell }`.
+    .help = Usa `bash`, `zsh`, `fish` o `powershell`.
+oro-audit-failed =
+    Se { $vulnerabilities ->
+        [one] encontró un paquete vulnerable
+       *[other] encontraron { $vulnerabilities } paquetes vulnerables
+    } y { $violations ->
+        [one] una violación
+       *[other] { $violations } violaciones
+    } de la política.
+    .help = Actualiza o reemplaza los paquetes de la lista de arriba, o cambia el `oro-policy.kdl` del proyecto.
@@ -0,0 +1,48 @@
+# Spanish translations of `../en-US/oro.ftl`. Every message there needs to be
+# translated here too.
+
+## Printed after an error.
+
+debug-log-location = Hay un registro de depuración completo de esta ejecución en:
+
+## `--dry-run`
+
+dry-run-remove = eliminar { $path }
+dry-run-extract = extraer  { $name } ({ $resolved }) en { $path }
+dry-run-link = enlazar  { $to } -> { $from }
+dry-run-summary = { $removed } por eliminar, { $extracted } por extraer, { $linked } por enlazar.
+
+## `oro view`
+
+view-summary = { $name }@{ $version } | { $license } | deps: { $deps } | versiones: { $releases }
+view-deprecated = OBSOLETO
+view-keywords = palabras clave: { $keywords }
+view-bins = ejecutables: { $bins }
+view-provenance = procedencia: { $status }
+view-provenance-verified = verificada
+view-provenance-none = ninguna
+view-provenance-invalid = no válida
+view-dependencies = dependencias:
+view-more-dependencies = (...y { $count } más)
+view-maintainers = mantenedores:
+view-published = publicado { $when } por { $user }
+
+## `oro inspect`
+
+inspect-resolved = resuelto: { $resolved }
+inspect-integrity = integridad: { $integrity }
+inspect-size = tamaño: { $size } ({ $unpacked } descomprimido)
+inspect-files = archivos ({ $count }):
+inspect-shebangs = shebangs:
+inspect-install-scripts = scripts de instalación:
+inspect-install-scripts-none = ninguno
+
+## `oro audit`
+
+audit-vulnerability = { $name }@{ $version } ({ $severity }): { $title }
+audit-installed-at = instalado en: { $path }
+audit-violation = { $name }@{ $version } { $reason }. (política de { $rule })
+audit-pulled-in-by = incluido por: { $path }
+audit-clean = No se encontraron vulnerabilidades conocidas ni violaciones de la política.
+audit-sarif-written = Se escribieron los resultados SARIF en { $path }.
+audit-advisories-unsupported = { $url } no ofrece avisos de seguridad, así que solo se comprobó la política.
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use miette::{Diagnostic, LabeledSpan, Report, Severity, SourceCode};
+
+use crate::{localizer, FluentArgs, DEFAULT_LOCALE};
+
+/// Translates `report`'s message and help, looking them up by its code, with
+/// `::` replaced by `-`. `oro::run::missing_script` is translated by the
+/// `oro-run-missing_script` message, for example, and its help by that
+/// message's `.help` attribute. `args` fills in their placeholders.
+///
+/// Everything else about the report is kept. It's returned as-is if the
+/// current locale doesn't translate it, or if the translation needs
+/// placeholders `args` doesn't have.
+pub fn localize(report: Report, args: Option<&FluentArgs>) -> Report {
+    let localizer = localizer();
+    if localizer.locale == DEFAULT_LOCALE {
+        return report;
+    }
+    let Some(id) = report
+        .code()
+        .map(|code| code.to_string().replace("::", "-"))
+    else {
+        return report;
+    };
+    let Some(message) = localizer.format(&id, None, args) else {
+        return report;
+    };
+    let help = localizer.format(&id, Some("help"), args);
+    Report::new(Localized {
+        report,
+        message,
+        help,
+    })
+}
+
+struct Localized {
+    report: Report,
+    message: String,
+    help: Option<String>,
+}
+
+impl fmt::Debug for Localized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Localized")
+            .field("report", &self.report)
+            .field("message", &self.message)
+            .field("help", &self.help)
+            .finish()
+    }
+}
+
+impl fmt::Display for Localized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Localized {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.report.source()
+    }
+}
+
+impl Diagnostic for Localized {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.report.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match &self.help {
+            Some(help) => Some(Box::new(help)),
+            None => self.report.help(),
+        }
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.report.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.report.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.report.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.report.diagnostic_source()
+    }
+}
@@ -0,0 +1,14 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum OroI18nError {
+    /// The `locale` option asked for a language there's no translation for.
+    #[error("There's no translation for locale `{locale}`.")]
+    #[diagnostic(
+        code(oro_i18n::unsupported_locale),
+        url(docsrs),
+        help("Available locales are: {available}.")
+    )]
+    UnsupportedLocale { locale: String, available: String },
+}
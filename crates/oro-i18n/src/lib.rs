@@ -0,0 +1,266 @@
+//! Localized messages for Orogene's command line output and diagnostics,
+//! using [Fluent](https://projectfluent.org).
+//!
+//! Translations live in `locales/<locale>/oro.ftl`, and are compiled into the
+//! binary. Every locale translates all of the messages in the English
+//! `oro.ftl`. Translations of diagnostics go in `diagnostics.ftl` next to it,
+//! and any diagnostic that isn't translated is printed in English.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::FluentResource;
+use once_cell::sync::OnceCell;
+use unic_langid::LanguageIdentifier;
+
+pub use diagnostic::localize;
+pub use error::OroI18nError;
+pub use fluent_bundle::{FluentArgs, FluentValue};
+
+mod diagnostic;
+mod error;
+
+/// The locale everything is written in to begin with, and falls back to.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Every locale there's a translation for, along with its messages and its
+/// diagnostics, if it translates any. English diagnostics live with their
+/// definitions.
+const RESOURCES: &[(&str, &str, Option<&str>)] = &[
+    (
+        DEFAULT_LOCALE,
+        include_str!("../locales/en-US/oro.ftl"),
+        None,
+    ),
+    (
+        "es",
+        include_str!("../locales/es/oro.ftl"),
+        Some(include_str!("../locales/es/diagnostics.ftl")),
+    ),
+];
+
+static LOCALIZER: OnceCell<Localizer> = OnceCell::new();
+
+/// Picks the locale to translate messages into. `requested` comes from the
+/// `locale` option, and falls back to the `LC_ALL`, `LC_MESSAGES`, and
+/// `LANG` environment variables, then to English.
+///
+/// Asking for a locale that there's no translation for is an error, but
+/// unknown locales from the environment are quietly ignored. Only the first
+/// call has any effect.
+pub fn init(requested: Option<&str>) -> Result<(), OroI18nError> {
+    let locale = match requested {
+        Some(requested) => requested
+            .parse::<LanguageIdentifier>()
+            .ok()
+            .and_then(|langid| negotiate(&langid))
+            .ok_or_else(|| OroI18nError::UnsupportedLocale {
+                locale: requested.into(),
+                available: available_locales().collect::<Vec<_>>().join(", "),
+            })?,
+        None => env_locale()
+            .and_then(|langid| negotiate(&langid))
+            .unwrap_or(DEFAULT_LOCALE),
+    };
+    let _ = LOCALIZER.set(Localizer::new(locale));
+    Ok(())
+}
+
+/// Every locale there's a translation for.
+pub fn available_locales() -> impl Iterator<Item = &'static str> {
+    RESOURCES.iter().map(|(locale, ..)| *locale)
+}
+
+/// The locale messages are being translated into.
+pub fn current_locale() -> &'static str {
+    localizer().locale
+}
+
+/// Translates the message `id`, falling back to English, and then to `id`
+/// itself if there's no such message at all. Use [`t!`] instead of calling
+/// this directly.
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let localizer = localizer();
+    localizer
+        .format(id, None, args)
+        .or_else(|| localizer.fallback.as_ref()?.format(id, None, args))
+        .unwrap_or_else(|| id.into())
+}
+
+/// Translates a message, filling in its `$placeholders` with the given
+/// values.
+///
+/// ```
+/// # use oro_i18n::t;
+/// let line = t!("dry-run-summary", removed = 1, extracted = 2, linked = 0);
+/// assert_eq!(line, "1 to remove, 2 to extract, 0 to link.");
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($id:literal $(,)?) => {
+        $crate::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::message($id, Some(&args))
+    }};
+}
+
+fn localizer() -> &'static Localizer {
+    LOCALIZER.get_or_init(|| Localizer::new(DEFAULT_LOCALE))
+}
+
+/// Finds the translation for `requested`: either one for that exact locale,
+/// or one for the same language.
+fn negotiate(requested: &LanguageIdentifier) -> Option<&'static str> {
+    let locales = available_locales()
+        .map(|locale| (locale, locale.parse::<LanguageIdentifier>().unwrap()))
+        .collect::<Vec<_>>();
+    locales
+        .iter()
+        .find(|(_, langid)| langid == requested)
+        .or_else(|| {
+            locales
+                .iter()
+                .find(|(_, langid)| langid.language == requested.language)
+        })
+        .map(|(locale, _)| *locale)
+}
+
+/// The locale set in the environment. POSIX locales look like
+/// `es_ES.UTF-8@euro`, so the encoding and modifier are dropped.
+fn env_locale() -> Option<LanguageIdentifier> {
+    let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    value
+        .split(['.', '@'])
+        .next()?
+        .replace('_', "-")
+        .parse()
+        .ok()
+}
+
+struct Localizer {
+    locale: &'static str,
+    bundle: FluentBundle<FluentResource>,
+    /// English, for anything missing from `bundle`.
+    fallback: Option<Box<Localizer>>,
+}
+
+impl Localizer {
+    fn new(locale: &'static str) -> Self {
+        let (_, messages, diagnostics) = RESOURCES
+            .iter()
+            .find(|(name, ..)| *name == locale)
+            .expect("only known locales are loaded");
+        let langid = locale.parse().expect("known locales are valid");
+        let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+        // Unicode isolation marks around placeholders just show up as
+        // garbage in most terminals.
+        bundle.set_use_isolating(false);
+        for source in std::iter::once(messages).chain(diagnostics) {
+            let resource =
+                FluentResource::try_new(source.to_string()).unwrap_or_else(|(_, errors)| {
+                    panic!("invalid translations for {locale}: {errors:?}")
+                });
+            bundle
+                .add_resource(resource)
+                .unwrap_or_else(|errors| panic!("invalid translations for {locale}: {errors:?}"));
+        }
+        Self {
+            locale,
+            bundle,
+            fallback: (locale != DEFAULT_LOCALE).then(|| Box::new(Localizer::new(DEFAULT_LOCALE))),
+        }
+    }
+
+    /// Formats message `id`, or its `attribute`. Returns `None` if there's
+    /// no such message, or if it couldn't be formatted, like when it needs a
+    /// placeholder that wasn't provided.
+    fn format(
+        &self,
+        id: &str,
+        attribute: Option<&str>,
+        args: Option<&FluentArgs>,
+    ) -> Option<String> {
+        let message = self.bundle.get_message(id)?;
+        let pattern = match attribute {
+            Some(attribute) => message.get_attribute(attribute)?.value(),
+            None => message.value()?,
+        };
+        let mut errors = Vec::new();
+        let formatted = self.bundle.format_pattern(pattern, args, &mut errors);
+        errors.is_empty().then(|| formatted.into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn every_translation_parses() {
+        for locale in available_locales() {
+            Localizer::new(locale);
+        }
+    }
+
+    #[test]
+    fn every_locale_has_every_message() {
+        // Messages start at the beginning of a line. Comments, attributes,
+        // and multiline values don't.
+        fn ids(source: &str) -> BTreeSet<&str> {
+            source
+                .lines()
+                .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+                .filter(|id| {
+                    id.starts_with(|c: char| c.is_ascii_alphabetic())
+                        && id
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                })
+                .collect()
+        }
+        let (_, english, _) = RESOURCES[0];
+        assert!(!ids(english).is_empty());
+        for (locale, messages, _) in RESOURCES {
+            assert_eq!(ids(messages), ids(english), "messages for {locale}");
+        }
+    }
+
+    #[test]
+    fn negotiates_by_language() {
+        assert_eq!(negotiate(&"es-MX".parse().unwrap()), Some("es"));
+        assert_eq!(negotiate(&"en-GB".parse().unwrap()), Some("en-US"));
+        assert_eq!(negotiate(&"tlh".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn formats_placeholders() {
+        let spanish = Localizer::new("es");
+        let mut args = FluentArgs::new();
+        args.set("count", 3);
+        assert_eq!(
+            spanish.format("view-more-dependencies", None, Some(&args)),
+            Some("(...y 3 más)".into())
+        );
+    }
+
+    #[test]
+    fn unknown_messages_use_their_id() {
+        assert_eq!(
+            Localizer::new("es").format("no-such-message", None, None),
+            None
+        );
+        assert_eq!(message("no-such-message", None), "no-such-message");
+    }
+
+    #[test]
+    fn missing_placeholders_are_not_formatted() {
+        let english = Localizer::new(DEFAULT_LOCALE);
+        assert_eq!(english.format("view-more-dependencies", None, None), None);
+    }
+}
@@ -0,0 +1,297 @@
+//! Node.js bindings for Orogene's resolver and installer, so Node-based
+//! tools can resolve, apply, add, and remove dependencies in-process.
+//! Published to npm as `@orogene/node`.
+
+use std::path::{Path, PathBuf};
+
+use miette::Diagnostic;
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use nassun::{NassunOpts, PackageResolution};
+use node_maintainer::{InstallEvent, NodeMaintainer, NodeMaintainerOptions};
+use oro_common::CorgiManifest;
+use oro_package_spec::{PackageSpec, VersionSpec};
+use oro_pretty_json::Formatted;
+use url::Url;
+
+type ProgressCallback = ThreadsafeFunction<ProgressEvent, ErrorStrategy::Fatal>;
+
+/// Options shared by every function. Anything left out uses the same
+/// default as `oro` does.
+#[napi(object)]
+#[derive(Default)]
+pub struct Options {
+    /// Registry to fetch packages from.
+    pub registry: Option<String>,
+    /// How many packages to fetch at once.
+    pub concurrency: Option<u32>,
+    /// Skip running install scripts.
+    pub ignore_scripts: Option<bool>,
+    /// Only resolve and write `package-lock.kdl`, without touching
+    /// `node_modules/`.
+    pub lockfile_only: Option<bool>,
+    /// Save added packages as `devDependencies`.
+    pub save_dev: Option<bool>,
+    /// Prefix for the versions of added registry packages, like `^` or
+    /// `~`. Defaults to `^`.
+    pub save_prefix: Option<String>,
+}
+
+/// Something that happened during an install.
+#[napi(object)]
+pub struct ProgressEvent {
+    /// One of `resolved`, `pruned`, `extracted`, `scriptStarted`, or
+    /// `scriptLine`.
+    pub kind: String,
+    /// Name of the package the event is about, if any.
+    pub package: Option<String>,
+    /// The pruned path, the name of the script that started, or the line
+    /// a script printed.
+    pub detail: Option<String>,
+}
+
+/// What an install did.
+#[napi(object)]
+pub struct ApplyResult {
+    /// Packages in the resolved tree.
+    pub packages: u32,
+    /// Entries removed from `node_modules/`.
+    pub pruned: u32,
+    /// Packages extracted into `node_modules/`.
+    pub extracted: u32,
+}
+
+/// Resolves the project at `root`, and returns the contents of its
+/// `package-lock.kdl`, without writing anything.
+#[napi(ts_args_type = "root: string, options?: Options")]
+pub async fn resolve(root: String, options: Option<Options>) -> Result<String> {
+    let root = PathBuf::from(root);
+    let options = options.unwrap_or_default();
+    let manifest = read_manifest(&root)?;
+    let maintainer = maintainer_options(&root, &options, None)?
+        .resolve_manifest(corgi(&manifest)?)
+        .await
+        .map_err(to_napi_error)?;
+    Ok(maintainer.to_kdl().map_err(to_napi_error)?.to_string())
+}
+
+/// Makes `node_modules/` match the project's `package.json`, and writes
+/// `package-lock.kdl`.
+#[napi(
+    ts_args_type = "root: string, options?: Options, onProgress?: (event: ProgressEvent) => void"
+)]
+pub async fn apply(
+    root: String,
+    options: Option<Options>,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ApplyResult> {
+    let root = PathBuf::from(root);
+    let options = options.unwrap_or_default();
+    let manifest = read_manifest(&root)?;
+    apply_manifest(&root, &manifest, &options, on_progress).await
+}
+
+/// Adds `specs` to the project's dependencies, applies the change, and
+/// saves `package.json`.
+#[napi(
+    ts_args_type = "root: string, specs: string[], options?: Options, onProgress?: (event: ProgressEvent) => void"
+)]
+pub async fn add(
+    root: String,
+    specs: Vec<String>,
+    options: Option<Options>,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ApplyResult> {
+    let root = PathBuf::from(root);
+    let options = options.unwrap_or_default();
+    let mut manifest = read_manifest(&root)?;
+    let nassun = nassun_opts(&root, &options)?.build();
+    let deps = if options.save_dev.unwrap_or(false) {
+        "devDependencies"
+    } else {
+        "dependencies"
+    };
+    let prefix = options.save_prefix.as_deref().unwrap_or("^");
+    for spec in &specs {
+        let package = nassun.resolve(spec).await.map_err(to_napi_error)?;
+        let requested: PackageSpec = spec.parse().map_err(to_napi_error)?;
+        let saved = match (requested.target(), package.resolved()) {
+            (PackageSpec::Npm { .. }, PackageResolution::Npm { version, .. }) => {
+                let mut from = package.from().clone();
+                if let PackageSpec::Npm { requested, .. } = from.target_mut() {
+                    *requested = Some(VersionSpec::Tag(format!("{prefix}{version}")));
+                }
+                from.requested()
+            }
+            (PackageSpec::Git(info), _) => info.to_string(),
+            (PackageSpec::Url { url }, _) => url.to_string(),
+            (PackageSpec::Link { path }, _) => format!("link:{}", path.to_string_lossy()),
+            (PackageSpec::Dir { path }, _) => path.to_string_lossy().to_string(),
+            _ => package.from().requested(),
+        };
+        let name = package.name();
+        remove_dependency(&mut manifest, name);
+        if !manifest.value[deps].is_object() {
+            manifest.value[deps] = serde_json::Value::Object(Default::default());
+        }
+        manifest.value[deps][name] = serde_json::Value::String(saved);
+    }
+    let result = apply_manifest(&root, &manifest, &options, on_progress).await?;
+    write_manifest(&root, &manifest)?;
+    Ok(result)
+}
+
+/// Removes the packages named `names` from every kind of dependency in
+/// the project, applies the change, and saves `package.json`.
+#[napi(
+    ts_args_type = "root: string, names: string[], options?: Options, onProgress?: (event: ProgressEvent) => void"
+)]
+pub async fn remove(
+    root: String,
+    names: Vec<String>,
+    options: Option<Options>,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ApplyResult> {
+    let root = PathBuf::from(root);
+    let options = options.unwrap_or_default();
+    let mut manifest = read_manifest(&root)?;
+    for name in &names {
+        remove_dependency(&mut manifest, name);
+    }
+    let result = apply_manifest(&root, &manifest, &options, on_progress).await?;
+    write_manifest(&root, &manifest)?;
+    Ok(result)
+}
+
+async fn apply_manifest(
+    root: &Path,
+    manifest: &Formatted,
+    options: &Options,
+    on_progress: Option<ProgressCallback>,
+) -> Result<ApplyResult> {
+    let maintainer: NodeMaintainer = maintainer_options(root, options, on_progress)?
+        .resolve_manifest(corgi(manifest)?)
+        .await
+        .map_err(to_napi_error)?;
+    let mut result = ApplyResult {
+        packages: maintainer.package_count() as u32,
+        pruned: 0,
+        extracted: 0,
+    };
+    if !options.lockfile_only.unwrap_or(false) {
+        result.pruned = maintainer.prune().await.map_err(to_napi_error)? as u32;
+        result.extracted = maintainer.extract().await.map_err(to_napi_error)? as u32;
+        maintainer
+            .rebuild(options.ignore_scripts.unwrap_or(false))
+            .await
+            .map_err(to_napi_error)?;
+    }
+    maintainer
+        .write_lockfile(root.join("package-lock.kdl"))
+        .await
+        .map_err(to_napi_error)?;
+    Ok(result)
+}
+
+fn nassun_opts(root: &Path, options: &Options) -> Result<NassunOpts> {
+    let mut opts = NassunOpts::new().base_dir(root);
+    if let Some(registry) = &options.registry {
+        opts = opts.registry(parse_registry(registry)?);
+    }
+    Ok(opts)
+}
+
+fn maintainer_options(
+    root: &Path,
+    options: &Options,
+    on_progress: Option<ProgressCallback>,
+) -> Result<NodeMaintainerOptions> {
+    let mut opts = NodeMaintainer::builder().root(root);
+    if let Some(registry) = &options.registry {
+        opts = opts.registry(parse_registry(registry)?);
+    }
+    if let Some(concurrency) = options.concurrency {
+        opts = opts.concurrency(concurrency as usize);
+    }
+    if let Some(on_progress) = on_progress {
+        opts = opts.on_event(move |event| {
+            if let Some(event) = progress_event(event) {
+                on_progress.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        });
+    }
+    Ok(opts)
+}
+
+/// The [`ProgressEvent`] to send to JavaScript for `event`. Events that
+/// fire many times per package, like byte counts, aren't sent, since
+/// crossing into JavaScript for each one would slow installs down.
+fn progress_event(event: &InstallEvent<'_>) -> Option<ProgressEvent> {
+    let (kind, package, detail) = match event {
+        InstallEvent::Resolved(package) => ("resolved", Some(package.name()), None),
+        InstallEvent::Pruned(path) => ("pruned", None, Some(path.display().to_string())),
+        InstallEvent::Extracted(package) => ("extracted", Some(package.name()), None),
+        InstallEvent::ScriptStarted { package, event } => (
+            "scriptStarted",
+            Some(package.name()),
+            Some(event.to_string()),
+        ),
+        InstallEvent::ScriptLine(line) => ("scriptLine", None, Some(line.to_string())),
+        _ => return None,
+    };
+    Some(ProgressEvent {
+        kind: kind.into(),
+        package: package.map(String::from),
+        detail,
+    })
+}
+
+fn read_manifest(root: &Path) -> Result<Formatted> {
+    let path = root.join("package.json");
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| Error::from_reason(format!("Failed to read {}: {e}", path.display())))?;
+    oro_pretty_json::from_str(json)
+        .map_err(|e| Error::from_reason(format!("Failed to parse {}: {e}", path.display())))
+}
+
+fn write_manifest(root: &Path, manifest: &Formatted) -> Result<()> {
+    let path = root.join("package.json");
+    let json = oro_pretty_json::to_string_pretty(manifest)
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+    std::fs::write(&path, json)
+        .map_err(|e| Error::from_reason(format!("Failed to write {}: {e}", path.display())))
+}
+
+fn corgi(manifest: &Formatted) -> Result<CorgiManifest> {
+    serde_json::from_value(manifest.value.clone())
+        .map_err(|e| Error::from_reason(format!("Invalid package.json: {e}")))
+}
+
+fn remove_dependency(manifest: &mut Formatted, name: &str) {
+    for deps in [
+        "dependencies",
+        "devDependencies",
+        "optionalDependencies",
+        "peerDependencies",
+    ] {
+        if let Some(deps) = manifest.value[deps].as_object_mut() {
+            deps.remove(name);
+        }
+    }
+}
+
+fn parse_registry(registry: &str) -> Result<Url> {
+    registry
+        .parse()
+        .map_err(|e| Error::from_reason(format!("Invalid registry URL {registry}: {e}")))
+}
+
+/// Turns a diagnostic into a JavaScript error, with its code in front of
+/// its message, like `oro` prints them.
+fn to_napi_error(e: impl Diagnostic) -> Error {
+    match e.code() {
+        Some(code) => Error::from_reason(format!("{code}: {e}")),
+        None => Error::from_reason(e.to_string()),
+    }
+}
@@ -59,22 +59,29 @@ impl fmt::Display for GitHost {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GitInfo {
     Hosted {
+        /// Empty for gists referred to by their id alone.
         owner: String,
         repo: String,
         host: GitHost,
         committish: Option<String>,
         semver: Option<Range>,
+        /// Subdirectory of the repository the package lives in.
+        path: Option<String>,
         requested: Option<String>,
     },
     Url {
         url: Url,
         committish: Option<String>,
         semver: Option<Range>,
+        /// Subdirectory of the repository the package lives in.
+        path: Option<String>,
     },
     Ssh {
         ssh: String,
         committish: Option<String>,
         semver: Option<Range>,
+        /// Subdirectory of the repository the package lives in.
+        path: Option<String>,
     },
 }
 
@@ -97,6 +104,17 @@ impl GitInfo {
         }
     }
 
+    /// Subdirectory of the repository the package lives in, if any. Used
+    /// for packages inside git monorepos.
+    pub fn path(&self) -> Option<&str> {
+        use GitInfo::*;
+        match self {
+            Hosted { path, .. } => path.as_deref(),
+            Url { path, .. } => path.as_deref(),
+            Ssh { path, .. } => path.as_deref(),
+        }
+    }
+
     pub fn ssh(&self) -> Option<String> {
         use GitHost::*;
         use GitInfo::*;
@@ -109,9 +127,9 @@ impl GitInfo {
                 ..
             } => Some(match host {
                 GitHub => format!("git@github.com:{owner}/{repo}.git"),
-                Gist => format!("git@gist.github.com:/{repo}"),
+                Gist => format!("git@gist.github.com:{repo}.git"),
                 GitLab => format!("git@gitlab.com:{owner}/{repo}.git"),
-                Bitbucket => format!("git@bitbucket.com:{owner}/{repo}"),
+                Bitbucket => format!("git@bitbucket.org:{owner}/{repo}.git"),
             })
             .map(|url| url.parse().expect("URL failed to parse")),
         }
@@ -131,7 +149,7 @@ impl GitInfo {
                 GitHub => format!("https://github.com/{owner}/{repo}.git"),
                 Gist => format!("https://gist.github.com/{repo}.git"),
                 GitLab => format!("https://gitlab.com/{owner}/{repo}.git"),
-                Bitbucket => format!("https://bitbucket.com/{owner}/{repo}.git"),
+                Bitbucket => format!("https://bitbucket.org/{owner}/{repo}.git"),
             })
             .map(|url| url.parse().expect("URL failed to parse")),
         }
@@ -151,10 +169,10 @@ impl GitInfo {
             } => committish
                 .as_ref()
                 .map(|commit| match host {
-                    GitHub => format!("https://codeload.github.com/{owner}/{repo}/tag.gz/{commit}"),
+                    GitHub => format!("https://codeload.github.com/{owner}/{repo}/tar.gz/{commit}"),
                     Gist => format!("https://codeload.github.com/gist/{repo}/tar.gz/{commit}"),
                     GitLab => format!(
-                        "https://gitlan.com/{owner}/{repo}/repository/archive.tar.gz?ref={commit}"
+                        "https://gitlab.com/{owner}/{repo}/-/archive/{commit}/{repo}-{commit}.tar.gz"
                     ),
                     Bitbucket => {
                         format!("https://bitbucket.org/{owner}/{repo}/get/{commit}.tar.gz")
@@ -173,28 +191,22 @@ impl fmt::Display for GitInfo {
                 url,
                 committish,
                 semver,
+                path,
             } => {
                 if url.scheme() != "git" {
                     write!(f, "git+")?;
                 }
                 write!(f, "{url}")?;
-                if let Some(comm) = committish {
-                    write!(f, "#{comm}")?;
-                } else if let Some(semver) = semver {
-                    write!(f, "#semver:{semver}")?;
-                }
+                write_fragment(f, committish, semver, path)?;
             }
             Ssh {
                 ssh,
                 committish,
                 semver,
+                path,
             } => {
                 write!(f, "git+ssh://{ssh}")?;
-                if let Some(comm) = committish {
-                    write!(f, "#{comm}")?;
-                } else if let Some(semver) = semver {
-                    write!(f, "#semver:{semver}")?;
-                }
+                write_fragment(f, committish, semver, path)?;
             }
             Hosted {
                 requested,
@@ -203,27 +215,46 @@ impl fmt::Display for GitInfo {
                 host,
                 committish,
                 semver,
+                path,
             } => {
                 if let Some(requested) = requested {
                     if !requested.starts_with("git://") {
                         write!(f, "git+")?;
                     }
                     write!(f, "{requested}")?;
+                } else if owner.is_empty() {
+                    write!(f, "{host}:{repo}")?;
                 } else {
                     write!(f, "{host}:{owner}/{repo}")?;
                 }
 
-                if let Some(comm) = committish {
-                    write!(f, "#{comm}")?;
-                } else if let Some(semver) = semver {
-                    write!(f, "#semver:{semver}")?;
-                }
+                write_fragment(f, committish, semver, path)?;
             }
         }
         Ok(())
     }
 }
 
+fn write_fragment(
+    f: &mut fmt::Formatter<'_>,
+    committish: &Option<String>,
+    semver: &Option<Range>,
+    path: &Option<String>,
+) -> fmt::Result {
+    let mut sep = "#";
+    if let Some(comm) = committish {
+        write!(f, "{sep}{comm}")?;
+        sep = "::";
+    } else if let Some(semver) = semver {
+        write!(f, "{sep}semver:{semver}")?;
+        sep = "::";
+    }
+    if let Some(path) = path {
+        write!(f, "{sep}path:{path}")?;
+    }
+    Ok(())
+}
+
 impl FromStr for GitInfo {
     type Err = PackageSpecError;
 
@@ -270,6 +301,7 @@ mod tests {
             url: "https://foo.com/hello.git".parse().unwrap(),
             committish: Some("deadbeef".into()),
             semver: None,
+            path: None,
         };
         let parsed_url: GitInfo = "git+https://foo.com/hello.git#deadbeef".parse().unwrap();
         assert_eq!(parsed_url, info_url);
@@ -278,6 +310,7 @@ mod tests {
             ssh: "git@foo.com:here.git".into(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
         };
         let parsed_ssh: GitInfo = "git+ssh://git@foo.com:here.git#semver:>=1.2.3 <2.0.0-0"
             .parse()
@@ -290,6 +323,7 @@ mod tests {
             host: GitHost::GitHub,
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         };
         let parsed_hosted: GitInfo = "github:foo/bar".parse().unwrap();
@@ -302,6 +336,7 @@ mod tests {
             url: "https://foo.com/hello.git".parse().unwrap(),
             committish: Some("deadbeef".into()),
             semver: None,
+            path: None,
         };
         assert_eq!(
             String::from("git+https://foo.com/hello.git#deadbeef"),
@@ -311,6 +346,7 @@ mod tests {
             url: "git://foo.org/goodbye.git".parse().unwrap(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
         };
         assert_eq!(
             String::from("git://foo.org/goodbye.git#semver:>=1.2.3 <2.0.0-0"),
@@ -324,6 +360,7 @@ mod tests {
             ssh: "git@foo.com:here.git".into(),
             committish: Some("deadbeef".into()),
             semver: None,
+            path: None,
         };
         assert_eq!(
             String::from("git+ssh://git@foo.com:here.git#deadbeef"),
@@ -333,6 +370,7 @@ mod tests {
             ssh: "git@foo.com:here.git".into(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
         };
         assert_eq!(
             String::from("git+ssh://git@foo.com:here.git#semver:>=1.2.3 <2.0.0-0"),
@@ -348,6 +386,7 @@ mod tests {
             host: GitHost::GitHub,
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         };
         assert_eq!(String::from("github:foo/bar"), format!("{info}"));
@@ -357,6 +396,7 @@ mod tests {
             host: GitHost::GitHub,
             committish: Some("deadbeef".into()),
             semver: None,
+            path: None,
             requested: Some("https://github.com/foo/bar.git".into()),
         };
         assert_eq!(
@@ -369,6 +409,7 @@ mod tests {
             host: GitHost::GitHub,
             committish: Some("deadbeef".into()),
             semver: None,
+            path: None,
             requested: Some("git://gitlab.com/foo/bar.git".into()),
         };
         assert_eq!(
@@ -376,4 +417,81 @@ mod tests {
             format!("{info}")
         );
     }
+
+    #[test]
+    fn display_gist_id() {
+        let info: GitInfo = "gist:deadbeef#v1.0.0".parse().unwrap();
+        assert_eq!(String::from("gist:deadbeef#v1.0.0"), format!("{info}"));
+    }
+
+    #[test]
+    fn hosted_urls() {
+        let info: GitInfo = "gitlab:foo/bar#v1.0.0".parse().unwrap();
+        assert_eq!(
+            info.https().unwrap().as_str(),
+            "https://gitlab.com/foo/bar.git"
+        );
+        assert_eq!(info.ssh().unwrap(), "git@gitlab.com:foo/bar.git");
+        assert_eq!(
+            info.tarball().unwrap().as_str(),
+            "https://gitlab.com/foo/bar/-/archive/v1.0.0/bar-v1.0.0.tar.gz"
+        );
+
+        let info: GitInfo = "bitbucket:foo/bar#v1.0.0".parse().unwrap();
+        assert_eq!(
+            info.https().unwrap().as_str(),
+            "https://bitbucket.org/foo/bar.git"
+        );
+        assert_eq!(info.ssh().unwrap(), "git@bitbucket.org:foo/bar.git");
+        assert_eq!(
+            info.tarball().unwrap().as_str(),
+            "https://bitbucket.org/foo/bar/get/v1.0.0.tar.gz"
+        );
+
+        let info: GitInfo = "gist:deadbeef#v1.0.0".parse().unwrap();
+        assert_eq!(
+            info.https().unwrap().as_str(),
+            "https://gist.github.com/deadbeef.git"
+        );
+        assert_eq!(info.ssh().unwrap(), "git@gist.github.com:deadbeef.git");
+        assert_eq!(
+            info.tarball().unwrap().as_str(),
+            "https://codeload.github.com/gist/deadbeef/tar.gz/v1.0.0"
+        );
+
+        let info: GitInfo = "github:foo/bar#v1.0.0".parse().unwrap();
+        assert_eq!(
+            info.tarball().unwrap().as_str(),
+            "https://codeload.github.com/foo/bar/tar.gz/v1.0.0"
+        );
+    }
+
+    #[test]
+    fn display_path() {
+        let info = GitInfo::Hosted {
+            owner: "foo".into(),
+            repo: "bar".into(),
+            host: GitHost::GitHub,
+            committish: None,
+            semver: None,
+            path: Some("packages/baz".into()),
+            requested: None,
+        };
+        assert_eq!(
+            String::from("github:foo/bar#path:packages/baz"),
+            format!("{info}")
+        );
+        let info = GitInfo::Url {
+            url: "https://foo.com/hello.git".parse().unwrap(),
+            committish: None,
+            semver: Some("^1.2.3".parse().unwrap()),
+            path: Some("packages/baz".into()),
+        };
+        assert_eq!(
+            String::from(
+                "git+https://foo.com/hello.git#semver:>=1.2.3 <2.0.0-0::path:packages/baz"
+            ),
+            format!("{info}")
+        );
+    }
 }
@@ -8,6 +8,7 @@ use std::str::FromStr;
 use node_semver::{Range, Version};
 use nom::combinator::all_consuming;
 use nom::Err;
+use url::Url;
 
 pub use crate::error::{PackageSpecError, SpecErrorKind};
 pub use crate::gitinfo::{GitHost, GitInfo};
@@ -29,6 +30,9 @@ pub enum PackageSpec {
     Dir {
         path: PathBuf,
     },
+    Link {
+        path: PathBuf,
+    },
     Alias {
         name: String,
         spec: Box<PackageSpec>,
@@ -39,6 +43,9 @@ pub enum PackageSpec {
         requested: Option<VersionSpec>,
     },
     Git(GitInfo),
+    Url {
+        url: Url,
+    },
 }
 
 impl PackageSpec {
@@ -50,7 +57,7 @@ impl PackageSpec {
         use PackageSpec::*;
         match self {
             Alias { spec, .. } => spec.is_npm(),
-            Dir { .. } | Git(..) => false,
+            Dir { .. } | Link { .. } | Git(..) | Url { .. } => false,
             Npm { .. } => true,
         }
     }
@@ -87,7 +94,9 @@ impl PackageSpec {
         use PackageSpec::*;
         match self {
             Dir { path } => format!("{}", path.display()),
+            Link { path } => format!("link:{}", path.display()),
             Git(info) => format!("{info}"),
+            Url { url } => url.to_string(),
             Npm { ref requested, .. } => requested
                 .as_ref()
                 .map(|r| r.to_string())
@@ -116,7 +125,9 @@ impl fmt::Display for PackageSpec {
         use PackageSpec::*;
         match self {
             Dir { path } => write!(f, "{}", path.display()),
+            Link { path } => write!(f, "link:{}", path.display()),
             Git(info) => write!(f, "{info}"),
+            Url { url } => write!(f, "{url}"),
             Npm {
                 ref name,
                 ref requested,
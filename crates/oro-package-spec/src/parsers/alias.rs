@@ -6,7 +6,7 @@ use nom::sequence::{preceded, tuple};
 use nom::IResult;
 
 use crate::error::SpecParseError;
-use crate::parsers::{git, npm, path, util};
+use crate::parsers::{git, npm, path, url, util};
 use crate::PackageSpec;
 
 // alias_spec := [ [ '@' ], not('/')+ '/' ] not('@/')+ '@' prefixed-package-arg
@@ -36,13 +36,16 @@ pub(crate) fn alias_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseErr
     )(input)
 }
 
-/// prefixed_package-arg := ( "npm:" npm-pkg ) | ( [ "file:" ] path )
+/// prefixed_package-arg := ( "npm:" npm-pkg ) | link | file | path | url | git-pkg
 fn prefixed_package_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError<&str>> {
     context(
         "package spec",
         alt((
+            path::link_spec,
             // Paths don't need to be prefixed, but they can be.
-            preceded(opt(tag("file:")), path::path_spec),
+            path::file_spec,
+            path::path_spec,
+            url::url_spec,
             git::git_spec,
             preceded(tag("npm:"), npm::npm_spec),
         )),
@@ -1,8 +1,9 @@
 use node_semver::Range;
 use nom::branch::alt;
-use nom::bytes::complete::{tag_no_case as tag, take_till1, take_while};
+use nom::bytes::complete::{tag_no_case as tag, take_till1, take_until, take_while};
 use nom::combinator::{cut, map, map_res, opt, peek, rest};
 use nom::error::context;
+use nom::multi::separated_list1;
 use nom::sequence::{preceded, terminated};
 use nom::IResult;
 use url::Url;
@@ -19,12 +20,16 @@ pub(crate) fn git_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError
     )(input)
 }
 
-/// `git-shorthand := [ hosted-git-prefix ] not('/')+ '/' repo`
+/// `git-shorthand := [ hosted-git-prefix ] owner-and-repo | 'gist:' gist-id`
 fn git_shorthand(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
     let (input, maybe_host) = opt(hosted_git_prefix)(input)?;
-    let (input, owner) = map_res(take_till1(|c| c == '/'), util::no_url_encode)(input)?;
-    let (input, repo) = preceded(tag("/"), take_while(|c| c != '#'))(input)?;
-    let (input, (committish, semver)) = committish(input)?;
+    let (input, (owner, repo)) = if maybe_host == Some(GitHost::Gist) {
+        // Gists don't need an owner.
+        alt((owner_and_repo, map(gist_id, |id| ("", id))))(input)?
+    } else {
+        owner_and_repo(input)?
+    };
+    let (input, (committish, semver, subdir)) = committish(input)?;
     Ok((
         input,
         GitInfo::Hosted {
@@ -33,11 +38,24 @@ fn git_shorthand(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
             repo: repo.into(),
             committish: committish.map(String::from),
             semver,
+            path: subdir,
             requested: None,
         },
     ))
 }
 
+/// `owner-and-repo := not('/')+ '/' not('#')*`
+fn owner_and_repo(input: &str) -> IResult<&str, (&str, &str), SpecParseError<&str>> {
+    let (input, owner) = map_res(take_till1(|c| c == '/'), util::no_url_encode)(input)?;
+    let (input, repo) = preceded(tag("/"), take_while(|c| c != '#'))(input)?;
+    Ok((input, (owner, repo)))
+}
+
+/// `gist-id := not('/' | '#')+`
+fn gist_id(input: &str) -> IResult<&str, &str, SpecParseError<&str>> {
+    map_res(take_till1(|c| c == '/' || c == '#'), util::no_url_encode)(input)
+}
+
 /// `hosted-git-prefix := 'github:' | 'bitbucket:' | 'gist:' | 'gitlab:'`
 fn hosted_git_prefix(input: &str) -> IResult<&str, GitHost, SpecParseError<&str>> {
     map_res(
@@ -49,28 +67,58 @@ fn hosted_git_prefix(input: &str) -> IResult<&str, GitHost, SpecParseError<&str>
     )(input)
 }
 
-fn committish(input: &str) -> IResult<&str, (Option<String>, Option<Range>), SpecParseError<&str>> {
-    let (input, hash) = opt(preceded(
+enum GitFragment {
+    Committish(String),
+    Semver(Range),
+    Path(String),
+}
+
+/// A git spec's committish, semver range, and subdirectory, in that order.
+type Fragments = (Option<String>, Option<Range>, Option<String>);
+
+/// `committish := [ '#' fragment-part ( '::' fragment-part )* ]`
+///
+/// `fragment-part := 'semver:' semver-range | 'path:' subdir | committish`
+fn committish(input: &str) -> IResult<&str, Fragments, SpecParseError<&str>> {
+    let (input, parts) = opt(preceded(
         tag("#"),
-        alt((
-            map(preceded(tag("semver:"), cut(semver_range)), |req| {
-                (None, Some(req))
-            }),
-            map(map_res(rest, util::no_url_encode), |com| (Some(com), None)),
-        )),
+        separated_list1(tag("::"), fragment_part),
     ))(input)?;
-    Ok((
-        input,
-        if let Some((maybe_comm, maybe_semver)) = hash {
-            (maybe_comm.map(String::from), maybe_semver)
-        } else {
-            (None, None)
-        },
-    ))
+    let mut committish = None;
+    let mut semver = None;
+    let mut subdir = None;
+    for part in parts.unwrap_or_default() {
+        match part {
+            GitFragment::Committish(com) => committish = Some(com),
+            GitFragment::Semver(range) => semver = Some(range),
+            GitFragment::Path(path) => subdir = Some(path),
+        }
+    }
+    Ok((input, (committish, semver, subdir)))
+}
+
+fn fragment_part(input: &str) -> IResult<&str, GitFragment, SpecParseError<&str>> {
+    alt((
+        map(
+            preceded(tag("semver:"), cut(semver_range)),
+            GitFragment::Semver,
+        ),
+        map(preceded(tag("path:"), fragment_text), |path| {
+            GitFragment::Path(path.trim_matches('/').into())
+        }),
+        map(map_res(fragment_text, util::no_url_encode), |com| {
+            GitFragment::Committish(com.into())
+        }),
+    ))(input)
+}
+
+/// Everything up to the next `::` separator, or the end of the input.
+fn fragment_text(input: &str) -> IResult<&str, &str, SpecParseError<&str>> {
+    alt((take_until("::"), rest))(input)
 }
 
 fn semver_range(input: &str) -> IResult<&str, Range, SpecParseError<&str>> {
-    let (input, range) = map_res(take_till1(|_| false), Range::parse)(input)?;
+    let (input, range) = map_res(fragment_text, Range::parse)(input)?;
     Ok((input, range))
 }
 
@@ -79,7 +127,7 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
         alt((tag("git+"), peek(tag("git://")))),
         map_res(take_till1(|c| c == '#'), Url::parse),
     )(input)?;
-    let (input, (committish, semver)) = committish(input)?;
+    let (input, (committish, semver, subdir)) = committish(input)?;
     match url.host_str() {
         Some(host @ "github.com")
         | Some(host @ "gitlab.com")
@@ -89,7 +137,12 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                 .split('/')
                 .map(String::from)
                 .collect::<Vec<String>>();
-            if let [owner, repo] = &path[..] {
+            let owner_and_repo = match &path[..] {
+                [owner, repo] => Some((owner.clone(), repo)),
+                [id] if host == "gist.github.com" => Some((String::new(), id)),
+                _ => None,
+            };
+            if let Some((owner, repo)) = owner_and_repo {
                 Ok((
                     input,
                     GitInfo::Hosted {
@@ -100,7 +153,7 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                             "bitbucket.org" => GitHost::Bitbucket,
                             _ => unreachable!(),
                         },
-                        owner: owner.clone(),
+                        owner,
                         repo: if repo.ends_with(".git") {
                             String::from(&repo[..].replace(".git", ""))
                         } else {
@@ -108,6 +161,7 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                         },
                         committish,
                         semver,
+                        path: subdir,
                         requested: Some(url.to_string()),
                     },
                 ))
@@ -118,6 +172,7 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                         url,
                         committish,
                         semver,
+                        path: subdir,
                     },
                 ))
             }
@@ -128,6 +183,7 @@ fn git_url(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                 url,
                 committish,
                 semver,
+                path: subdir,
             },
         )),
     }
@@ -138,7 +194,7 @@ fn git_scp(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
     let (input, username) = opt(terminated(take_till1(|c| c == '@'), tag("@")))(input)?;
     let (input, host) = take_till1(|c| c == ':' || c == '#')(input)?;
     let (input, path) = opt(preceded(tag(":"), take_till1(|c| c == '#')))(input)?;
-    let (input, (committish, semver)) = committish(input)?;
+    let (input, (committish, semver, subdir)) = committish(input)?;
     let mut raw = String::new();
     if let Some(username) = username {
         raw.push_str(username);
@@ -182,6 +238,7 @@ fn git_scp(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                         },
                         committish,
                         semver,
+                        path: subdir,
                         requested: Some(raw),
                     },
                 ))
@@ -192,6 +249,7 @@ fn git_scp(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                         ssh: raw,
                         committish,
                         semver,
+                        path: subdir,
                     },
                 ))
             }
@@ -202,6 +260,7 @@ fn git_scp(input: &str) -> IResult<&str, GitInfo, SpecParseError<&str>> {
                 ssh: raw,
                 committish,
                 semver,
+                path: subdir,
             },
         )),
     }
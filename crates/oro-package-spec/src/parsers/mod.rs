@@ -3,4 +3,5 @@ pub mod git;
 pub mod npm;
 pub mod package;
 pub mod path;
+pub mod url;
 pub mod util;
@@ -6,16 +6,19 @@ use nom::sequence::preceded;
 use nom::IResult;
 
 use crate::error::SpecParseError;
-use crate::parsers::{alias, git, npm, path};
+use crate::parsers::{alias, git, npm, path, url};
 use crate::PackageSpec;
 
-/// package-spec := alias | ( [ "npm:" ] npm-pkg ) | ( [ "file:" ] path ) | git-pkg
+/// package-spec := alias | ( [ "npm:" ] npm-pkg ) | link | file | path | url | git-pkg
 pub(crate) fn package_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError<&str>> {
     context(
         "package arg",
         alt((
             alias::alias_spec,
-            preceded(opt(tag("file:")), path::path_spec),
+            path::link_spec,
+            path::file_spec,
+            path::path_spec,
+            url::url_spec,
             git::git_spec,
             preceded(opt(tag("npm:")), npm::npm_spec),
         )),
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use nom::branch::alt;
 use nom::bytes::complete::tag_no_case as tag;
 use nom::character::complete::{anychar, one_of};
-use nom::combinator::{map, map_res, opt, recognize, rest};
+use nom::combinator::{map, map_res, opt, recognize, rest, verify};
 use nom::error::context;
 use nom::multi::{many0, many1};
 use nom::sequence::{delimited, preceded, tuple};
@@ -22,6 +22,28 @@ pub(crate) fn path_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseErro
     )(input)
 }
 
+/// file := 'file:' ( relative-dir | absolute-dir | bare-dir )
+pub(crate) fn file_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError<&str>> {
+    context(
+        "file spec",
+        map(
+            preceded(tag("file:"), alt((relative_path, absolute_path, bare_path))),
+            |p| PackageSpec::Dir { path: p },
+        ),
+    )(input)
+}
+
+/// link := 'link:' ( relative-dir | absolute-dir )
+pub(crate) fn link_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError<&str>> {
+    context(
+        "link spec",
+        map(
+            preceded(tag("link:"), alt((relative_path, absolute_path))),
+            |p| PackageSpec::Link { path: p },
+        ),
+    )(input)
+}
+
 /// relative-path := [ '.' ] '.' [path-sep] .*
 fn relative_path(input: &str) -> IResult<&str, PathBuf, SpecParseError<&str>> {
     context(
@@ -33,6 +55,20 @@ fn relative_path(input: &str) -> IResult<&str, PathBuf, SpecParseError<&str>> {
     )(input)
 }
 
+/// bare-path := .+
+///
+/// Like npm, `file:` paths that don't start with `.` or a separator are
+/// relative. They get a leading `./`, so they still parse as paths once
+/// they've been printed without the `file:` prefix.
+fn bare_path(input: &str) -> IResult<&str, PathBuf, SpecParseError<&str>> {
+    context(
+        "bare path",
+        map(verify(rest, |p: &str| !p.is_empty()), |p| {
+            PathBuf::from(".").join(p)
+        }),
+    )(input)
+}
+
 /// absolute-path := [ alpha ':' ] path-sep+ [ '?' path-sep+ ] .*
 fn absolute_path(input: &str) -> IResult<&str, PathBuf, SpecParseError<&str>> {
     context(
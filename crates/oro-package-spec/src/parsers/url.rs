@@ -0,0 +1,24 @@
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case as tag;
+use nom::combinator::{map, map_res, peek, recognize, rest};
+use nom::error::context;
+use nom::sequence::preceded;
+use nom::IResult;
+use url::Url;
+
+use crate::error::SpecParseError;
+use crate::PackageSpec;
+
+/// `url-spec := ( 'https://' | 'http://' ) .*`
+pub(crate) fn url_spec(input: &str) -> IResult<&str, PackageSpec, SpecParseError<&str>> {
+    context(
+        "url spec",
+        map(
+            map_res(
+                recognize(preceded(peek(alt((tag("https://"), tag("http://")))), rest)),
+                Url::parse,
+            ),
+            |url| PackageSpec::Url { url },
+        ),
+    )(input)
+}
@@ -134,3 +134,64 @@ fn spaces() -> Result<()> {
     assert!(res.is_err());
     Ok(())
 }
+
+#[test]
+fn file_prefix() -> Result<()> {
+    let res = parse("file:../local-pkg")?;
+    assert_eq!(
+        res,
+        PackageSpec::Dir {
+            path: PathBuf::from("../local-pkg"),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn file_prefix_bare_path() -> Result<()> {
+    let res = parse("file:packages/b")?;
+    assert_eq!(
+        res,
+        PackageSpec::Dir {
+            path: PathBuf::from("./packages/b"),
+        }
+    );
+    assert_eq!(parse(&res.to_string())?, res);
+    assert_eq!(
+        parse("b@file:packages/b")?,
+        PackageSpec::Alias {
+            name: "b".into(),
+            spec: Box::new(res),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn link() -> Result<()> {
+    let res = parse("link:../local-pkg")?;
+    assert_eq!(
+        res,
+        PackageSpec::Link {
+            path: PathBuf::from("../local-pkg"),
+        }
+    );
+    assert_eq!(res.to_string(), "link:../local-pkg");
+    Ok(())
+}
+
+#[test]
+fn named_link() -> Result<()> {
+    let res = parse("foo@link:/src/foo")?;
+    assert_eq!(
+        res,
+        PackageSpec::Alias {
+            name: "foo".into(),
+            spec: Box::new(PackageSpec::Link {
+                path: PathBuf::from("/src/foo"),
+            })
+        }
+    );
+    assert_eq!(res.to_string(), "foo@link:/src/foo");
+    Ok(())
+}
@@ -18,6 +18,7 @@ fn git_spec_hosted_basic() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -35,6 +36,7 @@ fn git_spec_hosted_supported_hosts() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -47,6 +49,7 @@ fn git_spec_hosted_supported_hosts() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -59,6 +62,7 @@ fn git_spec_hosted_supported_hosts() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -71,6 +75,7 @@ fn git_spec_hosted_supported_hosts() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -90,6 +95,7 @@ fn git_spec_hosted_committish() -> Result<()> {
             repo: "bar".into(),
             committish: Some("dsfargeg".into()),
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -107,6 +113,7 @@ fn git_spec_hosted_semver() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
             requested: None,
         })
     );
@@ -119,12 +126,77 @@ fn git_spec_hosted_semver() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: Some("1.2.3".parse().unwrap()),
+            path: None,
             requested: None,
         })
     );
     Ok(())
 }
 
+#[test]
+fn git_spec_hosted_shorthand_committish() -> Result<()> {
+    let res = parse("gitlab:foo/bar#v1.0.0")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Hosted {
+            host: GitHost::GitLab,
+            owner: "foo".into(),
+            repo: "bar".into(),
+            committish: Some("v1.0.0".into()),
+            semver: None,
+            path: None,
+            requested: None,
+        })
+    );
+    let res = parse("bitbucket:foo/bar#semver:^1.2.3")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Hosted {
+            host: GitHost::Bitbucket,
+            owner: "foo".into(),
+            repo: "bar".into(),
+            committish: None,
+            semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
+            requested: None,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn git_spec_hosted_gist_id() -> Result<()> {
+    let res = parse("gist:11081aaa281#deadbeef")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Hosted {
+            host: GitHost::Gist,
+            owner: "".into(),
+            repo: "11081aaa281".into(),
+            committish: Some("deadbeef".into()),
+            semver: None,
+            path: None,
+            requested: None,
+        })
+    );
+    let res = parse("git+https://gist.github.com/11081aaa281.git")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Hosted {
+            host: GitHost::Gist,
+            owner: "".into(),
+            repo: "11081aaa281".into(),
+            committish: None,
+            semver: None,
+            path: None,
+            requested: Some("https://gist.github.com/11081aaa281.git".into()),
+        })
+    );
+    // Only gists can leave out the owner.
+    assert!(parse("gitlab:11081aaa281").is_err());
+    Ok(())
+}
+
 #[test]
 fn git_spec_hosted_implicit_github() -> Result<()> {
     let res = parse("foo/bar")?;
@@ -136,6 +208,7 @@ fn git_spec_hosted_implicit_github() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: None,
         })
     );
@@ -151,6 +224,7 @@ fn git_spec_url_basic() -> Result<()> {
             url: Url::parse("git://foo.com/foo/bar").unwrap(),
             committish: None,
             semver: None,
+            path: None,
         })
     );
     Ok(())
@@ -164,7 +238,8 @@ fn git_spec_url_gitplus() -> Result<()> {
         PackageSpec::Git(GitInfo::Url {
             url: Url::parse("https://foo.com/foo/bar").unwrap(),
             committish: None,
-            semver: None
+            semver: None,
+            path: None,
         })
     );
     Ok(())
@@ -179,6 +254,7 @@ fn git_spec_url_committish() -> Result<()> {
             url: Url::parse("git://foo.com/foo/bar").unwrap(),
             committish: Some("mybranch".into()),
             semver: None,
+            path: None,
         })
     );
     Ok(())
@@ -193,6 +269,7 @@ fn git_spec_url_semver() -> Result<()> {
             url: Url::parse("git://foo.com/foo/bar").unwrap(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
         })
     );
     Ok(())
@@ -209,6 +286,7 @@ fn git_spec_url_hosted() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: Some("https://github.com/foo/bar".into()),
         })
     );
@@ -226,6 +304,7 @@ fn git_spec_url_hosted_dotgit() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: Some("https://github.com/foo/bar.git".into()),
         })
     );
@@ -243,6 +322,7 @@ fn git_spec_url_hosted_committish() -> Result<()> {
             repo: "bar".into(),
             committish: Some("mybranch".into()),
             semver: None,
+            path: None,
             requested: Some("https://github.com/foo/bar.git".into()),
         })
     );
@@ -258,6 +338,7 @@ fn git_spec_scp_basic() -> Result<()> {
             ssh: "blah@foo.com:foo/bar".into(),
             committish: None,
             semver: None,
+            path: None,
         })
     );
     let res = parse("git+ssh://blah@foo.com:foo/bar")?;
@@ -267,6 +348,7 @@ fn git_spec_scp_basic() -> Result<()> {
             ssh: "blah@foo.com:foo/bar".into(),
             committish: None,
             semver: None,
+            path: None,
         })
     );
     Ok(())
@@ -281,6 +363,7 @@ fn git_spec_scp_committish() -> Result<()> {
             ssh: "blah@foo.com:foo/bar".into(),
             committish: Some("heythere".into()),
             semver: None,
+            path: None,
         })
     );
     Ok(())
@@ -295,6 +378,7 @@ fn git_spec_scp_semver() -> Result<()> {
             ssh: "blah@foo.com:foo/bar".into(),
             committish: None,
             semver: Some("^1.2.3".parse().unwrap()),
+            path: None,
         })
     );
     Ok(())
@@ -311,6 +395,7 @@ fn git_spec_scp_hosted() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: Some("git@github.com:foo/bar".into()),
         })
     );
@@ -328,8 +413,67 @@ fn git_spec_scp_hosted_dotgit() -> Result<()> {
             repo: "bar".into(),
             committish: None,
             semver: None,
+            path: None,
             requested: Some("git@github.com:foo/bar.git".into()),
         })
     );
     Ok(())
 }
+
+#[test]
+fn git_spec_hosted_path() -> Result<()> {
+    let res = parse("github:foo/bar#path:packages/baz")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Hosted {
+            host: GitHost::GitHub,
+            owner: "foo".into(),
+            repo: "bar".into(),
+            committish: None,
+            semver: None,
+            path: Some("packages/baz".into()),
+            requested: None,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn git_spec_url_committish_path() -> Result<()> {
+    let res = parse("git+https://foo.com/foo/bar#mybranch::path:/packages/baz/")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Url {
+            url: Url::parse("https://foo.com/foo/bar").unwrap(),
+            committish: Some("mybranch".into()),
+            semver: None,
+            path: Some("packages/baz".into()),
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn git_spec_scp_semver_path() -> Result<()> {
+    let res = parse("git+ssh://blah@foo.com:foo/bar#semver:^1.2.3::path:packages/baz")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Ssh {
+            ssh: "blah@foo.com:foo/bar".into(),
+            committish: None,
+            semver: Some("^1.2.3".parse().unwrap()),
+            path: Some("packages/baz".into()),
+        })
+    );
+    let res = parse("git+ssh://blah@foo.com:foo/bar#path:packages/baz::semver:^1.2.3")?;
+    assert_eq!(
+        res,
+        PackageSpec::Git(GitInfo::Ssh {
+            ssh: "blah@foo.com:foo/bar".into(),
+            committish: None,
+            semver: Some("^1.2.3".parse().unwrap()),
+            path: Some("packages/baz".into()),
+        })
+    );
+    Ok(())
+}
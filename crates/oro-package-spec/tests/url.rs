@@ -0,0 +1,58 @@
+use oro_package_spec::{PackageSpec, PackageSpecError};
+use url::Url;
+
+type Result<T> = std::result::Result<T, PackageSpecError>;
+
+fn parse(input: &str) -> Result<PackageSpec> {
+    input.parse()
+}
+
+#[test]
+fn url_spec_https() -> Result<()> {
+    let res = parse("https://example.com/foo/-/foo-1.2.3.tgz")?;
+    assert_eq!(
+        res,
+        PackageSpec::Url {
+            url: Url::parse("https://example.com/foo/-/foo-1.2.3.tgz").unwrap(),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn url_spec_http() -> Result<()> {
+    let res = parse("http://example.com/foo.tgz")?;
+    assert_eq!(
+        res,
+        PackageSpec::Url {
+            url: Url::parse("http://example.com/foo.tgz").unwrap(),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn url_spec_alias() -> Result<()> {
+    let res = parse("foo@https://example.com/foo.tgz")?;
+    assert_eq!(
+        res,
+        PackageSpec::Alias {
+            name: "foo".into(),
+            spec: Box::new(PackageSpec::Url {
+                url: Url::parse("https://example.com/foo.tgz").unwrap(),
+            }),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn url_spec_display() -> Result<()> {
+    let res = parse("foo@https://example.com/foo.tgz?token=abc")?;
+    assert_eq!(res.to_string(), "foo@https://example.com/foo.tgz?token=abc");
+    assert_eq!(
+        res.target().requested(),
+        "https://example.com/foo.tgz?token=abc"
+    );
+    Ok(())
+}
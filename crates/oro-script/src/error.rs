@@ -51,6 +51,27 @@ pub enum OroScriptError {
     #[error("Script exited with code {}", .0.code().unwrap_or(-1))]
     #[diagnostic(code(oro_script::script_error), url(docsrs))]
     ScriptError(std::process::ExitStatus, Option<Vec<u8>>, Option<Vec<u8>>),
+
+    /// The script ran for longer than it was allowed to, so it was killed,
+    /// along with any processes it started. Scripts that hang forever, like
+    /// ones waiting for input that'll never come, end up here too.
+    #[error("Script timed out after {}s, and was killed.", .0.as_secs())]
+    #[diagnostic(code(oro_script::script_timeout), url(docsrs))]
+    ScriptTimeout(std::time::Duration),
+
+    /// A script was supposed to run in a sandbox, but there's no way to
+    /// sandbox it on this system.
+    ///
+    /// On Linux, sandboxing uses [bubblewrap](https://github.com/containers/bubblewrap),
+    /// so `bwrap` has to be on your `PATH`. On macOS, it uses the built-in
+    /// `sandbox-exec`. Other platforms aren't supported yet.
+    #[error("Can't run scripts in a sandbox: {0}")]
+    #[diagnostic(
+        code(oro_script::sandbox_unavailable),
+        url(docsrs),
+        help("Install bubblewrap (`bwrap`) on Linux, or run scripts without a sandbox.")
+    )]
+    SandboxUnavailable(String),
 }
 
 pub(crate) type Result<T> = std::result::Result<T, OroScriptError>;
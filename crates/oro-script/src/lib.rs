@@ -3,13 +3,15 @@
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Output, Stdio};
+use std::time::{Duration, Instant};
 
 pub use error::OroScriptError;
 use error::Result;
 use oro_common::BuildManifest;
-use regex::Regex;
+pub use sandbox::Sandbox;
 
 mod error;
+mod sandbox;
 
 #[derive(Debug)]
 pub struct OroScript<'a> {
@@ -17,45 +19,61 @@ pub struct OroScript<'a> {
     event: String,
     package_path: PathBuf,
     paths: Vec<PathBuf>,
-    cmd: Command,
+    /// Runs whatever gets added to it inside a sandbox, if the script is
+    /// sandboxed.
+    sandbox: Option<Command>,
+    shell: Option<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
     workspace_path: Option<PathBuf>,
+    init_cwd: Option<PathBuf>,
+    timeout: Option<Duration>,
+    foreground: bool,
 }
 
 impl<'a> OroScript<'a> {
     pub fn new(package_path: impl AsRef<Path>, event: impl AsRef<str>) -> Result<Self> {
+        Self::with_sandbox(package_path, event, None)
+    }
+
+    /// Like [`OroScript::new`], but the script runs inside `sandbox`, so it
+    /// can only write to its own package directory (and whatever else the
+    /// sandbox allows), and can't reach the network unless allowed to. See
+    /// [`Sandbox`] for which platforms support this.
+    pub fn sandboxed(
+        package_path: impl AsRef<Path>,
+        event: impl AsRef<str>,
+        sandbox: &Sandbox,
+    ) -> Result<Self> {
+        Self::with_sandbox(package_path, event, Some(sandbox))
+    }
+
+    fn with_sandbox(
+        package_path: impl AsRef<Path>,
+        event: impl AsRef<str>,
+        sandbox: Option<&Sandbox>,
+    ) -> Result<Self> {
         let package_path = dunce::canonicalize(package_path.as_ref())?;
-        let shell = if cfg!(target_os = "windows") {
-            if let Some(com_spec) = std::env::var_os("ComSpec") {
-                com_spec
-            } else {
-                OsString::from("cmd")
-            }
-        } else {
-            OsString::from("sh")
-        };
-        let shell_str = shell.to_string_lossy();
-        let shell_is_cmd = Regex::new(r"(?:^|\\)cmd(?:\.exe)?$")
-            .unwrap()
-            .is_match(&shell_str);
-        let mut cmd = Command::new(&shell);
-        if shell_is_cmd {
-            cmd.arg("/d");
-            cmd.arg("/s");
-            cmd.arg("/c");
-        } else {
-            cmd.arg("-c");
-        }
-        cmd.current_dir(&package_path);
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+        let sandbox = sandbox
+            .map(|sandbox| sandbox.command(&package_path))
+            .transpose()?;
         Ok(Self {
             event: event.as_ref().into(),
             manifest: None,
             package_path,
             paths: Self::get_existing_paths(),
+            sandbox,
+            shell: None,
+            envs: Vec::new(),
+            stdin: Stdio::null(),
+            stdout: Stdio::piped(),
+            stderr: Stdio::piped(),
             workspace_path: None,
-            cmd,
+            init_cwd: None,
+            timeout: None,
+            foreground: false,
         })
     }
 
@@ -66,23 +84,55 @@ impl<'a> OroScript<'a> {
         self
     }
 
+    /// The shell the script runs in, like `bash`, `cmd`, `pwsh`, or the path
+    /// to any other interpreter that takes a command with `-c`. Defaults to
+    /// `sh`, or `%ComSpec%` (usually `cmd.exe`) on Windows.
+    pub fn shell(mut self, shell: impl AsRef<OsStr>) -> Self {
+        self.shell = Some(shell.as_ref().to_os_string());
+        self
+    }
+
+    /// Kills the script, along with any processes it started, if it's still
+    /// running after `timeout`. This only applies to scripts that get
+    /// [`OroScript::spawn`]ed, and is enforced by [`ScriptChild::wait`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The directory the install was started from, which the script gets
+    /// as `INIT_CWD`. Defaults to the current directory.
+    pub fn init_cwd(mut self, dir: impl AsRef<Path>) -> Self {
+        self.init_cwd = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Passes a configuration value to the script, the way npm does, as an
+    /// `npm_config_<key>` environment variable. Dashes in `key` become
+    /// underscores, so `user-agent` ends up in `npm_config_user_agent`.
+    pub fn config(self, key: impl AsRef<str>, value: impl AsRef<OsStr>) -> Self {
+        let key = format!("npm_config_{}", key.as_ref().replace('-', "_"));
+        self.env(key, value)
+    }
+
     /// Set an environment variable.
     pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
-        self.cmd.env(key.as_ref(), value.as_ref());
+        self.envs
+            .push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
         self
     }
 
     /// Set the [`Stdio`] that the script will use as its
     /// standard output stream.
     pub fn stdout(mut self, stdout: impl Into<Stdio>) -> Self {
-        self.cmd.stdout(stdout.into());
+        self.stdout = stdout.into();
         self
     }
 
     /// Set the [`Stdio`] that the script will use as its
     /// standard error stream.
     pub fn stderr(mut self, stderr: impl Into<Stdio>) -> Self {
-        self.cmd.stderr(stderr.into());
+        self.stderr = stderr.into();
         self
     }
 
@@ -93,15 +143,25 @@ impl<'a> OroScript<'a> {
     /// appropriate when running lifecycle scripts, but regular run-scripts
     /// and such cases can use [`Stdio::inherit`].
     pub fn stdin(mut self, stdin: impl Into<Stdio>) -> Self {
-        self.cmd.stdin(stdin.into());
+        self.stdin = stdin.into();
         self
     }
 
+    /// Runs the script in the foreground, sharing this process's terminal:
+    /// it reads from the same stdin and writes straight to the same stdout
+    /// and stderr, so it can prompt for input.
+    pub fn foreground(mut self) -> Self {
+        self.foreground = true;
+        self.stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+    }
+
     /// Execute script, collecting all its output.
     pub fn output(self) -> Result<Output> {
         self.set_all_paths()?
-            .set_script()?
-            .cmd
+            .set_package_env()?
+            .command()?
             .output()
             .map_err(OroScriptError::ScriptProcessError)
             .and_then(|out| {
@@ -119,52 +179,88 @@ impl<'a> OroScript<'a> {
 
     /// Spawn script as a child process.
     pub fn spawn(self) -> Result<ScriptChild> {
+        let timeout = self.timeout;
         self.set_all_paths()?
-            .set_script()?
-            .cmd
+            .set_package_env()?
+            .command()?
             .spawn()
-            .map(ScriptChild::new)
+            .map(|child| ScriptChild::new(child, timeout))
             .map_err(OroScriptError::SpawnError)
     }
 
-    fn set_script(mut self) -> Result<Self> {
+    /// Puts together the command that runs the script in its shell.
+    fn command(mut self) -> Result<Command> {
         let event = &self.event;
-        if let Some(pkg) = self.manifest {
-            let script = pkg
+        let script = match self.manifest {
+            Some(pkg) => pkg.scripts.get(event).cloned(),
+            None => BuildManifest::from_path(self.package_path.join("package.json"))?
                 .scripts
                 .get(event)
-                .ok_or_else(|| OroScriptError::MissingEvent(event.to_string()))?;
-            tracing::trace!(
-                "Executing script for event '{event}' for package at {}: {script}",
-                self.package_path.display()
-            );
-            #[cfg(windows)]
-            {
-                use std::os::windows::process::CommandExt;
-                self.cmd.raw_arg(script);
+                .cloned(),
+        }
+        .ok_or_else(|| OroScriptError::MissingEvent(event.to_string()))?;
+        tracing::trace!(
+            "Executing script for event '{event}' for package at {}: {script}",
+            self.package_path.display()
+        );
+        let shell = self.shell.take().unwrap_or_else(default_shell);
+        let shell_kind = ShellKind::of(&shell);
+        let mut cmd = match self.sandbox.take() {
+            Some(mut cmd) => {
+                cmd.arg(&shell);
+                cmd
             }
-            #[cfg(not(windows))]
-            self.cmd.arg(script);
+            None => Command::new(&shell),
+        };
+        cmd.args(shell_kind.args());
+        #[cfg(windows)]
+        if shell_kind == ShellKind::Cmd {
+            // cmd.exe does its own thing with quotes, so the script has to
+            // be passed along exactly as written.
+            use std::os::windows::process::CommandExt;
+            cmd.raw_arg(&script);
         } else {
-            let package_path = &self.package_path;
-            let pkg = BuildManifest::from_path(package_path.join("package.json"))?;
-            let script = pkg
-                .scripts
-                .get(event)
-                .ok_or_else(|| OroScriptError::MissingEvent(event.to_string()))?;
-            tracing::trace!(
-                "Executing script for event '{event}' for package at {}: {script}",
-                self.package_path.display()
-            );
-            #[cfg(windows)]
-            {
-                use std::os::windows::process::CommandExt;
-                self.cmd.raw_arg(script);
+            cmd.arg(&script);
+        }
+        #[cfg(not(windows))]
+        cmd.arg(&script);
+        cmd.current_dir(&self.package_path);
+        cmd.envs(self.envs);
+        cmd.env("npm_lifecycle_event", event);
+        cmd.env("npm_lifecycle_script", &script);
+        cmd.stdin(self.stdin);
+        cmd.stdout(self.stdout);
+        cmd.stderr(self.stderr);
+        // Scripts with a timeout get their own process group, so everything
+        // they start can be killed along with them. Foreground scripts stay
+        // in the terminal's process group, or they couldn't read from it, so
+        // only the script itself gets killed.
+        #[cfg(unix)]
+        if self.timeout.is_some() && !self.foreground {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        Ok(cmd)
+    }
+
+    /// Sets the rest of the environment npm gives lifecycle scripts:
+    /// `npm_package_*` variables for the package's name, version, and its
+    /// `config`, `engines`, and `bin` fields, flattened, along with
+    /// `npm_package_json` and `INIT_CWD`.
+    fn set_package_env(mut self) -> Result<Self> {
+        let manifest_path = self.package_path.join("package.json");
+        if let Ok(manifest) = std::fs::read_to_string(&manifest_path) {
+            let manifest = serde_json::from_str(&manifest)?;
+            for (key, value) in package_env(&manifest) {
+                self = self.env(key, value);
             }
-            #[cfg(not(windows))]
-            self.cmd.arg(script);
+            self = self.env("npm_package_json", &manifest_path);
         }
-        Ok(self)
+        let init_cwd = match self.init_cwd.take() {
+            Some(init_cwd) => init_cwd,
+            None => std::env::current_dir()?,
+        };
+        Ok(self.env("INIT_CWD", init_cwd))
     }
 
     fn set_all_paths(mut self) -> Result<Self> {
@@ -208,18 +304,20 @@ impl<'a> OroScript<'a> {
 /// Child process executing a script.
 pub struct ScriptChild {
     child: Child,
+    timeout: Option<Duration>,
     pub stdin: Option<ChildStdin>,
     pub stdout: Option<ChildStdout>,
     pub stderr: Option<ChildStderr>,
 }
 
 impl ScriptChild {
-    fn new(mut child: Child) -> Self {
+    fn new(mut child: Child, timeout: Option<Duration>) -> Self {
         Self {
             stdin: child.stdin.take(),
             stdout: child.stdout.take(),
             stderr: child.stderr.take(),
             child,
+            timeout,
         }
     }
 
@@ -236,17 +334,217 @@ impl ScriptChild {
     }
 
     /// Waits for the script to exit completely. If the script exits with a
-    /// non-zero status, [`OroScriptError::ScriptError`] is returned.
+    /// non-zero status, [`OroScriptError::ScriptError`] is returned. If it
+    /// runs past its [`OroScript::timeout`], it gets killed, along with any
+    /// processes it started, and [`OroScriptError::ScriptTimeout`] is
+    /// returned.
     pub fn wait(mut self) -> Result<()> {
-        self.child
-            .wait()
-            .map_err(OroScriptError::ScriptProcessError)
-            .and_then(|status| {
-                if status.success() {
-                    Ok(())
-                } else {
-                    Err(OroScriptError::ScriptError(status, None, None))
-                }
-            })
+        let status = match self.timeout {
+            Some(timeout) => self.wait_timeout(timeout)?,
+            None => self
+                .child
+                .wait()
+                .map_err(OroScriptError::ScriptProcessError)?,
+        };
+        if status.success() {
+            Ok(())
+        } else {
+            Err(OroScriptError::ScriptError(status, None, None))
+        }
+    }
+
+    /// Polls the script until it exits, or until `timeout` runs out, in
+    /// which case the whole process tree gets killed.
+    fn wait_timeout(&mut self, timeout: Duration) -> Result<std::process::ExitStatus> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if let Some(status) = self
+                .child
+                .try_wait()
+                .map_err(OroScriptError::ScriptProcessError)?
+            {
+                return Ok(status);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        tracing::debug!(
+            "Script with pid {} ran past its {}s timeout. Killing it.",
+            self.child.id(),
+            timeout.as_secs()
+        );
+        self.kill_tree();
+        // Reap it, so it doesn't linger as a zombie.
+        let _ = self.child.wait();
+        Err(OroScriptError::ScriptTimeout(timeout))
+    }
+
+    /// Kills the script and everything it started.
+    fn kill_tree(&mut self) {
+        let pid = self.child.id().to_string();
+        // The script leads its own process group (see
+        // `OroScript::timeout`), so the whole group can go at once.
+        #[cfg(unix)]
+        let killed = Command::new("kill")
+            .args(["-KILL", "--", &format!("-{pid}")])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        #[cfg(windows)]
+        let killed = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        #[cfg(not(any(unix, windows)))]
+        let killed: std::io::Result<std::process::ExitStatus> =
+            Err(std::io::ErrorKind::Unsupported.into());
+        if !matches!(killed, Ok(status) if status.success()) {
+            tracing::debug!(
+                "Failed to kill the process tree for pid {pid}. Killing just the script."
+            );
+            let _ = self.child.kill();
+        }
+    }
+}
+
+/// The shell scripts run in when none is given.
+fn default_shell() -> OsString {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("ComSpec").unwrap_or_else(|| OsString::from("cmd"))
+    } else {
+        OsString::from("sh")
+    }
+}
+
+/// The kinds of shells that need to be told to run a command differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellKind {
+    Cmd,
+    PowerShell,
+    /// `sh`, `bash`, and anything else that takes `-c <command>`.
+    Posix,
+}
+
+impl ShellKind {
+    fn of(shell: &OsStr) -> Self {
+        let shell = shell.to_string_lossy().to_lowercase();
+        let name = shell.rsplit(['/', '\\']).next().unwrap_or(&shell);
+        let name = name.strip_suffix(".exe").unwrap_or(name);
+        match name {
+            "cmd" => Self::Cmd,
+            "powershell" | "pwsh" => Self::PowerShell,
+            _ => Self::Posix,
+        }
+    }
+
+    /// Arguments that come before the command itself.
+    fn args(&self) -> &'static [&'static str] {
+        match self {
+            Self::Cmd => &["/d", "/s", "/c"],
+            Self::PowerShell => &["-NoProfile", "-NonInteractive", "-Command"],
+            Self::Posix => &["-c"],
+        }
+    }
+}
+
+/// The `npm_package_*` variables for `manifest`.
+fn package_env(manifest: &serde_json::Value) -> Vec<(String, String)> {
+    let mut env = Vec::new();
+    for field in ["name", "version", "config", "engines"] {
+        flatten_env(&format!("npm_package_{field}"), &manifest[field], &mut env);
+    }
+    match (&manifest["bin"], manifest["name"].as_str()) {
+        // A lone bin is named after the package, minus its scope.
+        (serde_json::Value::String(bin), Some(name)) => {
+            let name = name.rsplit('/').next().unwrap_or(name);
+            env.push((format!("npm_package_bin_{}", env_key(name)), bin.clone()));
+        }
+        (bin, _) => flatten_env("npm_package_bin", bin, &mut env),
+    }
+    env
+}
+
+/// Flattens `value` into variables named after the path leading to each
+/// value inside it, like `npm_package_config_port`.
+fn flatten_env(key: &str, value: &serde_json::Value, env: &mut Vec<(String, String)>) {
+    use serde_json::Value;
+    match value {
+        Value::Null => {}
+        Value::String(value) => env.push((key.into(), value.clone())),
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten_env(&format!("{key}_{}", env_key(child)), value, env);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten_env(&format!("{key}_{i}"), value, env);
+            }
+        }
+        value => env.push((key.into(), value.to_string())),
+    }
+}
+
+/// Like npm, replaces anything that doesn't belong in an environment
+/// variable's name with `_`.
+fn env_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_kinds() {
+        assert_eq!(ShellKind::of(OsStr::new("sh")), ShellKind::Posix);
+        assert_eq!(ShellKind::of(OsStr::new("/bin/bash")), ShellKind::Posix);
+        assert_eq!(ShellKind::of(OsStr::new("cmd")), ShellKind::Cmd);
+        assert_eq!(
+            ShellKind::of(OsStr::new(r"C:\Windows\System32\CMD.EXE")),
+            ShellKind::Cmd
+        );
+        assert_eq!(ShellKind::of(OsStr::new("pwsh")), ShellKind::PowerShell);
+        assert_eq!(
+            ShellKind::of(OsStr::new("powershell.exe")),
+            ShellKind::PowerShell
+        );
+    }
+
+    #[test]
+    fn flattens_package_fields() {
+        let manifest = serde_json::json!({
+            "name": "@scope/my-pkg",
+            "version": "1.2.3",
+            "description": "not included",
+            "config": { "port": 8080, "dev-server": { "host": "localhost" } },
+            "engines": { "node": ">=16" },
+            "bin": "./cli.js",
+        });
+        let mut env = package_env(&manifest);
+        env.sort();
+        let expected = [
+            ("npm_package_bin_my_pkg", "./cli.js"),
+            ("npm_package_config_dev_server_host", "localhost"),
+            ("npm_package_config_port", "8080"),
+            ("npm_package_engines_node", ">=16"),
+            ("npm_package_name", "@scope/my-pkg"),
+            ("npm_package_version", "1.2.3"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(env, expected);
+    }
+
+    #[test]
+    fn flattens_bin_maps() {
+        let manifest = serde_json::json!({
+            "name": "pkg",
+            "bin": { "pkg": "./a.js", "pkg-b": "./b.js" },
+        });
+        let env = package_env(&manifest);
+        assert!(env.contains(&("npm_package_bin_pkg".into(), "./a.js".into())));
+        assert!(env.contains(&("npm_package_bin_pkg_b".into(), "./b.js".into())));
     }
 }
@@ -0,0 +1,161 @@
+//! Running scripts with restricted access to the rest of the system.
+//!
+//! Rather than talking to the kernel directly, scripts get wrapped in the
+//! sandboxing tool each platform already has: [bubblewrap] on Linux, which
+//! sets up fresh mount and network namespaces, and `sandbox-exec` on macOS.
+//!
+//! [bubblewrap]: https://github.com/containers/bubblewrap
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{OroScriptError, Result};
+
+/// Restrictions to run a script under. See [`crate::OroScript::sandboxed`].
+///
+/// Sandboxed scripts can read anything, but can only write to their own
+/// package directory, the system's temporary directory, and any
+/// [`Sandbox::writable`] directories. They also can't reach the network,
+/// unless [`Sandbox::network`] is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    writable: Vec<PathBuf>,
+    network: bool,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the script write to `dir`, too.
+    pub fn writable(mut self, dir: impl AsRef<Path>) -> Self {
+        self.writable.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether the script may access the network. Defaults to false.
+    pub fn network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Directories the script for the package at `package_path` may write
+    /// to. Ones that don't exist are left out, since there's nothing to
+    /// allow.
+    #[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+    fn writable_dirs(&self, package_path: &Path) -> Vec<PathBuf> {
+        std::iter::once(package_path.to_path_buf())
+            .chain(std::iter::once(std::env::temp_dir()))
+            .chain(self.writable.iter().cloned())
+            .filter_map(|dir| dunce::canonicalize(dir).ok())
+            .collect()
+    }
+
+    /// A command that runs whatever arguments get added to it inside the
+    /// sandbox.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn command(&self, package_path: &Path) -> Result<Command> {
+        let mut cmd = Command::new(find_tool("bwrap", std::env::var_os("PATH"))?);
+        cmd.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]);
+        for dir in self.writable_dirs(package_path) {
+            cmd.arg("--bind").arg(&dir).arg(&dir);
+        }
+        if !self.network {
+            cmd.arg("--unshare-net");
+        }
+        cmd.args(["--die-with-parent", "--"]);
+        Ok(cmd)
+    }
+
+    /// A command that runs whatever arguments get added to it inside the
+    /// sandbox.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn command(&self, package_path: &Path) -> Result<Command> {
+        let mut cmd = Command::new(find_tool(
+            "/usr/bin/sandbox-exec",
+            std::env::var_os("PATH"),
+        )?);
+        cmd.arg("-p").arg(self.profile(package_path));
+        Ok(cmd)
+    }
+
+    /// A command that runs whatever arguments get added to it inside the
+    /// sandbox.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub(crate) fn command(&self, _package_path: &Path) -> Result<Command> {
+        Err(OroScriptError::SandboxUnavailable(
+            "sandboxing scripts isn't supported on this platform yet.".into(),
+        ))
+    }
+
+    /// The `sandbox-exec` profile for a script in the package at
+    /// `package_path`.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn profile(&self, package_path: &Path) -> String {
+        let mut profile = String::from("(version 1)(allow default)");
+        if !self.network {
+            profile.push_str("(deny network*)(allow network* (remote unix-socket))");
+        }
+        profile.push_str("(deny file-write*)(allow file-write*");
+        for dir in self.writable_dirs(package_path) {
+            let dir = dir
+                .to_string_lossy()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            profile.push_str(&format!(" (subpath \"{dir}\")"));
+        }
+        profile.push_str(" (literal \"/dev/null\") (literal \"/dev/tty\"))");
+        profile
+    }
+}
+
+/// Finds the sandboxing tool `tool`, either on `path` or, if it's an
+/// absolute path, right where it says. Scripts that are supposed to be
+/// sandboxed never run without it, so not finding it is an error.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn find_tool(tool: &str, path: Option<impl AsRef<OsStr>>) -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    which::which_in(tool, path, cwd).map_err(|_| {
+        OroScriptError::SandboxUnavailable(if Path::new(tool).is_absolute() {
+            format!("`{tool}` doesn't exist.")
+        } else {
+            format!("`{tool}` isn't on the PATH.")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn profile() {
+        let dir = std::env::current_dir().unwrap();
+        let profile = Sandbox::new().profile(&dir);
+        assert!(profile.contains("(deny network*)"));
+        assert!(profile.contains(&format!(
+            "(subpath \"{}\")",
+            dunce::canonicalize(&dir).unwrap().display()
+        )));
+        let profile = Sandbox::new().network(true).profile(&dir);
+        assert!(!profile.contains("(deny network*)"));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn missing_tool() {
+        let empty = std::env::temp_dir().join("oro-script-no-such-dir");
+        assert!(matches!(
+            find_tool("bwrap", Some(&empty)),
+            Err(OroScriptError::SandboxUnavailable(msg)) if msg.contains("PATH")
+        ));
+        assert!(matches!(
+            find_tool("/no/such/sandbox-exec", Some(&empty)),
+            Err(OroScriptError::SandboxUnavailable(msg)) if msg.contains("doesn't exist")
+        ));
+    }
+}
@@ -19,7 +19,7 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF OR
 // IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use once_cell::sync::Lazy;
 use regex::Regex;
@@ -32,60 +32,139 @@ static SHEBANG_REGEX: Lazy<Regex> = Lazy::new(|| {
 static DOLLAR_EXPR_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\$\{?(?P<var>[^$@#?\- \t{}:]+)\}?").unwrap());
 
+/// Writes `sh`, `cmd`, and PowerShell shims at `to` (plus `.cmd` and `.ps1`)
+/// that run the bin at `source`, using the program named in its shebang, if
+/// any.
 pub fn shim_bin(source: &Path, to: &Path) -> std::io::Result<()> {
+    let from = relative_source(source, to)?;
     // First, we blow away anything that already exists there.
-    // TODO: get rid of .expect()s?
-    let from = pathdiff::diff_paths(source, to.parent().expect("must have parent"))
-        .expect("paths should be diffable");
     cleanup_existing(to)?;
-    if let Ok(contents) = std::fs::read_to_string(source) {
-        let mut lines = contents.lines();
-        if let Some(first_line) = lines.next() {
-            if let Some(captures) = SHEBANG_REGEX.captures(first_line.trim_end()) {
-                let vars = captures.name("vars").map(|m| m.as_str());
-                let prog = captures.name("prog").map(|m| m.as_str());
-                let args = captures.name("args").map(|m| m.as_str());
-                return write_shim(&from, to, vars, prog, args);
-            }
-        }
-    }
-    write_shim(&from, to, None, None, None)
+    let shebang = read_shebang(source);
+    write_shim(&from, to, &shebang)
 }
 
-fn cleanup_existing(to: &Path) -> std::io::Result<()> {
-    if let Ok(meta) = to.metadata() {
-        if meta.is_dir() {
-            std::fs::remove_dir_all(to)?;
-        } else {
-            std::fs::remove_file(to)?;
-        }
+/// Like [`shim_bin`], except `to` itself is a real symlink to `source`
+/// instead of an `sh` shim, so shells that can follow it run the bin
+/// directly. The `.cmd` and `.ps1` shims are still written, for `cmd.exe`
+/// and PowerShell.
+///
+/// Creating symlinks on Windows requires either Developer Mode or
+/// administrator privileges. If the symlink can't be created, nothing is
+/// written and the error is returned, so callers can fall back to
+/// [`shim_bin`].
+pub fn symlink_bin(source: &Path, to: &Path) -> std::io::Result<()> {
+    let from = relative_source(source, to)?;
+    cleanup_existing(to)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(&from, to)?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&from, to)?;
+    let shebang = read_shebang(source);
+    write_cmd_shim(
+        &from,
+        to,
+        shebang.vars.as_deref(),
+        shebang.prog.as_deref(),
+        shebang.args.as_deref(),
+    )?;
+    write_pwsh_shim(
+        &from,
+        to,
+        shebang.vars.as_deref(),
+        shebang.prog.as_deref(),
+        shebang.args.as_deref(),
+    )?;
+    Ok(())
+}
+
+/// Path of `source`, relative to the directory `to` goes in. Shims find
+/// their bins relative to their own location, so this needs to work even
+/// when one of the paths is in `\\?\` form (which is how Windows spells
+/// long and UNC paths) and the other isn't.
+fn relative_source(source: &Path, to: &Path) -> std::io::Result<PathBuf> {
+    let parent = to.parent().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} has no parent directory", to.display()),
+        )
+    })?;
+    pathdiff::diff_paths(strip_verbatim(source), strip_verbatim(parent)).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "can't shim {} from {}, since there's no relative path between them",
+                source.display(),
+                to.display()
+            ),
+        )
+    })
+}
+
+/// Turns `\\?\C:\foo` into `C:\foo`, and `\\?\UNC\server\share` into
+/// `\\server\share`, so they can be compared to paths without the prefix.
+/// Anything else is returned as-is.
+fn strip_verbatim(path: &Path) -> PathBuf {
+    let path_str = path.to_string_lossy();
+    if let Some(unc) = path_str.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{unc}"))
+    } else if let Some(disk) = path_str.strip_prefix(r"\\?\") {
+        PathBuf::from(disk)
+    } else {
+        path.to_path_buf()
     }
-    let cmd = to.with_extension("cmd");
-    if let Ok(meta) = cmd.metadata() {
-        if meta.is_dir() {
-            std::fs::remove_dir_all(cmd)?;
-        } else {
-            std::fs::remove_file(cmd)?;
-        }
+}
+
+/// `to`, with `.{ext}` added to the end. Unlike [`Path::with_extension`],
+/// this keeps any dots already in the bin's name, so `foo.js` gets a
+/// `foo.js.cmd` shim instead of `foo.cmd`.
+fn shim_path(to: &Path, ext: &str) -> PathBuf {
+    let mut path = to.as_os_str().to_owned();
+    path.push(".");
+    path.push(ext);
+    path.into()
+}
+
+#[derive(Default)]
+struct Shebang {
+    vars: Option<String>,
+    prog: Option<String>,
+    args: Option<String>,
+}
+
+fn read_shebang(source: &Path) -> Shebang {
+    let Ok(contents) = std::fs::read_to_string(source) else {
+        return Shebang::default();
+    };
+    let Some(first_line) = contents.lines().next() else {
+        return Shebang::default();
+    };
+    let Some(captures) = SHEBANG_REGEX.captures(first_line.trim_end()) else {
+        return Shebang::default();
+    };
+    Shebang {
+        vars: captures.name("vars").map(|m| m.as_str().to_string()),
+        prog: captures.name("prog").map(|m| m.as_str().to_string()),
+        args: captures.name("args").map(|m| m.as_str().to_string()),
     }
-    let ps1 = to.with_extension("ps1");
-    if let Ok(meta) = ps1.metadata() {
-        if meta.is_dir() {
-            std::fs::remove_dir_all(ps1)?;
-        } else {
-            std::fs::remove_file(ps1)?;
+}
+
+fn cleanup_existing(to: &Path) -> std::io::Result<()> {
+    for path in [to.to_path_buf(), shim_path(to, "cmd"), shim_path(to, "ps1")] {
+        if let Ok(meta) = path.symlink_metadata() {
+            if meta.is_dir() {
+                std::fs::remove_dir_all(path)?;
+            } else {
+                std::fs::remove_file(path)?;
+            }
         }
     }
     Ok(())
 }
 
-fn write_shim(
-    from: &Path,
-    to: &Path,
-    vars: Option<&str>,
-    prog: Option<&str>,
-    args: Option<&str>,
-) -> std::io::Result<()> {
+fn write_shim(from: &Path, to: &Path, shebang: &Shebang) -> std::io::Result<()> {
+    let vars = shebang.vars.as_deref();
+    let prog = shebang.prog.as_deref();
+    let args = shebang.args.as_deref();
     write_cmd_shim(from, to, vars, prog, args)?;
     write_sh_shim(from, to, vars, prog, args)?;
     write_pwsh_shim(from, to, vars, prog, args)?;
@@ -123,7 +202,7 @@ fn write_cmd_shim(
         };
         cmd.push_str(&convert_to_set_commands(vars.unwrap_or("")));
         cmd.push_str("\r\n");
-        cmd.push_str(&format!("IF EXISTS \"%dp0%\\{prog}.exe\" (\r\n"));
+        cmd.push_str(&format!("IF EXIST \"%dp0%\\{prog}.exe\" (\r\n"));
         cmd.push_str(&format!("  SET \"_prog=%dp0%\\{prog}.exe\"\r\n"));
         cmd.push_str(") ELSE (\r\n");
         cmd.push_str(&format!(
@@ -139,7 +218,7 @@ fn write_cmd_shim(
         cmd.push_str(&format!("{target} %*\r\n",));
     }
 
-    std::fs::write(to.with_extension("cmd"), cmd)?;
+    std::fs::write(shim_path(to, "cmd"), cmd)?;
 
     Ok(())
 }
@@ -153,7 +232,7 @@ fn write_sh_shim(
 ) -> std::io::Result<()> {
     let mut sh = concat!(
         "#!/bin/sh\n",
-        r#"basedir = $(dirname "$(echo "$0" | sed -e 's,\\,/,g')")"#,
+        r#"basedir=$(dirname "$(echo "$0" | sed -e 's,\\,/,g')")"#,
         "\n\n",
         "case `uname` in\n",
         "    *CYGWIN*|*MINGW*|*MSYS*) basedir=`cygpath -w \"$basedir\"`;;\n",
@@ -248,7 +327,7 @@ fn write_pwsh_shim(
         pwsh.push_str("exit $LASTEXITCODE\n");
     }
 
-    std::fs::write(to.with_extension("ps1"), pwsh)?;
+    std::fs::write(shim_path(to, "ps1"), pwsh)?;
 
     Ok(())
 }
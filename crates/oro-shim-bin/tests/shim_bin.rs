@@ -71,3 +71,34 @@ fn multiple_variables() {
 fn shebang_with_env_s() {
     assert_fixture!("from.env.S");
 }
+
+#[test]
+fn dotted_bin_name() {
+    let tempdir = tempfile::tempdir_in(fixtures()).unwrap();
+    let to = tempdir.path().join("shim.js");
+    oro_shim_bin::shim_bin(&fixtures().join("from.env"), &to).unwrap();
+    assert!(to.exists());
+    assert!(tempdir.path().join("shim.js.cmd").exists());
+    assert!(tempdir.path().join("shim.js.ps1").exists());
+    assert!(!tempdir.path().join("shim.cmd").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_bin() {
+    let tempdir = tempfile::tempdir_in(fixtures()).unwrap();
+    let to = tempdir.path().join("shim");
+    oro_shim_bin::shim_bin(&fixtures().join("from.env"), &to).unwrap();
+    oro_shim_bin::symlink_bin(&fixtures().join("from.env"), &to).unwrap();
+    assert!(to.symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(
+        std::fs::read_to_string(&to).unwrap(),
+        std::fs::read_to_string(fixtures().join("from.env")).unwrap()
+    );
+    insta::assert_snapshot!(
+        "from.env.cmd",
+        std::fs::read_to_string(to.with_extension("cmd"))
+            .unwrap()
+            .replace('\r', "\\r")
+    );
+}
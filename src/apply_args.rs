@@ -1,20 +1,37 @@
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use clap::Args;
+use colored::Colorize;
+use humansize::{file_size_opts, FileSize};
 use indicatif::ProgressStyle;
-use miette::Result;
-use node_maintainer::{NodeMaintainer, NodeMaintainerOptions};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use nassun::{Nassun, SignatureVerification, TarballRewrite};
+use node_maintainer::{
+    AllowScripts, DryRunReport, HookScripts, LinkStrategy, Lockfile, NodeLinker, NodeMaintainer,
+    NodeMaintainerOptions, ResolutionExplanation, ResolutionMode, ScriptOutput, ScriptOutputMode,
+    Timings, META_FILE_NAME,
+};
+use oro_client::OroClient;
 use oro_common::CorgiManifest;
+use oro_i18n::t;
 use rand::seq::IteratorRandom;
+use serde::Serialize;
 use tracing::{Instrument, Span};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 use url::Url;
 
+use crate::error::OroError;
+use crate::progress::ApplyProgress;
+use crate::reporter::{self, NdjsonObserver, Reporter};
+
 /// Applies the current project's requested dependencies to `node_modules/`,
 /// adding, removing, and updating dependencies as needed. This command is
 /// intended to be an idempotent way to make sure your `node_modules` is in
 /// the right state to execute, based on your declared dependencies.
-#[derive(Debug, Args)]
+#[derive(Clone, Debug, Args)]
 #[command(next_help_heading = "Apply Options")]
 pub struct ApplyArgs {
     /// Prevent all apply operations from executing.
@@ -33,6 +50,25 @@ pub struct ApplyArgs {
     #[arg(long)]
     pub prefer_copy: bool,
 
+    /// Way of getting files out of the cache and into `node_modules`:
+    /// `reflink`, `clonefile`, `hardlink`, or `copy`.
+    ///
+    /// Can be provided multiple times, in order of preference. The first
+    /// one that works between the cache's drive and the project's is used,
+    /// and copying is always the last resort. Which ones work is remembered
+    /// in the cache. Defaults to `reflink`, `clonefile`, `hardlink`, then
+    /// `copy`.
+    #[arg(long)]
+    pub link_strategy: Vec<LinkStrategy>,
+
+    /// Map big files into memory instead of reading them.
+    ///
+    /// Applies to tarballs too big to download into memory, and to files
+    /// checked by `--repair`. This cuts down on copying and peak memory
+    /// use, but can misbehave on network filesystems.
+    #[arg(long)]
+    pub mmap: bool,
+
     /// Validate the integrity of installed files.
     ///
     /// When this is true, orogene will verify all files extracted from the
@@ -42,6 +78,25 @@ pub struct ApplyArgs {
     #[arg(long)]
     pub validate: bool,
 
+    /// Check installed packages for damage, and reinstall the damaged ones.
+    ///
+    /// Every file in `node_modules` is hashed and compared against what the
+    /// cache recorded for it. Packages with missing or modified files are
+    /// reinstalled, without touching the rest. Implies `--validate`.
+    #[arg(long)]
+    pub repair: bool,
+
+    /// Restrict extraction and linking to the bare minimum filesystem
+    /// access.
+    ///
+    /// Nothing outside of `node_modules/` and the cache will be modified,
+    /// and file permissions will only be changed where needed to make bins
+    /// executable. Any attempt to do otherwise fails the apply. Lifecycle
+    /// scripts are not covered by this, so consider combining it with
+    /// `--no-scripts`.
+    #[arg(long)]
+    pub least_privilege: bool,
+
     /// Whether to skip restoring packages into `node_modules` and just
     /// resolve the tree and write the lockfile.
     #[arg(long)]
@@ -52,14 +107,224 @@ pub struct ApplyArgs {
     #[arg(long, visible_alias = "frozen")]
     pub locked: bool,
 
+    /// Install even if some packages' peer dependencies conflict with the
+    /// rest of the tree.
+    ///
+    /// Each conflict is logged as a warning instead. The conflicting
+    /// packages might not work correctly together.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Resolve the dependency tree a second time, with package metadata
+    /// processed in a shuffled order, and fail if the two trees differ.
+    ///
+    /// This guards against non-reproducible resolutions, and is meant to be
+    /// run in CI. Nothing gets applied if the check fails.
+    #[arg(long)]
+    pub check_determinism: bool,
+
+    /// Skip checking the project's `packageManager` and `devEngines` fields
+    /// against the orogene and Node.js versions in use.
+    #[arg(long = "no-toolchain-check", action = clap::ArgAction::SetFalse)]
+    pub toolchain_check: bool,
+
     /// Skip running install scripts.
     #[arg(long = "no-scripts", alias = "ignore-scripts", action = clap::ArgAction::SetFalse)]
     pub scripts: bool,
 
+    /// Controls whether lifecycle scripts that set up git hooks (such as
+    /// `husky install` or `simple-git-hooks`) get run.
+    ///
+    /// With `auto`, these scripts are skipped when running in CI, or when
+    /// the project isn't inside a git repository (for example, during
+    /// container builds). Use `run` or `skip` to always run or always skip
+    /// them.
+    #[arg(long, default_value = "auto")]
+    pub hook_scripts: HookScripts,
+
+    /// Pattern used to recognize git hook scripts, matched against the
+    /// script's command. `*` matches any sequence of characters.
+    ///
+    /// Can be provided multiple times. Defaults to `husky`,
+    /// `simple-git-hooks`, `lefthook install`, and `git config
+    /// core.hooksPath`.
+    #[arg(long = "hook-script-pattern")]
+    pub hook_script_patterns: Vec<String>,
+
+    /// Controls which dependencies get to run their install scripts.
+    ///
+    /// With `trusted`, only direct dependencies, and packages listed under
+    /// `trustedDependencies` in your `package.json`, run their install
+    /// scripts. Skipped scripts are listed after applying. Use `all` to let
+    /// every dependency run its scripts.
+    #[arg(long, default_value = "trusted")]
+    pub allow_scripts: AllowScripts,
+
+    /// Run install scripts in a sandbox.
+    ///
+    /// Sandboxed scripts can only write to their own package directory and
+    /// the temporary directory, and can't access the network. This uses
+    /// `bwrap` on Linux, and `sandbox-exec` on macOS. It isn't supported on
+    /// Windows yet.
+    #[arg(long)]
+    pub sandbox_scripts: bool,
+
+    /// Name of a package whose install scripts should run outside the
+    /// sandbox, with `--sandbox-scripts`.
+    ///
+    /// Can be provided multiple times.
+    #[arg(long)]
+    pub sandbox_exempt: Vec<String>,
+
+    /// Kill any install script still running after this long, such as
+    /// `10m`, along with everything it started.
+    ///
+    /// Optional dependencies whose scripts time out are skipped. Otherwise,
+    /// the apply fails, saying which package's script timed out. Supported
+    /// units are `s`, `m`, `h`, `d`, and `w`.
+    #[arg(long, value_name = "DURATION", value_parser = crate::parse_duration)]
+    pub script_timeout: Option<Duration>,
+
+    /// Like `--script-timeout`, but for all install scripts together,
+    /// counted from when the first one starts.
+    #[arg(long, value_name = "DURATION", value_parser = crate::parse_duration)]
+    pub total_script_timeout: Option<Duration>,
+
+    /// Shell to run install scripts in, such as `bash`, `pwsh`, or the path
+    /// to another interpreter that accepts a command with `-c`.
+    ///
+    /// Defaults to `sh`, or `%ComSpec%` (usually `cmd.exe`) on Windows.
+    #[arg(long, value_name = "SHELL")]
+    pub script_shell: Option<PathBuf>,
+
+    /// Run install scripts in the foreground, one at a time, connected
+    /// directly to the terminal.
+    ///
+    /// Scripts that prompt for input, like ones that configure native
+    /// builds, need this to work. Their output is shown as-is, instead of
+    /// in the progress bar.
+    #[arg(long)]
+    pub foreground_scripts: bool,
+
+    /// Run install scripts for every package, even ones that already ran
+    /// them.
+    ///
+    /// Normally, packages that are still installed exactly as they were
+    /// when their scripts last ran, under the same OS, architecture, and
+    /// Node.js version, don't run them again.
+    #[arg(long)]
+    pub force_rebuild: bool,
+
+    /// How to show install scripts' output: `progress`, `prefixed`, or
+    /// `grouped`.
+    ///
+    /// `progress` only shows the latest line in the progress bar.
+    /// `prefixed` prints every line as it comes in, tagged with the package
+    /// and script it came from. `grouped` does the same, but holds on to
+    /// each script's output until it finishes, so it isn't interleaved with
+    /// others'. Defaults to `progress`.
+    #[arg(long, value_name = "MODE")]
+    pub script_output: Option<ScriptOutputMode>,
+
+    /// Patch a dependency right after it's extracted, using `--patch
+    /// <name>[@<semver range>]=<path>` format.
+    ///
+    /// Patches are unified diffs, such as the ones generated by `git diff`,
+    /// and their paths are relative to the project root. Can be provided
+    /// multiple times. Patches listed under `patchedDependencies` in
+    /// `package.json` are applied as well.
+    #[arg(
+        long = "patch",
+        alias = "patched-dependencies",
+        value_parser = crate::parse_key_value::<String, PathBuf>
+    )]
+    pub patched_dependencies: Vec<(String, PathBuf)>,
+
+    /// Add an entry to the default dependency catalog, using `--catalog
+    /// <name>=<spec>` format.
+    ///
+    /// Dependencies with a `catalog:` spec use the version their catalog
+    /// has for them. Can be provided multiple times, and takes precedence
+    /// over the `catalog` field in `package.json`.
+    #[arg(long = "catalog", value_parser = crate::parse_key_value::<String, String>)]
+    pub catalog: Vec<(String, String)>,
+
+    /// Resolve semver ranges to the project's own workspace packages when
+    /// they satisfy them, instead of fetching them from the registry.
+    #[arg(long)]
+    pub prefer_workspace_packages: bool,
+
+    /// Don't link the project's workspace packages into `node_modules/`.
+    ///
+    /// By default, every workspace package gets symlinked into the root
+    /// `node_modules/`, with its bins linked into `node_modules/.bin`, so
+    /// workspace packages can use each other without any extra setup.
+    #[arg(long = "no-link-workspaces", action = clap::ArgAction::SetFalse)]
+    pub link_workspaces: bool,
+
     /// Default dist-tag to use when resolving package versions.
     #[arg(long, default_value = "latest")]
     pub default_tag: String,
 
+    /// Which version to pick when resolving semver ranges.
+    ///
+    /// `highest` picks the newest matching version. `lowest` picks the
+    /// oldest matching version instead, for every dependency in the tree,
+    /// while `lowest-direct` only does so for the project's own direct
+    /// dependencies. Packages already in the lockfile are left alone.
+    #[arg(long, default_value = "highest")]
+    pub resolution_mode: ResolutionMode,
+
+    /// Explain why a package resolved to the version it did, such as
+    /// `react`.
+    ///
+    /// For every copy of the package in the tree, this prints what depends
+    /// on it, which published versions those would accept, and why the
+    /// resolver picked the one it did. Can be provided multiple times. With
+    /// `--json`, the explanations are included in the output as
+    /// `explanations`.
+    #[arg(long, value_name = "PACKAGE")]
+    pub explain_resolution: Vec<String>,
+
+    /// Skip package versions published less recently than this, such as
+    /// `3d` or `12h`.
+    ///
+    /// This guards against freshly-published malicious releases, by giving
+    /// the community some time to catch them. Supported units are `s`, `m`,
+    /// `h`, `d`, and `w`. Versions already in the lockfile, and explicitly
+    /// requested versions or tags, aren't affected.
+    #[arg(long, value_name = "AGE", value_parser = crate::parse_duration)]
+    pub minimum_release_age: Option<Duration>,
+
+    /// Only use package versions published before this date, to reproduce
+    /// what would've been resolved back then.
+    ///
+    /// Takes either an RFC 3339 timestamp, such as `2023-04-01T12:00:00Z`, or
+    /// a plain `YYYY-MM-DD` date, which means midnight UTC on that day.
+    /// Versions already in the lockfile, and explicitly requested versions or
+    /// tags, aren't affected.
+    #[arg(long, value_name = "DATE", value_parser = crate::parse_date)]
+    pub before: Option<SystemTime>,
+
+    /// Require registry packages matching this pattern to have a provenance
+    /// attestation, such as `@my-org/*`.
+    ///
+    /// Installs fail if a matching package was published without
+    /// provenance, or with provenance that isn't about that exact package.
+    /// Patterns starting with `!` exclude packages. Can be provided multiple
+    /// times, or as a list with `require-provenance` in `oro.kdl`.
+    #[arg(long, value_name = "PATTERN")]
+    pub require_provenance: Vec<String>,
+
+    /// Check the registry's signature over each package's integrity before
+    /// downloading it.
+    ///
+    /// `warn` logs a warning for packages whose signature can't be
+    /// verified, while `enforce` refuses to install them. Packages from
+    /// registries that don't publish signing keys count as unverified.
+    #[arg(long, value_name = "MODE", default_value = "off")]
+    pub verify_signatures: SignatureVerification,
+
     /// Controls number of concurrent operations during various apply steps
     /// (resolution fetches, extractions, etc).
     ///
@@ -72,9 +337,21 @@ pub struct ApplyArgs {
     /// `run_script`.
     ///
     /// This option is separate from `concurrency` because executing
-    /// concurrent scripts is a much heavier operation.
-    #[arg(long, default_value_t = node_maintainer::DEFAULT_SCRIPT_CONCURRENCY)]
-    pub script_concurrency: usize,
+    /// concurrent scripts is a much heavier operation. By default, it adapts
+    /// to how many CPUs the machine has, how busy they are, and how much
+    /// memory is left, holding back native builds more than other scripts.
+    /// Setting it uses a fixed limit instead.
+    #[arg(long)]
+    pub script_concurrency: Option<usize>,
+
+    /// Maximum number of resolved packages that can be waiting to be
+    /// extracted while the rest of the dependency tree is still resolving.
+    ///
+    /// Extracting packages as soon as they're resolved overlaps network
+    /// latency with extraction. Packages that don't fit are extracted after
+    /// resolution instead. Set this to 0 to turn pipelining off.
+    #[arg(long, default_value_t = node_maintainer::DEFAULT_PIPELINE_DEPTH)]
+    pub pipeline_depth: usize,
 
     /// Disable writing the lockfile after operations complete.
     ///
@@ -83,6 +360,30 @@ pub struct ApplyArgs {
     #[arg(long = "no-lockfile", action = clap::ArgAction::SetFalse)]
     pub lockfile: bool,
 
+    /// Also write an npm-compatible `package-lock.json` next to
+    /// `package-lock.kdl`.
+    ///
+    /// This lets tools that only understand npm lockfiles, such as `npm ci`,
+    /// keep working. When installing with the hoisted layout, npm's hidden
+    /// `node_modules/.package-lock.json` is written as well.
+    #[arg(long)]
+    pub npm_lockfile: bool,
+
+    /// Don't print which packages were added, removed, or changed when an
+    /// existing lockfile gets updated.
+    #[arg(long = "no-diff", action = clap::ArgAction::SetFalse)]
+    pub diff: bool,
+
+    /// Which layout to install dependencies with: `isolated`, `hoisted`, or
+    /// `pnp`.
+    ///
+    /// By default, whatever layout `node_modules/` already uses is kept, so
+    /// switching between them never leaves a mix of both behind. Layouts
+    /// left by pnpm, npm, and Yarn are recognized too. New projects use
+    /// `isolated`.
+    #[arg(long)]
+    pub node_linker: Option<NodeLinker>,
+
     /// Use the hoisted installation mode, where all dependencies and their
     /// transitive dependencies are installed as high up in the `node_modules`
     /// tree as possible.
@@ -92,19 +393,230 @@ pub struct ApplyArgs {
     /// compatibility.
     ///
     /// By default, dependencies are installed in "isolated" mode, using a
-    /// symlink/junction structure to simulate a dependency tree.
+    /// symlink/junction structure to simulate a dependency tree. This is the
+    /// same as `--node-linker hoisted`.
     #[arg(long)]
     pub hoisted: bool,
 
+    /// Use Plug'n'Play instead of materializing `node_modules/`.
+    ///
+    /// Packages are extracted once into a store inside the cache, and a
+    /// `.pnp.cjs` file is written to the project root that tells Node.js
+    /// where each package's dependencies live. Load it with `node --require
+    /// ./.pnp.cjs`. Packages with install scripts are extracted into
+    /// `node_modules/.oro-unplugged/` instead, so they can build themselves.
+    ///
+    /// This takes precedence over `--hoisted`, and is the same as
+    /// `--node-linker pnp`.
+    #[arg(long)]
+    pub pnp: bool,
+
+    /// Install packages from a global store inside the cache, pnpm-style.
+    ///
+    /// Each package is extracted into the store once, and its files are
+    /// hard linked into `node_modules/` from there, so identical packages
+    /// across projects only take up disk space once, and repeat installs
+    /// mostly just create links. Packages with install scripts are copied
+    /// instead, so they can't modify the shared files.
+    ///
+    /// Hard links only work if the cache is on the same drive as the
+    /// project. Files are copied otherwise.
+    #[arg(long)]
+    pub global_store: bool,
+
+    /// Reuse the results of install scripts across installs.
+    ///
+    /// After a package's `preinstall`, `install`, and `postinstall` scripts
+    /// run, its directory is snapshotted into the cache, and later installs
+    /// of the same package restore the snapshot instead of running the
+    /// scripts again. This mostly saves on rebuilding native addons.
+    /// Snapshots are specific to the OS, architecture, and Node.js version
+    /// they were taken on.
+    #[arg(long)]
+    pub side_effects_cache: bool,
+
+    /// Fail when two packages provide a bin with the same name.
+    ///
+    /// By default, direct dependencies win over transitive ones, ties go to
+    /// whichever package name sorts first, and the collision is only warned
+    /// about.
+    #[arg(long)]
+    pub strict_bins: bool,
+
+    /// On Windows, link bins with real symlinks where possible.
+    ///
+    /// Bins normally get `sh`, `.cmd`, and `.ps1` shims on Windows. With
+    /// this, the `sh` shim is replaced with a symlink to the bin itself,
+    /// as long as symlinks can be created, which requires Developer Mode.
+    /// Has no effect on other platforms.
+    #[arg(long)]
+    pub bin_symlinks: bool,
+
+    /// Report what would be removed, extracted, and linked, without
+    /// changing anything.
+    ///
+    /// Packages are still resolved, but `node_modules/`, `package.json`,
+    /// and the lockfile are left alone, and no lifecycle scripts are run.
+    /// With `--json`, the report is included in the output as `changes`.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print where the time went once applying is done.
+    ///
+    /// Covers resolving, fetching metadata, downloading tarballs,
+    /// extracting, linking bins, and running install scripts, along with
+    /// the packages that took the longest to install. Things that ran at
+    /// the same time are added up separately, so the parts can add up to
+    /// more than the whole. With `--json`, the breakdown is included in the
+    /// output as `timing`.
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Write the `--timing` breakdown to this file as JSON, for tracking
+    /// performance over time. Implies `--timing`.
+    #[arg(long)]
+    pub timing_file: Option<PathBuf>,
+
+    /// Glob pattern for paths inside `node_modules/` that pruning should
+    /// leave alone, such as tool caches.
+    ///
+    /// Patterns are relative to `node_modules/`, `*` matches any sequence of
+    /// characters, and matching a directory keeps everything in it. Can be
+    /// provided multiple times, or as a list with `prune-keep` in `oro.kdl`.
+    /// Defaults to `.cache` and `.vite`.
+    #[arg(long)]
+    pub prune_keep: Vec<String>,
+
+    /// Glob pattern for package names to also link into the root
+    /// `node_modules/` when using the isolated linker.
+    ///
+    /// Editor tooling often looks for packages like `eslint` plugins and
+    /// `prettier` directly in `node_modules/`, even when they're only
+    /// transitive dependencies. Patterns starting with `!` exclude packages.
+    /// Can be provided multiple times, or as a list with
+    /// `public-hoist-pattern` in `oro.kdl`. Defaults to `eslint*` and
+    /// `*prettier*`.
+    #[arg(long)]
+    pub public_hoist_pattern: Vec<String>,
+
+    /// Glob pattern for package names the hoisted linker may hoist all the
+    /// way up to the root `node_modules/`.
+    ///
+    /// Anything else stays inside the top-level package that depends on it,
+    /// so the project can't accidentally require it. Patterns starting with
+    /// `!` exclude packages. Can be provided multiple times, or as a list
+    /// with `hoist-pattern` in `oro.kdl`. Defaults to `*`, which hoists
+    /// everything.
+    #[arg(long)]
+    pub hoist_pattern: Vec<String>,
+
+    /// Operate on globally installed packages instead of the current
+    /// project.
+    ///
+    /// Packages go in `<prefix>/lib/node_modules`, and their bins in
+    /// `<prefix>/bin` (`<prefix>/node_modules` and `<prefix>` on Windows).
+    /// No lockfile is written.
+    #[arg(long, short)]
+    pub global: bool,
+
+    /// Prefix to install global packages into, with `--global`.
+    ///
+    /// Defaults to `$NPM_CONFIG_PREFIX`, or else the prefix of the `node`
+    /// on your `PATH`, same as npm.
+    #[arg(long)]
+    pub global_prefix: Option<PathBuf>,
+
+    /// Replace a `node_modules/` installed by npm, yarn, or pnpm.
+    ///
+    /// Their bookkeeping is removed, and the rest of `node_modules/` is
+    /// installed again from scratch with the selected node linker. Without
+    /// this, a foreign `node_modules/` is left alone, and applying fails.
+    #[arg(long)]
+    pub takeover: bool,
+
+    /// Merge duplicate copies of packages after resolving. Set by `oro
+    /// dedupe`.
+    #[arg(skip)]
+    pub dedupe: bool,
+
     #[arg(from_global)]
     pub registry: Url,
 
+    #[arg(from_global)]
+    pub mirrors: Vec<Url>,
+
+    #[arg(from_global)]
+    pub tarball_rewrites: Vec<TarballRewrite>,
+
     #[arg(from_global)]
     pub scoped_registries: Vec<(String, Url)>,
 
+    #[arg(from_global)]
+    pub credentials: Vec<(String, String, String)>,
+
+    #[arg(from_global)]
+    pub proxy: Option<Url>,
+
+    #[arg(from_global)]
+    pub https_proxy: Option<Url>,
+
+    #[arg(from_global)]
+    pub noproxy: Option<String>,
+
+    #[arg(from_global)]
+    pub cafile: Option<PathBuf>,
+
+    #[arg(from_global)]
+    pub ca: Vec<String>,
+
+    #[arg(from_global)]
+    pub strict_ssl: bool,
+
+    #[arg(from_global)]
+    pub cert: Option<PathBuf>,
+
+    #[arg(from_global)]
+    pub key: Option<PathBuf>,
+
+    #[arg(from_global)]
+    pub registry_certs: Vec<(String, PathBuf)>,
+
+    #[arg(from_global)]
+    pub registry_keys: Vec<(String, PathBuf)>,
+
+    #[arg(from_global)]
+    pub network_limit: Option<u64>,
+
+    #[arg(from_global)]
+    pub max_sockets: Option<usize>,
+
+    #[arg(from_global)]
+    pub max_sockets_per_host: Option<usize>,
+
+    #[arg(from_global)]
+    pub pool_idle_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    pub connect_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    pub tls_handshake_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    pub first_byte_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    pub request_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    pub dns_ttl: Option<std::time::Duration>,
+
     #[arg(from_global)]
     pub json: bool,
 
+    #[arg(from_global)]
+    pub reporter: Reporter,
+
     #[arg(from_global)]
     pub root: PathBuf,
 
@@ -113,24 +625,172 @@ pub struct ApplyArgs {
 
     #[arg(from_global)]
     pub emoji: bool,
+
+    /// Client to resolve with, instead of building a new one. `oro daemon`
+    /// sets this so every request shares the packuments it has fetched.
+    #[arg(skip)]
+    pub nassun: Option<Nassun>,
 }
 
+/// What applying `node_modules/` did. This is what gets printed with
+/// `--json`, so fields should only ever be added, never changed.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplySummary {
+    /// Packages in the resolved tree.
+    pub packages: usize,
+    /// Packages that weren't in `node_modules/` before.
+    pub added: usize,
+    /// Packages that are no longer in `node_modules/`.
+    pub removed: usize,
+    /// Packages that were replaced with a different version.
+    pub changed: usize,
+    pub pruned: usize,
+    pub extracted: usize,
+    pub dry_run: bool,
+    pub package_cache_hits: u64,
+    pub package_cache_misses: u64,
+    pub bytes_downloaded: u64,
+    /// Install scripts that ran, counting each of a package's scripts
+    /// separately.
+    pub scripts_run: usize,
+    /// Install scripts that were skipped because their packages aren't
+    /// trusted.
+    pub scripts_blocked: usize,
+    /// Installed packages that are looking for funding.
+    pub funded: usize,
+    /// Seconds it took.
+    pub time: f32,
+    pub phases: PhaseTimes,
+    /// What a dry run would've changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<DryRunReport>,
+    /// Where the time went, with `--timing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<TimingReport>,
+    /// Why packages resolved the way they did, with `--explain-resolution`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanations: Option<Vec<ResolutionExplanation>>,
+}
+
+/// How long each part of applying `node_modules/` took, in seconds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PhaseTimes {
+    pub resolve: f32,
+    pub prune: f32,
+    pub extract: f32,
+    /// Running install scripts and linking bins.
+    pub rebuild: f32,
+}
+
+/// Where the time went while applying, for `--timing`, in seconds. Things
+/// that ran at the same time are added up separately.
+#[derive(Clone, Debug, Serialize)]
+pub struct TimingReport {
+    /// When applying finished, in RFC 3339 format.
+    pub timestamp: String,
+    pub total: f32,
+    pub resolve: f32,
+    pub metadata: f32,
+    pub download: f32,
+    pub extract: f32,
+    pub bins: f32,
+    pub scripts: f32,
+    /// The packages that took the longest to install, slowest first.
+    pub slowest: Vec<PackageTimingReport>,
+}
+
+/// How long a single package took to install, in seconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct PackageTimingReport {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub extract: f32,
+    pub scripts: f32,
+}
+
+/// How many of the slowest packages `--timing` reports.
+const SLOWEST_PACKAGES: usize = 10;
+
 impl ApplyArgs {
-    pub async fn execute(&self, manifest: CorgiManifest) -> Result<()> {
-        let total_time = std::time::Instant::now();
+    /// Applies `manifest` to `node_modules/`, and returns what was done, or
+    /// `None` if applying is turned off.
+    pub async fn execute(&self, manifest: CorgiManifest) -> Result<Option<ApplySummary>> {
+        let total_time = Instant::now();
 
         if !self.apply {
             tracing::info!("{}Skipping applying node_modules/.", self.emoji_tada(),);
-            return Ok(());
+            return Ok(None);
         }
+        let mut phases = PhaseTimes::default();
 
         let root = &self.root;
-        let maintainer = self.resolve(manifest, self.configured_maintainer()).await?;
+        if self.toolchain_check && !self.global {
+            crate::toolchain::check(root).await?;
+        }
+        if self.check_determinism {
+            self.check_determinism(manifest.clone()).await?;
+        }
+        // Packages only get extracted ahead of time if they're going to be
+        // installed at all.
+        let progress = ApplyProgress::new(self.emoji);
+        let builder = self
+            .with_progress(self.configured_maintainer()?, &progress)
+            .pipeline_depth(if self.lockfile_only {
+                0
+            } else {
+                self.pipeline_depth
+            });
+        let installed = self.installed_tree().await?;
+        let phase_time = Instant::now();
+        let maintainer = self
+            .resolve(manifest, builder, &progress, !self.lockfile_only)
+            .await?;
+        phases.resolve = seconds_since(phase_time);
 
+        let cycles = maintainer.cycles();
+        if !cycles.is_empty() {
+            tracing::warn!(
+                "Found {} dependency {}. Lifecycle scripts for packages in a cycle can't run in dependency order. Run `oro ls --cycles` to list them.",
+                cycles.len(),
+                if cycles.len() == 1 { "cycle" } else { "cycles" }
+            );
+        }
+
+        let explanations = self.explain(&maintainer).await?;
+
+        let (mut pruned, mut extracted, mut changes) = (0, 0, None);
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        let mut funded = 0;
         if !self.lockfile_only {
-            self.prune(&maintainer).await?;
-            self.extract(&maintainer).await?;
-            self.rebuild(&maintainer).await?;
+            let phase_time = Instant::now();
+            pruned = self.prune(&maintainer).await?;
+            phases.prune = seconds_since(phase_time);
+            let phase_time = Instant::now();
+            extracted = self.extract(&maintainer, &progress).await?;
+            phases.extract = seconds_since(phase_time);
+            let phase_time = Instant::now();
+            self.rebuild(&maintainer, &progress).await?;
+            phases.rebuild = seconds_since(phase_time);
+            (added, removed, changed) =
+                package_changes(installed.as_ref(), &maintainer.to_lockfile()?);
+            if !self.dry_run {
+                // Funding is just a courtesy, so it shouldn't fail the install.
+                funded = match maintainer.funding().await {
+                    Ok(funding) => funding.len(),
+                    Err(e) => {
+                        tracing::debug!("Failed to collect funding information: {e}");
+                        0
+                    }
+                };
+            }
+            changes = maintainer.dry_run_report();
+            // With `--json`, the report is printed as part of the summary.
+            match &changes {
+                Some(report) if !self.json => self.print_dry_run_report(report),
+                _ => {}
+            }
         } else {
             tracing::info!(
                 "{}Skipping installing node_modules/, only writing lockfile.",
@@ -138,38 +798,442 @@ impl ApplyArgs {
             );
         }
 
-        if self.lockfile {
-            maintainer
-                .write_lockfile(root.join("package-lock.kdl"))
-                .await?;
+        if self.lockfile && !self.dry_run && !self.global {
+            let lockfile_path = root.join("package-lock.kdl");
+            let previous = if self.diff && !self.json {
+                async_std::fs::read_to_string(&lockfile_path)
+                    .await
+                    .ok()
+                    .and_then(|kdl| Lockfile::from_kdl(kdl).ok())
+            } else {
+                None
+            };
+            maintainer.write_lockfile(&lockfile_path).await?;
             tracing::info!(
                 "{}Wrote lockfile to package-lock.kdl.",
                 self.emoji_writing()
             );
+            if let Some(previous) = previous {
+                self.print_lockfile_diff(&lockfile_diff(&previous, &maintainer.to_lockfile()?));
+            }
+            if self.npm_lockfile {
+                maintainer
+                    .write_npm_lockfile(root.join("package-lock.json"))
+                    .await?;
+                if maintainer.node_linker() == NodeLinker::Hoisted && !self.lockfile_only {
+                    maintainer
+                        .write_hidden_npm_lockfile(
+                            root.join("node_modules").join(".package-lock.json"),
+                        )
+                        .await?;
+                }
+                tracing::info!(
+                    "{}Wrote npm lockfile to package-lock.json.",
+                    self.emoji_writing()
+                );
+            }
+        }
+
+        for problem in maintainer.peer_problems() {
+            tracing::warn!("Unmet peer dependency: {problem}");
+        }
+
+        let deprecations = maintainer.deprecations();
+        if !deprecations.is_empty() {
+            let mut block = format!(
+                "{} deprecated package{} installed:",
+                deprecations.len(),
+                if deprecations.len() == 1 { "" } else { "s" }
+            );
+            for deprecation in &deprecations {
+                for line in deprecation.to_string().lines() {
+                    block.push_str("\n  ");
+                    block.push_str(line);
+                }
+            }
+            tracing::warn!("{block}");
+        }
+
+        let counts = maintainer.cache_counts();
+        let summary = ApplySummary {
+            packages: maintainer.package_count(),
+            added,
+            removed,
+            changed,
+            pruned,
+            extracted,
+            dry_run: self.dry_run,
+            package_cache_hits: counts.package_hits,
+            package_cache_misses: counts.package_misses,
+            bytes_downloaded: counts.bytes_downloaded,
+            scripts_run: maintainer.scripts_run(),
+            scripts_blocked: maintainer
+                .blocked_scripts()
+                .iter()
+                .map(|blocked| blocked.events.len())
+                .sum(),
+            funded,
+            time: seconds_since(total_time),
+            timing: (self.timing || self.timing_file.is_some()).then(|| {
+                timing_report(
+                    &maintainer.timings(),
+                    seconds_since(total_time),
+                    phases.resolve,
+                )
+            }),
+            phases,
+            changes,
+            explanations,
+        };
+        if let (Some(timing), Some(path)) = (&summary.timing, &self.timing_file) {
+            let json = serde_json::to_string_pretty(timing).into_diagnostic()?;
+            async_std::fs::write(path, json)
+                .await
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to write timing report to {}", path.display()))?;
+        }
+        if self.reporter.is_ndjson() {
+            let mut event = serde_json::to_value(&summary).into_diagnostic()?;
+            event["type"] = "summary".into();
+            reporter::emit(event);
+        }
+        tracing::debug!(
+            "Cache use: {} packages extracted from the cache and {} downloaded, {} packuments revalidated and {} downloaded.",
+            counts.package_hits,
+            counts.package_misses,
+            counts.packument_revalidations,
+            counts.packument_misses
+        );
+
+        if self.dry_run {
+            tracing::info!("Dry run, so nothing was changed.");
+            self.print_timing(&summary);
+            return Ok(Some(summary));
         }
 
         tracing::info!(
             "{}Applied node_modules/ in {}s. {}",
             self.emoji_tada(),
-            total_time.elapsed().as_millis() as f32 / 1000.0,
+            summary.time,
             hackerish_encouragement()
         );
+        if !self.lockfile_only {
+            self.print_summary(&summary);
+        }
+        self.print_timing(&summary);
+        Ok(Some(summary))
+    }
+
+    /// Explains the packages asked about with `--explain-resolution`,
+    /// printing the explanations unless they're going into the JSON output.
+    async fn explain(
+        &self,
+        maintainer: &NodeMaintainer,
+    ) -> Result<Option<Vec<ResolutionExplanation>>> {
+        if self.explain_resolution.is_empty() {
+            return Ok(None);
+        }
+        let mut explanations = Vec::new();
+        for name in &self.explain_resolution {
+            let found = maintainer.explain(name).await?;
+            if !self.json {
+                if found.is_empty() {
+                    tracing::warn!(
+                        "{name} isn't in the resolved tree, so there's nothing to explain."
+                    );
+                }
+                for explanation in &found {
+                    tracing::info!("{explanation}");
+                }
+            }
+            explanations.extend(found);
+        }
+        Ok(Some(explanations))
+    }
+
+    fn print_timing(&self, summary: &ApplySummary) {
+        // With `--json`, it's part of the summary instead.
+        let (Some(timing), false) = (&summary.timing, self.json) else {
+            return;
+        };
+        tracing::info!("Timing:");
+        for (phase, seconds) in [
+            ("Resolving", timing.resolve),
+            ("Fetching metadata", timing.metadata),
+            ("Downloading", timing.download),
+            ("Extracting", timing.extract),
+            ("Linking bins", timing.bins),
+            ("Running scripts", timing.scripts),
+        ] {
+            tracing::info!("  {phase:<18} {seconds:.3}s");
+        }
+        if !timing.slowest.is_empty() {
+            tracing::info!("Slowest packages:");
+        }
+        for package in &timing.slowest {
+            let name = match &package.version {
+                Some(version) => format!("{}@{version}", package.name),
+                None => package.name.clone(),
+            };
+            tracing::info!(
+                "  {name}: {:.3}s extracting, {:.3}s running scripts",
+                package.extract,
+                package.scripts
+            );
+        }
+    }
+
+    fn print_summary(&self, summary: &ApplySummary) {
+        let ApplySummary {
+            added,
+            removed,
+            changed,
+            package_cache_hits: hits,
+            package_cache_misses: misses,
+            bytes_downloaded,
+            scripts_run,
+            scripts_blocked,
+            funded,
+            phases,
+            ..
+        } = summary;
+        tracing::info!("  {added} added, {removed} removed, {changed} changed.");
+        let downloaded = bytes_downloaded.file_size(file_size_opts::DECIMAL).unwrap();
+        if let Some(percent) = (hits * 100).checked_div(hits + misses) {
+            tracing::info!(
+                "  Downloaded {downloaded}, with {percent}% of packages coming from the cache."
+            );
+        } else {
+            tracing::info!("  Downloaded {downloaded}.");
+        }
+        tracing::info!(
+            "  Ran {scripts_run} install script{}, and skipped {scripts_blocked}.",
+            if *scripts_run == 1 { "" } else { "s" }
+        );
+        tracing::info!(
+            "  Took {}s to resolve, {}s to prune, {}s to extract, and {}s to build.",
+            phases.resolve,
+            phases.prune,
+            phases.extract,
+            phases.rebuild
+        );
+        if *funded > 0 {
+            tracing::info!(
+                "  {funded} package{} looking for funding. Run `oro fund` for details.",
+                if *funded == 1 { " is" } else { "s are" }
+            );
+        }
+    }
+
+    /// What `node_modules/` had in it before applying, if it was installed
+    /// by Orogene.
+    async fn installed_tree(&self) -> Result<Option<Lockfile>> {
+        let meta = self.node_modules_dir()?.join(META_FILE_NAME);
+        Ok(async_std::fs::read_to_string(meta)
+            .await
+            .ok()
+            .and_then(|kdl| Lockfile::from_kdl(kdl).ok()))
+    }
+
+    /// Prints `output` to stdout as JSON, for `--json`.
+    pub fn print_json(&self, output: &impl Serialize) -> Result<()> {
+        let output = serde_json::to_string_pretty(output)
+            .into_diagnostic()
+            .wrap_err("apply::serialize_json")?;
+        println!("{output}");
         Ok(())
     }
 
-    fn configured_maintainer(&self) -> NodeMaintainerOptions {
+    fn print_lockfile_diff(&self, changes: &[LockfileChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        let arrow = if self.emoji { "→" } else { "->" };
+        let mut block = String::from("Lockfile changes:");
+        for change in changes {
+            let line = match change {
+                LockfileChange::Added { name, version } => format!("+ {name} {version}").green(),
+                LockfileChange::Removed { name, version } => format!("- {name} {version}").red(),
+                LockfileChange::Changed { name, from, to } => {
+                    format!("~ {name} {from} {arrow} {to}").yellow()
+                }
+            };
+            block.push_str(&format!("\n  {line}"));
+        }
+        tracing::info!("{block}");
+    }
+
+    fn print_dry_run_report(&self, report: &DryRunReport) {
+        let relative = |path: &Path| {
+            path.strip_prefix(&self.root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        for path in &report.removed {
+            println!("{}", t!("dry-run-remove", path = relative(path)));
+        }
+        for package in &report.extracted {
+            println!(
+                "{}",
+                t!(
+                    "dry-run-extract",
+                    name = package.name.clone(),
+                    resolved = package.resolved.clone(),
+                    path = relative(&package.path),
+                )
+            );
+        }
+        for bin in &report.linked {
+            println!(
+                "{}",
+                t!(
+                    "dry-run-link",
+                    to = relative(&bin.to),
+                    from = relative(&bin.from)
+                )
+            );
+        }
+        println!(
+            "{}",
+            t!(
+                "dry-run-summary",
+                removed = report.removed.len(),
+                extracted = report.extracted.len(),
+                linked = report.linked.len(),
+            )
+        );
+    }
+
+    /// Resolves the dependency tree for `manifest` with the configured
+    /// options, without touching `node_modules/` or the lockfile.
+    pub async fn resolve_only(&self, manifest: CorgiManifest) -> Result<NodeMaintainer> {
+        let progress = ApplyProgress::new(self.emoji);
+        let builder = self.with_progress(self.configured_maintainer()?, &progress);
+        self.resolve(manifest, builder, &progress, false).await
+    }
+
+    /// Like [`Self::resolve_only`], but packages that break the project's
+    /// policy are all recorded in the result, instead of failing on the
+    /// first one.
+    pub async fn resolve_reporting_policy(
+        &self,
+        manifest: CorgiManifest,
+    ) -> Result<NodeMaintainer> {
+        let progress = ApplyProgress::new(self.emoji);
+        let builder = self.with_progress(
+            self.configured_maintainer()?.report_policy_violations(true),
+            &progress,
+        );
+        self.resolve(manifest, builder, &progress, false).await
+    }
+
+    /// A registry client with the configured credentials, proxies, and TLS
+    /// settings, for talking to the registry directly.
+    pub fn client(&self) -> Result<OroClient> {
+        let mut client = OroClient::builder().registry(self.registry.clone());
+        for (host, credentials) in crate::registry_credentials(&self.root, &self.credentials)? {
+            client = client.credentials(host, credentials);
+        }
+        if let Some(proxy) = &self.proxy {
+            client = client.proxy(proxy.clone());
+        }
+        if let Some(proxy) = &self.https_proxy {
+            client = client.https_proxy(proxy.clone());
+        }
+        if let Some(hosts) = &self.noproxy {
+            client = client.no_proxy(hosts);
+        }
+        let (tls, host_tls) = crate::TlsArgs {
+            cafile: self.cafile.as_deref(),
+            ca: &self.ca,
+            strict_ssl: self.strict_ssl,
+            cert: self.cert.as_deref(),
+            key: self.key.as_deref(),
+            registry_certs: &self.registry_certs,
+            registry_keys: &self.registry_keys,
+        }
+        .configs()?;
+        if let Some(tls) = tls {
+            client = client.tls(tls);
+        }
+        for (host, tls) in host_tls {
+            client = client.host_tls(host, tls);
+        }
+        Ok(client.build())
+    }
+
+    /// Reads the manifest to apply, as JSON: the project's `package.json`,
+    /// or a stand-in for it that lists the global packages, with
+    /// `--global`.
+    pub async fn read_manifest(&self) -> Result<String> {
+        match self.global_prefix()? {
+            Some(prefix) => {
+                serde_json::to_string(&node_maintainer::global_manifest(&prefix).await?)
+                    .into_diagnostic()
+            }
+            None => async_std::fs::read_to_string(self.root.join("package.json"))
+                .await
+                .into_diagnostic(),
+        }
+    }
+
+    /// The `node_modules/` directory this apply works on.
+    pub fn node_modules_dir(&self) -> Result<PathBuf> {
+        Ok(match self.global_prefix()? {
+            Some(prefix) => node_maintainer::global_root(&prefix).join("node_modules"),
+            None => self.root.join("node_modules"),
+        })
+    }
+
+    /// The prefix to install global packages into, if `--global` was used.
+    fn global_prefix(&self) -> Result<Option<PathBuf>> {
+        if !self.global {
+            return Ok(None);
+        }
+        self.global_prefix
+            .clone()
+            .or_else(node_maintainer::default_global_prefix)
+            .map(Some)
+            .ok_or_else(|| OroError::NoGlobalPrefix.into())
+    }
+
+    fn configured_maintainer(&self) -> Result<NodeMaintainerOptions> {
         let root = &self.root;
         let mut nm = NodeMaintainerOptions::new();
         nm = nm
             .registry(self.registry.clone())
             .locked(self.locked)
+            .dedupe(self.dedupe)
+            .force(self.force)
             .default_tag(&self.default_tag)
+            .resolution_mode(self.resolution_mode)
+            .explain_resolution(!self.explain_resolution.is_empty())
             .concurrency(self.concurrency)
-            .script_concurrency(self.script_concurrency)
             .root(root)
             .prefer_copy(self.prefer_copy)
-            .validate(self.validate)
+            .mmap(self.mmap)
+            .validate(self.validate || self.repair)
+            .repair(self.repair)
+            .least_privilege(self.least_privilege)
+            .node_linker(self.node_linker)
             .hoisted(self.hoisted)
+            .pnp(self.pnp)
+            .global_store(self.global_store)
+            .side_effects_cache(self.side_effects_cache)
+            .strict_bins(self.strict_bins)
+            .bin_symlinks(self.bin_symlinks)
+            .dry_run(self.dry_run)
+            .takeover(self.takeover)
+            .hook_scripts(self.hook_scripts)
+            .allow_scripts(self.allow_scripts)
+            .sandbox_scripts(self.sandbox_scripts)
+            .sandbox_exempt(&self.sandbox_exempt)
+            // Foreground scripts would write all over the events.
+            .foreground_scripts(self.foreground_scripts && !self.reporter.is_ndjson())
+            .force_rebuild(self.force_rebuild)
+            .prefer_workspace_packages(self.prefer_workspace_packages)
+            .link_workspaces(self.link_workspaces)
             .on_resolution_added(move || {
                 Span::current().pb_inc_length(1);
             })
@@ -183,65 +1247,238 @@ impl ApplyArgs {
                 span.pb_inc(1);
                 span.pb_set_message(&format!("{}", path.display()));
             })
-            .on_extract_progress(move |pkg| {
-                let span = Span::current();
-                span.pb_inc(1);
-                span.pb_set_message(&format!("{:?}", pkg.resolved()))
-            })
-            .on_script_start(|pkg, event| {
-                let span = Span::current();
-                span.pb_set_style(
-                    &ProgressStyle::default_bar()
-                        .template(&format!(
-                            "{{span_child_prefix}}{{spinner}} {}::{event} ({{elapsed}}): {{wide_msg:.dim}}",
-                            pkg.name(),
-                        ))
-                        .unwrap(),
-                );
-            })
             .on_script_line(|line| {
                 let span = Span::current();
                 span.pb_inc(1);
                 span.pb_set_message(line);
             });
 
+        for mirror in &self.mirrors {
+            nm = nm.mirror(mirror.clone());
+        }
+
+        for rewrite in &self.tarball_rewrites {
+            nm = nm.tarball_rewrite(rewrite.clone());
+        }
+
         for (scope, registry) in &self.scoped_registries {
             nm = nm.scope_registry(scope, registry.clone());
         }
 
+        for (host, credentials) in crate::registry_credentials(&self.root, &self.credentials)? {
+            nm = nm.credentials(host, credentials);
+        }
+        for host in crate::always_auth_hosts(&self.root, &self.credentials) {
+            nm = nm.always_auth(host);
+        }
+
         if let Some(cache) = self.cache.as_deref() {
             nm = nm.cache(cache);
         }
 
-        nm
+        if let Some(proxy) = &self.proxy {
+            nm = nm.proxy(proxy.clone());
+        }
+
+        if let Some(proxy) = &self.https_proxy {
+            nm = nm.https_proxy(proxy.clone());
+        }
+
+        if let Some(hosts) = &self.noproxy {
+            nm = nm.no_proxy(hosts);
+        }
+
+        let (tls, host_tls) = crate::TlsArgs {
+            cafile: self.cafile.as_deref(),
+            ca: &self.ca,
+            strict_ssl: self.strict_ssl,
+            cert: self.cert.as_deref(),
+            key: self.key.as_deref(),
+            registry_certs: &self.registry_certs,
+            registry_keys: &self.registry_keys,
+        }
+        .configs()?;
+        if let Some(tls) = tls {
+            nm = nm.tls(tls);
+        }
+        for (host, tls) in host_tls {
+            nm = nm.host_tls(host, tls);
+        }
+
+        if let Some(limit) = self.network_limit {
+            nm = nm.network_limit(limit);
+        }
+
+        if let Some(max) = self.max_sockets {
+            nm = nm.max_sockets(max);
+        }
+
+        if let Some(max) = self.max_sockets_per_host {
+            nm = nm.max_sockets_per_host(max);
+        }
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            nm = nm.pool_idle_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            nm = nm.connect_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.tls_handshake_timeout {
+            nm = nm.tls_handshake_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.first_byte_timeout {
+            nm = nm.first_byte_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.request_timeout {
+            nm = nm.request_timeout(timeout);
+        }
+
+        if let Some(ttl) = self.dns_ttl {
+            nm = nm.dns_ttl(ttl);
+        }
+
+        if let Some(age) = self.minimum_release_age {
+            nm = nm.minimum_release_age(age);
+        }
+
+        if let Some(before) = self.before {
+            nm = nm.before(before);
+        }
+
+        for pattern in &self.require_provenance {
+            nm = nm.require_provenance(pattern);
+        }
+
+        nm = nm.verify_signatures(self.verify_signatures);
+
+        if let Some(timeout) = self.script_timeout {
+            nm = nm.script_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.total_script_timeout {
+            nm = nm.total_script_timeout(timeout);
+        }
+
+        if let Some(concurrency) = self.script_concurrency {
+            nm = nm.script_concurrency(concurrency);
+        }
+
+        if self.reporter.is_ndjson() {
+            // Script output is reported as events instead of printed.
+            nm = nm
+                .on_event(reporter::install_event)
+                .request_observer(Arc::new(NdjsonObserver));
+        } else if let Some(mode) = self.script_output {
+            nm = nm.script_output(ScriptOutput::new(mode));
+        }
+
+        if let Some(shell) = &self.script_shell {
+            nm = nm.script_shell(shell);
+        }
+
+        if !self.prune_keep.is_empty() {
+            nm = nm.prune_keep(&self.prune_keep);
+        }
+        if !self.public_hoist_pattern.is_empty() {
+            nm = nm.public_hoist_patterns(&self.public_hoist_pattern);
+        }
+        if !self.hoist_pattern.is_empty() {
+            nm = nm.hoist_patterns(&self.hoist_pattern);
+        }
+        if !self.hook_script_patterns.is_empty() {
+            nm = nm.hook_script_patterns(&self.hook_script_patterns);
+        }
+        if !self.link_strategy.is_empty() {
+            nm = nm.link_strategies(self.link_strategy.iter().copied());
+        }
+
+        for (spec, patch) in &self.patched_dependencies {
+            nm = nm.patched_dependency(spec, patch);
+        }
+
+        for (name, spec) in &self.catalog {
+            nm = nm.catalog(name, spec);
+        }
+
+        if let Some(prefix) = self.global_prefix()? {
+            nm = nm
+                .script_config("global", "true")
+                .script_config("prefix", prefix.to_string_lossy())
+                .global(prefix);
+        }
+
+        // Install scripts get the effective configuration, like they would
+        // under npm. Plenty of them look at `npm_config_user_agent` to find
+        // out which package manager is running them.
+        nm = nm
+            .script_config(
+                "user-agent",
+                format!(
+                    "oro/{} {} {}",
+                    env!("CARGO_PKG_VERSION"),
+                    std::env::consts::OS,
+                    std::env::consts::ARCH
+                ),
+            )
+            .script_config("registry", self.registry.as_str())
+            .script_config("tag", &self.default_tag);
+        for (scope, registry) in &self.scoped_registries {
+            let scope = scope.strip_prefix('@').unwrap_or(scope);
+            nm = nm.script_config(format!("@{scope}:registry"), registry.as_str());
+        }
+        if let Some(cache) = self.cache.as_deref() {
+            nm = nm.script_config("cache", cache.to_string_lossy());
+        }
+
+        if let Some(nassun) = &self.nassun {
+            nm = nm.nassun(nassun.clone());
+        }
+
+        Ok(nm)
+    }
+
+    /// Builds a client with the configured options that keeps every
+    /// packument it fetches in memory, to be shared between resolutions
+    /// through [`ApplyArgs::nassun`].
+    pub fn memoizing_nassun(&self) -> Result<Nassun> {
+        Ok(self
+            .configured_maintainer()?
+            .memoize_metadata(true)
+            .build_nassun())
+    }
+
+    /// Shows `progress` for everything `builder` does, unless progress is
+    /// being reported as events instead.
+    fn with_progress(
+        &self,
+        builder: NodeMaintainerOptions,
+        progress: &Arc<ApplyProgress>,
+    ) -> NodeMaintainerOptions {
+        if self.reporter.is_ndjson() {
+            builder
+        } else {
+            progress.attach(builder)
+        }
     }
 
     async fn resolve(
         &self,
         root_manifest: CorgiManifest,
         builder: NodeMaintainerOptions,
+        progress: &ApplyProgress,
+        installing: bool,
     ) -> Result<NodeMaintainer> {
-        // Set up progress bar and timing stuff.
         let resolve_time = std::time::Instant::now();
-        let resolve_span = tracing::debug_span!("resolving");
-        resolve_span.pb_set_style(
-            &ProgressStyle::default_bar()
-                .template(&format!(
-                    "{}Resolving {}",
-                    self.emoji_magnifying_glass(),
-                    "{bar:40} [{pos}/{len}] {wide_msg:.dim}"
-                ))
-                .unwrap(),
-        );
-        resolve_span.pb_set_length(0);
-        let resolve_span_enter = resolve_span.enter();
+        progress.start_resolving(installing);
 
         // Actually do a resolve.
         let resolved_nm = builder.resolve_manifest(root_manifest).await?;
 
-        // Wrap up progress bar and print messages.
-        std::mem::drop(resolve_span_enter);
-        std::mem::drop(resolve_span);
+        progress.finish_resolving(resolved_nm.package_count());
         tracing::info!(
             "{}Resolved {} packages in {}s.",
             self.emoji_magnifying_glass(),
@@ -252,6 +1489,35 @@ impl ApplyArgs {
         Ok(resolved_nm)
     }
 
+    async fn check_determinism(&self, manifest: CorgiManifest) -> Result<()> {
+        let check_time = std::time::Instant::now();
+        let check_span = tracing::debug_span!("check_determinism");
+        check_span.pb_set_style(
+            &ProgressStyle::default_bar()
+                .template(&format!(
+                    "{}Checking determinism {}",
+                    self.emoji_magnifying_glass(),
+                    "{bar:40} [{pos}/{len}] {wide_msg:.dim}"
+                ))
+                .unwrap(),
+        );
+        check_span.pb_set_length(0);
+        let check_span_enter = check_span.enter();
+
+        self.configured_maintainer()?
+            .check_determinism(manifest)
+            .await?;
+
+        std::mem::drop(check_span_enter);
+        std::mem::drop(check_span);
+        tracing::info!(
+            "{}Resolution is deterministic. Checked in {}s.",
+            self.emoji_magnifying_glass(),
+            check_time.elapsed().as_millis() as f32 / 1000.0
+        );
+        Ok(())
+    }
+
     async fn prune(&self, maintainer: &NodeMaintainer) -> Result<usize> {
         // Set up progress bar and timing stuff.
         let prune_time = std::time::Instant::now();
@@ -283,28 +1549,18 @@ impl ApplyArgs {
         Ok(pruned)
     }
 
-    async fn extract(&self, maintainer: &NodeMaintainer) -> Result<usize> {
-        // Set up progress bar and timing stuff.
+    async fn extract(
+        &self,
+        maintainer: &NodeMaintainer,
+        progress: &ApplyProgress,
+    ) -> Result<usize> {
         let extract_time = std::time::Instant::now();
-        let extract_span = tracing::debug_span!("extract");
-        extract_span.pb_set_style(
-            &ProgressStyle::default_bar()
-                .template(&format!(
-                    "{}Extracting {}",
-                    self.emoji_package(),
-                    "{bar:40} [{pos}/{len}] {wide_msg:.dim}"
-                ))
-                .unwrap(),
-        );
-        extract_span.pb_set_length(maintainer.package_count() as u64);
-        let extract_span_enter = extract_span.enter();
+        progress.start_extracting();
 
         // Actually do the extraction.
         let extracted = maintainer.extract().await?;
 
-        // Wrap up progress bar and message.
-        std::mem::drop(extract_span_enter);
-        std::mem::drop(extract_span);
+        progress.finish_extracting();
         tracing::info!(
             "{}Extracted {extracted} package{} in {}s.",
             self.emoji_package(),
@@ -315,27 +1571,28 @@ impl ApplyArgs {
         Ok(extracted)
     }
 
-    async fn rebuild(&self, maintainer: &NodeMaintainer) -> Result<()> {
+    async fn rebuild(&self, maintainer: &NodeMaintainer, progress: &ApplyProgress) -> Result<()> {
         let script_time = std::time::Instant::now();
-        let script_span = if self.scripts {
+        // Foreground and printed scripts write straight to the terminal, so
+        // there's no progress bar for them to fight with.
+        let shows_progress = !self.foreground_scripts
+            && matches!(self.script_output, None | Some(ScriptOutputMode::Progress));
+        let script_span = if !shows_progress {
+            Span::none()
+        } else if self.scripts {
             tracing::info_span!("Building")
         } else {
             tracing::debug_span!("Building")
         };
-        if self.scripts {
-            script_span.pb_set_style(
-                &ProgressStyle::default_bar()
-                    .template(&format!(
-                        "{{spinner}} {}Running scripts {{wide_msg:.dim}}",
-                        self.emoji_run(),
-                    ))
-                    .unwrap(),
-            );
+        if self.scripts && shows_progress {
+            progress.start_scripts(&script_span);
         }
-        maintainer
+        let rebuilt = maintainer
             .rebuild(!self.scripts)
             .instrument(script_span)
-            .await?;
+            .await;
+        progress.finish_scripts();
+        rebuilt?;
         if self.scripts {
             tracing::info!(
                 "{}Ran lifecycle scripts in {}s.",
@@ -349,6 +1606,22 @@ impl ApplyArgs {
                 script_time.elapsed().as_millis() as f32 / 1000.0
             );
         }
+        let blocked = maintainer.blocked_scripts();
+        if !blocked.is_empty() {
+            tracing::warn!(
+                "Skipped install scripts for {} untrusted {}:",
+                blocked.len(),
+                if blocked.len() == 1 {
+                    "dependency"
+                } else {
+                    "dependencies"
+                }
+            );
+            for blocked in &blocked {
+                tracing::warn!("  {} ({})", blocked.name, blocked.events.join(", "));
+            }
+            tracing::warn!("Add them to `trustedDependencies` in package.json to run their scripts, or pass `--allow-scripts all` to run every dependency's scripts.");
+        }
         Ok(())
     }
 
@@ -391,6 +1664,101 @@ impl ApplyArgs {
 
 // Inspired and brazenly taken from SLIME:
 // https://github.com/slime/slime/blob/e193bc5f3431a2f71f1d7a0e3f28e6dc4dd5de2d/slime.el#L1360-L1375
+fn seconds_since(start: Instant) -> f32 {
+    start.elapsed().as_millis() as f32 / 1000.0
+}
+
+/// How many packages were added, removed, and changed going from `before`
+/// to `after`. Everything is new if there was nothing before.
+fn package_changes(before: Option<&Lockfile>, after: &Lockfile) -> (usize, usize, usize) {
+    let Some(before) = before else {
+        return (after.packages().len(), 0, 0);
+    };
+    let (mut added, mut changed) = (0, 0);
+    for (path, pkg) in after.packages() {
+        match before.packages().get(path) {
+            None => added += 1,
+            Some(old) if old.version != pkg.version || old.resolved != pkg.resolved => changed += 1,
+            Some(_) => {}
+        }
+    }
+    let removed = before
+        .packages()
+        .keys()
+        .filter(|path| !after.packages().contains_key(*path))
+        .count();
+    (added, removed, changed)
+}
+
+/// How a single package changed between two lockfiles.
+enum LockfileChange {
+    Added {
+        name: String,
+        version: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    Changed {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Which packages were added, removed, or changed going from `before` to
+/// `after`, sorted by name. Packages are compared by name rather than by
+/// where they're placed, so packages that just moved around don't show up,
+/// and a package with exactly one version swapped for another counts as
+/// changed.
+fn lockfile_diff(before: &Lockfile, after: &Lockfile) -> Vec<LockfileChange> {
+    fn versions(lockfile: &Lockfile) -> BTreeMap<String, BTreeSet<String>> {
+        let mut versions = BTreeMap::<_, BTreeSet<_>>::new();
+        for node in lockfile.packages().values().filter(|node| !node.is_root) {
+            let version = node
+                .version
+                .as_ref()
+                .map(|version| version.to_string())
+                .or_else(|| node.resolved.clone())
+                .unwrap_or_default();
+            versions
+                .entry(node.name.to_string())
+                .or_default()
+                .insert(version);
+        }
+        versions
+    }
+    let before = versions(before);
+    let after = versions(after);
+    let empty = BTreeSet::new();
+    let names = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+    let mut changes = Vec::new();
+    for name in names {
+        let old = before.get(name).unwrap_or(&empty);
+        let new = after.get(name).unwrap_or(&empty);
+        let removed = old.difference(new).collect::<Vec<_>>();
+        let added = new.difference(old).collect::<Vec<_>>();
+        if let ([from], [to]) = (&removed[..], &added[..]) {
+            changes.push(LockfileChange::Changed {
+                name: name.clone(),
+                from: (*from).clone(),
+                to: (*to).clone(),
+            });
+            continue;
+        }
+        changes.extend(removed.into_iter().map(|version| LockfileChange::Removed {
+            name: name.clone(),
+            version: version.clone(),
+        }));
+        changes.extend(added.into_iter().map(|version| LockfileChange::Added {
+            name: name.clone(),
+            version: version.clone(),
+        }));
+    }
+    changes
+}
+
 fn hackerish_encouragement() -> &'static str {
     let encouragements = [
         "Let the hacking commence!",
@@ -411,3 +1779,28 @@ fn hackerish_encouragement() -> &'static str {
         .choose(&mut rng)
         .expect("Iterator should not be empty.")
 }
+
+/// Turns `timings` into a [`TimingReport`], with only the slowest packages.
+fn timing_report(timings: &Timings, total: f32, resolve: f32) -> TimingReport {
+    TimingReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        total,
+        resolve,
+        metadata: timings.metadata.as_secs_f32(),
+        download: timings.download.as_secs_f32(),
+        extract: timings.extract.as_secs_f32(),
+        bins: timings.bins.as_secs_f32(),
+        scripts: timings.scripts.as_secs_f32(),
+        slowest: timings
+            .packages
+            .iter()
+            .take(SLOWEST_PACKAGES)
+            .map(|package| PackageTimingReport {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                extract: package.extract.as_secs_f32(),
+                scripts: package.scripts.as_secs_f32(),
+            })
+            .collect(),
+    }
+}
@@ -2,11 +2,13 @@ use async_trait::async_trait;
 use clap::Args;
 use miette::{IntoDiagnostic, Result};
 use nassun::PackageResolution;
+use node_maintainer::glob_match;
 use oro_common::CorgiManifest;
 use oro_package_spec::{PackageSpec, VersionSpec};
 use oro_pretty_json::Formatted;
+use serde::Serialize;
 
-use crate::apply_args::ApplyArgs;
+use crate::apply_args::{ApplyArgs, ApplySummary};
 use crate::commands::OroCommand;
 use crate::nassun_args::NassunArgs;
 
@@ -19,17 +21,52 @@ pub struct AddCmd {
 
     /// Prefix to prepend to package versions for resolved NPM dependencies.
     ///
-    /// For example, if you do `oro add foo@1.2.3 --prefix ~`, this will write `"foo": "~1.2.3"` to your `package.json`.
-    #[arg(long, default_value = "^")]
-    prefix: String,
+    /// Can be `^`, `~`, or an empty string to save exact versions. For
+    /// example, if you do `oro add foo@1.2.3 --save-prefix ~`, this will write
+    /// `"foo": "~1.2.3"` to your `package.json`. Set `save-prefix` in your
+    /// project's `oro.kdl` to change the default.
+    #[arg(
+        long,
+        alias = "prefix",
+        default_value = "^",
+        value_parser = parse_save_prefix
+    )]
+    save_prefix: String,
+
+    /// Save exact versions for resolved NPM dependencies, ignoring
+    /// `--save-prefix`.
+    #[arg(long, short = 'E')]
+    save_exact: bool,
 
     /// Add packages as devDependencies.
-    #[arg(long, short = 'D')]
-    dev: bool,
+    ///
+    /// Set `save-dev` in your project's `oro.kdl` to add packages as
+    /// devDependencies by default.
+    #[arg(long, short = 'D', alias = "dev")]
+    save_dev: bool,
 
     /// Add packages as optionalDependencies.
-    #[arg(long, short = 'O', visible_alias = "optional")]
-    opt: bool,
+    #[arg(long, short = 'O', visible_alias = "optional", alias = "opt")]
+    save_optional: bool,
+
+    /// Add packages as regular dependencies, even if `save-dev` is set, or
+    /// they match a `--save-dev-pattern`.
+    #[arg(long, short = 'P', alias = "prod")]
+    save_prod: bool,
+
+    /// Add packages whose names match this pattern as devDependencies,
+    /// unless another dependency type is requested.
+    ///
+    /// `*` matches any sequence of characters, so `@types/*` matches all
+    /// TypeScript type packages. Can be provided multiple times, or as a
+    /// list with `save-dev-pattern` in `oro.kdl`.
+    #[arg(long, value_name = "PATTERN")]
+    save_dev_pattern: Vec<String>,
+
+    /// Keep the order dependencies are already in when adding new ones to
+    /// `package.json`, instead of sorting them by name.
+    #[arg(long = "no-sort-dependencies", action = clap::ArgAction::SetFalse)]
+    sort_dependencies: bool,
 
     #[command(flatten)]
     apply: ApplyArgs,
@@ -38,16 +75,12 @@ pub struct AddCmd {
 #[async_trait]
 impl OroCommand for AddCmd {
     async fn execute(mut self) -> Result<()> {
-        let mut manifest = oro_pretty_json::from_str(
-            &async_std::fs::read_to_string(self.apply.root.join("package.json"))
-                .await
-                .into_diagnostic()?,
-        )
-        .into_diagnostic()?;
-        let nassun = NassunArgs::from_apply_args(&self.apply).to_nassun();
+        let mut manifest =
+            oro_pretty_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        let nassun = NassunArgs::from_apply_args(&self.apply).to_nassun()?;
         use PackageResolution as Pr;
         use PackageSpec as Ps;
-        let mut count = 0;
+        let mut added = Vec::new();
         for spec in &self.specs {
             let pkg = nassun.resolve(spec).await?;
             let name = pkg.name();
@@ -59,6 +92,8 @@ impl OroCommand for AddCmd {
                 Ps::Git(info) => {
                     format!("{info}")
                 }
+                Ps::Url { url } => url.to_string(),
+                Ps::Link { path } => format!("link:{}", path.to_string_lossy()),
                 Ps::Dir { path } => {
                     {
                         // TODO: make relative to root?
@@ -77,7 +112,7 @@ impl OroCommand for AddCmd {
                         Ps::Npm { requested, .. } => {
                             // We use Tag in a hacky way here to have some level of "preserved" formatting.
                             *requested =
-                                Some(VersionSpec::Tag(format!("{}{version}", self.prefix)));
+                                Some(VersionSpec::Tag(format!("{}{version}", self.save_prefix())));
                         }
                         _ => {
                             unreachable!("No other type of spec should be here.");
@@ -90,9 +125,14 @@ impl OroCommand for AddCmd {
                 "{}Resolved {spec} to {name}@{resolved_spec}.",
                 if self.apply.emoji { "🔍 " } else { "" }
             );
+            let deps = self.dep_kind_str(name);
             self.remove_from_manifest(&mut manifest, name);
-            self.add_to_manifest(&mut manifest, name, &resolved_spec);
-            count += 1;
+            self.add_to_manifest(&mut manifest, deps, name, &resolved_spec);
+            added.push(Added {
+                name: name.into(),
+                spec: resolved_spec,
+                dep_type: deps,
+            });
         }
 
         if self.apply.locked {
@@ -107,7 +147,18 @@ impl OroCommand for AddCmd {
                 .into_diagnostic()?;
 
         // Then, we apply the change.
-        self.apply.execute(corgi).await?;
+        let summary = self.apply.execute(corgi).await?;
+        if self.apply.json {
+            self.apply.print_json(&AddOutput {
+                added: &added,
+                summary,
+            })?;
+        }
+
+        // Global installs don't have a package.json to update.
+        if self.apply.dry_run || self.apply.global {
+            return Ok(());
+        }
 
         async_std::fs::write(
             self.apply.root.join("package.json"),
@@ -116,13 +167,21 @@ impl OroCommand for AddCmd {
         .await
         .into_diagnostic()?;
 
+        // Packages that ended up in different places get counted together.
+        let count = added.len();
+        let deps = match added.first() {
+            Some(first) if added.iter().all(|added| added.dep_type == first.dep_type) => {
+                first.dep_type
+            }
+            _ => "dependencies",
+        };
         tracing::info!(
             "{}Updated package.json with {count} new {}.",
             if self.apply.emoji { "📝 " } else { "" },
             if count == 1 {
-                self.dep_kind_str_singular()
+                dep_kind_str_singular(deps)
             } else {
-                self.dep_kind_str()
+                deps
             }
         );
 
@@ -130,12 +189,45 @@ impl OroCommand for AddCmd {
     }
 }
 
+/// A dependency added to `package.json`.
+#[derive(Debug, Serialize)]
+struct Added {
+    name: String,
+    /// What was saved for it, like `^1.2.3`.
+    spec: String,
+    /// Which kind of dependency it was saved as, like `devDependencies`.
+    #[serde(rename = "type")]
+    dep_type: &'static str,
+}
+
+/// What `oro add --json` prints.
+#[derive(Debug, Serialize)]
+struct AddOutput<'a> {
+    added: &'a [Added],
+    #[serde(flatten)]
+    summary: Option<ApplySummary>,
+}
+
 impl AddCmd {
-    fn add_to_manifest(&self, mani: &mut Formatted, name: &str, spec: &str) {
-        let deps = self.dep_kind_str();
+    fn save_prefix(&self) -> &str {
+        if self.save_exact {
+            ""
+        } else {
+            &self.save_prefix
+        }
+    }
+
+    fn add_to_manifest(&self, mani: &mut Formatted, deps: &str, name: &str, spec: &str) {
         tracing::debug!("Adding {name}@{spec} to {deps}.");
         mani.value[deps][name] =
             serde_json::to_value(spec).expect("Value is always a valid string");
+        if self.sort_dependencies {
+            if let Some(obj) = mani.value[deps].as_object_mut() {
+                let mut entries = std::mem::take(obj).into_iter().collect::<Vec<_>>();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                *obj = entries.into_iter().collect();
+            }
+        }
     }
 
     fn remove_from_manifest(&self, mani: &mut Formatted, name: &str) {
@@ -159,23 +251,35 @@ impl AddCmd {
         }
     }
 
-    fn dep_kind_str(&self) -> &'static str {
-        if self.dev {
-            "devDependencies"
-        } else if self.opt {
+    fn dep_kind_str(&self, name: &str) -> &'static str {
+        if self.save_prod {
+            "dependencies"
+        } else if self.save_optional {
             "optionalDependencies"
+        } else if self.save_dev
+            || self
+                .save_dev_pattern
+                .iter()
+                .any(|pattern| glob_match(pattern, name))
+        {
+            "devDependencies"
         } else {
             "dependencies"
         }
     }
+}
 
-    fn dep_kind_str_singular(&self) -> &'static str {
-        if self.dev {
-            "devDependency"
-        } else if self.opt {
-            "optionalDependency"
-        } else {
-            "dependency"
-        }
+fn dep_kind_str_singular(deps: &str) -> &'static str {
+    match deps {
+        "devDependencies" => "devDependency",
+        "optionalDependencies" => "optionalDependency",
+        _ => "dependency",
+    }
+}
+
+fn parse_save_prefix(prefix: &str) -> Result<String, String> {
+    match prefix {
+        "^" | "~" | "" => Ok(prefix.into()),
+        _ => Err("expected `^`, `~`, or an empty string".into()),
     }
 }
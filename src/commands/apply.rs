@@ -24,16 +24,16 @@ pub struct ApplyCmd {
 #[async_trait]
 impl OroCommand for ApplyCmd {
     async fn execute(mut self) -> Result<()> {
-        let corgi: CorgiManifest = serde_json::from_str(
-            &async_std::fs::read_to_string(self.apply.root.join("package.json"))
-                .await
-                .into_diagnostic()?,
-        )
-        .into_diagnostic()?;
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
         // Running `apply` with `--no-apply` doesn't make sense. We force it
         // here so that people can have `apply false` in their configurations
         // but have `oro apply` still work.
         self.apply.apply = true;
-        self.apply.execute(corgi).await
+        let summary = self.apply.execute(corgi).await?;
+        if self.apply.json {
+            self.apply.print_json(&summary)?;
+        }
+        Ok(())
     }
 }
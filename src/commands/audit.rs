@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use nassun::PackageResolution;
+use node_maintainer::{PolicyRule, PolicyViolation, TreePackage};
+use node_semver::Range;
+use oro_client::advisories::{Advisory, Severity};
+use oro_client::OroClientError;
+use oro_common::CorgiManifest;
+use oro_i18n::t;
+use url::Url;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+use crate::error::OroError;
+use crate::sarif::{Configuration, Level, Rule, RuleProperties, SarifLog};
+
+const POLICY_DOCS: &str = "https://orogene.dev/book/guide/node_modules.html#package-policies";
+const PROVENANCE_DOCS: &str = "https://orogene.dev/book/guide/node_modules.html#provenance";
+
+/// Check the current project's dependency tree for known vulnerabilities and
+/// policy violations.
+///
+/// The tree gets resolved the same way `oro apply` would do it, but nothing
+/// is installed. Every registry package is looked up in the registry's
+/// security advisories, and every package is checked against the project's
+/// `oro-policy.kdl` and `require-provenance` patterns. Unlike `oro apply`,
+/// which stops at the first policy violation, this reports all of them.
+///
+/// The command fails if it finds anything. Use `--sarif` to also write the
+/// results as a SARIF log, which GitHub code scanning and other CI security
+/// dashboards can ingest.
+#[derive(Debug, Args)]
+pub struct AuditCmd {
+    /// Also write the results to this file, as a SARIF log.
+    ///
+    /// The file is written before the command fails, so CI can upload it
+    /// either way.
+    #[arg(long, value_name = "PATH")]
+    sarif: Option<PathBuf>,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+/// An installed copy of a package that a security advisory applies to.
+struct Vulnerability {
+    name: String,
+    version: String,
+    /// Where the package is installed, like `node_modules/a/node_modules/b`.
+    path: String,
+    advisory: Advisory,
+}
+
+#[async_trait]
+impl OroCommand for AuditCmd {
+    async fn execute(self) -> Result<()> {
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        let maintainer = self.apply.resolve_reporting_policy(corgi).await?;
+        let vulnerabilities = self.vulnerabilities(&maintainer.tree()).await?;
+        let violations = maintainer.policy_violations();
+
+        if let Some(path) = &self.sarif {
+            let log = self.sarif_log(&vulnerabilities, violations);
+            let log = serde_json::to_string_pretty(&log)
+                .into_diagnostic()
+                .wrap_err("audit::serialize_sarif")?;
+            async_std::fs::write(path, log)
+                .await
+                .into_diagnostic()
+                .wrap_err("audit::write_sarif")?;
+            tracing::info!(
+                "{}",
+                t!("audit-sarif-written", path = path.display().to_string())
+            );
+        }
+
+        if self.apply.json {
+            let vulnerabilities = vulnerabilities
+                .iter()
+                .map(|vuln| {
+                    serde_json::json!({
+                        "name": vuln.name,
+                        "version": vuln.version,
+                        "path": vuln.path,
+                        "advisory": vuln.advisory,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let violations = violations
+                .iter()
+                .map(|violation| {
+                    serde_json::json!({
+                        "name": violation.name,
+                        "found": violation.found,
+                        "rule": violation.rule.id(),
+                        "reason": violation.reason,
+                        "path": violation.path,
+                    })
+                })
+                .collect::<Vec<_>>();
+            self.apply.print_json(&serde_json::json!({
+                "vulnerabilities": vulnerabilities,
+                "policyViolations": violations,
+            }))?;
+        } else if vulnerabilities.is_empty() && violations.is_empty() {
+            tracing::info!("{}", t!("audit-clean"));
+        } else {
+            for vuln in &vulnerabilities {
+                println!(
+                    "{}",
+                    t!(
+                        "audit-vulnerability",
+                        name = vuln.name.clone(),
+                        version = vuln.version.clone(),
+                        severity = severity_name(vuln.advisory.severity),
+                        title = vuln.advisory.title.clone(),
+                    )
+                );
+                println!("  {}", vuln.advisory.url);
+                println!("  {}", t!("audit-installed-at", path = vuln.path.clone()));
+            }
+            for violation in violations {
+                println!(
+                    "{}",
+                    t!(
+                        "audit-violation",
+                        name = violation.name.clone(),
+                        version = violation.found.clone(),
+                        reason = violation.reason.clone(),
+                        rule = violation.rule.id(),
+                    )
+                );
+                println!(
+                    "  {}",
+                    t!("audit-pulled-in-by", path = violation.path.join(" > "))
+                );
+            }
+        }
+
+        if vulnerabilities.is_empty() && violations.is_empty() {
+            Ok(())
+        } else {
+            Err(OroError::AuditFailed {
+                vulnerabilities: vulnerabilities.len(),
+                violations: violations.len(),
+            }
+            .into())
+        }
+    }
+}
+
+impl AuditCmd {
+    /// Installed registry packages that the registry has security
+    /// advisories for, most severe first.
+    async fn vulnerabilities(&self, tree: &[TreePackage]) -> Result<Vec<Vulnerability>> {
+        let installed = tree
+            .iter()
+            .filter_map(|pkg| match pkg.package.resolved() {
+                PackageResolution::Npm { version, .. } => Some((pkg, version)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut packages = HashMap::<String, Vec<String>>::new();
+        for (pkg, version) in &installed {
+            let versions = packages.entry(pkg.package.name().into()).or_default();
+            let version = version.to_string();
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        if packages.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let advisories = match self.apply.client()?.bulk_advisories(&packages).await {
+            Ok(advisories) => advisories,
+            Err(OroClientError::AuditUnsupported(url)) => {
+                tracing::warn!(
+                    "{}",
+                    t!("audit-advisories-unsupported", url = url.to_string())
+                );
+                return Ok(Vec::new());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut vulnerabilities = Vec::new();
+        for (pkg, version) in installed {
+            for advisory in advisories.get(pkg.package.name()).into_iter().flatten() {
+                // Advisories come back for every version that was asked
+                // about, so each copy only gets the ones that apply to it.
+                // Ranges the registry sends that can't be parsed are
+                // assumed to apply, rather than quietly ignored.
+                let applies = advisory
+                    .vulnerable_versions
+                    .parse::<Range>()
+                    .map(|range| range.satisfies(version))
+                    .unwrap_or(true);
+                if applies {
+                    vulnerabilities.push(Vulnerability {
+                        name: pkg.package.name().into(),
+                        version: version.to_string(),
+                        path: pkg.path.clone(),
+                        advisory: advisory.clone(),
+                    });
+                }
+            }
+        }
+        vulnerabilities.sort_by(|a, b| {
+            b.advisory
+                .severity
+                .cmp(&a.advisory.severity)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        Ok(vulnerabilities)
+    }
+
+    /// Results as a SARIF log. Everything gets reported against the
+    /// lockfile, since that's where each package in the tree is pinned, or
+    /// against `package.json` if there's no lockfile.
+    fn sarif_log(
+        &self,
+        vulnerabilities: &[Vulnerability],
+        violations: &[PolicyViolation],
+    ) -> SarifLog {
+        let file = ["package-lock.kdl", "package.json"]
+            .iter()
+            .map(|name| self.apply.root.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| self.apply.root.join("package.json"));
+        let uri = Url::from_file_path(&file)
+            .map(String::from)
+            .unwrap_or_else(|_| file.display().to_string());
+
+        let mut log = SarifLog::new();
+        for vuln in vulnerabilities {
+            let advisory = &vuln.advisory;
+            let level = match advisory.severity {
+                Severity::Critical | Severity::High => Level::Error,
+                Severity::Moderate => Level::Warning,
+                Severity::Low | Severity::Info => Level::Note,
+            };
+            let rule = Rule {
+                id: format!("advisory/{}", advisory.id),
+                short_description: advisory.title.clone().into(),
+                help_uri: Some(advisory.url.clone()),
+                default_configuration: Configuration { level },
+                properties: RuleProperties {
+                    tags: ["security", severity_name(advisory.severity)]
+                        .iter()
+                        .map(|tag| tag.to_string())
+                        .chain(advisory.cwe.iter().cloned())
+                        .collect(),
+                    security_severity: security_severity(advisory),
+                },
+            };
+            let message = format!(
+                "{}@{} has a {} severity vulnerability: {}",
+                vuln.name,
+                vuln.version,
+                severity_name(advisory.severity),
+                advisory.title,
+            );
+            log.add_result(rule, level, message, &uri, &vuln.path);
+        }
+        for violation in violations {
+            let message = format!(
+                "{}@{} {}. Pulled in by: {}",
+                violation.name,
+                violation.found,
+                violation.reason,
+                violation.path.join(" > "),
+            );
+            log.add_result(
+                policy_rule(violation.rule),
+                Level::Error,
+                message,
+                &uri,
+                &violation.path.join(" > "),
+            );
+        }
+        log
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Low => "low",
+        Severity::Moderate => "moderate",
+        Severity::High => "high",
+        Severity::Critical => "critical",
+    }
+}
+
+/// The advisory's CVSS score, or a score in the middle of the range for its
+/// severity if it doesn't have one.
+fn security_severity(advisory: &Advisory) -> Option<String> {
+    let score = match (&advisory.cvss, advisory.severity) {
+        (Some(cvss), _) if cvss.score > 0.0 => cvss.score,
+        (_, Severity::Critical) => 9.5,
+        (_, Severity::High) => 8.0,
+        (_, Severity::Moderate) => 5.5,
+        (_, Severity::Low) => 2.0,
+        (_, Severity::Info) => return None,
+    };
+    Some(format!("{score:.1}"))
+}
+
+fn policy_rule(rule: PolicyRule) -> Rule {
+    let (description, docs) = match rule {
+        PolicyRule::Package => ("Package is denied by the project's policy", POLICY_DOCS),
+        PolicyRule::License => (
+            "Package's license isn't allowed by the project's policy",
+            POLICY_DOCS,
+        ),
+        PolicyRule::Integrity => (
+            "Package's integrity hash is weaker than the project's policy requires",
+            POLICY_DOCS,
+        ),
+        PolicyRule::Provenance => (
+            "Package doesn't have the provenance the project requires",
+            PROVENANCE_DOCS,
+        ),
+    };
+    Rule {
+        id: format!("policy/{rule}"),
+        short_description: description.to_string().into(),
+        help_uri: Some(docs.into()),
+        default_configuration: Configuration {
+            level: Level::Error,
+        },
+        properties: RuleProperties {
+            tags: vec!["policy".into()],
+            security_severity: None,
+        },
+    }
+}
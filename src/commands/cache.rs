@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use nassun::cache::{BundleReport, CacheStats, RelocateReport};
+use node_maintainer::Lockfile;
+use oro_common::CorgiManifest;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// Fill the cache ahead of time, see what's in it, move it, or move
+/// packages between caches as a single bundle.
+///
+/// Bundles make it easy to prime the cache in CI, or to carry everything a
+/// project needs over to a machine without network access.
+#[derive(Debug, Args)]
+pub struct CacheCmd {
+    #[command(subcommand)]
+    action: CacheAction,
+
+    #[arg(from_global)]
+    root: PathBuf,
+
+    #[arg(from_global)]
+    cache: Option<PathBuf>,
+
+    #[arg(from_global)]
+    json: bool,
+
+    #[arg(from_global)]
+    emoji: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheAction {
+    /// Pack the cached packages in the project's lockfile into a bundle.
+    Export {
+        /// Where to write the bundle, usually ending in `.tgz`.
+        bundle: PathBuf,
+    },
+
+    /// Load a bundle made by `oro cache export` into the cache.
+    Import {
+        /// Bundle to load.
+        bundle: PathBuf,
+    },
+
+    /// Download every package in the project's dependency tree into the
+    /// cache, without installing anything.
+    ///
+    /// The tree comes from the lockfile, when there's an up-to-date one.
+    /// Handy for warming up CI images, or getting ready to work offline.
+    Prefetch {
+        #[command(flatten)]
+        apply: Box<ApplyArgs>,
+    },
+
+    /// Count what's in the cache, and how much space it takes up.
+    Stats,
+
+    /// Move the whole cache somewhere else, like onto a bigger disk.
+    ///
+    /// Projects installed from the old location keep working, except for
+    /// Plug'n'Play ones, which need another `oro apply`. Set `cache` in your
+    /// `oro.kdl` to the new location afterwards, so it gets used from then
+    /// on.
+    Move {
+        /// Where to move the cache to. Has to be empty or not exist yet.
+        to: PathBuf,
+    },
+}
+
+#[async_trait]
+impl OroCommand for CacheCmd {
+    async fn execute(self) -> Result<()> {
+        let cache = self
+            .cache
+            .clone()
+            .ok_or_else(|| miette!("No cache directory is configured."))?;
+        if let CacheAction::Prefetch { apply } = &self.action {
+            let corgi: CorgiManifest =
+                serde_json::from_str(&apply.read_manifest().await?).into_diagnostic()?;
+            let maintainer = apply.resolve_only(corgi).await?;
+            let fetched = maintainer.prefetch().await?;
+            return self.report_prefetch(fetched);
+        }
+        if let CacheAction::Stats = &self.action {
+            return self.report_stats(&nassun::cache::stats(&cache).await?);
+        }
+        if let CacheAction::Move { to } = &self.action {
+            let report = nassun::cache::relocate(&cache, to).await?;
+            return self.report_move(to, &report);
+        }
+        let (verb, report) = match &self.action {
+            CacheAction::Export { bundle } => {
+                let lock_path = self.root.join("package-lock.kdl");
+                let lockfile = Lockfile::from_kdl(
+                    async_std::fs::read_to_string(&lock_path)
+                        .await
+                        .into_diagnostic()
+                        .wrap_err_with(|| {
+                            format!(
+                                "Failed to read {}. Run `oro apply` to create it.",
+                                lock_path.display()
+                            )
+                        })?,
+                )?;
+                let integrities = lockfile
+                    .packages()
+                    .values()
+                    .filter_map(|pkg| pkg.integrity.clone());
+                let report = nassun::cache::export(&cache, integrities, bundle).await?;
+                for missing in &report.missing {
+                    tracing::warn!("{missing} isn't in the cache, so it wasn't exported.");
+                }
+                ("Exported", report)
+            }
+            CacheAction::Import { bundle } => {
+                ("Imported", nassun::cache::import(&cache, bundle).await?)
+            }
+            CacheAction::Prefetch { .. } | CacheAction::Stats | CacheAction::Move { .. } => {
+                unreachable!("handled above")
+            }
+        };
+        self.report(verb, &report)
+    }
+}
+
+impl CacheCmd {
+    fn report_prefetch(&self, fetched: usize) -> Result<()> {
+        if self.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "fetched": fetched,
+            }))
+            .into_diagnostic()
+            .wrap_err("cache::serialize")?;
+            println!("{output}");
+        } else {
+            tracing::info!(
+                "{}Downloaded {fetched} packages into the cache. Everything else was already there.",
+                if self.emoji { "📦 " } else { "" },
+            );
+        }
+        Ok(())
+    }
+
+    fn report_stats(&self, stats: &CacheStats) -> Result<()> {
+        if self.json {
+            let output = serde_json::to_string_pretty(stats)
+                .into_diagnostic()
+                .wrap_err("cache::serialize")?;
+            println!("{output}");
+        } else {
+            tracing::info!(
+                "{}{} entries, {} of them packages. {} files, taking up {} bytes.",
+                if self.emoji { "📦 " } else { "" },
+                stats.entries,
+                stats.packages,
+                stats.contents,
+                stats.content_bytes
+            );
+        }
+        Ok(())
+    }
+
+    fn report_move(&self, to: &Path, report: &RelocateReport) -> Result<()> {
+        if self.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "to": to,
+                "renamed": report.renamed,
+                "files_copied": report.files_copied,
+                "files_linked": report.files_linked,
+                "bytes_copied": report.bytes_copied,
+            }))
+            .into_diagnostic()
+            .wrap_err("cache::serialize")?;
+            println!("{output}");
+        } else {
+            if !report.renamed {
+                tracing::info!(
+                    "Copied {} files ({} bytes), and linked {} more.",
+                    report.files_copied,
+                    report.bytes_copied,
+                    report.files_linked
+                );
+            }
+            tracing::info!(
+                "{}Moved cache to {}. Set `cache` in your oro.kdl to use it from now on.",
+                if self.emoji { "📦 " } else { "" },
+                to.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn report(&self, verb: &str, report: &BundleReport) -> Result<()> {
+        if self.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "packages": report.packages,
+                "contents": report.contents,
+                "bytes": report.bytes,
+                "missing": report
+                    .missing
+                    .iter()
+                    .map(|sri| sri.to_string())
+                    .collect::<Vec<_>>(),
+            }))
+            .into_diagnostic()
+            .wrap_err("cache::serialize")?;
+            println!("{output}");
+        } else {
+            tracing::info!(
+                "{}{verb} {} packages ({} files, {} bytes).",
+                if self.emoji { "📦 " } else { "" },
+                report.packages,
+                report.contents,
+                report.bytes
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use node_maintainer::ProjectState;
+
+use crate::commands::OroCommand;
+
+/// Delete the project's `.oro/` state directory.
+///
+/// This directory holds Orogene's own per-project state, such as debug logs
+/// and install reports. None of it is needed to use the project's
+/// `node_modules/`, and it'll be recreated as needed.
+#[derive(Debug, Args)]
+pub struct CleanStateCmd {
+    #[arg(from_global)]
+    root: PathBuf,
+
+    #[arg(from_global)]
+    json: bool,
+
+    #[arg(from_global)]
+    emoji: bool,
+}
+
+#[async_trait]
+impl OroCommand for CleanStateCmd {
+    async fn execute(self) -> Result<()> {
+        let state = ProjectState::new(&self.root);
+        let removed = state.clean()?;
+        if self.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "path": state.dir().to_string_lossy(),
+                "removed": removed,
+            }))
+            .into_diagnostic()
+            .wrap_err("clean_state::serialize")?;
+            println!("{output}");
+        } else if removed {
+            tracing::info!(
+                "{}Removed {}",
+                if self.emoji { "🧹 " } else { "" },
+                state.dir().display()
+            );
+        } else {
+            tracing::info!("Nothing to clean up at {}", state.dir().display());
+        }
+        Ok(())
+    }
+}
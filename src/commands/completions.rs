@@ -0,0 +1,285 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use clap::{Arg, Args, Command, CommandFactory};
+use miette::Result;
+use serde_json::Value;
+
+use crate::commands::OroCommand;
+use crate::error::OroError;
+use crate::Orogene;
+
+/// Print a shell completion script.
+///
+/// Besides commands and options, the script completes script names for `oro
+/// run`, dependency names for `oro remove`, and option names for `oro config
+/// list`, by asking `oro` for them as you type. Supported shells are `bash`,
+/// `zsh`, `fish`, and `powershell`.
+///
+/// To load completions for every new shell, add one of these to your shell's
+/// startup file: `source <(oro completions bash)` for bash, `source <(oro
+/// completions zsh)` for zsh, `oro completions fish | source` for fish, or
+/// `oro completions powershell | Out-String | Invoke-Expression` for
+/// PowerShell.
+#[derive(Debug, Args)]
+pub struct CompletionsCmd {
+    /// Shell to print the completion script for.
+    #[arg()]
+    shell: Shell,
+}
+
+#[async_trait]
+impl OroCommand for CompletionsCmd {
+    async fn execute(self) -> Result<()> {
+        print!("{}", self.shell.script());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    fn script(&self) -> &'static str {
+        match self {
+            Self::Bash => BASH,
+            Self::Zsh => ZSH,
+            Self::Fish => FISH,
+            Self::Powershell => POWERSHELL,
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = OroError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "powershell" | "pwsh" => Ok(Self::Powershell),
+            _ => Err(OroError::UnknownShell(s.into())),
+        }
+    }
+}
+
+// Each script hands the words on the command line, and which one is being
+// completed, to `oro completion-helper`, and falls back to completing file
+// names when it has no suggestions.
+
+const BASH: &str = r#"_oro() {
+    local IFS=$'\n'
+    COMPREPLY=($(oro completion-helper "$COMP_CWORD" -- "${COMP_WORDS[@]}" 2>/dev/null))
+}
+complete -o default -F _oro oro
+"#;
+
+const ZSH: &str = r#"#compdef oro
+_oro() {
+    local -a completions
+    completions=("${(@f)$(oro completion-helper $((CURRENT - 1)) -- "${words[@]}" 2>/dev/null)}")
+    if [[ -n "${completions[*]}" ]]; then
+        compadd -a completions
+    else
+        _files
+    fi
+}
+if [[ "$funcstack[1]" == "_oro" ]]; then
+    _oro "$@"
+else
+    compdef _oro oro
+fi
+"#;
+
+const FISH: &str = r#"function __oro_complete
+    set -l tokens (commandline -opc)
+    oro completion-helper (count $tokens) -- $tokens (commandline -ct) 2>/dev/null
+end
+complete -c oro -a '(__oro_complete)'
+"#;
+
+const POWERSHELL: &str = r#"Register-ArgumentCompleter -Native -CommandName oro -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.StartOffset -lt $cursorPosition } |
+        ForEach-Object { $_.ToString() })
+    $index = $words.Count
+    if ($wordToComplete) { $index -= 1 }
+    oro completion-helper $index -- @words 2>$null | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }
+}
+"#;
+
+/// Prints suggestions for the word at `index` in `words`, one per line. Used
+/// by the scripts from `oro completions`.
+#[derive(Debug, Args)]
+pub struct CompletionHelperCmd {
+    /// Index of the word being completed. It might be one past the end of
+    /// `words` if there's nothing typed for it yet.
+    #[arg()]
+    index: usize,
+
+    /// Everything on the command line, starting with `oro` itself.
+    #[arg(last = true, allow_hyphen_values = true)]
+    words: Vec<String>,
+
+    #[arg(from_global)]
+    root: PathBuf,
+}
+
+#[async_trait]
+impl OroCommand for CompletionHelperCmd {
+    async fn execute(self) -> Result<()> {
+        let current = self.words.get(self.index).map(String::as_str).unwrap_or("");
+        let typed = self
+            .words
+            .iter()
+            .take(self.index)
+            .skip(1)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        for suggestion in suggestions(&Orogene::command(), &self.root, &typed, current) {
+            println!("{suggestion}");
+        }
+        Ok(())
+    }
+}
+
+/// Suggestions for `current`, given the words typed before it, without the
+/// leading `oro`.
+fn suggestions(root: &Command, project: &Path, typed: &[&str], current: &str) -> Vec<String> {
+    // Walk down to the subcommand being typed, keeping track of its parents
+    // so their global options can be suggested too.
+    let mut path = vec![root];
+    let mut pending: Option<&Arg> = None;
+    for word in typed {
+        let command = *path.last().unwrap();
+        if pending.take().is_some() || *word == "--" {
+            continue;
+        }
+        if let Some(long) = word.strip_prefix("--") {
+            if !long.contains('=') {
+                pending = find_arg(&path, |arg| arg.get_long() == Some(long))
+                    .filter(|arg| arg.get_action().takes_values());
+            }
+        } else if let Some(short) = word.strip_prefix('-').and_then(|s| s.chars().last()) {
+            if word.len() == 2 {
+                pending = find_arg(&path, |arg| arg.get_short() == Some(short))
+                    .filter(|arg| arg.get_action().takes_values());
+            }
+        } else if let Some(subcommand) = command
+            .get_subcommands()
+            .find(|sub| sub.get_name() == *word || sub.get_all_aliases().any(|a| a == *word))
+        {
+            path.push(subcommand);
+        }
+    }
+
+    let command = *path.last().unwrap();
+    let candidates = if let Some(arg) = pending {
+        arg.get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect()
+    } else if current.starts_with('-') {
+        let mut flags = BTreeSet::new();
+        for (depth, command) in path.iter().enumerate() {
+            let own = depth == path.len() - 1;
+            flags.extend(
+                command
+                    .get_arguments()
+                    .filter(|arg| (own || arg.is_global_set()) && !arg.is_hide_set())
+                    .filter_map(|arg| arg.get_long())
+                    .map(|long| format!("--{long}")),
+            );
+        }
+        flags.into_iter().collect()
+    } else if command.has_subcommands() {
+        command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_visible_aliases()))
+            .map(String::from)
+            .collect()
+    } else {
+        let names = path
+            .iter()
+            .skip(1)
+            .map(|c| c.get_name())
+            .collect::<Vec<_>>();
+        match &names[..] {
+            ["run"] => manifest_keys(project, &["scripts"]),
+            ["remove"] => manifest_keys(
+                project,
+                &[
+                    "dependencies",
+                    "devDependencies",
+                    "optionalDependencies",
+                    "peerDependencies",
+                ],
+            ),
+            ["config", "list"] => config_keys(root),
+            _ => Vec::new(),
+        }
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current))
+        .collect()
+}
+
+/// Finds an option on the innermost command in `path`, or a global one from
+/// any of its parents.
+fn find_arg<'a>(path: &[&'a Command], pred: impl Fn(&Arg) -> bool) -> Option<&'a Arg> {
+    path.iter().rev().enumerate().find_map(|(depth, command)| {
+        command
+            .get_arguments()
+            .find(|arg| (depth == 0 || arg.is_global_set()) && pred(arg))
+    })
+}
+
+/// Keys of the objects under `fields` in the project's `package.json`.
+fn manifest_keys(project: &Path, fields: &[&str]) -> Vec<String> {
+    let Ok(manifest) = std::fs::read_to_string(project.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = serde_json::from_str::<Value>(&manifest) else {
+        return Vec::new();
+    };
+    fields
+        .iter()
+        .filter_map(|field| manifest.get(field).and_then(Value::as_object))
+        .flat_map(|deps| deps.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Every option that can be set in `oro.kdl`, which is every long option of
+/// every command.
+fn config_keys(command: &Command) -> Vec<String> {
+    fn add(command: &Command, keys: &mut BTreeSet<String>) {
+        keys.extend(
+            command
+                .get_arguments()
+                .filter(|arg| arg.get_long().is_some() && !arg.is_hide_set())
+                .map(|arg| arg.get_id().to_string().replace('_', "-")),
+        );
+        for subcommand in command.get_subcommands() {
+            add(subcommand, keys);
+        }
+    }
+    let mut keys = BTreeSet::new();
+    add(command, &mut keys);
+    keys.into_iter().collect()
+}
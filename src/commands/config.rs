@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use oro_config::{ConfigOrigins, OroConfig};
+use serde_json::Value;
+
+use crate::commands::OroCommand;
+
+/// Options that are never shown, since they hold secrets.
+const SECRET_OPTIONS: &[&str] = &["credentials"];
+
+/// See how Orogene is configured, and where each option was set.
+#[derive(Debug, Args)]
+pub struct ConfigCmd {
+    #[command(subcommand)]
+    action: ConfigAction,
+
+    #[arg(from_global)]
+    json: bool,
+
+    /// The loaded config, with command line options layered on, and where
+    /// each option came from. Filled in once the config is loaded.
+    #[arg(skip)]
+    pub(crate) loaded: Option<(OroConfig, ConfigOrigins)>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    /// List every option that's set, from config files, environment
+    /// variables, and the command line, along with its value.
+    List {
+        /// Show where each option was set, like the `oro.kdl` file or
+        /// environment variable it came from.
+        #[arg(long)]
+        show_origin: bool,
+
+        /// Only list these options.
+        #[arg(value_name = "OPTION")]
+        options: Vec<String>,
+    },
+}
+
+#[async_trait]
+impl OroCommand for ConfigCmd {
+    async fn execute(self) -> Result<()> {
+        let (config, origins) = self
+            .loaded
+            .as_ref()
+            .ok_or_else(|| miette!("Config wasn't loaded."))?;
+        let ConfigAction::List {
+            show_origin,
+            options,
+        } = self.action;
+        let mut output = serde_json::Map::new();
+        for (key, origin) in origins.iter() {
+            if !options.is_empty() && !options.iter().any(|option| option == key) {
+                continue;
+            }
+            let value = if SECRET_OPTIONS.contains(&key) {
+                Value::String("<redacted>".into())
+            } else {
+                config.get::<Value>(key).unwrap_or(Value::Null)
+            };
+            if self.json {
+                let value = if show_origin {
+                    serde_json::json!({
+                        "value": value,
+                        "origin": origin.to_string(),
+                    })
+                } else {
+                    value
+                };
+                output.insert(key.into(), value);
+            } else if show_origin {
+                println!("{key} = {value} ({origin})");
+            } else {
+                println!("{key} = {value}");
+            }
+        }
+        if self.json {
+            let output = serde_json::to_string_pretty(&output)
+                .into_diagnostic()
+                .wrap_err("config::serialize")?;
+            println!("{output}");
+        }
+        Ok(())
+    }
+}
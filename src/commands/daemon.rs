@@ -0,0 +1,337 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use nassun::PackageResolution;
+use node_maintainer::{NodeMaintainer, ProjectState, TreePackage};
+use oro_common::CorgiManifest;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Used for requests that were valid, but failed.
+const SERVER_ERROR: i64 = -32000;
+
+/// Keeps the project's resolver warm in the background, and serves
+/// requests from it over a local socket.
+///
+/// Package metadata fetched by any request is kept in memory until the
+/// daemon exits, so repeated resolutions, like in editor integrations or
+/// big monorepos, skip straight to the work that changed. Restart the
+/// daemon to pick up newly-published versions.
+///
+/// Requests are JSON-RPC 2.0, one per line, over a Unix socket. The
+/// methods are `resolve`, `install`, `why` (with a `name` param), `ping`,
+/// and `shutdown`. Requests that change `node_modules/` or read the tree
+/// are handled one at a time, in the order they come in.
+#[derive(Debug, Args)]
+pub struct DaemonCmd {
+    /// Socket to listen on. Defaults to `.oro/daemon.sock` in the project.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[async_trait]
+impl OroCommand for DaemonCmd {
+    async fn execute(mut self) -> Result<()> {
+        let socket = match self.socket.take() {
+            Some(socket) => socket,
+            None => {
+                let state = ProjectState::new(&self.apply.root);
+                state.ensure()?;
+                state.daemon_socket()
+            }
+        };
+        self.apply.nassun = Some(self.apply.memoizing_nassun()?);
+        serve(Daemon::new(self.apply), socket).await
+    }
+}
+
+#[cfg(unix)]
+async fn serve(daemon: Daemon, socket: PathBuf) -> Result<()> {
+    use std::sync::Arc;
+
+    use async_std::io::BufReader;
+    use async_std::os::unix::net::{UnixListener, UnixStream};
+    use async_std::prelude::*;
+
+    use crate::error::OroError;
+
+    if UnixStream::connect(&socket).await.is_ok() {
+        return Err(OroError::DaemonAlreadyRunning(socket).into());
+    }
+    // Whatever's left is from a daemon that didn't get to clean up after
+    // itself, and would keep the socket from being bound.
+    let _ = async_std::fs::remove_file(&socket).await;
+    let listener = UnixListener::bind(&socket).await.into_diagnostic()?;
+    tracing::info!("Listening on {}.", socket.display());
+
+    let daemon = Arc::new(daemon);
+    let (stop, stopped) = async_std::channel::bounded::<()>(1);
+    loop {
+        let accepted = async { Some(listener.accept().await) };
+        let stopping = async {
+            let _ = stopped.recv().await;
+            None
+        };
+        let stream = match accepted.race(stopping).await {
+            Some(Ok((stream, _))) => stream,
+            Some(Err(err)) => {
+                tracing::warn!("Failed to accept a connection: {err}");
+                continue;
+            }
+            None => break,
+        };
+        let daemon = daemon.clone();
+        let stop = stop.clone();
+        async_std::task::spawn(async move {
+            let mut lines = BufReader::new(&stream).lines();
+            let mut writer = &stream;
+            while let Some(Ok(line)) = lines.next().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (response, shutdown) = daemon.respond(&line).await;
+                if let Some(response) = response {
+                    if writer
+                        .write_all(format!("{response}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                if shutdown {
+                    let _ = stop.try_send(());
+                    break;
+                }
+            }
+        });
+    }
+
+    let _ = async_std::fs::remove_file(&socket).await;
+    tracing::info!("Daemon shut down.");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve(_daemon: Daemon, _socket: PathBuf) -> Result<()> {
+    Err(crate::error::OroError::DaemonUnsupported.into())
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Requests without an id are notifications, which don't get a
+    /// response.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct WhyParams {
+    name: String,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Value,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: Value::Null,
+        }
+    }
+
+    /// A request that failed, with the diagnostic code of why, if there is
+    /// one, like `oro` would print it.
+    fn from_report(report: miette::Report) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: report.to_string(),
+            data: json!({ "code": report.code().map(|code| code.to_string()) }),
+        }
+    }
+}
+
+struct Daemon {
+    apply: ApplyArgs,
+    /// Held while working on the project, so requests don't step on each
+    /// other.
+    busy: async_std::sync::Mutex<()>,
+}
+
+impl Daemon {
+    fn new(apply: ApplyArgs) -> Self {
+        Self {
+            apply,
+            busy: async_std::sync::Mutex::new(()),
+        }
+    }
+
+    /// Handles a single line of input, and returns the response to send
+    /// back, if any, and whether the daemon should shut down.
+    async fn respond(&self, line: &str) -> (Option<Value>, bool) {
+        let request = match parse_request(line) {
+            Ok(request) => request,
+            Err(err) => return (Some(response(Value::Null, Err(err))), false),
+        };
+        let shutdown = request.method == "shutdown";
+        let result = self.call(&request.method, request.params).await;
+        (request.id.map(|id| response(id, result)), shutdown)
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "ping" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+            "shutdown" => Ok(Value::Null),
+            "resolve" => {
+                let _busy = self.busy.lock().await;
+                let maintainer = self.resolve().await.map_err(RpcError::from_report)?;
+                let lockfile = maintainer
+                    .to_kdl()
+                    .map_err(|err| RpcError::from_report(err.into()))?;
+                Ok(json!({
+                    "packages": maintainer.package_count(),
+                    "lockfile": lockfile.to_string(),
+                }))
+            }
+            "install" => {
+                let _busy = self.busy.lock().await;
+                let start = std::time::Instant::now();
+                self.install().await.map_err(RpcError::from_report)?;
+                Ok(json!({ "time": start.elapsed().as_millis() as f32 / 1000.0 }))
+            }
+            "why" => {
+                let params: WhyParams = serde_json::from_value(params)
+                    .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+                let _busy = self.busy.lock().await;
+                let maintainer = self.resolve().await.map_err(RpcError::from_report)?;
+                Ok(why(&maintainer.tree(), &params.name))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method `{method}`."),
+            )),
+        }
+    }
+
+    async fn manifest(&self) -> Result<CorgiManifest> {
+        serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()
+    }
+
+    async fn resolve(&self) -> Result<NodeMaintainer> {
+        self.apply.resolve_only(self.manifest().await?).await
+    }
+
+    async fn install(&self) -> Result<()> {
+        self.apply.execute(self.manifest().await?).await?;
+        Ok(())
+    }
+}
+
+fn parse_request(line: &str) -> Result<Request, RpcError> {
+    let request = serde_json::from_str::<Value>(line)
+        .map_err(|err| RpcError::new(PARSE_ERROR, err.to_string()))?;
+    let request = serde_json::from_value::<Request>(request)
+        .map_err(|err| RpcError::new(INVALID_REQUEST, err.to_string()))?;
+    if request.jsonrpc != "2.0" {
+        return Err(RpcError::new(INVALID_REQUEST, "`jsonrpc` must be \"2.0\"."));
+    }
+    Ok(request)
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": err.code,
+                "message": err.message,
+                "data": err.data,
+            },
+        }),
+    }
+}
+
+/// Every copy of the package called `name` in `tree`, along with what
+/// depends on it, and the shortest chain of dependencies that leads to it
+/// from the project.
+fn why(tree: &[TreePackage], name: &str) -> Value {
+    let mut dependents = HashMap::<&str, Vec<Value>>::new();
+    for pkg in tree {
+        for dep in &pkg.dependencies {
+            dependents.entry(&dep.path).or_default().push(json!({
+                "name": pkg.package.name(),
+                "path": pkg.path,
+                "requested": dep.requested.to_string(),
+                "type": dep.dep_type.as_str(),
+            }));
+        }
+    }
+
+    // Breadth-first from the root, so the first way each package is
+    // reached is the shortest.
+    let packages = tree
+        .iter()
+        .map(|pkg| (pkg.path.as_str(), pkg))
+        .collect::<HashMap<_, _>>();
+    let mut reached_from = HashMap::<&str, &str>::new();
+    let mut queue = VecDeque::from([""]);
+    while let Some(path) = queue.pop_front() {
+        let Some(pkg) = packages.get(path) else {
+            continue;
+        };
+        for dep in &pkg.dependencies {
+            if !dep.path.is_empty() && !reached_from.contains_key(dep.path.as_str()) {
+                reached_from.insert(&dep.path, path);
+                queue.push_back(&dep.path);
+            }
+        }
+    }
+
+    tree.iter()
+        .filter(|pkg| !pkg.path.is_empty() && pkg.package.name() == name)
+        .map(|pkg| {
+            let mut chain = Vec::new();
+            let mut path = pkg.path.as_str();
+            while let Some(from) = reached_from.get(path) {
+                chain.push(packages[path].package.name());
+                path = from;
+            }
+            chain.reverse();
+            let version = match pkg.package.resolved() {
+                PackageResolution::Npm { version, .. } => Some(version.to_string()),
+                _ => None,
+            };
+            json!({
+                "path": pkg.path,
+                "version": version,
+                "resolved": pkg.package.resolved().to_string(),
+                "dependents": dependents.remove(pkg.path.as_str()).unwrap_or_default(),
+                "chain": chain,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use oro_common::CorgiManifest;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// Reduces duplication in `node_modules/` by merging nested copies of
+/// packages into a single copy further up the tree, wherever one version
+/// satisfies everything that depends on it.
+///
+/// Duplicates tend to pile up in the lockfile as dependencies get added,
+/// removed, and updated over time. This command applies `node_modules/` as
+/// usual, but cleans them up first, and updates the lockfile accordingly.
+#[derive(Debug, Args)]
+#[clap(visible_aliases(["ddp"]))]
+pub struct DedupeCmd {
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[async_trait]
+impl OroCommand for DedupeCmd {
+    async fn execute(mut self) -> Result<()> {
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        // Like `oro apply`, this is meant to apply `node_modules/` even if
+        // `apply false` is configured.
+        self.apply.apply = true;
+        self.apply.dedupe = true;
+        let summary = self.apply.execute(corgi).await?;
+        if self.apply.json {
+            self.apply.print_json(&summary)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use oro_common::CorgiManifest;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// Lists funding URLs for the packages in the current project's dependency
+/// tree.
+///
+/// Packages list these in the `funding` field of their `package.json`. The
+/// tree gets resolved the same way `oro apply` would do it, but nothing is
+/// installed. Packages that are already installed are read from
+/// `node_modules/`, and the rest have their metadata fetched from the
+/// registry.
+#[derive(Debug, Args)]
+pub struct FundCmd {
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[async_trait]
+impl OroCommand for FundCmd {
+    async fn execute(self) -> Result<()> {
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        let maintainer = self.apply.resolve_only(corgi).await?;
+        let funding = maintainer.funding().await?;
+
+        if self.apply.json {
+            let funding = funding
+                .into_iter()
+                .map(|info| {
+                    serde_json::json!({
+                        "name": info.name,
+                        "version": info.version,
+                        "urls": info.urls,
+                    })
+                })
+                .collect::<Vec<_>>();
+            self.apply.print_json(&funding)?;
+        } else if funding.is_empty() {
+            tracing::info!("No packages are looking for funding.");
+        } else {
+            for info in funding {
+                match &info.version {
+                    Some(version) => println!("{}@{version}", info.name),
+                    None => println!("{}", info.name),
+                }
+                for url in &info.urls {
+                    println!("  {url}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use oro_common::CorgiManifest;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// Prints the current project's resolved dependency graph.
+///
+/// The graph gets resolved the same way `oro apply` would do it, but nothing
+/// is installed, and the lockfile is left alone. Edges are labeled with the
+/// requested spec and the kind of dependency, so you can visualize the
+/// output with tools like Graphviz or Mermaid, or post-process the JSON.
+#[derive(Debug, Args)]
+pub struct GraphCmd {
+    /// Output format: `dot`, `json`, or `mermaid`.
+    #[arg(long, default_value = "dot")]
+    format: GraphFormat,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GraphFormat {
+    Dot,
+    Json,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            "mermaid" => Ok(Self::Mermaid),
+            _ => Err(format!(
+                "unknown graph format `{s}`. Expected one of dot, json, or mermaid"
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl OroCommand for GraphCmd {
+    async fn execute(self) -> Result<()> {
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        let maintainer = self.apply.resolve_only(corgi).await?;
+        match self.format {
+            GraphFormat::Dot => print!("{}", maintainer.to_dot()),
+            GraphFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&maintainer.to_json()?).into_diagnostic()?
+            ),
+            GraphFormat::Mermaid => print!("{}", maintainer.to_mermaid()),
+        }
+        Ok(())
+    }
+}
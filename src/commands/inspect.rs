@@ -0,0 +1,279 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use async_std::io::ReadExt;
+use async_trait::async_trait;
+use clap::{Args, Subcommand};
+use colored::*;
+use humansize::{file_size_opts, FileSize};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use oro_i18n::t;
+use serde_json::Value;
+use ssri::Integrity;
+
+use crate::commands::OroCommand;
+use crate::nassun_args::NassunArgs;
+
+/// Lifecycle scripts that run automatically when a package is installed.
+const INSTALL_SCRIPTS: &[&str] = &["preinstall", "install", "postinstall"];
+
+/// Inspect packages without installing them.
+#[derive(Debug, Args)]
+pub struct InspectCmd {
+    #[command(subcommand)]
+    subcommand: InspectSubCmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum InspectSubCmd {
+    Tarball(InspectTarballCmd),
+}
+
+#[async_trait]
+impl OroCommand for InspectCmd {
+    async fn execute(self) -> Result<()> {
+        match self.subcommand {
+            InspectSubCmd::Tarball(cmd) => cmd.execute().await,
+        }
+    }
+}
+
+/// Show what's inside a package's tarball, without installing it.
+///
+/// Lists the tarball's files and their sizes, along with its integrity, any
+/// files with shebangs, and any scripts that would run when it gets
+/// installed. This is handy as a quick check before adding a new dependency.
+#[derive(Debug, Args)]
+struct InspectTarballCmd {
+    /// Package spec to inspect.
+    #[arg()]
+    pkg: String,
+
+    #[arg(from_global)]
+    json: bool,
+
+    #[command(flatten)]
+    nassun_args: NassunArgs,
+}
+
+struct TarballFile {
+    path: String,
+    size: u64,
+    mode: u32,
+    shebang: Option<String>,
+}
+
+#[async_trait]
+impl OroCommand for InspectTarballCmd {
+    async fn execute(self) -> Result<()> {
+        let pkg = self.nassun_args.to_nassun()?.resolve(&self.pkg).await?;
+        let data = self.tarball_data(&pkg).await?;
+        let integrity = Integrity::from(&data);
+
+        let mut files = Vec::new();
+        let mut manifest = Value::Null;
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&data[..]));
+        for entry in archive
+            .entries()
+            .into_diagnostic()
+            .wrap_err("inspect::tarball::entries")?
+        {
+            let mut entry = entry
+                .into_diagnostic()
+                .wrap_err("inspect::tarball::entry")?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry
+                .path()
+                .into_diagnostic()
+                .wrap_err("inspect::tarball::path")?
+                .to_string_lossy()
+                .to_string();
+            // Registry tarballs put everything under a single top-level
+            // directory, usually `package/`.
+            let path = match path.split_once('/') {
+                Some((_, rest)) => rest.to_string(),
+                None => path,
+            };
+            let size = entry.header().size().unwrap_or_default();
+            let mode = entry.header().mode().unwrap_or_default();
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .into_diagnostic()
+                .wrap_err("inspect::tarball::read")?;
+            if path == "package.json" {
+                manifest = serde_json::from_slice(&contents)
+                    .into_diagnostic()
+                    .wrap_err("inspect::tarball::manifest")?;
+            }
+            let shebang = contents.strip_prefix(b"#!").map(|rest| {
+                let line = rest.split(|b| *b == b'\n').next().unwrap_or_default();
+                String::from_utf8_lossy(line).trim().to_string()
+            });
+            files.push(TarballFile {
+                path,
+                size,
+                mode,
+                shebang,
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut scripts = INSTALL_SCRIPTS
+            .iter()
+            .filter_map(|event| {
+                manifest["scripts"][event]
+                    .as_str()
+                    .map(|script| (event.to_string(), script.to_string()))
+            })
+            .collect::<Vec<_>>();
+        // Just like NPM, packages with a `binding.gyp` but no install
+        // scripts of their own get built with node-gyp.
+        if scripts.is_empty() && files.iter().any(|f| f.path == "binding.gyp") {
+            scripts.push(("install".into(), "node-gyp rebuild".into()));
+        }
+
+        let name = manifest["name"]
+            .as_str()
+            .unwrap_or_else(|| pkg.name())
+            .to_string();
+        let version = manifest["version"].as_str().unwrap_or("0.0.0").to_string();
+        let unpacked: u64 = files.iter().map(|f| f.size).sum();
+
+        if self.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "name": name,
+                "version": version,
+                "resolved": pkg.resolved().to_string(),
+                "integrity": integrity.to_string(),
+                "size": data.len(),
+                "unpackedSize": unpacked,
+                "files": files.iter().map(|f| serde_json::json!({
+                    "path": f.path,
+                    "size": f.size,
+                    "mode": format!("{:o}", f.mode),
+                    "shebang": f.shebang,
+                })).collect::<Vec<_>>(),
+                "installScripts": scripts
+                    .iter()
+                    .map(|(event, script)| (event.clone(), Value::String(script.clone())))
+                    .collect::<serde_json::Map<_, _>>(),
+            }))
+            .into_diagnostic()
+            .wrap_err("inspect::tarball::serialize")?;
+            println!("{output}");
+            return Ok(());
+        }
+
+        // name@version
+        println!(
+            "{}@{}",
+            name.bright_green().underline(),
+            version.bright_green().underline()
+        );
+        println!(
+            "{}",
+            t!(
+                "inspect-resolved",
+                resolved = pkg.resolved().to_string().cyan().to_string()
+            )
+        );
+        println!(
+            "{}",
+            t!(
+                "inspect-integrity",
+                integrity = integrity.to_string().yellow().to_string()
+            )
+        );
+        println!(
+            "{}",
+            t!(
+                "inspect-size",
+                size = data
+                    .len()
+                    .file_size(file_size_opts::DECIMAL)
+                    .unwrap()
+                    .yellow()
+                    .to_string(),
+                unpacked = unpacked
+                    .file_size(file_size_opts::DECIMAL)
+                    .unwrap()
+                    .yellow()
+                    .to_string(),
+            )
+        );
+        println!();
+
+        // files:
+        //   1.2 KB  package.json
+        println!("{}", t!("inspect-files", count = files.len()));
+        for file in &files {
+            println!(
+                "{:>10}  {}",
+                file.size.file_size(file_size_opts::DECIMAL).unwrap(),
+                file.path
+            );
+        }
+        println!();
+
+        // shebangs:
+        //   bin/cli.js: #!/usr/bin/env node
+        let shebangs = files
+            .iter()
+            .filter_map(|f| f.shebang.as_ref().map(|s| (&f.path, s)))
+            .collect::<Vec<_>>();
+        if !shebangs.is_empty() {
+            println!("{}", t!("inspect-shebangs"));
+            for (path, shebang) in shebangs {
+                println!("  {path}: {}", format!("#!{shebang}").yellow());
+            }
+            println!();
+        }
+
+        // install scripts:
+        //   postinstall: node install.js
+        if scripts.is_empty() {
+            println!(
+                "{} {}",
+                t!("inspect-install-scripts"),
+                t!("inspect-install-scripts-none").green()
+            );
+        } else {
+            println!("{}", t!("inspect-install-scripts"));
+            for (event, script) in &scripts {
+                println!("  {}: {}", event.magenta(), script);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InspectTarballCmd {
+    /// Reads the package's tarball, from the cache if it's already there,
+    /// or by downloading it otherwise. The data is checked against the
+    /// package's integrity, if it has one.
+    async fn tarball_data(&self, pkg: &nassun::Package) -> Result<Vec<u8>> {
+        let cache = self.nassun_args.cache().map(PathBuf::from);
+        if let (Some(cache), Some(sri)) = (&cache, pkg.resolved().integrity()) {
+            if let Ok(data) = cacache::read_hash(cache, sri).await {
+                tracing::debug!("Read tarball for {} from cache.", pkg.name());
+                return Ok(data);
+            }
+        }
+        let mut data = Vec::new();
+        pkg.tarball()
+            .await?
+            .read_to_end(&mut data)
+            .await
+            .into_diagnostic()
+            .wrap_err("inspect::tarball::download")?;
+        if let Some(cache) = &cache {
+            if let Err(e) = cacache::write_hash(cache, &data).await {
+                tracing::debug!("Failed to write tarball to cache: {e}");
+            }
+        }
+        Ok(data)
+    }
+}
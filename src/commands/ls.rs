@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use oro_common::CorgiManifest;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+
+/// Lists the packages in the current project's resolved dependency tree.
+///
+/// The tree gets resolved the same way `oro apply` would do it, but nothing
+/// is installed, and the lockfile is left alone. Nested packages are indented
+/// under the package whose `node_modules/` they live in.
+#[derive(Debug, Args)]
+pub struct LsCmd {
+    /// Only list dependency cycles: sets of packages that depend on each
+    /// other in a loop.
+    #[arg(long)]
+    cycles: bool,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+#[async_trait]
+impl OroCommand for LsCmd {
+    async fn execute(self) -> Result<()> {
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+        let maintainer = self.apply.resolve_only(corgi).await?;
+
+        if self.cycles {
+            let cycles = maintainer.cycles();
+            if self.apply.json {
+                let cycles = cycles
+                    .into_iter()
+                    .map(|cycle| cycle.packages)
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&cycles).into_diagnostic()?
+                );
+            } else if cycles.is_empty() {
+                tracing::info!("No dependency cycles found.");
+            } else {
+                for cycle in cycles {
+                    println!("{cycle}");
+                }
+            }
+            return Ok(());
+        }
+
+        let lockfile = maintainer.to_lockfile()?;
+        let mut packages = lockfile.packages().values().collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.path.cmp(&b.path));
+        if self.apply.json {
+            let packages = packages
+                .into_iter()
+                .map(|pkg| {
+                    serde_json::json!({
+                        "path": pkg
+                            .path
+                            .iter()
+                            .map(|x| x.to_string())
+                            .collect::<Vec<_>>()
+                            .join("/node_modules/"),
+                        "name": pkg.name.to_string(),
+                        "version": pkg.version.as_ref().map(|v| v.to_string()),
+                        "resolved": pkg.resolved,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&packages).into_diagnostic()?
+            );
+        } else {
+            for pkg in packages {
+                let version = pkg
+                    .version
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .or_else(|| pkg.resolved.clone())
+                    .unwrap_or_default();
+                println!(
+                    "{}{}@{version}",
+                    "  ".repeat(pkg.path.len().saturating_sub(1)),
+                    pkg.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use clap::Args;
+use kdl::{KdlDocument, KdlNode};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use node_maintainer::Lockfile;
+use oro_common::CorgiManifest;
+use serde::Serialize;
+use url::Url;
+
+use crate::apply_args::ApplyArgs;
+use crate::commands::OroCommand;
+use crate::error::OroError;
+
+/// Migrate a project from npm, Yarn, or pnpm to Orogene.
+///
+/// Detects the project's existing lockfiles, `.npmrc`, and workspace
+/// configuration, and imports what it can. It then does a verification apply
+/// into a temporary directory and reports any packages that resolved to
+/// different versions than in the previous lockfile. The project is only
+/// switched over (by writing `package-lock.kdl` and `oro.kdl`) if that apply
+/// succeeds and nothing diverged. With `--force`, it's switched over even if
+/// some packages diverged.
+#[derive(Debug, Args)]
+pub struct MigrateCmd {
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+/// A package manager file found in the project.
+#[derive(Debug, Serialize)]
+struct Artifact {
+    path: PathBuf,
+    kind: &'static str,
+    imported: bool,
+    note: Option<String>,
+}
+
+/// Settings imported from `.npmrc`.
+#[derive(Debug, Default, Serialize)]
+struct Npmrc {
+    registry: Option<Url>,
+    scoped_registries: BTreeMap<String, Url>,
+    ignored: Vec<String>,
+}
+
+impl Npmrc {
+    fn configure(&self, apply: &mut ApplyArgs) {
+        if let Some(registry) = &self.registry {
+            apply.registry = registry.clone();
+        }
+        for (scope, registry) in &self.scoped_registries {
+            apply
+                .scoped_registries
+                .push((scope.clone(), registry.clone()));
+        }
+    }
+}
+
+/// A package whose resolved versions differ between the previous lockfile
+/// and the verification apply.
+#[derive(Debug, Serialize)]
+struct Divergence {
+    name: String,
+    previous: BTreeSet<String>,
+    current: BTreeSet<String>,
+}
+
+/// Every version of every package in a dependency tree, keyed by name.
+type TreeVersions = BTreeMap<String, BTreeSet<String>>;
+
+const NPM_LOCKFILES: &[&str] = &["npm-shrinkwrap.json", "package-lock.json"];
+
+#[async_trait]
+impl OroCommand for MigrateCmd {
+    async fn execute(self) -> Result<()> {
+        let root = self.apply.root.clone();
+        let manifest_path = root.join("package.json");
+        let manifest_src = std::fs::read_to_string(&manifest_path)
+            .into_diagnostic()
+            .wrap_err("migrate::read_manifest")?;
+        let manifest: CorgiManifest = serde_json::from_str(&manifest_src)
+            .into_diagnostic()
+            .wrap_err("migrate::parse_manifest")?;
+        let manifest_json: serde_json::Value = serde_json::from_str(&manifest_src)
+            .into_diagnostic()
+            .wrap_err("migrate::parse_manifest")?;
+
+        let mut artifacts = Vec::new();
+        let mut previous = None;
+
+        // Lockfiles. Only npm's can be imported directly, since that's the
+        // only format node-maintainer can read, but the others are still
+        // used to check the new tree against.
+        let npm_lock = NPM_LOCKFILES
+            .iter()
+            .map(|name| root.join(name))
+            .find(|path| path.exists());
+        if let Some(path) = &npm_lock {
+            let src = std::fs::read_to_string(path)
+                .into_diagnostic()
+                .wrap_err("migrate::read_npm_lock")?;
+            let lock = Lockfile::from_npm(src)?;
+            previous = Some(lockfile_versions(&lock));
+            artifacts.push(Artifact {
+                path: path.clone(),
+                kind: "npm lockfile",
+                imported: true,
+                note: None,
+            });
+        }
+        let yarn_lock = root.join("yarn.lock");
+        if yarn_lock.exists() {
+            let src = std::fs::read_to_string(&yarn_lock)
+                .into_diagnostic()
+                .wrap_err("migrate::read_yarn_lock")?;
+            previous.get_or_insert_with(|| yarn_lock_versions(&src));
+            artifacts.push(Artifact {
+                path: yarn_lock,
+                kind: "Yarn lockfile",
+                imported: false,
+                note: Some(
+                    "Dependencies will be resolved from scratch, and compared against it.".into(),
+                ),
+            });
+        }
+        let pnpm_lock = root.join("pnpm-lock.yaml");
+        if pnpm_lock.exists() {
+            let src = std::fs::read_to_string(&pnpm_lock)
+                .into_diagnostic()
+                .wrap_err("migrate::read_pnpm_lock")?;
+            previous.get_or_insert_with(|| pnpm_lock_versions(&src));
+            artifacts.push(Artifact {
+                path: pnpm_lock,
+                kind: "pnpm lockfile",
+                imported: false,
+                note: Some(
+                    "Dependencies will be resolved from scratch, and compared against it.".into(),
+                ),
+            });
+        }
+
+        // Registry configuration.
+        let npmrc_path = root.join(".npmrc");
+        let npmrc = if npmrc_path.exists() {
+            let npmrc = parse_npmrc(
+                &std::fs::read_to_string(&npmrc_path)
+                    .into_diagnostic()
+                    .wrap_err("migrate::read_npmrc")?,
+            );
+            artifacts.push(Artifact {
+                path: npmrc_path,
+                kind: ".npmrc",
+                imported: npmrc.registry.is_some() || !npmrc.scoped_registries.is_empty(),
+                note: if npmrc.ignored.is_empty() {
+                    None
+                } else {
+                    Some(format!(
+                        "Ignored unsupported settings: {}.",
+                        npmrc.ignored.join(", ")
+                    ))
+                },
+            });
+            npmrc
+        } else {
+            Npmrc::default()
+        };
+
+        // Workspaces.
+        if manifest_json.get("workspaces").is_some() {
+            artifacts.push(Artifact {
+                path: manifest_path.clone(),
+                kind: "workspaces",
+                imported: false,
+                note: Some(
+                    "Only the root package's dependencies are applied. Workspace packages can still be used with `oro run --workspace`.".into(),
+                ),
+            });
+        }
+        let pnpm_workspace = root.join("pnpm-workspace.yaml");
+        if pnpm_workspace.exists() {
+            artifacts.push(Artifact {
+                path: pnpm_workspace,
+                kind: "pnpm workspace",
+                imported: false,
+                note: Some("Move the package globs into the `workspaces` field of your package.json to use them with `oro run --workspace`.".into()),
+            });
+        }
+
+        // Verification apply, into a scratch copy of the project.
+        let scratch = tempfile::tempdir()
+            .into_diagnostic()
+            .wrap_err("migrate::tempdir")?;
+        copy_project_files(&root, scratch.path(), &manifest_json)?;
+        let mut apply = self.apply;
+        npmrc.configure(&mut apply);
+        let mut verify = apply.clone();
+        verify.root = scratch.path().to_path_buf();
+        verify.apply = true;
+        verify.lockfile = true;
+        verify.dry_run = false;
+        tracing::info!(
+            "{}Verifying migration in a temporary directory...",
+            if apply.emoji { "🔍 " } else { "" }
+        );
+        verify
+            .execute(manifest.clone())
+            .await
+            .wrap_err("migrate::verify")?;
+        let new_lock_path = scratch.path().join("package-lock.kdl");
+        let new_lock = Lockfile::from_kdl(
+            std::fs::read_to_string(&new_lock_path)
+                .into_diagnostic()
+                .wrap_err("migrate::read_new_lock")?,
+        )?;
+        let divergences = previous
+            .as_ref()
+            .map(|previous| diverged(previous, &lockfile_versions(&new_lock)))
+            .unwrap_or_default();
+
+        let oro_kdl = root.join("oro.kdl");
+        let config = npmrc_config(&npmrc);
+        let write_config = config.is_some() && !oro_kdl.exists();
+        let switch = !apply.dry_run && (divergences.is_empty() || apply.force);
+
+        if apply.json {
+            let output = serde_json::to_string_pretty(&serde_json::json!({
+                "artifacts": artifacts,
+                "npmrc": npmrc,
+                "divergences": divergences,
+                "migrated": switch,
+            }))
+            .into_diagnostic()
+            .wrap_err("migrate::serialize")?;
+            println!("{output}");
+        } else {
+            report(&artifacts, &divergences, previous.is_some());
+        }
+
+        if !divergences.is_empty() && !apply.force && !apply.dry_run {
+            return Err(OroError::MigrationDiverged(divergences.len()).into());
+        }
+
+        if !switch {
+            tracing::info!("Dry run, so the project was left unchanged.");
+            return Ok(());
+        }
+
+        std::fs::copy(&new_lock_path, root.join("package-lock.kdl"))
+            .into_diagnostic()
+            .wrap_err("migrate::write_lockfile")?;
+        if let Some(config) = config {
+            if write_config {
+                std::fs::write(&oro_kdl, config.to_string())
+                    .into_diagnostic()
+                    .wrap_err("migrate::write_config")?;
+            } else {
+                tracing::warn!(
+                    "{} already exists, so it was left alone. Add these settings to it to keep using your .npmrc registries:\n{config}",
+                    oro_kdl.display()
+                );
+            }
+        }
+
+        // Now that the tree is known to work, apply it for real.
+        apply.execute(manifest).await?;
+
+        let leftovers = NPM_LOCKFILES
+            .iter()
+            .chain(["yarn.lock", "pnpm-lock.yaml"].iter())
+            .filter(|name| root.join(name).exists())
+            .copied()
+            .collect::<Vec<_>>();
+        tracing::info!(
+            "{}Migrated to Orogene!",
+            if apply.emoji { "🎉 " } else { "" }
+        );
+        if !leftovers.is_empty() {
+            tracing::info!(
+                "Once you're happy with the result, you can delete {}.",
+                leftovers.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+fn report(artifacts: &[Artifact], divergences: &[Divergence], had_previous: bool) {
+    if artifacts.is_empty() {
+        tracing::info!("No npm, Yarn, or pnpm files were found.");
+    }
+    for artifact in artifacts {
+        tracing::info!(
+            "{} {} ({})",
+            if artifact.imported {
+                "Imported"
+            } else {
+                "Found"
+            },
+            artifact.path.display(),
+            artifact.kind
+        );
+        if let Some(note) = &artifact.note {
+            tracing::info!("    {note}");
+        }
+    }
+    if !had_previous {
+        tracing::info!("No previous lockfile to compare against.");
+    } else if divergences.is_empty() {
+        tracing::info!("All packages resolved to the same versions as before.");
+    } else {
+        tracing::warn!(
+            "{} package(s) resolved differently from the previous lockfile:",
+            divergences.len()
+        );
+        for divergence in divergences {
+            tracing::warn!(
+                "    {}: {} -> {}",
+                divergence.name,
+                fmt_versions(&divergence.previous),
+                fmt_versions(&divergence.current)
+            );
+        }
+    }
+}
+
+fn fmt_versions(versions: &BTreeSet<String>) -> String {
+    if versions.is_empty() {
+        "(none)".into()
+    } else {
+        versions.iter().cloned().collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Copies everything needed to apply the project somewhere else: its
+/// `package.json`, any existing lockfile, and any patch files it references.
+fn copy_project_files(root: &Path, dest: &Path, manifest: &serde_json::Value) -> Result<()> {
+    let mut files = vec![PathBuf::from("package.json")];
+    files.extend(
+        NPM_LOCKFILES
+            .iter()
+            .chain(["package-lock.kdl"].iter())
+            .map(PathBuf::from),
+    );
+    if let Some(patches) = manifest
+        .get("patchedDependencies")
+        .and_then(|patches| patches.as_object())
+    {
+        files.extend(
+            patches
+                .values()
+                .filter_map(|path| path.as_str())
+                .map(PathBuf::from)
+                .filter(|path| path.is_relative()),
+        );
+    }
+    for file in files {
+        let from = root.join(&file);
+        if !from.exists() {
+            continue;
+        }
+        let to = dest.join(&file);
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)
+                .into_diagnostic()
+                .wrap_err("migrate::copy_project_files")?;
+        }
+        std::fs::copy(&from, &to)
+            .into_diagnostic()
+            .wrap_err("migrate::copy_project_files")?;
+    }
+    Ok(())
+}
+
+fn lockfile_versions(lock: &Lockfile) -> TreeVersions {
+    let mut versions = TreeVersions::new();
+    for node in lock.packages().values().filter(|node| !node.is_root) {
+        if let Some(version) = &node.version {
+            versions
+                .entry(node.name.to_string())
+                .or_default()
+                .insert(version.to_string());
+        }
+    }
+    versions
+}
+
+/// Reads package versions out of a `yarn.lock`, in either the Yarn 1 or the
+/// Yarn 2+ format.
+fn yarn_lock_versions(src: &str) -> TreeVersions {
+    let mut versions = TreeVersions::new();
+    let mut current = None;
+    for line in src.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            // "foo@^1.0.0", foo@~1.0.0:
+            current = line
+                .trim_end_matches(':')
+                .split(',')
+                .next()
+                .map(|spec| spec.trim().trim_matches('"'))
+                .filter(|spec| *spec != "__metadata")
+                .and_then(|spec| {
+                    let idx = spec.get(1..)?.rfind('@')?;
+                    Some(spec[..idx + 1].to_string())
+                });
+        } else if let Some(name) = &current {
+            // version "1.2.3" (Yarn 1) or version: 1.2.3 (Yarn 2+)
+            let Some(version) = line.trim().strip_prefix("version") else {
+                continue;
+            };
+            let version = version.trim_start_matches(':').trim().trim_matches('"');
+            if node_semver::Version::parse(version).is_ok() && !version.contains("use.local") {
+                versions
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(version.to_string());
+            }
+            current = None;
+        }
+    }
+    versions
+}
+
+/// Reads package versions out of the `packages` section of a
+/// `pnpm-lock.yaml`.
+fn pnpm_lock_versions(src: &str) -> TreeVersions {
+    let mut versions = TreeVersions::new();
+    let mut in_packages = false;
+    for line in src.lines() {
+        if !line.starts_with(' ') && !line.trim().is_empty() {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if !in_packages || line.starts_with("   ") || !line.trim_end().ends_with(':') {
+            continue;
+        }
+        // /foo/1.2.3: (v5), /foo@1.2.3(peer@1.0.0): (v6), or 'foo@1.2.3': (v9)
+        let key = line
+            .trim()
+            .trim_end_matches(':')
+            .trim_matches('\'')
+            .trim_matches('"');
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let key = key.split('(').next().unwrap_or(key);
+        let Some(rest) = key.get(1..) else {
+            continue;
+        };
+        // v5 keys can also have `_peer@1.0.0` suffixes, so fall back to
+        // splitting on `/` if splitting on `@` doesn't give a valid name.
+        for idx in [rest.rfind('@'), rest.rfind('/')].into_iter().flatten() {
+            let name = &key[..idx + 1];
+            let version = key[idx + 2..].split('_').next().unwrap_or_default();
+            if is_package_name(name) && node_semver::Version::parse(version).is_ok() {
+                versions
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(version.to_string());
+                break;
+            }
+        }
+    }
+    versions
+}
+
+fn is_package_name(name: &str) -> bool {
+    match name.strip_prefix('@') {
+        Some(scoped) => scoped.matches('/').count() == 1,
+        None => !name.is_empty() && !name.contains('/'),
+    }
+}
+
+fn parse_npmrc(src: &str) -> Npmrc {
+    let mut npmrc = Npmrc::default();
+    for line in src.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+        let url = || value.parse::<Url>().ok();
+        if key == "registry" {
+            if let Some(url) = url() {
+                npmrc.registry = Some(url);
+                continue;
+            }
+        } else if let Some(scope) = key
+            .strip_suffix(":registry")
+            .and_then(|scope| scope.strip_prefix('@'))
+        {
+            if let Some(url) = url() {
+                npmrc.scoped_registries.insert(scope.into(), url);
+                continue;
+            }
+        }
+        // Per-registry credentials are read straight from `.npmrc`, so
+        // they don't need importing.
+        if !key.starts_with("//") {
+            npmrc.ignored.push(key.into());
+        }
+    }
+    npmrc
+}
+
+/// `oro.kdl` equivalent of the registries configured in `.npmrc`, if any.
+fn npmrc_config(npmrc: &Npmrc) -> Option<KdlDocument> {
+    if npmrc.registry.is_none() && npmrc.scoped_registries.is_empty() {
+        return None;
+    }
+    let mut options = KdlNode::new("options");
+    let children = options.ensure_children();
+    if let Some(registry) = &npmrc.registry {
+        let mut node = KdlNode::new("registry");
+        node.push(registry.to_string());
+        children.nodes_mut().push(node);
+    }
+    if !npmrc.scoped_registries.is_empty() {
+        let mut scoped = KdlNode::new("scoped-registries");
+        let scopes = scoped.ensure_children();
+        for (scope, registry) in &npmrc.scoped_registries {
+            let mut node = KdlNode::new(scope.as_str());
+            node.push(registry.to_string());
+            scopes.nodes_mut().push(node);
+        }
+        children.nodes_mut().push(scoped);
+    }
+    let mut doc = KdlDocument::new();
+    doc.nodes_mut().push(options);
+    doc.fmt();
+    Some(doc)
+}
+
+fn diverged(previous: &TreeVersions, current: &TreeVersions) -> Vec<Divergence> {
+    let names = previous
+        .keys()
+        .chain(current.keys())
+        .collect::<BTreeSet<_>>();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let previous = previous.get(name).cloned().unwrap_or_default();
+            let current = current.get(name).cloned().unwrap_or_default();
+            (previous != current).then(|| Divergence {
+                name: name.clone(),
+                previous,
+                current,
+            })
+        })
+        .collect()
+}
@@ -3,9 +3,22 @@ use miette::Result;
 
 pub mod add;
 pub mod apply;
+pub mod audit;
+pub mod cache;
+pub mod clean_state;
+pub mod completions;
+pub mod config;
+pub mod daemon;
+pub mod dedupe;
+pub mod fund;
+pub mod graph;
+pub mod inspect;
+pub mod ls;
+pub mod migrate;
 pub mod ping;
 pub mod reapply;
 pub mod remove;
+pub mod run;
 pub mod view;
 
 #[async_trait]
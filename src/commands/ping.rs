@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::time::Instant;
 
 use async_trait::async_trait;
@@ -15,6 +16,42 @@ pub struct PingCmd {
     #[arg(from_global)]
     registry: Url,
 
+    #[arg(from_global)]
+    credentials: Vec<(String, String, String)>,
+
+    #[arg(from_global)]
+    root: PathBuf,
+
+    #[arg(from_global)]
+    proxy: Option<Url>,
+
+    #[arg(from_global)]
+    https_proxy: Option<Url>,
+
+    #[arg(from_global)]
+    noproxy: Option<String>,
+
+    #[arg(from_global)]
+    cafile: Option<PathBuf>,
+
+    #[arg(from_global)]
+    ca: Vec<String>,
+
+    #[arg(from_global)]
+    strict_ssl: bool,
+
+    #[arg(from_global)]
+    cert: Option<PathBuf>,
+
+    #[arg(from_global)]
+    key: Option<PathBuf>,
+
+    #[arg(from_global)]
+    registry_certs: Vec<(String, PathBuf)>,
+
+    #[arg(from_global)]
+    registry_keys: Vec<(String, PathBuf)>,
+
     #[arg(from_global)]
     json: bool,
 
@@ -28,7 +65,36 @@ impl OroCommand for PingCmd {
         let start = Instant::now();
         let registry = self.registry;
         tracing::info!("{}ping: {registry}", if self.emoji { "🗣️ " } else { "" });
-        let client = OroClient::new(registry.clone());
+        let mut client = OroClient::builder().registry(registry.clone());
+        for (host, credentials) in crate::registry_credentials(&self.root, &self.credentials)? {
+            client = client.credentials(host, credentials);
+        }
+        if let Some(proxy) = self.proxy {
+            client = client.proxy(proxy);
+        }
+        if let Some(proxy) = self.https_proxy {
+            client = client.https_proxy(proxy);
+        }
+        if let Some(hosts) = &self.noproxy {
+            client = client.no_proxy(hosts);
+        }
+        let (tls, host_tls) = crate::TlsArgs {
+            cafile: self.cafile.as_deref(),
+            ca: &self.ca,
+            strict_ssl: self.strict_ssl,
+            cert: self.cert.as_deref(),
+            key: self.key.as_deref(),
+            registry_certs: &self.registry_certs,
+            registry_keys: &self.registry_keys,
+        }
+        .configs()?;
+        if let Some(tls) = tls {
+            client = client.tls(tls);
+        }
+        for (host, tls) in host_tls {
+            client = client.host_tls(host, tls);
+        }
+        let client = client.build();
         let payload = client.ping().await?;
         let time = start.elapsed().as_micros() as f32 / 1000.0;
         tracing::info!("{}pong: {time}ms", if self.emoji { "👂 " } else { "" });
@@ -19,37 +19,42 @@ impl OroCommand for ReapplyCmd {
     async fn execute(mut self) -> Result<()> {
         let total_time = std::time::Instant::now();
 
-        tracing::info!(
-            "{}Clearing node_modules/...",
-            if self.apply.emoji { "🚮 " } else { "" },
-        );
-
-        std::fs::remove_dir_all(self.apply.root.join("node_modules")).into_diagnostic()?;
-
-        tracing::info!(
-            "{}node_modules/ cleared in {}s.",
-            if self.apply.emoji { "🚮 " } else { "" },
-            total_time.elapsed().as_millis() as f32 / 1000.0,
-        );
-
-        let corgi: CorgiManifest = serde_json::from_str(
-            &async_std::fs::read_to_string(self.apply.root.join("package.json"))
-                .await
-                .into_diagnostic()?,
-        )
-        .into_diagnostic()?;
+        // This has to happen before `node_modules/` is cleared, since that's
+        // where global installs keep track of what's installed.
+        let corgi: CorgiManifest =
+            serde_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
+
+        if self.apply.dry_run {
+            tracing::info!("Dry run, so node_modules/ won't be cleared first.");
+        } else {
+            tracing::info!(
+                "{}Clearing node_modules/...",
+                if self.apply.emoji { "🚮 " } else { "" },
+            );
+
+            std::fs::remove_dir_all(self.apply.node_modules_dir()?).into_diagnostic()?;
+
+            tracing::info!(
+                "{}node_modules/ cleared in {}s.",
+                if self.apply.emoji { "🚮 " } else { "" },
+                total_time.elapsed().as_millis() as f32 / 1000.0,
+            );
+        }
 
         // Running `reapply` with `--no-apply` doesn't make sense. We force it
         // here so that people can have `apply false` in their configurations
         // but have `oro apply` still work.
         self.apply.apply = true;
-        self.apply.execute(corgi).await?;
+        let summary = self.apply.execute(corgi).await?;
 
         tracing::info!(
             "{}Reapply done in {}s.",
             if self.apply.emoji { "✨ " } else { "" },
             total_time.elapsed().as_millis() as f32 / 1000.0,
         );
+        if self.apply.json {
+            self.apply.print_json(&summary)?;
+        }
         Ok(())
     }
 }
@@ -4,8 +4,9 @@ use miette::{IntoDiagnostic, Result};
 use nassun::PackageSpec;
 use oro_common::CorgiManifest;
 use oro_pretty_json::Formatted;
+use serde::Serialize;
 
-use crate::apply_args::ApplyArgs;
+use crate::apply_args::{ApplyArgs, ApplySummary};
 use crate::commands::OroCommand;
 use crate::OroError;
 
@@ -25,13 +26,10 @@ pub struct RemoveCmd {
 #[async_trait]
 impl OroCommand for RemoveCmd {
     async fn execute(mut self) -> Result<()> {
-        let mut manifest = oro_pretty_json::from_str(
-            &async_std::fs::read_to_string(self.apply.root.join("package.json"))
-                .await
-                .into_diagnostic()?,
-        )
-        .into_diagnostic()?;
+        let mut manifest =
+            oro_pretty_json::from_str(&self.apply.read_manifest().await?).into_diagnostic()?;
         let mut count = 0;
+        let mut removed = Vec::new();
         for name in &self.names {
             if let Ok(PackageSpec::Npm {
                 name: spec_name, ..
@@ -40,7 +38,11 @@ impl OroCommand for RemoveCmd {
                 if &spec_name != name {
                     tracing::warn!("Ignoring version specifier in `{name}`. Arguments to `oro remove` should only be package names. Proceeding with `{spec_name}` instead.");
                 }
-                count += self.remove_from_manifest(&mut manifest, &spec_name);
+                let removed_from = self.remove_from_manifest(&mut manifest, &spec_name);
+                if removed_from > 0 {
+                    removed.push(spec_name);
+                }
+                count += removed_from;
             } else {
                 return Err(OroError::InvalidPackageName(name.clone()).into());
             }
@@ -58,7 +60,18 @@ impl OroCommand for RemoveCmd {
                 .into_diagnostic()?;
 
         // Then, we apply the change.
-        self.apply.execute(corgi).await?;
+        let summary = self.apply.execute(corgi).await?;
+        if self.apply.json {
+            self.apply.print_json(&RemoveOutput {
+                removed: &removed,
+                summary,
+            })?;
+        }
+
+        // Global installs don't have a package.json to update.
+        if self.apply.dry_run || self.apply.global {
+            return Ok(());
+        }
 
         async_std::fs::write(
             self.apply.root.join("package.json"),
@@ -77,6 +90,15 @@ impl OroCommand for RemoveCmd {
     }
 }
 
+/// What `oro remove --json` prints.
+#[derive(Debug, Serialize)]
+struct RemoveOutput<'a> {
+    /// Names of the dependencies that were removed.
+    removed: &'a [String],
+    #[serde(flatten)]
+    summary: Option<ApplySummary>,
+}
+
 impl RemoveCmd {
     fn remove_from_manifest(&self, mani: &mut Formatted, name: &str) -> usize {
         let mut count = 0;
@@ -0,0 +1,508 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use clap::Args;
+use indicatif::ProgressStyle;
+use miette::{IntoDiagnostic, Result};
+use node_maintainer::{ScriptOutput, ScriptOutputMode, WorkspaceScripts, Workspaces};
+use oro_common::Manifest;
+use oro_script::OroScript;
+use serde::Deserialize;
+use tracing::Span;
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+
+use crate::commands::OroCommand;
+use crate::error::OroError;
+
+/// Run a package script, along with any scripts it depends on.
+#[derive(Debug, Args)]
+pub struct RunCmd {
+    /// Name of the script to run.
+    #[arg()]
+    script: String,
+
+    /// Name of the workspace package to run the script in.
+    ///
+    /// Defaults to the project at `--root`.
+    #[arg(long, short, conflicts_with = "recursive")]
+    workspace: Option<String>,
+
+    /// Run the script in every workspace package that has it, in dependency
+    /// order.
+    ///
+    /// A package's script only starts once the scripts of the workspace
+    /// packages it depends on have finished. Only the script itself runs in
+    /// each package, without any `dependsOn` or `pre`/`post` scripts.
+    #[arg(long, short)]
+    recursive: bool,
+
+    /// Only run the script in this workspace package, with `--recursive`.
+    ///
+    /// Can be provided multiple times.
+    #[arg(long, value_name = "NAME", requires = "recursive")]
+    filter: Vec<String>,
+
+    /// With `--recursive`, keep running scripts in other packages after one
+    /// fails, instead of stopping.
+    #[arg(long = "no-bail", action = clap::ArgAction::SetFalse)]
+    bail: bool,
+
+    /// Maximum number of scripts to run at the same time, with
+    /// `--recursive`.
+    #[arg(long, default_value_t = node_maintainer::DEFAULT_SCRIPT_CONCURRENCY)]
+    script_concurrency: usize,
+
+    /// How to show the scripts' output with `--recursive`: `progress`,
+    /// `prefixed`, or `grouped`.
+    ///
+    /// `progress` shows the latest line of each running script, and all of
+    /// a script's output only if it fails. `prefixed` prints every line as
+    /// it comes in, tagged with the package and script it came from.
+    /// `grouped` does the same, but holds on to each script's output until
+    /// it finishes. Defaults to `progress`.
+    #[arg(long, value_name = "MODE", requires = "recursive")]
+    script_output: Option<ScriptOutputMode>,
+
+    /// Shell to run the scripts in, such as `bash`, `pwsh`, or the path to
+    /// another interpreter that accepts a command with `-c`.
+    ///
+    /// Defaults to `sh`, or `%ComSpec%` (usually `cmd.exe`) on Windows.
+    #[arg(long, value_name = "SHELL")]
+    script_shell: Option<PathBuf>,
+
+    /// Don't run `pre<script>` and `post<script>` scripts around each script
+    /// that runs.
+    #[arg(long = "no-pre-post", action = clap::ArgAction::SetFalse)]
+    pre_post: bool,
+
+    #[arg(from_global)]
+    root: PathBuf,
+}
+
+#[async_trait]
+impl OroCommand for RunCmd {
+    async fn execute(self) -> Result<()> {
+        if self.recursive {
+            return self.run_recursive().await;
+        }
+        let packages = load_packages(&self.root).await?;
+        let start = if let Some(workspace) = &self.workspace {
+            packages
+                .iter()
+                .position(|pkg| pkg.name.as_deref() == Some(workspace.as_str()))
+                .ok_or_else(|| OroError::UnknownWorkspace(workspace.clone()))?
+        } else {
+            0
+        };
+
+        let mut planner = ScriptPlanner {
+            packages: &packages,
+            visiting: Vec::new(),
+            done: HashSet::new(),
+            plan: Vec::new(),
+            pre_post: self.pre_post,
+        };
+        planner.visit(start, &self.script, true)?;
+
+        for (idx, script) in planner.plan {
+            let pkg = &packages[idx];
+            tracing::info!("> {} {script}", pkg.display_name());
+            let package_dir = pkg.path.clone();
+            let root = self.root.clone();
+            let shell = self.script_shell.clone();
+            async_std::task::spawn_blocking(move || {
+                let mut script = OroScript::new(&package_dir, script)?
+                    .workspace_path(&root)
+                    .stdin(Stdio::inherit())
+                    .stdout(Stdio::inherit())
+                    .stderr(Stdio::inherit());
+                if let Some(shell) = shell {
+                    script = script.shell(shell);
+                }
+                if let Some(node_options) = pnp_node_options(&root) {
+                    script = script.env("NODE_OPTIONS", node_options);
+                }
+                script.spawn()?.wait()
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+impl RunCmd {
+    /// Runs the script across workspace packages, showing a spinner with
+    /// the latest output line for each script that's running.
+    async fn run_recursive(self) -> Result<()> {
+        let mut runner = WorkspaceScripts::new()
+            .concurrency(self.script_concurrency)
+            .bail(self.bail)
+            .on_script_start(|pkg, event| {
+                Span::current().pb_set_style(
+                    &ProgressStyle::default_bar()
+                        .template(&format!(
+                            "{{span_child_prefix}}{{spinner}} {}::{event} ({{elapsed}}): {{wide_msg:.dim}}",
+                            pkg.name(),
+                        ))
+                        .unwrap(),
+                );
+            })
+            .on_script_line(|line| {
+                Span::current().pb_set_message(line);
+            });
+        for name in &self.filter {
+            runner = runner.workspace(name);
+        }
+        if let Some(shell) = &self.script_shell {
+            runner = runner.script_shell(shell);
+        }
+        let mode = self.script_output.unwrap_or_default();
+        runner = runner.script_output(ScriptOutput::new(mode));
+        let report = runner.run(&self.root, &self.script).await?;
+        let script = &self.script;
+        for failure in &report.failed {
+            // Other modes already printed the output as it came in.
+            if mode == ScriptOutputMode::Progress {
+                tracing::error!(
+                    "`{script}` failed in {}: {}\n{}",
+                    failure.name,
+                    failure.error,
+                    failure.output
+                );
+            } else {
+                tracing::error!("`{script}` failed in {}: {}", failure.name, failure.error);
+            }
+        }
+        if !report.skipped.is_empty() {
+            tracing::warn!(
+                "Didn't run `{script}` in {}, since an earlier script failed. Use `--no-bail` to keep going after failures.",
+                report.skipped.join(", ")
+            );
+        }
+        tracing::info!(
+            "Ran `{script}` in {} package{}.",
+            report.succeeded.len(),
+            if report.succeeded.len() == 1 { "" } else { "s" }
+        );
+        if report.failed.is_empty() {
+            Ok(())
+        } else {
+            Err(OroError::WorkspaceScriptsFailed {
+                script: script.clone(),
+                count: report.failed.len(),
+            }
+            .into())
+        }
+    }
+}
+
+/// `NODE_OPTIONS` that load the project's Plug'n'Play runtime, if it was
+/// installed with `--pnp`.
+fn pnp_node_options(root: &Path) -> Option<String> {
+    let runtime = root.join(node_maintainer::PNP_FILE_NAME);
+    if !runtime.exists() {
+        return None;
+    }
+    // Scripts might run in a workspace package, rather than the root.
+    let runtime = if runtime.is_absolute() {
+        runtime
+    } else {
+        std::env::current_dir().ok()?.join(runtime)
+    };
+    let require = format!("--require \"{}\"", runtime.display());
+    Some(match std::env::var("NODE_OPTIONS") {
+        Ok(existing) if !existing.trim().is_empty() => format!("{existing} {require}"),
+        _ => require,
+    })
+}
+
+/// Orogene-specific configuration, read from the `orogene` field in
+/// `package.json`.
+#[derive(Debug, Default, Deserialize)]
+struct OroManifestConfig {
+    #[serde(default)]
+    scripts: HashMap<String, ScriptConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptConfig {
+    /// Scripts that need to run before this one. Plain names refer to
+    /// scripts in the same package. Names prefixed with `^` refer to scripts
+    /// in any workspace packages this package depends on.
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+#[derive(Debug)]
+struct WorkspacePackage {
+    name: Option<String>,
+    path: PathBuf,
+    manifest: Manifest,
+    config: OroManifestConfig,
+}
+
+impl WorkspacePackage {
+    async fn load(path: PathBuf) -> Result<Self> {
+        let manifest: Manifest = serde_json::from_str(
+            &async_std::fs::read_to_string(path.join("package.json"))
+                .await
+                .into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+        Self::from_manifest(path, manifest)
+    }
+
+    fn from_manifest(path: PathBuf, manifest: Manifest) -> Result<Self> {
+        let config = manifest
+            ._rest
+            .get("orogene")
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .into_diagnostic()?
+            .unwrap_or_default();
+        Ok(Self {
+            name: manifest.name.clone(),
+            path,
+            manifest,
+            config,
+        })
+    }
+
+    fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    /// Names of all the dependencies this package declares, of any type.
+    fn dependency_names(&self) -> impl Iterator<Item = &String> {
+        self.manifest
+            .dependencies
+            .keys()
+            .chain(self.manifest.dev_dependencies.keys())
+            .chain(self.manifest.optional_dependencies.keys())
+            .chain(self.manifest.peer_dependencies.keys())
+    }
+}
+
+/// Loads the root package, followed by all of its workspace packages.
+async fn load_packages(root: &Path) -> Result<Vec<WorkspacePackage>> {
+    let root_pkg = WorkspacePackage::load(root.to_path_buf()).await?;
+    let mut packages = Vec::new();
+    for dir in Workspaces::package_dirs(root, &root_pkg.manifest.workspaces) {
+        packages.push(WorkspacePackage::load(root.join(dir)).await?);
+    }
+    packages.insert(0, root_pkg);
+    Ok(packages)
+}
+
+/// Works out the order scripts need to run in, based on their `dependsOn`
+/// declarations.
+struct ScriptPlanner<'a> {
+    packages: &'a [WorkspacePackage],
+    visiting: Vec<(usize, String)>,
+    done: HashSet<(usize, String)>,
+    plan: Vec<(usize, String)>,
+    /// Whether `pre<script>` and `post<script>` run along with each script,
+    /// like with npm.
+    pre_post: bool,
+}
+
+impl<'a> ScriptPlanner<'a> {
+    fn visit(&mut self, idx: usize, script: &str, required: bool) -> Result<()> {
+        let key = (idx, script.to_string());
+        if self.done.contains(&key) {
+            return Ok(());
+        }
+        if let Some(pos) = self.visiting.iter().position(|k| k == &key) {
+            let cycle = self.visiting[pos..]
+                .iter()
+                .chain(std::iter::once(&key))
+                .map(|(idx, script)| format!("{}#{script}", self.packages[*idx].display_name()))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(OroError::ScriptDependencyCycle(cycle).into());
+        }
+
+        let packages = self.packages;
+        let pkg = &packages[idx];
+        let has_script = pkg.manifest.scripts.contains_key(script);
+        let depends_on = pkg
+            .config
+            .scripts
+            .get(script)
+            .map(|config| config.depends_on.clone());
+        if required && !has_script && depends_on.is_none() {
+            return Err(OroError::MissingScript {
+                package: pkg.display_name(),
+                script: script.into(),
+            }
+            .into());
+        }
+
+        self.visiting.push(key.clone());
+        for dep in depends_on.unwrap_or_default() {
+            if let Some(dep) = dep.strip_prefix('^') {
+                let dep_idxs = pkg
+                    .dependency_names()
+                    .filter_map(|name| packages.iter().position(|p| p.name.as_ref() == Some(name)))
+                    .filter(|dep_idx| *dep_idx != idx)
+                    .collect::<Vec<_>>();
+                for dep_idx in dep_idxs {
+                    self.visit(dep_idx, dep, false)?;
+                }
+            } else {
+                self.visit(idx, &dep, true)?;
+            }
+        }
+        self.visiting.pop();
+
+        self.done.insert(key.clone());
+        if has_script {
+            if self.pre_post {
+                self.push_once(idx, format!("pre{script}"));
+            }
+            self.plan.push(key);
+            if self.pre_post {
+                self.push_once(idx, format!("post{script}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a `pre<script>` or `post<script>` to the plan, if the package
+    /// has it and it isn't in the plan already, like when something
+    /// `dependsOn` it directly.
+    fn push_once(&mut self, idx: usize, script: String) {
+        if self.packages[idx].manifest.scripts.contains_key(&script)
+            && self.done.insert((idx, script.clone()))
+        {
+            self.plan.push((idx, script));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, manifest: serde_json::Value) -> WorkspacePackage {
+        WorkspacePackage::from_manifest(
+            PathBuf::from(name),
+            serde_json::from_value(manifest).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn plan(packages: &[WorkspacePackage], script: &str, pre_post: bool) -> Result<Vec<String>> {
+        let mut planner = ScriptPlanner {
+            packages,
+            visiting: Vec::new(),
+            done: HashSet::new(),
+            plan: Vec::new(),
+            pre_post,
+        };
+        planner.visit(0, script, true)?;
+        Ok(planner
+            .plan
+            .into_iter()
+            .map(|(idx, script)| format!("{}#{script}", packages[idx].display_name()))
+            .collect())
+    }
+
+    #[test]
+    fn pre_and_post_scripts() {
+        let packages = [package(
+            "a",
+            serde_json::json!({
+                "name": "a",
+                "scripts": {
+                    "postbuild": "echo post",
+                    "build": "echo build",
+                    "prebuild": "echo pre"
+                }
+            }),
+        )];
+        assert_eq!(
+            plan(&packages, "build", true).unwrap(),
+            ["a#prebuild", "a#build", "a#postbuild"]
+        );
+    }
+
+    #[test]
+    fn no_pre_post() {
+        let packages = [package(
+            "a",
+            serde_json::json!({
+                "name": "a",
+                "scripts": {
+                    "prebuild": "echo pre",
+                    "build": "echo build",
+                    "postbuild": "echo post"
+                }
+            }),
+        )];
+        assert_eq!(plan(&packages, "build", false).unwrap(), ["a#build"]);
+    }
+
+    #[test]
+    fn pre_script_without_script() {
+        let packages = [
+            package(
+                "a",
+                serde_json::json!({
+                    "name": "a",
+                    "dependencies": { "b": "*" },
+                    "scripts": { "prebuild": "echo pre" },
+                    "orogene": { "scripts": { "test": { "dependsOn": ["^build"] } } }
+                }),
+            ),
+            package(
+                "b",
+                serde_json::json!({
+                    "name": "b",
+                    "scripts": { "prebuild": "echo pre" }
+                }),
+            ),
+        ];
+        assert!(plan(&packages, "build", true).is_err());
+        // Dependencies without the script are skipped, pre script and all.
+        assert!(plan(&packages, "test", true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pre_scripts_run_once() {
+        let packages = [
+            package(
+                "a",
+                serde_json::json!({
+                    "name": "a",
+                    "dependencies": { "b": "*" },
+                    "scripts": { "build": "echo build" },
+                    "orogene": {
+                        "scripts": { "build": { "dependsOn": ["^prebuild", "^build"] } }
+                    }
+                }),
+            ),
+            package(
+                "b",
+                serde_json::json!({
+                    "name": "b",
+                    "scripts": {
+                        "prebuild": "echo pre",
+                        "build": "echo build",
+                        "postbuild": "echo post"
+                    }
+                }),
+            ),
+        ];
+        assert_eq!(
+            plan(&packages, "build", true).unwrap(),
+            ["b#prebuild", "b#build", "b#postbuild", "a#build"]
+        );
+    }
+}
@@ -4,6 +4,7 @@ use colored::*;
 use humansize::{file_size_opts, FileSize};
 use miette::{IntoDiagnostic, Result, WrapErr};
 use oro_common::{Bin, DeprecationInfo, Manifest, NpmUser, Person, PersonField, VersionMetadata};
+use oro_i18n::t;
 use term_grid::{Cell, Direction, Filling, Grid, GridOptions};
 
 use crate::commands::OroCommand;
@@ -27,7 +28,7 @@ pub struct ViewCmd {
 #[async_trait]
 impl OroCommand for ViewCmd {
     async fn execute(self) -> Result<()> {
-        let pkg = self.nassun_args.to_nassun().resolve(&self.pkg).await?;
+        let pkg = self.nassun_args.to_nassun()?.resolve(&self.pkg).await?;
         let packument = pkg.packument().await?;
         let metadata = pkg.metadata().await?;
         // TODO: oro view pkg [<field>[.<subfield>...]]
@@ -66,23 +67,30 @@ impl OroCommand for ViewCmd {
 
             // name@version | license | deps: 123 | releases: 123
             println!(
-                "{}@{} | {} | deps: {} | releases: {}",
-                name.clone()
-                    .unwrap_or_else(|| String::from(""))
-                    .bright_green()
-                    .underline(),
-                version
-                    .clone()
-                    .unwrap_or_else(|| "0.0.0".parse().unwrap())
-                    .to_string()
-                    .bright_green()
-                    .underline(),
-                license
-                    .clone()
-                    .unwrap_or_else(|| "Proprietary".to_string())
-                    .green(),
-                dependencies.len().to_string().cyan(),
-                packument.versions.len().to_string().yellow(),
+                "{}",
+                t!(
+                    "view-summary",
+                    name = name
+                        .clone()
+                        .unwrap_or_else(|| String::from(""))
+                        .bright_green()
+                        .underline()
+                        .to_string(),
+                    version = version
+                        .clone()
+                        .unwrap_or_else(|| "0.0.0".parse().unwrap())
+                        .to_string()
+                        .bright_green()
+                        .underline()
+                        .to_string(),
+                    license = license
+                        .clone()
+                        .unwrap_or_else(|| "Proprietary".to_string())
+                        .green()
+                        .to_string(),
+                    deps = dependencies.len().to_string().cyan().to_string(),
+                    releases = packument.versions.len().to_string().yellow().to_string(),
+                )
             );
 
             // <descrition>
@@ -98,7 +106,7 @@ impl OroCommand for ViewCmd {
 
             // DEPRECATED - <deprecation message>
             if let Some(info) = deprecated.as_ref() {
-                let deprecated = "DEPRECATED".on_magenta();
+                let deprecated = t!("view-deprecated").on_magenta();
                 if let DeprecationInfo::Reason(msg) = info {
                     println!("{deprecated} {msg}\n");
                 } else {
@@ -109,12 +117,15 @@ impl OroCommand for ViewCmd {
             // keywords: foo, bar, baz
             if !keywords.is_empty() {
                 println!(
-                    "keywords: {}\n",
-                    keywords
-                        .iter()
-                        .map(|k| k.yellow().to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
+                    "{}\n",
+                    t!(
+                        "view-keywords",
+                        keywords = keywords
+                            .iter()
+                            .map(|k| k.yellow().to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )
                 );
             }
 
@@ -133,11 +144,15 @@ impl OroCommand for ViewCmd {
                         .collect::<Vec<String>>(),
                 };
                 println!(
-                    "bins: {}\n",
-                    bins.iter()
-                        .map(|b| b.yellow().to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
+                    "{}\n",
+                    t!(
+                        "view-bins",
+                        bins = bins
+                            .iter()
+                            .map(|b| b.yellow().to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )
                 );
             }
 
@@ -163,6 +178,41 @@ impl OroCommand for ViewCmd {
             }
             println!();
 
+            // provenance: verified
+            // .repository: https://github.com/foo/bar
+            match pkg.provenance().await {
+                Ok(Some(provenance)) => {
+                    println!(
+                        "{}",
+                        t!(
+                            "view-provenance",
+                            status = t!("view-provenance-verified").green().to_string()
+                        )
+                    );
+                    if let Some(repository) = &provenance.repository {
+                        println!(".repository: {}", repository.cyan());
+                    }
+                    if let Some(build) = &provenance.build {
+                        println!(".build: {}", build.cyan());
+                    }
+                }
+                Ok(None) => println!(
+                    "{}",
+                    t!(
+                        "view-provenance",
+                        status = t!("view-provenance-none").yellow().to_string()
+                    )
+                ),
+                Err(err) => println!(
+                    "{} ({err})",
+                    t!(
+                        "view-provenance",
+                        status = t!("view-provenance-invalid").red().to_string()
+                    )
+                ),
+            }
+            println!();
+
             // dependencies:
             // foo: ^1.2.3  bar: ^0.1.0
             if !dependencies.is_empty() {
@@ -179,10 +229,10 @@ impl OroCommand for ViewCmd {
                     grid.add(Cell::from(val));
                 }
                 if let Some(out) = grid.fit_into_width(width) {
-                    print!("dependencies:\n{out}");
+                    print!("{}\n{out}", t!("view-dependencies"));
                     let count = dependencies.len();
                     if count > max_deps {
-                        println!("(...and {} more)", count - max_deps);
+                        println!("{}", t!("view-more-dependencies", count = count - max_deps));
                     }
                 }
                 println!();
@@ -191,7 +241,7 @@ impl OroCommand for ViewCmd {
             // maintainers:
             // - Alex <something@email.com>
             if !maintainers.is_empty() {
-                println!("maintainers:");
+                println!("{}", t!("view-maintainers"));
                 for person in maintainers.iter() {
                     match person {
                         PersonField::Str(string) => {
@@ -233,9 +283,12 @@ impl OroCommand for ViewCmd {
                             .wrap_err("view::bad_date")?,
                     );
                     print!(
-                        "published {} by {}",
-                        human.to_string().yellow(),
-                        name.yellow()
+                        "{}",
+                        t!(
+                            "view-published",
+                            when = human.to_string().yellow().to_string(),
+                            user = name.yellow().to_string(),
+                        )
                     );
                     if let Some(email) = email {
                         print!(" <{}>", email.cyan());
@@ -1,4 +1,5 @@
 use miette::Diagnostic;
+use oro_i18n::FluentArgs;
 use thiserror::Error;
 
 #[derive(Debug, Error, Diagnostic)]
@@ -15,4 +16,214 @@ pub enum OroError {
         help("Use the package name as it appears in your package.json instead.")
     )]
     InvalidPackageName(String),
+
+    /// A script was requested that isn't defined in the package's
+    /// `scripts`, and has no `dependsOn` configuration under
+    /// `orogene.scripts` either.
+    #[error("Package `{package}` has no script named `{script}`.")]
+    #[diagnostic(
+        code(oro::run::missing_script),
+        url(docsrs),
+        help("Add a `{script}` entry to the `scripts` field of this package's package.json.")
+    )]
+    MissingScript { package: String, script: String },
+
+    /// The `dependsOn` declarations for a script eventually depend on the
+    /// script itself, so there's no order they could be run in.
+    #[error("Script dependency cycle detected: {0}")]
+    #[diagnostic(
+        code(oro::run::dependency_cycle),
+        url(docsrs),
+        help("Remove one of the `dependsOn` entries in this cycle.")
+    )]
+    ScriptDependencyCycle(String),
+
+    /// The workspace package passed to `--workspace` couldn't be found in
+    /// the current project.
+    #[error("No workspace package named `{0}` was found.")]
+    #[diagnostic(
+        code(oro::run::unknown_workspace),
+        url(docsrs),
+        help(
+            "Make sure the package is listed in the `workspaces` field of your root package.json."
+        )
+    )]
+    UnknownWorkspace(String),
+
+    /// `oro run --recursive` ran a script in several workspace packages,
+    /// and it failed in some of them. Their output is printed above.
+    #[error("`{script}` failed in {count} workspace package(s).")]
+    #[diagnostic(
+        code(oro::run::workspace_scripts_failed),
+        url(docsrs),
+        help("Check the output of the failed scripts above.")
+    )]
+    WorkspaceScriptsFailed { script: String, count: usize },
+
+    /// The verification apply done by `oro migrate` resolved some packages
+    /// to different versions than the project's previous lockfile, so the
+    /// project wasn't switched over.
+    #[error("{0} package(s) resolved to different versions than in the previous lockfile.")]
+    #[diagnostic(
+        code(oro::migrate::diverged),
+        url(docsrs),
+        help("Review the differences above, then rerun with `--force` to migrate anyway.")
+    )]
+    MigrationDiverged(usize),
+
+    /// The root project's `packageManager` or `devEngines` fields ask for a
+    /// different version of orogene, Node.js, or some other part of the
+    /// toolchain than the one in use.
+    #[error("This project expects a different toolchain:\n{}", .0.iter().map(|problem| format!("  {problem}")).collect::<Vec<_>>().join("\n"))]
+    #[diagnostic(
+        code(oro::apply::toolchain_mismatch),
+        url(docsrs),
+        help("Switch to the versions the project asks for, or use `--no-toolchain-check` to skip this check.")
+    )]
+    ToolchainMismatch(Vec<String>),
+
+    /// `--global` was used without a `--global-prefix`, and there's no
+    /// default prefix to fall back to.
+    #[error("Couldn't figure out where to install global packages.")]
+    #[diagnostic(
+        code(oro::apply::no_global_prefix),
+        url(docsrs),
+        help("Pass `--global-prefix`, set `NPM_CONFIG_PREFIX`, or make sure `node` is on your `PATH`.")
+    )]
+    NoGlobalPrefix,
+
+    /// A `--registry-cert` was given for a registry without a matching
+    /// `--registry-key`.
+    #[error("No client key was given for the certificate for `{0}`.")]
+    #[diagnostic(
+        code(oro::tls::missing_client_key),
+        url(docsrs),
+        help("Pass `--registry-key {0}=/path/to/key.pem` too.")
+    )]
+    MissingClientKey(String),
+
+    /// A `--registry-key` was given for a registry without a matching
+    /// `--registry-cert`.
+    #[error("No client certificate was given for the key for `{0}`.")]
+    #[diagnostic(
+        code(oro::tls::missing_client_cert),
+        url(docsrs),
+        help("Pass `--registry-cert {0}=/path/to/cert.pem` too.")
+    )]
+    MissingClientCert(String),
+
+    /// `--credentials` was given a field that isn't one of the supported
+    /// ones.
+    #[error("Unknown credential field `{field}` for `{host}`.")]
+    #[diagnostic(
+        code(oro::credentials::unknown_field),
+        url(docsrs),
+        help("Supported fields are `token`, `username`, `password`, `auth`, `s3-access-key-id`, `s3-secret-access-key`, `s3-session-token`, and `s3-region`.")
+    )]
+    UnknownCredentialField { host: String, field: String },
+
+    /// The credentials for a registry didn't include enough to
+    /// authenticate with, like a `username` without a `password`.
+    #[error("Incomplete credentials for `{0}`.")]
+    #[diagnostic(
+        code(oro::credentials::incomplete),
+        url(docsrs),
+        help("Provide a `token`, both a `username` and a `password`, an `auth`, both an `s3-access-key-id` and an `s3-secret-access-key`, or a `helper`.")
+    )]
+    IncompleteCredentials(String),
+
+    /// `oro daemon` found another daemon already listening on its socket.
+    #[error("A daemon is already listening on {}.", .0.display())]
+    #[diagnostic(
+        code(oro::daemon::already_running),
+        url(docsrs),
+        help("Send it a `shutdown` request first, or pass `--socket` to listen somewhere else.")
+    )]
+    DaemonAlreadyRunning(std::path::PathBuf),
+
+    /// `oro daemon` listens on a Unix socket, which isn't available on
+    /// this platform.
+    #[error("`oro daemon` isn't supported on this platform yet.")]
+    #[diagnostic(code(oro::daemon::unsupported), url(docsrs))]
+    DaemonUnsupported,
+
+    /// `--reporter` was given something other than `default` or `ndjson`.
+    #[error("Unknown reporter `{0}`.")]
+    #[diagnostic(
+        code(oro::reporter::unknown),
+        url(docsrs),
+        help("Use `default` or `ndjson`.")
+    )]
+    UnknownReporter(String),
+
+    /// `oro completions` was given a shell it doesn't have a script for.
+    #[error("Can't generate completions for unknown shell `{0}`.")]
+    #[diagnostic(
+        code(oro::completions::unknown_shell),
+        url(docsrs),
+        help("Use `bash`, `zsh`, `fish`, or `powershell`.")
+    )]
+    UnknownShell(String),
+
+    /// `oro audit` found packages with known vulnerabilities, or packages
+    /// that break the project's policy. They're listed above.
+    #[error("Found {vulnerabilities} vulnerable package(s) and {violations} policy violation(s).")]
+    #[diagnostic(
+        code(oro::audit::failed),
+        url(docsrs),
+        help("Update or replace the packages listed above, or change the project's `oro-policy.kdl`.")
+    )]
+    AuditFailed {
+        vulnerabilities: usize,
+        violations: usize,
+    },
+}
+
+impl OroError {
+    /// Values for the placeholders in this error's translations. See
+    /// [`oro_i18n::localize`].
+    pub fn fluent_args(&self) -> FluentArgs<'static> {
+        let mut args = FluentArgs::new();
+        match self {
+            Self::InvalidPackageName(name) => args.set("name", name.clone()),
+            Self::MissingScript { package, script } => {
+                args.set("package", package.clone());
+                args.set("script", script.clone());
+            }
+            Self::ScriptDependencyCycle(cycle) => args.set("cycle", cycle.clone()),
+            Self::UnknownWorkspace(workspace) => args.set("workspace", workspace.clone()),
+            Self::WorkspaceScriptsFailed { script, count } => {
+                args.set("script", script.clone());
+                args.set("count", *count);
+            }
+            Self::MigrationDiverged(count) => args.set("count", *count),
+            Self::ToolchainMismatch(problems) => args.set(
+                "problems",
+                problems
+                    .iter()
+                    .map(|problem| format!("  {problem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Self::MissingClientKey(host)
+            | Self::MissingClientCert(host)
+            | Self::IncompleteCredentials(host) => args.set("host", host.clone()),
+            Self::UnknownCredentialField { host, field } => {
+                args.set("host", host.clone());
+                args.set("field", field.clone());
+            }
+            Self::DaemonAlreadyRunning(socket) => args.set("socket", socket.display().to_string()),
+            Self::UnknownReporter(reporter) => args.set("reporter", reporter.clone()),
+            Self::UnknownShell(shell) => args.set("shell", shell.clone()),
+            Self::AuditFailed {
+                vulnerabilities,
+                violations,
+            } => {
+                args.set("vulnerabilities", *vulnerabilities);
+                args.set("violations", *violations);
+            }
+            Self::NoGlobalPrefix | Self::DaemonUnsupported => {}
+        }
+        args
+    }
 }
@@ -1,7 +1,8 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Args;
-use nassun::{Nassun, NassunOpts};
+use miette::Result;
+use nassun::{Nassun, NassunOpts, TarballRewrite};
 use url::Url;
 
 use crate::apply_args::ApplyArgs;
@@ -15,9 +16,75 @@ pub struct NassunArgs {
     #[arg(from_global)]
     registry: Url,
 
+    #[arg(from_global)]
+    mirrors: Vec<Url>,
+
+    #[arg(from_global)]
+    tarball_rewrites: Vec<TarballRewrite>,
+
     #[arg(from_global)]
     scoped_registries: Vec<(String, Url)>,
 
+    #[arg(from_global)]
+    credentials: Vec<(String, String, String)>,
+
+    #[arg(from_global)]
+    proxy: Option<Url>,
+
+    #[arg(from_global)]
+    https_proxy: Option<Url>,
+
+    #[arg(from_global)]
+    noproxy: Option<String>,
+
+    #[arg(from_global)]
+    cafile: Option<PathBuf>,
+
+    #[arg(from_global)]
+    ca: Vec<String>,
+
+    #[arg(from_global)]
+    strict_ssl: bool,
+
+    #[arg(from_global)]
+    cert: Option<PathBuf>,
+
+    #[arg(from_global)]
+    key: Option<PathBuf>,
+
+    #[arg(from_global)]
+    registry_certs: Vec<(String, PathBuf)>,
+
+    #[arg(from_global)]
+    registry_keys: Vec<(String, PathBuf)>,
+
+    #[arg(from_global)]
+    network_limit: Option<u64>,
+
+    #[arg(from_global)]
+    max_sockets: Option<usize>,
+
+    #[arg(from_global)]
+    max_sockets_per_host: Option<usize>,
+
+    #[arg(from_global)]
+    pool_idle_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    connect_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    tls_handshake_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    first_byte_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    request_timeout: Option<std::time::Duration>,
+
+    #[arg(from_global)]
+    dns_ttl: Option<std::time::Duration>,
+
     #[arg(from_global)]
     root: PathBuf,
 
@@ -30,23 +97,113 @@ impl NassunArgs {
         Self {
             default_tag: apply_args.default_tag.clone(),
             registry: apply_args.registry.clone(),
+            mirrors: apply_args.mirrors.clone(),
+            tarball_rewrites: apply_args.tarball_rewrites.clone(),
             scoped_registries: apply_args.scoped_registries.clone(),
+            credentials: apply_args.credentials.clone(),
+            proxy: apply_args.proxy.clone(),
+            https_proxy: apply_args.https_proxy.clone(),
+            noproxy: apply_args.noproxy.clone(),
+            cafile: apply_args.cafile.clone(),
+            ca: apply_args.ca.clone(),
+            strict_ssl: apply_args.strict_ssl,
+            cert: apply_args.cert.clone(),
+            key: apply_args.key.clone(),
+            registry_certs: apply_args.registry_certs.clone(),
+            registry_keys: apply_args.registry_keys.clone(),
+            network_limit: apply_args.network_limit,
+            max_sockets: apply_args.max_sockets,
+            max_sockets_per_host: apply_args.max_sockets_per_host,
+            pool_idle_timeout: apply_args.pool_idle_timeout,
+            connect_timeout: apply_args.connect_timeout,
+            tls_handshake_timeout: apply_args.tls_handshake_timeout,
+            first_byte_timeout: apply_args.first_byte_timeout,
+            request_timeout: apply_args.request_timeout,
+            dns_ttl: apply_args.dns_ttl,
             root: apply_args.root.clone(),
             cache: apply_args.cache.clone(),
         }
     }
 
-    pub fn to_nassun(&self) -> Nassun {
+    pub fn cache(&self) -> Option<&Path> {
+        self.cache.as_deref()
+    }
+
+    pub fn to_nassun(&self) -> Result<Nassun> {
         let mut nassun_opts = NassunOpts::new()
             .registry(self.registry.clone())
             .base_dir(self.root.clone())
             .default_tag(&self.default_tag);
+        for mirror in &self.mirrors {
+            nassun_opts = nassun_opts.mirror(mirror.clone());
+        }
+        for rewrite in &self.tarball_rewrites {
+            nassun_opts = nassun_opts.tarball_rewrite(rewrite.clone());
+        }
         for (scope, registry) in &self.scoped_registries {
             nassun_opts = nassun_opts.scope_registry(scope.clone(), registry.clone());
         }
+        for (host, credentials) in crate::registry_credentials(&self.root, &self.credentials)? {
+            nassun_opts = nassun_opts.credentials(host, credentials);
+        }
+        for host in crate::always_auth_hosts(&self.root, &self.credentials) {
+            nassun_opts = nassun_opts.always_auth(host);
+        }
         if let Some(cache) = &self.cache {
             nassun_opts = nassun_opts.cache(cache.clone());
         }
-        nassun_opts.build()
+        if let Some(proxy) = &self.proxy {
+            nassun_opts = nassun_opts.proxy(proxy.clone());
+        }
+        if let Some(proxy) = &self.https_proxy {
+            nassun_opts = nassun_opts.https_proxy(proxy.clone());
+        }
+        if let Some(hosts) = &self.noproxy {
+            nassun_opts = nassun_opts.no_proxy(hosts);
+        }
+        let (tls, host_tls) = crate::TlsArgs {
+            cafile: self.cafile.as_deref(),
+            ca: &self.ca,
+            strict_ssl: self.strict_ssl,
+            cert: self.cert.as_deref(),
+            key: self.key.as_deref(),
+            registry_certs: &self.registry_certs,
+            registry_keys: &self.registry_keys,
+        }
+        .configs()?;
+        if let Some(tls) = tls {
+            nassun_opts = nassun_opts.tls(tls);
+        }
+        for (host, tls) in host_tls {
+            nassun_opts = nassun_opts.host_tls(host, tls);
+        }
+        if let Some(limit) = self.network_limit {
+            nassun_opts = nassun_opts.network_limit(limit);
+        }
+        if let Some(max) = self.max_sockets {
+            nassun_opts = nassun_opts.max_sockets(max);
+        }
+        if let Some(max) = self.max_sockets_per_host {
+            nassun_opts = nassun_opts.max_sockets_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            nassun_opts = nassun_opts.pool_idle_timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            nassun_opts = nassun_opts.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.tls_handshake_timeout {
+            nassun_opts = nassun_opts.tls_handshake_timeout(timeout);
+        }
+        if let Some(timeout) = self.first_byte_timeout {
+            nassun_opts = nassun_opts.first_byte_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            nassun_opts = nassun_opts.request_timeout(timeout);
+        }
+        if let Some(ttl) = self.dns_ttl {
+            nassun_opts = nassun_opts.dns_ttl(ttl);
+        }
+        Ok(nassun_opts.build())
     }
 }
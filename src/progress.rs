@@ -0,0 +1,180 @@
+//! Progress bars for `apply`, one for each phase.
+
+use std::sync::{Arc, Mutex};
+
+use humansize::{file_size_opts, FileSize};
+use indicatif::ProgressStyle;
+use node_maintainer::NodeMaintainerOptions;
+use tracing::Span;
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+
+/// Progress bars for applying `node_modules/`. Packages start downloading
+/// and extracting as soon as they're resolved, so resolving, downloading,
+/// and extracting each get a bar of their own, shown together. Install
+/// scripts get one too, with a spinner under it for each package that's
+/// building.
+///
+/// Each bar is a span that [`tracing_indicatif`] draws, so nothing gets
+/// drawn with `--no-progress`, or when stderr isn't a terminal, and the
+/// messages logged as each phase finishes are all that's left.
+pub(crate) struct ApplyProgress {
+    emoji: bool,
+    resolving: Mutex<Span>,
+    downloading: Mutex<Span>,
+    extracting: Mutex<Span>,
+    scripts: Mutex<Span>,
+}
+
+impl ApplyProgress {
+    pub(crate) fn new(emoji: bool) -> Arc<Self> {
+        let progress = Self {
+            emoji,
+            resolving: Mutex::new(tracing::debug_span!(parent: None, "resolving")),
+            downloading: Mutex::new(tracing::debug_span!(parent: None, "downloading")),
+            extracting: Mutex::new(tracing::debug_span!(parent: None, "extracting")),
+            scripts: Mutex::new(Span::none()),
+        };
+        progress.style(
+            &progress.resolving,
+            "🔍 ",
+            "{emoji}Resolving   {bar:40} [{pos}/{len}] {wide_msg:.dim}",
+        );
+        progress.style(
+            &progress.downloading,
+            "🚚 ",
+            "{emoji}Downloading {bytes} ({binary_bytes_per_sec}) {wide_msg:.dim}",
+        );
+        // How many packages there are to extract isn't known until
+        // resolution is done, so there's no bar until then.
+        progress.style(
+            &progress.extracting,
+            "📦 ",
+            "{emoji}Extracting  [{pos}] {wide_msg:.dim}",
+        );
+        progress.resolving.lock().unwrap().pb_set_length(0);
+        Arc::new(progress)
+    }
+
+    /// Hooks the bars up to `builder`'s progress callbacks, replacing any
+    /// that were already there.
+    pub(crate) fn attach(
+        self: &Arc<Self>,
+        builder: NodeMaintainerOptions,
+    ) -> NodeMaintainerOptions {
+        let (added, resolved, extracted, downloaded, scripts) = (
+            self.clone(),
+            self.clone(),
+            self.clone(),
+            self.clone(),
+            self.clone(),
+        );
+        builder
+            .on_resolution_added(move || {
+                added.resolving.lock().unwrap().pb_inc_length(1);
+            })
+            .on_resolve_progress(move |pkg| {
+                let span = resolved.resolving.lock().unwrap();
+                span.pb_inc(1);
+                span.pb_set_message(&pkg.resolved().to_string());
+            })
+            .on_extract_progress(move |pkg| {
+                let span = extracted.extracting.lock().unwrap();
+                span.pb_inc(1);
+                span.pb_set_message(&pkg.resolved().to_string());
+            })
+            .on_extract_bytes(move |pkg, bytes| {
+                let span = downloaded.downloading.lock().unwrap();
+                span.pb_inc(bytes.bytes);
+                span.pb_set_message(&format!(
+                    "{} ({})",
+                    pkg.name(),
+                    bytes
+                        .package_bytes
+                        .file_size(file_size_opts::DECIMAL)
+                        .unwrap()
+                ));
+            })
+            .on_script_start(move |pkg, event| {
+                scripts.scripts.lock().unwrap().pb_inc(1);
+                // Each script runs in a span of its own, under the scripts
+                // bar, which gets a spinner for as long as it's running.
+                Span::current().pb_set_style(
+                    &ProgressStyle::default_bar()
+                        .template(&format!(
+                            "{{span_child_prefix}}{{spinner}} {}::{event} ({{elapsed}}): {{wide_msg:.dim}}",
+                            pkg.name(),
+                        ))
+                        .unwrap(),
+                );
+            })
+    }
+
+    /// Shows the resolving bar, along with the downloading and extracting
+    /// ones if packages are going to be installed, since those start while
+    /// the tree is still resolving.
+    pub(crate) fn start_resolving(&self, installing: bool) {
+        show(&self.resolving);
+        if installing {
+            show(&self.downloading);
+            show(&self.extracting);
+        }
+    }
+
+    /// Takes down the resolving bar, now that there are `packages` to
+    /// extract.
+    pub(crate) fn finish_resolving(&self, packages: usize) {
+        *self.resolving.lock().unwrap() = Span::none();
+        self.style(
+            &self.extracting,
+            "📦 ",
+            "{emoji}Extracting  {bar:40} [{pos}/{len}] ETA {eta} {wide_msg:.dim}",
+        );
+        self.extracting
+            .lock()
+            .unwrap()
+            .pb_set_length(packages as u64);
+    }
+
+    pub(crate) fn start_extracting(&self) {
+        show(&self.downloading);
+        show(&self.extracting);
+    }
+
+    pub(crate) fn finish_extracting(&self) {
+        *self.downloading.lock().unwrap() = Span::none();
+        *self.extracting.lock().unwrap() = Span::none();
+    }
+
+    /// Uses `span` as the scripts bar, counting scripts as they start. Each
+    /// script's own span goes under it, as long as scripts run inside it.
+    pub(crate) fn start_scripts(&self, span: &Span) {
+        self.style_span(
+            span,
+            "🏃 ",
+            "{spinner} {emoji}Running scripts [{pos} started] ({elapsed}) {wide_msg:.dim}",
+        );
+        *self.scripts.lock().unwrap() = span.clone();
+    }
+
+    pub(crate) fn finish_scripts(&self) {
+        *self.scripts.lock().unwrap() = Span::none();
+    }
+
+    fn style(&self, span: &Mutex<Span>, emoji: &str, template: &str) {
+        self.style_span(&span.lock().unwrap(), emoji, template);
+    }
+
+    fn style_span(&self, span: &Span, emoji: &str, template: &str) {
+        let emoji = if self.emoji { emoji } else { "" };
+        span.pb_set_style(
+            &ProgressStyle::default_bar()
+                .template(&template.replace("{emoji}", emoji))
+                .unwrap(),
+        );
+    }
+}
+
+/// Bars only get drawn once their span has been entered.
+fn show(span: &Mutex<Span>) {
+    span.lock().unwrap().in_scope(|| {});
+}
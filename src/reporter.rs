@@ -0,0 +1,153 @@
+//! `--reporter ndjson`: structured events on stdout, one JSON object per
+//! line, for CI systems and wrappers to parse instead of scraping the
+//! human-oriented output. That output still gets printed, but to stderr.
+
+use std::fmt;
+use std::str::FromStr;
+
+use nassun::{CacheStatus, RequestEvent, RequestKind, RequestObserver};
+use node_maintainer::InstallEvent;
+use serde_json::{json, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use url::Url;
+
+use crate::error::OroError;
+
+/// How progress gets reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Reporter {
+    /// Progress bars and log messages, for people.
+    #[default]
+    Default,
+    /// Newline-delimited JSON events on stdout, for programs.
+    Ndjson,
+}
+
+impl Reporter {
+    pub fn is_ndjson(&self) -> bool {
+        *self == Reporter::Ndjson
+    }
+}
+
+impl FromStr for Reporter {
+    type Err = OroError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(OroError::UnknownReporter(s.into())),
+        }
+    }
+}
+
+/// Prints a single event.
+pub fn emit(event: Value) {
+    // `println!` holds the stdout lock for the whole line, so events from
+    // different threads never get mixed up.
+    println!("{event}");
+}
+
+/// Prints the event for an [`InstallEvent`]. Events that fire many times
+/// per package, like byte counts, are left out.
+pub fn install_event(event: &InstallEvent<'_>) {
+    let event = match event {
+        InstallEvent::Resolved(package) => json!({
+            "type": "resolved",
+            "name": package.name(),
+            "resolved": package.resolved().to_string(),
+        }),
+        InstallEvent::Pruned(path) => json!({
+            "type": "pruned",
+            "path": path.display().to_string(),
+        }),
+        InstallEvent::Extracted(package) => json!({
+            "type": "extracted",
+            "name": package.name(),
+            "resolved": package.resolved().to_string(),
+        }),
+        InstallEvent::ScriptStarted { package, event } => json!({
+            "type": "scriptStarted",
+            "name": package.name(),
+            "event": event,
+        }),
+        InstallEvent::ScriptLine(line) => json!({
+            "type": "scriptLine",
+            "line": line,
+        }),
+        _ => return,
+    };
+    emit(event);
+}
+
+/// Prints an event when each registry request starts, and another when it
+/// finishes.
+#[derive(Debug)]
+pub struct NdjsonObserver;
+
+impl RequestObserver for NdjsonObserver {
+    fn on_request_start(&self, url: &Url, kind: RequestKind) {
+        emit(json!({
+            "type": "fetchStart",
+            "url": url.as_str(),
+            "kind": kind_str(kind),
+        }));
+    }
+
+    fn on_request(&self, event: &RequestEvent) {
+        emit(json!({
+            "type": "fetchFinish",
+            "url": event.url.as_str(),
+            "kind": kind_str(event.kind),
+            "status": event.status,
+            "durationMs": event.duration.as_millis() as u64,
+            "retries": event.retries,
+            "bytes": event.bytes,
+            "cache": match event.cache {
+                CacheStatus::Uncached => "uncached",
+                CacheStatus::Miss => "miss",
+                CacheStatus::Hit => "hit",
+            },
+            "error": event.error,
+        }));
+    }
+}
+
+fn kind_str(kind: RequestKind) -> &'static str {
+    match kind {
+        RequestKind::Packument => "packument",
+        RequestKind::Tarball => "tarball",
+        RequestKind::Other => "other",
+    }
+}
+
+/// Prints an event for every warning and error that gets logged.
+pub struct NdjsonLayer;
+
+impl<S: Subscriber> Layer<S> for NdjsonLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let kind = match *event.metadata().level() {
+            Level::ERROR => "error",
+            Level::WARN => "warning",
+            _ => return,
+        };
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        emit(json!({
+            "type": kind,
+            "message": message.0,
+        }));
+    }
+}
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Just enough of [SARIF
+//! 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+//! to hand `oro audit` results to GitHub code scanning and other CI
+//! security dashboards.
+
+use serde::Serialize;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// A SARIF log with a single run of `oro`.
+#[derive(Debug, Serialize)]
+pub(crate) struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [Run; 1],
+}
+
+#[derive(Debug, Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+/// Something a result can be reported against, like a single advisory, or
+/// a kind of policy rule.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Rule {
+    pub(crate) id: String,
+    pub(crate) short_description: Message,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) help_uri: Option<String>,
+    pub(crate) default_configuration: Configuration,
+    pub(crate) properties: RuleProperties,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Configuration {
+    pub(crate) level: Level,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RuleProperties {
+    pub(crate) tags: Vec<String>,
+    /// A CVSS-like score from `0.0` to `10.0`, as a string. GitHub uses
+    /// this to sort security alerts into low, medium, high, and critical.
+    #[serde(rename = "security-severity", skip_serializing_if = "Option::is_none")]
+    pub(crate) security_severity: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Message {
+    pub(crate) text: String,
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self { text }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: Level,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+    logical_locations: Vec<LogicalLocation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+}
+
+#[derive(Debug, Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogicalLocation {
+    fully_qualified_name: String,
+    kind: &'static str,
+}
+
+impl SarifLog {
+    pub(crate) fn new() -> Self {
+        Self {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: [Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "orogene",
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: "https://orogene.dev",
+                        rules: Vec::new(),
+                    },
+                },
+                results: Vec::new(),
+            }],
+        }
+    }
+
+    /// Reports a result against `rule`, which only gets added to the log
+    /// the first time it's used. `uri` is the file the result belongs to,
+    /// and `package` is where in `node_modules/` the offending package is,
+    /// like `node_modules/a/node_modules/b`.
+    pub(crate) fn add_result(
+        &mut self,
+        rule: Rule,
+        level: Level,
+        message: String,
+        uri: &str,
+        package: &str,
+    ) {
+        let run = &mut self.runs[0];
+        let rules = &mut run.tool.driver.rules;
+        let rule_index = match rules.iter().position(|r| r.id == rule.id) {
+            Some(index) => index,
+            None => {
+                rules.push(rule);
+                rules.len() - 1
+            }
+        };
+        run.results.push(SarifResult {
+            rule_id: rules[rule_index].id.clone(),
+            rule_index,
+            level,
+            message: message.into(),
+            locations: vec![Location {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation { uri: uri.into() },
+                },
+                logical_locations: vec![LogicalLocation {
+                    fully_qualified_name: package.into(),
+                    kind: "module",
+                }],
+            }],
+        });
+    }
+}
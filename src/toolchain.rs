@@ -0,0 +1,217 @@
+//! Checks the root project's `packageManager` and `devEngines` fields
+//! against the toolchain that's actually in use, the same way corepack does
+//! for `packageManager`.
+
+use std::path::Path;
+use std::process::Command;
+
+use miette::Result;
+use node_semver::{Range, Version};
+use serde_json::Value;
+
+use crate::error::OroError;
+
+/// Names orogene goes by in `packageManager` and `devEngines`.
+const OROGENE_NAMES: &[&str] = &["oro", "orogene"];
+
+/// What to do when a `devEngines` requirement isn't met. Orogene can't
+/// download other toolchains, so `download` is treated like `error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OnFail {
+    Ignore,
+    Warn,
+    Error,
+}
+
+/// A single `devEngines` entry.
+#[derive(Debug)]
+struct Requirement {
+    name: String,
+    version: Option<String>,
+    on_fail: OnFail,
+}
+
+impl Requirement {
+    fn describe(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{version}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    fn accepts(&self, name: &str, version: Option<&Version>) -> bool {
+        if self.name != name {
+            return false;
+        }
+        match (&self.version, version) {
+            (None, _) => true,
+            (Some(range), Some(version)) => range
+                .parse::<Range>()
+                .map(|range| range.satisfies(version))
+                .unwrap_or(false),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Checks the `packageManager` and `devEngines` fields in `root`'s
+/// `package.json`. Unmet requirements are logged as warnings or returned as
+/// an error, depending on what the project asked for.
+pub(crate) async fn check(root: &Path) -> Result<()> {
+    let Ok(manifest) = async_std::fs::read_to_string(root.join("package.json")).await else {
+        return Ok(());
+    };
+    let Ok(manifest) = serde_json::from_str::<Value>(&manifest) else {
+        return Ok(());
+    };
+    let current = env!("CARGO_PKG_VERSION")
+        .parse::<Version>()
+        .expect("orogene's own version is valid semver");
+    let mut problems = Vec::new();
+
+    if let Some(spec) = manifest["packageManager"].as_str() {
+        // Corepack-style specs can have a `+<hash>` suffix.
+        let spec = spec.split('+').next().unwrap_or_default();
+        let (name, version) = spec.rsplit_once('@').unwrap_or((spec, ""));
+        if !OROGENE_NAMES.contains(&name) {
+            problems.push((
+                OnFail::Warn,
+                format!("packageManager wants {spec}, but this is oro@{current}."),
+            ));
+        } else if version.parse::<Version>().ok().as_ref() != Some(&current) {
+            problems.push((
+                OnFail::Error,
+                format!("packageManager wants {spec}, but this is oro@{current}."),
+            ));
+        }
+    }
+
+    let dev_engines = &manifest["devEngines"];
+
+    let package_managers = requirements(&dev_engines["packageManager"]);
+    if !package_managers.iter().any(|req| {
+        OROGENE_NAMES
+            .iter()
+            .any(|name| req.accepts(name, Some(&current)))
+    }) {
+        problems.extend(unmet(
+            "packageManager",
+            &package_managers,
+            format!("oro@{current}"),
+        ));
+    }
+
+    let runtimes = requirements(&dev_engines["runtime"]);
+    // Only Node.js is checked. Projects that also allow other runtimes get
+    // the benefit of the doubt.
+    if !runtimes.is_empty() && runtimes.iter().all(|req| req.name == "node") {
+        let node = node_version();
+        if !runtimes
+            .iter()
+            .any(|req| req.accepts("node", node.as_ref()))
+        {
+            let found = match node {
+                Some(version) => format!("node@{version}"),
+                None => "no usable `node`".into(),
+            };
+            problems.extend(unmet("runtime", &runtimes, found));
+        }
+    }
+
+    let os = current_os();
+    let systems = requirements(&dev_engines["os"]);
+    if !systems.iter().any(|req| req.accepts(os, None)) {
+        problems.extend(unmet("os", &systems, os.into()));
+    }
+
+    let cpu = current_cpu();
+    let cpus = requirements(&dev_engines["cpu"]);
+    if !cpus.iter().any(|req| req.accepts(cpu, None)) {
+        problems.extend(unmet("cpu", &cpus, cpu.into()));
+    }
+
+    let mut errors = Vec::new();
+    for (on_fail, problem) in problems {
+        match on_fail {
+            OnFail::Ignore => tracing::debug!("{problem}"),
+            OnFail::Warn => tracing::warn!("{problem}"),
+            OnFail::Error => errors.push(problem),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(OroError::ToolchainMismatch(errors).into())
+    }
+}
+
+/// Reads a `devEngines` field, which can be either a single entry or an
+/// array of them. Entries without a name are skipped.
+fn requirements(field: &Value) -> Vec<Requirement> {
+    let entries = match field {
+        Value::Array(entries) => entries.iter().collect(),
+        Value::Object(_) => vec![field],
+        _ => Vec::new(),
+    };
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            Some(Requirement {
+                name: entry["name"].as_str()?.to_string(),
+                version: entry["version"].as_str().map(String::from),
+                on_fail: match entry["onFail"].as_str() {
+                    Some("ignore") => OnFail::Ignore,
+                    Some("warn") => OnFail::Warn,
+                    _ => OnFail::Error,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Describes a `devEngines` field none of whose entries were met. Nothing is
+/// reported for fields without any entries.
+fn unmet(field: &str, reqs: &[Requirement], found: String) -> Option<(OnFail, String)> {
+    let on_fail = reqs.iter().map(|req| req.on_fail).max()?;
+    let wanted = reqs
+        .iter()
+        .map(|req| req.describe())
+        .collect::<Vec<_>>()
+        .join(" or ");
+    Some((
+        on_fail,
+        format!("devEngines.{field} wants {wanted}, but found {found}."),
+    ))
+}
+
+fn node_version() -> Option<Version> {
+    let output = Command::new("node").arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_start_matches('v')
+        .parse()
+        .ok()
+}
+
+/// The current OS, using Node.js's `process.platform` names.
+fn current_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    }
+}
+
+/// The current CPU architecture, using Node.js's `process.arch` names.
+fn current_cpu() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64",
+        arch => arch,
+    }
+}
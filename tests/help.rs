@@ -12,6 +12,66 @@ fn apply_markdown() {
     insta::assert_snapshot!("apply", sub_md("apply"));
 }
 
+#[test]
+fn audit_markdown() {
+    insta::assert_snapshot!("audit", sub_md("audit"));
+}
+
+#[test]
+fn cache_markdown() {
+    insta::assert_snapshot!("cache", sub_md("cache"));
+}
+
+#[test]
+fn clean_state_markdown() {
+    insta::assert_snapshot!("clean-state", sub_md("clean-state"));
+}
+
+#[test]
+fn completions_markdown() {
+    insta::assert_snapshot!("completions", sub_md("completions"));
+}
+
+#[test]
+fn config_markdown() {
+    insta::assert_snapshot!("config", sub_md("config"));
+}
+
+#[test]
+fn daemon_markdown() {
+    insta::assert_snapshot!("daemon", sub_md("daemon"));
+}
+
+#[test]
+fn dedupe_markdown() {
+    insta::assert_snapshot!("dedupe", sub_md("dedupe"));
+}
+
+#[test]
+fn fund_markdown() {
+    insta::assert_snapshot!("fund", sub_md("fund"));
+}
+
+#[test]
+fn graph_markdown() {
+    insta::assert_snapshot!("graph", sub_md("graph"));
+}
+
+#[test]
+fn inspect_markdown() {
+    insta::assert_snapshot!("inspect", sub_md("inspect"));
+}
+
+#[test]
+fn ls_markdown() {
+    insta::assert_snapshot!("ls", sub_md("ls"));
+}
+
+#[test]
+fn migrate_markdown() {
+    insta::assert_snapshot!("migrate", sub_md("migrate"));
+}
+
 #[test]
 fn ping_markdown() {
     insta::assert_snapshot!("ping", sub_md("ping"));
@@ -27,6 +87,11 @@ fn remove_markdown() {
     insta::assert_snapshot!("remove", sub_md("remove"));
 }
 
+#[test]
+fn run_markdown() {
+    insta::assert_snapshot!("run", sub_md("run"));
+}
+
 #[test]
 fn view_markdown() {
     insta::assert_snapshot!("view", sub_md("view"));